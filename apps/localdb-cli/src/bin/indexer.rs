@@ -1,6 +1,6 @@
 use std::{env, fs, path::PathBuf};
 use localdb_core::config::Config;
-use localdb_core::data_processor::DataProcessor;
+use localdb_core::data_processor::{ChunkingConfig, DataProcessor};
 use localdb_text::TantivyIndexer;
 use localdb_embed::get_default_embedder;
 use localdb_vector::LanceDbIndexer;
@@ -21,15 +21,23 @@ fn main() -> anyhow::Result<()> {
         let tantivy_indexer = TantivyIndexer::new(PathBuf::from(&tantivy_index_dir))?; println!("Created Tantivy index at: {}", tantivy_index_dir);
         let count = tantivy_indexer.index_files(&data_dir)?; println!("📊 Indexed {} documents into Tantivy", count); count
     } else { 0 };
-    let data_processor = DataProcessor::new();
+    let data_processor = DataProcessor::with_config(ChunkingConfig::from_config(&config)?);
     let chunks = if let Some(limit) = limit_lance_index { println!("🔢 Limiting LanceDB indexing to {} files", limit); data_processor.process_directory_limited(&data_dir, limit)? } else { data_processor.process_directory(&data_dir)? };
-    if !chunks.is_empty() {
+    // Load the embedder before touching the existing LanceDB index so a missing
+    // model leaves it intact and degrades to a text-only run.
+    let embedder = if chunks.is_empty() { None } else {
+        match get_default_embedder() {
+            Ok(e) => Some(e),
+            Err(e) if localdb_embed::is_embedder_unavailable(&e) => { eprintln!("⚠️  DEGRADED MODE: {}; skipping LanceDB indexing (text index only)", e); None }
+            Err(e) => return Err(e),
+        }
+    };
+    if let Some(embedder) = embedder {
         let lancedb_path = PathBuf::from(config.get("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string()));
         if lancedb_path.exists() { fs::remove_dir_all(&lancedb_path)?; }
         fs::create_dir_all(&lancedb_path)?;
-        let lancedb_indexer = tokio::runtime::Runtime::new()?.block_on(async { LanceDbIndexer::new(&lancedb_path, "documents").await })?;
-        let embedder = get_default_embedder()?;
-        let texts: Vec<String> = chunks.iter().map(|c| c.content.clone()).collect();
+        let lancedb_indexer = tokio::runtime::Runtime::new()?.block_on(async { LanceDbIndexer::new(&lancedb_path, "documents").await })?.with_data_root(&data_dir);
+        let texts = localdb_core::preprocess::Preprocessor::from_config(&config, "documents")?.embedding_texts(&chunks);
         let embeddings = embedder.embed_batch(&texts)?;
         tokio::runtime::Runtime::new()?.block_on(async { lancedb_indexer.index(&chunks, &embeddings).await })?;
     }
@@ -1,22 +1,1709 @@
+use std::collections::HashSet;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
-use localdb_core::config::Config;
-use localdb_core::data_processor::DataProcessor;
-use localdb_hybrid::HybridSearchEngine;
-use localdb_text::TantivyIndexer;
+use anyhow::Context;
+use localdb_cli::exit::{self, ErrorClass};
+use localdb_core::config::{set_toml_string, set_toml_value, Config};
+use localdb_core::crypt;
+use localdb_core::data_processor::{doc_id_of, ChunkingConfig, ChunkingStrategy, DataProcessor};
+use localdb_core::error::Error as CoreError;
+use localdb_core::facets::FacetAliases;
+use localdb_core::feedback::{self, FeedbackLog, Shown};
+use localdb_core::incremental::PreviousIngest;
+use localdb_core::preprocess::Preprocessor;
+use localdb_core::profile::ProfileReport;
+use localdb_core::retention::RetentionPolicy;
+use localdb_core::roots::{load_roots, single_root_extensions, DataRoot, RootMap};
+use localdb_core::traits::{Reranker, TextIndexer, TokenCounter};
+use localdb_core::transcript::Moment;
+use localdb_core::types::{DocumentChunk, FusionWeights};
+use localdb_core::writer_lock::WriterLock;
+use localdb_hybrid::{EmbedderState, FusionStrategy, HybridSearchEngine, QueryOptions};
+use localdb_text::{TextIndex, TextSearch};
 use localdb_vector::LanceDbIndexer;
 use localdb_embed::get_default_embedder;
 
-fn parse_args() -> (String, Vec<String>) {
-    let mut args: Vec<String> = env::args().collect();
+/// Whether the global `flag` was given (anywhere); removed from `args`.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let given = args.iter().any(|a| a == flag);
+    args.retain(|a| a != flag);
+    given
+}
+
+/// The values of every `flag <value>` pair in `args`, in order; removed from `args`.
+fn take_values(args: &mut Vec<String>, flag: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    while let Some(i) = args.iter().position(|a| a == flag) {
+        args.remove(i);
+        if i < args.len() { values.push(args.remove(i)); }
+    }
+    values
+}
+
+fn parse_args(mut args: Vec<String>) -> anyhow::Result<(String, Vec<String>)> {
     let prog = args.remove(0);
-    if args.is_empty() { eprintln!("Usage: {} <ingest|query> [args...]", prog); std::process::exit(1); }
+    if args.is_empty() { return Err(ErrorClass::Usage.error(format!("{} [--json-errors] [--wait] <ingest [--full] [--watch] [--profile] [--include glob] [--exclude glob] [dir]|query [\"<query>\"] [--also \"<phrasing>\"] [--facet /path] [--speaker name] [--lang code] [--max-per-doc N] [--raw]|relocate --data-root <dir> [--root name]|feedback <query_id> <rank> [open|copy|reject]|tune [--dry-run]|bench-embed [--n 1000] [--dry-run]|judge \"<query>\" [--a max_score] [--b rrf]|calibrate [--dry-run] [--reset]|replay --text <dir> --vector <dir> [--k 10] [--limit N]|facet <list|rename OLD NEW>|open <doc_id|doc_path>|play <chunk_id>|verify [answer_file]|scratch <add <-|file> [--name N]|list|clear>|chunk-preview <file>|sync <[user@]host> [--dry-run]|manifest|embeddings <export|import>|stats [--index] [--facets] [--corpus] [--top N]|serve [--listen addr]|scrub|maintain|gc [--dry-run]|lock|unlock|migrate-ids>", prog))); }
     let cmd = args.remove(0);
-    (cmd, args)
+    Ok((cmd, args))
+}
+
+/// Print a banner when the embedding model is missing and only text search is served.
+fn warn_if_degraded<TI, VI>(engine: &HybridSearchEngine<TI, VI>)
+where TI: localdb_core::traits::TextIndexer, VI: localdb_core::traits::VectorIndexer + 'static {
+    if let EmbedderState::EmbedderUnavailable(reason) = engine.embedder_state() {
+        eprintln!("⚠️  DEGRADED MODE: {}", reason);
+        eprintln!("⚠️  Serving text-only results. Set APP_MODEL_DIR to a BGE-M3 model directory to enable vector search.");
+        tracing::warn!(%reason, "Embedder unavailable; running text-only");
+    }
+}
+
+type Engine = HybridSearchEngine<TextSearch, LanceDbIndexer>;
+
+/// The query-side engine as configured (translations, query rewriting, facet
+/// aliases, latency budget, fusion and its score calibration, vector timeout),
+/// plus the facet aliases it applies.
+fn search_engine(config: &Config, lancedb_path: &Path) -> anyhow::Result<(Engine, FacetAliases)> {
+    let tantivy_index_dir: String = config.get("data.tantivy_index_dir").unwrap_or_else(|_| "../dev_data/indexes/tantivy".to_string());
+    search_engine_at(config, Path::new(&tantivy_index_dir), lancedb_path, rewrite_queries(config))
+}
+
+/// Whether keyword queries are spell-corrected and expanded to other word
+/// forms (`search.text.rewrite`; `query --raw` turns it off for one query).
+fn rewrite_queries(config: &Config) -> bool { config.get::<bool>("search.text.rewrite").unwrap_or(true) }
+
+/// `search_engine` over the given index directories (another generation).
+fn search_engine_at(config: &Config, tantivy_index_dir: &Path, lancedb_path: &Path, rewrite: bool) -> anyhow::Result<(Engine, FacetAliases)> {
+    let (engine, aliases) = open_search_engine(config, tantivy_index_dir, lancedb_path, rewrite, EmbedderState::from_result(get_default_embedder())?)?;
+    warn_if_degraded(&engine);
+    Ok((engine, aliases))
+}
+
+/// `search_engine_at` around an embedder already loaded, e.g. to reopen the
+/// indexes after they changed without loading the model again.
+fn open_search_engine(config: &Config, tantivy_index_dir: &Path, lancedb_path: &Path, rewrite: bool, embedder: EmbedderState) -> anyhow::Result<(Engine, FacetAliases)> {
+    check_vector_model(lancedb_path, &embedder)?;
+    let mut text = TextSearch::open(tantivy_index_dir)?.with_query_rewriting(rewrite);
+    if let Ok(dict) = config.get::<String>("search.text.translation_dict") {
+        // A missing or broken dictionary only costs cross-language matches.
+        static WARNED: std::sync::Once = std::sync::Once::new();
+        match localdb_text::translate::QueryTranslator::load(Path::new(&dict)) {
+            Ok(translator) => text = text.with_translations(translator),
+            Err(e) => WARNED.call_once(|| tracing::warn!(dict = %dict, error = %format!("{:#}", e), "Translation dictionary unavailable; searching without it")),
+        }
+    }
+    let aliases = facet_aliases(lancedb_path)?;
+    let text = text.with_facet_aliases(aliases.clone());
+    let vector = tokio::runtime::Runtime::new()?.block_on(async { LanceDbIndexer::new(lancedb_path, "documents").await })?
+        .with_latency_budget(localdb_vector::LatencyBudget::from_config(config)).with_facet_aliases(aliases.clone());
+    let (strategy, weights) = fusion_config(config)?;
+    let multi = multi_vectors(config, &embedder);
+    let sparse = sparse_terms(config, &embedder) && indexed_sparse_terms(lancedb_path)?;
+    let mut engine = HybridSearchEngine::from_state(text, vector, embedder).with_fusion(strategy, weights).with_multi_vectors(multi).with_sparse(sparse)
+        .with_calibration(score_calibration(lancedb_path)?)
+        .with_preprocessor(Preprocessor::from_config(config, "documents").context(ErrorClass::Config)?);
+    // 0 waits for the vector leg however long it takes.
+    let timeout_ms = config.get::<u64>("search.vector.timeout_ms").unwrap_or(2000);
+    if timeout_ms > 0 {
+        let max_running = config.get::<usize>("search.vector.max_running").unwrap_or(localdb_hybrid::MAX_VECTOR_LEGS);
+        engine = engine.with_vector_timeout(std::time::Duration::from_millis(timeout_ms)).with_max_vector_legs(max_running);
+    }
+    if let Some(r) = reranker(config) { engine = engine.with_reranker(r, config.get::<usize>("search.rerank.candidates").unwrap_or(20)); }
+    engine = engine.with_max_per_doc(config.get::<usize>("search.max_per_doc").unwrap_or(0));
+    Ok((engine, aliases))
+}
+
+/// The cross-encoder named by `search.rerank.model`, loaded once per process
+/// so reopened engines share it; `None` when unset or it cannot be loaded,
+/// in which case the next engine opened tries again.
+fn reranker(config: &Config) -> Option<std::sync::Arc<dyn Reranker>> {
+    static LOADED: std::sync::OnceLock<std::sync::Arc<dyn Reranker>> = std::sync::OnceLock::new();
+    let name = config.get::<String>("search.rerank.model").ok().filter(|m| !m.trim().is_empty())?;
+    if let Some(r) = LOADED.get() { return Some(r.clone()); }
+    match localdb_rerank::CrossEncoder::new(&name) {
+        Ok(r) => Some(LOADED.get_or_init(|| std::sync::Arc::new(r)).clone()),
+        Err(e) => {
+            eprintln!("⚠️  Could not load the {} reranker ({:#}); results are not reranked", name, e);
+            None
+        }
+    }
+}
+
+/// Whether the model's sparse lexical weights join the text index and its
+/// queries: with `embedding.sparse` on and a model that has them.
+fn sparse_terms(config: &Config, embedder: &EmbedderState) -> bool {
+    let EmbedderState::Ready(e) = embedder else { return false };
+    if !config.get::<bool>("embedding.sparse").unwrap_or(false) { return false; }
+    if e.sparse().is_none() {
+        eprintln!("⚠️  embedding.sparse is on but the model has no sparse head (sparse_linear); using dense vectors only");
+        return false;
+    }
+    true
+}
+
+/// Whether the text index was built with sparse lexical weights; queries
+/// use them only then.
+fn indexed_sparse_terms(lancedb_path: &Path) -> anyhow::Result<bool> {
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(&lancedb_path.to_string_lossy()))?;
+    rt.block_on(localdb_vector::table::text_sparse_terms(&conn, "documents"))
+}
+
+/// Whether chunks get token vectors and vector hits are rescored by MaxSim:
+/// with `embedding.multi_vector` on and a model that has token-level output.
+fn multi_vectors(config: &Config, embedder: &EmbedderState) -> bool {
+    let EmbedderState::Ready(e) = embedder else { return false };
+    if !config.get::<bool>("embedding.multi_vector").unwrap_or(false) { return false; }
+    if e.multi_vector().is_none() {
+        eprintln!("⚠️  embedding.multi_vector is on but the model has no ColBERT head (colbert_linear); using single vectors");
+        return false;
+    }
+    true
+}
+
+/// Fail before searching or ingesting when the model's vectors (its
+/// `config.json` `hidden_size`, or `embedding.truncate_dim`) are not as
+/// wide as the ones the `documents` collection holds, or its `embedder_id`
+/// (model, settings and weights fingerprint) is not the one recorded by the
+/// ingest that built it: `embedding.model` or `embedding.truncate_dim`
+/// changed, or the model directory was swapped. Collections built before
+/// ids were recorded are only checked for width. `ingest --full` skips it
+/// and rebuilds the collection.
+fn check_vector_model(lancedb_path: &Path, embedder: &EmbedderState) -> anyhow::Result<()> {
+    let EmbedderState::Ready(e) = embedder else { return Ok(()) };
+    tokio::runtime::Runtime::new()?.block_on(async {
+        let conn = localdb_vector::table::open_db(&lancedb_path.to_string_lossy()).await?;
+        localdb_vector::table::check_collection_dim(&conn, "documents", e.dim()).await?;
+        let current = localdb_vector::embed_provider::local::embedder_id(e.dim())?;
+        match localdb_vector::table::collection_embedder(&conn, "documents").await? {
+            Some(recorded) if recorded != current => anyhow::bail!("the vectors were embedded by {}, the configured model is {}", recorded, current),
+            _ => Ok(()),
+        }
+    }).with_context(|| format!("the embedding model does not fit the vector index in {}: switch embedding.model (or embedding.truncate_dim) back, or run `ingest --full` to rebuild it", lancedb_path.display()))
+        .context(ErrorClass::Config)
+}
+
+/// Number of stored paths checked against the new root before relocating.
+const RELOCATE_SAMPLE: usize = 20;
+
+/// Point the indexes at a corpus moved to `new_root`: optionally relativize
+/// legacy absolute paths under `old_root`, spot-check that sampled documents
+/// exist under `new_root`, then record the new root (Lance meta, Tantivy
+/// payload) and update `data.raw_txt_dir` in `config.toml`. With several
+/// roots, `root_name` picks the one that moved (`""` is the single root).
+fn relocate(config: &Config, root_name: &str, new_root: &Path, old_root: Option<&Path>, force: bool) -> anyhow::Result<()> {
+    if !new_root.is_dir() { anyhow::bail!("new data root {} is not a directory", new_root.display()); }
+    let new_root = std::fs::canonicalize(new_root)?;
+    let tantivy_index_dir = PathBuf::from(config.get::<String>("data.tantivy_index_dir").unwrap_or_else(|_| "../dev_data/indexes/tantivy".to_string()));
+    let lancedb_path = config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string());
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(&lancedb_path))?;
+    if let Some(old) = old_root { rt.block_on(localdb_vector::migrate::relativize_doc_paths(&conn, "documents", old))?; }
+    let sample = rt.block_on(localdb_vector::table::sample_doc_paths(&conn, "documents", RELOCATE_SAMPLE, localdb_core::seed::from_config(config)))?;
+    let mut roots = rt.block_on(localdb_vector::table::data_roots(&conn, "documents"))?;
+    roots.set(root_name, new_root.clone());
+    let prefix = format!("{}/", root_name);
+    let missing: Vec<PathBuf> = sample.iter()
+        .filter(|p| root_name.is_empty() || p.starts_with(&prefix))
+        .map(|p| roots.resolve(p)).filter(|p| !p.exists()).collect();
+    println!("Checked {} sampled documents under {}: {} missing", sample.len(), new_root.display(), missing.len());
+    for m in missing.iter().take(5) { println!("  missing: {}", m.display()); }
+    if !missing.is_empty() && !force { anyhow::bail!("{} sampled documents not found under the new root; pass --force to relocate anyway", missing.len()); }
+    rt.block_on(localdb_vector::table::set_data_roots(&conn, "documents", &roots))?;
+    if tantivy_index_dir.exists() { TextIndex::relocate(&tantivy_index_dir, root_name, &new_root)?; }
+    if root_name.is_empty() { set_toml_string(Path::new("config.toml"), "data", "raw_txt_dir", &new_root.to_string_lossy())?; }
+    else { println!("Update the `path` of root '{}' under [[data.roots]] in config.toml", root_name); }
+    println!("✅ Relocated indexes to data root {}", new_root.display());
+    Ok(())
+}
+
+/// Tantivy and Lance index directories from config.
+fn index_dirs(config: &Config) -> Vec<PathBuf> {
+    vec![
+        PathBuf::from(config.get::<String>("data.tantivy_index_dir").unwrap_or_else(|_| "../dev_data/indexes/tantivy".to_string())),
+        PathBuf::from(config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string())),
+    ]
+}
+
+/// Text analysis for indexes (and shards) created from now on.
+fn text_analysis(config: &Config) -> localdb_text::tantivy_utils::Analysis {
+    localdb_text::tantivy_utils::Analysis { transliterate: config.get::<bool>("search.text.transliterate").unwrap_or(false) }
+}
+
+/// Fail with `ErrorClass::MissingIndex` unless both index directories exist.
+fn require_indexes(config: &Config) -> anyhow::Result<()> {
+    match index_dirs(config).into_iter().find(|d| !d.is_dir()) {
+        Some(dir) => Err(ErrorClass::MissingIndex.error(format!("no index at {}; run `localdb-cli ingest` first", dir.display()))),
+        None => Ok(()),
+    }
+}
+
+/// Config keys of the index directories, in `index_dirs` order.
+const INDEX_DIR_KEYS: [&str; 2] = ["data.tantivy_index_dir", "data.lancedb_index_dir"];
+
+/// Scratch directories holding decrypted index copies, deleted on Ctrl-C.
+static UNSEALED: std::sync::Mutex<Vec<PathBuf>> = std::sync::Mutex::new(Vec::new());
+
+/// Index directories opened for one command. Sealed directories (and, under
+/// `security.encrypt_indexes`, plaintext ones) are decrypted into a private
+/// scratch directory (`scratch_dir`), never in place, and `config` points the
+/// command at the copies. `reseal` seals the copies that changed back over the
+/// originals; otherwise they are deleted on drop or Ctrl-C, so an error, crash
+/// or interrupt leaves the indexes as they were.
+struct IndexLock { config: Config, copies: Vec<(PathBuf, PathBuf, DirState)>, scratch: Option<PathBuf>, passphrase: Option<String> }
+
+/// Size and modification time of every file under a directory, to tell
+/// whether a command changed its copy.
+type DirState = Vec<(PathBuf, u64, Option<std::time::SystemTime>)>;
+
+impl IndexLock {
+    /// Decrypt any sealed index directories into scratch copies. With
+    /// `encrypt` (ingest under `security.encrypt_indexes`), directories that
+    /// started out plaintext are worked on as copies too and sealed when the
+    /// command finishes; a new passphrase is asked for twice.
+    fn acquire(config: &Config, encrypt: bool) -> anyhow::Result<Self> { Self::acquire_with(config, encrypt, None) }
+
+    /// `acquire` with the passphrase already known (`ingest --watch` asks once).
+    fn acquire_with(config: &Config, encrypt: bool, known: Option<&str>) -> anyhow::Result<Self> {
+        let dirs = index_dirs(config);
+        for d in &dirs { crypt::recover(d)?; }
+        let sealed = dirs.iter().any(|d| crypt::is_sealed(d));
+        let mut lock = Self { config: config.clone(), copies: Vec::new(), scratch: None, passphrase: None };
+        if !sealed && !encrypt { return Ok(lock); }
+        let passphrase = match known {
+            Some(p) => p.to_string(),
+            None if sealed => crypt::read_passphrase("Index passphrase: ")?,
+            None => crypt::read_new_passphrase("New index passphrase: ")?,
+        };
+        let scratch = scratch_dir()?;
+        lock.scratch = Some(scratch.clone());
+        for (i, (dir, key)) in dirs.iter().zip(INDEX_DIR_KEYS).enumerate() {
+            let copy = scratch.join(i.to_string());
+            if crypt::is_sealed(dir) { crypt::unseal_into(dir, &copy, &passphrase)?; } else if dir.is_dir() { copy_dir(dir, &copy)?; }
+            lock.config = lock.config.with_value(key, copy.to_string_lossy());
+            lock.copies.push((dir.clone(), copy.clone(), dir_state(&copy)));
+        }
+        lock.passphrase = Some(passphrase);
+        Ok(lock)
+    }
+
+    /// `acquire` for commands that read existing indexes, failing with
+    /// `ErrorClass::MissingIndex` before ingest has created them.
+    fn open(config: &Config) -> anyhow::Result<Self> {
+        require_indexes(config)?;
+        Self::acquire(config, false)
+    }
+
+    /// The config with the index directories pointing at the copies.
+    fn config(&self) -> &Config { &self.config }
+
+    /// The index passphrase, when the indexes are (or will be) sealed.
+    fn passphrase(&self) -> Option<&str> { self.passphrase.as_deref() }
+
+    /// Seal the copies that changed (all of them, for directories that
+    /// started out plaintext) over the originals, then delete them.
+    fn reseal(mut self) -> anyhow::Result<()> {
+        let Some(passphrase) = self.passphrase.take() else { return Ok(()) };
+        for (dir, copy, before) in &self.copies {
+            if !copy.is_dir() || (crypt::is_sealed(dir) && dir_state(copy) == *before) { continue; }
+            crypt::seal_into(copy, dir, &passphrase)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for IndexLock {
+    fn drop(&mut self) {
+        let Some(scratch) = self.scratch.take() else { return };
+        if let Err(e) = std::fs::remove_dir_all(&scratch) { eprintln!("⚠️  could not delete decrypted index copies in {}: {}", scratch.display(), e); }
+        UNSEALED.lock().unwrap_or_else(|e| e.into_inner()).retain(|d| *d != scratch);
+    }
+}
+
+/// A new private directory for decrypted index copies: in RAM under
+/// `$XDG_RUNTIME_DIR` or `/dev/shm` when there is one, else (with a warning)
+/// in the system temp directory. Registered for deletion on Ctrl-C.
+fn scratch_dir() -> anyhow::Result<PathBuf> {
+    let ram = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from).into_iter().chain([PathBuf::from("/dev/shm")]).find(|d| d.is_dir());
+    let base = ram.unwrap_or_else(|| {
+        eprintln!("⚠️  No RAM-backed scratch space; decrypted index copies go to {} while the command runs", std::env::temp_dir().display());
+        std::env::temp_dir()
+    });
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+    let dir = base.join(format!("localdb-unsealed-{}-{}", std::process::id(), nanos));
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    { use std::os::unix::fs::DirBuilderExt; builder.mode(0o700); }
+    builder.create(&dir)?;
+    UNSEALED.lock().unwrap_or_else(|e| e.into_inner()).push(dir.clone());
+    Ok(dir)
+}
+
+/// Delete every registered scratch directory; run by the Ctrl-C handler.
+fn remove_unsealed() {
+    for dir in UNSEALED.lock().unwrap_or_else(|e| e.into_inner()).drain(..) { let _ = std::fs::remove_dir_all(dir); }
+}
+
+fn copy_dir(src: &Path, dest: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dest)?;
+    for entry in walkdir::WalkDir::new(src).into_iter().filter_map(|e| e.ok()).filter(|e| e.file_type().is_file()) {
+        let to = dest.join(entry.path().strip_prefix(src)?);
+        if let Some(parent) = to.parent() { std::fs::create_dir_all(parent)?; }
+        std::fs::copy(entry.path(), &to)?;
+    }
+    Ok(())
+}
+
+fn dir_state(dir: &Path) -> DirState {
+    let mut state: DirState = walkdir::WalkDir::new(dir).into_iter().filter_map(|e| e.ok()).filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok().map(|m| (e.into_path(), m.len(), m.modified().ok()))).collect();
+    state.sort();
+    state
+}
+
+/// Writer locks on both index directories for `command` (see
+/// `localdb_core::writer_lock`); with `wait` (`--wait`), wait for another
+/// writer to finish instead of failing with `ErrorClass::Busy`. Taken before
+/// the `IndexLock`, so unsealing and resealing happen under it too.
+fn writing(config: &Config, command: &str, wait: bool) -> anyhow::Result<WriterLock> {
+    Ok(WriterLock::acquire(&index_dirs(config), command, wait, |dir, holder| println!("⏳ {} is being written by {}; waiting", dir.display(), holder))?)
+}
+
+/// Chunks already in the Lance `documents` table for roots that are offline,
+/// each id once.
+fn carry_over_offline(roots: &[DataRoot], lancedb_path: &Path) -> anyhow::Result<Vec<DocumentChunk>> {
+    let offline: Vec<&DataRoot> = roots.iter().filter(|r| !r.is_online()).collect();
+    if offline.is_empty() { return Ok(Vec::new()); }
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(&lancedb_path.to_string_lossy()))?;
+    let mut carried = Vec::new();
+    let mut seen = HashSet::new();
+    for root in offline {
+        // A lone root is unnamed: its stored paths carry no prefix.
+        let prefix = if roots.len() > 1 { format!("{}/", root.name()) } else { String::new() };
+        let mut chunks = rt.block_on(localdb_vector::table::stored_chunks(&conn, "documents", &prefix))?;
+        chunks.retain(|c| seen.insert(c.id.clone()));
+        println!("📴 Keeping {} indexed chunks from offline media: {}", chunks.len(), root.name());
+        carried.extend(chunks);
+    }
+    Ok(carried)
+}
+
+/// The processor as configured for chunking (`[chunking]`, retention, OCR,
+/// boilerplate, CSV/JSON Lines mappings, taxonomy, file guards, the model's
+/// tokenizer and, for semantic chunking, `embedder`); ingest adds its stores.
+fn data_processor(config: &Config, embedder: &EmbedderState) -> anyhow::Result<DataProcessor> {
+    let chunking = ChunkingConfig::from_config(config).context(ErrorClass::Config)?;
+    let semantic = chunking.strategy == ChunkingStrategy::Semantic;
+    let mut data_processor = DataProcessor::with_config(chunking).with_retention(RetentionPolicy::from_config(config))
+        .with_ocr(localdb_core::ocr::OcrConfig::from_config(config))
+        .with_boilerplate(localdb_core::boilerplate::BoilerplateConfig::from_config(config))
+        .with_csv(localdb_core::csv::CsvMapping::from_config(config))
+        .with_jsonl(localdb_core::jsonl::JsonlMapping::from_config(config))
+        .with_taxonomy(localdb_core::taxonomy::Taxonomy::from_config(config).context(ErrorClass::Config)?)
+        .with_guards(localdb_core::guards::FileGuards::from_config(config));
+    if let Some(tokens) = localdb_embed::default_token_counter()? { data_processor = data_processor.with_token_counter(std::sync::Arc::new(tokens)); }
+    if semantic {
+        if let Ok(batch_size) = config.get::<usize>("embedding.batch_size") { data_processor = data_processor.with_sentence_batch_size(batch_size); }
+        match embedder {
+            EmbedderState::Ready(e) => data_processor = data_processor.with_sentence_embedder(e.clone()),
+            EmbedderState::EmbedderUnavailable(reason) => eprintln!("⚠️  Semantic chunking needs the embedding model ({}); splitting by sentences", reason),
+        }
+    }
+    Ok(data_processor)
+}
+
+/// Print the chunks `file` would become under the current config: ids, token
+/// counts against `max_tokens` (capped by the model's `max_len`), words overlapping the previous chunk, source
+/// lines and metadata, then the full text of each. Nothing is written.
+fn chunk_preview(config: &Config, file: &Path) -> anyhow::Result<()> {
+    if !file.is_file() { return Err(ErrorClass::Usage.error(format!("{} is not a file", file.display()))); }
+    let semantic = ChunkingConfig::from_config(config).context(ErrorClass::Config)?.strategy == ChunkingStrategy::Semantic;
+    let embedder = if semantic { EmbedderState::from_result(get_default_embedder())? } else { EmbedderState::EmbedderUnavailable("not needed".to_string()) };
+    let processor = data_processor(config, &embedder)?;
+    // Doc ids and facets as in an ingest of the configured root holding the file.
+    let absolute = std::fs::canonicalize(file)?;
+    let root = load_roots(config, "../dev_data/txt").into_iter().map(|r| std::fs::canonicalize(&r.path).unwrap_or(r.path))
+        .find(|r| absolute.starts_with(r)).unwrap_or_else(|| absolute.parent().unwrap_or(Path::new(".")).to_path_buf());
+    let chunks = processor.process_file(&absolute, &root)?;
+    let chunking = processor.chunking();
+    println!("\n{} → {} chunks (max_tokens {}, {:?}, overlap {:.0}%)", file.display(), chunks.len(), processor.max_tokens(), chunking.strategy, chunking.overlap_percent * 100.0);
+    let mut tokens = Vec::new();
+    for (i, c) in chunks.iter().enumerate() {
+        let n = processor.count_tokens(&c.content);
+        tokens.push(n);
+        let overlap = i.checked_sub(1).map(|p| &chunks[p]).filter(|p| p.doc_id == c.doc_id).map_or(0, |p| localdb_core::chunker::overlap_words(&p.content, &c.content));
+        let over = if n > processor.max_tokens() { " ⚠️ over max_tokens" } else { "" };
+        println!("\n#{} {}  tokens={}{}{}{}", c.chunk_index, c.id, n, over, if overlap > 0 { format!("  overlap={} words", overlap) } else { String::new() },
+            c.span.map(|s| format!("  {}", s.lines())).unwrap_or_default());
+        let mut about = vec![format!("facet={}", c.category)];
+        if let Some(lang) = &c.lang { about.push(format!("lang={}", lang)); }
+        if let Some(title) = &c.title { about.push(format!("title={}", title)); }
+        if let Some(author) = &c.author { about.push(format!("author={}", author)); }
+        let mut meta: Vec<_> = c.meta.iter().collect();
+        meta.sort();
+        about.extend(meta.into_iter().map(|(k, v)| format!("{}={}", k, v)));
+        println!("   {}", about.join("  "));
+        for line in c.content.lines() { println!("   │ {}", line); }
+    }
+    if let (Some(min), Some(max)) = (tokens.iter().min(), tokens.iter().max()) {
+        println!("\n{} chunks, tokens min {} / avg {} / max {}", tokens.len(), min, tokens.iter().sum::<usize>() / tokens.len(), max);
+    }
+    Ok(())
+}
+
+/// Measure embedding throughput on this device at `bench::BATCH_SIZES` over
+/// `n` chunks (stored ones when there is an index, else generated filler),
+/// recommend a batch size and, unless `dry_run`, write it to config.toml as
+/// `embedding.batch_size`, which ingest embeds by.
+fn bench_embed(config: &Config, n: usize, dry_run: bool) -> anyhow::Result<()> {
+    use localdb_embed::bench;
+    let embedder = get_default_embedder()?;
+    let lancedb_path = &index_dirs(config)[1];
+    let stored = if lancedb_path.exists() {
+        let rt = tokio::runtime::Runtime::new()?;
+        let conn = rt.block_on(localdb_vector::table::open_db(&lancedb_path.to_string_lossy()))?;
+        rt.block_on(localdb_vector::table::stored_chunks(&conn, "documents", "")).unwrap_or_default()
+    } else { Vec::new() };
+    let texts: Vec<String> = if stored.len() >= n { stored.into_iter().take(n).map(|c| c.content).collect() } else {
+        let words = ChunkingConfig::from_config(config).context(ErrorClass::Config)?.max_tokens * 3 / 4;
+        println!("Too few stored chunks ({}); benchmarking on {} generated texts of {} words", stored.len(), n, words);
+        bench::sample_texts(n, words, localdb_core::seed::from_env())
+    };
+    // Tokens the model actually sees: its tokenizer's count, cut at max_len
+    // (words without the real model).
+    let counter = localdb_embed::default_token_counter()?;
+    let tokens: usize = texts.iter().map(|t| counter.as_ref().map_or_else(|| t.split_whitespace().count(), |c| c.count_tokens(t)).min(embedder.max_len())).sum();
+    println!("Embedding {} chunks ({} tokens) at batch sizes {:?}", texts.len(), tokens, bench::BATCH_SIZES);
+    let results = bench::measure(embedder.as_ref(), &texts, tokens, &bench::BATCH_SIZES)?;
+    println!("{:>6} {:>10} {:>12} {:>9}", "batch", "chunks/s", "tokens/s", "seconds");
+    for r in &results { println!("{:>6} {:>10.1} {:>12.0} {:>9.2}", r.batch_size, r.chunks_per_sec, r.tokens_per_sec, r.elapsed.as_secs_f64()); }
+    let Some(best) = bench::recommend(&results) else { return Err(ErrorClass::Usage.error("--n must be at least 1")) };
+    println!("Recommended batch size: {}", best);
+    if !dry_run {
+        set_toml_value(Path::new("config.toml"), "embedding", "batch_size", &best.to_string())?;
+        println!("Updated [embedding] batch_size in config.toml");
+    }
+    Ok(())
+}
+
+/// Largest documents listed in the ingest's corpus statistics.
+const LARGEST_DOCS: usize = 5;
+
+/// Ingest `roots` into both indexes: only the files changed since the last
+/// ingest (see `localdb_core::incremental`), or everything with `full` or
+/// when there is no text index yet. `full` also starts the vector collection
+/// again, so it is how to switch to a model of another width. The caller
+/// holds the writer locks and the `IndexLock`.
+/// Returns the files that could not be read.
+fn ingest(config: &Config, roots: &[DataRoot], full: bool, embedder: &EmbedderState) -> anyhow::Result<Vec<String>> {
+    let tantivy_index_dir = PathBuf::from(config.get::<String>("data.tantivy_index_dir").unwrap_or_else(|_| "../dev_data/indexes/tantivy".to_string()));
+    let lancedb_path = PathBuf::from(config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string()));
+    if !full { check_vector_model(&lancedb_path, embedder)?; }
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(&lancedb_path.to_string_lossy()))?;
+    // The last ingest's catalog says which files are unchanged; without
+    // a text index to update (or with --full) everything is rebuilt.
+    let previous = if full || !TextIndex::exists(&tantivy_index_dir) { PreviousIngest::default() }
+        else { PreviousIngest::new(rt.block_on(localdb_vector::catalog::records(&conn, localdb_vector::catalog::CATALOG_TABLE))?) };
+    let incremental = !previous.is_empty();
+    let mut data_processor = data_processor(config, embedder)?.with_previous(previous);
+    let fingerprint = data_processor.chunking_fingerprint();
+    if incremental && rt.block_on(localdb_vector::table::chunking_fingerprint(&conn, "documents"))?.is_some_and(|f| f != fingerprint) {
+        return Err(ErrorClass::Config.error("[chunking] or [boilerplate] changed since the last ingest; run `ingest --full` to re-chunk every file"));
+    }
+    // Sparse weights are written per chunk, so switching them on or off means
+    // indexing every chunk again.
+    let sparse = sparse_terms(config, embedder);
+    if incremental && rt.block_on(localdb_vector::table::text_sparse_terms(&conn, "documents"))? != sparse {
+        return Err(ErrorClass::Config.error("embedding.sparse changed since the text index was built; run `ingest --full` to index every chunk again"));
+    }
+    if let Some(blobs) = localdb_core::blobs::BlobStore::from_config(config) { data_processor = data_processor.with_blob_store(blobs); }
+    if let Some(assets) = localdb_core::assets::AssetStore::from_config(config) { data_processor = data_processor.with_asset_store(assets); }
+    let (mut chunks, catalog, changes) = data_processor.process_roots_incremental(roots)?;
+    if !chunks.is_empty() { print!("📊 Ingested {}", data_processor.corpus_stats(&chunks, &catalog, LARGEST_DOCS).render()); }
+    let root_map = RootMap::for_roots(roots);
+    let ngram_fallback = config.get::<bool>("search.text.ngram_fallback").unwrap_or(false);
+    let analysis = text_analysis(config);
+    let text = if incremental {
+        // Old chunks of modified and removed files go before the new ones arrive.
+        let stale = changes.stale();
+        if !stale.is_empty() {
+            let removed = rt.block_on(localdb_vector::table::delete_documents(&conn, "documents", "embeddings", &stale))?;
+            TextIndex::delete_documents(&tantivy_index_dir, &stale)?;
+            rt.block_on(localdb_vector::catalog::delete_records(&conn, localdb_vector::catalog::CATALOG_TABLE, &changes.dropped()))?;
+            println!("🗑️  Removed {} chunks of {} modified, deleted, expired or skipped files", removed.len(), stale.len());
+        }
+        TextIndex::open(&tantivy_index_dir, analysis)?.with_data_roots(root_map.clone()).with_ngram_fallback(ngram_fallback)
+    } else {
+        // The text index is rebuilt from scratch; carry over the stored chunks of
+        // roots whose media is unplugged so they stay searchable.
+        let carried = carry_over_offline(roots, &lancedb_path)?;
+        let sharded = config.get::<bool>("data.shard_text_index").unwrap_or(false);
+        let text = TextIndex::create(tantivy_index_dir.clone(), analysis, sharded)?.with_data_roots(root_map.clone()).with_ngram_fallback(ngram_fallback);
+        if full {
+            // The stored vectors may be another model's: drop the collection
+            // and embed the carried chunks again with the rest.
+            rt.block_on(localdb_vector::table::drop_collection(&conn, "documents", "embeddings"))?;
+            chunks.extend(carried);
+        } else if !carried.is_empty() {
+            // Without their vectors there is nothing to take sparse weights
+            // from; these chunks match by BM25 alone until reingested.
+            TextIndexer::index(&text, &carried)?;
+        }
+        text
+    };
+    let vector = rt.block_on(async { LanceDbIndexer::new(&lancedb_path, "documents").await })?.with_data_roots(root_map);
+    let mut engine = HybridSearchEngine::from_state(text, vector, with_cached_vectors(config, &rt, &lancedb_path, embedder, &chunks)?)
+        .with_multi_vectors(multi_vectors(config, embedder)).with_sparse(sparse)
+        .with_preprocessor(Preprocessor::from_config(config, "documents").context(ErrorClass::Config)?);
+    if let Ok(batch_size) = config.get::<usize>("embedding.batch_size") { engine = engine.with_embed_batch_size(batch_size); }
+    engine = engine.with_length_buckets(config.get::<bool>("embedding.sort_by_length").unwrap_or(true));
+    warn_if_degraded(&engine);
+    if !chunks.is_empty() || !incremental {
+        engine.index(&chunks)?;
+        rt.block_on(localdb_vector::tokens::index_token_ids(&conn, "documents"))?;
+        // Pair the startup self-test's canary with the model that built this index,
+        // and record that model so another generation can be queried with it (`replay`).
+        // Only a full rebuild re-embeds every chunk; an incremental ingest keeps both.
+        if let (EmbedderState::Ready(e), false) = (embedder, incremental) {
+            if let Err(e) = rt.block_on(localdb_vector::canary::write_canary(&lancedb_path, e.as_ref())) { eprintln!("⚠️  Could not write the self-test canary: {:#}", e); }
+            let embedder_id = localdb_vector::embed_provider::local::embedder_id(e.dim())?;
+            rt.block_on(localdb_vector::table::set_collection_embedder(&conn, "documents", &embedder_id))?;
+        }
+    }
+    rt.block_on(localdb_vector::catalog::put_records(&conn, localdb_vector::catalog::CATALOG_TABLE, &catalog))?;
+    rt.block_on(localdb_vector::table::set_chunking_fingerprint(&conn, "documents", &fingerprint))?;
+    if !incremental { rt.block_on(localdb_vector::table::set_text_sparse_terms(&conn, "documents", sparse))?; }
+    tracing::info!(count = chunks.len(), "Ingest complete");
+    Ok(changes.failed)
+}
+
+/// `data.progress_dir`, where long jobs report their progress.
+fn progress_dir(config: &Config) -> PathBuf {
+    PathBuf::from(config.get::<String>("data.progress_dir").unwrap_or_else(|_| "../dev_data/progress".to_string()))
+}
+
+/// Run `job`, reporting its progress (see `localdb_core::progress`) for the web UI.
+fn tracked<T>(config: &Config, job: &str, run: impl FnOnce() -> anyhow::Result<T>) -> anyhow::Result<T> {
+    localdb_core::progress::enable(&progress_dir(config), job);
+    let result = run();
+    localdb_core::progress::finish(result.as_ref().err().map(|e| format!("{:#}", e)));
+    result
+}
+
+/// How long the data roots must be quiet before `ingest --watch` reindexes;
+/// editors and copies fire bursts of events for one change.
+const WATCH_QUIET: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How often `ingest --watch` looks for roots whose media came online (or went away).
+const WATCH_RESCAN: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// `ingest --watch`: after the initial ingest, reindex incrementally whenever
+/// files under the online roots are created, changed or removed, or a root's
+/// media comes online, until interrupted. Each run takes the writer locks
+/// (waiting for other writers) and the `IndexLock`, and reseals before
+/// releasing them; between runs the indexes are free and sealed, so Ctrl-C
+/// only loses the run in progress. `indexed` holds the files of the catalog
+/// (`indexed_files`): a removal matters only when it takes one of them. A
+/// failed run is reported and watching goes on.
+fn watch(config: &Config, roots: &[DataRoot], embedder: &EmbedderState, encrypt: bool, passphrase: Option<String>, mut indexed: HashSet<PathBuf>) -> anyhow::Result<()> {
+    use notify::EventKind;
+    use std::sync::mpsc::RecvTimeoutError;
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| { if let Ok(e) = event { let _ = tx.send(e); } })?;
+    let mut watched = vec![false; roots.len()];
+    watch_online(&mut watcher, roots, &mut watched);
+    println!("👀 Watching {} for changes (Ctrl-C to stop)", roots.iter().filter(|r| r.is_online()).map(|r| r.path.display().to_string()).collect::<Vec<_>>().join(", "));
+    let relevant = |e: &notify::Event, indexed: &HashSet<PathBuf>| match e.kind {
+        // A removed directory has no extension to check; it matters if it held indexed files.
+        EventKind::Remove(_) => e.paths.iter().any(|p| indexed.iter().any(|f| f.starts_with(p))),
+        EventKind::Create(_) | EventKind::Modify(_) => e.paths.iter().any(|p| roots.iter().any(|r| r.accepts(p))),
+        _ => false,
+    };
+    loop {
+        let mut changed = match rx.recv_timeout(WATCH_RESCAN) {
+            Ok(e) => relevant(&e, &indexed),
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(e) => return Err(e.into()),
+        };
+        while let Ok(e) = rx.recv_timeout(WATCH_QUIET) { changed |= relevant(&e, &indexed); }
+        let online = watch_online(&mut watcher, roots, &mut watched);
+        if !online.is_empty() { println!("🔌 Now online: {}", online.join(", ")); changed = true; }
+        if !changed { continue; }
+        println!("🔄 Changes detected; reindexing");
+        match watch_pass(config, roots, embedder, encrypt, passphrase.as_deref()) {
+            Ok(files) => indexed = files,
+            Err(e) => eprintln!("⚠️  Ingest failed: {:#}; still watching", e),
+        }
+    }
+}
+
+/// Start watching the roots that are online and not yet watched, and forget
+/// those whose media went away; returns the names of the roots newly watched
+/// after the first call.
+fn watch_online(watcher: &mut impl notify::Watcher, roots: &[DataRoot], watched: &mut [bool]) -> Vec<String> {
+    let first = watched.iter().all(|w| !w);
+    let mut online = Vec::new();
+    for (root, on) in roots.iter().zip(watched.iter_mut()) {
+        match (root.is_online(), *on) {
+            (true, false) => match watcher.watch(&root.path, notify::RecursiveMode::Recursive) {
+                Ok(()) => { *on = true; if !first { online.push(root.name()); } }
+                Err(e) => eprintln!("⚠️  Cannot watch {}: {}", root.path.display(), e),
+            },
+            (false, true) => { let _ = watcher.unwatch(&root.path); *on = false; }
+            _ => {}
+        }
+    }
+    online
+}
+
+/// One `ingest --watch` run; returns the files now in the catalog.
+fn watch_pass(config: &Config, roots: &[DataRoot], embedder: &EmbedderState, encrypt: bool, passphrase: Option<&str>) -> anyhow::Result<HashSet<PathBuf>> {
+    // Wait out other writers (a manual ingest, gc, maintain) rather than skip the changes.
+    let _writing = writing(config, "ingest --watch", true)?;
+    let lock = IndexLock::acquire_with(config, encrypt, passphrase)?;
+    tracked(lock.config(), "ingest", || ingest(lock.config(), roots, false, embedder))?;
+    let indexed = indexed_files(lock.config(), roots)?;
+    lock.reseal()?;
+    Ok(indexed)
+}
+
+/// Absolute paths of the files in the ingest catalog (with the archives and
+/// other files that fragment `doc_path`s may belong to).
+fn indexed_files(config: &Config, roots: &[DataRoot]) -> anyhow::Result<HashSet<PathBuf>> {
+    let lancedb_path = PathBuf::from(config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string()));
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(&lancedb_path.to_string_lossy()))?;
+    let records = rt.block_on(localdb_vector::catalog::records(&conn, localdb_vector::catalog::CATALOG_TABLE))?;
+    let root_map = &RootMap::for_roots(roots);
+    Ok(records.iter().flat_map(|r| localdb_core::types::file_candidates(&r.doc_path).map(move |f| root_map.resolve(f))).collect())
+}
+
+/// The phrasings searched for `query` (see `HybridSearchEngine::query_variants`):
+/// itself, the user's `also` and, with `keywords`, the content words of a question.
+fn phrasings(query: &str, also: &[String], keywords: bool) -> Vec<String> {
+    let mut variants = vec![query.to_string()];
+    variants.extend(also.iter().filter(|a| !a.trim().is_empty()).cloned());
+    if keywords { variants.extend(localdb_core::answer::keywords(query)); }
+    variants
+}
+
+/// `search.fusion` strategy and per-leg weights (defaults: max score, 1.0 each).
+fn fusion_config(config: &Config) -> anyhow::Result<(FusionStrategy, FusionWeights)> {
+    let name = config.get::<String>("search.fusion.strategy").unwrap_or_else(|_| "max_score".to_string());
+    let strategy = FusionStrategy::parse(&name).ok_or_else(|| CoreError::InvalidConfig(format!("unknown search.fusion.strategy '{}' (expected max_score, weighted_sum or rrf)", name)))?;
+    let d = FusionWeights::default();
+    let weights = FusionWeights {
+        text: config.get("search.fusion.text_weight").unwrap_or(d.text),
+        vector: config.get("search.fusion.vector_weight").unwrap_or(d.vector),
+    };
+    Ok((strategy, weights))
+}
+
+/// Click feedback log, when `search.feedback.enabled` is set.
+fn feedback_log(config: &Config) -> Option<FeedbackLog> {
+    config.get::<bool>("search.feedback.enabled").unwrap_or(false).then(|| {
+        FeedbackLog::new(config.get::<String>("search.feedback.log").unwrap_or_else(|_| "../dev_data/feedback.jsonl".to_string()))
+    })
+}
+
+/// How hard results like a rejected one are pushed down
+/// (`search.feedback.reject_weight`, 0..=1; 1 zeroes an identical result).
+fn reject_weight(config: &Config) -> f32 {
+    config.get::<f32>("search.feedback.reject_weight").unwrap_or(0.5).clamp(0.0, 1.0)
+}
+
+/// Print click-through/MRR per fusion strategy and move the configured fusion
+/// weights toward the leg whose results get used (written to config.toml).
+fn tune(config: &Config, dry_run: bool) -> anyhow::Result<()> {
+    let log = FeedbackLog::new(config.get::<String>("search.feedback.log").unwrap_or_else(|_| "../dev_data/feedback.jsonl".to_string()));
+    let events = log.events()?;
+    let stats = feedback::strategy_stats(&events);
+    if stats.is_empty() { println!("No feedback recorded in {}; enable search.feedback.enabled and use `feedback` after queries", log.path().display()); return Ok(()); }
+    for s in &stats { println!("{:<10} queries={:<5} ctr={:.2} mrr={:.3}", s.strategy, s.queries, s.ctr(), s.mrr); }
+    let (_, current) = fusion_config(config)?;
+    let queries: usize = stats.iter().map(|s| s.queries).sum();
+    if queries < feedback::MIN_TUNING_QUERIES { println!("Need at least {} queries to tune (have {})", feedback::MIN_TUNING_QUERIES, queries); return Ok(()); }
+    let tuned = feedback::tune_weights(&events, current);
+    println!("Fusion weights: text {:.3} -> {:.3}, vector {:.3} -> {:.3}", current.text, tuned.text, current.vector, tuned.vector);
+    if !dry_run {
+        set_toml_value(Path::new("config.toml"), "search.fusion", "text_weight", &format!("{:.3}", tuned.text))?;
+        set_toml_value(Path::new("config.toml"), "search.fusion", "vector_weight", &format!("{:.3}", tuned.vector))?;
+        println!("Updated [search.fusion] in config.toml");
+    }
+    Ok(())
+}
+
+/// Replay the latest `limit` logged queries (all without a limit) against the
+/// configured indexes and a candidate generation in `candidate_text` /
+/// `candidate_vector`, and print top-`k` overlap and latency. Both are queried
+/// with the configured search settings and fusion; the candidate with the
+/// embedder its ingest recorded, else the configured one.
+fn replay(config: &Config, candidate_text: &Path, candidate_vector: &Path, k: usize, limit: Option<usize>) -> anyhow::Result<()> {
+    let log = FeedbackLog::new(config.get::<String>("search.feedback.log").unwrap_or_else(|_| "../dev_data/feedback.jsonl".to_string()));
+    let queries = localdb_core::replay::logged_queries(&log.events()?, limit);
+    if queries.is_empty() { println!("No queries recorded in {}; enable search.feedback.enabled and search for a while first", log.path().display()); return Ok(()); }
+    let lancedb_path = config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string());
+    let (current, _) = search_engine(config, Path::new(&lancedb_path))?;
+    let recorded = tokio::runtime::Runtime::new()?.block_on(async {
+        let conn = localdb_vector::table::open_db(&candidate_vector.to_string_lossy()).await?;
+        localdb_vector::table::collection_embedder(&conn, "documents").await
+    })?;
+    let embedder = match recorded {
+        Some(id) => { println!("Candidate embedder: {}", id); localdb_vector::embed_provider::local::recorded_embedder(&id) }
+        None => { eprintln!("⚠️  The candidate records no embedder; querying it with the configured one"); get_default_embedder() }
+    };
+    let (candidate, _) = open_search_engine(config, candidate_text, candidate_vector, rewrite_queries(config), EmbedderState::from_result(embedder)?)?;
+    warn_if_degraded(&candidate);
+    let ids = |engine: &Engine, q: &str| -> anyhow::Result<Vec<String>> { Ok(engine.query(q, k)?.into_iter().map(|h| h.id).collect()) };
+    let report = localdb_core::replay::replay(&queries, k, |q| ids(&current, q), |q| ids(&candidate, q))?;
+    print!("{}", report.render());
+    Ok(())
+}
+
+/// Fit per-leg score calibration (raw BM25 / cosine score to probability of
+/// relevance) from the judgments in `eval.dataset` and store it in Lance meta,
+/// where every query engine applies it at fusion. `reset` removes it.
+fn calibrate(config: &Config, dry_run: bool, reset: bool) -> anyhow::Result<()> {
+    use localdb_core::calibration::{labels, samples, ScoreCalibration, MIN_SAMPLES, SAMPLE_DEPTH};
+    use localdb_core::types::SourceKind;
+    let lancedb_path = config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string());
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(&lancedb_path))?;
+    if reset {
+        rt.block_on(localdb_vector::table::set_score_calibration(&conn, "documents", &ScoreCalibration::default()))?;
+        println!("Removed score calibration; fusion uses raw scores");
+        return Ok(());
+    }
+    let dataset = localdb_core::eval::EvalDataset::new(config.get::<String>("eval.dataset").unwrap_or_else(|_| "../dev_data/eval/judgments.jsonl".to_string()));
+    let labels = labels(&dataset.judgments()?);
+    if labels.is_empty() { println!("No left/right/both-bad judgments in {}; record some with `judge`", dataset.path().display()); return Ok(()); }
+    let (engine, _) = search_engine(config, Path::new(&lancedb_path))?;
+    let samples = samples(&labels, |q| engine.leg_hits(q, SAMPLE_DEPTH))?;
+    let calibration = ScoreCalibration::fit(&samples);
+    for (name, source, fit) in [("text", SourceKind::Text, &calibration.text), ("vector", SourceKind::Vector, &calibration.vector)] {
+        let n = samples.iter().filter(|s| s.source == source).count();
+        match fit {
+            Some(f) => println!("{:<6} {} samples: p(relevant) {:.2} at {:.3} up to {:.2} at {:.3}", name, n, f.ys[0], f.xs[0], f.ys[f.ys.len() - 1], f.xs[f.xs.len() - 1]),
+            None => println!("{:<6} {} samples: not calibrated (needs {} covering relevant and irrelevant hits)", name, n, MIN_SAMPLES),
+        }
+    }
+    if calibration.is_empty() || dry_run { return Ok(()); }
+    rt.block_on(localdb_vector::table::set_score_calibration(&conn, "documents", &calibration))?;
+    println!("Stored score calibration for {} labelled chunks; set search.fusion.strategy = \"weighted_sum\" to add the calibrated legs", labels.len());
+    Ok(())
+}
+
+/// Column width of each side in `judge`'s side-by-side view.
+const JUDGE_COLUMN: usize = 38;
+/// Lines of chunk text shown per side.
+const JUDGE_LINES: usize = 8;
+
+/// Show results of `query` that differ between strategies `a` and `b`, rank
+/// by rank and in a blind left/right order, and record the preferences into
+/// the eval dataset (`eval.dataset`).
+fn judge(config: &Config, query: &str, a: FusionStrategy, b: FusionStrategy) -> anyhow::Result<()> {
+    use localdb_core::eval::{disagreements, Candidate, EvalDataset, Judgment, Preference};
+    use localdb_core::seed::SeededRng;
+    let seed = localdb_core::seed::from_config(config);
+    let tantivy_index_dir: String = config.get("data.tantivy_index_dir").unwrap_or_else(|_| "../dev_data/indexes/tantivy".to_string());
+    let lancedb_path = config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string());
+    let rt = tokio::runtime::Runtime::new()?;
+    let text = TextSearch::open(Path::new(&tantivy_index_dir))?;
+    let vector = rt.block_on(LanceDbIndexer::new(Path::new(&lancedb_path), "documents"))?.with_latency_budget(localdb_vector::LatencyBudget::from_config(config));
+    let (_, weights) = fusion_config(config)?;
+    let mut engine = HybridSearchEngine::from_embedder_result(text, vector, get_default_embedder())?.with_fusion(a, weights)
+        .with_calibration(score_calibration(Path::new(&lancedb_path))?)
+        .with_preprocessor(Preprocessor::from_config(config, "documents")?);
+    warn_if_degraded(&engine);
+    let ids = |hits: Vec<localdb_core::types::SearchHit>| hits.into_iter().map(|h| h.id).collect::<Vec<_>>();
+    let left_ids = ids(engine.query(query, 10)?);
+    engine = engine.with_fusion(b, weights);
+    let right_ids = ids(engine.query(query, 10)?);
+
+    let dataset = EvalDataset::new(config.get::<String>("eval.dataset").unwrap_or_else(|_| "../dev_data/eval/judgments.jsonl".to_string()));
+    let pairs = disagreements(query, &left_ids, &right_ids, &dataset.judgments()?);
+    if pairs.is_empty() { println!("{} and {} agree on every unjudged rank for '{}'", a.name(), b.name(), query); return Ok(()); }
+    let conn = rt.block_on(localdb_vector::table::open_db(&lancedb_path))?;
+    let all: Vec<String> = pairs.iter().flat_map(|(_, x, y)| [x.clone(), y.clone()]).collect();
+    let content: std::collections::HashMap<String, String> = rt.block_on(localdb_vector::table::chunks_by_id(&conn, "documents", &all))?
+        .into_iter().map(|c| (c.id, c.content)).collect();
+    let mut recorded = 0;
+    for (rank, x, y) in pairs {
+        // Alternate sides per pair (seeded, so a session can be replayed) so neither strategy is always on the left.
+        let swap = SeededRng::for_key(seed, &format!("{}\n{}", query, rank)).coin();
+        let (left, right) = if swap { (Candidate { strategy: b.name().into(), id: y }, Candidate { strategy: a.name().into(), id: x }) } else { (Candidate { strategy: a.name().into(), id: x }, Candidate { strategy: b.name().into(), id: y }) };
+        println!("\n── '{}' rank {} ──", query, rank);
+        let column = |id: &str| wrap(content.get(id).map(String::as_str).unwrap_or("(chunk not found)"), JUDGE_COLUMN);
+        let (l, r) = (column(&left.id), column(&right.id));
+        println!("{:<w$} │ {}", format!("[1] {}", left.id), format!("[2] {}", right.id), w = JUDGE_COLUMN);
+        for i in 0..JUDGE_LINES.min(l.len().max(r.len())) {
+            println!("{:<w$} │ {}", l.get(i).map(String::as_str).unwrap_or(""), r.get(i).map(String::as_str).unwrap_or(""), w = JUDGE_COLUMN);
+        }
+        let preference = loop {
+            eprint!("Better? [1] left  [2] right  [t] tie  [b] both bad  [s] skip  [q] quit: ");
+            std::io::Write::flush(&mut std::io::stderr())?;
+            let mut line = String::new();
+            if std::io::stdin().read_line(&mut line)? == 0 { line = "q".to_string(); }
+            match line.trim() {
+                "1" => break Some(Preference::Left),
+                "2" => break Some(Preference::Right),
+                "t" => break Some(Preference::Tie),
+                "b" => break Some(Preference::BothBad),
+                "s" => break None,
+                "q" => { println!("Recorded {} judgments in {}", recorded, dataset.path().display()); return Ok(()); }
+                _ => continue,
+            }
+        };
+        let Some(preference) = preference else { continue };
+        let at_ms = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or(0);
+        dataset.append(&Judgment { query: query.to_string(), rank, left, right, preference, at_ms })?;
+        recorded += 1;
+    }
+    println!("Recorded {} judgments in {}", recorded, dataset.path().display());
+    Ok(())
+}
+
+/// Greedy word wrap to `width` characters.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut cur = String::new();
+    for word in text.split_whitespace() {
+        if !cur.is_empty() && cur.chars().count() + 1 + word.chars().count() > width { lines.push(std::mem::take(&mut cur)); }
+        if !cur.is_empty() { cur.push(' '); }
+        cur.extend(word.chars().take(width));
+    }
+    if !cur.is_empty() { lines.push(cur); }
+    lines
+}
+
+/// Catalog records keyed by `doc_id` (summaries, facets and file ages for
+/// display and reranking); empty (results still print) if the catalog is
+/// missing or unreadable.
+fn catalog_by_doc(lancedb_path: &Path) -> std::collections::HashMap<String, localdb_core::types::FileRecord> {
+    let read = || -> anyhow::Result<_> {
+        let rt = tokio::runtime::Runtime::new()?;
+        let conn = rt.block_on(localdb_vector::table::open_db(&lancedb_path.to_string_lossy()))?;
+        rt.block_on(localdb_vector::catalog::records(&conn, localdb_vector::catalog::CATALOG_TABLE))
+    };
+    let records = read().unwrap_or_else(|e| { tracing::warn!(error = %e, "Catalog unavailable"); Vec::new() });
+    records.into_iter().map(|r| (r.doc_id.clone(), r)).collect()
+}
+
+/// Chunk ids are `doc_id:hash`; catalog rows are per document.
+/// Hits fetched when `query --speaker` filters or rejections push hits
+/// down (and when `--lang` filters a browse), before keeping ten (`k`).
+const FILTER_DEPTH: usize = 50;
+
+/// Time range and speaker of the transcript chunks among `hits`, by chunk
+/// id, from the chunk metadata both legs return with their hits.
+fn hit_moments(hits: &[localdb_core::types::SearchHit]) -> std::collections::HashMap<String, Moment> {
+    hits.iter().filter_map(|h| Moment::from_meta(&h.meta).map(|m| (h.id.clone(), m))).collect()
+}
+
+/// Re-hash every cataloged file and report mismatches per document. Returns
+/// `false` when any file changed or could not be read.
+fn scrub(lancedb_path: &str) -> anyhow::Result<bool> {
+    use localdb_vector::catalog::{self, ScrubStatus};
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(lancedb_path))?;
+    let roots = rt.block_on(localdb_vector::table::data_roots(&conn, "documents"))?;
+    let results = rt.block_on(catalog::scrub(&conn, catalog::CATALOG_TABLE, &roots))?;
+    if results.is_empty() { println!("Catalog is empty; run `ingest` first"); return Ok(true); }
+    let (mut ok, mut bad, mut missing, mut offline) = (0, 0, 0, 0);
+    for (record, status) in &results {
+        match status {
+            ScrubStatus::Ok => ok += 1,
+            ScrubStatus::Mismatch { actual } => { bad += 1; println!("❌ {} ({}): hash mismatch, expected {} got {}", record.doc_id, record.doc_path, record.file_hash, actual); }
+            ScrubStatus::Unreadable(e) => { bad += 1; println!("❌ {} ({}): unreadable: {}", record.doc_id, record.doc_path, e); }
+            ScrubStatus::Missing => { missing += 1; println!("⚠️  {} ({}): missing", record.doc_id, record.doc_path); }
+            ScrubStatus::Offline(_) => offline += 1,
+        }
+    }
+    println!("Scrubbed {} files: {} ok, {} corrupt/unreadable, {} missing, {} on offline media", results.len(), ok, bad, missing, offline);
+    Ok(bad == 0)
+}
+
+/// Facet aliases recorded by `facet rename` (empty when the index has none).
+fn facet_aliases(lancedb_path: &Path) -> anyhow::Result<FacetAliases> {
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(&lancedb_path.to_string_lossy()))?;
+    rt.block_on(localdb_vector::table::facet_aliases(&conn))
+}
+
+/// Score calibration recorded in Lance meta by `calibrate` (none if not fitted).
+fn score_calibration(lancedb_path: &Path) -> anyhow::Result<localdb_core::calibration::ScoreCalibration> {
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(&lancedb_path.to_string_lossy()))?;
+    rt.block_on(localdb_vector::table::score_calibration(&conn, "documents"))
+}
+
+/// `facet list` prints the alias table; `facet rename OLD NEW` records an
+/// alias, failing unless some indexed document is filed under OLD. Stored
+/// values are rewritten later by `maintain`.
+fn facet(config: &Config, args: &[String]) -> anyhow::Result<()> {
+    let lancedb_path = config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string());
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(&lancedb_path))?;
+    let mut aliases = rt.block_on(localdb_vector::table::facet_aliases(&conn))?;
+    match (args.first().map(String::as_str), args.get(1), args.get(2)) {
+        (Some("list") | None, _, _) => {
+            if aliases.is_empty() { println!("No facet aliases"); }
+            for (old, new) in &aliases.0 { println!("{} → {}", old, new); }
+        }
+        (Some("rename"), Some(old), Some(new)) => {
+            // Stored facets under their current names, as queries see them.
+            let stored = rt.block_on(localdb_vector::table::distinct_values(&conn, "documents", "category"))?;
+            if !stored.iter().any(|c| localdb_core::facets::is_within(&aliases.resolve(c), old)) {
+                return Err(ErrorClass::Usage.error(format!("no indexed documents are filed under {}", localdb_core::facets::normalize(old))));
+            }
+            aliases.rename(old, new);
+            rt.block_on(localdb_vector::table::set_facet_aliases(&conn, &aliases))?;
+            println!("Aliased {} → {} (stored values are rewritten by `localdb-cli maintain`)", localdb_core::facets::normalize(old), localdb_core::facets::normalize(new));
+        }
+        _ => return Err(ErrorClass::Usage.error("localdb-cli facet <list|rename OLD NEW>")),
+    }
+    Ok(())
+}
+
+/// Print a document's original file: the source if it is reachable, else the
+/// blob store copy taken at ingest (source moved, deleted, or offline).
+fn open(config: &Config, target: &str) -> anyhow::Result<()> {
+    let lancedb_path = config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string());
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(&lancedb_path))?;
+    let roots = rt.block_on(localdb_vector::table::data_roots(&conn, "documents"))?;
+    let records = rt.block_on(localdb_vector::catalog::records(&conn, localdb_vector::catalog::CATALOG_TABLE))?;
+    let record = records.iter().find(|r| r.doc_id == target || r.doc_path == target).ok_or_else(|| anyhow::anyhow!("no cataloged document '{}'", target))?;
+    let path = match roots.openable(&record.doc_path) {
+        Some(p) => { eprintln!("📂 {}", p.display()); p }
+        None => {
+            let blob = localdb_core::blobs::BlobStore::from_config(config).and_then(|b| b.get(&record.file_hash));
+            let Some(p) = blob else {
+                anyhow::bail!("{} is {} and not in the blob store (set data.blob_store and re-ingest)", record.doc_path, roots.offline_label(&record.doc_path).unwrap_or_else(|| "missing".to_string()))
+            };
+            eprintln!("📦 {} (blob store copy from ingest)", record.doc_path);
+            p
+        }
+    };
+    std::io::copy(&mut std::fs::File::open(path)?, &mut std::io::stdout())?;
+    Ok(())
+}
+
+/// Print the `mpv` command that plays a transcript chunk's moment from the
+/// recording found next to its transcript at ingest.
+fn play(config: &Config, chunk_id: &str) -> anyhow::Result<()> {
+    let lancedb_path = config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string());
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(&lancedb_path))?;
+    let chunk = rt.block_on(localdb_vector::table::chunks_by_id(&conn, "documents", &[chunk_id.to_string()]))?.into_iter().next()
+        .ok_or_else(|| anyhow::anyhow!("no stored chunk '{}'", chunk_id))?;
+    let moment = Moment::from_doc_path(&chunk.doc_path).ok_or_else(|| anyhow::anyhow!("{} is not a transcript chunk", chunk_id))?;
+    let records = rt.block_on(localdb_vector::catalog::records(&conn, localdb_vector::catalog::CATALOG_TABLE))?;
+    let media = records.iter().find(|r| r.doc_id == chunk.doc_id).and_then(|r| r.meta.get(localdb_core::transcript::MEDIA_KEY))
+        .ok_or_else(|| anyhow::anyhow!("no recording was found next to the transcript of {} at ingest", chunk.doc_id))?;
+    let roots = rt.block_on(localdb_vector::table::data_roots(&conn, "documents"))?;
+    let path = roots.openable(media).ok_or_else(|| anyhow::anyhow!("{} is {}", media, roots.offline_label(media).unwrap_or_else(|| "missing".to_string())))?;
+    println!("{}", localdb_core::transcript::mpv_command(&path, moment.start));
+    Ok(())
+}
+
+/// Check each claim of a generated `answer` against the stored chunks it
+/// cites (see `localdb_core::citations`) and print the verdicts. Returns
+/// whether every claim is supported.
+fn verify(config: &Config, answer: &str) -> anyhow::Result<bool> {
+    use localdb_core::citations::{self, CitationThresholds, Support};
+    let lancedb_path = config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string());
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(&lancedb_path))?;
+    let ids: Vec<String> = citations::claims(answer).into_iter().flat_map(|c| c.cited).collect::<std::collections::BTreeSet<_>>().into_iter().collect();
+    let chunks: std::collections::HashMap<String, String> = rt.block_on(localdb_vector::table::chunks_by_id(&conn, "documents", &ids))?.into_iter().map(|c| (c.id, c.content)).collect();
+    let embedder = EmbedderState::from_result(get_default_embedder())?;
+    let embedder = match &embedder {
+        EmbedderState::Ready(e) => Some(e.as_ref()),
+        EmbedderState::EmbedderUnavailable(reason) => { eprintln!("⚠️  Embedding model unavailable ({}); checking word overlap only", reason); None }
+    };
+    let checked = citations::verify(answer, &chunks, embedder, CitationThresholds::from_config(config))?;
+    for c in &checked {
+        match &c.support {
+            Support::Supported { chunk_id, sentence, .. } => println!("✅ {}\n   ↳ [{}] {}", c.claim.text, chunk_id, sentence),
+            Support::Unsupported => println!("⚠️  Unsupported: {}\n   ↳ not found in {}", c.claim.text, c.claim.cited.join(", ")),
+            Support::Missing => println!("❓ Cites chunks not in the index: {}\n   ↳ {}", c.claim.text, c.claim.cited.join(", ")),
+            Support::Uncited => println!("❔ No citation: {}", c.claim.text),
+        }
+    }
+    let supported = checked.iter().filter(|c| matches!(c.support, Support::Supported { .. })).count();
+    println!("{} of {} claims supported by their citations", supported, checked.len());
+    Ok(supported == checked.len())
+}
+
+/// The session's scratch collection (`search.scratch.path`, forgotten
+/// `search.scratch.ttl_minutes` after the last add), encrypted with
+/// `passphrase` when the indexes are.
+fn scratch_pad(config: &Config, passphrase: Option<&str>) -> localdb_core::scratch::ScratchPad {
+    let path = config.get::<String>("search.scratch.path").unwrap_or_else(|_| "../dev_data/scratch.jsonl".to_string());
+    localdb_core::scratch::ScratchPad::new(path, std::time::Duration::from_secs(config.get::<u64>("search.scratch.ttl_minutes").unwrap_or(120) * 60))
+        .with_passphrase(passphrase.map(str::to_string))
+}
+
+/// The index passphrase, checked against a sealed index directory, when the
+/// indexes are sealed; `None` when they are plaintext.
+fn scratch_passphrase(config: &Config) -> anyhow::Result<Option<String>> {
+    let Some(sealed) = index_dirs(config).into_iter().find(|d| crypt::is_sealed(d)) else {
+        if config.get::<bool>("security.encrypt_indexes").unwrap_or(false) {
+            return Err(ErrorClass::MissingIndex.error("security.encrypt_indexes is set but the indexes are not sealed yet; run ingest first so the scratch collection shares their passphrase"));
+        }
+        return Ok(None);
+    };
+    let passphrase = crypt::read_passphrase("Index passphrase: ")?;
+    crypt::check_passphrase(&sealed, &passphrase)?;
+    Ok(Some(passphrase))
+}
+
+/// `scratch add <-|file> [--name N]`, `scratch list` and `scratch clear`:
+/// text searched alongside the index by `query` for the rest of the session,
+/// chunked like an ingested file and embedded once here.
+fn scratch(config: &Config, args: &[String]) -> anyhow::Result<()> {
+    use localdb_core::scratch::ScratchEntry;
+    let passphrase = match args.first().map(String::as_str) { Some("add" | "list") => scratch_passphrase(config)?, _ => None };
+    let pad = scratch_pad(config, passphrase.as_deref());
+    let now_ms = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or(0);
+    match (args.first().map(String::as_str), args.get(1)) {
+        (Some("add"), Some(source)) => {
+            let text = if source == "-" { std::io::read_to_string(std::io::stdin())? } else { std::fs::read_to_string(source).with_context(|| format!("reading {}", source))? };
+            let name = args.iter().position(|a| a == "--name").and_then(|i| args.get(i + 1)).cloned()
+                .unwrap_or_else(|| if source == "-" { "stdin".to_string() } else { source.clone() });
+            let doc_id = pad.next_doc_id(now_ms)?;
+            let processor = DataProcessor::with_config(ChunkingConfig::from_config(config).context(ErrorClass::Config)?);
+            let chunks = processor.chunk_text(&text, &doc_id, Path::new(&format!("scratch:{}", name)), "scratch")?;
+            if chunks.is_empty() { return Err(ErrorClass::Usage.error("nothing to add: the text is empty")); }
+            let vectors = match EmbedderState::from_result(get_default_embedder())? {
+                EmbedderState::Ready(e) => e.embed_batch(&Preprocessor::from_config(config, "documents").context(ErrorClass::Config)?.embedding_texts(&chunks))?,
+                EmbedderState::EmbedderUnavailable(reason) => { eprintln!("⚠️  Embedding model unavailable ({}); {} is searchable as text only", reason, name); Vec::new() }
+            };
+            println!("📝 Added {} ({} chunks) as {}; searched by `query` until {} minutes pass without another add", name, chunks.len(), doc_id, config.get::<u64>("search.scratch.ttl_minutes").unwrap_or(120));
+            pad.add(&ScratchEntry { name, added_at_ms: now_ms, chunks, vectors })?;
+        }
+        (Some("list"), None) => {
+            let entries = pad.entries(now_ms)?;
+            if entries.is_empty() { println!("The scratch collection is empty"); }
+            for e in &entries { println!("{} {} ({} chunks{})", e.chunks.first().map_or("", |c| c.doc_id.as_str()), e.name, e.chunks.len(), if e.vectors.is_empty() { ", text only" } else { "" }); }
+        }
+        (Some("clear"), None) => if pad.clear()? { println!("🧹 Cleared the scratch collection"); },
+        _ => return Err(ErrorClass::Usage.error("localdb-cli scratch <add <-|file> [--name N]|list|clear>")),
+    }
+    Ok(())
+}
+
+/// This deployment's `Manifest` (data roots + cataloged file hashes).
+fn local_manifest(lancedb_path: &str) -> anyhow::Result<localdb_core::sync::Manifest> {
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(lancedb_path))?;
+    let roots = rt.block_on(localdb_vector::table::data_roots(&conn, "documents"))?;
+    let records = rt.block_on(localdb_vector::catalog::records(&conn, localdb_vector::catalog::CATALOG_TABLE))?;
+    Ok(localdb_core::sync::Manifest::from_records(roots, &records))
+}
+
+/// Single-quote `s` for a POSIX shell (remote paths go through the remote shell).
+fn shell_quote(s: &str) -> String { format!("'{}'", s.replace('\'', "'\\''")) }
+
+fn run(cmd: &mut std::process::Command) -> anyhow::Result<std::process::Output> {
+    let out = cmd.output().map_err(|e| anyhow::anyhow!("failed to run {:?}: {}", cmd.get_program(), e))?;
+    if !out.status.success() { anyhow::bail!("{:?} failed: {}", cmd.get_program(), String::from_utf8_lossy(&out.stderr).trim()); }
+    Ok(out)
+}
+
+/// `run` with `input` on the command's stdin.
+fn run_with_input(cmd: &mut std::process::Command, input: &str) -> anyhow::Result<std::process::Output> {
+    use std::io::Write;
+    use std::process::Stdio;
+    let mut child = cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().map_err(|e| anyhow::anyhow!("failed to run {:?}: {}", cmd.get_program(), e))?;
+    child.stdin.take().expect("stdin is piped").write_all(input.as_bytes())?;
+    let out = child.wait_with_output()?;
+    if !out.status.success() { anyhow::bail!("{:?} failed: {}", cmd.get_program(), String::from_utf8_lossy(&out.stderr).trim()); }
+    Ok(out)
+}
+
+/// `embeddings export`: the serving vectors of the stored chunks of
+/// `doc_paths` as embedding cache entries (`CacheEntry::encode` lines). None
+/// when `[preprocess]` steps make the embedded text differ from the chunk
+/// content the cache is keyed by.
+fn export_embeddings(config: &Config, doc_paths: &[String]) -> anyhow::Result<String> {
+    use localdb_vector::cache::{file_entries, CacheEntry};
+    if !Preprocessor::from_config(config, "documents").context(ErrorClass::Config)?.is_identity() { return Ok(String::new()); }
+    let lancedb_path = config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string());
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(&lancedb_path))?;
+    let Some(dim) = rt.block_on(localdb_vector::table::collection_dim(&conn, "documents"))? else { return Ok(String::new()) };
+    let embedder_id = localdb_vector::embed_provider::local::embedder_id(dim as usize)?;
+    let entries = rt.block_on(file_entries(&conn, "documents", &embedder_id, doc_paths))?;
+    Ok(entries.iter().map(CacheEntry::encode).collect::<Vec<_>>().join("\n"))
+}
+
+/// `embeddings import`: add the entries in `lines` (`export_embeddings`
+/// output) to the `emb_cache` table; returns how many were new.
+fn import_embeddings(config: &Config, lines: &str) -> anyhow::Result<usize> {
+    let entries: Vec<_> = lines.lines().filter_map(localdb_vector::cache::CacheEntry::decode).collect();
+    if entries.is_empty() { return Ok(0); }
+    let lancedb_path = config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string());
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(&lancedb_path))?;
+    rt.block_on(localdb_vector::cache::put_missing(&conn, "emb_cache", entries))
+}
+
+/// `embedder` answering from the `emb_cache` table for the `chunks` it holds
+/// vectors of (those `sync` shipped in), so they are not embedded again.
+/// Unchanged with `[preprocess]` steps, which change the embedded text.
+fn with_cached_vectors(config: &Config, rt: &tokio::runtime::Runtime, lancedb_path: &Path, embedder: &EmbedderState, chunks: &[DocumentChunk]) -> anyhow::Result<EmbedderState> {
+    let EmbedderState::Ready(e) = embedder else { return Ok(embedder.clone()) };
+    if chunks.is_empty() || !Preprocessor::from_config(config, "documents").context(ErrorClass::Config)?.is_identity() { return Ok(embedder.clone()); }
+    let embedder_id = localdb_vector::embed_provider::local::embedder_id(e.dim())?;
+    let cached = rt.block_on(async {
+        let conn = localdb_vector::table::open_db(&lancedb_path.to_string_lossy()).await?;
+        localdb_vector::cache::cached_embedder(&conn, "emb_cache", &embedder_id, e.clone(), chunks).await
+    })?;
+    Ok(match cached {
+        Some(cached) => { println!("♻️  Reusing {} shipped vectors", cached.len()); EmbedderState::Ready(std::sync::Arc::new(cached)) }
+        None => embedder.clone(),
+    })
+}
+
+/// Two-way differential sync with another deployment over SSH: fetch its
+/// manifest (`sync.remote_command manifest`), then copy only the files whose
+/// content the other side lacks, in both directions, with `scp`, and the
+/// vectors of their chunks (`embeddings export`/`import`) so the receiving
+/// ingest does not embed them again. Paths with different content on each
+/// side are reported and left alone, and so are the other side's paths that
+/// could land outside a local root. Both sides index what they received on
+/// their next `ingest`.
+fn sync(config: &Config, remote: &str, dry_run: bool) -> anyhow::Result<()> {
+    use std::process::Command;
+    let lancedb_path = config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string());
+    let remote_command = config.get::<String>("sync.remote_command").unwrap_or_else(|_| "localdb-cli".to_string());
+    let local = local_manifest(&lancedb_path)?;
+    let out = run(Command::new("ssh").arg(remote).arg(format!("{} manifest", remote_command)))?;
+    let theirs = localdb_core::sync::Manifest::decode(&String::from_utf8_lossy(&out.stdout));
+    let plan = localdb_core::sync::plan(&local, &theirs);
+    println!("Sync with {}: {} to pull, {} to push, {} conflicts", remote, plan.pull.len(), plan.push.len(), plan.conflicts.len());
+    for p in &plan.conflicts { println!("⚠️  {}: content differs on both sides; skipped", p); }
+    for p in &plan.rejected { println!("⛔ {}: not a relative path under a root; refused", p); }
+    if dry_run {
+        for p in &plan.pull { println!("⬇️  {}", p); }
+        for p in &plan.push { println!("⬆️  {}", p); }
+        return Ok(());
+    }
+    let (mut pulled, mut pushed) = (Vec::new(), Vec::new());
+    for p in &plan.pull {
+        let dest = local.roots.resolve(p);
+        let under_root = local.roots.0.iter().any(|(_, root)| root.is_absolute() && dest.starts_with(root));
+        if !under_root || local.roots.offline_label(p).is_some() { println!("⏭️  {}: no local root for it (or offline); skipped", p); continue; }
+        if let Some(dir) = dest.parent() { std::fs::create_dir_all(dir)?; }
+        run(Command::new("scp").arg("-p").arg(format!("{}:{}", remote, shell_quote(&theirs.roots.resolve(p).to_string_lossy()))).arg(&dest))?;
+        println!("⬇️  {}", p); pulled.push(p.clone());
+    }
+    for p in &plan.push {
+        let Some(src) = local.roots.openable(p) else { println!("⏭️  {}: offline or missing here; skipped", p); continue };
+        let dest = theirs.roots.resolve(p);
+        if !dest.is_absolute() { println!("⏭️  {}: no matching root on {}; skipped", p, remote); continue; }
+        if let Some(dir) = dest.parent() { run(Command::new("ssh").arg(remote).arg(format!("mkdir -p {}", shell_quote(&dir.to_string_lossy()))))?; }
+        run(Command::new("scp").arg("-p").arg(&src).arg(format!("{}:{}", remote, shell_quote(&dest.to_string_lossy()))))?;
+        println!("⬆️  {}", p); pushed.push(p.clone());
+    }
+    // Without the vectors both sides still index everything; they just embed it.
+    if !pulled.is_empty() {
+        let shipped = run_with_input(Command::new("ssh").arg(remote).arg(format!("{} embeddings export", remote_command)), &pulled.join("\n"))
+            .and_then(|out| import_embeddings(config, &String::from_utf8_lossy(&out.stdout)));
+        match shipped { Ok(n) => println!("⬇️  {} vectors", n), Err(e) => eprintln!("⚠️  Could not fetch vectors from {}: {:#}; ingest will embed the pulled files", remote, e) }
+    }
+    if !pushed.is_empty() {
+        let shipped = export_embeddings(config, &pushed)
+            .and_then(|lines| run_with_input(Command::new("ssh").arg(remote).arg(format!("{} embeddings import", remote_command)), &lines));
+        if let Err(e) = shipped { eprintln!("⚠️  Could not send vectors to {}: {:#}; its ingest will embed the pushed files", remote, e); }
+    }
+    println!("Pulled {} and pushed {} files; run `localdb-cli ingest` here and on {} to index them", pulled.len(), pushed.len(), remote);
+    Ok(())
+}
+
+/// `search.vector.max_lag_share`: share of chunks that may lack a current
+/// serving vector before `stats` and `maintain` warn (default 0.01).
+fn max_vector_lag(config: &Config) -> f64 { config.get("search.vector.max_lag_share").unwrap_or(0.01) }
+
+/// Maintenance job: enforce `[[retention]]` rules by deleting expired
+/// documents from both indexes and the catalog, then rewrite facets renamed
+/// by `facet rename` in Lance and the catalog, warning first when serving
+/// vectors lag `embeddings`. Safe to run from cron.
+fn maintain(config: &Config) -> anyhow::Result<()> {
+    use localdb_vector::catalog::{self, CATALOG_TABLE};
+    let dirs = index_dirs(config);
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(&dirs[1].to_string_lossy()))?;
+    let aliases = rt.block_on(localdb_vector::table::facet_aliases(&conn))?;
+    if !aliases.is_empty() {
+        let chunks = rt.block_on(localdb_vector::table::rewrite_facets(&conn, "documents", &["category", "category_text"], &aliases))?;
+        let files = rt.block_on(localdb_vector::table::rewrite_facets(&conn, CATALOG_TABLE, &["category"], &aliases))?;
+        println!("Facets: rewrote {} chunks and {} catalog entries to renamed facets", chunks, files);
+    }
+    let active = rt.block_on(localdb_vector::table::collection_embedder(&conn, "documents"))?;
+    let coverage = rt.block_on(localdb_vector::index_build::vector_coverage(&conn, "documents", "embeddings", active.as_deref()))?;
+    if let Some(w) = coverage.warning(max_vector_lag(config)) { println!("⚠️  Vectors: {}", w); }
+    let policy = RetentionPolicy::from_config(config);
+    let records = rt.block_on(catalog::records(&conn, CATALOG_TABLE))?;
+    let expired = catalog::expired(&records, &policy, std::time::SystemTime::now());
+    if policy.is_empty() {
+        println!("No [[retention]] rules configured; nothing expires");
+    } else if expired.is_empty() {
+        println!("Retention: {} documents checked, none expired", records.len());
+    } else {
+        for r in &expired { println!("🗑️  {} ({}) expired", r.doc_id, r.category); }
+        let paths: Vec<String> = expired.iter().map(|r| r.doc_path.clone()).collect();
+        let chunk_ids = rt.block_on(localdb_vector::table::delete_documents(&conn, "documents", "embeddings", &paths))?;
+        if dirs[0].exists() { TextIndex::delete_documents(&dirs[0], &paths)?; }
+        rt.block_on(catalog::delete_records(&conn, CATALOG_TABLE, &paths))?;
+        println!("Retention: removed {} documents ({} chunks)", expired.len(), chunk_ids.len());
+    }
+    if let Some(blobs) = localdb_core::blobs::BlobStore::from_config(config) {
+        let orphans = blobs.uncataloged(records.iter().filter(|r| !expired.iter().any(|e| e.doc_path == r.doc_path)))?;
+        println!("Blobs: removed {} originals of files no longer cataloged", blobs.remove(&orphans)?);
+    }
+    Ok(())
+}
+
+/// Collection counts by embedding status; with `index`, the vector index
+/// state, to tell whether queries use the ANN index or brute-force.
+fn stats(config: &Config, index: bool, facets: Option<usize>, corpus: Option<usize>) -> anyhow::Result<()> {
+    use std::collections::BTreeMap;
+    let dirs = index_dirs(config);
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(&dirs[1].to_string_lossy()))?;
+    let info = rt.block_on(localdb_vector::index_build::index_info(&conn, "documents"))?;
+    let mut by_status: BTreeMap<String, usize> = BTreeMap::new();
+    for s in rt.block_on(localdb_vector::gc::column_values(&conn, "documents", "embedding_status"))? { *by_status.entry(s).or_default() += 1; }
+    println!("documents: {} chunks, {} with a serving vector", info.rows, info.vectors);
+    println!("  embedding status: {}", by_status.iter().map(|(s, n)| format!("{} {}", s, n)).collect::<Vec<_>>().join(", "));
+    let active = rt.block_on(localdb_vector::table::collection_embedder(&conn, "documents"))?;
+    let coverage = rt.block_on(localdb_vector::index_build::vector_coverage(&conn, "documents", "embeddings", active.as_deref()))?;
+    println!("  null vectors: {} ({:.1}%), {} unsynced from embeddings, {} stale", coverage.null, coverage.null_ratio() * 100.0, coverage.unsynced, coverage.stale);
+    if let Some(w) = coverage.warning(max_vector_lag(config)) { println!("  ⚠️  {}", w); }
+    if let Some(top) = facets {
+        let text = TextSearch::open(&dirs[0])?.with_facet_aliases(facet_aliases(&dirs[1])?);
+        let facets = text.facet_stats(top)?;
+        let total = facets.iter().map(|f| f.tokens).sum::<u64>().max(1);
+        println!("facets (by indexed tokens):");
+        for f in &facets {
+            println!("  {}: {} chunks in {} documents, avg {:.0} tokens per chunk, {:.0}% of the index", f.facet, f.chunks, f.documents, f.avg_chunk_tokens(), f.tokens as f64 * 100.0 / total as f64);
+            if let Some((path, share)) = &f.largest {
+                println!("    largest document: {} ({:.0}%){}", path, share * 100.0, if f.is_dominated() { "  ⚠️  dominates the facet's term statistics" } else { "" });
+            }
+            println!("    top terms: {}", f.top_terms.iter().map(|t| format!("{} {} ({} chunks)", t.term, t.count, t.chunks)).collect::<Vec<_>>().join(", "));
+        }
+    }
+    if let Some(top) = corpus {
+        // Sized as ingest sizes chunks; the model is not needed for that.
+        let processor = data_processor(config, &EmbedderState::EmbedderUnavailable("not needed".to_string()))?;
+        let mut tally = localdb_core::corpus::CorpusTally::default();
+        rt.block_on(localdb_vector::table::for_each_chunk(&conn, "documents", |c| tally.add(&c, processor.count_tokens(&c.content))))?;
+        let files = rt.block_on(localdb_vector::catalog::records(&conn, localdb_vector::catalog::CATALOG_TABLE))?.into_iter().map(|r| r.doc_path).collect();
+        print!("{}", tally.finish(&files, top).render());
+    }
+    if !index { return Ok(()); }
+    println!("vector index:");
+    println!("  active pointer: {}", info.active_index.as_deref().unwrap_or("none"));
+    println!("  indices on `vector`: {}", if info.vector_indices.is_empty() { "none".to_string() } else { info.vector_indices.join(", ") });
+    let Some(name) = &info.index_name else {
+        println!("  ⚠️  no ANN index: every query brute-forces all {} vectors", info.vectors);
+        println!("  fragments: {}", info.fragments);
+        return Ok(());
+    };
+    let opt = |v: Option<usize>| v.map(|n| n.to_string()).unwrap_or_else(|| "?".to_string());
+    println!("  serving: {} (nlist {}, m {}, nbits {})", name, opt(info.nlist), opt(info.m), opt(info.nbits));
+    if info.active_index.as_ref().is_some_and(|a| a != name) { println!("  ⚠️  active pointer names an index that no longer exists"); }
+    let built = info.built_at_ms.and_then(ago).unwrap_or_else(|| "unknown".to_string());
+    println!("  built: {}", built);
+    println!("  indexed vectors: {}", info.indexed_rows);
+    println!("  unindexed (brute-forced per query): {}{}", info.unindexed_rows, if info.unindexed_rows > info.indexed_rows / 10 { "  ⚠️  rebuild the index" } else { "" });
+    println!("  fragments: {}", info.fragments);
+    Ok(())
+}
+
+/// `ms since the epoch` → "N days/hours/minutes ago".
+fn ago(at_ms: i64) -> Option<String> {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).ok()?.as_millis() as i64;
+    let mins = (now - at_ms).max(0) / 60_000;
+    Some(match mins { m if m < 60 => format!("{} minutes ago", m), m if m < 48 * 60 => format!("{} hours ago", m / 60), m => format!("{} days ago", m / (24 * 60)) })
+}
+
+/// Consistency job: find Lance chunks missing from Tantivy (re-indexed there),
+/// Tantivy docs with no Lance chunk, chunks of files no longer cataloged,
+/// `embeddings` rows without a chunk, cache entries of embedders with no
+/// serving vectors, and stored originals of uncataloged files; remove the
+/// orphans and print what changed.
+fn gc(config: &Config, dry_run: bool) -> anyhow::Result<()> {
+    use localdb_vector::catalog::{self, CATALOG_TABLE};
+    use localdb_vector::gc as lgc;
+    use std::collections::BTreeSet;
+    let dirs = index_dirs(config);
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(&dirs[1].to_string_lossy()))?;
+    let ids = rt.block_on(lgc::column_values(&conn, "documents", "id"))?;
+    let paths = rt.block_on(lgc::column_values(&conn, "documents", "doc_path"))?;
+    let records = rt.block_on(catalog::records(&conn, CATALOG_TABLE))?;
+    let cataloged: HashSet<String> = records.iter().map(|r| r.doc_path.clone()).collect();
+    let blobs = localdb_core::blobs::BlobStore::from_config(config);
+    let orphan_blobs = match &blobs { Some(b) => b.uncataloged(&records)?, None => Vec::new() };
+    // Without a catalog (indexes from before it existed) nothing counts as uncataloged.
+    let uncataloged: Vec<String> = if cataloged.is_empty() { Vec::new() } else {
+        lgc::uncataloged(&paths, &cataloged)
+    };
+    let (text_ids, live) = if dirs[0].exists() { (TextIndex::stored_ids(&dirs[0])?, true) } else { (HashSet::new(), false) };
+    let lance_ids: HashSet<&String> = ids.iter().collect();
+    let uncataloged_set: HashSet<&String> = uncataloged.iter().collect();
+    let missing_in_text: Vec<String> = if live { ids.iter().zip(&paths).filter(|(id, p)| !text_ids.contains(*id) && !uncataloged_set.contains(p)).map(|(id, _)| id.clone()).collect() } else { Vec::new() };
+    let orphan_text: Vec<String> = text_ids.iter().filter(|id| !lance_ids.contains(id)).cloned().collect();
+    let orphan_embeddings = rt.block_on(lgc::orphan_embeddings(&conn, "documents", "embeddings"))?;
+    // One row per token: count chunks, not rows.
+    let token_table = localdb_vector::tokens::token_table("documents");
+    let orphan_tokens: Vec<String> = rt.block_on(lgc::orphan_embeddings(&conn, "documents", &token_table))?.into_iter().collect::<BTreeSet<_>>().into_iter().collect();
+    let keep: BTreeSet<String> = rt.block_on(lgc::column_values(&conn, "embeddings", "embedder_id"))?.into_iter().collect();
+    // With no serving embeddings there is no way to tell which embedder is current.
+    let stale = if keep.is_empty() { BTreeSet::new() } else { rt.block_on(lgc::stale_cache_embedders(&conn, "emb_cache", &keep))? };
+
+    println!("GC report{}:", if dry_run { " (dry run)" } else { "" });
+    println!("  {} chunks missing from the text index", missing_in_text.len());
+    println!("  {} text docs with no Lance chunk", orphan_text.len());
+    println!("  {} documents no longer in the catalog", uncataloged.len());
+    for p in &uncataloged { println!("    {}", p); }
+    println!("  {} embeddings rows without a chunk", orphan_embeddings.len());
+    println!("  {} token vector sets without a chunk", orphan_tokens.len());
+    println!("  cache entries of unknown embedders: {}", if stale.is_empty() { "none".to_string() } else { stale.iter().cloned().collect::<Vec<_>>().join(", ") });
+    if blobs.is_some() { println!("  {} stored originals no catalog entry references", orphan_blobs.len()); }
+    if dry_run { return Ok(()); }
+
+    if !uncataloged.is_empty() {
+        rt.block_on(localdb_vector::table::delete_documents(&conn, "documents", "embeddings", &uncataloged))?;
+        if live { TextIndex::delete_documents(&dirs[0], &uncataloged)?; }
+    }
+    if !orphan_text.is_empty() { TextIndex::delete_ids(&dirs[0], &orphan_text)?; }
+    if !missing_in_text.is_empty() {
+        let chunks = rt.block_on(localdb_vector::table::chunks_by_id(&conn, "documents", &missing_in_text))?;
+        TextIndexer::index(&TextIndex::open(&dirs[0], text_analysis(config))?, &chunks)?;
+    }
+    rt.block_on(lgc::delete_ids(&conn, "embeddings", &orphan_embeddings))?;
+    rt.block_on(lgc::delete_ids(&conn, &token_table, &orphan_tokens))?;
+    let purged = rt.block_on(lgc::purge_cache(&conn, "emb_cache", &stale))?;
+    let removed_blobs = match &blobs { Some(b) => b.remove(&orphan_blobs)?, None => 0 };
+    println!("Repaired: re-indexed {} chunks as text, removed {} text docs, {} documents, {} embeddings rows, {} token vector sets, {} cache entries, {} stored originals",
+        missing_in_text.len(), orphan_text.len(), uncataloged.len(), orphan_embeddings.len(), orphan_tokens.len(), purged, removed_blobs);
+    Ok(())
+}
+
+/// The embedded web UI: a search box over `/search`.
+#[cfg(feature = "web")]
+const WEB_UI: &str = include_str!("../../web/index.html");
+/// Schema of protobuf `/search` pages.
+#[cfg(feature = "web")]
+const SEARCH_PROTO: &str = include_str!("../../web/search.proto");
+
+/// What `serve` answers from, shared by the connection threads.
+#[cfg(feature = "web")]
+struct Api<'a> {
+    /// The engine opened at the current epoch (reopened when it moves).
+    engines: &'a localdb_core::epoch_cache::EpochCell<Engine>,
+    /// The same without query rewriting, opened on the first `raw=1` search.
+    raw_engines: &'a localdb_core::epoch_cache::EpochCell<Engine>,
+    /// Opens the engine over the indexes as they are now; `true` opens it
+    /// without query rewriting.
+    reopen: &'a (dyn Fn(bool) -> anyhow::Result<Engine> + Sync),
+    /// Rendered `/search` pages by ETag, dropped when the epoch moves.
+    pages: &'a localdb_core::epoch_cache::EpochMap<localdb_cli::http::Response>,
+    /// Per-request index epoch (see `localdb_vector::table::index_epoch`).
+    epoch: &'a (dyn Fn() -> anyhow::Result<String> + Sync),
+    /// Stored chunks of one document, in order.
+    chunks: &'a (dyn Fn(&str) -> anyhow::Result<Vec<DocumentChunk>> + Sync),
+    /// Images kept at ingest (`data.asset_store`), if configured.
+    assets: Option<&'a localdb_core::assets::AssetStore>,
+    /// Where long jobs report their progress (`data.progress_dir`).
+    progress: &'a Path,
+    /// Default and maximum `k`.
+    limits: (usize, usize),
+    /// Also search the keyword form of questions (`search.multi_query.keywords`).
+    keywords: bool,
+    /// `search.feedback.reject_weight`.
+    reject_weight: f32,
+}
+
+/// `serve`: the web UI at `/`, `GET /search?q=&also=&reject=&facet=&k=&max_per_doc=&lang=&raw=` as JSON and the
+/// document viewer's `/document/<doc_id>/{content,chunks}`, answered by
+/// `server.workers` threads; connections beyond what they have queued get
+/// `503`. Errors are logged and answered with a bare `500`. Pages carry an `ETag` over the request and the index epoch;
+/// clients revalidating with `If-None-Match` get `304` until the indexes
+/// change. Partial (vector timed out) pages get none. The engine and the
+/// last `server.cached_pages` pages are kept per epoch (`epoch_cache`): the
+/// first request after an ingest, flip or rename reopens the indexes and
+/// renders afresh.
+#[cfg(feature = "web")]
+fn serve(config: &Config, listen: &str) -> anyhow::Result<()> {
+    use localdb_core::epoch_cache::{EpochCell, EpochMap};
+    let lancedb_path = PathBuf::from(config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string()));
+    let tantivy_index_dir = PathBuf::from(config.get::<String>("data.tantivy_index_dir").unwrap_or_else(|_| "../dev_data/indexes/tantivy".to_string()));
+    let (engine, _) = search_engine(config, &lancedb_path)?;
+    let limits = (config.get::<usize>("search.default_limit").unwrap_or(10), config.get::<usize>("search.max_limit").unwrap_or(100));
+    let rt = tokio::runtime::Runtime::new()?;
+    let conn = rt.block_on(localdb_vector::table::open_db(&lancedb_path.to_string_lossy()))?;
+    self_test(&engine, &rt, &lancedb_path);
+    let epoch = || rt.block_on(localdb_vector::table::index_epoch(&conn, "documents"));
+    let embedder = engine.embedder_state().clone();
+    let reopen = |raw: bool| {
+        tracing::info!(raw, "Indexes changed; reopening");
+        Ok(open_search_engine(config, &tantivy_index_dir, &lancedb_path, !raw && rewrite_queries(config), embedder.clone())?.0)
+    };
+    let engines = EpochCell::new(&epoch()?, engine);
+    let raw_engines = EpochCell::default();
+    let pages = EpochMap::new(config.get::<usize>("server.cached_pages").unwrap_or(256));
+    let chunks = |doc_id: &str| rt.block_on(localdb_vector::table::document_chunks(&conn, "documents", doc_id));
+    let assets = localdb_core::assets::AssetStore::from_config(config);
+    let progress = progress_dir(config);
+    let keywords = config.get::<bool>("search.multi_query.keywords").unwrap_or(false);
+    let api = Api { engines: &engines, raw_engines: &raw_engines, reopen: &reopen, pages: &pages, epoch: &epoch, chunks: &chunks, assets: assets.as_ref(), progress: &progress, limits, keywords, reject_weight: reject_weight(config) };
+    let listener = std::net::TcpListener::bind(listen)?;
+    let workers = config.get::<usize>("server.workers").unwrap_or(8).max(1);
+    println!("Serving on http://{} ({} workers)", listener.local_addr()?, workers);
+    let (queue, queued) = std::sync::mpsc::sync_channel::<std::net::TcpStream>(workers * 4);
+    let queued = std::sync::Mutex::new(queued);
+    std::thread::scope(|s| {
+        for _ in 0..workers {
+            let (api, queued) = (&api, &queued);
+            s.spawn(move || loop {
+                let Ok(stream) = queued.lock().unwrap_or_else(|e| e.into_inner()).recv() else { return };
+                if let Err(e) = serve_connection(stream, api) { tracing::debug!(error = %e, "connection dropped"); }
+            });
+        }
+        for stream in listener.incoming().filter_map(Result::ok) {
+            if let Err(std::sync::mpsc::TrySendError::Full(mut stream)) = queue.try_send(stream) {
+                let busy = localdb_cli::http::Response::text(503, "busy").with_header("Retry-After", "1");
+                if let Err(e) = busy.write_to(&mut stream, false) { tracing::debug!(error = %e, "connection dropped"); }
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Check the loaded model against the canary collection before serving, and
+/// warn loudly if the model and the vector index do not fit together. Never
+/// stops the server: text search still works.
+#[cfg(feature = "web")]
+fn self_test(engine: &Engine, rt: &tokio::runtime::Runtime, lancedb_path: &Path) {
+    let EmbedderState::Ready(embedder) = engine.embedder_state() else { return };
+    let result = rt.block_on(async {
+        let conn = localdb_vector::table::open_db(&lancedb_path.to_string_lossy()).await?;
+        localdb_vector::canary::self_test(&conn, "documents", embedder.as_ref()).await
+    });
+    match result {
+        Ok(Some(problems)) if problems.is_empty() => println!("✅ Self-test passed ({} canary queries)", localdb_core::canary::QUERIES.len()),
+        Ok(Some(problems)) => {
+            eprintln!("{}", localdb_core::canary::warning(&problems));
+            tracing::warn!(problems = problems.len(), "Startup self-test failed");
+        }
+        Ok(None) => println!("ℹ️  No self-test canary in this index yet; the next full ingest writes one"),
+        Err(e) => eprintln!("⚠️  Startup self-test could not run: {:#}", e),
+    }
+}
+
+#[cfg(feature = "web")]
+fn serve_connection(mut stream: std::net::TcpStream, api: &Api) -> anyhow::Result<()> {
+    use localdb_cli::http::{Request, Response};
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(10)))?;
+    let (response, head_only) = match Request::read(&mut std::io::BufReader::new(stream.try_clone()?)) {
+        Ok(req) if req.method == "GET" && req.path == "/progress" => {
+            tracing::info!(method = %req.method, path = %req.path, "event stream");
+            return progress_events(&mut stream, api.progress);
+        }
+        Ok(req) => {
+            let response = respond(&req, api).unwrap_or_else(|e| {
+                tracing::error!(path = %req.path, error = %format!("{:#}", e), "request failed");
+                Response::text(500, "internal error")
+            });
+            tracing::info!(method = %req.method, path = %req.path, status = response.status, "request");
+            (response, req.method == "HEAD")
+        }
+        Err(e) => (Response::text(400, &e.to_string()), false),
+    };
+    response.write_to(&mut stream, head_only)?;
+    Ok(())
+}
+
+/// How often `/progress` re-reads the job files, how long it stays silent
+/// before a keep-alive comment (which also notices a departed client), and
+/// how long one stream lasts: it holds a worker, so it ends and the browser's
+/// `EventSource` reconnects.
+#[cfg(feature = "web")]
+const PROGRESS_POLL: std::time::Duration = std::time::Duration::from_millis(500);
+#[cfg(feature = "web")]
+const PROGRESS_KEEPALIVE: std::time::Duration = std::time::Duration::from_secs(15);
+#[cfg(feature = "web")]
+const PROGRESS_STREAM_FOR: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// `GET /progress`: a `progress` event for every job in `dir` when connecting
+/// and again whenever its state changes (or it stalls), until the client
+/// disconnects or `PROGRESS_STREAM_FOR` has passed.
+#[cfg(feature = "web")]
+fn progress_events(stream: &mut std::net::TcpStream, dir: &Path) -> anyhow::Result<()> {
+    use localdb_cli::http::{sse_event, write_event_stream_head};
+    use std::io::Write;
+    write_event_stream_head(stream)?;
+    localdb_core::progress::prune(dir, localdb_core::progress::now_ms());
+    let mut sent: std::collections::HashMap<String, (localdb_core::progress::Progress, bool)> = std::collections::HashMap::new();
+    let mut quiet = Instant::now();
+    let opened = Instant::now();
+    while opened.elapsed() < PROGRESS_STREAM_FOR {
+        for p in localdb_core::progress::read_all(dir) {
+            let stalled = p.is_stalled(localdb_core::progress::now_ms());
+            if sent.get(&p.job).is_some_and(|(q, s)| *q == p && *s == stalled) { continue; }
+            let data = serde_json::json!({
+                "job": p.job, "phase": p.phase, "done": p.done, "total": p.total, "percent": p.percent(), "finished": p.finished,
+                "error": p.error, "stalled": stalled, "updated_at": p.updated_at,
+            });
+            stream.write_all(sse_event("progress", &data.to_string()).as_bytes())?;
+            sent.insert(p.job.clone(), (p, stalled));
+            quiet = Instant::now();
+        }
+        if quiet.elapsed() >= PROGRESS_KEEPALIVE { stream.write_all(b": keep-alive\n\n")?; quiet = Instant::now(); }
+        stream.flush()?;
+        std::thread::sleep(PROGRESS_POLL);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "web")]
+fn respond(req: &localdb_cli::http::Request, api: &Api) -> anyhow::Result<localdb_cli::http::Response> {
+    use localdb_cli::http::{self, Encoding, Response};
+    use localdb_cli::proto::{self, Message};
+    if req.method != "GET" && req.method != "HEAD" { return Ok(Response::text(405, "GET only").with_header("Allow", "GET, HEAD")); }
+    let encoding = Encoding::negotiate(req.header("accept-encoding"));
+    let response = match req.path.as_str() {
+        "/" => Response::new(200, "text/html; charset=utf-8", WEB_UI),
+        "/search.proto" => Response::new(200, "text/plain; charset=utf-8", SEARCH_PROTO),
+        path if path.starts_with("/document/") => document(req, api, &path["/document/".len()..], encoding)?,
+        path if path.starts_with("/asset/") => asset(req, api, &path["/asset/".len()..])?,
+        "/search" => {
+            let epoch = (api.epoch)()?;
+            // `raw=1` searches the query as typed, without spelling fixes or word forms.
+            let raw = req.param("raw").is_some_and(|r| r == "1" || r == "true");
+            let engine = if raw { api.raw_engines.get_or_build(&epoch, || (api.reopen)(true))? } else { api.engines.get_or_build(&epoch, || (api.reopen)(false))? };
+            let (engine, (default_k, max_k)) = (engine.as_ref(), api.limits);
+            let q = req.param("q").unwrap_or("");
+            let facet = req.param("facet").filter(|f| !f.is_empty());
+            let k = match req.param("k").map(str::parse::<usize>) {
+                None => default_k,
+                Some(Ok(k)) if k > 0 => k.min(max_k),
+                Some(_) => return Ok(Response::text(400, "k must be a positive integer")),
+            };
+            // Chunks per document, overriding search.max_per_doc (0 lifts the cap).
+            let max_per_doc = match req.param("max_per_doc").filter(|n| !n.is_empty()).map(str::parse::<usize>) {
+                None => None,
+                Some(Ok(n)) => Some(n),
+                Some(Err(_)) => return Ok(Response::text(400, "max_per_doc must be a non-negative integer")),
+            };
+            let lang = req.param("lang").filter(|l| !l.is_empty());
+            // Further phrasings of `q`, searched with it (repeatable).
+            let also: Vec<String> = req.params_named("also").into_iter().map(str::to_string).collect();
+            // Chunk ids the user marked "not like this" this session (repeatable).
+            let rejected: Vec<String> = req.params_named("reject").into_iter().filter(|r| !r.is_empty()).map(str::to_string).collect();
+            let protobuf = req.param("format") == Some("pb") || req.header("accept").is_some_and(|a| a.contains(proto::CONTENT_TYPE));
+            // Each format and content coding is its own representation with its own tag.
+            let format = if protobuf { "pb" } else { "json" };
+            let tag = http::etag(&[q, &also.join("\n"), &rejected.join("\n"), facet.unwrap_or(""), &k.to_string(), &max_per_doc.map(|n| n.to_string()).unwrap_or_default(), lang.unwrap_or(""), if raw { "raw" } else { "" }, engine.fusion_strategy().name(), format, encoding.token().unwrap_or("identity")], &epoch);
+            if http::if_none_match(req.header("if-none-match"), &tag) { return Response::not_modified(&tag).encode(encoding); }
+            if let Some(page) = api.pages.get(&epoch, &tag) { return page.encode(encoding); }
+            // Searches filter by language in both legs, browsing drops other
+            // languages afterwards, and rejections push some hits down, so
+            // fetch more to keep `k`.
+            let depth = if (lang.is_some() && q.trim().is_empty()) || !rejected.is_empty() { FILTER_DEPTH.max(k) } else { k };
+            let mut outcome = if q.trim().is_empty() {
+                localdb_hybrid::QueryOutcome { hits: engine.browse(facet, depth)?, partial: None }
+            } else { engine.query_variants_with(&phrasings(q, &also, api.keywords), depth, QueryOptions { max_per_doc, lang: lang.map(str::to_string), facet: facet.map(str::to_string) })? };
+            if !q.trim().is_empty() { engine.penalize_rejected(&mut outcome.hits, &rejected, api.reject_weight)?; }
+            if let Some(lang) = lang { outcome.hits.retain(|h| h.in_lang(lang)); }
+            outcome.hits.truncate(k);
+            let source = |h: &localdb_core::types::SearchHit| match h.source { localdb_core::types::SourceKind::Text => "text", localdb_core::types::SourceKind::Vector => "vec" };
+            let page = if protobuf {
+                let page = Message::new().string(1, q).string(2, facet.unwrap_or("")).string(3, &epoch).string(4, outcome.partial.as_deref().unwrap_or("")).string(6, lang.unwrap_or(""));
+                let page = outcome.hits.iter().fold(page, |page, h| page.message(5, &Message::new().string(1, &h.id).float(2, h.score).string(3, source(h))
+                    .string(4, h.title.as_deref().unwrap_or("")).string(5, h.author.as_deref().unwrap_or("")).int64(6, h.created_at.unwrap_or(0)).string(7, h.lang.as_deref().unwrap_or(""))
+                    .int64(8, h.span.map_or(0, |s| s.start_offset as i64)).int64(9, h.span.map_or(0, |s| s.end_offset as i64))
+                    .int64(10, h.span.map_or(0, |s| s.start_line as i64)).int64(11, h.span.map_or(0, |s| s.end_line as i64))));
+                Response::new(200, proto::CONTENT_TYPE, page.into_bytes())
+            } else {
+                let hits: Vec<_> = outcome.hits.iter().map(|h| serde_json::json!({ "id": h.id, "score": h.score, "source": source(h), "title": h.title, "author": h.author, "created_at": h.created_at, "lang": h.lang })).collect();
+                Response::json(serde_json::json!({ "query": q, "facet": facet, "lang": lang, "epoch": epoch, "partial": outcome.partial, "hits": hits }).to_string())
+            };
+            if outcome.partial.is_some() { page } else {
+                let page = page.with_etag(&tag);
+                api.pages.insert(&epoch, &tag, page.clone());
+                page
+            }
+        }
+        _ => Response::text(404, "not found"),
+    };
+    response.encode(encoding)
+}
+
+/// `/document/<doc_id>/content` (text rebuilt from the stored chunks; one
+/// byte range via `Range`, `?range=a-b` or `?chunk=<chunk id>`),
+/// `/document/<doc_id>/chunks` (each chunk's byte span, for anchoring) and
+/// `/document/<doc_id>/assets` (its images, each after a chunk id).
+#[cfg(feature = "web")]
+fn document(req: &localdb_cli::http::Request, api: &Api, target: &str, encoding: localdb_cli::http::Encoding) -> anyhow::Result<localdb_cli::http::Response> {
+    use localdb_cli::document::Document;
+    use localdb_cli::http::{self, ByteRange, Response};
+    let Some((doc_id, view)) = target.rsplit_once('/').filter(|(_, v)| ["content", "chunks", "assets"].contains(v)) else { return Ok(Response::text(404, "not found")) };
+    let epoch = (api.epoch)()?;
+    let tag = http::etag(&["document", doc_id, view, encoding.token().unwrap_or("identity")], &epoch);
+    if http::if_none_match(req.header("if-none-match"), &tag) { return Ok(Response::not_modified(&tag)); }
+    if view == "assets" {
+        let assets = match api.assets { Some(store) => store.manifest(doc_id)?, None => Vec::new() };
+        let assets: Vec<_> = assets.iter().map(|a| serde_json::json!({
+            "name": a.name, "alt": a.alt, "media_type": a.media_type, "after_chunk": a.after_chunk, "url": format!("/asset/{}", a.hash),
+        })).collect();
+        return Ok(Response::json(serde_json::json!({ "doc_id": doc_id, "assets": assets }).to_string()).with_etag(&tag));
+    }
+    let chunks = (api.chunks)(doc_id)?;
+    let Some(first) = chunks.first() else { return Ok(Response::text(404, &format!("no document '{}'", doc_id))) };
+    let doc = Document::assemble(&chunks);
+    if view == "chunks" {
+        let spans: Vec<_> = doc.spans.iter().map(|s| serde_json::json!({ "id": s.id, "index": s.index, "start": s.start, "end": s.end })).collect();
+        let body = serde_json::json!({ "doc_id": doc_id, "doc_path": first.doc_path, "length": doc.text.len(), "epoch": epoch, "chunks": spans });
+        return Ok(Response::json(body.to_string()).with_etag(&tag));
+    }
+    let range = match (req.header("range"), req.param("range"), req.param("chunk")) {
+        (Some(header), _, _) => ByteRange::parse(Some(header), doc.text.len()),
+        (None, Some(range), _) => ByteRange::parse(Some(&format!("bytes={}", range.trim_start_matches("bytes="))), doc.text.len()),
+        (None, None, Some(chunk)) => match doc.span(chunk) {
+            Some(span) => ByteRange::Partial(span.start, span.end),
+            None => return Ok(Response::text(404, &format!("no chunk '{}' in '{}'", chunk, doc_id))),
+        },
+        (None, None, None) => ByteRange::Full,
+    };
+    Ok(Response::new(200, "text/plain; charset=utf-8", doc.text).with_etag(&tag).with_range(range))
+}
+
+/// `/asset/<hash>`: a stored image. Content-addressed, so cacheable forever;
+/// sandboxed so a scripted SVG from a book cannot act as the UI's origin.
+#[cfg(feature = "web")]
+fn asset(req: &localdb_cli::http::Request, api: &Api, hash: &str) -> anyhow::Result<localdb_cli::http::Response> {
+    use localdb_cli::http::{self, Response};
+    let Some(path) = api.assets.and_then(|store| store.get(hash)) else { return Ok(Response::text(404, "no such asset")) };
+    let tag = format!("\"{}\"", hash);
+    if http::if_none_match(req.header("if-none-match"), &tag) { return Ok(Response::not_modified(&tag)); }
+    let bytes = std::fs::read(path)?;
+    Ok(Response::new(200, localdb_core::assets::sniff(&bytes), bytes).with_header("ETag", &tag).with_header("Cache-Control", "public, max-age=31536000, immutable")
+        .with_header("Content-Security-Policy", "sandbox").with_header("X-Content-Type-Options", "nosniff"))
 }
 
-fn main() -> anyhow::Result<()> {
+/// Exits with the code of the failure's `ErrorClass` (see `localdb_cli::exit`);
+/// `--json-errors` prints the error as JSON.
+fn main() -> std::process::ExitCode {
     // Initialize logging once; respect RUST_LOG if set
     {
         use tracing_subscriber::prelude::*;
@@ -25,40 +1712,371 @@ fn main() -> anyhow::Result<()> {
             .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
         tracing_subscriber::registry().with(filter).with(fmt).init();
     }
-    let config = Config::load().map_err(|e| { eprintln!("Error loading config: {}", e); e })?;
-    let (cmd, args) = parse_args();
-    match cmd.as_str() {
+    // Decrypted index copies must not outlive an interrupted command.
+    if let Err(e) = ctrlc::set_handler(|| { remove_unsealed(); std::process::exit(130); }) { tracing::warn!(error = %e, "Could not install the Ctrl-C handler"); }
+    let mut args: Vec<String> = env::args().collect();
+    let json_errors = take_flag(&mut args, "--json-errors");
+    match parse_args(args).and_then(|(cmd, args)| dispatch(&cmd, args)) {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            if json_errors { eprintln!("{}", exit::json(&e)); } else { eprintln!("Error: {:#}", e); }
+            std::process::ExitCode::from(ErrorClass::of(&e).code())
+        }
+    }
+}
+
+fn dispatch(cmd: &str, mut args: Vec<String>) -> anyhow::Result<()> {
+    let config = Config::load().context(ErrorClass::Config)?;
+    // Commands that write the indexes wait for another writer instead of failing.
+    let wait = take_flag(&mut args, "--wait");
+    if let Ok(device) = config.get::<String>("embedding.device") {
+        localdb_embed::prefer_device(localdb_embed::DeviceChoice::parse(&device).context(ErrorClass::Config)?);
+    }
+    if let Ok(model) = config.get::<String>("embedding.model") {
+        localdb_embed::ModelRegistry::current().get(&model).context(ErrorClass::Config)?;
+        localdb_embed::prefer_model(&model);
+    }
+    if let Ok(dim) = config.get::<usize>("embedding.truncate_dim") {
+        localdb_embed::matryoshka::check_truncation(&localdb_embed::model_spec()?, dim).context(ErrorClass::Config)?;
+        localdb_embed::prefer_output_dim(Some(dim));
+    }
+    if let Ok(max_len) = config.get::<usize>("embedding.max_len") { localdb_embed::prefer_max_len(Some(max_len)); }
+    if let Ok(pooling) = config.get::<String>("embedding.pooling") {
+        localdb_embed::prefer_pooling(Some(localdb_embed::Pooling::parse(&pooling).context(ErrorClass::Config)?));
+    }
+    if config.get::<bool>("embedding.sliding_window").unwrap_or(false) {
+        localdb_embed::prefer_sliding_window(Some(config.get::<usize>("embedding.window_overlap").unwrap_or(localdb_embed::window::DEFAULT_OVERLAP)));
+    }
+    match cmd {
         "ingest" => {
-            let data_dir = args.first().map(PathBuf::from).unwrap_or_else(|| {
-                let dir: String = config.get("data.raw_txt_dir").unwrap_or_else(|_| "../dev_data/txt".to_string()); PathBuf::from(dir)
-            });
-            tracing::info!(path = %data_dir.display(), "Ingesting");
-            let data_processor = DataProcessor::new();
-            let chunks = data_processor.process_directory(&data_dir)?;
-            let tantivy_index_dir: String = config.get("data.tantivy_index_dir").unwrap_or_else(|_| "../dev_data/indexes/tantivy".to_string());
-            let lancedb_path = PathBuf::from(config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string()));
-            let text = TantivyIndexer::new(PathBuf::from(&tantivy_index_dir))?;
-            let vector = tokio::runtime::Runtime::new()?.block_on(async { LanceDbIndexer::new(&lancedb_path, "documents").await })?;
-            let embedder = get_default_embedder()?;
-            let engine = HybridSearchEngine::new(text, vector, embedder);
-            engine.index(&chunks)?;
-            tracing::info!(count = chunks.len(), "Ingest complete");
+            // Repeatable; they scope every root after its configured patterns.
+            let patterns: Vec<String> = take_values(&mut args, "--include").into_iter().chain(take_values(&mut args, "--exclude").into_iter().map(|g| format!("!{}", g))).collect();
+            let profile = args.iter().any(|a| a == "--profile");
+            let full = args.iter().any(|a| a == "--full");
+            let watching = args.iter().any(|a| a == "--watch");
+            let args: Vec<String> = args.into_iter().filter(|a| !matches!(a.as_str(), "--profile" | "--full" | "--watch")).collect();
+            if profile { localdb_core::profile::enable(); }
+            let started = Instant::now();
+            // An explicit directory overrides the configured roots.
+            let mut roots = match args.first() {
+                Some(dir) => vec![DataRoot { patterns: config.get("data.patterns").unwrap_or_default(), extensions: single_root_extensions(&config), ..DataRoot::single(dir) }],
+                None => load_roots(&config, "../dev_data/txt"),
+            };
+            for r in &mut roots { r.patterns.extend(patterns.iter().cloned()); }
+            for r in &roots { tracing::info!(path = %r.path.display(), root = %r.name(), "Ingesting"); }
+            let encrypt = config.get::<bool>("security.encrypt_indexes").unwrap_or(false);
+            let writer = writing(&config, "ingest", wait)?;
+            let lock = IndexLock::acquire(&config, encrypt)?;
+            let embedder = EmbedderState::from_result(get_default_embedder())?;
+            let failed = tracked(lock.config(), "ingest", || ingest(lock.config(), &roots, full, &embedder))?;
+            if profile { print!("{}", ProfileReport::snapshot(started.elapsed()).render()); }
+            let indexed = if watching { indexed_files(lock.config(), &roots)? } else { HashSet::new() };
+            let passphrase = lock.passphrase().map(str::to_string);
+            lock.reseal()?;
+            drop(writer);
+            if watching { watch(&config, &roots, &embedder, encrypt, passphrase, indexed)?; }
+            if !failed.is_empty() {
+                return Err(ErrorClass::PartialIngest.error(format!("{} files could not be read: {}", failed.len(), failed.join(", "))));
+            }
+            // Everything is searchable as text; vectors wait for the model.
+            if let EmbedderState::EmbedderUnavailable(reason) = &embedder {
+                return Err(ErrorClass::ModelMissing.error(format!("{}; indexed for text search only", reason)));
+            }
         }
         "query" => {
-            let query_text = args.first().cloned().unwrap_or_else(|| {
-                eprintln!("Usage: localdb-cli query \"<query>\""); std::process::exit(1)
-            });
-            let tantivy_index_dir: String = config.get("data.tantivy_index_dir").unwrap_or_else(|_| "../dev_data/indexes/tantivy".to_string());
+            // `query ""` (or no argument) browses the newest documents.
+            let also = take_values(&mut args, "--also");
+            let raw = args.iter().any(|a| a == "--raw");
+            let flag = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).cloned();
+            let (facet, speaker, lang) = (flag("--facet"), flag("--speaker"), flag("--lang"));
+            let max_per_doc = flag("--max-per-doc").map(|n| n.parse::<usize>().map_err(|_| ErrorClass::Usage.error(format!("--max-per-doc takes a number of chunks, got '{}'", n)))).transpose()?;
+            let query_text = args.first().filter(|a| !a.starts_with("--")).cloned().unwrap_or_default();
+            let lock = IndexLock::open(&config)?;
+            let config = lock.config();
             let lancedb_path = PathBuf::from(config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string()));
-            let text = localdb_text::TantivySearchEngine::new(PathBuf::from(&tantivy_index_dir))?;
-            let vector = tokio::runtime::Runtime::new()?.block_on(async { localdb_vector::LanceDbIndexer::new(&lancedb_path, "documents").await })?;
-            let embedder = get_default_embedder()?;
-            let engine = HybridSearchEngine::new(text, vector, embedder);
-            let hits = engine.query(&query_text, 10)?;
-            println!("Top hits for '{}':", query_text);
-            for (i, h) in hits.iter().enumerate() { println!("{i:>2}. {} [{}] score={:.3}", h.id, match h.source { localdb_core::types::SourceKind::Text => "text", localdb_core::types::SourceKind::Vector => "vec" }, h.score); }
-        }
-        _ => { eprintln!("Unknown command: {}", cmd); std::process::exit(1); }
+            let tantivy_index_dir: String = config.get("data.tantivy_index_dir").unwrap_or_else(|_| "../dev_data/indexes/tantivy".to_string());
+            let (engine, aliases) = search_engine_at(&config, Path::new(&tantivy_index_dir), &lancedb_path, !raw && rewrite_queries(&config))?;
+            // Show what is actually searched; the vector leg gets the spelling fixes too.
+            let rewrite = engine.text().rewrite(&query_text)?;
+            if rewrite.is_changed() {
+                println!("🔁 Searching for: {} (--raw to search as typed)", rewrite.expanded);
+                tracing::info!(query = %rewrite.original, rewritten = %rewrite.expanded, "Rewrote query");
+            }
+            let query_text = if rewrite.is_changed() { rewrite.corrected } else { query_text };
+            let now_ms = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or(0);
+            let rejected = match feedback_log(&config) { Some(log) => feedback::session_rejections(&log.events()?, now_ms), None => Vec::new() };
+            // The speaker filter (and the language filter when browsing) drops hits
+            // after retrieval, and rejections push some down, so fetch more to keep ten.
+            let k = if speaker.is_some() || (lang.is_some() && query_text.trim().is_empty()) || !rejected.is_empty() { FILTER_DEPTH } else { 10 };
+            let outcome = if query_text.trim().is_empty() {
+                localdb_hybrid::QueryOutcome { hits: engine.browse(facet.as_deref(), k)?, partial: None }
+            } else { engine.query_variants_with(&phrasings(&query_text, &also, config.get::<bool>("search.multi_query.keywords").unwrap_or(false)), k, QueryOptions { max_per_doc, lang: lang.clone(), facet: facet.clone() })? };
+            if let Some(reason) = &outcome.partial { tracing::warn!(%reason, query = %query_text, "Partial results: text leg only"); }
+            let mut hits = outcome.hits;
+            let scratch = if query_text.trim().is_empty() { Vec::new() } else { scratch_pad(&config, lock.passphrase()).entries(now_ms)? };
+            if !scratch.is_empty() {
+                let (text, vectors) = localdb_core::scratch::collection(&scratch);
+                let (strategy, weights) = fusion_config(&config)?;
+                let pad = HybridSearchEngine::from_state(text, vectors, engine.embedder_state().clone()).with_fusion(strategy, weights)
+                    .with_preprocessor(Preprocessor::from_config(&config, "documents").context(ErrorClass::Config)?);
+                // Scores from two separately fused engines do not compare; fuse the rankings.
+                let pad_hits = pad.query_variants(&phrasings(&query_text, &also, config.get::<bool>("search.multi_query.keywords").unwrap_or(false)), k)?.hits;
+                hits = localdb_hybrid::rank_fusion(vec![hits, pad_hits]);
+                hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+                println!("📝 Searching {} scratch documents too", scratch.len());
+            }
+            if !query_text.trim().is_empty() && !rejected.is_empty() {
+                engine.penalize_rejected(&mut hits, &rejected, reject_weight(&config))?;
+                println!("👎 Pushing down results like the {} rejected this session", rejected.len());
+            }
+            let mut catalog = catalog_by_doc(&lancedb_path);
+            for r in catalog.values_mut() { if let Some(c) = aliases.rename_stored(&r.category) { r.category = c; } }
+            if let Some(expr) = config.get::<String>("search.rerank.expr").ok().filter(|e| !e.trim().is_empty()).filter(|_| !query_text.trim().is_empty()) {
+                let expr = localdb_core::rerank::RerankExpr::parse(&expr)?;
+                expr.apply(&mut hits, |h, rank| {
+                    let record = catalog.get(doc_id_of(&h.id));
+                    localdb_core::rerank::RerankContext {
+                        score: h.score, rank, source: h.source,
+                        age_years: record.map(|r| (now_ms - r.modified_at).max(0) as f32 / (365.25 * 86_400_000.0)).unwrap_or(0.0),
+                        category: record.map(|r| r.category.as_str()).unwrap_or(""),
+                        doc_path: record.map(|r| r.doc_path.as_str()).unwrap_or(""),
+                    }
+                });
+            }
+            let moments = hit_moments(&hits);
+            if let Some(speaker) = &speaker {
+                hits.retain(|h| moments.get(&h.id).and_then(|m| m.speaker.as_deref()).is_some_and(|s| s.eq_ignore_ascii_case(speaker)));
+            }
+            if let Some(lang) = &lang { hits.retain(|h| h.in_lang(lang)); }
+            hits.truncate(10);
+            if query_text.trim().is_empty() { println!("Browsing {}:", facet.as_deref().unwrap_or("all documents")); } else { println!("Top hits for '{}':", query_text); }
+            if let Some(reason) = &outcome.partial { println!("(partial results: {}; text matches only)", reason); }
+            for (i, h) in hits.iter().enumerate() {
+                println!("{i:>2}. {} [{}] score={:.3}", h.id, match h.source { localdb_core::types::SourceKind::Text => "text", localdb_core::types::SourceKind::Vector => "vec" }, h.score);
+                if let Some(r) = catalog.get(doc_id_of(&h.id)) {
+                    if !r.summary.is_empty() { println!("    📄 {}", r.summary); }
+                    if let Some(tags) = r.meta.get("tags") { println!("    🏷️  {}", tags.replace(',', ", ")); }
+                }
+                if let Some(span) = &h.span { println!("    📍 {} (bytes {}..{})", span.lines(), span.start_offset, span.end_offset); }
+                if let Some(e) = scratch.iter().find(|e| e.chunks.first().is_some_and(|c| c.doc_id == doc_id_of(&h.id))) { println!("    📝 scratch: {}", e.name); }
+                if let Some(also) = h.meta.get(localdb_core::dedupe::ALSO_IN_KEY) { println!("    🪞 also in {}", also); }
+                if let Some(m) = moments.get(&h.id) { println!("    🎙️  {} (localdb-cli play {})", m.label(), h.id); }
+            }
+            if let Some(log) = feedback_log(&config).filter(|_| !query_text.trim().is_empty() && !hits.is_empty()) {
+                let shown = hits.iter().map(|h| Shown { id: h.id.clone(), source: h.source }).collect();
+                let query_id = log.record_query(&query_text, engine.fusion_strategy().name(), shown)?;
+                println!("Query id {} (record use: localdb-cli feedback {} <rank> open|copy|reject)", query_id, query_id);
+            }
+            drop(engine);
+            lock.reseal()?;
+        }
+        "feedback" => {
+            let (Some(query_id), Some(rank)) = (args.first(), args.get(1).and_then(|r| r.parse::<usize>().ok()).filter(|r| *r > 0)) else {
+                return Err(ErrorClass::Usage.error("localdb-cli feedback <query_id> <rank> [open|copy|reject]"))
+            };
+            let action = match args.get(2).map(String::as_str) {
+                None | Some("open") => Some(feedback::Action::Open),
+                Some("copy") => Some(feedback::Action::Copy),
+                // "Not like this": later queries this session push similar results down.
+                Some("reject") => None,
+                Some(other) => return Err(ErrorClass::Usage.error(format!("unknown action '{}' (expected open, copy or reject)", other))),
+            };
+            let log = feedback_log(&config).ok_or_else(|| anyhow::anyhow!("feedback is disabled; set search.feedback.enabled = true"))?;
+            match action { Some(action) => log.record_action(query_id, rank, action)?, None => log.record_reject(query_id, rank)? }
+        }
+        "scratch" => scratch(&config, &args)?,
+        "chunk-preview" => {
+            let Some(file) = args.first() else { return Err(ErrorClass::Usage.error("localdb-cli chunk-preview <file>")) };
+            chunk_preview(&config, Path::new(file))?;
+        }
+        "tune" => tune(&config, args.iter().any(|a| a == "--dry-run"))?,
+        "bench-embed" => {
+            let n = args.iter().position(|a| a == "--n").and_then(|i| args.get(i + 1)).and_then(|v| v.parse::<usize>().ok()).unwrap_or(1000);
+            bench_embed(&config, n, args.iter().any(|a| a == "--dry-run"))?;
+        }
+        "replay" => {
+            let flag = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).cloned();
+            let (Some(text), Some(vector)) = (flag("--text"), flag("--vector")) else {
+                return Err(ErrorClass::Usage.error("localdb-cli replay --text <tantivy dir> --vector <lancedb dir> [--k 10] [--limit N]"))
+            };
+            let number = |name: &str| flag(name).map(|n| n.parse::<usize>().map_err(|_| ErrorClass::Usage.error(format!("{} expects a number, got '{}'", name, n)))).transpose();
+            let (k, limit) = (number("--k")?.unwrap_or(10).max(1), number("--limit")?);
+            let lock = IndexLock::open(&config)?;
+            let config = lock.config();
+            replay(&config, Path::new(&text), Path::new(&vector), k, limit)?;
+            lock.reseal()?;
+        }
+        "calibrate" => {
+            let dry_run = args.iter().any(|a| a == "--dry-run");
+            let _writer = if dry_run { None } else { Some(writing(&config, "calibrate", wait)?) };
+            let lock = IndexLock::open(&config)?;
+            let config = lock.config();
+            calibrate(&config, dry_run, args.iter().any(|a| a == "--reset"))?;
+            lock.reseal()?;
+        }
+        "judge" => {
+            let flag = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).cloned();
+            let Some(query_text) = args.first().filter(|a| !a.starts_with("--")).cloned() else {
+                return Err(ErrorClass::Usage.error("localdb-cli judge \"<query>\" [--a max_score] [--b rrf]"))
+            };
+            let strategy = |name: &str, default: FusionStrategy| match flag(name) {
+                Some(n) => FusionStrategy::parse(&n).ok_or_else(|| ErrorClass::Usage.error(format!("unknown strategy '{}' (expected max_score, weighted_sum or rrf)", n))),
+                None => Ok(default),
+            };
+            let (a, b) = (strategy("--a", FusionStrategy::MaxScore)?, strategy("--b", FusionStrategy::Rrf)?);
+            let lock = IndexLock::open(&config)?;
+            let config = lock.config();
+            judge(&config, &query_text, a, b)?;
+            lock.reseal()?;
+        }
+        "relocate" => {
+            let flag = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).map(PathBuf::from);
+            let Some(new_root) = flag("--data-root") else {
+                return Err(ErrorClass::Usage.error("localdb-cli relocate --data-root /new/path [--root name] [--from /old/path] [--force]"))
+            };
+            let root_name = args.iter().position(|a| a == "--root").and_then(|i| args.get(i + 1)).cloned().unwrap_or_default();
+            let _writer = writing(&config, "relocate", wait)?;
+            let lock = IndexLock::open(&config)?;
+            let config = lock.config();
+            relocate(&config, &root_name, &new_root, flag("--from").as_deref(), args.iter().any(|a| a == "--force"))?;
+            lock.reseal()?;
+        }
+        "scrub" => {
+            let lock = IndexLock::open(&config)?;
+            let config = lock.config();
+            let lancedb_path = config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string());
+            let clean = scrub(&lancedb_path)?;
+            lock.reseal()?;
+            if !clean { return Err(ErrorClass::Integrity.error("some cataloged files are corrupt or unreadable")); }
+        }
+        "facet" => {
+            let _writer = if args.first().is_some_and(|a| a == "rename") { Some(writing(&config, "facet rename", wait)?) } else { None };
+            let lock = IndexLock::open(&config)?;
+            let config = lock.config();
+            facet(&config, &args)?;
+            lock.reseal()?;
+        }
+        "play" => {
+            let Some(chunk_id) = args.first() else { return Err(ErrorClass::Usage.error("localdb-cli play <chunk_id>")) };
+            let lock = IndexLock::open(&config)?;
+            let config = lock.config();
+            play(&config, chunk_id)?;
+            lock.reseal()?;
+        }
+        "open" => {
+            let Some(target) = args.first() else { return Err(ErrorClass::Usage.error("localdb-cli open <doc_id|doc_path>")) };
+            let lock = IndexLock::open(&config)?;
+            let config = lock.config();
+            open(&config, target)?;
+            lock.reseal()?;
+        }
+        "verify" => {
+            let answer = match args.first() {
+                Some(path) => std::fs::read_to_string(path).with_context(|| format!("reading {}", path))?,
+                None => std::io::read_to_string(std::io::stdin())?,
+            };
+            let lock = IndexLock::open(&config)?;
+            let config = lock.config();
+            let supported = verify(&config, &answer)?;
+            lock.reseal()?;
+            if !supported { return Err(ErrorClass::UnsupportedClaims.error("some claims are not backed by the chunks they cite")); }
+        }
+        "manifest" => {
+            let lock = IndexLock::open(&config)?;
+            let config = lock.config();
+            let lancedb_path = config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string());
+            println!("{}", local_manifest(&lancedb_path)?.encode());
+            lock.reseal()?;
+        }
+        "embeddings" => match args.first().map(String::as_str) {
+            Some("export") => {
+                let doc_paths: Vec<String> = std::io::read_to_string(std::io::stdin())?.lines().filter(|l| !l.is_empty()).map(str::to_string).collect();
+                let lock = IndexLock::open(&config)?;
+                let config = lock.config();
+                println!("{}", export_embeddings(&config, &doc_paths)?);
+                lock.reseal()?;
+            }
+            Some("import") => {
+                let lines = std::io::read_to_string(std::io::stdin())?;
+                let _writer = writing(&config, "embeddings import", wait)?;
+                let lock = IndexLock::open(&config)?;
+                let config = lock.config();
+                let added = import_embeddings(&config, &lines)?;
+                lock.reseal()?;
+                eprintln!("Imported {} cached vectors", added);
+            }
+            _ => return Err(ErrorClass::Usage.error("localdb-cli embeddings <export|import> (reads doc paths or exported vectors on stdin)")),
+        },
+        "sync" => {
+            let Some(remote) = args.first().filter(|a| !a.starts_with("--")) else {
+                return Err(ErrorClass::Usage.error("localdb-cli sync <[user@]host> [--dry-run]"))
+            };
+            let lock = IndexLock::open(&config)?;
+            let config = lock.config();
+            sync(&config, remote, args.iter().any(|a| a == "--dry-run"))?;
+            lock.reseal()?;
+        }
+        "stats" => {
+            let lock = IndexLock::open(&config)?;
+            let config = lock.config();
+            let top = args.iter().position(|a| a == "--top").and_then(|i| args.get(i + 1)).and_then(|v| v.parse::<usize>().ok()).unwrap_or(10);
+            stats(&config, args.iter().any(|a| a == "--index"), args.iter().any(|a| a == "--facets").then_some(top), args.iter().any(|a| a == "--corpus").then_some(top))?;
+            lock.reseal()?;
+        }
+        #[cfg(not(feature = "web"))]
+        "serve" => anyhow::bail!("this build has no web server; rebuild with `--features server`"),
+        #[cfg(feature = "web")]
+        "serve" => {
+            let listen = args.iter().position(|a| a == "--listen").and_then(|i| args.get(i + 1)).cloned()
+                .unwrap_or_else(|| config.get::<String>("server.listen").unwrap_or_else(|_| "127.0.0.1:8080".to_string()));
+            // Serving writes nothing: sealed indexes are read from RAM scratch
+            // copies, which the Ctrl-C/SIGTERM handler deletes, so there is
+            // nothing to reseal on the way out.
+            let lock = IndexLock::open(&config)?;
+            serve(lock.config(), &listen)?;
+        }
+        "gc" => {
+            let dry_run = args.iter().any(|a| a == "--dry-run");
+            let _writer = if dry_run { None } else { Some(writing(&config, "gc", wait)?) };
+            let lock = IndexLock::open(&config)?;
+            let config = lock.config();
+            gc(&config, dry_run)?;
+            lock.reseal()?;
+        }
+        "maintain" => {
+            let _writer = writing(&config, "maintain", wait)?;
+            let lock = IndexLock::open(&config)?;
+            let config = lock.config();
+            maintain(&config)?;
+            lock.reseal()?;
+        }
+        "lock" => {
+            let _writer = writing(&config, "lock", wait)?;
+            let passphrase = crypt::read_new_passphrase("New index passphrase: ")?;
+            for d in index_dirs(&config).iter().filter(|d| d.is_dir() && !crypt::is_sealed(d)) {
+                println!("🔒 Sealed {} files in {}", crypt::seal_dir(d, &passphrase)?, d.display());
+            }
+        }
+        "unlock" => {
+            let _writer = writing(&config, "unlock", wait)?;
+            let passphrase = crypt::read_passphrase("Index passphrase: ")?;
+            for d in index_dirs(&config).iter().filter(|d| crypt::is_sealed(d)) {
+                println!("🔓 Restored {} files in {}", crypt::unseal_dir(d, &passphrase)?, d.display());
+            }
+        }
+        "migrate-ids" => {
+            let _writer = writing(&config, "migrate-ids", wait)?;
+            let lock = IndexLock::open(&config)?;
+            let config = lock.config();
+            let lancedb_path = config.get::<String>("data.lancedb_index_dir").unwrap_or_else(|_| "../dev_data/indexes/lancedb".to_string());
+            let renamed = tokio::runtime::Runtime::new()?.block_on(async {
+                let conn = localdb_vector::table::open_db(&lancedb_path).await?;
+                localdb_vector::migrate::migrate_chunk_ids(&conn, "documents", "embeddings").await
+            })?;
+            lock.reseal()?;
+            println!("Migrated {} chunk ids to content-based ids", renamed);
+        }
+        _ => return Err(ErrorClass::Usage.error(format!("unknown command '{}'", cmd))),
     }
     Ok(())
 }
@@ -18,6 +18,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("\n🔍 Found {} results for: \"{}\"", results.len(), query_text);
     for (i, result) in results.iter().enumerate() {
         println!("\n  {}. score={:.4}  id={}  category={}  path={}", i + 1, result.score, result.id, result.category, result.path);
+        if let Some(offline) = &result.offline { println!("     📴 {} (not available to open)", offline); }
         println!("     📝 Context: {}", result.snippet);
     }
     println!("\n📊 Facet counts:");
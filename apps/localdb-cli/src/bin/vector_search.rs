@@ -27,7 +27,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("\n🔍 Found {} results for: \"{}\"", results.len(), query_text);
     for (i, result) in results.iter().enumerate() {
         println!("\n  {}. score={:.4}  id={}  category={}  path={}", i + 1, result.score, result.id, result.category, result.path);
-        println!("     📝 Content: {}", result.content);
+        if let Some(offline) = &result.offline { println!("     📴 {} (not available to open)", offline); }
+        println!("     📝 Content: {}", localdb_core::answer::emphasize_ansi(&result.content, query_text));
     }
     Ok(())
 }
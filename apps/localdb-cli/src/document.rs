@@ -0,0 +1,66 @@
+//! Document text for the `serve` viewer, rebuilt from stored chunks.
+//!
+//! Every source kind (text, EPUB, ZIM, OCR'd scans, JSON Lines) ends up as
+//! chunks, so the viewer reads the document back from them instead of from
+//! the original file. Chunks are joined in `chunk_index` order with blank
+//! lines between paragraphs; the word overlap between consecutive windows of
+//! a split paragraph is written once. `spans` gives each chunk's byte range
+//! in the result, which is what `Range` requests anchor to.
+
+use localdb_core::types::DocumentChunk;
+
+/// Shortest repeated word run treated as split-paragraph overlap rather than
+/// coincidence.
+pub const MIN_OVERLAP_WORDS: usize = 8;
+
+/// Where one chunk sits in the assembled text (`start..end`, bytes).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkSpan {
+    pub id: String,
+    pub index: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Document {
+    pub text: String,
+    pub spans: Vec<ChunkSpan>,
+}
+
+impl Document {
+    /// Join `chunks` (any order; sorted by `chunk_index`).
+    pub fn assemble(chunks: &[DocumentChunk]) -> Self {
+        let mut ordered: Vec<&DocumentChunk> = chunks.iter().collect();
+        ordered.sort_by_key(|c| c.chunk_index);
+        let mut doc = Self::default();
+        let mut previous: Option<&str> = None;
+        for chunk in ordered {
+            let content = chunk.content.trim();
+            let (start, skip) = match previous.map(|p| overlap(p, content)) {
+                None => (0, 0),
+                // Continuation of a split paragraph: the span starts at the
+                // shared words, which are already written.
+                Some(Some((prev_suffix, next_prefix))) => (doc.text.len() - prev_suffix, next_prefix),
+                Some(None) => { doc.text.push_str("\n\n"); (doc.text.len(), 0) }
+            };
+            doc.text.push_str(&content[skip..]);
+            doc.spans.push(ChunkSpan { id: chunk.id.clone(), index: chunk.chunk_index, start, end: doc.text.len() });
+            previous = Some(content);
+        }
+        doc
+    }
+
+    pub fn span(&self, chunk_id: &str) -> Option<&ChunkSpan> { self.spans.iter().find(|s| s.id == chunk_id) }
+}
+
+/// When `next` opens with the last `MIN_OVERLAP_WORDS` or more words of
+/// `prev`: the byte length of that run at the end of `prev` and at the start
+/// of `next`.
+fn overlap(prev: &str, next: &str) -> Option<(usize, usize)> {
+    let tail: Vec<&str> = prev.split_whitespace().collect();
+    let head: Vec<&str> = next.split_whitespace().collect();
+    let k = (MIN_OVERLAP_WORDS..=tail.len().min(head.len())).rev().find(|&k| tail[tail.len() - k..] == head[..k])?;
+    let offset = |word: &str, within: &str| word.as_ptr() as usize - within.as_ptr() as usize;
+    Some((prev.len() - offset(tail[tail.len() - k], prev), offset(head[k - 1], next) + head[k - 1].len()))
+}
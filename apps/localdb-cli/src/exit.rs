@@ -0,0 +1,111 @@
+//! Exit codes and error output of `localdb-cli`.
+//!
+//! Every failure belongs to an `ErrorClass` with a stable exit code, so shell
+//! scripts and the maintenance daemon can tell a broken config from a missing
+//! index or a model that is not installed without parsing messages. Errors
+//! are classed where they arise, by attaching the class as `anyhow` context
+//! (`ErrorClass::error`, `.context(ErrorClass::Config)`); core errors
+//! (`InvalidConfig`, `EmbedderUnavailable`, `IndexBusy`) are classed by kind.
+//! Anything else is a plain `Failure`. With `--json-errors` the error is
+//! written to stderr as one JSON object instead of text (`json`).
+
+use std::fmt;
+
+use localdb_core::error::Error as CoreError;
+
+/// Why a command failed. Codes never change meaning once released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Anything not classed below (I/O, index errors, bugs): exit 1.
+    Failure,
+    /// `scrub` found corrupt or unreadable files: exit 2.
+    Integrity,
+    /// `config.toml` could not be loaded or a setting is invalid: exit 3.
+    Config,
+    /// The text or vector index does not exist yet (run `ingest`): exit 4.
+    MissingIndex,
+    /// The embedding model is not installed: exit 5. Ingest still indexes
+    /// the chunks for text search; their vectors are pending.
+    ModelMissing,
+    /// Ingest finished, but some files could not be read: exit 6.
+    PartialIngest,
+    /// `verify` flagged claims of an answer its citations do not back: exit 7.
+    UnsupportedClaims,
+    /// Another process is writing the indexes (retry, or pass `--wait`): exit 8.
+    Busy,
+    /// Unknown command or bad arguments: exit 64 (`EX_USAGE` of sysexits.h).
+    Usage,
+}
+
+impl ErrorClass {
+    pub const ALL: [ErrorClass; 9] = [Self::Failure, Self::Integrity, Self::Config, Self::MissingIndex, Self::ModelMissing, Self::PartialIngest, Self::UnsupportedClaims, Self::Busy, Self::Usage];
+
+    pub fn code(self) -> u8 {
+        match self {
+            Self::Failure => 1,
+            Self::Integrity => 2,
+            Self::Config => 3,
+            Self::MissingIndex => 4,
+            Self::ModelMissing => 5,
+            Self::PartialIngest => 6,
+            Self::UnsupportedClaims => 7,
+            Self::Busy => 8,
+            Self::Usage => 64,
+        }
+    }
+
+    /// Name in `--json-errors` output.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Failure => "failure",
+            Self::Integrity => "integrity",
+            Self::Config => "config",
+            Self::MissingIndex => "missing_index",
+            Self::ModelMissing => "model_missing",
+            Self::PartialIngest => "partial_ingest",
+            Self::UnsupportedClaims => "unsupported_claims",
+            Self::Busy => "busy",
+            Self::Usage => "usage",
+        }
+    }
+
+    /// An error of this class saying `message`.
+    pub fn error(self, message: impl fmt::Display + fmt::Debug + Send + Sync + 'static) -> anyhow::Error {
+        anyhow::Error::msg(message).context(self)
+    }
+
+    /// The class attached to `err` (the outermost, if several), else the
+    /// class of the first core error in its chain, else `Failure`.
+    pub fn of(err: &anyhow::Error) -> Self {
+        if let Some(class) = err.downcast_ref::<ErrorClass>() { return *class; }
+        err.chain().find_map(|e| match e.downcast_ref::<CoreError>() {
+            Some(CoreError::InvalidConfig(_)) => Some(Self::Config),
+            Some(CoreError::EmbedderUnavailable(_)) => Some(Self::ModelMissing),
+            Some(CoreError::IndexBusy(_)) => Some(Self::Busy),
+            _ => None,
+        }).unwrap_or(Self::Failure)
+    }
+}
+
+impl fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Failure => "failed",
+            Self::Integrity => "integrity check failed",
+            Self::Config => "configuration error",
+            Self::MissingIndex => "index missing",
+            Self::ModelMissing => "embedding model missing",
+            Self::PartialIngest => "partial ingest",
+            Self::UnsupportedClaims => "unsupported claims",
+            Self::Busy => "index busy",
+            Self::Usage => "usage",
+        })
+    }
+}
+
+/// `err` as the one-line JSON object `--json-errors` prints:
+/// `{"class":"config","code":3,"error":"configuration error: ..."}`.
+pub fn json(err: &anyhow::Error) -> String {
+    let class = ErrorClass::of(err);
+    serde_json::json!({ "class": class.name(), "code": class.code(), "error": format!("{:#}", err) }).to_string()
+}
@@ -0,0 +1,300 @@
+//! Minimal HTTP/1.1 for `localdb-cli serve` and the embedded web UI.
+//!
+//! One request per connection (`Connection: close`), GET/HEAD only, request
+//! bodies ignored: enough for a LAN search box without pulling in a web
+//! framework. Result pages carry a strong `ETag` derived from the query, its
+//! options and the index epoch (`localdb_vector::table::index_epoch`), so a
+//! client revalidating with `If-None-Match` gets `304 Not Modified` with no
+//! body until the indexes change.
+//!
+//! Bodies of 1 KiB or more are compressed with zstd or gzip when the client's
+//! `Accept-Encoding` allows (`Encoding::negotiate`, zstd preferred): JSON
+//! pages and document text shrink 5–10× over slow 2.4 GHz links.
+//!
+//! Document bodies also honour a single `Range` (`ByteRange`), so the viewer
+//! loads a large book a few chunks at a time. Ranges count bytes of the
+//! uncompressed body and partial responses are sent uncompressed.
+//!
+//! The one exception to one-response-per-connection is `GET /progress`, a
+//! server-sent event stream (`write_event_stream_head`, `sse_event`) that
+//! stays open until the client leaves.
+
+use anyhow::{anyhow, bail, Result};
+use std::io::{BufRead, Write};
+
+/// Smallest body worth compressing.
+pub const MIN_COMPRESS_LEN: usize = 1024;
+
+/// Longest request line or header line accepted.
+pub const MAX_LINE: usize = 8 * 1024;
+/// Most headers accepted per request.
+pub const MAX_HEADERS: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    /// Path without the query string, percent-decoded.
+    pub path: String,
+    /// Decoded query parameters in order.
+    pub params: Vec<(String, String)>,
+    /// Header names lowercased.
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    /// Read the request line and headers.
+    pub fn read(r: &mut impl BufRead) -> Result<Self> {
+        let line = read_line(r)?;
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next()) else { bail!("malformed request line") };
+        if !version.starts_with("HTTP/1.") { bail!("unsupported protocol {}", version); }
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let params = query.split('&').filter(|p| !p.is_empty())
+            .map(|p| { let (k, v) = p.split_once('=').unwrap_or((p, "")); (form_decode(k), form_decode(v)) })
+            .collect();
+        let mut headers = Vec::new();
+        loop {
+            let line = read_line(r)?;
+            if line.is_empty() { break; }
+            if headers.len() == MAX_HEADERS { bail!("too many headers"); }
+            let (name, value) = line.split_once(':').ok_or_else(|| anyhow!("malformed header"))?;
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+        Ok(Self { method: method.to_string(), path: percent_decode(path), params, headers })
+    }
+
+    /// First header named `name` (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+
+    /// First query parameter named `name`.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    /// Every query parameter named `name`, in order.
+    pub fn params_named(&self, name: &str) -> Vec<&str> {
+        self.params.iter().filter(|(n, _)| n == name).map(|(_, v)| v.as_str()).collect()
+    }
+}
+
+fn read_line(r: &mut impl BufRead) -> Result<String> {
+    let mut buf = Vec::new();
+    r.take(MAX_LINE as u64 + 2).read_until(b'\n', &mut buf)?;
+    if !buf.ends_with(b"\n") { bail!(if buf.len() > MAX_LINE { "line too long" } else { "connection closed mid-request" }); }
+    let line = String::from_utf8(buf).map_err(|_| anyhow!("request is not UTF-8"))?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// `%XX` decoding; invalid escapes are kept as written.
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%').then(|| s.get(i + 1..i + 3)).flatten().and_then(|h| u8::from_str_radix(h, 16).ok());
+        match hex {
+            Some(b) => { out.push(b); i += 3; }
+            None => { out.push(bytes[i]); i += 1; }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Query-string decoding: `+` is a space.
+fn form_decode(s: &str) -> String { percent_decode(&s.replace('+', " ")) }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        Self { status, headers: vec![("Content-Type".to_string(), content_type.to_string())], body: body.into() }
+    }
+
+    pub fn json(body: String) -> Self { Self::new(200, "application/json; charset=utf-8", body) }
+
+    pub fn text(status: u16, body: &str) -> Self { Self::new(status, "text/plain; charset=utf-8", body) }
+
+    /// `304` for a matching `If-None-Match`: validator only, no body.
+    pub fn not_modified(etag: &str) -> Self {
+        Self { status: 304, headers: Vec::new(), body: Vec::new() }.with_etag(etag)
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Attach `etag` and ask clients to revalidate before reusing the page.
+    pub fn with_etag(self, etag: &str) -> Self {
+        self.with_header("ETag", etag).with_header("Cache-Control", "no-cache")
+    }
+
+    /// Serialize; `head_only` drops the body but keeps its length.
+    pub fn write_to(&self, w: &mut impl Write, head_only: bool) -> std::io::Result<()> {
+        write!(w, "HTTP/1.1 {} {}\r\n", self.status, reason(self.status))?;
+        for (name, value) in &self.headers { write!(w, "{}: {}\r\n", name, value)?; }
+        write!(w, "Content-Length: {}\r\nConnection: close\r\n\r\n", self.body.len())?;
+        if !head_only { w.write_all(&self.body)?; }
+        w.flush()
+    }
+}
+
+/// A response content coding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding { Identity, Gzip, Zstd }
+
+impl Encoding {
+    /// `Content-Encoding` token (`None` for identity).
+    pub fn token(self) -> Option<&'static str> {
+        match self { Encoding::Identity => None, Encoding::Gzip => Some("gzip"), Encoding::Zstd => Some("zstd") }
+    }
+
+    /// Best coding allowed by an `Accept-Encoding` header: zstd, then gzip,
+    /// else identity. `q=0` refuses a coding; `*` stands for unlisted ones.
+    pub fn negotiate(accept_encoding: Option<&str>) -> Self {
+        let Some(header) = accept_encoding else { return Encoding::Identity };
+        let offers: Vec<(String, f32)> = header.split(',').filter_map(|item| {
+            let mut parts = item.split(';');
+            let coding = parts.next()?.trim().to_ascii_lowercase();
+            let q = parts.find_map(|p| p.trim().strip_prefix("q=")).map_or(1.0, |v| v.trim().parse().unwrap_or(0.0));
+            (!coding.is_empty()).then_some((coding, q))
+        }).collect();
+        let q = |name: &str| offers.iter().find(|(c, _)| c == name).or_else(|| offers.iter().find(|(c, _)| c == "*")).map_or(0.0, |(_, q)| *q);
+        let (zstd, gzip) = (q("zstd"), q("gzip"));
+        if zstd > 0.0 && zstd >= gzip { Encoding::Zstd } else if gzip > 0.0 { Encoding::Gzip } else { Encoding::Identity }
+    }
+}
+
+fn compressible(content_type: &str) -> bool {
+    content_type.starts_with("text/") || content_type.starts_with("application/json") || content_type.starts_with("application/x-protobuf")
+}
+
+impl Response {
+    /// Compress the body with `encoding` if it is compressible and at least
+    /// `MIN_COMPRESS_LEN` bytes. Always adds `Vary: Accept-Encoding, Accept`,
+    /// since the representation depends on both.
+    pub fn encode(mut self, encoding: Encoding) -> Result<Self> {
+        self = self.with_header("Vary", "Accept-Encoding, Accept");
+        let content_type = self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case("content-type")).map(|(_, v)| v.as_str()).unwrap_or("");
+        let Some(token) = encoding.token() else { return Ok(self) };
+        if self.status != 200 || self.body.len() < MIN_COMPRESS_LEN || !compressible(content_type) { return Ok(self); }
+        self.body = match encoding {
+            Encoding::Gzip => {
+                let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                gz.write_all(&self.body)?;
+                gz.finish()?
+            }
+            Encoding::Zstd => zstd::encode_all(&self.body[..], 3)?,
+            Encoding::Identity => unreachable!("identity has no token"),
+        };
+        Ok(self.with_header("Content-Encoding", token))
+    }
+}
+
+/// A `Range` header resolved against a body length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// No usable range (absent, malformed or several ranges): send it all.
+    Full,
+    /// Bytes `start..end`.
+    Partial(usize, usize),
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    /// One `bytes=a-b`, `bytes=a-` or `bytes=-suffix` range over `len` bytes;
+    /// `b` past the end is clamped.
+    pub fn parse(header: Option<&str>, len: usize) -> Self {
+        let Some(spec) = header.and_then(|h| h.trim().strip_prefix("bytes=")) else { return ByteRange::Full };
+        let Some((first, last)) = spec.split_once('-').filter(|_| !spec.contains(',')) else { return ByteRange::Full };
+        let num = |s: &str| s.trim().parse::<usize>().ok();
+        match (first.trim().is_empty(), last.trim().is_empty()) {
+            (true, true) => ByteRange::Full,
+            (true, false) => match num(last) {
+                Some(0) => ByteRange::Unsatisfiable,
+                Some(_) if len == 0 => ByteRange::Unsatisfiable,
+                Some(n) => ByteRange::Partial(len.saturating_sub(n), len),
+                None => ByteRange::Full,
+            },
+            (false, open_ended) => match (num(first), if open_ended { Some(usize::MAX) } else { num(last) }) {
+                (Some(a), Some(b)) if a <= b => if a >= len { ByteRange::Unsatisfiable } else { ByteRange::Partial(a, b.saturating_add(1).min(len)) },
+                _ => ByteRange::Full,
+            },
+        }
+    }
+}
+
+impl Response {
+    /// Apply `range` to a `200` body: `206` with `Content-Range`, or `416`
+    /// (also for an empty span or one past the body, e.g. a chunk's).
+    /// Advertises `Accept-Ranges` either way.
+    pub fn with_range(mut self, range: ByteRange) -> Self {
+        if self.status != 200 { return self; }
+        let len = self.body.len();
+        match range {
+            ByteRange::Full => self.with_header("Accept-Ranges", "bytes"),
+            ByteRange::Partial(start, end) if start < end && end <= len => {
+                self.status = 206;
+                self.body.truncate(end);
+                self.body.drain(..start);
+                self.with_header("Accept-Ranges", "bytes").with_header("Content-Range", &format!("bytes {}-{}/{}", start, end - 1, len))
+            }
+            ByteRange::Partial(..) | ByteRange::Unsatisfiable => Self::text(416, "range not satisfiable").with_header("Content-Range", &format!("bytes */{}", len)),
+        }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        206 => "Partial Content",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        416 => "Range Not Satisfiable",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Strong validator for a result page: blake3 over the request `parts`
+/// (query and options, in a fixed order) and the index `epoch`.
+pub fn etag(parts: &[&str], epoch: &str) -> String {
+    let mut h = blake3::Hasher::new();
+    for p in parts.iter().chain([&epoch]) {
+        h.update(&(p.len() as u64).to_le_bytes());
+        h.update(p.as_bytes());
+    }
+    format!("\"{}\"", &h.finalize().to_hex()[..20])
+}
+
+/// Whether an `If-None-Match` header matches `etag` (weak comparison, as
+/// RFC 9110 asks for GET: `W/` prefixes are ignored; `*` matches anything).
+pub fn if_none_match(header: Option<&str>, etag: &str) -> bool {
+    let Some(header) = header else { return false };
+    let strip = |t: &str| t.trim().trim_start_matches("W/").to_string();
+    header.split(',').any(|t| t.trim() == "*" || strip(t) == strip(etag))
+}
+
+/// Head of a `text/event-stream` response (server-sent events). Events follow
+/// until either side closes the connection, so there is no `Content-Length`.
+pub fn write_event_stream_head(w: &mut impl Write) -> std::io::Result<()> {
+    write!(w, "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n")?;
+    w.flush()
+}
+
+/// One server-sent event; each line of `data` becomes a `data:` line.
+pub fn sse_event(event: &str, data: &str) -> String {
+    let mut out = format!("event: {}\n", event);
+    for line in data.lines() { out.push_str(&format!("data: {}\n", line)); }
+    out.push('\n');
+    out
+}
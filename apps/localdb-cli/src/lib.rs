@@ -0,0 +1,13 @@
+//! localdb-cli library
+//!
+//! Pieces of the command-line app that are worth unit testing on their own.
+//! `http` is the small HTTP/1.1 layer behind `localdb-cli serve`; `proto`
+//! encodes its result pages as protobuf and `document` rebuilds document
+//! text for its viewer. `http` needs the `web` feature. `exit` maps failures
+//! to stable exit codes and machine-readable error output.
+
+pub mod document;
+pub mod exit;
+#[cfg(feature = "web")]
+pub mod http;
+pub mod proto;
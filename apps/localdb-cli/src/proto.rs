@@ -0,0 +1,63 @@
+//! Protobuf encoding of `/search` pages, for clients that would rather not
+//! parse JSON (`Accept: application/x-protobuf` or `?format=pb`).
+//!
+//! The schema is `web/search.proto` (also served at `/search.proto`). Only
+//! the three wire types it needs are written: varint, length-delimited and
+//! fixed32. Empty strings and zero integers are omitted, as proto3 does for defaults.
+
+/// Content type of protobuf bodies.
+pub const CONTENT_TYPE: &str = "application/x-protobuf";
+
+const VARINT: u32 = 0;
+const LEN: u32 = 2;
+const FIXED32: u32 = 5;
+
+/// A message under construction.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Message { buf: Vec<u8> }
+
+impl Message {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn string(self, field: u32, value: &str) -> Self {
+        if value.is_empty() { return self; }
+        self.bytes(field, value.as_bytes())
+    }
+
+    /// `int64`: negative values take ten bytes, as two's complement.
+    pub fn int64(mut self, field: u32, value: i64) -> Self {
+        if value == 0 { return self; }
+        self.key(field, VARINT);
+        varint(&mut self.buf, value as u64);
+        self
+    }
+
+    pub fn float(mut self, field: u32, value: f32) -> Self {
+        self.key(field, FIXED32);
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    /// Embedded message; repeated fields are written by calling this once per element.
+    pub fn message(self, field: u32, value: &Message) -> Self { self.bytes(field, &value.buf) }
+
+    pub fn into_bytes(self) -> Vec<u8> { self.buf }
+
+    fn bytes(mut self, field: u32, value: &[u8]) -> Self {
+        self.key(field, LEN);
+        varint(&mut self.buf, value.len() as u64);
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    fn key(&mut self, field: u32, wire_type: u32) { varint(&mut self.buf, u64::from((field << 3) | wire_type)); }
+}
+
+/// Base-128 varint, least significant group first.
+pub fn varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
@@ -0,0 +1,31 @@
+use localdb_cli::document::Document;
+use localdb_core::types::DocumentChunk;
+
+fn chunk(index: usize, content: &str) -> DocumentChunk {
+    DocumentChunk { chunk_index: index, total_chunks: 3, ..localdb_testkit::chunk(&format!("book:{}", index), content) }
+}
+
+#[test]
+fn chunks_are_joined_in_order_with_spans() {
+    let doc = Document::assemble(&[chunk(1, "Second paragraph."), chunk(0, "  First paragraph.\n")]);
+    assert_eq!(doc.text, "First paragraph.\n\nSecond paragraph.");
+    let second = doc.span("book:1").unwrap();
+    assert_eq!(&doc.text[second.start..second.end], "Second paragraph.");
+    assert_eq!(doc.spans[0].start, 0);
+    assert!(doc.span("book:9").is_none());
+}
+
+#[test]
+fn split_paragraph_overlap_is_written_once() {
+    let words: Vec<String> = (0..30).map(|i| format!("w{}", i)).collect();
+    let first = words[..20].join(" ");
+    let second = words[12..].join(" ");
+    let doc = Document::assemble(&[chunk(0, &first), chunk(1, &second), chunk(2, "Short one, then w28 w29.")]);
+    assert_eq!(doc.text, format!("{}\n\nShort one, then w28 w29.", words.join(" ")));
+    let span = doc.span("book:1").unwrap();
+    assert_eq!(&doc.text[span.start..span.end], second, "the span covers the shared words");
+
+    // Fewer than MIN_OVERLAP_WORDS shared words is coincidence, not overlap.
+    let doc = Document::assemble(&[chunk(0, "Boil the water"), chunk(1, "the water again")]);
+    assert_eq!(doc.text, "Boil the water\n\nthe water again");
+}
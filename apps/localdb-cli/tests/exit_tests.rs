@@ -0,0 +1,41 @@
+use anyhow::Context;
+use localdb_cli::exit::{json, ErrorClass};
+use localdb_core::error::Error as CoreError;
+
+#[test]
+fn codes_are_distinct_and_nonzero() {
+    let mut codes: Vec<u8> = ErrorClass::ALL.iter().map(|c| c.code()).collect();
+    assert!(codes.iter().all(|c| *c > 0));
+    codes.sort();
+    codes.dedup();
+    assert_eq!(codes.len(), ErrorClass::ALL.len());
+    assert_eq!(ErrorClass::Integrity.code(), 2, "scrub's exit code predates the classes");
+}
+
+#[test]
+fn attached_class_survives_more_context() {
+    let err = ErrorClass::MissingIndex.error("no index at /tmp/x").context("while searching");
+    assert_eq!(ErrorClass::of(&err), ErrorClass::MissingIndex);
+    let err = anyhow::anyhow!("bad toml").context(ErrorClass::Config);
+    assert_eq!(ErrorClass::of(&err), ErrorClass::Config);
+}
+
+#[test]
+fn core_errors_are_classed_by_kind() {
+    let missing: anyhow::Error = CoreError::EmbedderUnavailable("no model".into()).into();
+    assert_eq!(ErrorClass::of(&missing.context("loading")), ErrorClass::ModelMissing);
+    let invalid: anyhow::Result<()> = Err(CoreError::InvalidConfig("rerank expression: x".into()).into());
+    assert_eq!(ErrorClass::of(&invalid.context("query").unwrap_err()), ErrorClass::Config);
+    let busy: anyhow::Error = CoreError::IndexBusy("held by PID 7 (ingest)".into()).into();
+    assert_eq!(ErrorClass::of(&busy), ErrorClass::Busy);
+    assert_eq!(ErrorClass::of(&anyhow::anyhow!("disk full")), ErrorClass::Failure);
+}
+
+#[test]
+fn json_errors_carry_class_code_and_full_message() {
+    let err = ErrorClass::PartialIngest.error("2 files could not be read");
+    let v: serde_json::Value = serde_json::from_str(&json(&err)).unwrap();
+    assert_eq!(v["class"], "partial_ingest");
+    assert_eq!(v["code"], 6);
+    assert_eq!(v["error"], "partial ingest: 2 files could not be read");
+}
@@ -0,0 +1,130 @@
+#![cfg(feature = "web")]
+
+use localdb_cli::http::{etag, if_none_match, sse_event, write_event_stream_head, ByteRange, Encoding, Request, Response, MIN_COMPRESS_LEN};
+use std::io::Read;
+
+#[test]
+fn request_line_params_and_headers_are_parsed() {
+    let raw = b"GET /search?q=boil+water%21&also=jars&k=5&facet=&also=canning HTTP/1.1\r\nHost: box.local\r\nIf-None-Match: \"abc\"\r\n\r\n";
+    let req = Request::read(&mut &raw[..]).unwrap();
+    assert_eq!((req.method.as_str(), req.path.as_str()), ("GET", "/search"));
+    assert_eq!(req.param("q"), Some("boil water!"));
+    assert_eq!(req.param("k"), Some("5"));
+    assert_eq!(req.param("facet"), Some(""));
+    assert_eq!(req.params_named("also"), ["jars", "canning"]);
+    assert_eq!(req.header("if-none-match"), Some("\"abc\""));
+    assert_eq!(req.header("HOST"), Some("box.local"));
+
+    assert!(Request::read(&mut &b"GET /\r\n\r\n"[..]).is_err(), "no protocol");
+    assert!(Request::read(&mut &b"GET / HTTP/1.1\r\nHost: x"[..]).is_err(), "truncated headers");
+}
+
+#[test]
+fn etag_follows_query_options_and_epoch() {
+    let a = etag(&["water", "", "10", "rrf"], "d3.m1");
+    assert_eq!(a, etag(&["water", "", "10", "rrf"], "d3.m1"));
+    assert!(a.starts_with('"') && a.ends_with('"'));
+    assert_ne!(a, etag(&["water", "", "10", "rrf"], "d4.m1"), "new index epoch");
+    assert_ne!(a, etag(&["water", "", "20", "rrf"], "d3.m1"), "different options");
+    assert_ne!(etag(&["ab", "c"], "e"), etag(&["a", "bc"], "e"), "parts are length-delimited");
+}
+
+#[test]
+fn if_none_match_uses_weak_comparison() {
+    let tag = etag(&["q"], "d1.m0");
+    assert!(if_none_match(Some(&tag), &tag));
+    assert!(if_none_match(Some(&format!("\"other\", W/{}", tag)), &tag));
+    assert!(if_none_match(Some("*"), &tag));
+    assert!(!if_none_match(Some("\"other\""), &tag));
+    assert!(!if_none_match(None, &tag));
+}
+
+#[test]
+fn not_modified_has_validator_and_no_body() {
+    let mut out = Vec::new();
+    Response::not_modified("\"t\"").write_to(&mut out, false).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.starts_with("HTTP/1.1 304 Not Modified\r\n"));
+    assert!(text.contains("ETag: \"t\"\r\n") && text.contains("Cache-Control: no-cache\r\n"));
+    assert!(text.ends_with("Content-Length: 0\r\nConnection: close\r\n\r\n"));
+
+    let mut out = Vec::new();
+    Response::json("{}".to_string()).write_to(&mut out, true).unwrap();
+    assert!(String::from_utf8(out).unwrap().ends_with("Content-Length: 2\r\nConnection: close\r\n\r\n"), "HEAD keeps the length, drops the body");
+}
+
+#[test]
+fn accept_encoding_prefers_zstd_and_honours_q_values() {
+    assert_eq!(Encoding::negotiate(None), Encoding::Identity);
+    assert_eq!(Encoding::negotiate(Some("gzip, deflate, br, zstd")), Encoding::Zstd);
+    assert_eq!(Encoding::negotiate(Some("gzip, deflate")), Encoding::Gzip);
+    assert_eq!(Encoding::negotiate(Some("zstd;q=0.5, gzip")), Encoding::Gzip);
+    assert_eq!(Encoding::negotiate(Some("zstd;q=0, *")), Encoding::Gzip, "refused coding, wildcard for the rest");
+    assert_eq!(Encoding::negotiate(Some("br, identity")), Encoding::Identity);
+}
+
+#[test]
+fn large_bodies_are_compressed_and_small_ones_left_alone() {
+    let body = "{\"hits\":[".to_string() + &"{\"id\":\"water/boiling#3\",\"score\":0.5},".repeat(100) + "]}";
+    assert!(body.len() >= MIN_COMPRESS_LEN);
+
+    let gz = Response::json(body.clone()).encode(Encoding::Gzip).unwrap();
+    assert!(gz.headers.contains(&("Content-Encoding".to_string(), "gzip".to_string())));
+    assert!(gz.body.len() < body.len() / 4);
+    let mut plain = String::new();
+    flate2::read::GzDecoder::new(&gz.body[..]).read_to_string(&mut plain).unwrap();
+    assert_eq!(plain, body);
+
+    let zst = Response::json(body.clone()).encode(Encoding::Zstd).unwrap();
+    assert!(zst.headers.contains(&("Content-Encoding".to_string(), "zstd".to_string())));
+    assert_eq!(zstd::decode_all(&zst.body[..]).unwrap(), body.as_bytes());
+
+    let small = Response::json("{}".to_string()).encode(Encoding::Zstd).unwrap();
+    assert_eq!(small.body, b"{}");
+    assert!(small.headers.iter().all(|(n, _)| n != "Content-Encoding"));
+    assert!(small.headers.contains(&("Vary".to_string(), "Accept-Encoding, Accept".to_string())), "caches still key on the coding");
+}
+
+#[test]
+fn single_byte_ranges_are_parsed_and_clamped() {
+    let r = |h: &str| ByteRange::parse(Some(h), 100);
+    assert_eq!(ByteRange::parse(None, 100), ByteRange::Full);
+    assert_eq!(r("bytes=0-9"), ByteRange::Partial(0, 10));
+    assert_eq!(r("bytes=90-"), ByteRange::Partial(90, 100));
+    assert_eq!(r("bytes=-5"), ByteRange::Partial(95, 100));
+    assert_eq!(r("bytes=50-500"), ByteRange::Partial(50, 100), "end past the body is clamped");
+    assert_eq!(r("bytes=100-"), ByteRange::Unsatisfiable);
+    assert_eq!(r("bytes=0-1,5-6"), ByteRange::Full, "several ranges: whole body");
+    assert_eq!(r("bytes=9-0"), ByteRange::Full, "invalid ranges are ignored");
+    assert_eq!(r("items=0-1"), ByteRange::Full);
+}
+
+#[test]
+fn ranges_give_partial_content_left_uncompressed() {
+    let body = "x".repeat(2 * MIN_COMPRESS_LEN);
+    let part = Response::new(200, "text/plain; charset=utf-8", body.clone()).with_range(ByteRange::Partial(10, 20)).encode(Encoding::Gzip).unwrap();
+    assert_eq!((part.status, part.body.len()), (206, 10));
+    assert!(part.headers.contains(&("Content-Range".to_string(), format!("bytes 10-19/{}", body.len()))));
+    assert!(part.headers.iter().all(|(n, _)| n != "Content-Encoding"));
+
+    let full = Response::new(200, "text/plain; charset=utf-8", body.clone()).with_range(ByteRange::Full);
+    assert!(full.headers.contains(&("Accept-Ranges".to_string(), "bytes".to_string())));
+    let none = Response::new(200, "text/plain", body.clone()).with_range(ByteRange::Unsatisfiable);
+    assert_eq!(none.status, 416);
+    assert!(none.headers.contains(&("Content-Range".to_string(), format!("bytes */{}", body.len()))));
+    for span in [ByteRange::Partial(10, 10), ByteRange::Partial(20, 10), ByteRange::Partial(0, body.len() + 1)] {
+        assert_eq!(Response::new(200, "text/plain", body.clone()).with_range(span).status, 416, "{:?}", span);
+    }
+}
+
+#[test]
+fn progress_streams_as_server_sent_events() {
+    let mut head = Vec::new();
+    write_event_stream_head(&mut head).unwrap();
+    let head = String::from_utf8(head).unwrap();
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains("Content-Type: text/event-stream\r\n"));
+    assert!(!head.contains("Content-Length"));
+    assert_eq!(sse_event("progress", "{\"job\":\"ingest\"}"), "event: progress\ndata: {\"job\":\"ingest\"}\n\n");
+    assert_eq!(sse_event("note", "a\nb"), "event: note\ndata: a\ndata: b\n\n");
+}
@@ -0,0 +1,25 @@
+use localdb_cli::proto::{varint, Message};
+
+#[test]
+fn varints_use_seven_bit_groups() {
+    let enc = |v| { let mut b = Vec::new(); varint(&mut b, v); b };
+    assert_eq!(enc(1), [0x01]);
+    assert_eq!(enc(300), [0xac, 0x02]);
+    assert_eq!(enc(u64::MAX).len(), 10);
+}
+
+#[test]
+fn messages_match_the_protobuf_wire_format() {
+    // Hit { id: "a", score: 1.0, source: "vec" }
+    let hit = Message::new().string(1, "a").float(2, 1.0).string(3, "vec");
+    assert_eq!(hit.clone().into_bytes(), [0x0a, 1, b'a', 0x15, 0, 0, 0x80, 0x3f, 0x1a, 3, b'v', b'e', b'c']);
+
+    // SearchPage { query: "q", hits: [hit] }; the empty facet is omitted.
+    let page = Message::new().string(1, "q").string(2, "").message(5, &hit).into_bytes();
+    assert_eq!(&page[..3], [0x0a, 1, b'q']);
+    assert_eq!(&page[3..5], [0x2a, 13]);
+    assert_eq!(&page[5..], hit.into_bytes());
+
+    // Hit { created_at: 300 }; zero is omitted.
+    assert_eq!(Message::new().int64(6, 300).int64(7, 0).into_bytes(), [0x30, 0xac, 0x02]);
+}
@@ -0,0 +1,93 @@
+//! Extractive answer spotting for question-shaped queries.
+//!
+//! For a query like "how long do you boil jars?", each sentence of a result's
+//! snippet is scored by term-frequency cosine against the query's content
+//! words; the best one is emphasized (bold in HTML snippets and the terminal)
+//! so the reader's eye lands on the likely answer first. Lexical on purpose:
+//! it runs per displayed result and needs no model.
+//!
+//! `keywords` turns a question into its content words, a second phrasing for
+//! multi-query search (`search.multi_query.keywords`).
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use crate::summary::sentence_spans;
+
+/// Leading words that make a query a question even without a `?`.
+const QUESTION_WORDS: &[&str] = &[
+    "how", "what", "why", "when", "where", "which", "who", "whom", "whose",
+    "can", "could", "should", "would", "will", "do", "does", "did", "is", "are", "was", "were",
+];
+
+/// Words ignored when comparing: question words plus common function words.
+const STOPWORDS: &[&str] = &[
+    "the", "and", "for", "you", "your", "with", "that", "this", "from", "into", "have", "has",
+    "much", "many", "long", "not", "but", "any", "its", "are", "was", "were", "can", "could",
+    "should", "would", "will", "does", "did", "how", "what", "why", "when", "where", "which", "who",
+];
+
+/// Minimum cosine for a sentence to count as an answer.
+const MIN_SCORE: f32 = 0.2;
+
+/// Whether `query` reads as a question (ends with `?` or starts with a question word).
+pub fn is_question(query: &str) -> bool {
+    let q = query.trim();
+    q.ends_with('?') || q.split_whitespace().next().is_some_and(|w| QUESTION_WORDS.contains(&w.to_lowercase().as_str()))
+}
+
+/// Byte range of the sentence of `text` that best answers `query`, or `None`
+/// when the query is not a question or no sentence is similar enough.
+pub fn best_sentence(text: &str, query: &str) -> Option<Range<usize>> {
+    if !is_question(query) { return None; }
+    let q = term_counts(query);
+    if q.is_empty() { return None; }
+    let spans = sentence_spans(text);
+    // A single sentence is the whole snippet; emphasizing it says nothing.
+    if spans.len() < 2 { return None; }
+    spans.into_iter()
+        .map(|r| { let score = cosine(&q, &term_counts(&text[r.clone()])); (r, score) })
+        .filter(|(_, s)| *s >= MIN_SCORE)
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(r, _)| r)
+}
+
+/// The content words of a question-shaped `query`, in order ("how long do you
+/// boil jars?" → "boil jars"); `None` for other queries or when nothing
+/// would change.
+pub fn keywords(query: &str) -> Option<String> {
+    if !is_question(query) { return None; }
+    let words: Vec<&str> = query.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() > 2 && !STOPWORDS.contains(&w.to_lowercase().as_str()))
+        .collect();
+    let keywords = words.join(" ");
+    (!keywords.is_empty() && keywords != query.trim()).then_some(keywords)
+}
+
+/// `text` with the answer sentence wrapped in ANSI bold, for terminal output.
+pub fn emphasize_ansi(text: &str, query: &str) -> String {
+    match best_sentence(text, query) {
+        Some(r) => format!("{}\x1b[1m{}\x1b[0m{}", &text[..r.start], &text[r.clone()], &text[r.end..]),
+        None => text.to_string(),
+    }
+}
+
+/// Lowercased content words of `text`: longer than two characters, not stopwords.
+pub(crate) fn content_words(text: &str) -> HashSet<String> { term_counts(text).into_keys().collect() }
+
+fn term_counts(text: &str) -> HashMap<String, f32> {
+    let mut counts = HashMap::new();
+    for w in text.split(|c: char| !c.is_alphanumeric()).filter(|w| w.chars().count() > 2) {
+        let w = w.to_lowercase();
+        if STOPWORDS.contains(&w.as_str()) { continue; }
+        *counts.entry(w).or_insert(0.0) += 1.0;
+    }
+    counts
+}
+
+fn cosine(a: &HashMap<String, f32>, b: &HashMap<String, f32>) -> f32 {
+    let dot: f32 = a.iter().filter_map(|(w, x)| b.get(w).map(|y| x * y)).sum();
+    let norm = |m: &HashMap<String, f32>| m.values().map(|v| v * v).sum::<f32>().sqrt();
+    let denom = norm(a) * norm(b);
+    if denom == 0.0 { 0.0 } else { dot / denom }
+}
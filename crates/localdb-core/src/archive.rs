@@ -0,0 +1,152 @@
+//! Zip and gzipped tar archives for ingest.
+//!
+//! Offline dumps often ship as one compressed bundle. `entries` reads the
+//! regular files of a `.zip`, `.tar.gz` or `.tgz` in archive order, keeping
+//! those `keep` accepts by inner path; `DataProcessor` makes each supported
+//! one (`is_supported`: plain text, EPUB, CSV/TSV) its own document, with
+//! `doc_path` `<archive doc_path>#<inner path>`, doc id `<archive id>#<inner
+//! id>` and a facet under the archive's name (`entry_facet`). The archive gets
+//! one catalog record. Entries that escape the archive (`../`, absolute
+//! paths) or inflate past `MAX_ENTRY_BYTES` are skipped, and so is everything
+//! after the first `MAX_ARCHIVE_BYTES` decompressed; nested archives, ZIMs,
+//! scans, JSON Lines and transcripts inside an archive are not read. Zips
+//! need the `zip` feature, tar archives the `tar` feature (both default).
+#![cfg_attr(not(all(feature = "zip", feature = "tar")), allow(dead_code, unused_imports))]
+
+use anyhow::{bail, Context, Result};
+use std::io::{Cursor, Read};
+use std::path::{Component, Path};
+
+use crate::{csv, epub};
+
+/// Largest entry read, decompressed (256 MiB).
+pub const MAX_ENTRY_BYTES: u64 = 256 << 20;
+
+/// Most read from one archive, decompressed, over all its entries (1 GiB).
+pub const MAX_ARCHIVE_BYTES: u64 = 1 << 30;
+
+/// Archive extensions, longest first.
+const EXTENSIONS: &[&str] = &[".tar.gz", ".tgz", ".zip"];
+
+/// One file inside an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Inner path with `/` separators (`guides/fire.txt`).
+    pub path: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Whether `path` is a `.zip`, `.tar.gz` or `.tgz` by name.
+pub fn is_archive(path: &Path) -> bool { extension(path).is_some() }
+
+fn extension(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+    EXTENSIONS.iter().copied().find(|ext| name.ends_with(ext))
+}
+
+/// Archive name without its extension (`bundle.tar.gz` → `bundle`).
+pub fn stem(path: &Path) -> String {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    match extension(path) { Some(ext) => name[..name.len() - ext.len()].to_string(), None => name }
+}
+
+/// Whether an entry at `inner` is read: `.txt`, EPUB or CSV/TSV.
+pub fn is_supported(inner: &str) -> bool {
+    let path = Path::new(inner);
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("txt")) || epub::is_epub(path) || csv::is_table(path)
+}
+
+/// Facet of an entry: the archive's directory facet, then the archive name
+/// and the entry's folders (`dumps` + `bundle` + `guides/fire.txt` →
+/// `dumps/bundle/guides`).
+pub fn entry_facet(category: &str, archive: &str, inner: &str) -> String {
+    let dirs = inner.rsplit_once('/').map_or("", |(d, _)| d);
+    std::iter::once(archive).chain(dirs.split('/').filter(|s| !s.is_empty())).fold(category.to_string(), |facet, s| csv::row_facet(&facet, s))
+}
+
+/// The regular files of the archive at `path` (read as `bytes`) whose inner
+/// path `keep` accepts, in archive order. Unsafe or oversized entries, and
+/// those past `MAX_ARCHIVE_BYTES`, are reported and skipped.
+pub fn entries(path: &Path, bytes: &[u8], keep: impl Fn(&str) -> bool) -> Result<Vec<Entry>> {
+    match extension(path) {
+        Some(".zip") => zip_entries(path, bytes, keep),
+        Some(_) => tar_entries(path, bytes, keep),
+        None => bail!("{} is not a .zip, .tar.gz or .tgz archive", path.display()),
+    }
+}
+
+#[cfg(feature = "zip")]
+fn zip_entries(path: &Path, bytes: &[u8], keep: impl Fn(&str) -> bool) -> Result<Vec<Entry>> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(bytes)).context("reading the zip directory")?;
+    let mut entries = Vec::new();
+    let mut left = MAX_ARCHIVE_BYTES;
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+        if !file.is_file() { continue; }
+        let Some(inner) = file.enclosed_name().and_then(|p| inner_path(&p)) else { eprintln!("⚠️  Skipping {} in {}: outside the archive", file.name(), path.display()); continue };
+        if !keep(&inner) { continue; }
+        if let Some(bytes) = read_capped(&mut file, &inner, path, &mut left)? { entries.push(Entry { path: inner, bytes }); }
+        if left == 0 { break; }
+    }
+    Ok(entries)
+}
+
+#[cfg(not(feature = "zip"))]
+fn zip_entries(path: &Path, _bytes: &[u8], _keep: impl Fn(&str) -> bool) -> Result<Vec<Entry>> {
+    bail!("cannot read {}: built without the `zip` feature", path.display())
+}
+
+#[cfg(feature = "tar")]
+fn tar_entries(path: &Path, bytes: &[u8], keep: impl Fn(&str) -> bool) -> Result<Vec<Entry>> {
+    let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(bytes));
+    let mut entries = Vec::new();
+    let mut left = MAX_ARCHIVE_BYTES;
+    for entry in tar.entries().context("reading the tar stream")? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() { continue; }
+        let name = entry.path()?.into_owned();
+        let Some(inner) = inner_path(&name) else { eprintln!("⚠️  Skipping {} in {}: outside the archive", name.display(), path.display()); continue };
+        if !keep(&inner) { continue; }
+        if let Some(bytes) = read_capped(&mut entry, &inner, path, &mut left)? { entries.push(Entry { path: inner, bytes }); }
+        if left == 0 { break; }
+    }
+    Ok(entries)
+}
+
+#[cfg(not(feature = "tar"))]
+fn tar_entries(path: &Path, _bytes: &[u8], _keep: impl Fn(&str) -> bool) -> Result<Vec<Entry>> {
+    bail!("cannot read {}: built without the `tar` feature", path.display())
+}
+
+/// `/`-joined normal components of `path`; `None` if it climbs out or is absolute.
+fn inner_path(path: &Path) -> Option<String> {
+    let mut parts = Vec::new();
+    for c in path.components() {
+        match c {
+            Component::Normal(p) => parts.push(p.to_string_lossy().into_owned()),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+/// At most `MAX_ENTRY_BYTES` of `reader`, taken from the `left` bytes the
+/// archive may still inflate to; `None` (reported) if there is more. An entry
+/// that does not fit in `left` sets it to 0: the rest of the archive is skipped.
+fn read_capped(reader: &mut impl Read, inner: &str, archive: &Path, left: &mut u64) -> Result<Option<Vec<u8>>> {
+    let cap = MAX_ENTRY_BYTES.min(*left);
+    let mut bytes = Vec::new();
+    reader.take(cap + 1).read_to_end(&mut bytes).with_context(|| format!("reading {} in {}", inner, archive.display()))?;
+    if bytes.len() as u64 > cap {
+        if cap < MAX_ENTRY_BYTES {
+            eprintln!("⚠️  Skipping {} and the rest of {}: more than {} MiB decompressed", inner, archive.display(), MAX_ARCHIVE_BYTES >> 20);
+            *left = 0;
+        } else {
+            eprintln!("⚠️  Skipping {} in {}: larger than {} MiB", inner, archive.display(), MAX_ENTRY_BYTES >> 20);
+        }
+        return Ok(None);
+    }
+    *left -= bytes.len() as u64;
+    Ok(Some(bytes))
+}
@@ -0,0 +1,137 @@
+//! Images referenced by HTML/EPUB sources, kept for the web UI
+//! (`data.asset_store`).
+//!
+//! Text-only rendering loses the diagrams an illustrated how-to depends on,
+//! so ingest stores each image an EPUB chapter references. Images are kept
+//! content-addressed like `blobs` (`<store>/ab/cdef…`) and downscaled so no
+//! side exceeds `data.asset_max_dimension` (default 1024 px, 0 keeps
+//! originals): a full-page plate becomes a JPEG/PNG thumbnail small enough for
+//! a weak link. Formats the `image` crate cannot decode (SVG, WebP) are stored
+//! unchanged, and so is everything without the `images` feature. Each document's images are listed in `<store>/docs/<key>.json`
+//! in reading order, each anchored after the chunk it follows.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+#[cfg(feature = "images")]
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use crate::blobs::BlobStore;
+use crate::config::{expand_path, Config};
+
+/// Longest side of a stored image unless `data.asset_max_dimension` says otherwise.
+pub const DEFAULT_MAX_DIMENSION: u32 = 1024;
+
+/// One stored image of a document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Asset {
+    /// Path inside the source (`OEBPS/images/pump.png`).
+    pub name: String,
+    /// blake3 of the stored bytes (the thumbnail when downscaled).
+    pub hash: String,
+    pub media_type: String,
+    pub alt: Option<String>,
+    /// Chunk the image follows in reading order; `None` before any text.
+    pub after_chunk: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AssetStore { blobs: BlobStore, max_dimension: u32 }
+
+impl AssetStore {
+    pub fn new(dir: impl Into<PathBuf>, max_dimension: u32) -> Self { Self { blobs: BlobStore::new(dir), max_dimension } }
+
+    /// The store named by `data.asset_store`, if set.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let max = config.get::<u32>("data.asset_max_dimension").unwrap_or(DEFAULT_MAX_DIMENSION);
+        config.get::<String>("data.asset_store").ok().filter(|d| !d.trim().is_empty()).map(|d| Self::new(expand_path(d), max))
+    }
+
+    pub fn dir(&self) -> &Path { self.blobs.dir() }
+
+    /// Store one image, downscaled when larger than the limit. Returns the
+    /// hash and media type of what was stored.
+    pub fn put_image(&self, name: &str, bytes: &[u8]) -> Result<(String, String)> {
+        let (bytes, media_type) = match downscale(bytes, self.max_dimension) {
+            Some((thumb, media_type)) => (thumb, media_type),
+            None => (bytes.to_vec(), media_type(name)),
+        };
+        let hash = blake3::hash(&bytes).to_hex().to_string();
+        self.blobs.put(&hash, &bytes)?;
+        Ok((hash, media_type.to_string()))
+    }
+
+    /// Stored image for `hash`; anything but a hex digest is refused.
+    pub fn get(&self, hash: &str) -> Option<PathBuf> {
+        if hash.len() < 16 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) { return None; }
+        self.blobs.get(hash)
+    }
+
+    /// Replace the image list of `doc_id`.
+    pub fn put_manifest(&self, doc_id: &str, assets: &[Asset]) -> Result<()> {
+        let path = self.manifest_path(doc_id);
+        let dir = path.parent().unwrap_or(self.dir());
+        fs::create_dir_all(dir)?;
+        let tmp = path.with_extension(format!("json.tmp{}", crate::blobs::tmp_suffix()));
+        fs::write(&tmp, serde_json::to_vec(assets)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// Images of `doc_id` in reading order (none if it has no manifest).
+    pub fn manifest(&self, doc_id: &str) -> Result<Vec<Asset>> {
+        let path = self.manifest_path(doc_id);
+        if !path.is_file() { return Ok(Vec::new()); }
+        serde_json::from_slice(&fs::read(&path)?).with_context(|| format!("reading {}", path.display()))
+    }
+
+    fn manifest_path(&self, doc_id: &str) -> PathBuf {
+        self.dir().join("docs").join(format!("{}.json", &blake3::hash(doc_id.as_bytes()).to_hex()[..32]))
+    }
+}
+
+/// Media type by file extension (`application/octet-stream` when unknown).
+pub fn media_type(name: &str) -> &'static str {
+    let ext = name.rsplit_once('.').map(|(_, e)| e.to_ascii_lowercase()).unwrap_or_default();
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Media type of stored bytes from their signature (blobs carry no name).
+pub fn sniff(bytes: &[u8]) -> &'static str {
+    let head = &bytes[..bytes.len().min(512)];
+    if head.starts_with(b"\x89PNG") { "image/png" }
+    else if head.starts_with(&[0xff, 0xd8, 0xff]) { "image/jpeg" }
+    else if head.starts_with(b"GIF8") { "image/gif" }
+    else if head.starts_with(b"RIFF") && head.get(8..12) == Some(&b"WEBP"[..]) { "image/webp" }
+    else if String::from_utf8_lossy(head).contains("<svg") { "image/svg+xml" }
+    else { "application/octet-stream" }
+}
+
+/// A thumbnail when `bytes` decode and exceed `max` px on a side: PNG if the
+/// image has transparency, else JPEG.
+#[cfg(feature = "images")]
+fn downscale(bytes: &[u8], max: u32) -> Option<(Vec<u8>, &'static str)> {
+    if max == 0 { return None; }
+    let img = image::load_from_memory(bytes).ok()?;
+    if img.width() <= max && img.height() <= max { return None; }
+    let thumb = img.thumbnail(max, max);
+    let mut out = Vec::new();
+    if thumb.color().has_alpha() {
+        thumb.write_to(&mut Cursor::new(&mut out), image::ImageFormat::Png).ok()?;
+        Some((out, "image/png"))
+    } else {
+        image::DynamicImage::ImageRgb8(thumb.to_rgb8()).write_to(&mut Cursor::new(&mut out), image::ImageFormat::Jpeg).ok()?;
+        Some((out, "image/jpeg"))
+    }
+}
+
+#[cfg(not(feature = "images"))]
+fn downscale(_bytes: &[u8], _max: u32) -> Option<(Vec<u8>, &'static str)> { None }
@@ -0,0 +1,116 @@
+//! Content-addressed store for original files (`data.blob_store`).
+//!
+//! When configured, ingest copies each source file into the store under its
+//! blake3 digest (the catalog's `file_hash`), laid out like git objects:
+//! `<store>/ab/cdef…`. The index then stays self-contained when sources are
+//! reorganized, deleted, or on unplugged media; `localdb-cli open` falls back
+//! to the stored copy. Identical files are stored once. Blobs of files that
+//! left the catalog are removed by `sweep` (`localdb-cli maintain` and `gc`).
+
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::config::{expand_path, Config};
+use crate::types::FileRecord;
+
+#[derive(Debug, Clone)]
+pub struct BlobStore { dir: PathBuf }
+
+impl BlobStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self { Self { dir: dir.into() } }
+
+    /// The store named by `data.blob_store`, if set.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        config.get::<String>("data.blob_store").ok().filter(|d| !d.trim().is_empty()).map(|d| Self::new(expand_path(d)))
+    }
+
+    pub fn dir(&self) -> &Path { &self.dir }
+
+    /// Where the blob for `hash` lives (whether or not it exists).
+    pub fn path_for(&self, hash: &str) -> PathBuf {
+        let split = hash.len().min(2);
+        self.dir.join(&hash[..split]).join(&hash[split..])
+    }
+
+    /// Stored blob for `hash`, if present.
+    pub fn get(&self, hash: &str) -> Option<PathBuf> {
+        Some(self.path_for(hash)).filter(|p| p.is_file())
+    }
+
+    /// Store `bytes`, whose blake3 hex digest is `hash`. Existing blobs are
+    /// kept; new ones are written to a temp file and renamed into place.
+    pub fn put(&self, hash: &str, bytes: &[u8]) -> Result<PathBuf> {
+        let path = self.path_for(hash);
+        if path.is_file() { return Ok(path); }
+        let dir = path.parent().unwrap_or(&self.dir);
+        fs::create_dir_all(dir)?;
+        let tmp = dir.join(format!(".{}.tmp{}", hash, tmp_suffix()));
+        fs::write(&tmp, bytes)?;
+        fs::rename(&tmp, &path)?;
+        Ok(path)
+    }
+
+    /// Hashes of every stored blob (sorted); in-flight temp files are skipped.
+    pub fn hashes(&self) -> Result<Vec<String>> {
+        if !self.dir.is_dir() { return Ok(Vec::new()); }
+        let mut hashes = Vec::new();
+        for fan in fs::read_dir(&self.dir)? {
+            let fan = fan?;
+            if !fan.file_type()?.is_dir() { continue; }
+            let prefix = fan.file_name().to_string_lossy().into_owned();
+            for blob in fs::read_dir(fan.path())? {
+                let blob = blob?;
+                let name = blob.file_name().to_string_lossy().into_owned();
+                if blob.file_type()?.is_file() && !name.starts_with('.') { hashes.push(format!("{}{}", prefix, name)); }
+            }
+        }
+        hashes.sort();
+        Ok(hashes)
+    }
+
+    /// Stored hashes not in `keep`.
+    pub fn unreferenced(&self, keep: &HashSet<String>) -> Result<Vec<String>> {
+        Ok(self.hashes()?.into_iter().filter(|h| !keep.contains(h)).collect())
+    }
+
+    /// Stored hashes no record of `catalog` has. An empty catalog (indexes
+    /// from before it existed) references nothing it can vouch for, so then
+    /// nothing counts as unreferenced.
+    pub fn uncataloged<'a>(&self, catalog: impl IntoIterator<Item = &'a FileRecord>) -> Result<Vec<String>> {
+        let keep: HashSet<String> = catalog.into_iter().map(|r| r.file_hash.clone()).collect();
+        if keep.is_empty() { return Ok(Vec::new()); }
+        self.unreferenced(&keep)
+    }
+
+    /// Delete the blobs of `hashes` (and fan-out directories left empty);
+    /// returns how many were removed.
+    pub fn remove(&self, hashes: &[String]) -> Result<usize> {
+        let mut removed = 0;
+        for hash in hashes {
+            let path = self.path_for(hash);
+            if !path.is_file() { continue; }
+            fs::remove_file(&path)?;
+            removed += 1;
+            // Fails while other blobs share the directory.
+            if let Some(dir) = path.parent().filter(|d| *d != self.dir) { let _ = fs::remove_dir(dir); }
+        }
+        Ok(removed)
+    }
+
+    /// Delete every blob whose hash is not in `keep`; returns their hashes.
+    pub fn sweep(&self, keep: &HashSet<String>) -> Result<Vec<String>> {
+        let unreferenced = self.unreferenced(keep)?;
+        self.remove(&unreferenced)?;
+        Ok(unreferenced)
+    }
+}
+
+/// Unique per process and call, so threads writing the same file never share
+/// a temp file.
+pub(crate) fn tmp_suffix() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    format!("{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed))
+}
@@ -0,0 +1,147 @@
+//! Repeated headers, footers and watermarks, stripped before chunking.
+//!
+//! A line counts as boilerplate when it appears on at least `min_share` of a
+//! document's pages (scan pages, EPUB chapters, form-feed pages of a text
+//! file) and the document has `min_pages` or more. Lines are compared
+//! normalized — trimmed, lowercased, whitespace collapsed, digits as `#` — so
+//! `Page 12` and `Page 13` are the same footer. With `across_files` the text
+//! files of one folder (a scraped site) are also compared with each other, one
+//! file per page, skipping files the `guards` would skip. What was removed is
+//! reported at ingest and kept in the file's catalog record (`boilerplate`).
+//! Off unless `[boilerplate] enabled`; stored and BM25-indexed text changes.
+//!
+//! The same line keys and counts (`line_key`, `lines_on`, `is_page_marker`)
+//! drive the `strip_boilerplate` step of `preprocess`, which cleans only the
+//! text that is embedded, and the `max_repeats` junk filter (`junk`), which
+//! drops chunks made only of such lines.
+
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::guards::FileGuards;
+
+/// Longest line (in chars) considered; body text is longer.
+pub const MAX_LINE_CHARS: usize = 80;
+
+/// `[boilerplate]` settings.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct BoilerplateConfig {
+    pub enabled: bool,
+    /// Share of pages (0–1] a line must appear on.
+    pub min_share: f32,
+    /// Fewer pages than this and nothing is removed.
+    pub min_pages: usize,
+    /// Also compare the `.txt` files of each folder with each other.
+    pub across_files: bool,
+}
+
+impl Default for BoilerplateConfig {
+    fn default() -> Self { Self { enabled: false, min_share: 0.6, min_pages: 3, across_files: false } }
+}
+
+impl BoilerplateConfig {
+    /// `[boilerplate]`, or the defaults (off; 60% of at least 3 pages, per document).
+    pub fn from_config(config: &Config) -> Self { config.get::<Self>("boilerplate").unwrap_or_default() }
+}
+
+/// One stripped line: as first seen, and on how many pages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Removed {
+    pub line: String,
+    pub pages: usize,
+}
+
+/// Comparison key of `line`, or `None` when it cannot be boilerplate
+/// (blank, too long, or without a letter or digit).
+pub fn line_key(line: &str) -> Option<String> {
+    let line = line.trim();
+    if line.is_empty() || line.chars().count() > MAX_LINE_CHARS || !line.chars().any(char::is_alphanumeric) { return None; }
+    Some(line.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase().chars().map(|c| if c.is_ascii_digit() { '#' } else { c }).collect())
+}
+
+/// `12`, `- 12 -`, `Page 12`, `page 3 of 40`, `3/40`.
+pub fn is_page_marker(line: &str) -> bool {
+    let t = line.trim().trim_matches(|c: char| c == '-' || c == '–' || c == '—' || c.is_whitespace()).to_lowercase();
+    let t = t.strip_prefix("page").map(str::trim_start).unwrap_or(&t);
+    let numbers: Vec<&str> = t.split(" of ").flat_map(|p| p.split('/')).map(str::trim).collect();
+    numbers.iter().all(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Keys of the lines on at least `needed` (at least 2) of `pages`.
+pub fn lines_on<S: AsRef<str>>(pages: &[S], needed: usize) -> HashSet<String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for page in pages {
+        let keys: HashSet<String> = page.as_ref().lines().filter_map(line_key).collect();
+        for key in keys { *counts.entry(key).or_insert(0) += 1; }
+    }
+    counts.into_iter().filter(|&(_, n)| n >= needed.max(2)).map(|(k, _)| k).collect()
+}
+
+/// Keys of the lines on at least `min_share` of `pages`.
+pub fn detect<S: AsRef<str>>(pages: &[S], config: &BoilerplateConfig) -> HashSet<String> {
+    if !config.enabled || pages.len() < config.min_pages.max(2) { return HashSet::new(); }
+    lines_on(pages, (pages.len() as f32 * config.min_share.clamp(0.0, 1.0)).ceil() as usize)
+}
+
+/// `pages` without the lines whose key is in `keys`, and what was removed
+/// (most frequent first).
+pub fn strip<S: AsRef<str>>(pages: &[S], keys: &HashSet<String>) -> (Vec<String>, Vec<Removed>) {
+    if keys.is_empty() { return (pages.iter().map(|p| p.as_ref().to_string()).collect(), Vec::new()); }
+    let mut removed: Vec<(String, Removed)> = Vec::new();
+    let stripped = pages.iter().map(|page| {
+        let mut on_page = HashSet::new();
+        page.as_ref().lines().filter(|line| {
+            let Some(key) = line_key(line).filter(|k| keys.contains(k)) else { return true };
+            if on_page.insert(key.clone()) {
+                match removed.iter_mut().find(|(k, _)| *k == key) {
+                    Some((_, r)) => r.pages += 1,
+                    None => removed.push((key, Removed { line: line.trim().to_string(), pages: 1 })),
+                }
+            }
+            false
+        }).collect::<Vec<_>>().join("\n")
+    }).collect();
+    let mut removed: Vec<Removed> = removed.into_iter().map(|(_, r)| r).collect();
+    removed.sort_by(|a, b| b.pages.cmp(&a.pages).then_with(|| a.line.cmp(&b.line)));
+    (stripped, removed)
+}
+
+/// `"ACME FIELD MANUAL" ×40; "Page 1" ×38`.
+pub fn report(removed: &[Removed]) -> String {
+    removed.iter().map(|r| format!("\"{}\" ×{}", r.line, r.pages)).collect::<Vec<_>>().join("; ")
+}
+
+/// Boilerplate keys shared by the `.txt` files of each folder (`across_files`),
+/// computed once per folder per ingest (`keys_for`) and then only read (`get`).
+#[derive(Debug, Default)]
+pub struct FolderBoilerplate {
+    config: BoilerplateConfig,
+    guards: FileGuards,
+    dirs: HashMap<PathBuf, HashSet<String>>,
+}
+
+impl FolderBoilerplate {
+    /// Files `guards` would skip (oversized, binary) are not compared.
+    pub fn new(config: BoilerplateConfig, guards: FileGuards) -> Self { Self { config, guards, dirs: HashMap::new() } }
+
+    /// Keys for files in `dir` (empty unless `across_files`).
+    pub fn keys_for(&mut self, dir: &Path) -> &HashSet<String> {
+        let (config, guards) = (&self.config, &self.guards);
+        self.dirs.entry(dir.to_path_buf()).or_insert_with(|| {
+            if !config.enabled || !config.across_files { return HashSet::new(); }
+            let mut files: Vec<PathBuf> = fs::read_dir(dir).into_iter().flatten().filter_map(|e| e.ok())
+                .filter(|e| e.metadata().is_ok_and(|m| m.is_file() && !guards.oversized(m.len())))
+                .map(|e| e.path()).filter(|p| p.extension().and_then(|e| e.to_str()) == Some("txt")).collect();
+            files.sort();
+            let texts: Vec<String> = files.iter().filter_map(|p| fs::read(p).ok()).filter(|b| guards.rejects_text(b).is_none()).map(crate::charset::decode).collect();
+            detect(&texts, config)
+        })
+    }
+
+    /// Keys already computed for `dir`.
+    pub fn get(&self, dir: &Path) -> Option<&HashSet<String>> { self.dirs.get(dir) }
+}
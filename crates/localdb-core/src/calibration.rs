@@ -0,0 +1,164 @@
+//! Score calibration for hybrid fusion.
+//!
+//! BM25 scores are unbounded and depend on the corpus; cosine similarities sit
+//! in a narrow band that depends on the embedder. Weighting one against the
+//! other is only meaningful once both are mapped to the same scale.
+//! `localdb-cli calibrate` turns the pairwise judgments of the eval dataset
+//! into relevance labels (`labels`), looks up the raw score each leg gives the
+//! judged chunks (`samples`), and fits one monotone (isotonic) map per leg
+//! from raw score to probability of relevance (`ScoreCalibration::fit`). The
+//! result is stored in the Lance `meta` table and applied to each leg's scores
+//! before fusion.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::eval::{Judgment, Preference};
+use crate::types::{SearchHit, SourceKind};
+
+/// Fewest labelled scores (with both outcomes present) a leg needs to be fitted.
+pub const MIN_SAMPLES: usize = 20;
+
+/// Hits per leg searched for the judged chunks of each query.
+pub const SAMPLE_DEPTH: usize = 100;
+
+/// A judged chunk of a query: relevant (the preferred side) or not (the other
+/// side, or both sides of a both-bad judgment).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub query: String,
+    pub id: String,
+    pub relevant: bool,
+}
+
+/// One raw score with its label.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub source: SourceKind,
+    pub score: f32,
+    pub relevant: bool,
+}
+
+/// Labels implied by `judgments`; ties say nothing about relevance and are
+/// skipped. A chunk judged again for the same query keeps its latest label.
+pub fn labels(judgments: &[Judgment]) -> Vec<Label> {
+    let mut latest: HashMap<(&str, &str), (usize, bool)> = HashMap::new();
+    for (i, j) in judgments.iter().enumerate() {
+        let (left, right) = match j.preference {
+            Preference::Left => (true, false),
+            Preference::Right => (false, true),
+            Preference::BothBad => (false, false),
+            Preference::Tie => continue,
+        };
+        latest.insert((j.query.as_str(), j.left.id.as_str()), (i, left));
+        latest.insert((j.query.as_str(), j.right.id.as_str()), (i, right));
+    }
+    let mut labels: Vec<(usize, Label)> = latest.into_iter()
+        .map(|((query, id), (i, relevant))| (i, Label { query: query.to_string(), id: id.to_string(), relevant })).collect();
+    labels.sort_by(|(i, a), (j, b)| i.cmp(j).then_with(|| a.id.cmp(&b.id)));
+    labels.into_iter().map(|(_, l)| l).collect()
+}
+
+/// Raw scores of the labelled chunks. `legs` returns the unfused hits of both
+/// legs for a query (see `HybridSearchEngine::leg_hits`); a chunk a leg did
+/// not return gives no sample for that leg.
+pub fn samples(labels: &[Label], mut legs: impl FnMut(&str) -> Result<Vec<SearchHit>>) -> Result<Vec<Sample>> {
+    let mut by_query: Vec<(&str, Vec<&Label>)> = Vec::new();
+    for l in labels {
+        match by_query.iter_mut().find(|(q, _)| *q == l.query) {
+            Some((_, ls)) => ls.push(l),
+            None => by_query.push((l.query.as_str(), vec![l])),
+        }
+    }
+    let mut samples = Vec::new();
+    for (query, labels) in by_query {
+        let hits = legs(query)?;
+        for l in labels {
+            samples.extend(hits.iter().filter(|h| h.id == l.id).map(|h| Sample { source: h.source, score: h.score, relevant: l.relevant }));
+        }
+    }
+    Ok(samples)
+}
+
+/// Monotone piecewise-linear map from raw score to probability, fitted by
+/// pool-adjacent-violators. Below the first knot and above the last the end
+/// values hold.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Isotonic {
+    /// Raw scores, ascending.
+    pub xs: Vec<f32>,
+    /// Probabilities at `xs`, non-decreasing.
+    pub ys: Vec<f32>,
+}
+
+impl Isotonic {
+    /// Fit to `(score, relevant)` pairs; `None` without both outcomes.
+    pub fn fit(points: &[(f32, bool)]) -> Option<Self> {
+        if !points.iter().any(|p| p.1) || !points.iter().any(|p| !p.1) { return None; }
+        let mut sorted: Vec<(f32, f32)> = points.iter().filter(|p| p.0.is_finite()).map(|&(x, r)| (x, if r { 1.0 } else { 0.0 })).collect();
+        sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+        // Blocks of (lowest x, highest x, sum of labels, count).
+        let mut blocks: Vec<(f32, f32, f32, f32)> = Vec::new();
+        for (x, y) in sorted {
+            match blocks.last_mut() {
+                Some(b) if b.1 == x => { b.2 += y; b.3 += 1.0; }
+                _ => blocks.push((x, x, y, 1.0)),
+            }
+            while blocks.len() > 1 {
+                let (b, a) = (blocks[blocks.len() - 1], blocks[blocks.len() - 2]);
+                if a.2 / a.3 <= b.2 / b.3 { break; }
+                blocks.truncate(blocks.len() - 2);
+                blocks.push((a.0, b.1, a.2 + b.2, a.3 + b.3));
+            }
+        }
+        let (mut xs, mut ys) = (Vec::new(), Vec::new());
+        for (lo, hi, sum, n) in blocks {
+            xs.push(lo);
+            ys.push(sum / n);
+            if hi > lo { xs.push(hi); ys.push(sum / n); }
+        }
+        Some(Self { xs, ys })
+    }
+
+    pub fn predict(&self, score: f32) -> f32 {
+        let (Some(&first), Some(&last)) = (self.xs.first(), self.xs.last()) else { return score };
+        if score <= first { return self.ys[0]; }
+        if score >= last { return self.ys[self.ys.len() - 1]; }
+        let i = self.xs.partition_point(|&x| x <= score);
+        let (x0, x1, y0, y1) = (self.xs[i - 1], self.xs[i], self.ys[i - 1], self.ys[i]);
+        y0 + (y1 - y0) * (score - x0) / (x1 - x0)
+    }
+}
+
+/// Per-leg calibration; an unfitted leg keeps its raw scores.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScoreCalibration {
+    pub text: Option<Isotonic>,
+    pub vector: Option<Isotonic>,
+}
+
+impl ScoreCalibration {
+    /// Fit each leg that has at least `MIN_SAMPLES` samples.
+    pub fn fit(samples: &[Sample]) -> Self {
+        let leg = |source: SourceKind| {
+            let points: Vec<(f32, bool)> = samples.iter().filter(|s| s.source == source).map(|s| (s.score, s.relevant)).collect();
+            if points.len() < MIN_SAMPLES { None } else { Isotonic::fit(&points) }
+        };
+        Self { text: leg(SourceKind::Text), vector: leg(SourceKind::Vector) }
+    }
+
+    pub fn is_empty(&self) -> bool { self.text.is_none() && self.vector.is_none() }
+
+    /// `score` of a `source` hit as a probability of relevance.
+    pub fn apply(&self, source: SourceKind, score: f32) -> f32 {
+        let leg = match source { SourceKind::Text => &self.text, SourceKind::Vector => &self.vector };
+        leg.as_ref().map_or(score, |f| f.predict(score))
+    }
+
+    /// JSON, as stored in the Lance `meta` table.
+    pub fn encode(&self) -> String { serde_json::to_string(self).unwrap_or_default() }
+
+    /// Inverse of `encode`; anything unreadable is no calibration.
+    pub fn decode(s: &str) -> Self { serde_json::from_str(s).unwrap_or_default() }
+}
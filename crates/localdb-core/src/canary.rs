@@ -0,0 +1,104 @@
+//! Startup self-test on a built-in canary corpus.
+//!
+//! Each ingest with a working embedder also indexes the few short documents
+//! below into a hidden vector collection (`COLLECTION`), replacing what an
+//! earlier ingest left there. On startup the server re-embeds the `QUERIES`
+//! with the model it loaded and checks that each still finds its document
+//! first, that the stored vectors are finite and non-zero, and that the
+//! collections have the model's width. A swapped model, a wrong dimension or
+//! corrupted vectors fail here, with a loud warning, instead of as silently
+//! bad results.
+
+use anyhow::Result;
+use std::fmt;
+
+use crate::data_processor::chunk_id;
+use crate::types::{DocumentChunk, SearchHit};
+
+/// Hidden vector collection holding the canary documents.
+pub const COLLECTION: &str = "_canary";
+
+/// `(doc_id, text)` of the canary documents; topics far enough apart that
+/// any working model tells them apart.
+pub const DOCS: &[(&str, &str)] = &[
+    ("water", "Boil water for at least one minute to kill germs before drinking it. At high altitude boil it for three minutes, then let it cool in a covered pot."),
+    ("fire", "A bow drill starts a fire by friction: spin a dry spindle against a softwood hearth board until the powder it grinds out smoulders, then blow the ember into tinder."),
+    ("canning", "Pack tomatoes into clean glass jars, add lemon juice for acidity, seal the lids and process the jars in a boiling water bath for forty minutes."),
+    ("solar", "Wire the solar panels through a charge controller to the battery bank; the controller stops the batteries from overcharging on sunny days."),
+];
+
+/// `(query, doc_id of the document it must rank first)`.
+pub const QUERIES: &[(&str, &str)] = &[
+    ("how do I make drinking water safe", "water"),
+    ("start a fire without matches", "fire"),
+    ("preserve tomatoes in jars", "canning"),
+    ("connect solar panels to batteries", "solar"),
+];
+
+/// The canary documents as one chunk each.
+pub fn chunks() -> Vec<DocumentChunk> {
+    DOCS.iter().map(|&(doc_id, text)| DocumentChunk {
+        id: chunk_id(doc_id, text, 0), doc_id: doc_id.to_string(), doc_path: format!("{}/{}.txt", COLLECTION, doc_id),
+        category: format!("/{}", COLLECTION), category_text: COLLECTION.to_string(), content: text.to_string(),
+        chunk_index: 0, total_chunks: 1, title: None, author: None, created_at: None, lang: None, meta: Default::default(), span: None,
+    }).collect()
+}
+
+/// Something the self-test found wrong.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// `collection` stores vectors of another width than the model produces.
+    DimMismatch { collection: String, stored: usize, model: usize },
+    /// `count` of the vectors checked in `collection` are NaN, infinite or all zero.
+    BadVectors { collection: String, count: usize, checked: usize },
+    /// `query` did not rank its document first; `got` is what it ranked first.
+    Missed { query: String, expected: String, got: Option<String> },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::DimMismatch { collection, stored, model } => write!(f, "collection '{}' stores {}-dim vectors but the model produces {}-dim vectors", collection, stored, model),
+            Problem::BadVectors { collection, count, checked } => write!(f, "{} of {} sampled vectors in '{}' are NaN, infinite or zero", count, checked, collection),
+            Problem::Missed { query, expected, got: Some(got) } => write!(f, "query \"{}\" ranked '{}' first instead of '{}'", query, got, expected),
+            Problem::Missed { query, expected, got: None } => write!(f, "query \"{}\" found nothing (expected '{}')", query, expected),
+        }
+    }
+}
+
+/// A vector that no model produces: any NaN or infinite component, or all zeros.
+pub fn is_bad_vector(v: &[f32]) -> bool { v.iter().any(|x| !x.is_finite()) || v.iter().all(|&x| x == 0.0) }
+
+/// `BadVectors` for `collection` when any of `vectors` is bad.
+pub fn check_vectors(collection: &str, vectors: &[Vec<f32>]) -> Option<Problem> {
+    let count = vectors.iter().filter(|v| is_bad_vector(v)).count();
+    (count > 0).then(|| Problem::BadVectors { collection: collection.to_string(), count, checked: vectors.len() })
+}
+
+/// `DimMismatch` for `collection` when its recorded width is not `model`'s.
+pub fn check_dim(collection: &str, stored: Option<usize>, model: usize) -> Option<Problem> {
+    stored.filter(|&s| s != model).map(|stored| Problem::DimMismatch { collection: collection.to_string(), stored, model })
+}
+
+/// Run every `QUERIES` entry through `search` (ranked hits of the canary
+/// collection) and report those whose document is not ranked first.
+pub fn check_queries(mut search: impl FnMut(&str) -> Result<Vec<SearchHit>>) -> Result<Vec<Problem>> {
+    let docs = chunks();
+    let doc_of = |id: &str| docs.iter().find(|c| c.id == id).map_or_else(|| id.to_string(), |c| c.doc_id.clone());
+    let mut problems = Vec::new();
+    for &(query, expected) in QUERIES {
+        let got = search(query)?.first().map(|h| doc_of(&h.id));
+        if got.as_deref() != Some(expected) {
+            problems.push(Problem::Missed { query: query.to_string(), expected: expected.to_string(), got });
+        }
+    }
+    Ok(problems)
+}
+
+/// The startup warning for `problems`, one line per problem.
+pub fn warning(problems: &[Problem]) -> String {
+    let mut out = String::from("🚨 SELF-TEST FAILED: the embedding model and the vector index do not match; vector results are unreliable\n");
+    for p in problems { out.push_str(&format!("🚨   {}\n", p)); }
+    out.push_str("🚨 Re-ingest with the model the index was built with, or rebuild the index (ingest --full).");
+    out
+}
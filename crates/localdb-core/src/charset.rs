@@ -0,0 +1,38 @@
+//! Text encoding detection for ingested text files.
+//!
+//! Old survival manuals and mirrored forums come as Latin-1/Windows-1252,
+//! Windows-1251 or KOI8-R as often as UTF-8. Decoding them as lossy UTF-8
+//! turns every accented or Cyrillic letter into U+FFFD, which neither BM25
+//! nor the embedder can match. A byte order mark wins; valid UTF-8 is taken
+//! as is; anything else is decoded in the encoding `chardetng` guesses from
+//! the byte statistics (`encoding_rs` does the decoding). Without the
+//! `charset` feature anything but UTF-8 is decoded lossily.
+
+#[cfg(feature = "charset")]
+use encoding_rs::{Encoding, UTF_8};
+
+/// The encoding `bytes` are most likely in: the one their BOM names, UTF-8
+/// when they are valid UTF-8, else `chardetng`'s guess.
+#[cfg(feature = "charset")]
+pub fn detect(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) { return encoding; }
+    if std::str::from_utf8(bytes).is_ok() { return UTF_8; }
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, false)
+}
+
+/// `bytes` as text in the encoding `detect` finds, without the BOM.
+/// Sequences still malformed in that encoding become U+FFFD.
+pub fn decode(bytes: Vec<u8>) -> String {
+    match String::from_utf8(bytes) {
+        Ok(text) => match text.strip_prefix('\u{feff}') { Some(rest) => rest.to_string(), None => text },
+        Err(e) => decode_legacy(e.into_bytes()),
+    }
+}
+
+#[cfg(feature = "charset")]
+fn decode_legacy(bytes: Vec<u8>) -> String { detect(&bytes).decode(&bytes).0.into_owned() }
+
+#[cfg(not(feature = "charset"))]
+fn decode_legacy(bytes: Vec<u8>) -> String { String::from_utf8_lossy(&bytes).into_owned() }
@@ -0,0 +1,222 @@
+//! Splitting document text into chunks.
+//!
+//! `Chunker` (in `traits`) is the extension point: `DataProcessor::with_chunker`
+//! plugs in a domain-specific splitter (recipes, code, ...) without forking
+//! the processor. `ParagraphChunker` is the default: paragraphs (blank-line
+//! separated) become chunks, and long ones are split with overlap by words or
+//! whole sentences, or cut where sentence embeddings diverge
+//! (`ChunkingStrategy`), within `max_tokens` as the embedder's tokenizer
+//! counts them (`with_token_counter`; otherwise word count / 0.75). A custom
+//! chunker can wrap one to split what it does not recognize.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::data_processor::{next_chunk_id, ChunkingConfig, ChunkingStrategy};
+use crate::summary::sentence_spans;
+use crate::traits::{Chunker, Embedder, TokenCounter};
+use crate::types::{ChunkSource, DocumentChunk, Meta};
+
+/// The default `Chunker` (see the module docs), configured by `[chunking]`.
+#[derive(Clone, Default)]
+pub struct ParagraphChunker {
+    pub(crate) config: ChunkingConfig,
+    tokens: Option<Arc<dyn TokenCounter>>,
+    sentence_embedder: Option<Arc<dyn Embedder>>,
+    sentence_batch: Option<usize>,
+}
+
+impl Chunker for ParagraphChunker {
+    fn chunk(&self, content: &str, source: &ChunkSource) -> Result<Vec<DocumentChunk>> {
+        self.chunk_sections(&[content], source.doc_id, Path::new(source.doc_path), source.category)
+    }
+}
+
+impl ParagraphChunker {
+    pub fn new(config: ChunkingConfig) -> Self { Self { config, ..Self::default() } }
+
+    /// Size chunks by the embedder's tokenizer: `max_tokens` is capped at its
+    /// `max_len` and long paragraphs are split into windows that fit.
+    pub fn with_token_counter(mut self, tokens: Arc<dyn TokenCounter>) -> Self { self.tokens = Some(tokens); self }
+
+    /// Embed sentences with `embedder` to place `ChunkingStrategy::Semantic`
+    /// boundaries.
+    pub fn with_sentence_embedder(mut self, embedder: Arc<dyn Embedder>) -> Self { self.sentence_embedder = Some(embedder); self }
+
+    /// Embed at most `batch_size` sentences at a time (`embedding.batch_size`
+    /// in the CLI); by default a section's sentences are one batch.
+    pub fn with_sentence_batch_size(mut self, batch_size: usize) -> Self { self.sentence_batch = Some(batch_size.max(1)); self }
+
+    /// Paragraph chunks of consecutive sections (EPUB chapters): chunks never
+    /// cross a section boundary; ids and `chunk_index` run across the document.
+    pub(crate) fn chunk_sections<S: AsRef<str>>(&self, sections: &[S], doc_id: &str, file_path: &Path, category: &str) -> Result<Vec<DocumentChunk>> {
+        if let Some(embedder) = self.semantic_embedder() { return self.chunk_semantic(sections, embedder, doc_id, file_path, category); }
+        let paragraphs: Vec<&str> = sections.iter().flat_map(|s| s.as_ref().split("\n\n")).collect();
+        let mut document_chunks = Vec::new();
+        let mut chunk_index = 0;
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        for paragraph in paragraphs {
+            let paragraph = paragraph.trim(); if paragraph.is_empty() { continue; }
+            if self.count_tokens(paragraph) <= self.max_tokens() {
+                document_chunks.push(DocumentChunk { id: next_chunk_id(&mut seen, doc_id, paragraph), doc_id: doc_id.to_string(), doc_path: file_path.to_string_lossy().to_string(), category: category.to_string(), category_text: category.to_string(), content: paragraph.to_string(), chunk_index, total_chunks: 0, title: None, author: None, created_at: None, lang: None, meta: Meta::new(), span: None });
+                chunk_index += 1;
+            } else {
+                for sub_chunk in self.split_paragraph_with_overlap(paragraph) {
+                    document_chunks.push(DocumentChunk { id: next_chunk_id(&mut seen, doc_id, &sub_chunk), doc_id: doc_id.to_string(), doc_path: file_path.to_string_lossy().to_string(), category: category.to_string(), category_text: category.to_string(), content: sub_chunk, chunk_index, total_chunks: 0, title: None, author: None, created_at: None, lang: None, meta: Meta::new(), span: None });
+                    chunk_index += 1;
+                }
+            }
+        }
+        let total_chunks = document_chunks.len(); for chunk in &mut document_chunks { chunk.total_chunks = total_chunks; }
+        Ok(document_chunks)
+    }
+
+    /// The embedder for semantic chunking, when that strategy is on and one is set.
+    fn semantic_embedder(&self) -> Option<&dyn Embedder> {
+        self.sentence_embedder.as_deref().filter(|_| self.config.strategy == ChunkingStrategy::Semantic)
+    }
+
+    /// `ChunkingStrategy::Semantic` over consecutive sections: each section's
+    /// sentences are embedded `sentence_batch` at a time and grouped greedily;
+    /// chunks never cross a section boundary.
+    fn chunk_semantic<S: AsRef<str>>(&self, sections: &[S], embedder: &dyn Embedder, doc_id: &str, file_path: &Path, category: &str) -> Result<Vec<DocumentChunk>> {
+        let max = self.max_tokens();
+        let mut pieces = Vec::new();
+        for section in sections {
+            let section = section.as_ref();
+            let sentences: Vec<&str> = sentence_spans(section).into_iter().map(|r| &section[r]).collect();
+            if sentences.is_empty() { continue; }
+            let texts: Vec<String> = sentences.iter().map(|s| s.to_string()).collect();
+            let mut vectors = Vec::with_capacity(texts.len());
+            for batch in texts.chunks(self.sentence_batch.unwrap_or(texts.len())) { vectors.extend(embedder.embed_batch(batch)?); }
+            let mut run: Vec<&str> = Vec::new();
+            for (i, sentence) in sentences.iter().enumerate() {
+                // Vectors are L2-normalized, so the dot product is the cosine.
+                let distance = if i == 0 { 0.0 } else { 1.0 - vectors[i - 1].iter().zip(&vectors[i]).map(|(a, b)| a * b).sum::<f32>() };
+                let grown = if run.is_empty() { sentence.to_string() } else { format!("{} {}", run.join(" "), sentence) };
+                if !run.is_empty() && (distance > self.config.semantic_threshold || self.count_tokens(&grown) > max) { pieces.push(run.join(" ")); run.clear(); }
+                if self.count_tokens(sentence) > max { pieces.extend(self.split_words(sentence)); } else { run.push(sentence); }
+            }
+            if !run.is_empty() { pieces.push(run.join(" ")); }
+        }
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        let total_chunks = pieces.len();
+        Ok(pieces.into_iter().enumerate().map(|(chunk_index, content)| DocumentChunk { id: next_chunk_id(&mut seen, doc_id, &content), doc_id: doc_id.to_string(), doc_path: file_path.to_string_lossy().to_string(), category: category.to_string(), category_text: category.to_string(), content, chunk_index, total_chunks, title: None, author: None, created_at: None, lang: None, meta: Meta::new(), span: None }).collect())
+    }
+
+    /// Id of the last chunk `chunk_sections` made from each paragraph of `sections`.
+    pub(crate) fn paragraph_ends(&self, sections: &[String], chunks: &[DocumentChunk]) -> Vec<String> {
+        if self.semantic_embedder().is_some() { return semantic_paragraph_ends(sections, chunks); }
+        let mut ends = Vec::new();
+        let mut produced = 0;
+        for paragraph in sections.iter().flat_map(|s| s.split("\n\n")).map(str::trim).filter(|p| !p.is_empty()) {
+            produced += if self.count_tokens(paragraph) <= self.max_tokens() { 1 } else { self.split_paragraph_with_overlap(paragraph).len() };
+            if let Some(chunk) = produced.checked_sub(1).and_then(|i| chunks.get(i)) { ends.push(chunk.id.clone()); }
+        }
+        ends
+    }
+
+    /// Token count from the token counter, else word count divided by a constant.
+    pub(crate) fn count_tokens(&self, text: &str) -> usize {
+        match &self.tokens {
+            Some(tokens) => tokens.count_tokens(text),
+            None => { let word_count = text.split_whitespace().count(); (word_count as f32 / 0.75) as usize }
+        }
+    }
+
+    /// Largest chunk in tokens: `max_tokens`, capped at the embedder's `max_len`.
+    pub(crate) fn max_tokens(&self) -> usize {
+        let max = self.config.max_tokens;
+        self.tokens.as_ref().map_or(max, |t| max.min(t.max_len()))
+    }
+
+    /// Break a long paragraph into overlapping windows of at most `max_tokens`
+    /// tokens each, per `ChunkingConfig::strategy`.
+    pub(crate) fn split_paragraph_with_overlap(&self, paragraph: &str) -> Vec<String> {
+        match self.config.strategy {
+            ChunkingStrategy::Words => self.split_words(paragraph),
+            ChunkingStrategy::Sentences | ChunkingStrategy::Semantic => self.split_sentences(paragraph),
+        }
+    }
+
+    /// Overlapping word windows.
+    pub(crate) fn split_words(&self, paragraph: &str) -> Vec<String> {
+        let words: Vec<&str> = paragraph.split_whitespace().collect();
+        let mut chunks = Vec::new(); let mut start = 0;
+        while start < words.len() {
+            let end = self.window_end(&words, start);
+            chunks.push(words[start..end].join(" "));
+            if end >= words.len() { break; }
+            // Clamp overlap below the window size so the window always advances
+            // (an overlap_percent >= 1.0 used to loop forever).
+            let window = end - start;
+            start = end - ((window as f32 * self.config.overlap_percent) as usize).min(window - 1);
+        }
+        chunks
+    }
+
+    /// Greedy runs of whole sentences (`sentence_spans`), each repeating the
+    /// last `overlap_percent` of the previous run's sentences.
+    fn split_sentences(&self, paragraph: &str) -> Vec<String> {
+        let spans = sentence_spans(paragraph);
+        let max = self.max_tokens();
+        let text = |from: usize, to: usize| &paragraph[spans[from].start..spans[to - 1].end];
+        let mut chunks = Vec::new(); let mut start = 0;
+        while start < spans.len() {
+            let mut end = start;
+            while end < spans.len() && self.count_tokens(text(start, end + 1)) <= max { end += 1; }
+            if end == start {
+                chunks.extend(self.split_words(text(start, start + 1)));
+                start += 1;
+                continue;
+            }
+            chunks.push(text(start, end).to_string());
+            if end >= spans.len() { break; }
+            let window = end - start;
+            let mut overlap = ((window as f32 * self.config.overlap_percent) as usize).min(window - 1);
+            // Shrink the overlap until the next run gains a sentence.
+            while overlap > 0 && self.count_tokens(text(end - overlap, end + 1)) > max { overlap -= 1; }
+            start = end - overlap;
+        }
+        chunks
+    }
+
+    /// End of the longest window starting at `start` that fits `max_tokens`
+    /// (at least one word, so an oversized word still advances). Every word
+    /// is at least one token, so no window is longer than `max_tokens` words.
+    fn window_end(&self, words: &[&str], start: usize) -> usize {
+        let max = self.max_tokens();
+        let fits = |end: usize| self.count_tokens(&words[start..end].join(" ")) <= max;
+        let (mut lo, mut hi) = (start + 1, words.len().min(start + max.max(1)));
+        if fits(hi) { return hi; }
+        // `lo` fits (or is the one-word minimum), `hi` does not.
+        while hi - lo > 1 {
+            let mid = lo + (hi - lo) / 2;
+            if fits(mid) { lo = mid; } else { hi = mid; }
+        }
+        lo
+    }
+}
+
+/// `paragraph_ends` for semantic chunks, which ignore paragraph breaks: the
+/// first chunk (in order) holding the paragraph's last sentence.
+fn semantic_paragraph_ends(sections: &[String], chunks: &[DocumentChunk]) -> Vec<String> {
+    let mut ends = Vec::new();
+    let mut at = 0;
+    for paragraph in sections.iter().flat_map(|s| s.split("\n\n")).map(str::trim).filter(|p| !p.is_empty()) {
+        let Some(last) = sentence_spans(paragraph).pop().map(|r| &paragraph[r]) else { continue };
+        while at < chunks.len() && !chunks[at].content.contains(last) { at += 1; }
+        let Some(chunk) = chunks.get(at) else { break };
+        ends.push(chunk.id.clone());
+    }
+    ends
+}
+
+/// Words `next` repeats from the end of `previous`: the overlap of
+/// consecutive windows of one paragraph, 0 between paragraphs.
+pub fn overlap_words(previous: &str, next: &str) -> usize {
+    let (previous, next): (Vec<&str>, Vec<&str>) = (previous.split_whitespace().collect(), next.split_whitespace().collect());
+    (1..previous.len().min(next.len())).rev().find(|&n| previous[previous.len() - n..] == next[..n]).unwrap_or(0)
+}
@@ -0,0 +1,159 @@
+//! Citation checks for answers generated from search results (RAG).
+//!
+//! A local model answering from retrieved chunks cites them by chunk id in
+//! brackets: `Boil jars for ten minutes [canning/jars:3f2a9c1b0d4e].`
+//! (several as `[a; b]` or `[a][b]`). Offline there is no second source to
+//! check it against, so `verify` checks each claim against what it cites:
+//! a claim (one sentence of the answer, see `claims`) is supported when a
+//! sentence of a cited chunk shares at least `min_overlap` of the claim's
+//! content words and, given an embedder, is at least `min_similarity` cosine
+//! similar to it. Everything else is flagged: unsupported, citing chunks that
+//! are not stored, or citing nothing.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+
+use crate::answer::content_words;
+use crate::config::Config;
+use crate::summary::sentence_spans;
+use crate::traits::Embedder;
+
+/// `[citations]` thresholds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CitationThresholds {
+    /// Share of the claim's content words a supporting sentence must contain.
+    pub min_overlap: f32,
+    /// Cosine similarity a supporting sentence must reach (with an embedder).
+    pub min_similarity: f32,
+}
+
+impl Default for CitationThresholds {
+    fn default() -> Self { Self { min_overlap: 0.5, min_similarity: 0.6 } }
+}
+
+impl CitationThresholds {
+    /// `citations.min_overlap` and `citations.min_similarity`, defaulting to 0.5 and 0.6.
+    pub fn from_config(config: &Config) -> Self {
+        let d = Self::default();
+        Self { min_overlap: config.get("citations.min_overlap").unwrap_or(d.min_overlap), min_similarity: config.get("citations.min_similarity").unwrap_or(d.min_similarity) }
+    }
+}
+
+/// One sentence of an answer and the chunk ids it cites.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Claim {
+    /// The sentence without its citations.
+    pub text: String,
+    pub cited: Vec<String>,
+}
+
+/// How a claim fares against its citations.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Support {
+    /// `sentence` of `chunk_id` backs the claim (`overlap` of its content
+    /// words; `similarity` when an embedder checked it).
+    Supported { chunk_id: String, sentence: String, overlap: f32, similarity: Option<f32> },
+    /// The cited chunks exist but none of their sentences backs the claim.
+    Unsupported,
+    /// None of the cited chunks is stored.
+    Missing,
+    /// The claim cites nothing.
+    Uncited,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckedClaim {
+    pub claim: Claim,
+    pub support: Support,
+}
+
+/// The claims of `answer`: its sentences with their bracketed citations
+/// taken out. Citations opening a sentence (`... ten minutes. [id]`) belong
+/// to the one before; sentences with no words left are dropped.
+pub fn claims(answer: &str) -> Vec<Claim> {
+    let mut out: Vec<Claim> = Vec::new();
+    for span in sentence_spans(answer) {
+        let (text, mut cited, leading) = strip_citations(&answer[span]);
+        let words = text.chars().any(char::is_alphanumeric);
+        if let Some(last) = out.last_mut() {
+            last.cited.extend(cited.drain(..leading));
+            if !words { last.cited.append(&mut cited); }
+        }
+        if words { out.push(Claim { text, cited }); }
+    }
+    for claim in &mut out { let mut seen = HashSet::new(); claim.cited.retain(|id| seen.insert(id.clone())); }
+    out
+}
+
+/// `sentence` without citations, the ids cited in order, and how many of
+/// them come before any other text.
+fn strip_citations(sentence: &str) -> (String, Vec<String>, usize) {
+    let mut text = String::new();
+    let mut cited = Vec::new();
+    let mut leading = 0;
+    let mut rest = sentence;
+    while let Some(open) = rest.find('[') {
+        let Some(close) = rest[open..].find(']').map(|c| open + c) else { break };
+        let ids = citation_ids(&rest[open + 1..close]);
+        if ids.is_empty() { text.push_str(&rest[..=close]); } else {
+            text.push_str(&rest[..open]);
+            if text.trim().is_empty() { leading += ids.len(); }
+            cited.extend(ids);
+        }
+        rest = &rest[close + 1..];
+    }
+    text.push_str(rest);
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ").replace(" .", ".").replace(" ,", ",");
+    (text, cited, leading)
+}
+
+/// Chunk ids in a bracket's contents (`doc:hash`, `;`/`,`-separated, no
+/// whitespace inside an id); none unless every part is one.
+fn citation_ids(inner: &str) -> Vec<String> {
+    let parts: Vec<&str> = inner.split([';', ',']).map(str::trim).collect();
+    if parts.iter().all(|p| !p.is_empty() && p.contains(':') && !p.contains(char::is_whitespace)) { parts.into_iter().map(str::to_string).collect() } else { Vec::new() }
+}
+
+/// Check every claim of `answer` against the stored text of the chunks it
+/// cites (`chunks`, by id), by content-word overlap and, with `embedder`,
+/// embedding similarity (see the module docs).
+pub fn verify(answer: &str, chunks: &HashMap<String, String>, embedder: Option<&dyn Embedder>, thresholds: CitationThresholds) -> Result<Vec<CheckedClaim>> {
+    let mut out = Vec::new();
+    for claim in claims(answer) {
+        let words = content_words(&claim.text);
+        let stored: Vec<&String> = claim.cited.iter().filter(|id| chunks.contains_key(*id)).collect();
+        let support = if claim.cited.is_empty() { Support::Uncited } else if stored.is_empty() { Support::Missing } else {
+            // Cheap overlap first; only the sentences that pass are embedded.
+            let mut candidates: Vec<(&String, &str, f32)> = Vec::new();
+            for id in stored {
+                let text = &chunks[id];
+                for span in sentence_spans(text) {
+                    let sentence = &text[span];
+                    let shared = content_words(sentence).intersection(&words).count();
+                    let overlap = if words.is_empty() { 0.0 } else { shared as f32 / words.len() as f32 };
+                    if overlap >= thresholds.min_overlap { candidates.push((id, sentence, overlap)); }
+                }
+            }
+            best_support(&claim.text, candidates, embedder, thresholds.min_similarity)?
+        };
+        out.push(CheckedClaim { claim, support });
+    }
+    Ok(out)
+}
+
+fn best_support(claim: &str, candidates: Vec<(&String, &str, f32)>, embedder: Option<&dyn Embedder>, min_similarity: f32) -> Result<Support> {
+    let supported = |(id, sentence, overlap): (&String, &str, f32), similarity| Support::Supported { chunk_id: id.clone(), sentence: sentence.to_string(), overlap, similarity };
+    let Some(embedder) = embedder.filter(|_| !candidates.is_empty()) else {
+        return Ok(candidates.into_iter().max_by(|a, b| a.2.total_cmp(&b.2)).map_or(Support::Unsupported, |c| supported(c, None)));
+    };
+    let texts: Vec<String> = std::iter::once(claim).chain(candidates.iter().map(|c| c.1)).map(str::to_string).collect();
+    let vectors = embedder.embed_batch(&texts)?;
+    // Embedders return L2-normalized vectors, so the dot product is the cosine.
+    let (claim_vec, sentence_vecs) = vectors.split_first().ok_or_else(|| anyhow::anyhow!("embedder returned no vectors"))?;
+    Ok(candidates.into_iter().zip(sentence_vecs)
+        .map(|(c, v)| (c, claim_vec.iter().zip(v).map(|(a, b)| a * b).sum::<f32>()))
+        .filter(|(_, similarity)| *similarity >= min_similarity)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or(Support::Unsupported, |(c, similarity)| supported(c, Some(similarity))))
+}
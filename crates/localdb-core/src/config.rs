@@ -1,3 +1,9 @@
+//! Lightweight configuration loader and path helpers.
+//!
+//! Uses Figment to merge `config.toml` + `config.<env>.toml` + `APP_*` env vars.
+//! Provides helpers to expand `~` and `${VAR}` and to resolve relative paths
+//! against a known base directory.
+
 use figment::{
     providers::{Env, Format, Toml},
     Figment,
@@ -5,6 +11,7 @@ use figment::{
 use std::env;
 use std::path::{Path, PathBuf};
 
+#[derive(Clone)]
 pub struct Config {
     figment: Figment,
 }
@@ -36,6 +43,11 @@ impl Config {
             .map_err(|e| anyhow::anyhow!("Failed to get '{}': {}", key, e))
     }
 
+    /// This config with `key` (a dotted path) set to `value`, over every source.
+    pub fn with_value(&self, key: &str, value: impl serde::Serialize) -> Self {
+        Self { figment: self.figment.clone().merge((key, value)) }
+    }
+
     fn validate_for_env(&self, env: &str) -> anyhow::Result<()> {
         match env {
             "dev" | "development" => {}
@@ -66,8 +78,51 @@ pub fn resolve_with_base<S: AsRef<str>>(base: &Path, p: S) -> PathBuf {
     let p = expand_path(p);
     if p.is_absolute() { p } else { base.join(p) }
 }
-//! Lightweight configuration loader and path helpers.
-//!
-//! Uses Figment to merge `config.toml` + `config.<env>.toml` + `APP_*` env vars.
-//! Provides helpers to expand `~` and `${VAR}` and to resolve relative paths
-//! against a known base directory.
+
+/// Absolute path for a stored `doc_path`, for display or opening the file.
+/// Stored paths are relative to the ingest data root with `/` separators;
+/// legacy absolute paths pass through. Without a root the relative path is
+/// returned unchanged.
+pub fn resolve_doc_path(data_root: Option<&Path>, stored: &str) -> PathBuf {
+    let p = Path::new(stored);
+    if p.is_absolute() { return p.to_path_buf(); }
+    match data_root {
+        Some(root) => stored.split('/').filter(|c| !c.is_empty()).fold(root.to_path_buf(), |acc, c| acc.join(c)),
+        None => p.to_path_buf(),
+    }
+}
+
+/// Set `key = "value"` under `[section]` of a TOML file, editing lines in
+/// place so comments and layout survive. Creates the key, section or file as
+/// needed.
+pub fn set_toml_string(path: &Path, section: &str, key: &str, value: &str) -> anyhow::Result<()> {
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+    set_toml_value(path, section, key, &format!("\"{}\"", escaped))
+}
+
+/// Like `set_toml_string`, but writes `raw` verbatim as the value (numbers,
+/// booleans, arrays).
+pub fn set_toml_value(path: &Path, section: &str, key: &str, raw: &str) -> anyhow::Result<()> {
+    let text = std::fs::read_to_string(path).unwrap_or_default();
+    let new_line = format!("{} = {}", key, raw);
+    let header = format!("[{}]", section);
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let start = lines.iter().position(|l| l.trim() == header);
+    match start {
+        None => {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) { lines.push(String::new()); }
+            lines.push(header);
+            lines.push(new_line);
+        }
+        Some(start) => {
+            let end = lines[start + 1..].iter().position(|l| l.trim_start().starts_with('[')).map(|i| start + 1 + i).unwrap_or(lines.len());
+            let existing = (start + 1..end).find(|&i| lines[i].split('=').next().is_some_and(|k| k.trim() == key));
+            match existing {
+                Some(i) => lines[i] = new_line,
+                None => lines.insert(start + 1, new_line),
+            }
+        }
+    }
+    std::fs::write(path, lines.join("\n") + "\n")?;
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! Corpus statistics: what an ingest actually produced, for a quick sanity
+//! check (a folder of scans that yielded no text, a facet that came out
+//! empty, one giant file that is half the index, chunks far under or over
+//! `max_tokens`). `CorpusStats::collect` summarizes a set of chunks; ingest
+//! prints it for the chunks it wrote and `localdb-cli stats --corpus` for
+//! everything stored, streamed through a `CorpusTally`. Files are counted
+//! from the catalog's `doc_path`s, so the fragments of one file (`<file>#…`)
+//! count once, under its extension.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+use crate::types::{file_candidates, DocumentChunk};
+
+/// Upper bounds (inclusive) of the token histogram's buckets; one more
+/// bucket counts the chunks above the last.
+pub const TOKEN_BUCKETS: [usize; 6] = [16, 32, 64, 128, 256, 512];
+
+/// One document's share of the corpus.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocSize {
+    pub doc_path: String,
+    pub chunks: usize,
+    pub tokens: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CorpusStats {
+    /// Distinct files the documents belong to.
+    pub files: usize,
+    pub chunks: usize,
+    pub tokens: usize,
+    /// Files per lowercased extension (`""` for none).
+    pub files_by_extension: BTreeMap<String, usize>,
+    pub chunks_by_category: BTreeMap<String, usize>,
+    /// Chunks per `TOKEN_BUCKETS` bucket, the last entry those above it.
+    pub token_histogram: Vec<usize>,
+    /// The documents with the most tokens, largest first.
+    pub largest_docs: Vec<DocSize>,
+}
+
+/// `CorpusStats` built one chunk at a time, so stored chunks can be
+/// streamed rather than loaded at once.
+#[derive(Debug, Clone)]
+pub struct CorpusTally { stats: CorpusStats, docs: HashMap<String, DocSize> }
+
+impl Default for CorpusTally {
+    fn default() -> Self { Self { stats: CorpusStats { token_histogram: vec![0; TOKEN_BUCKETS.len() + 1], ..CorpusStats::default() }, docs: HashMap::new() } }
+}
+
+impl CorpusTally {
+    /// Count `chunk`, `tokens` long.
+    pub fn add(&mut self, chunk: &DocumentChunk, tokens: usize) {
+        self.stats.chunks += 1;
+        self.stats.tokens += tokens;
+        *self.stats.chunks_by_category.entry(chunk.category.clone()).or_default() += 1;
+        self.stats.token_histogram[TOKEN_BUCKETS.partition_point(|&b| b < tokens)] += 1;
+        let doc = self.docs.entry(chunk.doc_path.clone()).or_insert_with(|| DocSize { doc_path: chunk.doc_path.clone(), chunks: 0, tokens: 0 });
+        doc.chunks += 1;
+        doc.tokens += tokens;
+    }
+
+    /// The statistics, keeping the `top` largest documents. Each document
+    /// counts toward the file in `files` (catalog `doc_path`s) it belongs
+    /// to, or, in none, its `doc_path` up to the first `#`.
+    pub fn finish(self, files: &HashSet<String>, top: usize) -> CorpusStats {
+        let mut stats = self.stats;
+        let mut seen: HashSet<&str> = HashSet::new();
+        for path in self.docs.keys() {
+            let file = file_candidates(path).find(|f| files.contains(*f)).unwrap_or_else(|| path.split('#').next().unwrap_or(path));
+            if !seen.insert(file) { continue; }
+            let ext = Path::new(file).extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+            *stats.files_by_extension.entry(ext).or_default() += 1;
+        }
+        stats.files = seen.len();
+        let mut largest: Vec<DocSize> = self.docs.into_values().collect();
+        largest.sort_by(|a, b| b.tokens.cmp(&a.tokens).then_with(|| a.doc_path.cmp(&b.doc_path)));
+        largest.truncate(top);
+        stats.largest_docs = largest;
+        stats
+    }
+}
+
+impl CorpusStats {
+    /// Statistics of `chunks` of the catalog's `files`, sized by
+    /// `count_tokens`, keeping the `top` largest documents.
+    pub fn collect(chunks: &[DocumentChunk], files: &HashSet<String>, count_tokens: impl Fn(&str) -> usize, top: usize) -> Self {
+        let mut tally = CorpusTally::default();
+        for c in chunks { tally.add(c, count_tokens(&c.content)); }
+        tally.finish(files, top)
+    }
+
+    /// Human-readable report, one section per statistic.
+    pub fn render(&self) -> String {
+        let mut out = format!("corpus: {} files, {} chunks, {} tokens\n", self.files, self.chunks, self.tokens);
+        fn by_count(m: &BTreeMap<String, usize>) -> Vec<(&String, &usize)> {
+            let mut v: Vec<(&String, &usize)> = m.iter().collect();
+            v.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+            v
+        }
+        let extensions: Vec<String> = by_count(&self.files_by_extension).into_iter().map(|(e, n)| format!("{} {}", if e.is_empty() { "(none)" } else { e }, n)).collect();
+        out.push_str(&format!("  files by extension: {}\n", extensions.join(", ")));
+        out.push_str("  chunks by category:\n");
+        for (category, n) in by_count(&self.chunks_by_category) { out.push_str(&format!("    {}: {}\n", if category.is_empty() { "(root)" } else { category }, n)); }
+        out.push_str("  tokens per chunk:\n");
+        let widest = self.token_histogram.iter().copied().max().unwrap_or(0).max(1);
+        for (i, n) in self.token_histogram.iter().enumerate() {
+            let label = match (i.checked_sub(1).map(|p| TOKEN_BUCKETS[p]), TOKEN_BUCKETS.get(i)) {
+                (None, Some(hi)) => format!("≤{}", hi),
+                (Some(lo), Some(hi)) => format!("{}-{}", lo + 1, hi),
+                (Some(lo), None) => format!(">{}", lo),
+                (None, None) => unreachable!("TOKEN_BUCKETS is not empty"),
+            };
+            out.push_str(&format!("    {:>8} {:>7} {}\n", label, n, "█".repeat(n * 30 / widest)));
+        }
+        if !self.largest_docs.is_empty() {
+            out.push_str("  largest documents:\n");
+            let total = self.tokens.max(1);
+            for d in &self.largest_docs { out.push_str(&format!("    {} — {} tokens in {} chunks ({:.0}%)\n", d.doc_path, d.tokens, d.chunks, d.tokens as f64 * 100.0 / total as f64)); }
+        }
+        out
+    }
+}
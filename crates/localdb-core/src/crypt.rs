@@ -0,0 +1,313 @@
+//! Optional encryption at rest for index directories (Tantivy, Lance).
+//!
+//! A sealed directory holds one key header (`.localdb-key`: Argon2id salt plus
+//! a passphrase verifier) and every other file replaced by `<name>.lenc`, its
+//! XChaCha20-Poly1305 encryption. Files are encrypted in 1 MiB segments (nonce
+//! = random per-file prefix + segment counter, last-segment flag as associated
+//! data), so large Lance fragments stream and truncation is detected.
+//!
+//! Engines only read plaintext, so the CLI decrypts a sealed directory into a
+//! scratch copy for each command (`unseal_into`) and seals changed copies back
+//! over it (`seal_into`); the directory itself is never plaintext. `lock` /
+//! `unlock` seal or decrypt it for good. Small files kept beside the indexes
+//! (the scratch pad) are encrypted whole with `seal_bytes`. Behind the
+//! `encryption` feature.
+
+use anyhow::Result;
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+
+/// Key header written at the root of a sealed directory.
+pub const KEY_FILE: &str = ".localdb-key";
+/// Extension appended to encrypted files.
+pub const SEALED_EXT: &str = "lenc";
+
+const KEY_MAGIC: &[u8; 8] = b"LDBKEY1\0";
+const FILE_MAGIC: &[u8; 8] = b"LDBENC1\0";
+const BYTES_MAGIC: &[u8; 8] = b"LDBBYT1\0";
+const VERIFIER: &[u8] = b"localdb-index-key";
+const SALT_LEN: usize = 16;
+const PREFIX_LEN: usize = 16;
+const SEGMENT: usize = 1 << 20;
+const TAG_LEN: usize = 16;
+
+/// Whether `dir` is currently sealed.
+pub fn is_sealed(dir: &Path) -> bool { dir.join(KEY_FILE).is_file() }
+
+/// Cipher derived from a passphrase and the directory's salt.
+struct DirKey { cipher: XChaCha20Poly1305 }
+
+impl DirKey {
+    fn derive(passphrase: &str, salt: &[u8]) -> Result<Self> {
+        let mut key = [0u8; 32];
+        Argon2::default().hash_password_into(passphrase.as_bytes(), salt, &mut key).map_err(|e| Error::Operation(format!("key derivation failed: {}", e)))?;
+        Ok(Self { cipher: XChaCha20Poly1305::new(Key::from_slice(&key)) })
+    }
+
+    /// New random salt; writes the key header into `dir`.
+    fn create(dir: &Path, passphrase: &str) -> Result<Self> {
+        let salt: [u8; SALT_LEN] = rand_bytes();
+        let key = Self::derive(passphrase, &salt)?;
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let verifier = key.cipher.encrypt(&nonce, VERIFIER).map_err(|_| Error::Operation("encryption failed".into()))?;
+        let mut header = Vec::with_capacity(8 + SALT_LEN + nonce.len() + verifier.len());
+        header.extend_from_slice(KEY_MAGIC);
+        header.extend_from_slice(&salt);
+        header.extend_from_slice(&nonce);
+        header.extend_from_slice(&verifier);
+        fs::write(dir.join(KEY_FILE), header)?;
+        Ok(key)
+    }
+
+    /// Read the key header of a sealed `dir` and check the passphrase.
+    fn open(dir: &Path, passphrase: &str) -> Result<Self> {
+        let header = fs::read(dir.join(KEY_FILE))?;
+        let body = header.strip_prefix(KEY_MAGIC.as_slice()).ok_or_else(|| Error::InvalidConfig(format!("{} is not a localdb key header", dir.join(KEY_FILE).display())))?;
+        if body.len() < SALT_LEN + 24 { return Err(Error::InvalidConfig("truncated key header".into()).into()); }
+        let (salt, rest) = body.split_at(SALT_LEN);
+        let (nonce, verifier) = rest.split_at(24);
+        let key = Self::derive(passphrase, salt)?;
+        match key.cipher.decrypt(XNonce::from_slice(nonce), verifier) {
+            Ok(v) if v == VERIFIER => Ok(key),
+            _ => Err(Error::Operation(format!("wrong passphrase for {}", dir.display())).into()),
+        }
+    }
+
+    fn nonce(prefix: &[u8; PREFIX_LEN], counter: u64) -> XNonce {
+        let mut n = [0u8; 24];
+        n[..PREFIX_LEN].copy_from_slice(prefix);
+        n[PREFIX_LEN..].copy_from_slice(&counter.to_be_bytes());
+        XNonce::clone_from_slice(&n)
+    }
+
+    fn encrypt_file(&self, src: &Path, dst: &Path) -> Result<()> {
+        let mut input = fs::File::open(src)?;
+        let mut out = fs::File::create(dst)?;
+        let prefix: [u8; PREFIX_LEN] = rand_bytes();
+        out.write_all(FILE_MAGIC)?;
+        out.write_all(&prefix)?;
+        let mut cur = vec![0u8; SEGMENT];
+        let mut next = vec![0u8; SEGMENT];
+        let mut n = read_full(&mut input, &mut cur)?;
+        let mut counter = 0u64;
+        loop {
+            let m = if n == SEGMENT { read_full(&mut input, &mut next)? } else { 0 };
+            let last = m == 0;
+            let ct = self.cipher.encrypt(&Self::nonce(&prefix, counter), Payload { msg: &cur[..n], aad: &[u8::from(last)] })
+                .map_err(|_| Error::Operation(format!("encryption failed for {}", src.display())))?;
+            out.write_all(&ct)?;
+            if last { break; }
+            std::mem::swap(&mut cur, &mut next);
+            n = m;
+            counter += 1;
+        }
+        out.sync_all()?;
+        Ok(())
+    }
+
+    fn decrypt_file(&self, src: &Path, dst: &Path) -> Result<()> {
+        let corrupt = || Error::Operation(format!("{} is corrupt or was encrypted with another key", src.display()));
+        let mut input = fs::File::open(src)?;
+        let mut head = [0u8; 8 + PREFIX_LEN];
+        if read_full(&mut input, &mut head)? != head.len() || &head[..8] != FILE_MAGIC { return Err(corrupt().into()); }
+        let mut prefix = [0u8; PREFIX_LEN];
+        prefix.copy_from_slice(&head[8..]);
+        let mut out = fs::File::create(dst)?;
+        let mut cur = vec![0u8; SEGMENT + TAG_LEN];
+        let mut next = vec![0u8; SEGMENT + TAG_LEN];
+        let mut n = read_full(&mut input, &mut cur)?;
+        let mut counter = 0u64;
+        loop {
+            let m = if n == cur.len() { read_full(&mut input, &mut next)? } else { 0 };
+            let last = m == 0;
+            let pt = self.cipher.decrypt(&Self::nonce(&prefix, counter), Payload { msg: &cur[..n], aad: &[u8::from(last)] }).map_err(|_| corrupt())?;
+            out.write_all(&pt)?;
+            if last { break; }
+            std::mem::swap(&mut cur, &mut next);
+            n = m;
+            counter += 1;
+        }
+        out.sync_all()?;
+        Ok(())
+    }
+}
+
+/// Whether `bytes` came from `seal_bytes`.
+pub fn is_sealed_bytes(bytes: &[u8]) -> bool { bytes.starts_with(BYTES_MAGIC) }
+
+/// Encrypt `plain` whole under a key derived from `passphrase` and a new
+/// salt, which are stored with it.
+pub fn seal_bytes(plain: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let salt: [u8; SALT_LEN] = rand_bytes();
+    let key = DirKey::derive(passphrase, &salt)?;
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ct = key.cipher.encrypt(&nonce, plain).map_err(|_| Error::Operation("encryption failed".into()))?;
+    let mut out = Vec::with_capacity(BYTES_MAGIC.len() + SALT_LEN + nonce.len() + ct.len());
+    out.extend_from_slice(BYTES_MAGIC);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ct);
+    Ok(out)
+}
+
+/// Decrypt what `seal_bytes` made; a wrong passphrase or altered bytes fail.
+pub fn open_bytes(sealed: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let corrupt = || Error::Operation("wrong passphrase, or the data is corrupt".into());
+    let body = sealed.strip_prefix(BYTES_MAGIC.as_slice()).ok_or_else(corrupt)?;
+    if body.len() < SALT_LEN + 24 { return Err(corrupt().into()); }
+    let (salt, rest) = body.split_at(SALT_LEN);
+    let (nonce, ct) = rest.split_at(24);
+    let key = DirKey::derive(passphrase, salt)?;
+    Ok(key.cipher.decrypt(XNonce::from_slice(nonce), ct).map_err(|_| corrupt())?)
+}
+
+/// Fail unless `passphrase` opens the sealed `dir`.
+pub fn check_passphrase(dir: &Path, passphrase: &str) -> Result<()> { DirKey::open(dir, passphrase).map(|_| ()) }
+
+/// Encrypt every file under `dir` with a key derived from `passphrase`.
+/// Returns the number of files sealed. The sealed copy is built beside `dir`
+/// and swapped in whole (see `seal_into`).
+pub fn seal_dir(dir: &Path, passphrase: &str) -> Result<usize> {
+    if is_sealed(dir) { return Err(Error::Operation(format!("{} is already sealed", dir.display())).into()); }
+    seal_into(dir, dir, passphrase)
+}
+
+/// Decrypt a sealed `dir` for good. Returns the number of files restored.
+/// A wrong passphrase fails before any file is touched; the plaintext copy
+/// is built beside `dir` and swapped in whole.
+pub fn unseal_dir(dir: &Path, passphrase: &str) -> Result<usize> {
+    recover(dir)?;
+    let staged = sibling(dir, "staged");
+    let n = unseal_into(dir, &staged, passphrase)?;
+    replace_dir(dir, &staged)?;
+    Ok(n)
+}
+
+/// Decrypt a sealed `dir` into `dest` (created if missing), leaving `dir`
+/// sealed. Returns the number of files restored.
+pub fn unseal_into(dir: &Path, dest: &Path, passphrase: &str) -> Result<usize> {
+    if !is_sealed(dir) { return Err(Error::Operation(format!("{} is not sealed", dir.display())).into()); }
+    let key = DirKey::open(dir, passphrase)?;
+    let sealed: Vec<PathBuf> = walkdir::WalkDir::new(dir).into_iter().filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file() && e.path().extension().is_some_and(|x| x == SEALED_EXT))
+        .map(|e| e.into_path()).collect();
+    fs::create_dir_all(dest)?;
+    for f in &sealed {
+        let dst = dest.join(f.strip_prefix(dir).unwrap_or(f)).with_extension("");
+        if let Some(parent) = dst.parent() { fs::create_dir_all(parent)?; }
+        key.decrypt_file(f, &dst)?;
+    }
+    Ok(sealed.len())
+}
+
+/// Seal the plaintext directory `src` as `dir` under a new key. Nothing at
+/// `dir` changes until every file is encrypted: the sealed copy is built in
+/// `<dir>.staged` and swapped in by rename (see `recover`). `src` may be
+/// `dir` itself. Returns the number of files sealed.
+pub fn seal_into(src: &Path, dir: &Path, passphrase: &str) -> Result<usize> {
+    recover(dir)?;
+    let staged = sibling(dir, "staged");
+    fs::create_dir_all(&staged)?;
+    let files = plain_files(src);
+    let key = DirKey::create(&staged, passphrase)?;
+    for f in &files {
+        let dst = sealed_path(&staged.join(f.strip_prefix(src).unwrap_or(f)));
+        if let Some(parent) = dst.parent() { fs::create_dir_all(parent)?; }
+        key.encrypt_file(f, &dst)?;
+    }
+    replace_dir(dir, &staged)?;
+    Ok(files.len())
+}
+
+/// Finish or undo a `seal_into`/`unseal_dir` that was interrupted: a staged
+/// copy is swapped in only if the original was already moved aside, else
+/// it is dropped.
+pub fn recover(dir: &Path) -> Result<()> {
+    let (staged, old) = (sibling(dir, "staged"), sibling(dir, "old"));
+    if !dir.exists() && old.is_dir() && staged.is_dir() { fs::rename(&staged, dir)?; }
+    if staged.exists() { fs::remove_dir_all(&staged)?; }
+    if old.exists() { fs::remove_dir_all(&old)?; }
+    Ok(())
+}
+
+/// Move `staged` to `dir`, the previous `dir` aside to `<dir>.old` first.
+fn replace_dir(dir: &Path, staged: &Path) -> Result<()> {
+    let old = sibling(dir, "old");
+    if old.exists() { fs::remove_dir_all(&old)?; }
+    fs::create_dir_all(dir)?;
+    fs::rename(dir, &old)?;
+    fs::rename(staged, dir)?;
+    fs::remove_dir_all(&old)?;
+    Ok(())
+}
+
+/// `<dir>.<suffix>` beside `dir`.
+fn sibling(dir: &Path, suffix: &str) -> PathBuf {
+    let mut name = dir.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Passphrase from `LOCALDB_PASSPHRASE`, else read from stdin after `prompt`.
+pub fn read_passphrase(prompt: &str) -> Result<String> {
+    if let Some(p) = std::env::var("LOCALDB_PASSPHRASE").ok().filter(|p| !p.is_empty()) { return Ok(p); }
+    prompt_line(prompt)
+}
+
+/// `read_passphrase` for a new key: typed passphrases are asked for twice
+/// and must match.
+pub fn read_new_passphrase(prompt: &str) -> Result<String> {
+    if let Some(p) = std::env::var("LOCALDB_PASSPHRASE").ok().filter(|p| !p.is_empty()) { return Ok(p); }
+    let pass = prompt_line(prompt)?;
+    if prompt_line("Repeat the passphrase: ")? != pass { return Err(Error::InvalidConfig("passphrases do not match".into()).into()); }
+    Ok(pass)
+}
+
+fn prompt_line(prompt: &str) -> Result<String> {
+    eprint!("{}", prompt);
+    std::io::stderr().flush()?;
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    let pass = line.trim_end_matches(['\r', '\n']).to_string();
+    if pass.is_empty() { return Err(Error::InvalidConfig("empty passphrase".into()).into()); }
+    Ok(pass)
+}
+
+fn plain_files(dir: &Path) -> Vec<PathBuf> {
+    walkdir::WalkDir::new(dir).into_iter().filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file() && e.file_name() != KEY_FILE)
+        .map(|e| e.into_path()).collect()
+}
+
+fn sealed_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(SEALED_EXT);
+    PathBuf::from(name)
+}
+
+fn rand_bytes<const N: usize>() -> [u8; N] {
+    use chacha20poly1305::aead::rand_core::RngCore;
+    let mut b = [0u8; N];
+    OsRng.fill_bytes(&mut b);
+    b
+}
+
+/// Fill `buf` from `r` until full or EOF; returns the bytes read.
+fn read_full(r: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match r.read(&mut buf[filled..])? {
+            0 => break,
+            k => filled += k,
+        }
+    }
+    Ok(filled)
+}
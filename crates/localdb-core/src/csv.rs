@@ -0,0 +1,113 @@
+//! CSV/TSV ingest: one chunk per row.
+//!
+//! The first row is the header. Each data row becomes one chunk whose text is
+//! the `csv.text_columns` values (all columns when unset) as `header: value`
+//! lines, so a seed inventory row reads like a short record to the embedder;
+//! a single text column is used as-is. The value of `csv.facet_column`
+//! extends the file's facet (`seeds` + `Tomato` → `seeds/Tomato`) and
+//! `csv.meta_columns` land in `DocumentChunk::meta`. Configured columns a file
+//! lacks are ignored. `.tsv` is tab-separated; quoting follows RFC 4180.
+
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::types::Meta;
+
+/// Which columns feed the chunk text, the facet and the metadata (`[csv]`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct CsvMapping {
+    pub text_columns: Vec<String>,
+    pub facet_column: Option<String>,
+    pub meta_columns: Vec<String>,
+}
+
+impl CsvMapping {
+    /// `[csv]`, or all columns as text with no facet/metadata columns.
+    pub fn from_config(config: &Config) -> Self { config.get::<Self>("csv").unwrap_or_default() }
+}
+
+/// One data row mapped for chunking.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row {
+    pub text: String,
+    pub facet: Option<String>,
+    pub meta: Meta,
+}
+
+/// Whether `path` is a `.csv` or `.tsv` file.
+pub fn is_table(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("csv") || e.eq_ignore_ascii_case("tsv"))
+}
+
+/// Field separator for `path`: tab for `.tsv`, else comma.
+pub fn delimiter(path: &Path) -> char {
+    if path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("tsv")) { '\t' } else { ',' }
+}
+
+/// Records of `content`. Quoted fields may hold delimiters, newlines and
+/// doubled quotes; blank lines are skipped; a leading BOM is dropped.
+pub fn parse(content: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let (mut in_quotes, mut quoted) = (false, false);
+    let mut chars = content.trim_start_matches('\u{feff}').chars().peekable();
+    let mut end_record = |record: &mut Vec<String>, field: &mut String, quoted: bool| {
+        if record.is_empty() && field.is_empty() && !quoted { return; }
+        record.push(std::mem::take(field));
+        records.push(std::mem::take(record));
+    };
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            if c != '"' { field.push(c); }
+            else if chars.peek() == Some(&'"') { field.push('"'); chars.next(); }
+            else { in_quotes = false; }
+        } else if c == '"' && field.is_empty() {
+            (in_quotes, quoted) = (true, true);
+        } else if c == delimiter {
+            record.push(std::mem::take(&mut field));
+            quoted = false;
+        } else if c == '\n' || c == '\r' {
+            if c == '\r' && chars.peek() == Some(&'\n') { chars.next(); }
+            end_record(&mut record, &mut field, quoted);
+            quoted = false;
+        } else {
+            field.push(c);
+        }
+    }
+    end_record(&mut record, &mut field, quoted);
+    records
+}
+
+/// Data rows of a table with `mapping` applied; rows without text are dropped.
+pub fn rows(content: &str, delimiter: char, mapping: &CsvMapping) -> Vec<Row> {
+    let mut records = parse(content, delimiter).into_iter();
+    let Some(header) = records.next() else { return Vec::new() };
+    let header: Vec<String> = header.iter().map(|h| h.trim().to_string()).collect();
+    let column = |name: &str| header.iter().position(|h| h.eq_ignore_ascii_case(name.trim()));
+    let mut text_columns: Vec<usize> = mapping.text_columns.iter().filter_map(|c| column(c)).collect();
+    if text_columns.is_empty() { text_columns = (0..header.len()).collect(); }
+    let facet_column = mapping.facet_column.as_deref().and_then(column);
+    let meta_columns: Vec<usize> = mapping.meta_columns.iter().filter_map(|c| column(c)).collect();
+
+    records.filter_map(|record| {
+        let value = |i: usize| record.get(i).map(|v| v.trim()).filter(|v| !v.is_empty());
+        let text = if text_columns.len() == 1 {
+            value(text_columns[0]).unwrap_or_default().to_string()
+        } else {
+            text_columns.iter().filter_map(|&i| value(i).map(|v| format!("{}: {}", header[i], v))).collect::<Vec<_>>().join("\n")
+        };
+        if text.is_empty() { return None; }
+        let meta = meta_columns.iter().filter_map(|&i| value(i).map(|v| (header[i].clone(), v.to_string()))).collect();
+        Some(Row { text, facet: facet_column.and_then(value).map(str::to_string), meta })
+    }).collect()
+}
+
+/// Facet of a row: the file's facet plus the row's value as one more segment.
+pub fn row_facet(category: &str, value: &str) -> String {
+    let segment = value.replace('/', "-");
+    if category.trim_matches('/').is_empty() { return format!("{}{}", if category.starts_with('/') { "/" } else { "" }, segment); }
+    format!("{}/{}", category.trim_end_matches('/'), segment)
+}
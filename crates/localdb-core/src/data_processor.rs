@@ -1,77 +1,1085 @@
-use anyhow::Result;
-use crate::types::DocumentChunk;
+//! Pragmatic paragraph-based text chunker for `.txt`, EPUB and ZIM sources.
+//!
+//! Splits input files by blank lines, then further splits long paragraphs with
+//! overlap, by words or by whole sentences (`ChunkingStrategy`); the semantic
+//! strategy instead cuts where the embeddings of adjacent sentences diverge
+//! (`chunker::ParagraphChunker`; `with_chunker` plugs in another `Chunker`). EPUBs are read chapter by chapter (see `epub`) and no chunk spans
+//! two chapters. A ZIM archive becomes one document per article (see `zim`),
+//! as does each text, EPUB or CSV/TSV file inside a zip or tar.gz (see `archive`);
+//! scanned images and PDFs are read page by page through `ocr`; every CSV/TSV
+//! row is its own chunk (see `csv`), every JSON Lines record its own document (see `jsonl`)
+//! and Whisper transcripts are chunked by speaker turn with time ranges (see `transcript`). Tokens are counted
+//! with the embedder's tokenizer when one is set (`with_token_counter`), so a
+//! chunk never exceeds its `max_len`; otherwise word count / 0.75 stands in.
+//! Text files are decoded in their detected encoding (see `charset`);
+//! oversized files and binary content are skipped (see `guards`).
+//! With `[boilerplate]` on, running headers, footers and watermark lines are
+//! stripped before chunking (see `boilerplate`); junk chunks are dropped after it (see `junk`); a chunk
+//! repeated across files is kept once (see `dedupe`). Chunk ids are derived from content (see `chunk_id`),
+//! not from position.
+//!
+//! Files are read and chunked in parallel on rayon's global pool
+//! (`RAYON_NUM_THREADS` caps it); doc ids, duplicate scanned pages and ZIM
+//! archives are then settled in file order, so the output is the same on any
+//! number of threads.
+//!
+//! Given the previous ingest's catalog (`with_previous`), files it already
+//! holds unchanged are skipped and `process_roots_incremental` reports what
+//! changed (see `incremental`).
+//!
+//! Registered `pre_chunk` and `post_chunk` hooks (`with_hooks`) run on every
+//! document (see `hooks`).
+
+use anyhow::{bail, Context, Result};
+use crate::archive;
+use crate::assets::{Asset, AssetStore};
+use crate::blobs::BlobStore;
+use crate::config::Config;
+use crate::boilerplate::{self, BoilerplateConfig, FolderBoilerplate, Removed};
+use crate::charset;
+use crate::chunker::ParagraphChunker;
+use crate::corpus::CorpusStats;
+use crate::csv::{self, CsvMapping};
+use crate::dedupe;
+use crate::epub;
+use crate::folder_meta::FolderMetaCache;
+use crate::guards::FileGuards;
+use crate::hooks::HookRegistry;
+use crate::incremental::{IngestChanges, PreviousIngest};
+use crate::jsonl::{self, JsonlMapping};
+use crate::junk::{Junk, JunkFilters, JunkReport};
+use crate::lang;
+use crate::ocr::{self, OcrConfig};
+use crate::phash::{text_hash, PageIndex};
+use crate::profile::{self, Stage};
+use crate::progress;
+use crate::retention::RetentionPolicy;
+use crate::roots::DataRoot;
+use crate::spans;
+use crate::summary::{summarize, SUMMARY_SENTENCES};
+use crate::taxonomy::Taxonomy;
+use crate::traits::{Chunker, Embedder, TokenCounter};
+use crate::transcript::{self, Segment};
+use crate::types::{file_candidates, is_in_file, ChunkSource, DocumentChunk, FileRecord, Meta};
+use crate::zim::{self, ZimArticle, ZimSource};
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `FileRecord::meta` key listing pages skipped as duplicates of earlier scans.
+pub const DUPLICATE_PAGES_KEY: &str = "duplicate_pages";
+
+/// `FileRecord::meta` key holding the image and text hashes of a scan's
+/// indexed pages (`<page>=<hash>/<text hash>; ...`, hex), so pages of later
+/// scans are still matched against them while the file is unchanged.
+pub const PAGE_HASHES_KEY: &str = "page_hashes";
+
+/// `FileRecord::meta` key reporting the boilerplate lines stripped from a file.
+pub const BOILERPLATE_KEY: &str = "boilerplate";
+
+/// Hex chars of the content hash kept in a chunk id.
+pub const CHUNK_ID_HASH_LEN: usize = 12;
+
+/// Stable chunk id: `doc_id:` plus a prefix of the blake3 hash of `content`,
+/// so inserting a paragraph does not renumber the rest of the document.
+/// `occurrence` disambiguates repeats of identical content within one document
+/// (0 for the first, then `~2`, `~3`, ... in document order). Position lives in
+/// `DocumentChunk::chunk_index`.
+pub fn chunk_id(doc_id: &str, content: &str, occurrence: usize) -> String {
+    chunk_id_from_hash(doc_id, blake3::hash(content.as_bytes()).to_hex().as_str(), occurrence)
+}
+
+/// `chunk_id` from a precomputed blake3 hex digest of the content (the
+/// `content_hash` column of the Lance `documents` table).
+pub fn chunk_id_from_hash(doc_id: &str, content_hash: &str, occurrence: usize) -> String {
+    let prefix = content_hash.get(..CHUNK_ID_HASH_LEN).unwrap_or(content_hash);
+    if occurrence == 0 { format!("{}:{}", doc_id, prefix) } else { format!("{}:{}~{}", doc_id, prefix, occurrence + 1) }
+}
+
+/// The `doc_id` a `chunk_id` was made from (the part before its last `:`).
+pub fn doc_id_of(chunk_id: &str) -> &str { chunk_id.rsplit_once(':').map(|(d, _)| d).unwrap_or(chunk_id) }
+
+pub(crate) fn next_chunk_id(seen: &mut HashMap<String, usize>, doc_id: &str, content: &str) -> String {
+    let n = seen.entry(content.to_string()).or_insert(0);
+    let id = chunk_id(doc_id, content, *n);
+    *n += 1;
+    id
+}
+
+/// Full-file blake3 hex digest, as stored in the catalog at ingest and
+/// recomputed by `localdb-cli scrub` to detect bit-rot or tampering.
+pub fn file_hash(path: &Path) -> std::io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    std::io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Extensions `canonical_doc_id` drops (case-sensitive, as ids always were).
+const DOC_ID_EXTENSIONS: &[&str] = &[".txt", ".epub", ".zim", ".csv", ".tsv", ".jsonl", ".ndjson", ".json", ".vtt", ".srt", ".tar.gz", ".tgz", ".zip"];
+
+/// Canonical document id: the path relative to `data_dir` with `/` separators
+/// and without a `DOC_ID_EXTENSIONS` extension (`survival/fire/basics`). Files outside
+/// `data_dir` fall back to their file name. Unique per file under one root.
+pub fn canonical_doc_id(file_path: &Path, data_dir: &Path) -> String {
+    let rel = relative_doc_path(file_path, data_dir);
+    DOC_ID_EXTENSIONS.iter().find_map(|ext| rel.strip_suffix(ext)).map(str::to_string).unwrap_or(rel)
+}
+
+/// Portable `doc_path` for storage: relative to `data_dir` with `/` separators
+/// (`survival/fire/basics.txt`), so an index survives moving between machines.
+/// Resolve it for display with `config::resolve_doc_path` and the data root
+/// recorded at ingest. Files outside `data_dir` fall back to their file name.
+pub fn relative_doc_path(file_path: &Path, data_dir: &Path) -> String {
+    let relative = file_path.strip_prefix(data_dir).ok().filter(|r| !r.as_os_str().is_empty())
+        .unwrap_or_else(|| file_path.file_name().map(Path::new).unwrap_or(file_path));
+    relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+/// Form-feed pages of a text (as pdftotext and some scrapers write them), or
+/// the whole text when it has none.
+pub fn text_pages(text: &str) -> Vec<String> {
+    let pages: Vec<String> = text.split('\x0c').filter(|p| !p.trim().is_empty()).map(str::to_string).collect();
+    if pages.is_empty() { vec![text.to_string()] } else { pages }
+}
+
+/// Creation time of a file (ms since the epoch), or `modified_at` on
+/// filesystems that do not record one.
+fn created_millis(metadata: &fs::Metadata, modified_at: i64) -> i64 {
+    metadata.created().ok().and_then(|c| c.duration_since(UNIX_EPOCH).ok()).map_or(modified_at, |d| d.as_millis() as i64)
+}
+
+/// Prepend a root's facet prefix to a category (`/library` + `fire` → `/library/fire`).
+fn join_facet(prefix: Option<&str>, category: &str) -> String {
+    match prefix.map(|p| p.trim_end_matches('/')).filter(|p| !p.is_empty()) {
+        None => category.to_string(),
+        Some(p) => {
+            let rest = category.trim_matches('/');
+            let p = if p.starts_with('/') { p.to_string() } else { format!("/{}", p) };
+            if rest.is_empty() { p } else { format!("{}/{}", p, rest) }
+        }
+    }
+}
+
+/// Store each chunk's detected language; a chunk too short or too mixed to
+/// tell keeps the `language` of its folder or record metadata, if any.
+fn tag_lang(chunks: &mut [DocumentChunk]) {
+    for c in chunks { c.lang = lang::detect(&c.content).code().map(str::to_string).or_else(|| c.meta.get("language").cloned()); }
+}
+
+/// Doc id under the old scheme (file stem); collides across folders.
+pub fn legacy_doc_id(file_path: &Path) -> String {
+    file_path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| file_path.to_string_lossy().to_string())
+}
+
+/// Store the images of an EPUB's chapters, each paired with its paragraph
+/// position across the whole book (`chunk_sections` order). Missing or
+/// unstorable images are reported and left out.
+fn store_images(store: &AssetStore, epub_bytes: &[u8], chapters: &[epub::Chapter], file_path: &Path) -> Vec<(usize, Asset)> {
+    let mut refs = Vec::new();
+    let mut offset = 0;
+    for chapter in chapters {
+        refs.extend(chapter.images.iter().map(|i| (offset + i.paragraph, i)));
+        offset += chapter.text.split("\n\n").filter(|p| !p.trim().is_empty()).count();
+    }
+    let names: Vec<&str> = refs.iter().map(|(_, i)| i.src.as_str()).collect();
+    let files = match epub::read_files(epub_bytes, &names) {
+        Ok(files) => files,
+        Err(e) => { eprintln!("⚠️  No images from {}: {:#}", file_path.display(), e); return Vec::new(); }
+    };
+    let mut assets = Vec::new();
+    for ((paragraph, image), bytes) in refs.into_iter().zip(files) {
+        let stored = bytes.ok_or_else(|| anyhow::anyhow!("not in the archive")).and_then(|b| store.put_image(&image.src, &b));
+        match stored {
+            Ok((hash, media_type)) => assets.push((paragraph, Asset { name: image.src.clone(), hash, media_type, alt: image.alt.clone(), after_chunk: None })),
+            Err(e) => eprintln!("⚠️  Skipping image {} in {}: {:#}", image.src, file_path.display(), e),
+        }
+    }
+    assets
+}
+
+/// Collision check for doc ids assigned during one ingest. Every document that
+/// maps to an id claimed by another (found up front by `contest`, or already
+/// taken) gets the id with `~` and a hash prefix of its doc path appended, so
+/// the result does not depend on file order, and a warning is logged. Ids
+/// reserved for files of the previous ingest go to those files only.
+#[derive(Default)]
+struct DocIdRegistry { taken: HashMap<String, PathBuf>, reserved: HashMap<String, String>, contested: HashSet<String> }
+
+impl DocIdRegistry {
+    /// Hold `id` for the file at `doc_path`, which had it last time.
+    fn reserve(&mut self, id: String, doc_path: &str) {
+        self.taken.insert(id.clone(), PathBuf::from(doc_path));
+        self.reserved.insert(id, doc_path.to_string());
+    }
+
+    /// Note the candidate ids of a run's documents; those seen more than once
+    /// are suffixed for every claimant.
+    fn contest<'a>(&mut self, ids: impl IntoIterator<Item = &'a str>) {
+        let mut seen = HashSet::new();
+        for id in ids { if !seen.insert(id) { self.contested.insert(id.to_string()); } }
+    }
+
+    fn assign(&mut self, id: String, file_path: &Path, doc_path: &str) -> String {
+        if self.reserved.get(&id).is_some_and(|p| p == doc_path) {
+            self.reserved.remove(&id);
+            self.taken.insert(id.clone(), file_path.to_path_buf());
+            return id;
+        }
+        if !self.taken.contains_key(&id) && !self.contested.contains(&id) { self.taken.insert(id.clone(), file_path.to_path_buf()); return id; }
+        let hash = blake3::hash(doc_path.as_bytes()).to_hex();
+        let mut candidate = format!("{}~{}", id, &hash.as_str()[..8]);
+        let mut n = 2;
+        while self.taken.contains_key(&candidate) { candidate = format!("{}~{}-{}", id, &hash.as_str()[..8], n); n += 1; }
+        tracing::warn!("doc id collision: {} maps to '{}' like another file; using '{}'", file_path.display(), id, candidate);
+        self.taken.insert(candidate.clone(), file_path.to_path_buf());
+        candidate
+    }
+}
+
+/// State shared by every file of one ingest run: doc ids handed out, the
+/// scanned pages seen (for `ocr.dedupe`) and the boilerplate of each folder
+/// (for `boilerplate.across_files`), the junk chunks dropped and the
+/// previously indexed files skipped as unchanged, unreadable, expired or
+/// now empty, oversized or binary.
+/// Files in `reread` are read even if they look unchanged
+/// (`PreviousIngest::dependents`).
+struct IngestRun { doc_ids: DocIdRegistry, pages: PageIndex, folders: FolderBoilerplate, junk: JunkReport, unchanged: Vec<String>, failed: Vec<String>, expired: Vec<String>, skipped: Vec<String>, reread: HashSet<String> }
+
+impl IngestRun {
+    fn new(ocr: &OcrConfig, boilerplate: &BoilerplateConfig, guards: FileGuards) -> Self {
+        Self { doc_ids: DocIdRegistry::default(), pages: PageIndex::new(ocr.duplicate_distance), folders: FolderBoilerplate::new(boilerplate.clone(), guards), junk: JunkReport::default(), unchanged: Vec::new(), failed: Vec::new(), expired: Vec::new(), skipped: Vec::new(), reread: HashSet::new() }
+    }
+
+    fn print_junk(&self) {
+        if self.junk.total() > 0 { println!("🧹 Dropped {} junk chunks: {}", self.junk.total(), self.junk.summary()); }
+    }
+}
+
+/// What every worker of one `process_files_in` call shares, read-only.
+struct Batch<'a> {
+    data_dir: &'a Path,
+    prefixed: &'a (dyn Fn(String) -> String + Sync),
+    facet_prefix: Option<&'a str>,
+    folders: &'a FolderBoilerplate,
+    reread: &'a HashSet<String>,
+    now: SystemTime,
+    total: usize,
+}
+
+/// A file as a worker leaves it. Files are read and chunked in parallel under
+/// their candidate doc ids; what depends on earlier files (doc id collisions,
+/// duplicate scanned pages, ZIM articles) is settled afterwards in file order,
+/// so the output does not depend on scheduling.
+enum Prepared {
+    /// Empty, oversized or binary; already reported. Holds the `doc_path`.
+    Skipped(String),
+    /// Past its retention; already reported. Holds the `doc_path`.
+    Expired(String),
+    /// Could not be read or parsed; already reported. Holds the `doc_path`.
+    Failed(String),
+    /// As in the previous ingest (`with_previous`); holds the `doc_path`.
+    Unchanged(String),
+    /// Streamed in file order (articles get doc ids as they are read).
+    Zim { category: String },
+    /// OCR'd pages of a scan, chunked once duplicates are claimed (`ocr.dedupe`).
+    Scan { info: FileInfo, pages: Vec<ocr::ScanPage> },
+    File(Box<PreparedFile>),
+}
+
+impl Prepared {
+    /// Candidate doc ids of the documents settled from this file.
+    fn doc_ids(&self) -> Vec<&str> {
+        match self {
+            Prepared::Scan { info, .. } => vec![info.doc_id.as_str()],
+            Prepared::File(file) => file.docs.iter().map(|d| d.doc_id.as_str()).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Where a file came from and what its catalog record needs.
+struct FileInfo {
+    path: PathBuf,
+    /// Candidate id; `settle` may give the file another on a collision.
+    doc_id: String,
+    doc_path: String,
+    category: String,
+    hash: String,
+    size: u64,
+    modified_at: i64,
+    /// Creation time (ms), or the modification time where the filesystem has none.
+    created_at: i64,
+    /// From the format's own metadata (EPUB `dc:title`/`dc:creator`).
+    title: Option<String>,
+    author: Option<String>,
+}
+
+impl FileInfo {
+    /// Give `chunks` the document metadata of this file.
+    fn stamp(&self, chunks: &mut [DocumentChunk]) {
+        for c in chunks {
+            c.title = self.title.clone();
+            c.author = self.author.clone();
+            c.created_at = Some(self.created_at);
+        }
+    }
+}
+
+struct PreparedFile {
+    info: FileInfo,
+    summary: String,
+    /// Catalog metadata of the file itself, before folder metadata.
+    record_meta: Meta,
+    /// One per document: the file's, or one per JSON Lines record.
+    docs: Vec<PreparedDoc>,
+    /// Whether the catalog record takes the (first) document's final doc id,
+    /// rather than the file's.
+    record_doc: bool,
+    junk: JunkReport,
+}
+
+struct PreparedDoc {
+    doc_id: String,
+    chunks: Vec<DocumentChunk>,
+    /// Image manifest, when images are kept.
+    assets: Option<Vec<Asset>>,
+}
 
-#[derive(Debug, Clone)]
+impl PreparedDoc {
+    /// Re-key the chunks (and image anchors) under doc id `to`.
+    fn move_to(&mut self, to: &str) {
+        let from = self.doc_id.as_str();
+        let moved = |id: &str| id.strip_prefix(from).map_or_else(|| id.to_string(), |rest| format!("{}{}", to, rest));
+        for c in &mut self.chunks { c.id = moved(&c.id); c.doc_id = to.to_string(); }
+        for a in self.assets.iter_mut().flatten() { a.after_chunk = a.after_chunk.as_deref().map(&moved); }
+        self.doc_id = to.to_string();
+    }
+}
+
+/// `chunk_index` and `total_chunks` per document after chunks were dropped.
+fn renumber(chunks: &mut [DocumentChunk]) {
+    let mut totals: HashMap<String, usize> = HashMap::new();
+    for c in chunks.iter_mut() { let n = totals.entry(c.doc_id.clone()).or_insert(0); c.chunk_index = *n; *n += 1; }
+    for c in chunks.iter_mut() { c.total_chunks = totals[&c.doc_id]; }
+}
+
+/// One `<page>=<hash>/<text hash>` entry of `PAGE_HASHES_KEY`.
+fn parse_page_hashes(entry: &str) -> Option<(usize, u64, u64)> {
+    let (page, hashes) = entry.split_once('=')?;
+    let (hash, text) = hashes.split_once('/')?;
+    Some((page.parse().ok()?, u64::from_str_radix(hash, 16).ok()?, u64::from_str_radix(text, 16).ok()?))
+}
+
+fn is_txt(path: &Path) -> bool { path.extension().and_then(|e| e.to_str()) == Some("txt") }
+
+/// How a paragraph longer than `max_tokens` is split; names as in config
+/// (`words`, `sentences`, `semantic`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkingStrategy {
+    /// Word windows; may cut mid-sentence.
+    #[default]
+    Words,
+    /// Whole sentences per chunk, overlap counted in sentences. A sentence
+    /// longer than `max_tokens` on its own is split by words.
+    Sentences,
+    /// Runs of sentences cut where adjacent sentence embeddings diverge by
+    /// more than `semantic_threshold`, or at `max_tokens`; paragraph breaks
+    /// are not boundaries. Needs `with_sentence_embedder`; without one (and
+    /// for CSV rows and transcripts) it splits like `Sentences`.
+    Semantic,
+}
+
+/// Default `ChunkingConfig::semantic_threshold`.
+pub const DEFAULT_SEMANTIC_THRESHOLD: f32 = 0.4;
+
+/// `[chunking]` settings.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ChunkingConfig {
     pub max_tokens: usize,
+    /// Share of each window repeated at the start of the next (words or
+    /// sentences, per `strategy`).
     pub overlap_percent: f32,
+    pub strategy: ChunkingStrategy,
+    /// Cosine distance between adjacent sentences above which
+    /// `ChunkingStrategy::Semantic` starts a new chunk.
+    pub semantic_threshold: f32,
+    /// Junk chunks dropped after chunking (`[chunking.filters]`).
+    pub filters: JunkFilters,
+    /// Index one copy of a chunk repeated across files (see `dedupe`).
+    pub dedupe: bool,
 }
 
 impl Default for ChunkingConfig {
     fn default() -> Self {
-        Self { max_tokens: 500, overlap_percent: 0.2 }
+        Self { max_tokens: 500, overlap_percent: 0.2, strategy: ChunkingStrategy::Words, semantic_threshold: DEFAULT_SEMANTIC_THRESHOLD, filters: JunkFilters::default(), dedupe: true }
+    }
+}
+
+impl ChunkingConfig {
+    /// `[chunking]`, or the defaults when the section is absent. Unknown keys
+    /// and values `validate` rejects are errors.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let Ok(value) = config.get::<serde_json::Value>("chunking") else { return Ok(Self::default()) };
+        let chunking: Self = serde_json::from_value(value).context("invalid [chunking]")?;
+        chunking.validate().context("invalid [chunking]")?;
+        Ok(chunking)
+    }
+
+    /// `max_tokens` above zero, `overlap_percent` in [0, 1) and, for the
+    /// semantic strategy, `semantic_threshold` in (0, 2] (cosine distance);
+    /// filter shares in [0, 1].
+    pub fn validate(&self) -> Result<()> {
+        if self.max_tokens == 0 { bail!("max_tokens must be at least 1"); }
+        if !(0.0..1.0).contains(&self.overlap_percent) { bail!("overlap_percent must be in [0, 1) (a share of the window), got {}", self.overlap_percent); }
+        if self.strategy == ChunkingStrategy::Semantic && !(self.semantic_threshold > 0.0 && self.semantic_threshold <= 2.0) {
+            bail!("semantic_threshold must be in (0, 2] (cosine distance), got {}", self.semantic_threshold);
+        }
+        self.filters.validate()
     }
 }
 
 #[derive(Default)]
 pub struct DataProcessor {
-    chunking_config: ChunkingConfig,
+    paragraphs: ParagraphChunker,
+    chunker: Option<Arc<dyn Chunker>>,
+    retention: RetentionPolicy,
+    taxonomy: Taxonomy,
+    blobs: Option<BlobStore>,
+    assets: Option<AssetStore>,
+    ocr: OcrConfig,
+    boilerplate: BoilerplateConfig,
+    csv: CsvMapping,
+    jsonl: JsonlMapping,
+    previous: PreviousIngest,
+    hooks: HookRegistry,
+    guards: FileGuards,
 }
 
 impl DataProcessor {
     /// Create a new processor with default chunking config.
     pub fn new() -> Self { Self::default() }
 
-    /// Process a directory recursively, collecting `.txt` files and returning
+    /// Create a processor with an explicit chunking config.
+    pub fn with_config(chunking_config: ChunkingConfig) -> Self { Self { paragraphs: ParagraphChunker::new(chunking_config), ..Self::default() } }
+
+    /// Skip files whose retention period (by modification time) has expired.
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self { self.retention = retention; self }
+
+    /// Assign facets from a curated taxonomy instead of the directory layout.
+    pub fn with_taxonomy(mut self, taxonomy: Taxonomy) -> Self { self.taxonomy = taxonomy; self }
+
+    /// Copy every processed file into a content-addressed store.
+    pub fn with_blob_store(mut self, blobs: BlobStore) -> Self { self.blobs = Some(blobs); self }
+
+    /// Keep the images EPUB chapters reference, anchored to their chunks.
+    pub fn with_asset_store(mut self, assets: AssetStore) -> Self { self.assets = Some(assets); self }
+
+    /// Language and render resolution for scanned images and PDFs.
+    pub fn with_ocr(mut self, ocr: OcrConfig) -> Self { self.ocr = ocr; self }
+
+    /// Which repeated header/footer lines are stripped before chunking.
+    pub fn with_boilerplate(mut self, boilerplate: BoilerplateConfig) -> Self { self.boilerplate = boilerplate; self }
+
+    /// Column mapping for CSV/TSV files (text, facet and metadata columns).
+    pub fn with_csv(mut self, csv: CsvMapping) -> Self { self.csv = csv; self }
+
+    /// Field mapping for JSON Lines records (content, id, facet and metadata fields).
+    pub fn with_jsonl(mut self, jsonl: JsonlMapping) -> Self { self.jsonl = jsonl; self }
+
+    /// Size chunks by the embedder's tokenizer: `max_tokens` is capped at its
+    /// `max_len` and long paragraphs are split into windows that fit.
+    pub fn with_token_counter(mut self, tokens: Arc<dyn TokenCounter>) -> Self { self.paragraphs = self.paragraphs.with_token_counter(tokens); self }
+
+    /// Embed sentences with `embedder` to place `ChunkingStrategy::Semantic`
+    /// boundaries.
+    pub fn with_sentence_embedder(mut self, embedder: Arc<dyn Embedder>) -> Self { self.paragraphs = self.paragraphs.with_sentence_embedder(embedder); self }
+
+    /// Embed at most `batch_size` sentences at a time for semantic chunking.
+    pub fn with_sentence_batch_size(mut self, batch_size: usize) -> Self { self.paragraphs = self.paragraphs.with_sentence_batch_size(batch_size); self }
+
+    /// Split documents with `chunker` instead of the paragraph splitter
+    /// (`ParagraphChunker`). CSV rows and transcripts keep their own chunking.
+    pub fn with_chunker(mut self, chunker: Arc<dyn Chunker>) -> Self { self.chunker = Some(chunker); self }
+
+    /// Skip files the previous ingest's catalog holds unchanged, and keep the
+    /// doc ids it gave the files still present.
+    pub fn with_previous(mut self, previous: PreviousIngest) -> Self { self.previous = previous; self }
+
+    /// Run the `pre_chunk` and `post_chunk` hooks of `hooks` on every document.
+    pub fn with_hooks(mut self, hooks: HookRegistry) -> Self { self.hooks = hooks; self }
+
+    /// Size limit and binary sniffing for the files read (default: 512 MiB, sniffing on).
+    pub fn with_guards(mut self, guards: FileGuards) -> Self { self.guards = guards; self }
+
+    /// Process a directory recursively, collecting `.txt`/`.epub`/`.zim` files and returning
     /// `DocumentChunk`s. Logs progress. Returns an empty list if no files found.
     pub fn process_directory(&self, data_dir: &Path) -> Result<Vec<DocumentChunk>> {
-        let files = self.list_txt_files(data_dir);
+        let files = profile::time(Stage::Scan, || self.list_source_files(data_dir));
         if files.is_empty() {
-            println!("No .txt files found under {}.", data_dir.display());
+            println!("No .txt/.epub/.zim files found under {}.", data_dir.display());
             return Ok(vec![]);
         }
-        let mut all_chunks = Vec::new();
-        for (file_index, file_path) in files.iter().enumerate() {
-            println!("Processing file {}/{}: {}", file_index + 1, files.len(), file_path.display());
-            let content = self.read_file_content(file_path)?;
-            let doc_id = self.extract_doc_id(file_path);
-            let category = self.get_facet_from_path(file_path, data_dir);
-            let chunks = self.chunk_content(&content, &doc_id, file_path, &category)?;
-            all_chunks.extend(chunks);
-        }
-        println!("Processed {} files into {} chunks", files.len(), all_chunks.len());
-        Ok(all_chunks)
+        self.process_files(&files, data_dir)
+    }
+
+    /// Chunk one file as `process_directory(data_dir)` would (doc id and facet
+    /// relative to `data_dir`); `localdb-cli chunk-preview` shows the result.
+    pub fn process_file(&self, file_path: &Path, data_dir: &Path) -> Result<Vec<DocumentChunk>> {
+        self.process_files(&[file_path.to_path_buf()], data_dir)
+    }
+
+    /// Tokens in `text` as chunk sizes are measured (see `with_token_counter`).
+    pub fn count_tokens(&self, text: &str) -> usize { self.paragraphs.count_tokens(text) }
+
+    /// The chunking settings in effect.
+    pub fn chunking(&self) -> &ChunkingConfig { &self.paragraphs.config }
+
+    /// Largest chunk in tokens: `max_tokens`, capped by the token counter's `max_len`.
+    pub fn max_tokens(&self) -> usize { self.paragraphs.max_tokens() }
+
+    /// blake3 of what decides chunk boundaries (chunking settings, the capped
+    /// `max_tokens`, boilerplate stripping). Chunks from an ingest with another
+    /// fingerprint must not be mixed in by an incremental one.
+    pub fn chunking_fingerprint(&self) -> String {
+        blake3::hash(format!("{:?}|{}|{:?}", self.paragraphs.config, self.max_tokens(), self.boilerplate).as_bytes()).to_hex()[..16].to_string()
+    }
+
+    /// `CorpusStats` of `chunks` (e.g. those `process_roots_incremental`
+    /// returned) of the files in `catalog`, sized by this processor's token
+    /// counter, with the `top` largest documents.
+    pub fn corpus_stats(&self, chunks: &[DocumentChunk], catalog: &[FileRecord], top: usize) -> CorpusStats {
+        let files = catalog.iter().map(|r| r.doc_path.clone()).collect();
+        CorpusStats::collect(chunks, &files, |t| self.count_tokens(t), top)
     }
 
     pub fn process_directory_limited(&self, data_dir: &Path, limit: usize) -> Result<Vec<DocumentChunk>> {
-        let mut files = self.list_txt_files(data_dir);
-        if files.is_empty() { println!("No .txt files found under {}.", data_dir.display()); return Ok(vec![]); }
+        let mut files = profile::time(Stage::Scan, || self.list_source_files(data_dir));
+        if files.is_empty() { println!("No .txt/.epub/.zim files found under {}.", data_dir.display()); return Ok(vec![]); }
         if files.len() > limit { files.truncate(limit); println!("🔢 Limited to first {} files", limit); }
+        self.process_files(&files, data_dir)
+    }
+
+    /// Process several data roots in one pass. Each root contributes the files
+    /// its connector accepts; with more than one root, doc ids and stored
+    /// `doc_path`s are prefixed with the root name (see `RootMap`), and every
+    /// category gets the root's facet prefix. Doc id collisions are checked
+    /// across all roots. Roots whose media is offline are skipped.
+    pub fn process_roots(&self, roots: &[DataRoot]) -> Result<Vec<DocumentChunk>> {
+        Ok(self.process_roots_cataloged(roots)?.0)
+    }
+
+    /// `process_roots` plus one `FileRecord` (full-file hash) per ingested file.
+    pub fn process_roots_cataloged(&self, roots: &[DataRoot]) -> Result<(Vec<DocumentChunk>, Vec<FileRecord>)> {
+        let (chunks, catalog, _) = self.process_roots_incremental(roots)?;
+        Ok((chunks, catalog))
+    }
+
+    /// `process_roots_cataloged` for the new and modified files only (all
+    /// files without `with_previous`), and how the files listed under the
+    /// online roots compare with the previous ingest.
+    pub fn process_roots_incremental(&self, roots: &[DataRoot]) -> Result<(Vec<DocumentChunk>, Vec<FileRecord>, IngestChanges)> {
+        let mut run = IngestRun::new(&self.ocr, &self.boilerplate, self.guards);
+        // Stored doc paths of a root start with this (see `process_files_in`).
+        let prefix = |root: &DataRoot| if roots.len() > 1 { format!("{}/", root.name()) } else { String::new() };
+        let mut listed = Vec::new();
+        for root in roots {
+            if !root.is_online() { println!("📴 Skipping offline media: {} ({})", root.name(), root.path.display()); continue; }
+            let files = profile::time(Stage::Scan, || self.list_files(&root.path, |p| root.accepts(p)));
+            if files.is_empty() { println!("No {} files found under {}.", root.extensions.join("/"), root.path.display()); }
+            listed.push((root, files));
+        }
+        let mut seen = HashSet::new();
+        // Files that may have changed, for the files whose duplicates they hold.
+        let mut touched = HashSet::new();
+        for (root, files) in &listed {
+            for file_path in files {
+                let doc_path = format!("{}{}", prefix(root), relative_doc_path(file_path, &root.path));
+                if let Some(record) = self.previous.record(&doc_path) {
+                    run.doc_ids.reserve(record.doc_id.clone(), &doc_path);
+                    let stat = fs::metadata(file_path).ok().map(|m| (m.len(), m.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map_or(0, |d| d.as_millis() as i64)));
+                    if !stat.is_some_and(|(size, modified_at)| self.previous.looks_unchanged(&doc_path, size, modified_at)) { touched.insert(doc_path.clone()); }
+                }
+                seen.insert(doc_path);
+            }
+        }
+        let scanned: Vec<String> = listed.iter().map(|(root, _)| prefix(root)).collect();
+        let removed = self.previous.removed(&seen, |p| scanned.iter().any(|s| p.starts_with(s.as_str())));
+        touched.extend(removed.iter().cloned());
+        // Their `also_in` names files that changed or left.
+        let holders = if self.paragraphs.config.dedupe { self.previous.holders_of(&touched) } else { HashSet::new() };
+        run.reread = self.previous.dependents(&touched);
+        run.reread.extend(holders);
+        // Unchanged scans are not read again; their pages still count for `ocr.dedupe`.
+        for doc_path in listed.iter().flat_map(|(root, files)| files.iter().map(move |f| format!("{}{}", prefix(root), relative_doc_path(f, &root.path)))) {
+            if touched.contains(&doc_path) || run.reread.contains(&doc_path) { continue; }
+            let Some(hashes) = self.previous.record(&doc_path).and_then(|r| r.meta.get(PAGE_HASHES_KEY)) else { continue };
+            for (page, hash, text) in hashes.split("; ").filter_map(parse_page_hashes) { run.pages.claim(hash, text, format!("{}#{}", doc_path, page)); }
+        }
+        // Chunk hashes of the files not read this time, for `dedupe`.
+        let mut indexed: HashMap<String, String> = HashMap::new();
+        if self.paragraphs.config.dedupe {
+            for (root, files) in &listed {
+                for doc_path in files.iter().map(|f| format!("{}{}", prefix(root), relative_doc_path(f, &root.path))) {
+                    if touched.contains(&doc_path) || run.reread.contains(&doc_path) { continue; }
+                    let Some(record) = self.previous.record(&doc_path) else { continue };
+                    for hash in dedupe::listed(&record.meta, dedupe::CHUNK_HASHES_KEY) { indexed.insert(hash, doc_path.clone()); }
+                }
+            }
+        }
         let mut all_chunks = Vec::new();
-        for (file_index, file_path) in files.iter().enumerate() {
-            println!("Processing file {}/{}: {}", file_index + 1, files.len(), file_path.display());
-            let content = self.read_file_content(file_path)?;
-            let doc_id = self.extract_doc_id(file_path);
-            let category = self.get_facet_from_path(file_path, data_dir);
-            let chunks = self.chunk_content(&content, &doc_id, file_path, &category)?;
-            all_chunks.extend(chunks);
+        let mut catalog = Vec::new();
+        let mut only: Option<HashSet<String>> = None;
+        loop {
+            for (root, files) in &listed {
+                let files: Vec<PathBuf> = match &only {
+                    Some(only) => files.iter().filter(|f| only.contains(&format!("{}{}", prefix(root), relative_doc_path(f, &root.path)))).cloned().collect(),
+                    None => files.clone(),
+                };
+                if files.is_empty() { continue; }
+                let name = (roots.len() > 1).then(|| root.name());
+                all_chunks.extend(self.process_files_in(&files, &root.path, name.as_deref(), root.facet_prefix.as_deref(), &mut run, &mut catalog)?);
+            }
+            // Unchanged files holding text that was just read are read again,
+            // so `dedupe` compares the copies and keeps the first.
+            let again: HashSet<String> = all_chunks.iter()
+                .filter_map(|c| indexed.get(&dedupe::chunk_hash(&c.content)).filter(|holder| !is_in_file(&c.doc_path, holder)))
+                .cloned().collect();
+            if again.is_empty() { break; }
+            indexed.retain(|_, holder| !again.contains(holder));
+            run.unchanged.retain(|p| !again.contains(p));
+            run.reread.extend(again.iter().cloned());
+            only = Some(again);
+        }
+        if only.is_some() {
+            // Back in listing order, which decides the copy `dedupe` keeps.
+            let order: HashMap<String, usize> = listed.iter().flat_map(|(root, files)| files.iter().map(move |f| format!("{}{}", prefix(root), relative_doc_path(f, &root.path)))).enumerate().map(|(i, p)| (p, i)).collect();
+            all_chunks.sort_by_key(|c| file_candidates(&c.doc_path).find_map(|f| order.get(f)).copied().unwrap_or(usize::MAX));
+        }
+        run.print_junk();
+        self.drop_duplicates(&mut all_chunks, &mut catalog);
+        let (modified, added): (Vec<&FileRecord>, Vec<&FileRecord>) = catalog.iter().partition(|r| self.previous.record(&r.doc_path).is_some());
+        let changes = IngestChanges {
+            unchanged: run.unchanged, failed: run.failed, expired: run.expired, skipped: run.skipped, modified: modified.into_iter().map(|r| r.doc_path.clone()).collect(),
+            removed, added: added.len(),
+        };
+        if !self.previous.is_empty() { println!("♻️  Since the last ingest: {}", changes.summary()); }
+        Ok((all_chunks, catalog, changes))
+    }
+
+    fn process_files(&self, files: &[PathBuf], data_dir: &Path) -> Result<Vec<DocumentChunk>> {
+        let mut run = IngestRun::new(&self.ocr, &self.boilerplate, self.guards);
+        let mut catalog = Vec::new();
+        let mut chunks = self.process_files_in(files, data_dir, None, None, &mut run, &mut catalog)?;
+        run.print_junk();
+        self.drop_duplicates(&mut chunks, &mut catalog);
+        Ok(chunks)
+    }
+
+    fn process_files_in(&self, files: &[PathBuf], data_dir: &Path, root_name: Option<&str>, facet_prefix: Option<&str>, run: &mut IngestRun, catalog: &mut Vec<FileRecord>) -> Result<Vec<DocumentChunk>> {
+        let prefixed = |s: String| match root_name { Some(n) => format!("{}/{}", n, s), None => s };
+        // Workers only read the folder boilerplate, so work it out first.
+        for file_path in files.iter().filter(|p| is_txt(p)) { run.folders.keys_for(file_path.parent().unwrap_or(Path::new("."))); }
+        let batch = Batch { data_dir, prefixed: &prefixed, facet_prefix, folders: &run.folders, reread: &run.reread, now: SystemTime::now(), total: files.len() };
+        let prepared: Vec<Result<Prepared>> = files.par_iter().enumerate().map(|(i, file_path)| self.prepare_file(&batch, i, file_path)).collect();
+        run.doc_ids.contest(prepared.iter().flatten().flat_map(Prepared::doc_ids));
+        let mut all_chunks = Vec::new();
+        let mut folder_meta = FolderMetaCache::new(data_dir);
+        for (file_path, prepared) in files.iter().zip(prepared) {
+            let file = match prepared? {
+                Prepared::Skipped(doc_path) => { if self.previous.record(&doc_path).is_some() { run.skipped.push(doc_path); } continue; }
+                Prepared::Expired(doc_path) => { if self.previous.record(&doc_path).is_some() { run.expired.push(doc_path); } continue; }
+                Prepared::Unchanged(doc_path) => { run.unchanged.push(doc_path); continue; }
+                Prepared::Failed(doc_path) => { run.failed.push(doc_path); continue; }
+                Prepared::Zim { category } => {
+                    let meta = folder_meta.for_dir(file_path.parent().unwrap_or(data_dir))?.to_meta();
+                    match self.process_zim(file_path, data_dir, &category, &prefixed, &mut run.doc_ids, catalog) {
+                        Ok(mut chunks) => {
+                            self.drop_junk(&mut chunks, &mut run.junk);
+                            for c in &mut chunks { c.meta = meta.clone(); }
+                            tag_lang(&mut chunks);
+                            self.hooks.post_chunk(&mut chunks)?;
+                            renumber(&mut chunks);
+                            all_chunks.extend(chunks);
+                        }
+                        Err(e) => {
+                            eprintln!("⚠️  Skipping unreadable ZIM {}: {:#}", file_path.display(), e);
+                            run.failed.push(prefixed(relative_doc_path(file_path, data_dir)));
+                        }
+                    }
+                    continue;
+                }
+                Prepared::Scan { info, pages } => {
+                    let mut texts = Vec::new();
+                    let mut duplicate_pages = Vec::new();
+                    let mut page_hashes = Vec::new();
+                    for page in pages {
+                        let source = format!("{}#{}", info.doc_path, page.number);
+                        let hashes = page.fingerprint.map(|hash| (hash, text_hash(&page.text)));
+                        // A scan read again finds its own pages among those kept from before.
+                        match hashes.and_then(|(hash, text)| run.pages.claim(hash, text, source.clone())).filter(|canonical| *canonical != source) {
+                            Some(canonical) => { println!("  ♻️  page {} of {} duplicates {}; not indexed", page.number, info.doc_path, canonical); duplicate_pages.push(format!("{}={}", page.number, canonical)); }
+                            None => {
+                                if let Some((hash, text)) = hashes { page_hashes.push(format!("{}={:016x}/{:016x}", page.number, hash, text)); }
+                                texts.push(page.text);
+                            }
+                        }
+                    }
+                    let mut file = self.finish_sections(info, texts, None, Vec::new(), &run.folders)?;
+                    // Skipped re-scans stay traceable: `<page>=<canonical doc_path>#<page>; ...`.
+                    if !duplicate_pages.is_empty() { file.record_meta.insert(DUPLICATE_PAGES_KEY.to_string(), duplicate_pages.join("; ")); }
+                    if !page_hashes.is_empty() { file.record_meta.insert(PAGE_HASHES_KEY.to_string(), page_hashes.join("; ")); }
+                    file
+                }
+                Prepared::File(file) => *file,
+            };
+            self.settle(file, data_dir, run, &mut folder_meta, catalog, &mut all_chunks)?;
         }
         println!("Processed {} files into {} chunks", files.len(), all_chunks.len());
         Ok(all_chunks)
     }
 
-    /// Read a text file, attempting UTF-8 first and falling back to raw bytes.
-    fn read_file_content(&self, file_path: &Path) -> Result<String> {
-        match fs::read_to_string(file_path) {
-            Ok(content) => Ok(content),
-            Err(_) => Ok(String::from_utf8_lossy(&fs::read(file_path)?).to_string()),
+    /// Read and chunk one file on a worker thread (see `Prepared`).
+    fn prepare_file(&self, batch: &Batch, file_index: usize, file_path: &Path) -> Result<Prepared> {
+        let dir = self.get_facet_from_path(file_path, batch.data_dir);
+        let category = self.taxonomy.facet_for(&(batch.prefixed)(dir.clone())).unwrap_or_else(|| join_facet(batch.facet_prefix, &dir));
+        let doc_path = (batch.prefixed)(relative_doc_path(file_path, batch.data_dir));
+        // One file that vanished or is unreadable fails alone, not the ingest.
+        let metadata = match fs::metadata(file_path) {
+            Ok(m) => m,
+            Err(e) => { eprintln!("⚠️  Skipping unreadable {}: {}", file_path.display(), e); return Ok(Prepared::Failed(doc_path)); }
+        };
+        let modified = metadata.modified().unwrap_or(batch.now);
+        if self.retention.is_expired(&category, modified, batch.now) { println!("⏳ Skipping expired {} (retention for {})", file_path.display(), category); return Ok(Prepared::Expired(doc_path)); }
+        let modified_at = modified.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or(0);
+        let reread = batch.reread.contains(&doc_path);
+        if !reread && self.previous.looks_unchanged(&doc_path, metadata.len(), modified_at) { return Ok(Prepared::Unchanged(doc_path)); }
+        println!("Processing file {}/{}: {}", file_index + 1, batch.total, file_path.display());
+        progress::report("read", file_index as u64 + 1, Some(batch.total as u64));
+        if zim::is_zim(file_path) { return Ok(Prepared::Zim { category }); }
+        if self.guards.oversized(metadata.len()) {
+            eprintln!("⚠️  Skipping {}: {} MiB is over data.max_file_mb ({})", file_path.display(), metadata.len() >> 20, self.guards.max_file_mb);
+            return Ok(Prepared::Skipped(doc_path));
+        }
+        let bytes = match profile::time(Stage::Read, || fs::read(file_path)) {
+            Ok(b) => b,
+            Err(e) => { eprintln!("⚠️  Skipping unreadable {}: {}", file_path.display(), e); return Ok(Prepared::Failed(doc_path)); }
+        };
+        // Containers are binary by design; everything else is read as text.
+        let container = archive::is_archive(file_path) || epub::is_epub(file_path) || ocr::is_scan(file_path);
+        if let Some(reason) = self.guards.rejects_text(&bytes).filter(|_| !container) {
+            eprintln!("⚠️  Skipping {}: {}", file_path.display(), reason);
+            return Ok(Prepared::Skipped(doc_path));
+        }
+        let hash = blake3::hash(&bytes).to_hex().to_string();
+        // Touched or copied, but the same bytes.
+        if !reread && self.previous.same_content(&doc_path, &hash) { return Ok(Prepared::Unchanged(doc_path)); }
+        if let Some(blobs) = &self.blobs { blobs.put(&hash, &bytes)?; }
+        let mut info = FileInfo {
+            path: file_path.to_path_buf(), doc_id: (batch.prefixed)(canonical_doc_id(file_path, batch.data_dir)),
+            doc_path, category, hash, size: bytes.len() as u64, modified_at, created_at: created_millis(&metadata, modified_at),
+            title: None, author: None,
+        };
+        if archive::is_archive(file_path) { return self.prepare_archive(info, &bytes, batch.folders); }
+        if transcript::is_transcript(file_path) {
+            let cues = transcript::cues(&charset::decode(bytes));
+            if cues.is_empty() { eprintln!("⚠️  Skipping {}: no timed cues", file_path.display()); return Ok(Prepared::Skipped(info.doc_path)); }
+            let segments = transcript::segments(&cues, |s| self.paragraphs.count_tokens(s) <= self.paragraphs.max_tokens());
+            let spoken = segments.iter().map(|s| s.content.as_str()).collect::<Vec<_>>().join("\n\n");
+            let mut record_meta = Meta::new();
+            match transcript::media_for(file_path) {
+                Some(media) => { record_meta.insert(transcript::MEDIA_KEY.to_string(), (batch.prefixed)(relative_doc_path(&media, batch.data_dir))); }
+                None => println!("  no recording next to {}; `play` will not find it", file_path.display()),
+            }
+            let mut junk = JunkReport::default();
+            let mut chunks = profile::time(Stage::Chunk, || self.chunk_transcript(&segments, &info.doc_id, &info.category, &info.doc_path))?;
+            self.drop_junk(&mut chunks, &mut junk);
+            info.stamp(&mut chunks);
+            let doc = PreparedDoc { doc_id: info.doc_id.clone(), chunks, assets: None };
+            return Ok(Prepared::File(Box::new(PreparedFile { summary: summarize(&spoken, SUMMARY_SENTENCES), record_meta, docs: vec![doc], record_doc: true, junk, info })));
+        }
+        if jsonl::is_jsonl(file_path) {
+            let mut junk = JunkReport::default();
+            let mut docs = Vec::new();
+            for record in jsonl::records(&charset::decode(bytes), file_path, &self.jsonl) {
+                let record = match record { Ok(r) => r, Err(e) => { eprintln!("⚠️  Skipping record in {}: {:#}", file_path.display(), e); continue; } };
+                let doc_id = record.id.clone().map(batch.prefixed).unwrap_or_else(|| format!("{}#{}", info.doc_id, record.number));
+                let mut chunks = profile::time(Stage::Chunk, || self.chunk_jsonl_record(&record, &doc_id, &info.category, &info.doc_path))?;
+                self.drop_junk(&mut chunks, &mut junk);
+                info.stamp(&mut chunks);
+                docs.push(PreparedDoc { doc_id, chunks, assets: None });
+            }
+            println!("  {} records in {}", docs.len(), file_path.display());
+            return Ok(Prepared::File(Box::new(PreparedFile { summary: String::new(), record_meta: Meta::new(), docs, record_doc: false, junk, info })));
+        }
+        let mut rows = None;
+        let mut images = Vec::new();
+        // The decoded text of plain-text formats, which chunk spans point into.
+        let mut source = None;
+        let sections = if epub::is_epub(file_path) {
+            match epub::read_chapters(&bytes) {
+                Ok(chapters) => {
+                    let about = epub::read_metadata(&bytes).unwrap_or_default();
+                    (info.title, info.author) = (about.title, about.author);
+                    if let Some(store) = &self.assets { images = store_images(store, &bytes, &chapters, file_path); }
+                    chapters.into_iter().map(|c| c.text).collect()
+                }
+                Err(e) => { eprintln!("⚠️  Skipping unreadable EPUB {}: {:#}", file_path.display(), e); return Ok(Prepared::Failed(info.doc_path)); }
+            }
+        } else if ocr::is_scan(file_path) {
+            match profile::time(Stage::Read, || ocr::read_scan(file_path, &self.ocr)) {
+                // Duplicate pages are claimed in file order, so chunking waits.
+                Ok(pages) if !pages.is_empty() && self.ocr.dedupe => return Ok(Prepared::Scan { info, pages }),
+                Ok(pages) if !pages.is_empty() => pages.into_iter().map(|p| p.text).collect(),
+                Ok(_) => { eprintln!("⚠️  Skipping {}: OCR found no text", file_path.display()); return Ok(Prepared::Skipped(info.doc_path)); }
+                Err(e) => { eprintln!("⚠️  Skipping scan {}: {:#}", file_path.display(), e); return Ok(Prepared::Failed(info.doc_path)); }
+            }
+        } else if csv::is_table(file_path) {
+            let table = csv::rows(&charset::decode(bytes), csv::delimiter(file_path), &self.csv);
+            let texts = table.iter().map(|r| r.text.clone()).collect();
+            rows = Some(table);
+            texts
+        } else {
+            text_pages(source.insert(charset::decode(bytes)))
+        };
+        let mut file = self.finish_sections(info, sections, rows, images, batch.folders)?;
+        if let Some(text) = &source { for doc in &mut file.docs { spans::locate(text, &mut doc.chunks); } }
+        Ok(Prepared::File(Box::new(file)))
+    }
+
+    /// The supported files inside a zip or tar.gz, one document each, prepared
+    /// as if they were files of their own (see `archive`). The archive keeps
+    /// one catalog record under its own doc id.
+    fn prepare_archive(&self, info: FileInfo, bytes: &[u8], folders: &FolderBoilerplate) -> Result<Prepared> {
+        let entries = match profile::time(Stage::Read, || archive::entries(&info.path, bytes, archive::is_supported)) {
+            Ok(entries) => entries,
+            Err(e) => { eprintln!("⚠️  Skipping unreadable archive {}: {:#}", info.path.display(), e); return Ok(Prepared::Failed(info.doc_path)); }
+        };
+        let name = archive::stem(&info.path);
+        let mut docs = Vec::new();
+        let mut junk = JunkReport::default();
+        for entry in entries {
+            let inner = Path::new(&entry.path);
+            if let Some(reason) = self.guards.rejects_text(&entry.bytes).filter(|_| !epub::is_epub(inner)) {
+                eprintln!("⚠️  Skipping {} in {}: {}", entry.path, info.path.display(), reason);
+                continue;
+            }
+            let mut rows = None;
+            let mut source = None;
+            let mut about = epub::Metadata::default();
+            let sections = if epub::is_epub(inner) {
+                match epub::read_chapters(&entry.bytes) {
+                    Ok(chapters) => { about = epub::read_metadata(&entry.bytes).unwrap_or_default(); chapters.into_iter().map(|c| c.text).collect() }
+                    Err(e) => { eprintln!("⚠️  Skipping unreadable EPUB {} in {}: {:#}", entry.path, info.path.display(), e); continue; }
+                }
+            } else if csv::is_table(inner) {
+                let table = csv::rows(&charset::decode(entry.bytes), csv::delimiter(inner), &self.csv);
+                let texts = table.iter().map(|r| r.text.clone()).collect();
+                rows = Some(table);
+                texts
+            } else {
+                text_pages(source.insert(charset::decode(entry.bytes)))
+            };
+            let entry_info = FileInfo {
+                path: PathBuf::from(format!("{}#{}", info.path.display(), entry.path)),
+                doc_id: format!("{}#{}", info.doc_id, canonical_doc_id(inner, Path::new(""))),
+                doc_path: format!("{}#{}", info.doc_path, entry.path),
+                category: archive::entry_facet(&info.category, &name, &entry.path),
+                hash: info.hash.clone(), size: info.size, modified_at: info.modified_at, created_at: info.created_at,
+                title: about.title, author: about.author,
+            };
+            let mut file = self.finish_sections(entry_info, sections, rows, Vec::new(), folders)?;
+            if let Some(text) = &source { for doc in &mut file.docs { spans::locate(text, &mut doc.chunks); } }
+            junk.merge(&file.junk);
+            docs.extend(file.docs);
+        }
+        println!("  {} documents from {}", docs.len(), info.path.display());
+        Ok(Prepared::File(Box::new(PreparedFile { summary: String::new(), record_meta: Meta::new(), docs, record_doc: false, junk, info })))
+    }
+
+    /// Boilerplate stripping, chunking, junk filtering and image anchoring of
+    /// a document read as sections (or CSV rows).
+    fn finish_sections(&self, info: FileInfo, sections: Vec<String>, rows: Option<Vec<csv::Row>>, images: Vec<(usize, Asset)>, folders: &FolderBoilerplate) -> Result<PreparedFile> {
+        // EPUB image anchors count paragraphs, so those chapters are left whole.
+        let (mut sections, removed) = if rows.is_none() && images.is_empty() { self.strip_boilerplate(&info.path, sections, folders) } else { (sections, Vec::new()) };
+        if rows.is_none() { self.hooks.pre_chunk(&info.doc_path, &mut sections)?; }
+        let mut record_meta = Meta::new();
+        if !removed.is_empty() {
+            let report = boilerplate::report(&removed);
+            println!("  ✂️  boilerplate removed from {}: {}", info.path.display(), report);
+            record_meta.insert(BOILERPLATE_KEY.to_string(), report);
+        }
+        let content = sections.join("\n\n");
+        let doc_path = Path::new(&info.doc_path);
+        let mut chunks = profile::time(Stage::Chunk, || match &rows {
+            Some(rows) => self.chunk_rows(rows, &info.doc_id, doc_path, &info.category),
+            None => self.chunk_sections(&sections, &info.doc_id, doc_path, &info.category),
+        })?;
+        info.stamp(&mut chunks);
+        let ends = if images.is_empty() { Vec::new() } else { self.paragraph_ends(&sections, &chunks) };
+        let mut junk = JunkReport::default();
+        // CSV rows are data, never junk.
+        let kept_instead = if rows.is_none() { self.drop_junk(&mut chunks, &mut junk) } else { HashMap::new() };
+        let assets = (self.assets.is_some() && !images.is_empty()).then(|| {
+            // An image after a dropped chunk follows the kept chunk before it.
+            let ends: Vec<Option<String>> = ends.into_iter().map(|id| match kept_instead.get(&id) { Some(before) => before.clone(), None => Some(id) }).collect();
+            images.into_iter().map(|(paragraph, asset)| Asset { after_chunk: paragraph.checked_sub(1).and_then(|i| ends.get(i).cloned().flatten()), ..asset }).collect()
+        });
+        let doc = PreparedDoc { doc_id: info.doc_id.clone(), chunks, assets };
+        Ok(PreparedFile { summary: summarize(&content, SUMMARY_SENTENCES), record_meta, docs: vec![doc], record_doc: true, junk, info })
+    }
+
+    /// Settle a prepared file in file order: final doc ids (moving its chunks
+    /// on a collision), folder metadata, image manifests and the catalog record.
+    fn settle(&self, file: PreparedFile, data_dir: &Path, run: &mut IngestRun, folder_meta: &mut FolderMetaCache, catalog: &mut Vec<FileRecord>, all_chunks: &mut Vec<DocumentChunk>) -> Result<()> {
+        let PreparedFile { info, summary, record_meta, docs, record_doc, junk } = file;
+        let meta = folder_meta.for_dir(info.path.parent().unwrap_or(data_dir))?.to_meta();
+        let mut doc_id = info.doc_id.clone();
+        for mut doc in docs {
+            let id = run.doc_ids.assign(doc.doc_id.clone(), &info.path, &info.doc_path);
+            if id != doc.doc_id { doc.move_to(&id); }
+            if record_doc { doc_id = id.clone(); }
+            if let (Some(store), Some(assets)) = (&self.assets, &doc.assets) { store.put_manifest(&id, assets)?; }
+            // Row and record metadata win over inherited folder metadata.
+            for c in &mut doc.chunks { let own = std::mem::take(&mut c.meta); c.meta = meta.clone(); c.meta.extend(own); }
+            tag_lang(&mut doc.chunks);
+            self.hooks.post_chunk(&mut doc.chunks)?;
+            // Hooks may drop or add chunks.
+            renumber(&mut doc.chunks);
+            all_chunks.extend(doc.chunks);
+        }
+        let mut file_meta = meta;
+        file_meta.extend(record_meta);
+        catalog.push(FileRecord { doc_id, doc_path: info.doc_path, category: info.category, file_hash: info.hash, size: info.size, modified_at: info.modified_at, summary, meta: file_meta });
+        run.junk.merge(&junk);
+        Ok(())
+    }
+
+    /// Drop the chunks `[chunking.filters]` rejects, counting them in `report`,
+    /// and renumber the rest per document. Returns each dropped chunk's id with
+    /// the kept chunk before it in its document, if any.
+    fn drop_junk(&self, chunks: &mut Vec<DocumentChunk>, report: &mut JunkReport) -> HashMap<String, Option<String>> {
+        let filters = &self.paragraphs.config.filters;
+        let mut dropped = HashMap::new();
+        if *filters == JunkFilters::default() { return dropped; }
+        let repeated = filters.repeated(chunks);
+        let mut last_kept: HashMap<String, String> = HashMap::new();
+        chunks.retain(|c| match filters.classify(&c.content).or_else(|| repeated.contains(&c.id).then_some(Junk::Repeated)) {
+            Some(junk) => { report.record(junk); dropped.insert(c.id.clone(), last_kept.get(&c.doc_id).cloned()); false }
+            None => { last_kept.insert(c.doc_id.clone(), c.id.clone()); true }
+        });
+        if !dropped.is_empty() { renumber(chunks); }
+        dropped
+    }
+
+    /// With `[chunking] dedupe`, keep one copy of each chunk repeated across
+    /// files (see `dedupe`), renumbering the documents that lost chunks.
+    fn drop_duplicates(&self, chunks: &mut Vec<DocumentChunk>, catalog: &mut [FileRecord]) {
+        if !self.paragraphs.config.dedupe { return; }
+        let report = dedupe::dedupe(chunks, catalog);
+        if report.chunks == 0 { return; }
+        renumber(chunks);
+        println!("🪞 Dropped {} chunks repeated from other files in {} files; one copy of each is indexed", report.chunks, report.files);
+    }
+
+    /// `sections` without the lines on most of its pages and, for `.txt` files
+    /// with `boilerplate.across_files`, those on most `.txt` files of its folder.
+    fn strip_boilerplate(&self, file_path: &Path, sections: Vec<String>, folders: &FolderBoilerplate) -> (Vec<String>, Vec<Removed>) {
+        let mut keys = boilerplate::detect(&sections, &self.boilerplate);
+        if is_txt(file_path) {
+            keys.extend(folders.get(file_path.parent().unwrap_or(Path::new("."))).into_iter().flatten().cloned());
+        }
+        boilerplate::strip(&sections, &keys)
+    }
+
+    /// Stream the articles of a ZIM archive: doc id = article title (prefixed
+    /// like file ids with several roots), facet per namespace. The archive gets
+    /// one catalog record, hashed from disk; it is not copied to the blob store.
+    fn process_zim(&self, file_path: &Path, data_dir: &Path, category: &str, prefixed: &dyn Fn(String) -> String, doc_ids: &mut DocIdRegistry, catalog: &mut Vec<FileRecord>) -> Result<Vec<DocumentChunk>> {
+        let mut source = ZimSource::open(file_path)?;
+        let archive = file_path.file_stem().and_then(|s| s.to_str()).unwrap_or("zim").to_string();
+        let doc_path = prefixed(relative_doc_path(file_path, data_dir));
+        let article_path = |url: &str| format!("{}#{}", doc_path, url);
+        let metadata = fs::metadata(file_path)?;
+        let modified_at = metadata.modified().ok().and_then(|m| m.duration_since(UNIX_EPOCH).ok()).map(|d| d.as_millis() as i64).unwrap_or(0);
+        let created_at = created_millis(&metadata, modified_at);
+        let mut chunks = Vec::new();
+        let mut articles = 0;
+        for article in source.articles() {
+            let article = article?;
+            let doc_id = doc_ids.assign(prefixed(article.title.clone()), file_path, &article_path(&article.url));
+            chunks.extend(profile::time(Stage::Chunk, || self.chunk_zim_article(&article, &doc_id, category, &archive, &doc_path))?);
+            articles += 1;
         }
+        for c in &mut chunks { c.created_at = Some(created_at); }
+        println!("  {} articles from {}", articles, archive);
+        catalog.push(FileRecord {
+            doc_id: prefixed(canonical_doc_id(file_path, data_dir)), doc_path, category: category.to_string(), file_hash: file_hash(file_path)?,
+            size: metadata.len(), modified_at, summary: String::new(), meta: Meta::new(),
+        });
+        Ok(chunks)
     }
 
-    /// Derive a document id (file stem) from a path.
-    fn extract_doc_id(&self, file_path: &Path) -> String { file_path.file_stem().unwrap().to_string_lossy().to_string() }
+    /// Chunk one ZIM article under `doc_id`; chunks point at
+    /// `<doc_path>#<article url>` and are filed under `zim::article_facet`.
+    pub fn chunk_zim_article(&self, article: &ZimArticle, doc_id: &str, category: &str, archive: &str, doc_path: &str) -> Result<Vec<DocumentChunk>> {
+        let path = format!("{}#{}", doc_path, article.url);
+        let mut chunks = self.chunk_sections(&[article.text.as_str()], doc_id, Path::new(&path), &zim::article_facet(category, archive, article.namespace))?;
+        for c in &mut chunks { c.title = Some(article.title.clone()); }
+        Ok(chunks)
+    }
+
+    /// Chunk one JSON Lines record under `doc_id`; chunks point at
+    /// `<doc_path>#<record number>`, are filed under `jsonl::record_facet` and
+    /// carry the record's metadata fields.
+    pub fn chunk_jsonl_record(&self, record: &jsonl::Record, doc_id: &str, category: &str, doc_path: &str) -> Result<Vec<DocumentChunk>> {
+        let path = format!("{}#{}", doc_path, record.number);
+        let category = record.category.as_deref().map(|c| jsonl::record_facet(category, c)).unwrap_or_else(|| category.to_string());
+        let mut chunks = self.chunk_sections(&[record.text.as_str()], doc_id, Path::new(&path), &category)?;
+        for c in &mut chunks { c.meta = record.meta.clone(); }
+        Ok(chunks)
+    }
+
+    /// One chunk per transcript segment (long ones split by words), with its
+    /// time range and speaker in the chunk's `doc_path` fragment
+    /// (`<doc_path>#t=83.00,100.50&speaker=Alice`, see `transcript::Moment`)
+    /// and in `meta` (`start`, `end`, `speaker`).
+    pub fn chunk_transcript(&self, segments: &[Segment], doc_id: &str, category: &str, doc_path: &str) -> Result<Vec<DocumentChunk>> {
+        let mut document_chunks: Vec<DocumentChunk> = Vec::new();
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        for segment in segments {
+            let path = format!("{}#{}", doc_path, segment.moment.fragment());
+            let pieces = if self.paragraphs.count_tokens(&segment.content) <= self.paragraphs.max_tokens() { vec![segment.content.clone()] } else { self.paragraphs.split_words(&segment.content) };
+            for content in pieces {
+                let chunk_index = document_chunks.len();
+                document_chunks.push(DocumentChunk { id: next_chunk_id(&mut seen, doc_id, &content), doc_id: doc_id.to_string(), doc_path: path.clone(), category: category.to_string(), category_text: category.to_string(), content, chunk_index, total_chunks: 0, title: None, author: None, created_at: None, lang: None, meta: segment.moment.to_meta(), span: None });
+            }
+        }
+        let total_chunks = document_chunks.len(); for chunk in &mut document_chunks { chunk.total_chunks = total_chunks; }
+        Ok(document_chunks)
+    }
+
+    /// Backward-compatibility map from legacy doc ids (file stems, the scheme
+    /// before `canonical_doc_id`) to the ids `process_directory` assigns now.
+    /// A legacy id with several entries was a collision under the old scheme.
+    pub fn legacy_doc_id_map(&self, data_dir: &Path) -> HashMap<String, Vec<String>> {
+        let mut doc_ids = DocIdRegistry::default();
+        let mut map: HashMap<String, Vec<String>> = HashMap::new();
+        // Stem-based ids predate ZIM, scan, JSON Lines, transcript and archive support.
+        let files: Vec<PathBuf> = self.list_source_files(data_dir).into_iter().filter(|p| !zim::is_zim(p) && !ocr::is_scan(p) && !jsonl::is_jsonl(p) && !transcript::is_transcript(p) && !archive::is_archive(p)).collect();
+        let ids: Vec<String> = files.iter().map(|p| canonical_doc_id(p, data_dir)).collect();
+        doc_ids.contest(ids.iter().map(String::as_str));
+        for (file_path, id) in files.iter().zip(ids) {
+            let id = doc_ids.assign(id, file_path, &relative_doc_path(file_path, data_dir));
+            map.entry(legacy_doc_id(file_path)).or_default().push(id);
+        }
+        map
+    }
 
     /// Build a simple facet from the directory path relative to the root.
     fn get_facet_from_path(&self, file_path: &Path, data_dir: &Path) -> String {
@@ -80,56 +1088,78 @@ impl DataProcessor {
         "misc".to_string()
     }
 
+    /// Chunk an in-memory document exactly as `process_directory` would chunk a
+    /// file with this content. Never panics on arbitrary input (fuzzed).
+    pub fn chunk_text(&self, content: &str, doc_id: &str, file_path: &Path, category: &str) -> Result<Vec<DocumentChunk>> {
+        let mut chunks = self.chunk_content(content, doc_id, file_path, category)?;
+        spans::locate(content, &mut chunks);
+        Ok(chunks)
+    }
+
     /// Split content into paragraph chunks, then add overlapped sub-chunks for
     /// paragraphs exceeding the token budget.
     fn chunk_content(&self, content: &str, doc_id: &str, file_path: &Path, category: &str) -> Result<Vec<DocumentChunk>> {
-        let paragraphs: Vec<&str> = content.split("\n\n").collect();
-        let mut document_chunks = Vec::new();
-        let mut chunk_index = 0;
-        for paragraph in paragraphs {
-            let paragraph = paragraph.trim(); if paragraph.is_empty() { continue; }
-            let tokens = self.count_tokens(paragraph);
-            if tokens <= self.chunking_config.max_tokens {
-                document_chunks.push(DocumentChunk { id: format!("{}:{}", doc_id, chunk_index), doc_id: doc_id.to_string(), doc_path: file_path.to_string_lossy().to_string(), category: category.to_string(), category_text: category.to_string(), content: paragraph.to_string(), chunk_index, total_chunks: 0 });
-                chunk_index += 1;
-            } else {
-                for sub_chunk in self.split_paragraph_with_overlap(paragraph) {
-                    document_chunks.push(DocumentChunk { id: format!("{}:{}", doc_id, chunk_index), doc_id: doc_id.to_string(), doc_path: file_path.to_string_lossy().to_string(), category: category.to_string(), category_text: category.to_string(), content: sub_chunk, chunk_index, total_chunks: 0 });
-                    chunk_index += 1;
-                }
+        self.chunk_sections(&[content], doc_id, file_path, category)
+    }
+
+    /// `Chunker::chunk` over consecutive sections (EPUB chapters): the
+    /// paragraph splitter unless `with_chunker` set another. Chunks never
+    /// cross a section boundary; ids and `chunk_index` run across the document.
+    fn chunk_sections<S: AsRef<str>>(&self, sections: &[S], doc_id: &str, file_path: &Path, category: &str) -> Result<Vec<DocumentChunk>> {
+        let Some(chunker) = &self.chunker else { return self.paragraphs.chunk_sections(sections, doc_id, file_path, category) };
+        let doc_path = file_path.to_string_lossy().to_string();
+        let source = ChunkSource { doc_id, doc_path: &doc_path, category };
+        let mut chunks = Vec::new();
+        for section in sections { chunks.extend(chunker.chunk(section.as_ref(), &source)?); }
+        chunks.retain(|c| !c.content.trim().is_empty());
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        let total_chunks = chunks.len();
+        for (chunk_index, c) in chunks.iter_mut().enumerate() {
+            c.id = next_chunk_id(&mut seen, doc_id, &c.content);
+            (c.doc_id, c.doc_path, c.chunk_index, c.total_chunks) = (doc_id.to_string(), doc_path.clone(), chunk_index, total_chunks);
+            if c.category.is_empty() { c.category = category.to_string(); }
+            c.category_text = c.category.clone();
+        }
+        Ok(chunks)
+    }
+
+    /// One chunk per CSV row (long rows split with overlap), filed under the
+    /// row's facet and carrying its metadata columns; ids and `chunk_index`
+    /// run across the file.
+    fn chunk_rows(&self, rows: &[csv::Row], doc_id: &str, file_path: &Path, category: &str) -> Result<Vec<DocumentChunk>> {
+        let mut document_chunks: Vec<DocumentChunk> = Vec::new();
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        for row in rows {
+            let row_category = row.facet.as_deref().map(|f| csv::row_facet(category, f)).unwrap_or_else(|| category.to_string());
+            let pieces = if self.paragraphs.count_tokens(&row.text) <= self.paragraphs.max_tokens() { vec![row.text.clone()] } else { self.paragraphs.split_paragraph_with_overlap(&row.text) };
+            for content in pieces {
+                let chunk_index = document_chunks.len();
+                document_chunks.push(DocumentChunk { id: next_chunk_id(&mut seen, doc_id, &content), doc_id: doc_id.to_string(), doc_path: file_path.to_string_lossy().to_string(), category: row_category.clone(), category_text: row_category.clone(), content, chunk_index, total_chunks: 0, title: None, author: None, created_at: None, lang: None, meta: row.meta.clone(), span: None });
             }
         }
         let total_chunks = document_chunks.len(); for chunk in &mut document_chunks { chunk.total_chunks = total_chunks; }
         Ok(document_chunks)
     }
 
-    /// Rough token count: word count divided by a constant.
-    fn count_tokens(&self, text: &str) -> usize { let word_count = text.split_whitespace().count(); (word_count as f32 / 0.75) as usize }
+    /// Id of the last chunk made from each paragraph of `sections`, for
+    /// anchoring EPUB images; a custom chunker's chunks are not traced back
+    /// to paragraphs, so its images go before the first chunk.
+    fn paragraph_ends(&self, sections: &[String], chunks: &[DocumentChunk]) -> Vec<String> {
+        if self.chunker.is_some() { return Vec::new(); }
+        self.paragraphs.paragraph_ends(sections, chunks)
+    }
 
-    /// Break a long paragraph into overlapping word windows.
-    fn split_paragraph_with_overlap(&self, paragraph: &str) -> Vec<String> {
-        let words: Vec<&str> = paragraph.split_whitespace().collect();
-        let words_per_chunk = 300; let overlap_words = (words_per_chunk as f32 * self.chunking_config.overlap_percent) as usize;
-        let mut chunks = Vec::new(); let mut start = 0;
-        while start < words.len() {
-            let end = (start + words_per_chunk).min(words.len());
-            chunks.push(words[start..end].join(" "));
-            if end >= words.len() { break; }
-            start = end - overlap_words;
-        }
-        chunks
-//! Pragmatic paragraph-based text chunker for `.txt` sources.
-//!
-//! Splits input files by blank lines, then further splits long paragraphs with
-//! overlap. Token count is approximated by word count / 0.75.
+    /// Find all `.txt`, `.epub`, `.zim`, `.csv`/`.tsv`, JSON Lines, transcript, scan (image/PDF) and archive files recursively under `root`.
+    fn list_source_files(&self, root: &Path) -> Vec<PathBuf> {
+        self.list_files(root, |p| p.extension().and_then(|s| s.to_str()) == Some("txt") || epub::is_epub(p) || zim::is_zim(p) || ocr::is_scan(p) || csv::is_table(p) || jsonl::is_jsonl(p) || transcript::is_transcript(p) || archive::is_archive(p))
     }
 
-    /// Find all `.txt` files recursively under `root`.
-    fn list_txt_files(&self, root: &Path) -> Vec<PathBuf> {
-        let mut txt_files = Vec::new();
+    /// Find all files under `root` accepted by `keep`, sorted.
+    fn list_files(&self, root: &Path, keep: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+        let mut files = Vec::new();
         for entry in walkdir::WalkDir::new(root).into_iter().filter_map(|e| e.ok()).filter(|e| e.file_type().is_file()) {
-            let path = entry.path(); if path.extension().and_then(|s| s.to_str()) == Some("txt") { txt_files.push(path.to_path_buf()); }
+            let path = entry.path(); if keep(path) { files.push(path.to_path_buf()); }
         }
-        txt_files.sort(); txt_files
+        files.sort(); files
     }
 }
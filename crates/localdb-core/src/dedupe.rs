@@ -0,0 +1,93 @@
+//! Exact-duplicate chunks across files (`[chunking] dedupe`).
+//!
+//! Mirrored downloads and copied folders produce chunks with the same text,
+//! i.e. the same blake3 `content_hash`; indexed as they are, every query that
+//! finds one finds all of them. Only the first copy in file order is kept.
+//! Its `meta` lists the other sources under `ALSO_IN_KEY`, and the catalog
+//! record of each file that lost chunks names the files holding them under
+//! `DUPLICATES_IN_KEY`, so an incremental ingest reads it again when one of
+//! those changes (`PreviousIngest::dependents`), and reads a holder again
+//! when one of those files changes, to update `also_in`
+//! (`PreviousIngest::holders_of`). Each record also lists the hashes of its
+//! indexed chunks (`CHUNK_HASHES_KEY`): an incremental ingest reads an
+//! unchanged file again when a file it does read repeats one, so the two are
+//! still compared. Repeats within one file stay (see `chunk_id`).
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::types::{file_candidates, DocumentChunk, FileRecord, Meta};
+
+/// `DocumentChunk::meta` key listing the other `doc_path`s with this chunk's text (a JSON list).
+pub const ALSO_IN_KEY: &str = "also_in";
+
+/// `FileRecord::meta` key listing the files whose chunks stand in for some
+/// of this file's (a JSON list of `doc_path`s).
+pub const DUPLICATES_IN_KEY: &str = "duplicates_in";
+
+/// `FileRecord::meta` key listing `chunk_hash` of each chunk indexed for the
+/// file (a JSON list).
+pub const CHUNK_HASHES_KEY: &str = "chunk_hashes";
+
+/// What `dedupe` dropped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DedupeReport {
+    /// Chunks dropped as copies.
+    pub chunks: usize,
+    /// Files that lost at least one chunk.
+    pub files: usize,
+}
+
+/// The file among `files` (catalog `doc_path`s) a chunk's `doc_path`
+/// belongs to: archive entries, ZIM articles and transcript moments
+/// (`file#part`) belong to their file, whose name may hold a `#` itself.
+pub fn file_of<'a>(doc_path: &'a str, files: &HashSet<String>) -> &'a str {
+    file_candidates(doc_path).find(|f| files.contains(*f)).unwrap_or(doc_path)
+}
+
+/// Short hash of a chunk's text, as listed under `CHUNK_HASHES_KEY`.
+pub fn chunk_hash(content: &str) -> String { blake3::hash(content.as_bytes()).to_hex()[..16].to_string() }
+
+/// The list stored under `key` in `meta` (`ALSO_IN_KEY`, `DUPLICATES_IN_KEY`,
+/// `CHUNK_HASHES_KEY`); ingests before these were JSON joined them with `; `.
+pub fn listed(meta: &Meta, key: &str) -> Vec<String> {
+    let Some(value) = meta.get(key) else { return Vec::new() };
+    serde_json::from_str(value).unwrap_or_else(|_| value.split("; ").map(str::to_string).collect())
+}
+
+fn json_list(items: impl IntoIterator<Item = String>) -> String {
+    serde_json::to_string(&items.into_iter().collect::<Vec<_>>()).unwrap_or_default()
+}
+
+/// Drop every chunk whose text an earlier chunk of another file already has,
+/// recording the sources on the kept chunk and in `catalog` (see the module
+/// docs). Chunks keep their order; the caller renumbers the documents that
+/// lost chunks.
+pub fn dedupe(chunks: &mut Vec<DocumentChunk>, catalog: &mut [FileRecord]) -> DedupeReport {
+    let files: HashSet<String> = catalog.iter().map(|r| r.doc_path.clone()).collect();
+    let mut first: HashMap<blake3::Hash, usize> = HashMap::new();
+    let mut also_in: HashMap<usize, BTreeSet<String>> = HashMap::new();
+    let mut holders: HashMap<String, BTreeSet<String>> = HashMap::new();
+    let mut kept: Vec<DocumentChunk> = Vec::with_capacity(chunks.len());
+    let mut dropped = 0;
+    for chunk in chunks.drain(..) {
+        let hash = blake3::hash(chunk.content.as_bytes());
+        match first.get(&hash) {
+            Some(&i) if file_of(&kept[i].doc_path, &files) != file_of(&chunk.doc_path, &files) => {
+                also_in.entry(i).or_default().insert(chunk.doc_path.clone());
+                holders.entry(file_of(&chunk.doc_path, &files).to_string()).or_default().insert(file_of(&kept[i].doc_path, &files).to_string());
+                dropped += 1;
+            }
+            Some(_) => kept.push(chunk),
+            None => { first.insert(hash, kept.len()); kept.push(chunk); }
+        }
+    }
+    for (i, paths) in also_in { kept[i].meta.insert(ALSO_IN_KEY.to_string(), json_list(paths)); }
+    let mut hashes: HashMap<&str, BTreeSet<String>> = HashMap::new();
+    for c in &kept { hashes.entry(file_of(&c.doc_path, &files)).or_default().insert(chunk_hash(&c.content)); }
+    for record in catalog.iter_mut() {
+        if let Some(files) = holders.get(&record.doc_path) { record.meta.insert(DUPLICATES_IN_KEY.to_string(), json_list(files.iter().cloned())); }
+        if let Some(hashes) = hashes.remove(record.doc_path.as_str()) { record.meta.insert(CHUNK_HASHES_KEY.to_string(), json_list(hashes)); }
+    }
+    *chunks = kept;
+    DedupeReport { chunks: dropped, files: holders.len() }
+}
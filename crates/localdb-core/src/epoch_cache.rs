@@ -0,0 +1,90 @@
+//! Caches tied to the index epoch.
+//!
+//! A long-running server keeps things derived from the indexes: the open
+//! search engine (whose Tantivy searcher is a snapshot, and which read facet
+//! aliases and score calibration when opened) and rendered result pages.
+//! Each is stored with the epoch it was built at (`d<version>.m<version>`,
+//! see `localdb_vector::table::index_epoch`) and rebuilt or dropped on first
+//! use after an ingest, generation flip, backfill or facet rename moves the
+//! epoch, so no cached artifact outlives the index it came from.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// One value, rebuilt when the epoch changes.
+pub struct EpochCell<T> { slot: Mutex<Option<(String, Arc<T>)>> }
+
+impl<T> Default for EpochCell<T> {
+    fn default() -> Self { Self { slot: Mutex::new(None) } }
+}
+
+impl<T> EpochCell<T> {
+    /// A cell already holding `value` built at `epoch`.
+    pub fn new(epoch: &str, value: T) -> Self { Self { slot: Mutex::new(Some((epoch.to_string(), Arc::new(value)))) } }
+
+    /// The value built at `epoch`, running `build` first when the cell holds
+    /// none or one from another epoch. Concurrent callers wait for one build;
+    /// a failed build leaves the old value in place for the next attempt.
+    pub fn get_or_build<E>(&self, epoch: &str, build: impl FnOnce() -> Result<T, E>) -> Result<Arc<T>, E> {
+        let mut slot = self.slot.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((built_at, value)) = slot.as_ref() {
+            if built_at == epoch { return Ok(value.clone()); }
+        }
+        let value = Arc::new(build()?);
+        *slot = Some((epoch.to_string(), value.clone()));
+        Ok(value)
+    }
+
+    /// Epoch of the value held, if any.
+    pub fn epoch(&self) -> Option<String> {
+        self.slot.lock().unwrap_or_else(|e| e.into_inner()).as_ref().map(|(e, _)| e.clone())
+    }
+}
+
+struct Entries<V> {
+    epoch: String,
+    values: HashMap<String, V>,
+    /// Keys oldest first, for eviction.
+    order: VecDeque<String>,
+}
+
+/// Up to `capacity` values by key, all dropped when the epoch changes; the
+/// oldest entry makes room for a new one.
+pub struct EpochMap<V> { capacity: usize, entries: Mutex<Entries<V>> }
+
+impl<V: Clone> EpochMap<V> {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, entries: Mutex::new(Entries { epoch: String::new(), values: HashMap::new(), order: VecDeque::new() }) }
+    }
+
+    /// The value stored under `key` at `epoch`.
+    pub fn get(&self, epoch: &str, key: &str) -> Option<V> {
+        let entries = self.current(epoch);
+        entries.values.get(key).cloned()
+    }
+
+    pub fn insert(&self, epoch: &str, key: &str, value: V) {
+        if self.capacity == 0 { return; }
+        let mut entries = self.current(epoch);
+        if entries.values.insert(key.to_string(), value).is_none() { entries.order.push_back(key.to_string()); }
+        while entries.values.len() > self.capacity {
+            let Some(oldest) = entries.order.pop_front() else { break };
+            entries.values.remove(&oldest);
+        }
+    }
+
+    pub fn len(&self) -> usize { self.entries.lock().unwrap_or_else(|e| e.into_inner()).values.len() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// The entries, emptied first if they were stored at another epoch.
+    fn current(&self, epoch: &str) -> std::sync::MutexGuard<'_, Entries<V>> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.epoch != epoch {
+            entries.epoch = epoch.to_string();
+            entries.values.clear();
+            entries.order.clear();
+        }
+        entries
+    }
+}
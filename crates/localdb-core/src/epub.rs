@@ -0,0 +1,319 @@
+//! EPUB reader for ingest.
+//!
+//! An EPUB is a zip of XHTML documents. `META-INF/container.xml` names the
+//! package (`.opf`) file; its `<manifest>` maps item ids to files and its
+//! `<spine>` lists the reading order. `read_chapters` walks the spine and
+//! strips each item's HTML to plain text with one paragraph per block element
+//! (`<p>`, headings, list items, ...), so `DataProcessor` can chunk every
+//! chapter on its own. Scripts, styles and `<head>` are dropped; common
+//! entities are decoded. No XML validation: broken markup degrades to text.
+//! Members that inflate past `MAX_ENTRY_BYTES` fail the book (zip bombs).
+//! Images (`<img>`, SVG `<image>`) are listed per chapter with their archive
+//! path and position so `assets` can keep them for the web UI. The package's
+//! Dublin Core `<metadata>` gives the book's title and author (`read_metadata`).
+//! Reading the zip needs the `zip` feature; without it every book fails.
+#![cfg_attr(not(feature = "zip"), allow(dead_code, unused_imports))]
+
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+
+/// Largest archive member read, inflated; a bigger one fails the book.
+pub const MAX_ENTRY_BYTES: u64 = 64 << 20;
+
+/// One spine item as plain text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    /// Paragraphs separated by blank lines.
+    pub text: String,
+    /// Referenced images in document order.
+    pub images: Vec<ImageRef>,
+}
+
+/// An image referenced from a chapter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageRef {
+    /// Archive path (`OEBPS/images/pump.png`) once read by `read_chapters`;
+    /// the `src` as written in `strip_html` output.
+    pub src: String,
+    pub alt: Option<String>,
+    /// How many of the chapter's paragraphs come before the image.
+    pub paragraph: usize,
+}
+
+/// Book-level metadata from the package file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// First `dc:title`.
+    pub title: Option<String>,
+    /// First `dc:creator`.
+    pub author: Option<String>,
+}
+
+/// Whether `path` looks like an EPUB by extension.
+pub fn is_epub(path: &std::path::Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("epub"))
+}
+
+/// Chapters of an EPUB in spine order; items without text are skipped.
+#[cfg(feature = "zip")]
+pub fn read_chapters(bytes: &[u8]) -> Result<Vec<Chapter>> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(bytes)).context("not a zip archive")?;
+    let (opf_path, opf) = read_package(&mut zip)?;
+    let base = opf_path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("");
+
+    let mut manifest: HashMap<String, String> = HashMap::new();
+    let mut spine = Vec::new();
+    for t in tags(&opf) {
+        match t.name.as_str() {
+            "item" => if let (Some(id), Some(href)) = (t.attr("id"), t.attr("href")) { manifest.insert(id, resolve_href(base, &href)); },
+            "itemref" => if let Some(idref) = t.attr("idref") { spine.push(idref); },
+            _ => {}
+        }
+    }
+    if spine.is_empty() { return Err(anyhow!("package {} has an empty spine", opf_path)); }
+
+    let mut chapters = Vec::new();
+    for idref in spine {
+        let Some(path) = manifest.get(&idref) else { continue };
+        let mut chapter = strip_html(&read_entry(&mut zip, path)?);
+        let dir = path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("");
+        chapter.images.retain(|i| !i.src.contains(':'));
+        for image in &mut chapter.images { image.src = resolve_href(dir, &image.src); }
+        if !chapter.text.is_empty() { chapters.push(chapter); }
+    }
+    Ok(chapters)
+}
+
+/// Title and author of an EPUB; either is `None` when the package has none.
+#[cfg(feature = "zip")]
+pub fn read_metadata(bytes: &[u8]) -> Result<Metadata> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(bytes)).context("not a zip archive")?;
+    let (_, opf) = read_package(&mut zip)?;
+    Ok(package_metadata(&opf))
+}
+
+/// Path and text of the package file named by `META-INF/container.xml`.
+#[cfg(feature = "zip")]
+fn read_package(zip: &mut zip::ZipArchive<Cursor<&[u8]>>) -> Result<(String, String)> {
+    let container = read_entry(zip, "META-INF/container.xml")?;
+    let opf_path = tags(&container).find(|t| t.name == "rootfile").and_then(|t| t.attr("full-path"))
+        .ok_or_else(|| anyhow!("container.xml names no rootfile"))?;
+    let opf = read_entry(zip, &opf_path)?;
+    Ok((opf_path, opf))
+}
+
+/// The first non-empty `<dc:title>` and `<dc:creator>` of a package document.
+fn package_metadata(opf: &str) -> Metadata {
+    let mut meta = Metadata::default();
+    let mut open: Option<String> = None;
+    let mut text = String::new();
+    for piece in markup(opf) {
+        match piece {
+            Piece::Tag(t) if !t.closing && !t.self_closing && (t.name == "title" || t.name == "creator") => { open = Some(t.name); text.clear(); }
+            Piece::Tag(t) if t.closing && open.as_deref() == Some(t.name.as_str()) => {
+                let value = decode_entities(&text.split_whitespace().collect::<Vec<_>>().join(" "));
+                let slot = if t.name == "title" { &mut meta.title } else { &mut meta.author };
+                if slot.is_none() && !value.is_empty() { *slot = Some(value); }
+                open = None;
+            }
+            Piece::Text(s) if open.is_some() => text.push_str(s),
+            _ => {}
+        }
+    }
+    meta
+}
+
+/// Raw bytes of archive entries (`ImageRef::src` paths); `None` for missing ones.
+#[cfg(feature = "zip")]
+pub fn read_files(bytes: &[u8], names: &[&str]) -> Result<Vec<Option<Vec<u8>>>> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(bytes)).context("not a zip archive")?;
+    Ok(names.iter().map(|name| read_bytes(&mut zip, name).ok()).collect())
+}
+
+#[cfg(feature = "zip")]
+fn read_entry(zip: &mut zip::ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<String> {
+    Ok(String::from_utf8_lossy(&read_bytes(zip, name)?).into_owned())
+}
+
+#[cfg(feature = "zip")]
+fn read_bytes(zip: &mut zip::ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<Vec<u8>> {
+    let entry = zip.by_name(name).with_context(|| format!("missing {}", name))?;
+    if entry.size() > MAX_ENTRY_BYTES { return Err(anyhow!("{} inflates to {} bytes", name, entry.size())); }
+    let mut bytes = Vec::new();
+    entry.take(MAX_ENTRY_BYTES + 1).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > MAX_ENTRY_BYTES { return Err(anyhow!("{} inflates past {} bytes", name, MAX_ENTRY_BYTES)); }
+    Ok(bytes)
+}
+
+#[cfg(not(feature = "zip"))]
+pub fn read_chapters(_bytes: &[u8]) -> Result<Vec<Chapter>> { Err(anyhow!("built without the `zip` feature")) }
+
+#[cfg(not(feature = "zip"))]
+pub fn read_metadata(_bytes: &[u8]) -> Result<Metadata> { Err(anyhow!("built without the `zip` feature")) }
+
+#[cfg(not(feature = "zip"))]
+pub fn read_files(_bytes: &[u8], _names: &[&str]) -> Result<Vec<Option<Vec<u8>>>> { Err(anyhow!("built without the `zip` feature")) }
+
+/// Archive path of a manifest `href` relative to the package directory.
+fn resolve_href(base: &str, href: &str) -> String {
+    let href = percent_decode(href.split('#').next().unwrap_or(href));
+    let mut parts: Vec<&str> = if base.is_empty() { Vec::new() } else { base.split('/').collect() };
+    for seg in href.split('/') {
+        match seg {
+            "" | "." => {}
+            ".." => { parts.pop(); }
+            s => parts.push(s),
+        }
+    }
+    parts.join("/")
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%').then(|| s.get(i + 1..i + 3)).flatten().and_then(|h| u8::from_str_radix(h, 16).ok());
+        match hex {
+            Some(b) => { out.push(b); i += 3; }
+            None => { out.push(bytes[i]); i += 1; }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// A start or end tag: lowercase local name (namespace prefix dropped) and
+/// the raw attribute text.
+struct Tag<'a> { name: String, closing: bool, self_closing: bool, attrs: &'a str }
+
+impl Tag<'_> {
+    fn attr(&self, key: &str) -> Option<String> {
+        let mut rest = self.attrs;
+        while let Some(eq) = rest.find('=') {
+            let name = rest[..eq].split_whitespace().last().unwrap_or("");
+            let after = rest[eq + 1..].trim_start();
+            let (value, tail) = match after.chars().next() {
+                Some(q @ ('"' | '\'')) => match after[1..].find(q) {
+                    Some(end) => (&after[1..1 + end], &after[end + 2..]),
+                    None => (&after[1..], ""),
+                },
+                _ => { let end = after.find(char::is_whitespace).unwrap_or(after.len()); (&after[..end], &after[end..]) }
+            };
+            if name.rsplit(':').next() == Some(key) { return Some(decode_entities(value)); }
+            rest = tail;
+        }
+        None
+    }
+}
+
+enum Piece<'a> { Tag(Tag<'a>), Text(&'a str) }
+
+/// Tags of a markup document in order.
+fn tags(src: &str) -> impl Iterator<Item = Tag<'_>> {
+    markup(src).filter_map(|piece| match piece { Piece::Tag(t) => Some(t), Piece::Text(_) => None })
+}
+
+/// Tags and raw text runs; comments, declarations and processing
+/// instructions are skipped, CDATA sections are text.
+fn markup(src: &str) -> impl Iterator<Item = Piece<'_>> {
+    let mut rest = src;
+    std::iter::from_fn(move || loop {
+        if rest.is_empty() { return None; }
+        let Some(lt) = rest.find('<') else { let text = rest; rest = ""; return Some(Piece::Text(text)); };
+        if lt > 0 { let text = &rest[..lt]; rest = &rest[lt..]; return Some(Piece::Text(text)); }
+        if let Some((open, close)) = [("<!--", "-->"), ("<![CDATA[", "]]>")].into_iter().find(|(open, _)| rest.starts_with(open)) {
+            let body = &rest[open.len()..];
+            let (inner, consumed) = match body.find(close) { Some(e) => (&body[..e], open.len() + e + close.len()), None => (body, rest.len()) };
+            rest = &rest[consumed..];
+            if open == "<![CDATA[" { return Some(Piece::Text(inner)); }
+            continue;
+        }
+        let (raw, consumed) = match rest.find('>') { Some(e) => (&rest[1..e], e + 1), None => (&rest[1..], rest.len()) };
+        rest = &rest[consumed..];
+        if raw.starts_with('!') || raw.starts_with('?') { continue; }
+        let closing = raw.starts_with('/');
+        let self_closing = raw.ends_with('/');
+        let body = raw.trim_start_matches('/').trim_end_matches('/');
+        let name_end = body.find(char::is_whitespace).unwrap_or(body.len());
+        let name = body[..name_end].rsplit(':').next().unwrap_or("").to_ascii_lowercase();
+        return Some(Piece::Tag(Tag { name, closing, self_closing, attrs: &body[name_end..] }));
+    })
+}
+
+/// Elements that end a paragraph.
+const BLOCK_TAGS: &[&str] = &[
+    "p", "div", "section", "article", "aside", "blockquote", "pre", "li", "ul", "ol", "dl", "dt", "dd",
+    "h1", "h2", "h3", "h4", "h5", "h6", "table", "tr", "td", "th", "hr", "figure", "figcaption", "body",
+];
+
+/// Elements whose content is never text.
+const SKIP_TAGS: &[&str] = &["head", "script", "style", "svg", "math"];
+
+/// Plain text of an XHTML document: one paragraph per block element.
+pub fn strip_html(html: &str) -> Chapter {
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut skipping: Option<String> = None;
+    let mut images = Vec::new();
+    let mut image = |t: &Tag, key: &str, paragraphs: &[String], current: &str| {
+        let Some(src) = t.attr(key).filter(|s| !s.trim().is_empty()) else { return };
+        let paragraph = paragraphs.len() + usize::from(!current.trim().is_empty());
+        images.push(ImageRef { src: src.trim().to_string(), alt: t.attr("alt").map(|a| a.trim().to_string()).filter(|a| !a.is_empty()), paragraph });
+    };
+    let flush = |current: &mut String, paragraphs: &mut Vec<String>| {
+        let p = current.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !p.is_empty() { paragraphs.push(p); }
+        current.clear();
+    };
+    for piece in markup(html) {
+        match piece {
+            Piece::Tag(t) => {
+                if let Some(skip) = &skipping {
+                    if skip == "svg" && t.name == "image" && !t.closing { image(&t, "href", &paragraphs, &current); }
+                    if t.closing && t.name == *skip { skipping = None; }
+                    continue;
+                }
+                if !t.closing && !t.self_closing && SKIP_TAGS.contains(&t.name.as_str()) { skipping = Some(t.name.clone()); continue; }
+                if t.name == "br" { current.push(' '); continue; }
+                if t.name == "img" && !t.closing { image(&t, "src", &paragraphs, &current); continue; }
+                if BLOCK_TAGS.contains(&t.name.as_str()) { flush(&mut current, &mut paragraphs); }
+            }
+            Piece::Text(raw) => {
+                if skipping.is_none() { current.push_str(&decode_entities(raw)); }
+            }
+        }
+    }
+    flush(&mut current, &mut paragraphs);
+    Chapter { text: paragraphs.join("\n\n"), images }
+}
+
+/// Decode the XML entities plus `&nbsp;` and numeric references; unknown
+/// entities are kept as written.
+fn decode_entities(s: &str) -> String {
+    if !s.contains('&') { return s.to_string(); }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest[1..].find(';').filter(|&end| end <= 10).and_then(|end| {
+            let name = &rest[1..1 + end];
+            let c = match name {
+                "amp" => Some('&'), "lt" => Some('<'), "gt" => Some('>'), "quot" => Some('"'), "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'), "mdash" => Some('—'), "ndash" => Some('–'), "hellip" => Some('…'),
+                "lsquo" => Some('‘'), "rsquo" => Some('’'), "ldquo" => Some('“'), "rdquo" => Some('”'),
+                _ => name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")).map(|h| u32::from_str_radix(h, 16).ok())
+                    .unwrap_or_else(|| name.strip_prefix('#').and_then(|d| d.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, end + 2))
+        });
+        match decoded {
+            Some((c, len)) => { out.push(c); rest = &rest[len..]; }
+            None => { out.push('&'); rest = &rest[1..]; }
+        }
+    }
+    out.push_str(rest);
+    out
+}
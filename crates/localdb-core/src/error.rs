@@ -10,6 +10,21 @@ pub enum Error {
 
     #[error("Operation failed: {0}")]
     Operation(String),
+
+    #[error("Embedder unavailable: {0}")]
+    EmbedderUnavailable(String),
+
+    /// Another process is writing the index (see `writer_lock`).
+    #[error("Index busy: {0}")]
+    IndexBusy(String),
+
+    /// A record batch column is missing or has an unexpected Arrow type.
+    #[error("Column '{column}' missing or not {expected}")]
+    BadColumn { column: String, expected: &'static str },
+
+    /// A stored field of a specific document is missing or has the wrong type.
+    #[error("Field '{field}' missing or not {expected} in doc {doc_id}")]
+    BadField { doc_id: String, field: String, expected: &'static str },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
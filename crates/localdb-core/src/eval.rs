@@ -0,0 +1,97 @@
+//! Pairwise relevance judgments for evaluating search strategies.
+//!
+//! `localdb-cli judge` runs one query under two fusion strategies, shows the
+//! results that differ at each rank side by side (in a blind, per-pair order),
+//! and appends the preference to a JSON-lines dataset. `win_rates` summarizes
+//! the dataset per strategy; the same file can feed offline eval runs.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// One side of a comparison.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candidate {
+    pub strategy: String,
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Preference {
+    Left,
+    Right,
+    Tie,
+    /// Neither result is relevant.
+    BothBad,
+}
+
+/// A recorded preference between two results at the same 1-based `rank`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Judgment {
+    pub query: String,
+    pub rank: usize,
+    pub left: Candidate,
+    pub right: Candidate,
+    pub preference: Preference,
+    pub at_ms: i64,
+}
+
+/// Append-only JSON-lines judgment file.
+pub struct EvalDataset { path: PathBuf }
+
+impl EvalDataset {
+    pub fn new(path: impl Into<PathBuf>) -> Self { Self { path: path.into() } }
+
+    pub fn path(&self) -> &Path { &self.path }
+
+    pub fn append(&self, judgment: &Judgment) -> Result<()> {
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) { fs::create_dir_all(dir)?; }
+        let mut f = fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(f, "{}", serde_json::to_string(judgment)?)?;
+        Ok(())
+    }
+
+    /// All judgments; a missing file is empty and malformed lines are skipped.
+    pub fn judgments(&self) -> Result<Vec<Judgment>> {
+        if !self.path.exists() { return Ok(Vec::new()); }
+        Ok(fs::read_to_string(&self.path)?.lines().filter_map(|l| serde_json::from_str(l).ok()).collect())
+    }
+}
+
+/// Ranks (1-based) where two result lists disagree, with the two ids. Pairs
+/// already judged for `query` (in either order) are left out.
+pub fn disagreements(query: &str, a: &[String], b: &[String], judged: &[Judgment]) -> Vec<(usize, String, String)> {
+    let seen = |x: &str, y: &str| judged.iter().any(|j| j.query == query && ((j.left.id == x && j.right.id == y) || (j.left.id == y && j.right.id == x)));
+    a.iter().zip(b).enumerate()
+        .filter(|(_, (x, y))| x != y && !seen(x, y))
+        .map(|(i, (x, y))| (i + 1, x.clone(), y.clone()))
+        .collect()
+}
+
+/// Pairwise record of one strategy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WinRate {
+    pub strategy: String,
+    pub wins: usize,
+    pub losses: usize,
+    pub ties: usize,
+}
+
+/// Per-strategy wins/losses/ties (both-bad counts as a tie), sorted by name.
+pub fn win_rates(judgments: &[Judgment]) -> Vec<WinRate> {
+    let mut by: BTreeMap<&str, WinRate> = BTreeMap::new();
+    for j in judgments {
+        let (l, r) = (j.left.strategy.as_str(), j.right.strategy.as_str());
+        if l == r { continue; }
+        match j.preference {
+            Preference::Left => { by.entry(l).or_default().wins += 1; by.entry(r).or_default().losses += 1; }
+            Preference::Right => { by.entry(r).or_default().wins += 1; by.entry(l).or_default().losses += 1; }
+            Preference::Tie | Preference::BothBad => { by.entry(l).or_default().ties += 1; by.entry(r).or_default().ties += 1; }
+        }
+    }
+    by.into_iter().map(|(s, rate)| WinRate { strategy: s.to_string(), ..rate }).collect()
+}
@@ -0,0 +1,90 @@
+//! Facet aliases: renamed directories without a reindex.
+//!
+//! Renaming `/garden/veg` to `/garden/vegetables` on disk would orphan every
+//! indexed chunk filed under the old facet. `localdb-cli facet rename` records
+//! an alias (old → new, stored in Lance `meta`) instead. Aliases apply
+//! segment-wise to descendants (`/garden/veg/roots` → `/garden/vegetables/roots`)
+//! and are honoured at query/display time; `localdb-cli maintain` later
+//! rewrites the stored values in place.
+
+/// Normalized alias pairs (`/old`, `/new`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FacetAliases(pub Vec<(String, String)>);
+
+/// `/a/b` form: leading slash, no trailing slash.
+pub fn normalize(facet: &str) -> String {
+    format!("/{}", facet.trim().trim_matches('/'))
+}
+
+/// Remainder of `facet` below `prefix` (`""` when equal), if `facet` is `prefix`
+/// or one of its descendants.
+fn below<'a>(facet: &'a str, prefix: &str) -> Option<&'a str> {
+    if prefix == "/" { return Some(if facet == "/" { "" } else { facet }); }
+    match facet.strip_prefix(prefix) {
+        Some("") => Some(""),
+        Some(rest) if rest.starts_with('/') => Some(rest),
+        _ => None,
+    }
+}
+
+/// Whether `facet` is `prefix` or one of its descendants.
+pub fn is_within(facet: &str, prefix: &str) -> bool { below(&normalize(facet), &normalize(prefix)).is_some() }
+
+impl FacetAliases {
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+
+    /// Record `old` → `new`. Earlier aliases that pointed into `old` are
+    /// redirected so chains resolve in one step; an alias back to an old name
+    /// (undoing a rename) drops the pair.
+    pub fn rename(&mut self, old: &str, new: &str) {
+        let (old, new) = (normalize(old), normalize(new));
+        if old == new { return; }
+        for pair in &mut self.0 {
+            if let Some(rest) = below(&pair.1, &old) { pair.1 = format!("{}{}", new, rest); }
+        }
+        self.0.retain(|(o, n)| o != n && *o != old);
+        self.0.push((old, new));
+    }
+
+    /// Current name of a stored `category` (most specific alias wins).
+    pub fn resolve(&self, category: &str) -> String {
+        let c = normalize(category);
+        self.0.iter()
+            .filter_map(|(o, n)| below(&c, o).map(|rest| (o.len(), format!("{}{}", n, rest))))
+            .max_by_key(|(len, _)| *len)
+            .map(|(_, r)| r)
+            .unwrap_or(c)
+    }
+
+    /// New value for a stored category if an alias applies, in the stored
+    /// spelling (with or without the leading `/`); `None` when unchanged.
+    pub fn rename_stored(&self, stored: &str) -> Option<String> {
+        let resolved = self.resolve(stored);
+        if resolved == normalize(stored) { return None; }
+        Some(if stored.starts_with('/') { resolved } else { resolved.trim_start_matches('/').to_string() })
+    }
+
+    /// Stored facets whose documents now fall under `facet`: the facet itself
+    /// plus the old names that resolve into it. Used to expand facet filters.
+    pub fn sources(&self, facet: &str) -> Vec<String> {
+        let f = normalize(facet);
+        let mut out = vec![f.clone()];
+        for (o, n) in &self.0 {
+            // Old subtree entirely inside the filter, or the filter is a part of the new subtree.
+            let candidate = if below(n, &f).is_some() { Some(o.clone()) } else { below(&f, n).map(|rest| format!("{}{}", o, rest)) };
+            if let Some(c) = candidate.filter(|c| below(&self.resolve(c), &f).is_some()) {
+                if !out.contains(&c) { out.push(c); }
+            }
+        }
+        out
+    }
+
+    /// `old\tnew` lines (the Lance `meta` value).
+    pub fn encode(&self) -> String {
+        self.0.iter().map(|(o, n)| format!("{}\t{}", o, n)).collect::<Vec<_>>().join("\n")
+    }
+
+    pub fn decode(s: &str) -> Self {
+        Self(s.lines().filter_map(|l| l.split_once('\t')).map(|(o, n)| (normalize(o), normalize(n))).collect())
+    }
+}
@@ -0,0 +1,37 @@
+//! Fault injection for crash-recovery tests.
+//!
+//! Pipeline stages call `check(point)` at their step boundaries. A test arms a
+//! point with `arm(point, n)`: the `n`th time the current thread reaches it,
+//! `check` fails as if the process had been killed there, leaving whatever
+//! was already written. Rerunning the stage must converge to a consistent
+//! state. Unarmed, `check` is a thread-local lookup and always succeeds.
+
+use std::cell::RefCell;
+
+use crate::error::Error;
+
+thread_local! {
+    static ARMED: RefCell<Option<(String, usize)>> = const { RefCell::new(None) };
+}
+
+/// Fail the `nth` (1-based) time `point` is reached on this thread.
+pub fn arm(point: &str, nth: usize) {
+    ARMED.with(|a| *a.borrow_mut() = Some((point.to_string(), nth.max(1))));
+}
+
+pub fn disarm() {
+    ARMED.with(|a| *a.borrow_mut() = None);
+}
+
+/// Step boundary `point`: errors when an armed fault fires here.
+pub fn check(point: &str) -> Result<(), Error> {
+    ARMED.with(|a| {
+        let mut armed = a.borrow_mut();
+        let Some((p, left)) = armed.as_mut().filter(|(p, _)| p == point) else { return Ok(()) };
+        *left -= 1;
+        if *left > 0 { return Ok(()); }
+        let msg = format!("injected fault at {}", p);
+        *armed = None;
+        Err(Error::Operation(msg))
+    })
+}
@@ -0,0 +1,214 @@
+//! Implicit-feedback telemetry for search quality (local only).
+//!
+//! Each served query appends a `Query` event (the ranked hits and the fusion
+//! strategy that produced them); opening or copying a result appends an
+//! `Action` event. From the log, `strategy_stats` computes click-through and
+//! mean reciprocal rank per strategy, and `tune_weights` nudges the per-leg
+//! fusion weights toward the leg whose hits actually get used. The log is a
+//! JSON-lines file and never leaves the machine.
+//!
+//! A result can also be rejected ("not like this", a `Reject` event). It
+//! does not count as use; instead `session_rejections` lists the chunks
+//! rejected in the current session, and the search engine pushes down later
+//! results that resemble them (`HybridSearchEngine::penalize_rejected`).
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::types::{FusionWeights, SourceKind};
+
+/// Idle time (ms) after which the next query starts a new session.
+pub const SESSION_IDLE_MS: i64 = 30 * 60 * 1000;
+
+/// Queries a log needs before `tune_weights` moves the weights.
+pub const MIN_TUNING_QUERIES: usize = 20;
+
+/// How far one tuning pass moves each weight toward its click-derived target.
+const LEARNING_RATE: f32 = 0.5;
+
+/// What the user did with a result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Open,
+    Copy,
+}
+
+/// One shown result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Shown {
+    pub id: String,
+    pub source: SourceKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum Event {
+    /// A served query; `results` in rank order (rank 1 first).
+    Query { query_id: String, query: String, strategy: String, results: Vec<Shown>, at_ms: i64 },
+    /// The result at 1-based `rank` of `query_id` was opened or copied.
+    Action { query_id: String, rank: usize, action: Action, at_ms: i64 },
+    /// The result at 1-based `rank` of `query_id` was marked "not like this".
+    Reject { query_id: String, rank: usize, at_ms: i64 },
+}
+
+impl Event {
+    fn at_ms(&self) -> i64 {
+        match self { Event::Query { at_ms, .. } | Event::Action { at_ms, .. } | Event::Reject { at_ms, .. } => *at_ms }
+    }
+}
+
+/// Append-only JSON-lines event log.
+pub struct FeedbackLog { path: PathBuf }
+
+impl FeedbackLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self { Self { path: path.into() } }
+
+    pub fn path(&self) -> &Path { &self.path }
+
+    /// Append `event`, creating the file (and its directory) on first use.
+    pub fn record(&self, event: &Event) -> Result<()> {
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) { fs::create_dir_all(dir)?; }
+        let mut f = fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(f, "{}", serde_json::to_string(event)?)?;
+        Ok(())
+    }
+
+    /// Record a served query; returns its new `query_id`.
+    pub fn record_query(&self, query: &str, strategy: &str, results: Vec<Shown>) -> Result<String> {
+        let at_ms = now_ms();
+        let query_id = blake3::hash(format!("{}\n{}\n{}", query, at_ms, std::process::id()).as_bytes()).to_hex().as_str()[..12].to_string();
+        self.record(&Event::Query { query_id: query_id.clone(), query: query.to_string(), strategy: strategy.to_string(), results, at_ms })?;
+        Ok(query_id)
+    }
+
+    /// Record that the result at 1-based `rank` of `query_id` was used.
+    /// Fails with `Error::NotFound` unless the query was logged with that rank.
+    pub fn record_action(&self, query_id: &str, rank: usize, action: Action) -> Result<()> {
+        self.check_shown(query_id, rank)?;
+        self.record(&Event::Action { query_id: query_id.to_string(), rank, action, at_ms: now_ms() })
+    }
+
+    /// Record that the result at 1-based `rank` of `query_id` is not wanted.
+    /// Fails like `record_action`.
+    pub fn record_reject(&self, query_id: &str, rank: usize) -> Result<()> {
+        self.check_shown(query_id, rank)?;
+        self.record(&Event::Reject { query_id: query_id.to_string(), rank, at_ms: now_ms() })
+    }
+
+    fn check_shown(&self, query_id: &str, rank: usize) -> Result<()> {
+        let shown = self.events()?.into_iter().find_map(|e| match e { Event::Query { query_id: id, results, .. } if id == query_id => Some(results.len()), _ => None });
+        match shown {
+            None => Err(Error::NotFound(format!("no logged query '{}'", query_id)).into()),
+            Some(n) if rank == 0 || rank > n => Err(Error::NotFound(format!("query '{}' showed {} results, not rank {}", query_id, n, rank)).into()),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// All events; a missing log is empty and malformed lines are skipped.
+    pub fn events(&self) -> Result<Vec<Event>> {
+        if !self.path.exists() { return Ok(Vec::new()); }
+        Ok(fs::read_to_string(&self.path)?.lines().filter_map(|l| serde_json::from_str(l).ok()).collect())
+    }
+}
+
+/// Chunk ids rejected in the session open at `now_ms`: the trailing events
+/// with no gap longer than `SESSION_IDLE_MS` (none if the log has been idle
+/// that long). Oldest first, without repeats.
+pub fn session_rejections(events: &[Event], now_ms: i64) -> Vec<String> {
+    let mut start = events.len();
+    let mut later = now_ms;
+    while start > 0 && later - events[start - 1].at_ms() <= SESSION_IDLE_MS { start -= 1; later = events[start].at_ms(); }
+    let session = &events[start..];
+    let mut ids: Vec<String> = Vec::new();
+    for e in session {
+        let Event::Reject { query_id, rank, .. } = e else { continue };
+        let shown = events.iter().find_map(|q| match q { Event::Query { query_id: id, results, .. } if id == query_id => results.get(rank.wrapping_sub(1)), _ => None });
+        if let Some(s) = shown.filter(|s| !ids.contains(&s.id)) { ids.push(s.id.clone()); }
+    }
+    ids
+}
+
+/// Click statistics for one fusion strategy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrategyStats {
+    pub strategy: String,
+    pub queries: usize,
+    /// Queries with at least one opened/copied result.
+    pub clicked_queries: usize,
+    /// Mean over queries of 1/rank of the first used result (0 when none).
+    pub mrr: f32,
+}
+
+impl StrategyStats {
+    /// Click-through rate: share of queries where a result was used.
+    pub fn ctr(&self) -> f32 { if self.queries == 0 { 0.0 } else { self.clicked_queries as f32 / self.queries as f32 } }
+}
+
+/// Per-strategy stats, sorted by strategy name.
+pub fn strategy_stats(events: &[Event]) -> Vec<StrategyStats> {
+    let first_rank = first_used_ranks(events);
+    let mut by_strategy: BTreeMap<&str, (usize, usize, f32)> = BTreeMap::new();
+    for e in events {
+        if let Event::Query { query_id, strategy, .. } = e {
+            let entry = by_strategy.entry(strategy.as_str()).or_default();
+            entry.0 += 1;
+            if let Some(rank) = first_rank.get(query_id.as_str()) { entry.1 += 1; entry.2 += 1.0 / *rank as f32; }
+        }
+    }
+    by_strategy.into_iter().map(|(s, (queries, clicked, rr))| StrategyStats {
+        strategy: s.to_string(), queries, clicked_queries: clicked, mrr: if queries == 0 { 0.0 } else { rr / queries as f32 },
+    }).collect()
+}
+
+/// Smallest used rank per query.
+fn first_used_ranks(events: &[Event]) -> BTreeMap<&str, usize> {
+    let mut ranks: BTreeMap<&str, usize> = BTreeMap::new();
+    for e in events {
+        if let Event::Action { query_id, rank, .. } = e {
+            let r = ranks.entry(query_id.as_str()).or_insert(*rank);
+            *r = (*r).min(*rank);
+        }
+    }
+    ranks
+}
+
+/// Weights moved toward each leg's click-through (clicks per shown hit,
+/// Laplace-smoothed), normalized so the larger weight is 1.0. Returns
+/// `current` unchanged until the log holds `MIN_TUNING_QUERIES` queries.
+pub fn tune_weights(events: &[Event], current: FusionWeights) -> FusionWeights {
+    let mut shown: BTreeMap<&str, Vec<SourceKind>> = BTreeMap::new();
+    for e in events {
+        if let Event::Query { query_id, results, .. } = e { shown.insert(query_id.as_str(), results.iter().map(|r| r.source).collect()); }
+    }
+    if shown.len() < MIN_TUNING_QUERIES { return current; }
+    let (mut text, mut vector) = ((0usize, 0usize), (0usize, 0usize)); // (shown, used)
+    for sources in shown.values() {
+        for s in sources { if *s == SourceKind::Text { text.0 += 1 } else { vector.0 += 1 } }
+    }
+    for e in events {
+        if let Event::Action { query_id, rank, .. } = e {
+            match shown.get(query_id.as_str()).and_then(|s| s.get(rank.wrapping_sub(1))) {
+                Some(SourceKind::Text) => text.1 += 1,
+                Some(SourceKind::Vector) => vector.1 += 1,
+                None => {}
+            }
+        }
+    }
+    let rate = |(n, used): (usize, usize)| (used as f32 + 1.0) / (n as f32 + 2.0);
+    let (rt, rv) = (rate(text), rate(vector));
+    let top = rt.max(rv);
+    let step = |w: f32, target: f32| w + LEARNING_RATE * (target - w);
+    let (t, v) = (step(current.text, rt / top), step(current.vector, rv / top));
+    let norm = t.max(v).max(f32::EPSILON);
+    FusionWeights { text: t / norm, vector: v / norm }
+}
+
+fn now_ms() -> i64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or(0)
+}
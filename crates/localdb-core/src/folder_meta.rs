@@ -0,0 +1,100 @@
+//! Folder-level metadata inherited by documents (`.meta.toml`).
+//!
+//! A `.meta.toml` in any data directory describes every document beneath it:
+//!
+//! ```toml
+//! tags = ["seed-saving", "usda"]
+//! source = "USDA extension bulletins"
+//! trust = "high"
+//! language = "en"
+//! ```
+//!
+//! Files are merged from the data root down to a document's directory: tags
+//! accumulate and a deeper file overrides `source`, `trust`, and `language`.
+//! The result lands in `DocumentChunk::meta` and `FileRecord::meta` at ingest.
+
+use anyhow::{anyhow, Result};
+use figment::providers::{Format, Toml};
+use figment::Figment;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::types::Meta;
+
+/// Name of the per-directory metadata file.
+pub const META_FILE: &str = ".meta.toml";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct FolderMeta {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub source: Option<String>,
+    pub trust: Option<String>,
+    pub language: Option<String>,
+}
+
+impl FolderMeta {
+    /// The `.meta.toml` in `dir`, if any.
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(META_FILE);
+        if !path.is_file() { return Ok(None); }
+        Figment::from(Toml::file(&path)).extract().map(Some).map_err(|e| anyhow!("invalid {}: {}", path.display(), e))
+    }
+
+    /// `self` as inherited by a subdirectory described by `child`.
+    pub fn merge(&self, child: &FolderMeta) -> FolderMeta {
+        let mut tags = self.tags.clone();
+        for t in &child.tags { if !tags.contains(t) { tags.push(t.clone()); } }
+        FolderMeta {
+            tags,
+            source: child.source.clone().or_else(|| self.source.clone()),
+            trust: child.trust.clone().or_else(|| self.trust.clone()),
+            language: child.language.clone().or_else(|| self.language.clone()),
+        }
+    }
+
+    /// Chunk metadata keys: `tags` (comma-separated), `source`, `trust`, `language`.
+    pub fn to_meta(&self) -> Meta {
+        let mut meta = Meta::new();
+        if !self.tags.is_empty() { meta.insert("tags".into(), self.tags.join(",")); }
+        for (k, v) in [("source", &self.source), ("trust", &self.trust), ("language", &self.language)] {
+            if let Some(v) = v { meta.insert(k.into(), v.clone()); }
+        }
+        meta
+    }
+}
+
+/// Inherited metadata per directory under one data root, loading each
+/// `.meta.toml` once.
+pub struct FolderMetaCache {
+    root: PathBuf,
+    cache: HashMap<PathBuf, FolderMeta>,
+}
+
+impl FolderMetaCache {
+    pub fn new(root: &Path) -> Self { Self { root: root.to_path_buf(), cache: HashMap::new() } }
+
+    /// Metadata inherited by documents in `dir` (the root's own file included).
+    pub fn for_dir(&mut self, dir: &Path) -> Result<FolderMeta> {
+        if let Some(m) = self.cache.get(dir) { return Ok(m.clone()); }
+        let parent = match dir.parent() {
+            Some(p) if dir != self.root && dir.starts_with(&self.root) => self.for_dir(p)?,
+            _ => FolderMeta::default(),
+        };
+        let merged = match FolderMeta::load(dir)? { Some(own) => parent.merge(&own), None => parent };
+        self.cache.insert(dir.to_path_buf(), merged.clone());
+        Ok(merged)
+    }
+}
+
+/// `key\tvalue` lines, sorted by key (how the catalog stores `Meta`).
+pub fn encode_meta(meta: &Meta) -> String {
+    let mut pairs: Vec<_> = meta.iter().collect();
+    pairs.sort();
+    pairs.into_iter().map(|(k, v)| format!("{}\t{}", k, v.replace(['\t', '\n'], " "))).collect::<Vec<_>>().join("\n")
+}
+
+pub fn decode_meta(s: &str) -> Meta {
+    s.lines().filter_map(|l| l.split_once('\t')).map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
@@ -0,0 +1,52 @@
+//! Include/exclude glob patterns scoping which files of a data root are
+//! ingested (`patterns` under `[data]` and `[[data.roots]]`, `ingest
+//! --include/--exclude`), so a tree can be scoped without reorganizing it.
+//!
+//! Patterns match the path relative to the root, `/`-separated: `*` matches
+//! within one component, `?` one character, `**` any number of components. A
+//! pattern without `/` matches the file name in any directory (`*.md` is
+//! `**/*.md`); a leading `/` anchors at the root and a trailing `/` matches
+//! everything below a directory. `!` excludes. A file is in scope when it
+//! matches an include pattern (or there are none) and no exclude pattern.
+//! The root's `extensions` apply as well.
+
+use std::path::{Component, Path};
+
+/// Whether the root-relative `relative` path is in scope of `patterns`.
+pub fn in_scope<S: AsRef<str>>(patterns: &[S], relative: &Path) -> bool {
+    if patterns.is_empty() { return true; }
+    let components: Vec<String> = relative.components().filter_map(|c| match c { Component::Normal(s) => Some(s.to_string_lossy().into_owned()), _ => None }).collect();
+    let path: Vec<&str> = components.iter().map(String::as_str).collect();
+    let (exclude, include): (Vec<&str>, Vec<&str>) = patterns.iter().map(|p| p.as_ref().trim()).filter(|p| !p.is_empty() && *p != "!").partition(|p| p.starts_with('!'));
+    if exclude.iter().any(|p| matches(&p[1..], &path)) { return false; }
+    include.is_empty() || include.iter().any(|p| matches(p, &path))
+}
+
+/// Whether glob `pattern` matches the path `components` (see the module docs).
+pub fn matches(pattern: &str, components: &[&str]) -> bool {
+    let mut parts: Vec<&str> = pattern.split('/').filter(|p| !p.is_empty()).collect();
+    if !pattern.trim_end_matches('/').contains('/') { parts.insert(0, "**"); }
+    if pattern.ends_with('/') { parts.push("**"); }
+    match_components(&parts, components)
+}
+
+fn match_components(parts: &[&str], path: &[&str]) -> bool {
+    match parts.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|i| match_components(rest, &path[i..])),
+        Some((part, rest)) => path.split_first().is_some_and(|(name, tail)| match_name(part, name) && match_components(rest, tail)),
+    }
+}
+
+/// `*` and `?` within one component; a `*` backtracks to the last one seen.
+fn match_name(pattern: &str, name: &str) -> bool {
+    let (p, n): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut pi, mut ni, mut star, mut mark) = (0, 0, None, 0);
+    while ni < n.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == n[ni]) { pi += 1; ni += 1; }
+        else if pi < p.len() && p[pi] == '*' { star = Some(pi); mark = ni; pi += 1; }
+        else if let Some(s) = star { pi = s + 1; mark += 1; ni = mark; }
+        else { return false; }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
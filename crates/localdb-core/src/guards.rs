@@ -0,0 +1,64 @@
+//! Guards that keep stray files out of an ingest (`data.max_file_mb`,
+//! `data.skip_binary`).
+//!
+//! A disk image or database file that happens to carry an accepted extension
+//! would be read whole into memory and chunked into garbage. Files larger than
+//! `max_file_mb` are skipped before they are read; ZIM archives are exempt, as
+//! they are read article by article. Text formats (plain text, CSV/TSV, JSON
+//! Lines, transcripts, archive entries) whose first `SNIFF_BYTES` look binary
+//! (`is_binary`) are skipped after. Skipped files are reported and left out of
+//! the catalog, like expired ones.
+
+use crate::config::Config;
+
+/// Bytes inspected by `is_binary`.
+pub const SNIFF_BYTES: usize = 8192;
+
+/// Default `FileGuards::max_file_mb`.
+pub const DEFAULT_MAX_FILE_MB: u64 = 512;
+
+/// Share of control characters above which text counts as binary.
+const MAX_CONTROL_SHARE: f32 = 0.1;
+
+/// `data.max_file_mb` and `data.skip_binary`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileGuards {
+    /// Largest file read, in MiB; 0 means no limit.
+    pub max_file_mb: u64,
+    /// Skip text-format files whose content looks binary.
+    pub skip_binary: bool,
+}
+
+impl Default for FileGuards {
+    fn default() -> Self { Self { max_file_mb: DEFAULT_MAX_FILE_MB, skip_binary: true } }
+}
+
+impl FileGuards {
+    /// `data.max_file_mb` and `data.skip_binary`, defaulting to 512 MiB with sniffing on.
+    pub fn from_config(config: &Config) -> Self {
+        let d = Self::default();
+        Self { max_file_mb: config.get("data.max_file_mb").unwrap_or(d.max_file_mb), skip_binary: config.get("data.skip_binary").unwrap_or(d.skip_binary) }
+    }
+
+    /// Whether a file of `size` bytes is over the limit.
+    pub fn oversized(&self, size: u64) -> bool { self.max_file_mb > 0 && size > self.max_file_mb << 20 }
+
+    /// Why text-format `bytes` are not read, if they are not.
+    pub fn rejects_text(&self, bytes: &[u8]) -> Option<&'static str> {
+        (self.skip_binary && is_binary(bytes)).then_some("binary content")
+    }
+}
+
+/// Byte-order marks of UTF-8, UTF-16 (LE, BE) and UTF-32 BE text.
+const BOMS: [&[u8]; 4] = [&[0xEF, 0xBB, 0xBF], &[0xFF, 0xFE], &[0xFE, 0xFF], &[0, 0, 0xFE, 0xFF]];
+
+/// Whether `bytes` look like binary data rather than text: a NUL byte, or
+/// more than a tenth control characters, in the first `SNIFF_BYTES`. UTF-16
+/// and UTF-32 text with a byte-order mark is text (see `charset`).
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let head = &bytes[..bytes.len().min(SNIFF_BYTES)];
+    if head.is_empty() || BOMS.iter().any(|bom| head.starts_with(bom)) { return false; }
+    if head.contains(&0) { return true; }
+    let control = head.iter().filter(|&&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0C | 0x1B)).count();
+    control as f32 > head.len() as f32 * MAX_CONTROL_SHARE
+}
@@ -0,0 +1,91 @@
+//! Lifecycle hooks for applications built on these crates.
+//!
+//! A `Hook` can step in at five points, each defaulting to doing nothing:
+//! `pre_chunk` (a document's text sections before chunking: text files,
+//! EPUBs, scans and archive entries; CSV rows, JSON Lines records, ZIM
+//! articles and transcripts are chunked without it), `post_chunk` (one
+//! document's chunks with final doc ids and folder metadata; a ZIM archive's
+//! chunks all at once), `pre_index` (each batch the hybrid engine is asked to
+//! index), `pre_query` (the query text before either leg sees it) and
+//! `post_fusion` (the fused hits before they are ranked and cut to `k`).
+//! Hooks can rewrite, enrich or drop what they are given. They are kept in a
+//! `HookRegistry`, run in registration order, and the first error stops the
+//! operation, naming the hook.
+
+use anyhow::{Context, Result};
+use std::sync::Arc;
+
+use crate::types::{DocumentChunk, SearchHit};
+
+/// Custom metadata enrichment or filtering; override the points you need.
+pub trait Hook: Send + Sync {
+    /// Shown in errors.
+    fn name(&self) -> &str;
+
+    /// Sections of the document at `doc_path` (stored form), about to be chunked.
+    fn pre_chunk(&self, _doc_path: &str, _sections: &mut Vec<String>) -> Result<()> { Ok(()) }
+
+    /// Chunks of one document; `chunk_index` and `total_chunks` are
+    /// renumbered afterwards, so chunks may be dropped or added.
+    fn post_chunk(&self, _chunks: &mut Vec<DocumentChunk>) -> Result<()> { Ok(()) }
+
+    /// A batch about to be embedded and written to both indexes.
+    fn pre_index(&self, _chunks: &mut Vec<DocumentChunk>) -> Result<()> { Ok(()) }
+
+    /// Query text, before it is searched.
+    fn pre_query(&self, _query: &mut String) -> Result<()> { Ok(()) }
+
+    /// Fused hits for `query` (unsorted); scores may be changed, hits dropped.
+    fn post_fusion(&self, _query: &str, _hits: &mut Vec<SearchHit>) -> Result<()> { Ok(()) }
+}
+
+/// Registered hooks, in order. Cheap to clone (hooks are shared).
+#[derive(Clone, Default)]
+pub struct HookRegistry { hooks: Vec<Arc<dyn Hook>> }
+
+impl HookRegistry {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn register(&mut self, hook: Arc<dyn Hook>) { self.hooks.push(hook); }
+
+    /// `register`, builder-style.
+    pub fn with(mut self, hook: Arc<dyn Hook>) -> Self { self.register(hook); self }
+
+    pub fn len(&self) -> usize { self.hooks.len() }
+
+    pub fn is_empty(&self) -> bool { self.hooks.is_empty() }
+
+    pub fn pre_chunk(&self, doc_path: &str, sections: &mut Vec<String>) -> Result<()> {
+        self.run("pre_chunk", |h| h.pre_chunk(doc_path, sections))
+    }
+
+    pub fn post_chunk(&self, chunks: &mut Vec<DocumentChunk>) -> Result<()> {
+        self.run("post_chunk", |h| h.post_chunk(chunks))
+    }
+
+    pub fn pre_index(&self, chunks: &mut Vec<DocumentChunk>) -> Result<()> {
+        self.run("pre_index", |h| h.pre_index(chunks))
+    }
+
+    /// `query` as every `pre_query` hook leaves it.
+    pub fn pre_query(&self, query: &str) -> Result<String> {
+        let mut query = query.to_string();
+        self.run("pre_query", |h| h.pre_query(&mut query))?;
+        Ok(query)
+    }
+
+    pub fn post_fusion(&self, query: &str, hits: &mut Vec<SearchHit>) -> Result<()> {
+        self.run("post_fusion", |h| h.post_fusion(query, hits))
+    }
+
+    fn run(&self, point: &str, mut f: impl FnMut(&dyn Hook) -> Result<()>) -> Result<()> {
+        for hook in &self.hooks { f(hook.as_ref()).with_context(|| format!("{} hook `{}`", point, hook.name()))?; }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for HookRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.hooks.iter().map(|h| h.name())).finish()
+    }
+}
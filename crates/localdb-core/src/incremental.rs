@@ -0,0 +1,122 @@
+//! Incremental ingest: only new and modified files are read and chunked.
+//!
+//! The catalog written by the previous ingest (one `FileRecord` per file:
+//! `doc_path`, blake3 of the bytes, size, modification time) is the manifest.
+//! A listed file whose size and modification time match its record is
+//! unchanged and not even read; one that differs is hashed, and if the bytes
+//! still match (a `touch`, a copy that kept the content) it is unchanged too.
+//! Everything else is re-chunked, and the caller deletes the modified and
+//! removed files' old chunks (`IngestChanges::stale`) before indexing the new
+//! ones. Files keep the doc ids they were given, even when a new file maps to
+//! the same id. A file whose duplicate chunks were indexed under another file
+//! (see `dedupe`), or whose scanned pages were skipped as duplicates of its
+//! pages, is read again whenever that file changes, so the text does not
+//! vanish with the other file's old chunks; the other way round, a file
+//! holding such chunks is read again when the file that left them out
+//! changes, and an unchanged file is read again when a file that is read
+//! repeats one of its chunks, so duplicates are still found across the two.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::data_processor::DUPLICATE_PAGES_KEY;
+use crate::dedupe::{self, DUPLICATES_IN_KEY};
+use crate::types::FileRecord;
+
+/// The catalog of the previous ingest, by `doc_path`.
+#[derive(Debug, Clone, Default)]
+pub struct PreviousIngest { files: HashMap<String, FileRecord> }
+
+impl PreviousIngest {
+    pub fn new(records: Vec<FileRecord>) -> Self { Self { files: records.into_iter().map(|r| (r.doc_path.clone(), r)).collect() } }
+
+    pub fn is_empty(&self) -> bool { self.files.is_empty() }
+
+    pub fn record(&self, doc_path: &str) -> Option<&FileRecord> { self.files.get(doc_path) }
+
+    /// Whether the file at `doc_path` still has the recorded size and
+    /// modification time (ms), i.e. can be skipped without reading it.
+    pub fn looks_unchanged(&self, doc_path: &str, size: u64, modified_at: i64) -> bool {
+        self.record(doc_path).is_some_and(|r| r.size == size && r.modified_at == modified_at)
+    }
+
+    /// Whether `hash` is the recorded content hash of `doc_path`.
+    pub fn same_content(&self, doc_path: &str, hash: &str) -> bool {
+        self.record(doc_path).is_some_and(|r| r.file_hash == hash)
+    }
+
+    /// Recorded files with chunks indexed under one of `changed` (by
+    /// `DUPLICATES_IN_KEY`) or scanned pages skipped as duplicates of its
+    /// pages (`DUPLICATE_PAGES_KEY`), directly or through another such file.
+    /// They must be read again even if they look unchanged.
+    pub fn dependents(&self, changed: &HashSet<String>) -> HashSet<String> {
+        let mut found = HashSet::new();
+        loop {
+            let matched: Vec<String> = self.files.values()
+                .filter(|r| !found.contains(&r.doc_path) && holders(r).iter().any(|h| changed.contains(h) || found.contains(h)))
+                .map(|r| r.doc_path.clone()).collect();
+            if matched.is_empty() { return found; }
+            found.extend(matched);
+        }
+    }
+
+    /// Files holding chunks that one of `changed` left out, whose `also_in`
+    /// names it (`DUPLICATES_IN_KEY` of the `changed` records).
+    pub fn holders_of(&self, changed: &HashSet<String>) -> HashSet<String> {
+        changed.iter().filter_map(|p| self.record(p)).flat_map(|r| dedupe::listed(&r.meta, DUPLICATES_IN_KEY)).collect()
+    }
+
+    /// Recorded files that were not listed this time (`seen`), among those
+    /// `in_scope` (under a root that was scanned). Sorted.
+    pub fn removed(&self, seen: &HashSet<String>, in_scope: impl Fn(&str) -> bool) -> Vec<String> {
+        let mut removed: Vec<String> = self.files.keys().filter(|p| in_scope(p) && !seen.contains(*p)).cloned().collect();
+        removed.sort();
+        removed
+    }
+}
+
+/// Files holding text a record's file left out: where its duplicate chunks
+/// were indexed, and the files of the canonical pages of its skipped scan
+/// pages (`<page>=<doc_path>#<page>`).
+fn holders(record: &FileRecord) -> Vec<String> {
+    let chunks = dedupe::listed(&record.meta, DUPLICATES_IN_KEY).into_iter();
+    let pages = record.meta.get(DUPLICATE_PAGES_KEY).into_iter().flat_map(|p| p.split("; "))
+        .filter_map(|p| p.split_once('=')).filter_map(|(_, canonical)| canonical.rsplit_once('#')).map(|(file, _)| file.to_string());
+    chunks.chain(pages).collect()
+}
+
+/// How the listed files compare with the previous ingest (by `doc_path`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IngestChanges {
+    /// Skipped; their chunks and catalog records stay as they are.
+    pub unchanged: Vec<String>,
+    /// Re-chunked; their old chunks are stale.
+    pub modified: Vec<String>,
+    /// Gone from their root.
+    pub removed: Vec<String>,
+    /// Files not in the previous catalog.
+    pub added: usize,
+    /// Listed but unreadable (reported and skipped); chunks from an earlier
+    /// ingest stay as they are.
+    pub failed: Vec<String>,
+    /// Indexed before but now past their retention; dropped like removed files.
+    pub expired: Vec<String>,
+    /// Indexed before but now empty, oversized or binary (reported and
+    /// skipped); dropped like removed files.
+    pub skipped: Vec<String>,
+}
+
+impl IngestChanges {
+    /// `doc_path`s whose indexed chunks must be deleted.
+    pub fn stale(&self) -> Vec<String> { self.modified.iter().chain(&self.dropped()).cloned().collect() }
+
+    /// `doc_path`s whose catalog records must be deleted.
+    pub fn dropped(&self) -> Vec<String> { self.removed.iter().chain(&self.expired).chain(&self.skipped).cloned().collect() }
+
+    /// `2 new, 1 modified, 140 unchanged, 0 removed files` (`, 3 expired`,
+    /// `, 1 skipped` when any).
+    pub fn summary(&self) -> String {
+        let expired = if self.expired.is_empty() { String::new() } else { format!(", {} expired", self.expired.len()) };
+        let skipped = if self.skipped.is_empty() { String::new() } else { format!(", {} skipped", self.skipped.len()) };
+        format!("{} new, {} modified, {} unchanged, {} removed files{}{}", self.added, self.modified.len(), self.unchanged.len(), self.removed.len(), expired, skipped)
+    }
+}
@@ -0,0 +1,104 @@
+//! JSON Lines ingest for corpora pre-processed by other tools.
+//!
+//! Each non-empty line of a `.jsonl`/`.ndjson` file, or each element of a
+//! top-level array in a `.json` file, is one JSON object and becomes its own
+//! document. `jsonl.content_field` holds the text to chunk and
+//! `jsonl.id_field` the doc id (the file's id plus `#<n>` when a record has
+//! none); the value of `jsonl.category_field` extends the file's facet
+//! (`library` + `medical/first-aid` → `library/medical/first-aid`) and
+//! `jsonl.meta_fields` land in `DocumentChunk::meta`. Fields are dotted paths
+//! (`source.title`); numbers, booleans and arrays of them are used as text.
+//! Records that are not objects or lack content are reported, not dropped
+//! silently.
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::csv;
+use crate::types::Meta;
+
+/// Which fields hold the text, doc id, facet and metadata (`[jsonl]`).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct JsonlMapping {
+    pub content_field: String,
+    pub id_field: Option<String>,
+    pub category_field: Option<String>,
+    pub meta_fields: Vec<String>,
+}
+
+impl Default for JsonlMapping {
+    fn default() -> Self { Self { content_field: "text".to_string(), id_field: Some("id".to_string()), category_field: None, meta_fields: Vec::new() } }
+}
+
+impl JsonlMapping {
+    /// `[jsonl]`, or content in `text` and ids in `id`.
+    pub fn from_config(config: &Config) -> Self { config.get::<Self>("jsonl").unwrap_or_default() }
+}
+
+/// One record mapped for chunking.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// 1-based line (`.jsonl`) or array position (`.json`).
+    pub number: usize,
+    pub id: Option<String>,
+    pub text: String,
+    pub category: Option<String>,
+    pub meta: Meta,
+}
+
+/// Whether `path` is a `.jsonl`, `.ndjson` or `.json` file.
+pub fn is_jsonl(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| ["jsonl", "ndjson", "json"].iter().any(|x| x.eq_ignore_ascii_case(e)))
+}
+
+fn is_json_document(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("json"))
+}
+
+/// Records of `content` with `mapping` applied, in file order. Each bad
+/// record is an error naming its line or position; the rest still parse.
+pub fn records(content: &str, path: &Path, mapping: &JsonlMapping) -> Vec<Result<Record>> {
+    let content = content.trim_start_matches('\u{feff}');
+    if is_json_document(path) {
+        return match serde_json::from_str::<Value>(content) {
+            Ok(Value::Array(items)) => items.iter().enumerate().map(|(i, v)| record(v, i + 1, mapping).map_err(|e| anyhow!("element {}: {}", i + 1, e))).collect(),
+            Ok(value) => vec![record(&value, 1, mapping)],
+            Err(e) => vec![Err(anyhow!("invalid JSON: {}", e))],
+        };
+    }
+    content.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()).map(|(i, line)| {
+        serde_json::from_str::<Value>(line).map_err(|e| anyhow!("{}", e)).and_then(|v| record(&v, i + 1, mapping)).map_err(|e| anyhow!("line {}: {}", i + 1, e))
+    }).collect()
+}
+
+fn record(value: &Value, number: usize, mapping: &JsonlMapping) -> Result<Record> {
+    if !value.is_object() { return Err(anyhow!("not a JSON object")); }
+    let get = |path: &str| field(value, path).and_then(as_text).filter(|s| !s.is_empty());
+    let text = get(&mapping.content_field).ok_or_else(|| anyhow!("no `{}` field", mapping.content_field))?;
+    let meta = mapping.meta_fields.iter().filter_map(|f| get(f).map(|v| (f.clone(), v))).collect();
+    Ok(Record { number, id: mapping.id_field.as_deref().and_then(get), text, category: mapping.category_field.as_deref().and_then(get), meta })
+}
+
+fn field<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |v, key| v.get(key))
+}
+
+fn as_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Array(items) => Some(items.iter().filter_map(as_text).filter(|s| !s.is_empty()).collect::<Vec<_>>().join(", ")),
+        Value::Null | Value::Object(_) => None,
+    }
+}
+
+/// Facet of a record: the file's facet plus each `/`-separated segment of its
+/// category value.
+pub fn record_facet(category: &str, value: &str) -> String {
+    value.split('/').map(str::trim).filter(|s| !s.is_empty()).fold(category.to_string(), |facet, segment| csv::row_facet(&facet, segment))
+}
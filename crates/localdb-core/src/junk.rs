@@ -0,0 +1,135 @@
+//! Junk-chunk filters (`[chunking.filters]`): chunks too short to answer
+//! anything, mostly numbers and punctuation, or so repetitive they carry no
+//! content (tables of contents, page-number runs), or repeated through a
+//! document (running page headers and footers that survived as chunks of
+//! their own, found with `[boilerplate]`'s line keys and counts). They match many queries weakly and crowd out real passages,
+//! so they are dropped after chunking; ingest reports how many each filter
+//! removed. CSV rows are data and are never filtered. Every filter is off
+//! unless configured.
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::boilerplate::{line_key, lines_on};
+use crate::types::DocumentChunk;
+
+/// Chunks with fewer words than this are never judged by entropy.
+pub const MIN_ENTROPY_WORDS: usize = 8;
+
+/// `[chunking.filters]` thresholds; the defaults keep everything.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JunkFilters {
+    /// Fewest non-whitespace characters a chunk needs.
+    pub min_chars: usize,
+    /// Largest share (0–1) of words without a letter (numbers, dot leaders,
+    /// punctuation). Prose stays under 0.1; a table of contents is over half.
+    pub max_symbol_share: f32,
+    /// Smallest word entropy, as a share (0–1) of the most the chunk's word
+    /// count allows. Prose sits around 0.8; a run of page numbers near 0.2.
+    pub min_entropy: f32,
+    /// Most chunks of a document a line may occur in (compared as
+    /// `boilerplate::line_key` compares them, so `Page 3` and `Page 4`
+    /// match); a chunk made only of lines seen more often is dropped. 0
+    /// turns the filter off.
+    pub max_repeats: usize,
+}
+
+impl Default for JunkFilters {
+    fn default() -> Self { Self { min_chars: 0, max_symbol_share: 1.0, min_entropy: 0.0, max_repeats: 0 } }
+}
+
+/// Which filter rejected a chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Junk {
+    TooShort,
+    MostlySymbols,
+    LowEntropy,
+    Repeated,
+}
+
+impl JunkFilters {
+    /// Shares must lie in [0, 1].
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.max_symbol_share) { bail!("filters.max_symbol_share must be in [0, 1], got {}", self.max_symbol_share); }
+        if !(0.0..=1.0).contains(&self.min_entropy) { bail!("filters.min_entropy must be in [0, 1], got {}", self.min_entropy); }
+        Ok(())
+    }
+
+    /// The first filter `text` fails, in the order of `Junk`.
+    pub fn classify(&self, text: &str) -> Option<Junk> {
+        if text.chars().filter(|c| !c.is_whitespace()).count() < self.min_chars { return Some(Junk::TooShort); }
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let symbols = words.iter().filter(|w| !w.chars().any(char::is_alphabetic)).count();
+        if !words.is_empty() && symbols as f32 / words.len() as f32 > self.max_symbol_share { return Some(Junk::MostlySymbols); }
+        if word_entropy(text).is_some_and(|e| e < self.min_entropy) { return Some(Junk::LowEntropy); }
+        None
+    }
+
+    /// Ids of the chunks made only of lines that occur in more than
+    /// `max_repeats` chunks of their document, each chunk counted as a page
+    /// by `boilerplate::lines_on`; none when the filter is off.
+    pub fn repeated(&self, chunks: &[DocumentChunk]) -> HashSet<String> {
+        if self.max_repeats == 0 { return HashSet::new(); }
+        let mut docs: HashMap<&str, Vec<&DocumentChunk>> = HashMap::new();
+        for c in chunks { docs.entry(c.doc_id.as_str()).or_default().push(c); }
+        let mut ids = HashSet::new();
+        for doc in docs.into_values() {
+            let contents: Vec<&str> = doc.iter().map(|c| c.content.as_str()).collect();
+            let keys = lines_on(&contents, self.max_repeats + 1);
+            if keys.is_empty() { continue; }
+            for c in doc {
+                let lines: Vec<String> = c.content.lines().filter_map(line_key).collect();
+                if !lines.is_empty() && lines.iter().all(|k| keys.contains(k)) { ids.insert(c.id.clone()); }
+            }
+        }
+        ids
+    }
+}
+
+/// `word` lowercased with its digits as `#`.
+fn normalize_word(word: &str) -> String {
+    word.to_lowercase().chars().map(|c| if c.is_ascii_digit() { '#' } else { c }).collect()
+}
+
+/// Shannon entropy of the words of `text` (lowercased, digits as `#`) over
+/// its maximum, `log2(words)`; `None` under `MIN_ENTROPY_WORDS` words.
+pub fn word_entropy(text: &str) -> Option<f32> {
+    let words: Vec<String> = text.split_whitespace().map(normalize_word).collect();
+    if words.len() < MIN_ENTROPY_WORDS { return None; }
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for w in &words { *counts.entry(w).or_insert(0) += 1; }
+    let n = words.len() as f32;
+    let entropy: f32 = counts.values().map(|&c| { let p = c as f32 / n; -p * p.log2() }).sum();
+    Some(entropy / n.log2())
+}
+
+/// Chunks dropped per filter in one ingest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JunkReport {
+    pub too_short: usize,
+    pub mostly_symbols: usize,
+    pub low_entropy: usize,
+    pub repeated: usize,
+}
+
+impl JunkReport {
+    pub fn record(&mut self, junk: Junk) {
+        match junk { Junk::TooShort => self.too_short += 1, Junk::MostlySymbols => self.mostly_symbols += 1, Junk::LowEntropy => self.low_entropy += 1, Junk::Repeated => self.repeated += 1 }
+    }
+
+    pub fn merge(&mut self, other: &JunkReport) {
+        self.too_short += other.too_short;
+        self.mostly_symbols += other.mostly_symbols;
+        self.low_entropy += other.low_entropy;
+        self.repeated += other.repeated;
+    }
+
+    pub fn total(&self) -> usize { self.too_short + self.mostly_symbols + self.low_entropy + self.repeated }
+
+    /// `3 too short, 1 mostly numbers/punctuation, 0 low-entropy, 12 repeated`.
+    pub fn summary(&self) -> String {
+        format!("{} too short, {} mostly numbers/punctuation, {} low-entropy, {} repeated", self.too_short, self.mostly_symbols, self.low_entropy, self.repeated)
+    }
+}
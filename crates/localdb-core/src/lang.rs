@@ -0,0 +1,86 @@
+//! Lightweight language guess for chunks and queries.
+//!
+//! Chunking stores the guess on each chunk (`DocumentChunk::lang`, an ISO
+//! 639-1 code) so results can be filtered by language in multilingual
+//! collections; both index legs filter on it while searching. The text index
+//! uses it for one thing only, whether to add character n-grams: every
+//! language shares the same tokenizer, English stopwords and no stemming, and
+//! whole-word BM25 works poorly for languages that inflect or compound heavily
+//! (Finnish cases, German compounds): `maanviljelijöille` never matches
+//! `maanviljelijä`. For those, the indexer also fills a character n-gram
+//! field and the searcher adds an n-gram subquery. Detection is a
+//! stopword/character vote — cheap, dependency-free, and good enough for that.
+
+/// Languages the vote distinguishes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    English,
+    German,
+    Finnish,
+    French,
+    Spanish,
+    Russian,
+    Unknown,
+}
+
+impl Lang {
+    /// Whether text in this language should also be matched by character n-grams.
+    pub fn uses_ngrams(self) -> bool { matches!(self, Lang::German | Lang::Finnish) }
+
+    /// ISO 639-1 code, as stored on chunks; `None` for `Unknown`.
+    pub fn code(self) -> Option<&'static str> {
+        match self {
+            Lang::English => Some("en"),
+            Lang::German => Some("de"),
+            Lang::Finnish => Some("fi"),
+            Lang::French => Some("fr"),
+            Lang::Spanish => Some("es"),
+            Lang::Russian => Some("ru"),
+            Lang::Unknown => None,
+        }
+    }
+
+    /// Inverse of `code` (case-insensitive); other codes are `Unknown`.
+    pub fn from_code(code: &str) -> Self {
+        match code.to_ascii_lowercase().as_str() {
+            "en" => Lang::English,
+            "de" => Lang::German,
+            "fi" => Lang::Finnish,
+            "fr" => Lang::French,
+            "es" => Lang::Spanish,
+            "ru" => Lang::Russian,
+            _ => Lang::Unknown,
+        }
+    }
+}
+
+const EN: &[&str] = &["the", "and", "of", "to", "is", "in", "that", "with", "for", "on", "are", "this", "it", "be"];
+const DE: &[&str] = &["und", "der", "die", "das", "ist", "nicht", "mit", "ein", "eine", "zu", "den", "von", "auf", "für", "sich", "im", "dem"];
+const FI: &[&str] = &["ja", "on", "ei", "että", "se", "oli", "kun", "mutta", "hän", "ovat", "tai", "myös", "kanssa", "sekä", "voi", "jos"];
+// Words shared by French and Spanish (`la`, `que`, `en`) are left out of both.
+const FR: &[&str] = &["le", "les", "et", "est", "des", "une", "du", "pas", "pour", "dans", "au", "sur", "ce", "qui", "avec", "sont"];
+const ES: &[&str] = &["el", "los", "las", "y", "es", "del", "una", "por", "con", "para", "lo", "como", "pero", "más", "está", "al"];
+const RU: &[&str] = &["и", "в", "не", "на", "что", "с", "по", "это", "как", "для", "он", "из", "но", "от", "я", "к"];
+
+/// Words sampled from the start of a text.
+const SAMPLE_WORDS: usize = 400;
+
+/// Guess the language of `text` (a document chunk or a query).
+pub fn detect(text: &str) -> Lang {
+    let lower = text.to_lowercase();
+    let words: Vec<&str> = lower.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).take(SAMPLE_WORDS).collect();
+    let hits = |list: &[&str]| words.iter().filter(|w| list.contains(w)).count();
+    let (en, mut de, mut fi, mut fr, mut es, mut ru) = (hits(EN), hits(DE), hits(FI), hits(FR), hits(ES), hits(RU));
+    // Letters that are strong hints on their own (short queries have no stopwords).
+    if lower.contains(['ß', 'ü']) { de += 2; }
+    if lower.contains(['ä', 'ö']) { fi += 1; de += 1; }
+    if words.iter().any(|w| ["aa", "ii", "uu", "yy", "ää", "öö"].iter().any(|d| w.contains(d))) { fi += 1; }
+    if lower.contains(['ç', 'è', 'ê', 'œ']) { fr += 1; }
+    if lower.contains(['ñ', '¿', '¡']) { es += 2; }
+    // Cyrillic script: only Russian is voted for here.
+    if lower.chars().any(|c| ('\u{0400}'..='\u{04FF}').contains(&c)) { ru += 2; }
+    match [(en, Lang::English), (de, Lang::German), (fi, Lang::Finnish), (fr, Lang::French), (es, Lang::Spanish), (ru, Lang::Russian)].into_iter().max_by_key(|(n, _)| *n) {
+        Some((n, lang)) if n > 0 => lang,
+        _ => Lang::Unknown,
+    }
+}
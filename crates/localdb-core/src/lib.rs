@@ -7,13 +7,61 @@
 //! Core types, traits, config helpers, and chunking utilities shared across the
 //! workspace. This crate defines the domain model (`DocumentChunk`), the primary
 //! trait surfaces (`Embedder`, `TextIndexer`, `VectorIndexer`, `SearchEngine`),
-//! and a pragmatic `DataProcessor` for turning a directory of `.txt` files into
-//! chunks suitable for indexing.
+//! and a pragmatic `DataProcessor` for turning a directory of `.txt` (and EPUB)
+//! files into chunks suitable for indexing; Kiwix ZIM archives stream in
+//! article by article (`zim`).
 //!
 //! The documentation of each module provides more details.
 
+pub mod answer;
+pub mod archive;
+pub mod assets;
+pub mod blobs;
+pub mod boilerplate;
+pub mod calibration;
+pub mod canary;
+pub mod charset;
+pub mod chunker;
+pub mod citations;
 pub mod config;
+pub mod corpus;
+#[cfg(feature = "encryption")]
+pub mod crypt;
+pub mod csv;
 pub mod data_processor;
+pub mod dedupe;
+pub mod epoch_cache;
+pub mod epub;
 pub mod error;
+pub mod eval;
+pub mod facets;
+pub mod fault;
+pub mod feedback;
+pub mod folder_meta;
+pub mod globs;
+pub mod guards;
+pub mod hooks;
+pub mod incremental;
+pub mod jsonl;
+pub mod junk;
+pub mod lang;
+pub mod ocr;
+pub mod phash;
+pub mod preprocess;
+pub mod profile;
+pub mod progress;
+pub mod replay;
+pub mod rerank;
+pub mod retention;
+pub mod roots;
+pub mod scratch;
+pub mod seed;
+pub mod spans;
+pub mod summary;
+pub mod sync;
+pub mod taxonomy;
 pub mod traits;
+pub mod transcript;
 pub mod types;
+pub mod writer_lock;
+pub mod zim;
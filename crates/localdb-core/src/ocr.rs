@@ -0,0 +1,157 @@
+//! OCR for scanned images and image-only PDFs (`ocr` feature).
+//!
+//! With the feature, `.png`/`.jpg`/`.jpeg`/`.tif`/`.tiff` scans go through
+//! Tesseract (the `tesseract` bindings: needs libtesseract plus the
+//! traineddata of `ocr.language`). PDFs are read with poppler's `pdftotext`;
+//! pages without a text layer are rasterized with `pdftoppm` at `ocr.dpi` and
+//! recognized instead. Each page is one section, so no chunk spans two pages.
+//!
+//! Every page image also gets a perceptual hash (`phash`); with `ocr.dedupe`
+//! a page matching one already ingested in this run is skipped and the file's
+//! catalog record names the canonical page (`duplicate_pages`).
+//!
+//! Data roots read scans only when their extensions list them (see `roots`).
+//! Without the feature such files are still picked up and skipped with a
+//! warning naming the feature, never dropped silently.
+
+use anyhow::Result;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::phash;
+
+/// Image extensions recognized as scans.
+pub const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "tif", "tiff"];
+
+/// PDF pages with fewer non-whitespace characters than this are treated as
+/// image-only and OCR'd.
+pub const MIN_PAGE_TEXT_CHARS: usize = 16;
+
+/// OCR settings (`[ocr]` in config).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OcrConfig {
+    /// Tesseract language code(s), `+`-separated (`eng`, `eng+deu`).
+    pub language: String,
+    /// Resolution image-only PDF pages are rendered at.
+    pub dpi: u32,
+    /// Skip pages that duplicate a page already ingested in this run.
+    pub dedupe: bool,
+    /// Most differing hash bits for two pages to count as the same.
+    pub duplicate_distance: u32,
+}
+
+impl Default for OcrConfig {
+    fn default() -> Self { Self { language: "eng".to_string(), dpi: 300, dedupe: true, duplicate_distance: phash::DEFAULT_MAX_DISTANCE } }
+}
+
+impl OcrConfig {
+    /// `ocr.language`, `ocr.dpi`, `ocr.dedupe` and `ocr.duplicate_distance`,
+    /// defaulting to `eng` at 300 dpi with dedupe on.
+    pub fn from_config(config: &Config) -> Self {
+        let d = Self::default();
+        Self {
+            language: config.get("ocr.language").unwrap_or(d.language), dpi: config.get("ocr.dpi").unwrap_or(d.dpi),
+            dedupe: config.get("ocr.dedupe").unwrap_or(d.dedupe), duplicate_distance: config.get("ocr.duplicate_distance").unwrap_or(d.duplicate_distance),
+        }
+    }
+}
+
+/// One recognized page of a scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanPage {
+    /// 1-based page number in the file.
+    pub number: usize,
+    pub text: String,
+    /// `phash::dhash` of the page image; `None` for PDF pages read from
+    /// their text layer.
+    pub fingerprint: Option<u64>,
+}
+
+/// Whether this build can OCR.
+pub fn enabled() -> bool { cfg!(feature = "ocr") }
+
+fn has_extension(path: &Path, exts: &[&str]) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| exts.iter().any(|x| x.eq_ignore_ascii_case(e)))
+}
+
+pub fn is_image(path: &Path) -> bool { has_extension(path, IMAGE_EXTENSIONS) }
+
+pub fn is_pdf(path: &Path) -> bool { has_extension(path, &["pdf"]) }
+
+/// A file that needs this module to become text.
+pub fn is_scan(path: &Path) -> bool { is_image(path) || is_pdf(path) }
+
+/// Pages of a scan (an image is one page) with their fingerprints. Pages
+/// that yield no text are dropped.
+#[cfg(feature = "ocr")]
+pub fn read_scan(path: &Path, config: &OcrConfig) -> Result<Vec<ScanPage>> {
+    let pages = if is_pdf(path) { imp::pdf_pages(path, config)? } else { vec![(imp::recognize(path, &config.language)?, phash::dhash_file(path))] };
+    Ok(pages.into_iter().enumerate().map(|(i, (text, fingerprint))| ScanPage { number: i + 1, text: tidy(&text), fingerprint }).filter(|p| !p.text.is_empty()).collect())
+}
+
+#[cfg(not(feature = "ocr"))]
+pub fn read_scan(_path: &Path, _config: &OcrConfig) -> Result<Vec<ScanPage>> {
+    anyhow::bail!("built without the `ocr` feature (rebuild with `--features ocr`)")
+}
+
+/// Text of a scan, one section per page.
+pub fn read_pages(path: &Path, config: &OcrConfig) -> Result<Vec<String>> {
+    Ok(read_scan(path, config)?.into_iter().map(|p| p.text).collect())
+}
+
+/// Trim each line, drop form feeds and keep blank lines as paragraph breaks.
+#[cfg(feature = "ocr")]
+fn tidy(page: &str) -> String {
+    let lines: Vec<&str> = page.split('\n').map(|l| l.trim_matches(|c: char| c.is_whitespace() || c == '\x0c')).collect();
+    let mut out = String::new();
+    for para in lines.split(|l| l.is_empty()).filter(|p| !p.is_empty()) {
+        if !out.is_empty() { out.push_str("\n\n"); }
+        out.push_str(&para.join("\n"));
+    }
+    out
+}
+
+#[cfg(feature = "ocr")]
+mod imp {
+    use super::{OcrConfig, MIN_PAGE_TEXT_CHARS};
+    use crate::phash;
+    use anyhow::{anyhow, Context, Result};
+    use std::path::Path;
+    use std::process::Command;
+
+    pub(super) fn recognize(image: &Path, language: &str) -> Result<String> {
+        let path = image.to_str().ok_or_else(|| anyhow!("non-UTF-8 path {}", image.display()))?;
+        tesseract::ocr(path, language).map_err(|e| anyhow!("tesseract ({}): {}", language, e))
+    }
+
+    /// `pdftotext` per page, OCR (and a fingerprint) for pages without a
+    /// text layer.
+    pub(super) fn pdf_pages(pdf: &Path, config: &OcrConfig) -> Result<Vec<(String, Option<u64>)>> {
+        let out = Command::new("pdftotext").args(["-enc", "UTF-8"]).arg(pdf).arg("-").output()
+            .context("running pdftotext (install poppler-utils)")?;
+        if !out.status.success() { return Err(anyhow!("pdftotext failed: {}", String::from_utf8_lossy(&out.stderr).trim())); }
+        let text = String::from_utf8_lossy(&out.stdout).into_owned();
+        let mut pages: Vec<(String, Option<u64>)> = text.split('\x0c').map(|p| (p.to_string(), None)).collect();
+        if pages.last().is_some_and(|(p, _)| p.trim().is_empty()) { pages.pop(); }
+        for (i, page) in pages.iter_mut().enumerate() {
+            if page.0.chars().filter(|c| !c.is_whitespace()).count() < MIN_PAGE_TEXT_CHARS {
+                *page = recognize_pdf_page(pdf, i + 1, config)?;
+            }
+        }
+        Ok(pages)
+    }
+
+    /// Render one page (1-based) with `pdftoppm`, recognize and fingerprint it.
+    fn recognize_pdf_page(pdf: &Path, page: usize, config: &OcrConfig) -> Result<(String, Option<u64>)> {
+        let prefix = std::env::temp_dir().join(format!("localdb-ocr-{}-{}", crate::blobs::tmp_suffix(), page));
+        let n = page.to_string();
+        let status = Command::new("pdftoppm").args(["-r", &config.dpi.to_string(), "-f", &n, "-l", &n, "-png", "-singlefile"]).arg(pdf).arg(&prefix).status()
+            .context("running pdftoppm (install poppler-utils)")?;
+        if !status.success() { return Err(anyhow!("pdftoppm failed on page {}", page)); }
+        let image = prefix.with_extension("png");
+        let text = recognize(&image, &config.language);
+        let fingerprint = phash::dhash_file(&image);
+        let _ = std::fs::remove_file(&image);
+        Ok((text.with_context(|| format!("page {}", page))?, fingerprint))
+    }
+}
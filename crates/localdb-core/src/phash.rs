@@ -0,0 +1,100 @@
+//! Perceptual hashes of scanned pages, for skipping re-uploaded scans.
+//!
+//! `dhash` is a 64-bit difference hash: the page is shrunk to 9×8 grayscale
+//! and each bit says whether a pixel is brighter than its right neighbour.
+//! Rescanning, recompression and resizing flip only a few bits, so pages at
+//! most `ocr.duplicate_distance` bits apart (Hamming) look like the same
+//! page. Pages of one form or template look alike too, so their recognized
+//! text must agree as well: `text_hash` is a 64-bit simhash of the words,
+//! which OCR noise moves by a few bits, and the two must be at most
+//! `MAX_TEXT_DISTANCE` bits apart.
+//! `PageIndex` avoids comparing each page with every other: split into eight
+//! bytes, two hashes at most 7 bits apart agree on at least one byte, so only
+//! pages sharing a byte are compared. Image hashes need the `images` feature.
+
+#[cfg(feature = "images")]
+use image::imageops::FilterType;
+#[cfg(feature = "images")]
+use image::DynamicImage;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Default `ocr.duplicate_distance`.
+pub const DEFAULT_MAX_DISTANCE: u32 = 4;
+/// Largest distance `PageIndex` can search (one less than its byte bands).
+pub const MAX_SUPPORTED_DISTANCE: u32 = 7;
+/// Most differing `text_hash` bits for two pages' text to be the same.
+pub const MAX_TEXT_DISTANCE: u32 = 12;
+
+/// Difference hash of an image, row-major from the top left.
+#[cfg(feature = "images")]
+pub fn dhash(img: &DynamicImage) -> u64 {
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut bits = 0u64;
+    for y in 0..8 {
+        for x in 0..8 { bits = (bits << 1) | u64::from(small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0]); }
+    }
+    bits
+}
+
+/// `dhash` of an image file; `None` if it does not decode.
+#[cfg(feature = "images")]
+pub fn dhash_file(path: &Path) -> Option<u64> { image::open(path).ok().map(|img| dhash(&img)) }
+
+#[cfg(not(feature = "images"))]
+pub fn dhash_file(_path: &Path) -> Option<u64> { None }
+
+/// Simhash of the words (two characters or more, lowercased) of a page's text.
+pub fn text_hash(text: &str) -> u64 {
+    let mut weights = [0i32; 64];
+    for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| w.chars().nth(1).is_some()) {
+        let digest = blake3::hash(word.to_lowercase().as_bytes());
+        let h = u64::from_le_bytes(digest.as_bytes()[..8].try_into().expect("8 bytes"));
+        for (bit, w) in weights.iter_mut().enumerate() { *w += if (h >> bit) & 1 == 1 { 1 } else { -1 }; }
+    }
+    weights.iter().enumerate().fold(0, |bits, (bit, &w)| if w > 0 { bits | (1 << bit) } else { bits })
+}
+
+/// Differing bits.
+pub fn distance(a: u64, b: u64) -> u32 { (a ^ b).count_ones() }
+
+/// Pages seen during one ingest, by hash, each with its `text_hash` and
+/// source (`<doc_path>#<page>`).
+#[derive(Debug, Default)]
+pub struct PageIndex {
+    pages: Vec<(u64, u64, String)>,
+    bands: [HashMap<u8, Vec<usize>>; 8],
+    max_distance: u32,
+}
+
+impl PageIndex {
+    /// `max_distance` is capped at `MAX_SUPPORTED_DISTANCE`.
+    pub fn new(max_distance: u32) -> Self { Self { max_distance: max_distance.min(MAX_SUPPORTED_DISTANCE), ..Self::default() } }
+
+    /// Source of the earliest page within the distance of `hash` whose text
+    /// hash is within `MAX_TEXT_DISTANCE` of `text`.
+    pub fn find(&self, hash: u64, text: u64) -> Option<&str> {
+        self.bands.iter().enumerate().filter_map(|(band, map)| map.get(&band_byte(hash, band)))
+            .flatten().copied()
+            .filter(|&i| distance(self.pages[i].0, hash) <= self.max_distance && distance(self.pages[i].1, text) <= MAX_TEXT_DISTANCE)
+            .min()
+            .map(|i| self.pages[i].2.as_str())
+    }
+
+    /// The canonical source when a page (image `hash`, `text_hash` `text`)
+    /// duplicates one already seen; otherwise records `source` as canonical
+    /// for it and returns `None`.
+    pub fn claim(&mut self, hash: u64, text: u64, source: String) -> Option<String> {
+        if let Some(canonical) = self.find(hash, text) { return Some(canonical.to_string()); }
+        let i = self.pages.len();
+        self.pages.push((hash, text, source));
+        for (band, map) in self.bands.iter_mut().enumerate() { map.entry(band_byte(hash, band)).or_default().push(i); }
+        None
+    }
+
+    pub fn len(&self) -> usize { self.pages.len() }
+
+    pub fn is_empty(&self) -> bool { self.pages.is_empty() }
+}
+
+fn band_byte(hash: u64, band: usize) -> u8 { (hash >> (band * 8)) as u8 }
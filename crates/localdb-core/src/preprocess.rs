@@ -0,0 +1,154 @@
+//! Text cleaning applied before embedding (`[embedding.preprocess]`).
+//!
+//! Markdown syntax, running page headers/footers and ragged OCR whitespace
+//! pull vectors away from what a passage is about. A `Preprocessor` runs the
+//! configured `Step`s in order on every chunk before it is embedded, and on
+//! queries before they are embedded so both sides are cleaned alike. Stored
+//! and BM25-indexed text is left as ingested.
+//!
+//! Steps are configured per Lance collection
+//! (`[embedding.preprocess.collections.<name>]`), falling back to
+//! `[embedding.preprocess]`; by default none run. Changing them changes the
+//! vectors, so re-ingest afterwards.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::boilerplate::{is_page_marker, line_key, lines_on};
+use crate::config::Config;
+use crate::types::DocumentChunk;
+
+/// Default `boilerplate_repeats`.
+pub const DEFAULT_BOILERPLATE_REPEATS: usize = 3;
+
+/// One cleaning pass; names as in config (`strip_markdown`, ...).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    /// Headings, emphasis, code fences, list and quote markers, rules, table
+    /// pipes; links and images keep their text.
+    StripMarkdown,
+    /// Every whitespace run (line breaks included) becomes one space.
+    CollapseWhitespace,
+    /// Page-number lines (`12`, `Page 3 of 40`, `- 7 -`) and, within one
+    /// document, short lines repeated in `boilerplate_repeats` or more chunks,
+    /// compared as `[boilerplate]` compares them (`boilerplate::line_key`).
+    /// Works line by line, so list it before `collapse_whitespace`.
+    StripBoilerplate,
+    Lowercase,
+}
+
+/// The cleaning pipeline of one collection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preprocessor {
+    pub steps: Vec<Step>,
+    /// Chunks of one document a line must appear in to count as a header or footer.
+    pub boilerplate_repeats: usize,
+}
+
+impl Default for Preprocessor {
+    fn default() -> Self { Self { steps: Vec::new(), boilerplate_repeats: DEFAULT_BOILERPLATE_REPEATS } }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Section {
+    steps: Option<Vec<Step>>,
+    boilerplate_repeats: Option<usize>,
+}
+
+impl Preprocessor {
+    pub fn new(steps: Vec<Step>) -> Self { Self { steps, ..Self::default() } }
+
+    /// Pipeline of `collection`: its own section's settings, else those of
+    /// `[embedding.preprocess]`. Unknown step names are an error.
+    pub fn from_config(config: &Config, collection: &str) -> Result<Self> {
+        let section = |key: &str| -> Result<Section> {
+            match config.get::<serde_json::Value>(key) {
+                Ok(value) => serde_json::from_value(value).with_context(|| format!("invalid [{}]", key)),
+                Err(_) => Ok(Section::default()),
+            }
+        };
+        let base = section("embedding.preprocess")?;
+        let own = section(&format!("embedding.preprocess.collections.{}", collection))?;
+        let d = Self::default();
+        Ok(Self {
+            steps: own.steps.or(base.steps).unwrap_or(d.steps),
+            boilerplate_repeats: own.boilerplate_repeats.or(base.boilerplate_repeats).unwrap_or(d.boilerplate_repeats).max(2),
+        })
+    }
+
+    /// Whether every text passes through unchanged.
+    pub fn is_identity(&self) -> bool { self.steps.is_empty() }
+
+    /// `text` cleaned on its own (a query): `strip_boilerplate` drops page
+    /// numbers only, as repeats need the whole document.
+    pub fn clean(&self, text: &str) -> String { self.apply(text, &HashSet::new()) }
+
+    /// Texts to embed for `chunks`, in order. A chunk that cleans down to
+    /// nothing keeps its original text.
+    pub fn embedding_texts(&self, chunks: &[DocumentChunk]) -> Vec<String> {
+        if self.is_identity() { return chunks.iter().map(|c| c.content.clone()).collect(); }
+        let repeated = if self.steps.contains(&Step::StripBoilerplate) { self.repeated_lines(chunks) } else { HashMap::new() };
+        let none = HashSet::new();
+        chunks.iter().map(|c| {
+            let cleaned = self.apply(&c.content, repeated.get(c.doc_id.as_str()).unwrap_or(&none));
+            if cleaned.trim().is_empty() { c.content.clone() } else { cleaned }
+        }).collect()
+    }
+
+    fn apply(&self, text: &str, boilerplate: &HashSet<String>) -> String {
+        self.steps.iter().fold(text.to_string(), |text, step| match step {
+            Step::StripMarkdown => strip_markdown(&text),
+            Step::CollapseWhitespace => text.split_whitespace().collect::<Vec<_>>().join(" "),
+            Step::StripBoilerplate => text.lines().filter(|l| !is_page_marker(l) && !line_key(l).is_some_and(|k| boilerplate.contains(&k))).collect::<Vec<_>>().join("\n"),
+            Step::Lowercase => text.to_lowercase(),
+        })
+    }
+
+    /// Per document: keys of the short lines found in at least `boilerplate_repeats` of its chunks.
+    fn repeated_lines<'a>(&self, chunks: &'a [DocumentChunk]) -> HashMap<&'a str, HashSet<String>> {
+        let mut docs: HashMap<&str, Vec<&str>> = HashMap::new();
+        for c in chunks { docs.entry(c.doc_id.as_str()).or_default().push(&c.content); }
+        docs.into_iter().map(|(doc, contents)| (doc, lines_on(&contents, self.boilerplate_repeats))).collect()
+    }
+}
+
+fn strip_markdown(text: &str) -> String {
+    let mut out = Vec::new();
+    for line in text.lines() {
+        let t = line.trim_start();
+        if t.starts_with("```") || t.starts_with("~~~") { continue; }
+        if t.len() >= 3 && (t.chars().all(|c| c == '-' || c == ' ') || t.chars().all(|c| c == '*' || c == ' ') || t.chars().all(|c| c == '_' || c == ' ')) { continue; }
+        let t = t.trim_start_matches('#').trim_start();
+        let t = t.trim_start_matches('>').trim_start();
+        let t = ["- ", "* ", "+ "].iter().find_map(|m| t.strip_prefix(m)).unwrap_or(t);
+        let t = t.split_once(". ").filter(|(n, _)| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit())).map_or(t, |(_, rest)| rest);
+        let t = links_to_text(t).replace("**", "").replace("__", "").replace(['`', '*'], "");
+        let t = if t.contains('|') { t.split('|').map(str::trim).filter(|c| !c.is_empty() && !c.chars().all(|ch| ch == '-' || ch == ':')).collect::<Vec<_>>().join(" ") } else { t };
+        out.push(t);
+    }
+    out.join("\n")
+}
+
+/// `[text](url)` and `![alt](url)` → `text` / `alt`.
+fn links_to_text(line: &str) -> String {
+    let mut out = String::new();
+    let mut rest = line;
+    while let Some(open) = rest.find('[') {
+        let inner = &rest[open + 1..];
+        let Some(close) = inner.find(']') else { break };
+        let (label, tail) = (&inner[..close], &inner[close + 1..]);
+        let Some(url_end) = tail.strip_prefix('(').and_then(|t| t.find(')')) else {
+            out.push_str(&rest[..open + close + 2]);
+            rest = tail;
+            continue;
+        };
+        out.push_str(rest[..open].strip_suffix('!').unwrap_or(&rest[..open]));
+        out.push_str(label);
+        rest = &tail[url_end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
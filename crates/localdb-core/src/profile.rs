@@ -0,0 +1,140 @@
+//! Opt-in per-stage timing for the ingest pipeline.
+//!
+//! Stages are accumulated in process-wide counters so the crates involved in an
+//! ingest (chunker, embedder, Lance writer, Tantivy writer) can record their
+//! time without threading a profiler through every trait. Recording is a no-op
+//! until `enable()` is called (e.g. by `localdb-cli ingest --profile`), after
+//! which `ProfileReport::snapshot` yields the breakdown and the bottleneck.
+//! Files are read and chunked on several threads at once, so `read` and
+//! `chunk` add up the time of every thread and can exceed the wall clock.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// An ingest pipeline stage, in pipeline order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Walking the data directory for input files.
+    Scan,
+    /// Reading file contents from disk.
+    Read,
+    /// Splitting documents into chunks.
+    Chunk,
+    /// Tokenizing chunk text for the embedding model.
+    Tokenize,
+    /// Running the embedding model (forward pass and pooling).
+    EmbedForward,
+    /// Writing rows to the Lance `documents` table.
+    LanceWrite,
+    /// Adding documents to the Tantivy index writer.
+    TantivyWrite,
+    /// Committing the Tantivy index.
+    Commit,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 8] = [
+        Stage::Scan, Stage::Read, Stage::Chunk, Stage::Tokenize,
+        Stage::EmbedForward, Stage::LanceWrite, Stage::TantivyWrite, Stage::Commit,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Stage::Scan => "scan",
+            Stage::Read => "read",
+            Stage::Chunk => "chunk",
+            Stage::Tokenize => "tokenize",
+            Stage::EmbedForward => "embed forward",
+            Stage::LanceWrite => "lance write",
+            Stage::TantivyWrite => "tantivy write",
+            Stage::Commit => "commit",
+        }
+    }
+
+    /// What to upgrade when this stage dominates.
+    pub fn advice(self) -> &'static str {
+        match self {
+            Stage::Scan | Stage::Read => "disk-bound: faster storage helps; otherwise, patience",
+            Stage::Chunk => "CPU-bound chunking: patience (or fewer, larger files)",
+            Stage::Tokenize | Stage::EmbedForward => "model-bound: a GPU (CUDA/Metal) helps most",
+            Stage::LanceWrite | Stage::TantivyWrite | Stage::Commit => "write-bound: more RAM (page cache, writer heap) helps",
+        }
+    }
+
+    fn index(self) -> usize { self as usize }
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static NANOS: [AtomicU64; 8] = [const { AtomicU64::new(0) }; 8];
+
+/// Start recording stage timings (and clear any previous totals).
+pub fn enable() { reset(); ENABLED.store(true, Ordering::Relaxed); }
+
+pub fn is_enabled() -> bool { ENABLED.load(Ordering::Relaxed) }
+
+/// Zero all stage totals.
+pub fn reset() { for n in &NANOS { n.store(0, Ordering::Relaxed); } }
+
+/// Add `elapsed` to `stage` when profiling is enabled.
+pub fn record(stage: Stage, elapsed: Duration) {
+    if is_enabled() { NANOS[stage.index()].fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed); }
+}
+
+/// Run `f`, attributing its wall time to `stage`.
+pub fn time<T>(stage: Stage, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let out = f();
+    record(stage, start.elapsed());
+    out
+}
+
+/// Guard that records the time until drop against a stage.
+pub struct StageTimer { stage: Stage, start: Instant }
+
+impl Drop for StageTimer {
+    fn drop(&mut self) { record(self.stage, self.start.elapsed()); }
+}
+
+pub fn timer(stage: Stage) -> StageTimer { StageTimer { stage, start: Instant::now() } }
+
+/// Accumulated per-stage totals against the overall wall time.
+#[derive(Debug, Clone)]
+pub struct ProfileReport {
+    pub stages: Vec<(Stage, Duration)>,
+    pub wall: Duration,
+}
+
+impl ProfileReport {
+    /// Read the current totals.
+    pub fn snapshot(wall: Duration) -> Self {
+        let stages = Stage::ALL.iter().map(|&s| (s, Duration::from_nanos(NANOS[s.index()].load(Ordering::Relaxed)))).collect();
+        Self { stages, wall }
+    }
+
+    /// The stage with the largest total, if any time was recorded.
+    pub fn bottleneck(&self) -> Option<(Stage, Duration)> {
+        self.stages.iter().copied().filter(|(_, d)| !d.is_zero()).max_by_key(|(_, d)| *d)
+    }
+
+    /// Wall time not attributed to any stage (setup, model load, glue).
+    pub fn unaccounted(&self) -> Duration {
+        let total: Duration = self.stages.iter().map(|(_, d)| *d).sum();
+        self.wall.saturating_sub(total)
+    }
+
+    /// Human-readable table plus the bottleneck line.
+    pub fn render(&self) -> String {
+        let wall = self.wall.as_secs_f64().max(1e-9);
+        let mut out = String::from("Ingest profile\n");
+        let rows = self.stages.iter().map(|(s, d)| (s.label(), *d)).chain(std::iter::once(("other", self.unaccounted())));
+        for (label, d) in rows {
+            out.push_str(&format!("  {:<14} {:>10.3}s {:>5.1}%\n", label, d.as_secs_f64(), 100.0 * d.as_secs_f64() / wall));
+        }
+        out.push_str(&format!("  {:<14} {:>10.3}s\n", "total", self.wall.as_secs_f64()));
+        match self.bottleneck() {
+            Some((s, d)) => out.push_str(&format!("Bottleneck: {} ({:.1}% of wall time) — {}\n", s.label(), 100.0 * d.as_secs_f64() / wall, s.advice())),
+            None => out.push_str("Bottleneck: none (no stage time recorded)\n"),
+        }
+        out
+    }
+}
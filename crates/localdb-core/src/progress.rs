@@ -0,0 +1,126 @@
+//! Progress of long jobs (ingest, backfill, index builds) for the web UI.
+//!
+//! Like `profile`, progress goes through process-wide state so the crates
+//! doing the work (chunker, Lance writer, Tantivy writer, backfill, index
+//! build) can report it without threading a reporter through every trait.
+//! Reporting is a no-op until `enable(dir, job)` is called; after that each
+//! `report(phase, done, total)` updates the job's `Progress`, written to
+//! `<dir>/<job>.json` (atomically, at most every `MIN_INTERVAL` unless the
+//! phase changes or completes). Jobs run as separate processes, so
+//! `localdb-cli serve` polls the directory (`read_all`) and streams changes
+//! as server-sent events at `GET /progress`. Files of jobs done (or dead)
+//! for `KEEP_DONE` are deleted by `prune`, which `enable` runs.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Shortest time between two writes of the same phase.
+pub const MIN_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A job not finished and not updated for this long probably died.
+pub const STALL_AFTER: Duration = Duration::from_secs(120);
+
+/// How long a finished or stalled job's file is kept.
+pub const KEEP_DONE: Duration = Duration::from_secs(24 * 3600);
+
+/// Latest state of one job.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Progress {
+    /// `ingest`, `backfill`, `index_build`.
+    pub job: String,
+    /// What the job is doing now (`read`, `embed`, `lance write`, …).
+    pub phase: String,
+    pub done: u64,
+    /// Units of the phase, when known.
+    pub total: Option<u64>,
+    pub finished: bool,
+    /// Why the job stopped, when it failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Milliseconds since the epoch.
+    pub updated_at: i64,
+}
+
+impl Progress {
+    /// Share of the phase done (0–100), when its total is known.
+    pub fn percent(&self) -> Option<f32> {
+        self.total.map(|t| if t == 0 { 100.0 } else { (self.done.min(t) as f32 / t as f32) * 100.0 })
+    }
+
+    /// Unfinished and silent for `STALL_AFTER` as of `now_ms`.
+    pub fn is_stalled(&self, now_ms: i64) -> bool { !self.finished && now_ms - self.updated_at > STALL_AFTER.as_millis() as i64 }
+}
+
+struct Sink {
+    path: PathBuf,
+    current: Progress,
+    written: Option<Instant>,
+}
+
+static SINK: Mutex<Option<Sink>> = Mutex::new(None);
+
+/// Start reporting the progress of `job` into `dir`.
+pub fn enable(dir: &Path, job: &str) {
+    prune(dir, now_ms());
+    let current = Progress { job: job.to_string(), phase: "starting".to_string(), done: 0, total: None, finished: false, error: None, updated_at: now_ms() };
+    let mut sink = Sink { path: dir.join(format!("{}.json", job)), current, written: None };
+    write(&mut sink);
+    *SINK.lock().unwrap_or_else(|e| e.into_inner()) = Some(sink);
+}
+
+pub fn is_enabled() -> bool { SINK.lock().map(|s| s.is_some()).unwrap_or(false) }
+
+/// `done` of `total` units of `phase` are done.
+pub fn report(phase: &str, done: u64, total: Option<u64>) {
+    let mut guard = SINK.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(sink) = guard.as_mut() else { return };
+    let due = sink.current.phase != phase || total.is_some_and(|t| done >= t) || sink.written.is_none_or(|w| w.elapsed() >= MIN_INTERVAL);
+    sink.current.phase = phase.to_string();
+    sink.current.done = done;
+    sink.current.total = total;
+    if due { write(sink); }
+}
+
+/// Mark the job finished (failed with `error`) and stop reporting.
+pub fn finish(error: Option<String>) {
+    let Some(mut sink) = SINK.lock().unwrap_or_else(|e| e.into_inner()).take() else { return };
+    sink.current.finished = true;
+    sink.current.error = error;
+    write(&mut sink);
+}
+
+/// Write to a temporary file and rename it over the job's file, so a reader
+/// never sees half a document. Progress is best effort: errors are ignored.
+fn write(sink: &mut Sink) {
+    sink.current.updated_at = now_ms();
+    sink.written = Some(Instant::now());
+    let Ok(json) = serde_json::to_string(&sink.current) else { return };
+    if let Some(dir) = sink.path.parent() { let _ = fs::create_dir_all(dir); }
+    let tmp = sink.path.with_extension("json.tmp");
+    if fs::write(&tmp, json).is_ok() { let _ = fs::rename(&tmp, &sink.path); }
+}
+
+/// Latest progress of every job in `dir`, by job name; unreadable files are skipped.
+pub fn read_all(dir: &Path) -> Vec<Progress> {
+    let mut jobs: Vec<Progress> = fs::read_dir(dir).into_iter().flatten().filter_map(|e| e.ok()).map(|e| e.path())
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("json"))
+        .filter_map(|p| fs::read_to_string(p).ok()).filter_map(|s| serde_json::from_str(&s).ok()).collect();
+    jobs.sort_by(|a, b| a.job.cmp(&b.job));
+    jobs
+}
+
+/// Delete the files in `dir` of jobs finished or stalled more than
+/// `KEEP_DONE` before `now_ms`. Best effort, like writing.
+pub fn prune(dir: &Path, now_ms: i64) {
+    for path in fs::read_dir(dir).into_iter().flatten().filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.extension().and_then(|e| e.to_str()) == Some("json")) {
+        let Some(p) = fs::read_to_string(&path).ok().and_then(|s| serde_json::from_str::<Progress>(&s).ok()) else { continue };
+        if (p.finished || p.is_stalled(now_ms)) && now_ms - p.updated_at > KEEP_DONE.as_millis() as i64 { let _ = fs::remove_file(&path); }
+    }
+}
+
+pub fn now_ms() -> i64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or(0)
+}
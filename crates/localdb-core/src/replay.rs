@@ -0,0 +1,133 @@
+//! A/B replay of logged queries against two index generations.
+//!
+//! Before switching to an index built with new chunking or a new embedder,
+//! `localdb-cli replay` runs the queries users actually issued (the `Query`
+//! events of the feedback log) against the current and the candidate index
+//! and reports how far their top-k agree and how fast each answers. Chunk ids
+//! are content hashes, so re-chunking changes most of them; agreement is
+//! reported per chunk and per document (the chunk id before its last `:`).
+//! Which side runs first alternates per query so cache warm-up does not
+//! favour either.
+
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use crate::feedback::Event;
+
+/// Queries listed as the largest disagreements in a report.
+pub const WORST_SHOWN: usize = 10;
+
+/// One query run on both generations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryDiff {
+    pub query: String,
+    /// Top-k chunk ids shared, over k (see `overlap_at_k`).
+    pub chunk_overlap: f32,
+    /// The same for the documents of those chunks.
+    pub doc_overlap: f32,
+    /// Both put the same chunk first.
+    pub same_top: bool,
+    pub current: Duration,
+    pub candidate: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayReport {
+    pub k: usize,
+    pub diffs: Vec<QueryDiff>,
+}
+
+/// The latest `limit` distinct queries of the log (all without a limit),
+/// oldest first. Empty queries (browsing) are skipped.
+pub fn logged_queries(events: &[Event], limit: Option<usize>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut latest: Vec<String> = events.iter().rev().filter_map(|e| match e {
+        Event::Query { query, .. } if !query.trim().is_empty() && seen.insert(query.as_str()) => Some(query.clone()),
+        _ => None,
+    }).take(limit.unwrap_or(usize::MAX)).collect();
+    latest.reverse();
+    latest
+}
+
+/// Share of the top `k` of `a` and `b` found in both, over the longer of the
+/// two (at most `k`); two empty lists agree fully.
+pub fn overlap_at_k(a: &[String], b: &[String], k: usize) -> f32 {
+    let (a, b) = (&a[..a.len().min(k)], &b[..b.len().min(k)]);
+    let longer = a.len().max(b.len());
+    if longer == 0 { return 1.0; }
+    let b: HashSet<&String> = b.iter().collect();
+    a.iter().filter(|id| b.contains(id)).count() as f32 / longer as f32
+}
+
+/// Documents of ranked chunk ids, first appearance only.
+pub fn doc_ids(chunk_ids: &[String]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    chunk_ids.iter().map(|id| id.rsplit_once(':').map_or(id.as_str(), |(d, _)| d)).filter(|d| seen.insert(*d)).map(str::to_string).collect()
+}
+
+/// Run every query on both generations (each returns ranked chunk ids).
+pub fn replay(queries: &[String], k: usize, mut current: impl FnMut(&str) -> Result<Vec<String>>, mut candidate: impl FnMut(&str) -> Result<Vec<String>>) -> Result<ReplayReport> {
+    let mut diffs = Vec::with_capacity(queries.len());
+    for (i, query) in queries.iter().enumerate() {
+        let timed = |search: &mut dyn FnMut(&str) -> Result<Vec<String>>| -> Result<(Vec<String>, Duration)> {
+            let start = Instant::now();
+            let hits = search(query)?;
+            Ok((hits, start.elapsed()))
+        };
+        let ((a, current_time), (b, candidate_time)) = if i % 2 == 0 {
+            let a = timed(&mut current)?;
+            (a, timed(&mut candidate)?)
+        } else {
+            let b = timed(&mut candidate)?;
+            (timed(&mut current)?, b)
+        };
+        diffs.push(QueryDiff {
+            query: query.clone(), chunk_overlap: overlap_at_k(&a, &b, k), doc_overlap: overlap_at_k(&doc_ids(&a), &doc_ids(&b), k),
+            same_top: !a.is_empty() && a.first() == b.first(), current: current_time, candidate: candidate_time,
+        });
+    }
+    Ok(ReplayReport { k, diffs })
+}
+
+/// `q`-quantile (0–1) of `durations`, nearest rank.
+pub fn percentile(durations: &[Duration], q: f32) -> Duration {
+    let mut sorted = durations.to_vec();
+    sorted.sort();
+    let Some(last) = sorted.len().checked_sub(1) else { return Duration::ZERO };
+    sorted[((last as f32 * q.clamp(0.0, 1.0)).round() as usize).min(last)]
+}
+
+impl ReplayReport {
+    pub fn mean_chunk_overlap(&self) -> f32 { self.mean(|d| d.chunk_overlap) }
+
+    pub fn mean_doc_overlap(&self) -> f32 { self.mean(|d| d.doc_overlap) }
+
+    /// Share of queries whose first hit is the same chunk.
+    pub fn top_agreement(&self) -> f32 { self.mean(|d| if d.same_top { 1.0 } else { 0.0 }) }
+
+    fn mean(&self, f: impl Fn(&QueryDiff) -> f32) -> f32 {
+        if self.diffs.is_empty() { 0.0 } else { self.diffs.iter().map(f).sum::<f32>() / self.diffs.len() as f32 }
+    }
+
+    /// Summary, latency percentiles, then the `WORST_SHOWN` queries with the
+    /// least document overlap.
+    pub fn render(&self) -> String {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let (current, candidate): (Vec<Duration>, Vec<Duration>) = self.diffs.iter().map(|d| (d.current, d.candidate)).unzip();
+        let mut out = String::new();
+        let _ = writeln!(out, "Replayed {} queries, top {}", self.diffs.len(), self.k);
+        let _ = writeln!(out, "  overlap: chunks {:.2}, documents {:.2}; same first hit {:.0}%", self.mean_chunk_overlap(), self.mean_doc_overlap(), self.top_agreement() * 100.0);
+        for (name, times) in [("current", &current), ("candidate", &candidate)] {
+            let _ = writeln!(out, "  {:<9} p50 {:.1} ms, p95 {:.1} ms", name, ms(percentile(times, 0.5)), ms(percentile(times, 0.95)));
+        }
+        let mut worst: Vec<&QueryDiff> = self.diffs.iter().filter(|d| d.doc_overlap < 1.0).collect();
+        worst.sort_by(|a, b| a.doc_overlap.total_cmp(&b.doc_overlap));
+        if !worst.is_empty() { let _ = writeln!(out, "Least document overlap:"); }
+        for d in worst.into_iter().take(WORST_SHOWN) {
+            let _ = writeln!(out, "  {:.2}  {:>7.1} → {:>7.1} ms  {}", d.doc_overlap, ms(d.current), ms(d.candidate), d.query);
+        }
+        out
+    }
+}
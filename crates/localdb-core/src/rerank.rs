@@ -0,0 +1,242 @@
+//! Expression-based rerank hook for final scoring (`search.rerank.expr`).
+//!
+//! A small arithmetic DSL evaluated per hit after fusion, so scoring can be
+//! tuned from config without recompiling:
+//!
+//! ```text
+//! score * (1 + 0.2 * is_facet('/medical')) - 0.1 * age_years
+//! ```
+//!
+//! Values are numbers; conditions evaluate to 1 or 0. Operators: `+ - * /`,
+//! comparisons `< <= > >= == !=`, unary `-`, parentheses.
+//!
+//! - Variables: `score` (fused score), `rank` (1-based, before reranking),
+//!   `age_years` (source file age), `is_text`, `is_vector` (hit source)
+//! - Functions: `is_facet('/a')` (category is `/a` or below),
+//!   `path_contains('x')`, `min(a, b)`, `max(a, b)`, `ln(x)`, `if(c, a, b)`
+//!
+//! Expressions are checked when parsed; a non-finite result keeps the
+//! original score.
+
+use crate::error::Error;
+use crate::types::{SearchHit, SourceKind};
+
+/// Per-hit inputs to an expression.
+#[derive(Debug, Clone)]
+pub struct RerankContext<'a> {
+    pub score: f32,
+    pub rank: usize,
+    pub age_years: f32,
+    pub source: SourceKind,
+    pub category: &'a str,
+    pub doc_path: &'a str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Var { Score, Rank, AgeYears, IsText, IsVector }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op { Add, Sub, Mul, Div, Lt, Le, Gt, Ge, Eq, Ne }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Func { Min, Max, Ln, If }
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Num(f32),
+    Var(Var),
+    Neg(Box<Node>),
+    Bin(Op, Box<Node>, Box<Node>),
+    Call(Func, Vec<Node>),
+    IsFacet(String),
+    PathContains(String),
+}
+
+/// A parsed rerank expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RerankExpr { root: Node }
+
+impl RerankExpr {
+    pub fn parse(src: &str) -> Result<Self, Error> {
+        let mut p = Parser { tokens: tokenize(src)?, pos: 0 };
+        let root = p.comparison()?;
+        match p.peek() {
+            None => Ok(Self { root }),
+            Some(t) => Err(invalid(format!("unexpected '{}'", t))),
+        }
+    }
+
+    pub fn eval(&self, ctx: &RerankContext) -> f32 {
+        let v = eval(&self.root, ctx);
+        if v.is_finite() { v } else { ctx.score }
+    }
+
+    /// Rescore `hits` (ranked in their current order) and sort by the new
+    /// score. `context` supplies the per-hit inputs for a hit and its rank.
+    pub fn apply<'a>(&self, hits: &mut [SearchHit], context: impl Fn(&SearchHit, usize) -> RerankContext<'a>) {
+        for (i, h) in hits.iter_mut().enumerate() { h.score = self.eval(&context(h, i + 1)); }
+        hits.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    }
+}
+
+fn invalid(msg: String) -> Error { Error::InvalidConfig(format!("rerank expression: {}", msg)) }
+
+fn truth(b: bool) -> f32 { if b { 1.0 } else { 0.0 } }
+
+fn eval(node: &Node, ctx: &RerankContext) -> f32 {
+    match node {
+        Node::Num(n) => *n,
+        Node::Var(v) => match v {
+            Var::Score => ctx.score,
+            Var::Rank => ctx.rank as f32,
+            Var::AgeYears => ctx.age_years,
+            Var::IsText => truth(ctx.source == SourceKind::Text),
+            Var::IsVector => truth(ctx.source == SourceKind::Vector),
+        },
+        Node::Neg(n) => -eval(n, ctx),
+        Node::Bin(op, a, b) => {
+            let (a, b) = (eval(a, ctx), eval(b, ctx));
+            match op {
+                Op::Add => a + b, Op::Sub => a - b, Op::Mul => a * b, Op::Div => a / b,
+                Op::Lt => truth(a < b), Op::Le => truth(a <= b), Op::Gt => truth(a > b),
+                Op::Ge => truth(a >= b), Op::Eq => truth(a == b), Op::Ne => truth(a != b),
+            }
+        }
+        Node::Call(f, args) => {
+            let arg = |i: usize| eval(&args[i], ctx);
+            match f {
+                Func::Min => arg(0).min(arg(1)),
+                Func::Max => arg(0).max(arg(1)),
+                Func::Ln => arg(0).ln(),
+                Func::If => if arg(0) != 0.0 { arg(1) } else { arg(2) },
+            }
+        }
+        Node::IsFacet(f) => {
+            let (f, c) = (format!("/{}", f.trim_matches('/')), format!("/{}", ctx.category.trim_matches('/')));
+            truth(f == "/" || c == f || c.starts_with(&format!("{}/", f)))
+        }
+        Node::PathContains(s) => truth(ctx.doc_path.contains(s.as_str())),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok { Num(f32), Ident(String), Str(String), Sym(&'static str) }
+
+impl std::fmt::Display for Tok {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tok::Num(n) => write!(f, "{}", n),
+            Tok::Ident(s) => write!(f, "{}", s),
+            Tok::Str(s) => write!(f, "'{}'", s),
+            Tok::Sym(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+const SYMBOLS: &[&str] = &["<=", ">=", "==", "!=", "<", ">", "+", "-", "*", "/", "(", ")", ","];
+
+fn tokenize(src: &str) -> Result<Vec<Tok>, Error> {
+    let mut out = Vec::new();
+    let mut rest = src;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() { rest = &rest[c.len_utf8()..]; continue; }
+        if c.is_ascii_digit() || c == '.' {
+            let end = rest.find(|ch: char| !(ch.is_ascii_digit() || ch == '.')).unwrap_or(rest.len());
+            out.push(Tok::Num(rest[..end].parse().map_err(|_| invalid(format!("bad number '{}'", &rest[..end])))?));
+            rest = &rest[end..];
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest.find(|ch: char| !(ch.is_alphanumeric() || ch == '_')).unwrap_or(rest.len());
+            out.push(Tok::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if c == '\'' || c == '"' {
+            let end = rest[1..].find(c).ok_or_else(|| invalid("unterminated string".into()))?;
+            out.push(Tok::Str(rest[1..1 + end].to_string()));
+            rest = &rest[end + 2..];
+        } else if let Some(sym) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            out.push(Tok::Sym(sym));
+            rest = &rest[sym.len()..];
+        } else {
+            return Err(invalid(format!("unexpected character '{}'", c)));
+        }
+    }
+    Ok(out)
+}
+
+struct Parser { tokens: Vec<Tok>, pos: usize }
+
+impl Parser {
+    fn peek(&self) -> Option<&Tok> { self.tokens.get(self.pos) }
+
+    fn next(&mut self) -> Option<Tok> { let t = self.tokens.get(self.pos).cloned(); self.pos += 1; t }
+
+    fn eat(&mut self, sym: &str) -> bool {
+        let hit = matches!(self.peek(), Some(Tok::Sym(s)) if *s == sym);
+        if hit { self.pos += 1; }
+        hit
+    }
+
+    fn expect(&mut self, sym: &str) -> Result<(), Error> {
+        if self.eat(sym) { Ok(()) } else { Err(invalid(format!("expected '{}'", sym))) }
+    }
+
+    fn binary(&mut self, ops: &[(&str, Op)], operand: fn(&mut Self) -> Result<Node, Error>) -> Result<Node, Error> {
+        let mut left = operand(self)?;
+        'outer: loop {
+            for (sym, op) in ops {
+                if self.eat(sym) { left = Node::Bin(*op, Box::new(left), Box::new(operand(self)?)); continue 'outer; }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Node, Error> {
+        self.binary(&[("<=", Op::Le), (">=", Op::Ge), ("==", Op::Eq), ("!=", Op::Ne), ("<", Op::Lt), (">", Op::Gt)], Self::sum)
+    }
+
+    fn sum(&mut self) -> Result<Node, Error> { self.binary(&[("+", Op::Add), ("-", Op::Sub)], Self::term) }
+
+    fn term(&mut self) -> Result<Node, Error> { self.binary(&[("*", Op::Mul), ("/", Op::Div)], Self::unary) }
+
+    fn unary(&mut self) -> Result<Node, Error> {
+        if self.eat("-") { Ok(Node::Neg(Box::new(self.unary()?))) } else { self.primary() }
+    }
+
+    fn primary(&mut self) -> Result<Node, Error> {
+        match self.next() {
+            Some(Tok::Num(n)) => Ok(Node::Num(n)),
+            Some(Tok::Sym("(")) => { let e = self.comparison()?; self.expect(")")?; Ok(e) }
+            Some(Tok::Ident(name)) if self.eat("(") => self.call(&name),
+            Some(Tok::Ident(name)) => Ok(Node::Var(match name.as_str() {
+                "score" => Var::Score,
+                "rank" => Var::Rank,
+                "age_years" => Var::AgeYears,
+                "is_text" => Var::IsText,
+                "is_vector" => Var::IsVector,
+                _ => return Err(invalid(format!("unknown variable '{}'", name))),
+            })),
+            Some(t) => Err(invalid(format!("unexpected '{}'", t))),
+            None => Err(invalid("unexpected end of expression".into())),
+        }
+    }
+
+    /// Function call after `name(`.
+    fn call(&mut self, name: &str) -> Result<Node, Error> {
+        if matches!(name, "is_facet" | "path_contains") {
+            let Some(Tok::Str(s)) = self.next() else { return Err(invalid(format!("{}() takes a quoted string", name))) };
+            self.expect(")")?;
+            return Ok(if name == "is_facet" { Node::IsFacet(s) } else { Node::PathContains(s) });
+        }
+        let (func, arity) = match name {
+            "min" => (Func::Min, 2),
+            "max" => (Func::Max, 2),
+            "ln" => (Func::Ln, 1),
+            "if" => (Func::If, 3),
+            _ => return Err(invalid(format!("unknown function '{}'", name))),
+        };
+        let mut args = vec![self.comparison()?];
+        while self.eat(",") { args.push(self.comparison()?); }
+        self.expect(")")?;
+        if args.len() != arity { return Err(invalid(format!("{}() takes {} arguments, got {}", name, arity, args.len()))); }
+        Ok(Node::Call(func, args))
+    }
+}
@@ -0,0 +1,61 @@
+//! Per-facet retention rules (`[[retention]]` in config).
+//!
+//! A rule keeps documents under `facet` for `max_age_days` (by source file
+//! modification time), or forever when `max_age_days` is absent. The most
+//! specific matching facet wins, so `/news` can expire while `/news/archive`
+//! is kept. Expired documents are skipped at ingest and removed from both
+//! indexes and the catalog by `localdb-cli maintain`.
+
+use serde::Deserialize;
+use std::time::{Duration, SystemTime};
+
+use crate::config::Config;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RetentionRule {
+    /// Facet the rule applies to, including descendants (`/news`).
+    pub facet: String,
+    /// Maximum document age; `None` keeps documents forever.
+    #[serde(default)]
+    pub max_age_days: Option<u64>,
+}
+
+/// All configured rules; documents under no rule are kept forever.
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy { pub rules: Vec<RetentionRule> }
+
+impl RetentionPolicy {
+    /// Rules from `retention` (empty if unset).
+    pub fn from_config(config: &Config) -> Self {
+        Self { rules: config.get::<Vec<RetentionRule>>("retention").unwrap_or_default() }
+    }
+
+    pub fn is_empty(&self) -> bool { self.rules.is_empty() }
+
+    /// Maximum age for documents in `category`, from the most specific rule.
+    pub fn max_age(&self, category: &str) -> Option<Duration> {
+        self.rules.iter()
+            .filter(|r| facet_contains(&r.facet, category))
+            .max_by_key(|r| normalize(&r.facet).len())
+            .and_then(|r| r.max_age_days)
+            .map(|d| Duration::from_secs(d.saturating_mul(86_400)))
+    }
+
+    /// Whether a document in `category` last modified at `modified` has expired as of `now`.
+    pub fn is_expired(&self, category: &str, modified: SystemTime, now: SystemTime) -> bool {
+        match (self.max_age(category), now.duration_since(modified)) {
+            (Some(max), Ok(age)) => age > max,
+            _ => false,
+        }
+    }
+}
+
+fn normalize(facet: &str) -> String {
+    format!("/{}", facet.trim_matches('/'))
+}
+
+/// `facet` equals `category` or is one of its ancestors (segment-wise).
+fn facet_contains(facet: &str, category: &str) -> bool {
+    let (f, c) = (normalize(facet), normalize(category));
+    f == "/" || c == f || c.starts_with(&format!("{}/", f))
+}
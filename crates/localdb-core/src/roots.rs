@@ -0,0 +1,156 @@
+//! Multiple raw data roots (library drive, notes folder, USB stick, ...).
+//!
+//! Each root is configured under `[[data.roots]]` with its own facet prefix
+//! and connector settings (which file extensions to pick up, include/exclude
+//! globs as in `globs`). A single
+//! `data.raw_txt_dir` remains supported as an unnamed root.
+//!
+//! With several roots, stored `doc_path`s and doc ids are prefixed with the
+//! root name (`notes/garden/beds.txt`) and `RootMap` records where each named
+//! root lives so paths resolve to absolute at display time.
+//!
+//! Roots on removable media (USB stick, external HDD) may be unplugged. Their
+//! documents stay indexed and searchable; `RootMap::offline_label` marks such
+//! hits and `RootMap::openable` refuses them for open/export. Availability is
+//! checked on every call, so a returning drive is picked up without re-ingest.
+
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::config::{expand_path, resolve_doc_path, Config};
+
+/// One configured data root.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DataRoot {
+    pub path: PathBuf,
+    /// Short name used to prefix doc ids/paths; defaults to the last path component.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Facet prepended to every category from this root (e.g. `/library`).
+    #[serde(default)]
+    pub facet_prefix: Option<String>,
+    /// Connector settings: file extensions to ingest (without the dot).
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<String>,
+    /// Include/exclude globs on root-relative paths (see `globs`), after `data.patterns`.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+}
+
+/// Scans (PDFs and `ocr::IMAGE_EXTENSIONS`) cost an OCR pass each, and
+/// `.json` is as often config or data dumps as record arrays, so a root only
+/// reads them when its extensions list them.
+fn default_extensions() -> Vec<String> {
+    ["txt", "epub", "zim", "csv", "tsv", "jsonl", "ndjson", "vtt", "srt", "zip", "gz", "tgz"].iter().map(|e| e.to_string()).collect()
+}
+
+/// `data.extensions`, the extensions of the single `data.raw_txt_dir` root
+/// (and of `ingest <dir>`), else the defaults.
+pub fn single_root_extensions(config: &Config) -> Vec<String> {
+    config.get("data.extensions").unwrap_or_else(|_| default_extensions())
+}
+
+impl DataRoot {
+    /// An unnamed root with the default extensions (text, EPUB, ZIM, CSV/TSV, JSON Lines, transcripts, archives), as configured by `data.raw_txt_dir`.
+    pub fn single(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), name: None, facet_prefix: None, extensions: default_extensions(), patterns: Vec::new() }
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            self.path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| "root".to_string())
+        })
+    }
+
+    /// Whether the root's directory is currently reachable (media plugged in).
+    pub fn is_online(&self) -> bool { self.path.is_dir() }
+
+    /// Whether the root picks up `path` by extension and `patterns`. `gz`
+    /// means `.tar.gz` archives only; a lone gzipped file is not read.
+    pub fn accepts(&self, path: &Path) -> bool {
+        path.extension().and_then(|e| e.to_str()).is_some_and(|e| self.extensions.iter().any(|x| x.eq_ignore_ascii_case(e)) && (!e.eq_ignore_ascii_case("gz") || crate::archive::is_archive(path)))
+            && crate::globs::in_scope(&self.patterns, path.strip_prefix(&self.path).unwrap_or(path))
+    }
+}
+
+/// Roots from `data.roots`, else the single `data.raw_txt_dir` (or
+/// `fallback`); `data.patterns` scope every root.
+pub fn load_roots(config: &Config, fallback: &str) -> Vec<DataRoot> {
+    let patterns: Vec<String> = config.get("data.patterns").unwrap_or_default();
+    match config.get::<Vec<DataRoot>>("data.roots") {
+        Ok(roots) if !roots.is_empty() => roots.into_iter().map(|r| DataRoot { path: expand_path(r.path.to_string_lossy()), patterns: patterns.iter().cloned().chain(r.patterns).collect(), ..r }).collect(),
+        _ => vec![DataRoot { patterns, extensions: single_root_extensions(config), ..DataRoot::single(config.get::<String>("data.raw_txt_dir").unwrap_or_else(|_| fallback.to_string())) }],
+    }
+}
+
+/// Recorded location of each root, stored alongside an index. An entry with
+/// an empty name is the unnamed single root.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RootMap(pub Vec<(String, PathBuf)>);
+
+impl RootMap {
+    pub fn single(path: impl Into<PathBuf>) -> Self { Self(vec![(String::new(), path.into())]) }
+
+    /// Map for the roots of one ingest; a lone root stays unnamed so stored
+    /// paths are plain root-relative paths.
+    pub fn for_roots(roots: &[DataRoot]) -> Self {
+        let abs = |p: &Path| std::fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+        match roots {
+            [only] => Self::single(abs(&only.path)),
+            _ => Self(roots.iter().map(|r| (r.name(), abs(&r.path))).collect()),
+        }
+    }
+
+    /// Replace (or add) the location of root `name` (`""` for the unnamed root).
+    pub fn set(&mut self, name: &str, path: PathBuf) {
+        match self.0.iter_mut().find(|(n, _)| n == name) {
+            Some(entry) => entry.1 = path,
+            None => self.0.push((name.to_string(), path)),
+        }
+    }
+
+    /// One `name<TAB>path` line per root; the unnamed root is just its path.
+    pub fn encode(&self) -> String {
+        self.0.iter().map(|(n, p)| if n.is_empty() { p.to_string_lossy().to_string() } else { format!("{}\t{}", n, p.to_string_lossy()) }).collect::<Vec<_>>().join("\n")
+    }
+
+    pub fn decode(s: &str) -> Self {
+        Self(s.lines().filter(|l| !l.trim().is_empty()).map(|l| match l.split_once('\t') {
+            Some((n, p)) => (n.to_string(), PathBuf::from(p)),
+            None => (String::new(), PathBuf::from(l)),
+        }).collect())
+    }
+
+    /// The root a stored `doc_path` belongs to and the path relative to it: a
+    /// leading root name selects that root, otherwise the unnamed root (if any).
+    fn root_of<'a>(&'a self, stored: &'a str) -> (Option<(&'a str, &'a Path)>, &'a str) {
+        if let Some((head, rest)) = stored.split_once('/') {
+            if let Some((n, root)) = self.0.iter().find(|(n, _)| !n.is_empty() && n == head) {
+                return (Some((n.as_str(), root.as_path())), rest);
+            }
+        }
+        (self.0.iter().find(|(n, _)| n.is_empty()).map(|(n, p)| (n.as_str(), p.as_path())), stored)
+    }
+
+    /// Absolute path for a stored `doc_path`.
+    pub fn resolve(&self, stored: &str) -> PathBuf {
+        let (root, rest) = self.root_of(stored);
+        resolve_doc_path(root.map(|(_, p)| p), rest)
+    }
+
+    /// `Some("offline media: <label>")` when the document's root is not
+    /// reachable right now. The label is the root name (or its directory name).
+    pub fn offline_label(&self, stored: &str) -> Option<String> {
+        let (Some((name, root)), _) = self.root_of(stored) else { return None };
+        if root.is_dir() { return None; }
+        let label = if name.is_empty() { root.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| root.display().to_string()) } else { name.to_string() };
+        Some(format!("offline media: {}", label))
+    }
+
+    /// Resolved path for open/export actions, or `None` if the document's
+    /// media is offline (or the file is otherwise gone).
+    pub fn openable(&self, stored: &str) -> Option<PathBuf> {
+        if self.offline_label(stored).is_some() { return None; }
+        Some(self.resolve(stored)).filter(|p| p.exists())
+    }
+}
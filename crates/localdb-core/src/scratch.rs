@@ -0,0 +1,208 @@
+//! Scratch collection: ad-hoc text searched alongside the index for one
+//! session (`localdb-cli scratch add -`), e.g. a document just received,
+//! without an ingest cycle.
+//!
+//! Added text is chunked like an ingested file under doc id `scratch/<n>`
+//! and, when the embedder is available, embedded once. Entries live in a
+//! small JSON-lines file (`search.scratch.path`) and are forgotten once
+//! `ttl` has passed since the last one was added. `with_passphrase` keeps
+//! that file encrypted whole (`crypt::seal_bytes`), as the indexes are under
+//! `security.encrypt_indexes`. At query time `collection`
+//! loads them into in-memory indexes (`MemoryText`, BM25; `MemoryVectors`,
+//! exact cosine) whose hits are merged with the index's.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::traits::{TextIndexer, VectorIndexer};
+use crate::types::{DocumentChunk, SearchHit, SourceKind};
+
+/// Doc id prefix of scratch documents.
+pub const SCRATCH_PREFIX: &str = "scratch/";
+
+/// BM25 term-frequency saturation and length normalization.
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+
+/// One added text, chunked (and embedded, if the model was available).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScratchEntry {
+    /// Where it came from (`stdin`, a file name or `--name`).
+    pub name: String,
+    pub added_at_ms: i64,
+    pub chunks: Vec<DocumentChunk>,
+    /// One per chunk; empty when added without the embedder.
+    #[serde(default)]
+    pub vectors: Vec<Vec<f32>>,
+}
+
+/// Whether `doc_id` is a scratch document's.
+pub fn is_scratch(doc_id: &str) -> bool { doc_id.starts_with(SCRATCH_PREFIX) }
+
+/// The session's scratch entries, stored as JSON lines at `path`.
+pub struct ScratchPad { path: PathBuf, ttl: Duration, passphrase: Option<String> }
+
+impl ScratchPad {
+    pub fn new(path: impl Into<PathBuf>, ttl: Duration) -> Self { Self { path: path.into(), ttl, passphrase: None } }
+
+    /// Keep the file encrypted with `passphrase` (`None`: plaintext); a
+    /// plaintext file is encrypted on the next add. Needs the `encryption`
+    /// feature.
+    pub fn with_passphrase(mut self, passphrase: Option<String>) -> Self {
+        self.passphrase = passphrase;
+        self
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+
+    /// Entries of the open session: none once `ttl` has passed since the
+    /// last add. A missing file is empty and malformed lines are skipped.
+    pub fn entries(&self, now_ms: i64) -> Result<Vec<ScratchEntry>> {
+        if !self.path.exists() { return Ok(Vec::new()); }
+        let entries: Vec<ScratchEntry> = self.read()?.lines().filter_map(|l| serde_json::from_str(l).ok()).collect();
+        let last = entries.iter().map(|e| e.added_at_ms).max().unwrap_or(i64::MIN);
+        Ok(if now_ms.saturating_sub(last) > self.ttl.as_millis() as i64 { Vec::new() } else { entries })
+    }
+
+    /// Doc id for the next entry (`scratch/<n>`).
+    pub fn next_doc_id(&self, now_ms: i64) -> Result<String> {
+        Ok(format!("{}{}", SCRATCH_PREFIX, self.entries(now_ms)?.len() + 1))
+    }
+
+    /// Append `entry` (added at `entry.added_at_ms`); an expired session is
+    /// cleared first.
+    pub fn add(&self, entry: &ScratchEntry) -> Result<()> {
+        if self.entries(entry.added_at_ms)?.is_empty() { self.clear()?; }
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) { fs::create_dir_all(dir)?; }
+        let line = serde_json::to_string(entry)?;
+        let Some(passphrase) = &self.passphrase else {
+            let mut f = fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+            writeln!(f, "{}", line)?;
+            return Ok(());
+        };
+        let mut text = if self.path.exists() { self.read()? } else { String::new() };
+        text.push_str(&line);
+        text.push('\n');
+        fs::write(&self.path, seal(text.as_bytes(), passphrase)?)?;
+        Ok(())
+    }
+
+    /// The file's text, decrypted if it was sealed.
+    fn read(&self) -> Result<String> {
+        let bytes = fs::read(&self.path)?;
+        Ok(String::from_utf8_lossy(&open(bytes, self.passphrase.as_deref(), &self.path)?).into_owned())
+    }
+
+    /// Forget every entry; returns whether there was anything to forget.
+    pub fn clear(&self) -> Result<bool> {
+        if !self.path.exists() { return Ok(false); }
+        fs::remove_file(&self.path)?;
+        Ok(true)
+    }
+}
+
+#[cfg(feature = "encryption")]
+fn seal(plain: &[u8], passphrase: &str) -> Result<Vec<u8>> { crate::crypt::seal_bytes(plain, passphrase) }
+
+#[cfg(not(feature = "encryption"))]
+fn seal(_plain: &[u8], _passphrase: &str) -> Result<Vec<u8>> {
+    bail!("an encrypted scratch pad needs localdb-core's `encryption` feature")
+}
+
+/// `bytes` read from `path`, decrypted with `passphrase` if they were sealed.
+#[cfg(feature = "encryption")]
+fn open(bytes: Vec<u8>, passphrase: Option<&str>, path: &Path) -> Result<Vec<u8>> {
+    if !crate::crypt::is_sealed_bytes(&bytes) { return Ok(bytes); }
+    let Some(passphrase) = passphrase else { bail!("{} is encrypted; it needs the index passphrase", path.display()) };
+    crate::crypt::open_bytes(&bytes, passphrase).map_err(|e| anyhow::anyhow!("{}: {:#}", path.display(), e))
+}
+
+#[cfg(not(feature = "encryption"))]
+fn open(bytes: Vec<u8>, _passphrase: Option<&str>, _path: &Path) -> Result<Vec<u8>> { Ok(bytes) }
+
+/// In-memory indexes over `entries`: every chunk is searchable as text, the
+/// chunks of entries added with the embedder by vector too.
+pub fn collection(entries: &[ScratchEntry]) -> (MemoryText, MemoryVectors) {
+    let (text, vectors) = (MemoryText::default(), MemoryVectors::default());
+    for e in entries {
+        let _ = text.index(&e.chunks);
+        if e.vectors.len() == e.chunks.len() { let _ = vectors.index(&e.chunks, &e.vectors); }
+    }
+    (text, vectors)
+}
+
+/// `TextIndexer` over chunks held in memory, ranked by BM25 over their
+/// lowercased alphanumeric terms.
+#[derive(Default)]
+pub struct MemoryText { chunks: RwLock<Vec<(DocumentChunk, Vec<String>)>> }
+
+fn terms(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|t| !t.is_empty()).map(str::to_lowercase).collect()
+}
+
+impl TextIndexer for MemoryText {
+    fn index(&self, chunks: &[DocumentChunk]) -> Result<()> {
+        let mut stored = self.chunks.write().unwrap_or_else(|e| e.into_inner());
+        stored.extend(chunks.iter().map(|c| (c.clone(), terms(&c.content))));
+        Ok(())
+    }
+
+    fn search(&self, query: &str, k: usize) -> Result<Vec<SearchHit>> {
+        let stored = self.chunks.read().unwrap_or_else(|e| e.into_inner());
+        let mut wanted = terms(query);
+        wanted.sort();
+        wanted.dedup();
+        let n = stored.len() as f32;
+        let avg_len = stored.iter().map(|(_, t)| t.len()).sum::<usize>() as f32 / n.max(1.0);
+        let idf: HashMap<&str, f32> = wanted.iter().map(|w| {
+            let df = stored.iter().filter(|(_, t)| t.contains(w)).count() as f32;
+            (w.as_str(), (1.0 + (n - df + 0.5) / (df + 0.5)).ln())
+        }).collect();
+        let mut hits: Vec<SearchHit> = stored.iter().filter_map(|(chunk, t)| {
+            let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * t.len() as f32 / avg_len.max(1.0));
+            let score: f32 = wanted.iter().map(|w| {
+                let tf = t.iter().filter(|x| *x == w).count() as f32;
+                idf[w.as_str()] * tf * (BM25_K1 + 1.0) / (tf + norm)
+            }).sum();
+            (score > 0.0).then(|| SearchHit::for_chunk(chunk, score, SourceKind::Text))
+        }).collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(k);
+        Ok(hits)
+    }
+}
+
+/// `VectorIndexer` over vectors held in memory, searched exhaustively by cosine.
+#[derive(Default)]
+pub struct MemoryVectors { rows: RwLock<Vec<(DocumentChunk, Vec<f32>)>> }
+
+impl VectorIndexer for MemoryVectors {
+    fn index(&self, chunks: &[DocumentChunk], embeddings: &[Vec<f32>]) -> Result<()> {
+        let mut rows = self.rows.write().unwrap_or_else(|e| e.into_inner());
+        rows.extend(chunks.iter().cloned().zip(embeddings.iter().cloned()));
+        Ok(())
+    }
+
+    fn search_vec(&self, query_vec: &[f32], k: usize) -> Result<Vec<SearchHit>> {
+        let rows = self.rows.read().unwrap_or_else(|e| e.into_inner());
+        // Vectors are L2-normalized, so the dot product is the cosine; rows
+        // from another model (another dimension) are skipped.
+        let mut hits: Vec<SearchHit> = rows.iter().filter(|(_, v)| v.len() == query_vec.len())
+            .map(|(chunk, v)| SearchHit::for_chunk(chunk, v.iter().zip(query_vec).map(|(a, b)| a * b).sum(), SourceKind::Vector))
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(k);
+        Ok(hits)
+    }
+
+    fn vectors(&self, ids: &[String]) -> Result<HashMap<String, Vec<f32>>> {
+        let rows = self.rows.read().unwrap_or_else(|e| e.into_inner());
+        Ok(rows.iter().filter(|(c, _)| ids.contains(&c.id)).map(|(c, v)| (c.id.clone(), v.clone())).collect())
+    }
+}
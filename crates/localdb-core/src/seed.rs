@@ -0,0 +1,78 @@
+//! Seeded randomness for reproducible tests, eval runs and sampling.
+//!
+//! Every randomized choice the workspace makes (fake embeddings, sampled
+//! spot checks, the blind left/right order of `localdb-cli judge`) draws from
+//! a `SeededRng` built from one explicit seed, so two runs with the same seed
+//! and data agree bit-for-bit across machines and toolchains. The seed comes
+//! from `APP_SEED` (or `seed`/`eval.seed` in config), default `DEFAULT_SEED`.
+//!
+//! Lance's IVF_PQ training samples with its own unseeded RNG; compare index
+//! builds by recall, not by bytes.
+
+use crate::config::Config;
+
+/// Seed used when none is configured.
+pub const DEFAULT_SEED: u64 = 0;
+
+/// `APP_SEED` (a top-level `seed` key), else `eval.seed`, else `DEFAULT_SEED`.
+pub fn from_config(config: &Config) -> u64 {
+    config.get::<u64>("seed").or_else(|_| config.get::<u64>("eval.seed")).unwrap_or(DEFAULT_SEED)
+}
+
+/// `APP_SEED` from the environment, for code without a `Config`.
+pub fn from_env() -> u64 {
+    std::env::var("APP_SEED").ok().and_then(|s| s.trim().parse().ok()).unwrap_or(DEFAULT_SEED)
+}
+
+/// SplitMix64: small, fast and fully specified, so streams never change with
+/// a dependency or compiler upgrade. Not for cryptography.
+#[derive(Debug, Clone)]
+pub struct SeededRng { state: u64 }
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self { Self { state: seed } }
+
+    /// Independent stream for `key` under `seed` (e.g. one per query), so
+    /// results don't depend on how many draws came before.
+    pub fn for_key(seed: u64, key: &str) -> Self {
+        let mut h = blake3::Hasher::new();
+        h.update(&seed.to_le_bytes());
+        h.update(key.as_bytes());
+        let mut first = [0u8; 8];
+        first.copy_from_slice(&h.finalize().as_bytes()[..8]);
+        Self::new(u64::from_le_bytes(first))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n` (`0` when `n == 0`).
+    pub fn below(&mut self, n: usize) -> usize {
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 { (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32 }
+
+    pub fn coin(&mut self) -> bool { self.next_u64() >> 63 == 1 }
+
+    /// Fisher–Yates shuffle.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() { items.swap(i, self.below(i + 1)); }
+    }
+
+    /// `k` distinct items (all when `k >= items.len()`), in their original order.
+    pub fn sample<T: Clone>(&mut self, items: &[T], k: usize) -> Vec<T> {
+        let mut idx: Vec<usize> = (0..items.len()).collect();
+        let k = k.min(idx.len());
+        for i in 0..k { let j = i + self.below(idx.len() - i); idx.swap(i, j); }
+        let mut picked = idx[..k].to_vec();
+        picked.sort_unstable();
+        picked.into_iter().map(|i| items[i].clone()).collect()
+    }
+}
@@ -0,0 +1,61 @@
+//! Source locations of chunks (`DocumentChunk::span`), so a hit can be shown
+//! or cited as the exact place in the original file.
+//!
+//! Chunking trims paragraphs, drops boilerplate lines and page breaks, and
+//! joins word windows with single spaces, so offsets are recovered after the
+//! fact: `locate` finds each chunk's words, in order and separated only by
+//! whitespace, in the text the file was decoded to, starting after the
+//! previous chunk's first word (windows overlap). The text is split into
+//! words once and each chunk is looked up by its rarest word, so a chunk
+//! that is not there costs only that word's occurrences, not a rescan. A
+//! chunk not found that way (its words straddle a stripped line, or a custom
+//! `Chunker` rewrote them) keeps no span.
+//!
+//! Offsets are bytes of that decoded text: the file's own bytes for UTF-8,
+//! but not for a file decoded from another encoding (see `charset`), where
+//! they only index the text, not the file.
+
+use std::collections::HashMap;
+
+use crate::types::{DocumentChunk, SourceSpan};
+
+/// Set the `span` of each of `chunks`, one document's in order, within `text`.
+pub fn locate(text: &str, chunks: &mut [DocumentChunk]) {
+    let breaks: Vec<usize> = text.match_indices('\n').map(|(i, _)| i).collect();
+    let line = |offset: usize| breaks.partition_point(|&b| b < offset) + 1;
+    let words = words(text);
+    let mut at: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, (_, w)) in words.iter().enumerate() { at.entry(w).or_default().push(i); }
+    let mut from = 0;
+    for chunk in chunks {
+        let Some((first, last)) = find_words(&words, &at, from, &chunk.content) else { chunk.span = None; continue };
+        let (start, end) = (words[first].0, words[last].0 + words[last].1.len());
+        chunk.span = Some(SourceSpan { start_offset: start, end_offset: end, start_line: line(start), end_line: line(end - 1) });
+        from = first + 1;
+    }
+}
+
+/// The whitespace-separated words of `text` with their byte offsets.
+fn words(text: &str) -> Vec<(usize, &str)> {
+    let mut out = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => { out.push((s, &text[s..i])); start = None; }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start { out.push((s, &text[s..])); }
+    out
+}
+
+/// Indexes into `words` of the first and last word of the first run of
+/// `content`'s words at or after word `from`; `at` lists where each word is.
+fn find_words(words: &[(usize, &str)], at: &HashMap<&str, Vec<usize>>, from: usize, content: &str) -> Option<(usize, usize)> {
+    let wanted: Vec<&str> = content.split_whitespace().collect();
+    let (k, seen) = wanted.iter().enumerate().map(|(k, w)| (k, at.get(w).map_or(&[][..], Vec::as_slice))).min_by_key(|(_, seen)| seen.len())?;
+    seen[seen.partition_point(|&p| p < from + k)..].iter().map(|&p| p - k)
+        .find(|&s| words.get(s..s + wanted.len()).is_some_and(|run| run.iter().map(|(_, w)| *w).eq(wanted.iter().copied())))
+        .map(|s| (s, s + wanted.len() - 1))
+}
@@ -0,0 +1,98 @@
+//! Extractive per-document summaries (TextRank, no model required).
+//!
+//! Sentences are graph nodes weighted by word overlap (normalized by the log
+//! of both lengths, as in the original TextRank); PageRank picks the most
+//! central ones, which are returned in document order. Computed once at ingest
+//! and stored in the catalog, so results can show what a document is about
+//! instead of the first characters of whichever chunk matched.
+
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// Sentences kept in a stored summary.
+pub const SUMMARY_SENTENCES: usize = 3;
+
+/// Only the first sentences are ranked; similarity is quadratic in their count.
+const MAX_SENTENCES: usize = 300;
+/// Sentences shorter than this (in words) are headings or fragments, not summary material.
+const MIN_WORDS: usize = 4;
+const DAMPING: f64 = 0.85;
+const ITERATIONS: usize = 50;
+const EPSILON: f64 = 1e-6;
+
+/// Up to `max_sentences` central sentences of `text`, in document order,
+/// joined by spaces. Empty when the text has no usable sentence.
+pub fn summarize(text: &str, max_sentences: usize) -> String {
+    let sentences: Vec<String> = split_sentences(text).into_iter().filter(|s| s.split_whitespace().count() >= MIN_WORDS).take(MAX_SENTENCES).collect();
+    if sentences.len() <= max_sentences { return sentences.join(" "); }
+    let words: Vec<HashSet<String>> = sentences.iter().map(|s| content_words(s)).collect();
+    let n = sentences.len();
+    let mut weights = vec![vec![0.0f64; n]; n];
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let w = similarity(&words[i], &words[j]);
+            weights[i][j] = w;
+            weights[j][i] = w;
+        }
+    }
+    let out_sums: Vec<f64> = weights.iter().map(|row| row.iter().sum()).collect();
+    let mut scores = vec![1.0f64; n];
+    for _ in 0..ITERATIONS {
+        let next: Vec<f64> = (0..n).map(|i| {
+            let inbound: f64 = (0..n).filter(|&j| out_sums[j] > 0.0).map(|j| weights[j][i] / out_sums[j] * scores[j]).sum();
+            (1.0 - DAMPING) + DAMPING * inbound
+        }).collect();
+        let delta: f64 = next.iter().zip(&scores).map(|(a, b)| (a - b).abs()).sum();
+        scores = next;
+        if delta < EPSILON { break; }
+    }
+    let mut ranked: Vec<usize> = (0..n).collect();
+    // Ties (e.g. no overlap at all) favour earlier sentences.
+    ranked.sort_by(|&a, &b| scores[b].partial_cmp(&scores[a]).unwrap_or(std::cmp::Ordering::Equal).then(a.cmp(&b)));
+    let mut picked: Vec<usize> = ranked.into_iter().take(max_sentences).collect();
+    picked.sort_unstable();
+    picked.iter().map(|&i| sentences[i].as_str()).collect::<Vec<_>>().join(" ")
+}
+
+/// Byte ranges of the sentences of `text`: split on `.`/`!`/`?` followed by
+/// whitespace, and on blank lines; surrounding whitespace is trimmed.
+pub fn sentence_spans(text: &str) -> Vec<Range<usize>> {
+    let mut out = Vec::new();
+    let mut push = |start: usize, end: usize| {
+        let s = &text[start..end];
+        let lead = s.len() - s.trim_start().len();
+        let trimmed = s.trim();
+        if !trimmed.is_empty() { out.push(start + lead..start + lead + trimmed.len()); }
+    };
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, n)| n);
+        if matches!(c, '.' | '!' | '?') && next.is_none_or(char::is_whitespace) {
+            push(start, i + c.len_utf8());
+            start = i + c.len_utf8();
+        } else if c == '\n' && next == Some('\n') {
+            push(start, i);
+            start = i;
+        }
+    }
+    push(start, text.len());
+    out
+}
+
+/// Sentences of `text` with inner whitespace collapsed.
+fn split_sentences(text: &str) -> Vec<String> {
+    sentence_spans(text).into_iter().map(|r| collapse(&text[r])).collect()
+}
+
+fn collapse(s: &str) -> String { s.split_whitespace().collect::<Vec<_>>().join(" ") }
+
+fn content_words(sentence: &str) -> HashSet<String> {
+    sentence.split(|c: char| !c.is_alphanumeric()).filter(|w| w.chars().count() > 2).map(str::to_lowercase).collect()
+}
+
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let shared = a.intersection(b).count() as f64;
+    let norm = (a.len() as f64).ln() + (b.len() as f64).ln();
+    if shared == 0.0 || norm <= 0.0 { 0.0 } else { shared / norm }
+}
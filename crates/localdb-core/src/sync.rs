@@ -0,0 +1,95 @@
+//! Differential sync planning between two deployments (`localdb-cli sync`).
+//!
+//! Each side describes what it has indexed as a `Manifest`: its data roots
+//! plus one `(doc_path, file_hash)` per cataloged file. `plan` reconciles two
+//! manifests by content hash — a file whose bytes already exist on the other
+//! side (under any path) is not shipped — and reports paths that hold
+//! different content on each side as conflicts instead of overwriting either.
+//! A peer's `doc_path` that is not a plain relative path (absolute, or with
+//! a `..` component) is rejected: it could name a file outside every root.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path};
+
+use crate::roots::RootMap;
+use crate::types::FileRecord;
+
+/// What one deployment has indexed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    pub roots: RootMap,
+    /// `(doc_path, file_hash)` per cataloged file.
+    pub files: Vec<(String, String)>,
+}
+
+impl Manifest {
+    pub fn from_records(roots: RootMap, records: &[FileRecord]) -> Self {
+        Self { roots, files: records.iter().map(|r| (r.doc_path.clone(), r.file_hash.clone())).collect() }
+    }
+
+    /// `root\t<name>\t<path>` and `file\t<hash>\t<doc_path>` lines (the
+    /// output of `localdb-cli manifest`).
+    pub fn encode(&self) -> String {
+        let roots = self.roots.0.iter().map(|(n, p)| format!("root\t{}\t{}", n, p.to_string_lossy()));
+        let files = self.files.iter().map(|(p, h)| format!("file\t{}\t{}", h, p));
+        roots.chain(files).collect::<Vec<_>>().join("\n")
+    }
+
+    /// Parse `encode` output; unknown lines (banners, log noise) are skipped.
+    pub fn decode(s: &str) -> Self {
+        let mut m = Self::default();
+        for line in s.lines() {
+            let mut parts = line.splitn(3, '\t');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("root"), Some(name), Some(path)) => m.roots.0.push((name.to_string(), path.into())),
+                (Some("file"), Some(hash), Some(path)) => m.files.push((path.to_string(), hash.to_string())),
+                _ => {}
+            }
+        }
+        m
+    }
+}
+
+/// Files to copy in each direction, with the paths that differ on both sides.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncPlan {
+    /// Remote `doc_path`s whose content is missing locally.
+    pub pull: Vec<String>,
+    /// Local `doc_path`s whose content is missing remotely.
+    pub push: Vec<String>,
+    /// Paths present on both sides with different content; left untouched.
+    pub conflicts: Vec<String>,
+    /// Remote `doc_path`s that are not plain relative paths; never pulled.
+    pub rejected: Vec<String>,
+}
+
+impl SyncPlan {
+    pub fn is_empty(&self) -> bool { self.pull.is_empty() && self.push.is_empty() && self.conflicts.is_empty() && self.rejected.is_empty() }
+}
+
+/// True when `doc_path` is relative and made of plain names only (no root,
+/// prefix, `.` or `..` components), so it resolves beneath its root.
+pub fn is_plain_relative(doc_path: &str) -> bool {
+    !doc_path.is_empty() && Path::new(doc_path).components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Content hashes listed in `m`.
+fn hashes(m: &Manifest) -> HashSet<&str> { m.files.iter().map(|(_, h)| h.as_str()).collect() }
+
+/// `doc_path` → content hash of every file in `m`.
+fn paths(m: &Manifest) -> HashMap<&str, &str> { m.files.iter().map(|(p, h)| (p.as_str(), h.as_str())).collect() }
+
+pub fn plan(local: &Manifest, remote: &Manifest) -> SyncPlan {
+    let (local_hashes, remote_hashes, local_paths, remote_paths) = (hashes(local), hashes(remote), paths(local), paths(remote));
+    let mut out = SyncPlan::default();
+    for (path, hash) in &remote.files {
+        if !is_plain_relative(path) { out.rejected.push(path.clone()); continue; }
+        if local_hashes.contains(hash.as_str()) { continue; }
+        if local_paths.contains_key(path.as_str()) { out.conflicts.push(path.clone()) } else { out.pull.push(path.clone()) }
+    }
+    for (path, hash) in &local.files {
+        if remote_hashes.contains(hash.as_str()) || remote_paths.contains_key(path.as_str()) { continue; }
+        out.push.push(path.clone());
+    }
+    out
+}
@@ -0,0 +1,71 @@
+//! Curated facet taxonomy (`data.taxonomy`, a `taxonomy.toml`).
+//!
+//! By default a document's facet is its directory relative to the data root.
+//! A taxonomy file maps directories to curated facets instead, so the browsing
+//! hierarchy no longer has to follow the on-disk layout:
+//!
+//! ```toml
+//! [facets]
+//! "downloads/usda_pdfs" = "/gardening/soil"
+//! "notes/2023" = "/journal"
+//! ```
+//!
+//! Keys are directories as stored in `doc_path` (relative to the data root,
+//! prefixed by the root name when several roots are configured). A mapping
+//! covers subdirectories too (`downloads/usda_pdfs/2019` →
+//! `/gardening/soil/2019`) and the most specific key wins. A mapped facet is
+//! used as is; the root's `facet_prefix` only applies to unmapped directories.
+
+use anyhow::{anyhow, Result};
+use figment::providers::{Format, Toml};
+use figment::Figment;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::config::{expand_path, Config};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Taxonomy {
+    /// Directory → facet.
+    #[serde(default)]
+    pub facets: BTreeMap<String, String>,
+}
+
+fn trim_dir(dir: &str) -> String {
+    dir.replace('\\', "/").trim_matches('/').to_string()
+}
+
+impl Taxonomy {
+    /// Parse a taxonomy file; a missing file is an error.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.is_file() { return Err(anyhow!("taxonomy file {} not found", path.display())); }
+        Figment::from(Toml::file(path)).extract().map_err(|e| anyhow!("invalid taxonomy {}: {}", path.display(), e))
+    }
+
+    /// The file named by `data.taxonomy`, or an empty taxonomy when unset.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        match config.get::<String>("data.taxonomy") {
+            Ok(p) if !p.trim().is_empty() => Self::load(&expand_path(p)),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool { self.facets.is_empty() }
+
+    /// Curated facet for documents in `dir`, if a mapping covers it.
+    pub fn facet_for(&self, dir: &str) -> Option<String> {
+        let dir = trim_dir(dir);
+        let (key, facet) = self.facets.iter()
+            .map(|(k, f)| (trim_dir(k), f))
+            .filter(|(k, _)| k.is_empty() || dir == *k || dir.starts_with(&format!("{}/", k)))
+            .max_by_key(|(k, _)| k.len())?;
+        let rest = if key.is_empty() { dir.as_str() } else { dir[key.len()..].trim_start_matches('/') };
+        let base = format!("/{}", facet.trim_matches('/'));
+        Some(match (base.as_str(), rest) {
+            (_, "") => base,
+            ("/", r) => format!("/{}", r),
+            (b, r) => format!("{}/{}", b, r),
+        })
+    }
+}
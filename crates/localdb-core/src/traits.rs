@@ -1,24 +1,149 @@
 //! Trait surfaces for pluggable engines and embedders.
 
-use crate::types::{DocumentChunk, SearchHit};
+use std::collections::HashMap;
+
+use crate::types::{ChunkSource, DocumentChunk, Embedded, Heads, MultiVector, SearchHit, SparseVector};
 
 /// Produces L2-normalized embedding vectors for input text.
 pub trait Embedder: Send + Sync {
     fn dim(&self) -> usize;
     fn max_len(&self) -> usize;
+    /// Embed passages (chunks, documents).
     fn embed_batch(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>>;
+    /// Embed search queries; differs from `embed_batch` for models trained
+    /// with separate query and passage prefixes (E5).
+    fn embed_queries(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> { self.embed_batch(texts) }
+    /// The model's sparse (lexical weight) output, if it has one.
+    fn sparse(&self) -> Option<&dyn SparseEmbedder> { None }
+    /// The model's token-level (ColBERT) output, if it has one.
+    fn multi_vector(&self) -> Option<&dyn MultiVectorEmbedder> { None }
+    /// `embed_batch` with the `heads` outputs the model has, from one forward
+    /// pass where it can; the default asks each output in turn.
+    fn embed_passages_with(&self, texts: &[String], heads: Heads) -> anyhow::Result<Embedded> {
+        Ok(Embedded {
+            sparse: match self.sparse() { Some(s) if heads.sparse => Some(s.embed_sparse(texts)?), _ => None },
+            tokens: match self.multi_vector() { Some(m) if heads.tokens => Some(m.embed_tokens(texts)?), _ => None },
+            dense: self.embed_batch(texts)?,
+        })
+    }
+    /// `embed_queries` with the `heads` outputs, as `embed_passages_with`.
+    fn embed_queries_with(&self, texts: &[String], heads: Heads) -> anyhow::Result<Embedded> {
+        Ok(Embedded {
+            sparse: match self.sparse() { Some(s) if heads.sparse => Some(s.embed_sparse(texts)?), _ => None },
+            tokens: match self.multi_vector() { Some(m) if heads.tokens => Some(m.embed_tokens(texts)?), _ => None },
+            dense: self.embed_queries(texts)?,
+        })
+    }
+}
+
+/// Weights the tokens of a text by how much they matter for retrieval
+/// (BGE-M3's sparse head), for lexical matching beside the dense vectors.
+pub trait SparseEmbedder: Send + Sync {
+    /// One `SparseVector` per text; special tokens are left out.
+    fn embed_sparse(&self, texts: &[String]) -> anyhow::Result<Vec<SparseVector>>;
+}
+
+/// One vector per token of a text (BGE-M3's ColBERT head), scored against a
+/// query's by MaxSim: each query token's best match, averaged.
+pub trait MultiVectorEmbedder: Send + Sync {
+    /// Width of each token vector.
+    fn token_dim(&self) -> usize;
+    /// One `MultiVector` per text; padding is left out.
+    fn embed_tokens(&self, texts: &[String]) -> anyhow::Result<Vec<MultiVector>>;
+}
+
+/// Counts tokens the way an embedder sees them, so chunks can be sized to fit
+/// its `max_len` instead of being truncated at embedding time.
+pub trait TokenCounter: Send + Sync {
+    /// Tokens in `text`, special tokens included.
+    fn count_tokens(&self, text: &str) -> usize;
+    /// Longest sequence the embedder accepts.
+    fn max_len(&self) -> usize;
+}
+
+/// Splits a document's text into chunks (`DataProcessor::with_chunker`; the
+/// default is `chunker::ParagraphChunker`).
+pub trait Chunker: Send + Sync {
+    /// Chunks of `content`, one section of `source` (a file, EPUB chapter,
+    /// ZIM article or JSON Lines record), in order. Only `content` matters:
+    /// ids, `doc_id`, `doc_path`, `chunk_index` and `total_chunks` are set
+    /// across the document afterwards; a `category` or `meta` set here is kept.
+    fn chunk(&self, content: &str, source: &ChunkSource) -> anyhow::Result<Vec<DocumentChunk>>;
 }
 
 /// Indexes and searches the text corpus (e.g., Tantivy/BM25).
 pub trait TextIndexer: Send + Sync {
     fn index(&self, chunks: &[DocumentChunk]) -> anyhow::Result<()>;
+    /// `index` with each chunk's sparse vector (`Embedder::sparse`), for
+    /// backends that match them; the default indexes the text alone.
+    fn index_sparse(&self, chunks: &[DocumentChunk], sparse: &[SparseVector]) -> anyhow::Result<()> { let _ = sparse; self.index(chunks) }
     fn search(&self, query: &str, k: usize) -> anyhow::Result<Vec<SearchHit>>;
+    /// `search` among chunks detected as language `lang` (ISO 639-1), all
+    /// chunks for `None`. Backends that cannot filter while searching drop
+    /// other languages from the top `k`.
+    fn search_in(&self, query: &str, k: usize, lang: Option<&str>) -> anyhow::Result<Vec<SearchHit>> {
+        let mut hits = self.search(query, k)?;
+        if let Some(lang) = lang { hits.retain(|h| h.in_lang(lang)); }
+        Ok(hits)
+    }
+    /// `search_in` among chunks filed under `facet` or its subfacets (`None`
+    /// searches all). Backends that cannot filter by facet find nothing under one.
+    fn search_in_facet(&self, query: &str, k: usize, lang: Option<&str>, facet: Option<&str>) -> anyhow::Result<Vec<SearchHit>> {
+        match facet {
+            None => self.search_in(query, k, lang),
+            Some(_) => Ok(Vec::new()),
+        }
+    }
+    /// `search_in_facet` also matching `sparse`, the query's sparse vector,
+    /// against the chunks' (see `index_sparse`); the default ignores it.
+    fn search_sparse(&self, query: &str, sparse: &SparseVector, k: usize, lang: Option<&str>, facet: Option<&str>) -> anyhow::Result<Vec<SearchHit>> {
+        let _ = sparse;
+        self.search_in_facet(query, k, lang, facet)
+    }
+    /// Browse mode for empty queries: the top `k` documents (newest first where
+    /// the backend knows), optionally restricted to `facet` and its subfacets.
+    /// Backends without a browse order return no hits.
+    fn browse(&self, facet: Option<&str>, k: usize) -> anyhow::Result<Vec<SearchHit>> { let _ = (facet, k); Ok(Vec::new()) }
+    /// Stored text of the chunks `ids` (chunks it does not hold are left out).
+    /// Backends that do not store text return none.
+    fn texts(&self, ids: &[String]) -> anyhow::Result<HashMap<String, String>> { let _ = ids; Ok(HashMap::new()) }
 }
 
 /// Indexes and searches vector embeddings (e.g., Lance IVF_PQ).
 pub trait VectorIndexer: Send + Sync {
     fn index(&self, chunks: &[DocumentChunk], embeddings: &[Vec<f32>]) -> anyhow::Result<()>;
     fn search_vec(&self, query_vec: &[f32], k: usize) -> anyhow::Result<Vec<SearchHit>>;
+    /// `search_vec` among chunks detected as language `lang`, as
+    /// `TextIndexer::search_in`.
+    fn search_vec_in(&self, query_vec: &[f32], k: usize, lang: Option<&str>) -> anyhow::Result<Vec<SearchHit>> {
+        let mut hits = self.search_vec(query_vec, k)?;
+        if let Some(lang) = lang { hits.retain(|h| h.in_lang(lang)); }
+        Ok(hits)
+    }
+    /// `search_vec_in` among chunks under `facet`, as
+    /// `TextIndexer::search_in_facet`.
+    fn search_vec_in_facet(&self, query_vec: &[f32], k: usize, lang: Option<&str>, facet: Option<&str>) -> anyhow::Result<Vec<SearchHit>> {
+        match facet {
+            None => self.search_vec_in(query_vec, k, lang),
+            Some(_) => Ok(Vec::new()),
+        }
+    }
+    /// Stored vectors of the chunks `ids` (chunks without one are left out).
+    /// Backends that cannot look vectors up return none.
+    fn vectors(&self, ids: &[String]) -> anyhow::Result<HashMap<String, Vec<f32>>> { let _ = ids; Ok(HashMap::new()) }
+    /// Store the token vectors of the chunks `ids`, replacing any they had.
+    /// Backends without a multi-vector store ignore them.
+    fn index_token_vectors(&self, ids: &[String], vectors: &[MultiVector]) -> anyhow::Result<()> { let _ = (ids, vectors); Ok(()) }
+    /// Stored token vectors of the chunks `ids` (chunks without any are left out).
+    fn token_vectors(&self, ids: &[String]) -> anyhow::Result<HashMap<String, MultiVector>> { let _ = ids; Ok(HashMap::new()) }
+}
+
+/// Scores how well passages answer a query by reading each together with it
+/// (a cross-encoder such as bge-reranker): more accurate than comparing
+/// vectors and far slower, so it reorders only the top hits.
+pub trait Reranker: Send + Sync {
+    /// One relevance score per passage, higher is better.
+    fn score(&self, query: &str, passages: &[String]) -> anyhow::Result<Vec<f32>>;
 }
 
 /// Façade for a combined engine that exposes a unified interface.
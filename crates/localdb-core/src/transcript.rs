@@ -0,0 +1,194 @@
+//! Whisper transcripts (`.vtt`/`.srt`): time-coded chunks of recorded audio.
+//!
+//! Whisper writes its transcript next to the recording (`meeting.mp3` →
+//! `meeting.vtt`). Consecutive cues of one speaker are merged into a segment
+//! while it fits the chunk size; each segment is one chunk whose `doc_path`
+//! carries its time range and speaker as a media fragment
+//! (`meeting.vtt#t=83.00,100.50&speaker=Alice`), so search hits can name the
+//! moment and `mpv_command` can jump to it. Speakers come from WebVTT voice
+//! tags (`<v Alice>`) or a diarization prefix (`[SPEAKER_00]:`, as whisperX
+//! writes); the speaker also leads the chunk text so it is searchable.
+//! Bracketed sound cues (`[Music]`, `[Applause]`) are not speakers, and a cue
+//! holding nothing else is dropped.
+
+use std::path::{Path, PathBuf};
+
+use crate::types::Meta;
+
+/// Recordings looked for next to a transcript, in this order.
+pub const AUDIO_EXTENSIONS: &[&str] = &["mp3", "m4a", "wav", "ogg", "opus", "flac", "webm", "mp4", "mkv"];
+
+/// `FileRecord::meta` key of the recording found next to a transcript
+/// (stored like `doc_path`).
+pub const MEDIA_KEY: &str = "media";
+
+/// One timed line of a transcript.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    /// Seconds from the start of the recording.
+    pub start: f64,
+    pub end: f64,
+    pub speaker: Option<String>,
+    pub text: String,
+}
+
+/// Time range and speaker of a chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Moment {
+    pub start: f64,
+    pub end: f64,
+    pub speaker: Option<String>,
+}
+
+/// Merged cues: one chunk's worth of one speaker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub moment: Moment,
+    /// `Speaker: text`, or just the text without a speaker.
+    pub content: String,
+}
+
+/// Whether `path` is a `.vtt` or `.srt` file.
+pub fn is_transcript(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("vtt") || e.eq_ignore_ascii_case("srt"))
+}
+
+/// Cues of a WebVTT or SRT file. Blocks without a `-->` timing line
+/// (`WEBVTT` header, `NOTE`, `STYLE`) and empty cues are skipped; markup
+/// tags are dropped.
+pub fn cues(content: &str) -> Vec<Cue> {
+    let content = content.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+    content.split("\n\n").filter_map(|block| {
+        let mut lines = block.lines().skip_while(|l| !l.contains("-->"));
+        let (start, end) = lines.next()?.split_once("-->")?;
+        let (start, end) = (parse_time(start)?, parse_time(end.split_whitespace().next()?)?);
+        let (speaker, text) = split_speaker(&lines.map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>().join(" "));
+        (!text.is_empty()).then_some(Cue { start, end, speaker, text })
+    }).collect()
+}
+
+/// `HH:MM:SS.mmm` or `MM:SS.mmm` (`,` before the milliseconds in SRT).
+fn parse_time(s: &str) -> Option<f64> {
+    let s = s.trim().replace(',', ".");
+    let mut secs = 0.0;
+    for part in s.split(':') { secs = secs * 60.0 + part.parse::<f64>().ok()?; }
+    Some(secs)
+}
+
+/// Speaker from a leading `<v Name>`, `[Name]:` or `[SPEAKER_00]`, and the
+/// text without markup. Any other leading `[...]` is a sound cue: it stays in
+/// the text, unless it is all the text, which is then empty.
+fn split_speaker(line: &str) -> (Option<String>, String) {
+    // `<v Alice>` or, with classes, `<v.loud Alice>`.
+    let mut speaker = line.strip_prefix("<v").filter(|r| r.starts_with([' ', '.'])).and_then(|r| r.split_once('>'))
+        .map(|(tag, _)| tag.trim_start_matches(|c: char| c != ' ').trim().to_string());
+    let mut text = strip_tags(line);
+    if speaker.is_none() {
+        if let Some((name, rest)) = text.strip_prefix('[').and_then(|r| r.split_once(']')) {
+            if rest.starts_with(':') || is_diarization_label(name.trim()) {
+                speaker = Some(name.trim().to_string());
+                text = rest.trim_start_matches(':').trim().to_string();
+            } else if rest.trim().is_empty() {
+                text.clear();
+            }
+        }
+    }
+    (speaker.filter(|s| !s.is_empty()), text.trim().to_string())
+}
+
+/// `SPEAKER_00` and the like, as diarization tools label voices.
+fn is_diarization_label(name: &str) -> bool {
+    name.strip_prefix("SPEAKER_").is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Text without `<...>` markup, with WebVTT's escapes decoded.
+fn strip_tags(line: &str) -> String {
+    let mut out = String::new();
+    let mut in_tag = false;
+    for c in line.chars() {
+        match c { '<' => in_tag = true, '>' if in_tag => in_tag = false, _ if !in_tag => out.push(c), _ => {} }
+    }
+    out.replace("&lt;", "<").replace("&gt;", ">").replace("&nbsp;", " ").replace("&amp;", "&")
+}
+
+/// Merge consecutive cues of the same speaker while `fits` accepts the
+/// merged content.
+pub fn segments(cues: &[Cue], fits: impl Fn(&str) -> bool) -> Vec<Segment> {
+    let mut out: Vec<Segment> = Vec::new();
+    for cue in cues {
+        if let Some(last) = out.last_mut().filter(|s| s.moment.speaker == cue.speaker) {
+            let content = format!("{} {}", last.content, cue.text);
+            if fits(&content) { last.content = content; last.moment.end = cue.end; continue; }
+        }
+        let content = match &cue.speaker { Some(s) => format!("{}: {}", s, cue.text), None => cue.text.clone() };
+        out.push(Segment { moment: Moment { start: cue.start, end: cue.end, speaker: cue.speaker.clone() }, content });
+    }
+    out
+}
+
+impl Moment {
+    /// `t=<start>,<end>` plus `&speaker=<name>` (escaped), for a `doc_path`.
+    pub fn fragment(&self) -> String {
+        let mut f = format!("t={:.2},{:.2}", self.start, self.end);
+        if let Some(s) = &self.speaker { f.push_str("&speaker="); f.push_str(&s.replace('%', "%25").replace('&', "%26").replace('#', "%23")); }
+        f
+    }
+
+    /// The moment in a chunk `doc_path`'s fragment; `None` for other chunks.
+    pub fn from_doc_path(doc_path: &str) -> Option<Self> {
+        let (_, fragment) = doc_path.rsplit_once('#')?;
+        let (mut range, mut speaker) = (None, None);
+        for param in fragment.split('&') {
+            match param.split_once('=') {
+                Some(("t", t)) => range = t.split_once(',').and_then(|(s, e)| Some((s.parse().ok()?, e.parse().ok()?))),
+                Some(("speaker", s)) => speaker = Some(s.replace("%23", "#").replace("%26", "&").replace("%25", "%")),
+                _ => {}
+            }
+        }
+        range.map(|(start, end)| Self { start, end, speaker })
+    }
+
+    /// The moment recorded in a chunk's (or search hit's) `meta` by
+    /// `to_meta`; `None` for other chunks.
+    pub fn from_meta(meta: &Meta) -> Option<Self> {
+        let (start, end) = (meta.get("start")?.parse().ok()?, meta.get("end")?.parse().ok()?);
+        Some(Self { start, end, speaker: meta.get("speaker").cloned() })
+    }
+
+    /// `start`, `end` (seconds) and `speaker`, for `DocumentChunk::meta`.
+    pub fn to_meta(&self) -> Meta {
+        let mut meta = Meta::new();
+        meta.insert("start".to_string(), format!("{:.2}", self.start));
+        meta.insert("end".to_string(), format!("{:.2}", self.end));
+        if let Some(s) = &self.speaker { meta.insert("speaker".to_string(), s.clone()); }
+        meta
+    }
+
+    /// `1:23–1:40 Alice`.
+    pub fn label(&self) -> String {
+        let range = format!("{}–{}", clock(self.start), clock(self.end));
+        match &self.speaker { Some(s) => format!("{} {}", range, s), None => range }
+    }
+}
+
+/// `m:ss`, or `h:mm:ss` from an hour on.
+pub fn clock(secs: f64) -> String {
+    let s = secs.max(0.0) as u64;
+    if s >= 3600 { format!("{}:{:02}:{:02}", s / 3600, s / 60 % 60, s % 60) } else { format!("{}:{:02}", s / 60, s % 60) }
+}
+
+/// The recording a transcript was made from: a sibling with the same stem
+/// and an `AUDIO_EXTENSIONS` extension (`talk.vtt` → `talk.mp3`), or the
+/// stem itself when the transcript kept the audio extension (`talk.mp3.vtt`).
+pub fn media_for(transcript: &Path) -> Option<PathBuf> {
+    let stem = transcript.with_extension("");
+    if stem.extension().and_then(|e| e.to_str()).is_some_and(|e| AUDIO_EXTENSIONS.iter().any(|a| a.eq_ignore_ascii_case(e))) && stem.is_file() {
+        return Some(stem);
+    }
+    AUDIO_EXTENSIONS.iter().map(|ext| stem.with_extension(ext)).find(|p| p.is_file())
+}
+
+/// Shell command that plays `media` from `start` seconds with mpv.
+pub fn mpv_command(media: &Path, start: f64) -> String {
+    format!("mpv --start={:.2} '{}'", start, media.to_string_lossy().replace('\'', r"'\''"))
+}
@@ -5,6 +5,29 @@ use std::collections::HashMap;
 
 pub type ChunkId = String;
 pub type Meta = HashMap<String, String>;
+/// Lexical weights of a text: `(token id, weight)` pairs in the embedding
+/// model's vocabulary, sorted by id, weights positive (see `SparseEmbedder`).
+pub type SparseVector = Vec<(u32, f32)>;
+/// Token-level vectors of a text, one L2-normalized vector per token (see
+/// `MultiVectorEmbedder`).
+pub type MultiVector = Vec<Vec<f32>>;
+
+/// Outputs besides the dense vectors to compute in the same forward pass
+/// (`Embedder::embed_passages_with`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Heads {
+    pub sparse: bool,
+    pub tokens: bool,
+}
+
+/// What one forward pass over a batch gave: a dense vector per text and,
+/// when asked for and the model has them, its sparse and token vectors.
+#[derive(Debug, Clone, Default)]
+pub struct Embedded {
+    pub dense: Vec<Vec<f32>>,
+    pub sparse: Option<Vec<SparseVector>>,
+    pub tokens: Option<Vec<MultiVector>>,
+}
 
 /// A chunk of a source document that is independently indexed.
 ///
@@ -14,7 +37,15 @@ pub type Meta = HashMap<String, String>;
 /// - `category`/`category_text`: hierarchical facet (e.g., "/topic/subtopic")
 /// - `content`: the text payload of the chunk
 /// - `chunk_index`/`total_chunks`: position within the parent document
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// - `title`/`author`: of the parent document, when its format records them (EPUB, ZIM)
+/// - `created_at`: file creation time (ms since the epoch; modification time where unknown)
+/// - `lang`: ISO 639-1 code of the chunk's language (`lang::detect`, else the
+///   folder's `language`); `None` when undecided
+/// - `meta`: free-form metadata: inherited folder metadata (`folder_meta`: tags,
+///   source, trust, language), CSV/JSON Lines fields, transcript times
+/// - `span`: where the chunk's text sits in its source file (`spans::locate`);
+///   `None` for formats whose text is extracted (EPUB, ZIM, CSV, ...)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DocumentChunk {
     pub id: ChunkId,
     pub doc_id: String,
@@ -24,6 +55,78 @@ pub struct DocumentChunk {
     pub content: String,
     pub chunk_index: usize,
     pub total_chunks: usize,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<i64>,
+    #[serde(default)]
+    pub lang: Option<String>,
+    #[serde(default)]
+    pub meta: Meta,
+    #[serde(default)]
+    pub span: Option<SourceSpan>,
+}
+
+/// Location of a chunk in its source file: byte offsets into the file's
+/// decoded text (its own bytes for UTF-8 files), end exclusive, and the
+/// 1-based lines of its first and last character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceSpan {
+    pub start_offset: usize,
+    pub end_offset: usize,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+impl SourceSpan {
+    /// `line 12` or `lines 12-18`, for display and citations.
+    pub fn lines(&self) -> String {
+        if self.start_line == self.end_line { format!("line {}", self.start_line) } else { format!("lines {}-{}", self.start_line, self.end_line) }
+    }
+}
+
+/// The document a `Chunker` is splitting.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkSource<'a> {
+    pub doc_id: &'a str,
+    /// Stored `doc_path` (`file#entry` for archive entries, ZIM articles, records).
+    pub doc_path: &'a str,
+    pub category: &'a str,
+}
+
+/// Catalog entry for one ingested source file.
+///
+/// - `doc_id`/`doc_path`/`category`: as on the file's chunks (`doc_path` root-relative)
+/// - `file_hash`: blake3 hex digest of the full file bytes at ingest
+/// - `size`: file length in bytes at ingest
+/// - `modified_at`: file modification time (ms since the epoch), for retention
+/// - `summary`: extractive summary (`summary::summarize`), shown with results
+/// - `meta`: inherited folder metadata, as on the file's chunks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileRecord {
+    pub doc_id: String,
+    pub doc_path: String,
+    pub category: String,
+    pub file_hash: String,
+    pub size: u64,
+    pub modified_at: i64,
+    pub summary: String,
+    #[serde(default)]
+    pub meta: Meta,
+}
+
+/// Per-leg multipliers applied when fusing text and vector hits (1.0 each
+/// by default); `localdb-cli tune` learns them from click feedback.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FusionWeights {
+    pub text: f32,
+    pub vector: f32,
+}
+
+impl Default for FusionWeights {
+    fn default() -> Self { Self { text: 1.0, vector: 1.0 } }
 }
 
 /// Indicates which engine produced a result.
@@ -36,10 +139,61 @@ pub enum SourceKind {
 /// The minimal surface returned by all engines.
 ///
 /// `id` matches `DocumentChunk::id`. `score` is engine-specific but
-/// higher is always better. `source` labels the origin engine.
+/// higher is always better. `source` labels the origin engine. The document
+/// metadata of the chunk (`title`, `author`, `created_at`, `lang`, `meta`) and
+/// its `span` come along when the engine stores them.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchHit {
     pub id: ChunkId,
     pub score: f32,
     pub source: SourceKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    #[serde(default, skip_serializing_if = "Meta::is_empty")]
+    pub meta: Meta,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<SourceSpan>,
+}
+
+impl SearchHit {
+    /// A hit without document metadata.
+    pub fn new(id: impl Into<ChunkId>, score: f32, source: SourceKind) -> Self {
+        Self { id: id.into(), score, source, title: None, author: None, created_at: None, lang: None, meta: Meta::new(), span: None }
+    }
+
+    /// A hit on `chunk`, carrying its document metadata and span.
+    pub fn for_chunk(chunk: &DocumentChunk, score: f32, source: SourceKind) -> Self {
+        Self { title: chunk.title.clone(), author: chunk.author.clone(), created_at: chunk.created_at, lang: chunk.lang.clone(), meta: chunk.meta.clone(), span: chunk.span, ..Self::new(chunk.id.clone(), score, source) }
+    }
+
+    /// Whether the chunk was detected as language `lang` (ISO 639-1).
+    pub fn in_lang(&self, lang: &str) -> bool { self.lang.as_deref().is_some_and(|l| l.eq_ignore_ascii_case(lang)) }
+
+    /// Missing document metadata (and span) taken from `other` (the same chunk from another leg).
+    pub fn fill_from(&mut self, other: &SearchHit) {
+        if self.title.is_none() { self.title = other.title.clone(); }
+        if self.author.is_none() { self.author = other.author.clone(); }
+        if self.created_at.is_none() { self.created_at = other.created_at; }
+        if self.lang.is_none() { self.lang = other.lang.clone(); }
+        if self.meta.is_empty() { self.meta = other.meta.clone(); }
+        if self.span.is_none() { self.span = other.span; }
+    }
+}
+
+/// The paths of the files a stored `doc_path` may belong to, longest first:
+/// the path itself, then each prefix ending before a `#` (fragments are
+/// `<file>#…`, and a file name may contain `#` itself).
+pub fn file_candidates(doc_path: &str) -> impl Iterator<Item = &str> {
+    std::iter::once(doc_path).chain(doc_path.rmatch_indices('#').map(move |(i, _)| &doc_path[..i]))
+}
+
+/// True when `doc_path` is the file at `file` or a fragment of it (`<file>#…`).
+pub fn is_in_file(doc_path: &str, file: &str) -> bool {
+    doc_path.strip_prefix(file).is_some_and(|rest| rest.is_empty() || rest.starts_with('#'))
 }
@@ -0,0 +1,143 @@
+//! Advisory writer locks on index directories.
+//!
+//! Tantivy and Lance let readers run beside a writer, but two processes
+//! writing one index (an `ingest` while `ingest --watch` reindexes, `gc`
+//! during `maintain`) interleave commits and can leave it corrupt. Writers
+//! therefore lock `<dir>.lock` next to each index directory — next to, so
+//! `ingest --full` deleting the directory or `lock` sealing it leaves the lock
+//! alone. The lock is an OS advisory lock on the file (`flock`, `LockFileEx`
+//! on Windows), which the OS releases when its process exits however it ends,
+//! so a crashed writer leaves nothing stale behind. The file also names its
+//! `Holder` (PID, command, start time), only for the message: a second writer
+//! fails with `Error::IndexBusy` saying who holds it, or, asked to wait, polls
+//! until it is free. Readers take no lock.
+
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::progress::now_ms;
+
+/// How often a waiting writer checks the lock again.
+pub const POLL: Duration = Duration::from_millis(500);
+
+/// The process holding a lock.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Holder {
+    pub pid: u32,
+    /// CLI command (`ingest`, `gc`, …).
+    pub command: String,
+    /// Milliseconds since the epoch.
+    pub since: i64,
+}
+
+impl fmt::Display for Holder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PID {} ({}) since {}", self.pid, self.command, utc_time(self.since))
+    }
+}
+
+/// Lock file of `index_dir`: `<dir>.lock` beside it.
+pub fn lock_path(index_dir: &Path) -> PathBuf {
+    match index_dir.file_name() {
+        Some(name) => index_dir.with_file_name(format!("{}.lock", name.to_string_lossy())),
+        None => index_dir.join(".localdb.lock"),
+    }
+}
+
+/// The holder of `index_dir`'s lock, if a process holds it.
+pub fn holder(index_dir: &Path) -> Option<Holder> {
+    let path = lock_path(index_dir);
+    let file = File::open(&path).ok()?;
+    if FileExt::try_lock_shared(&file).is_ok() { let _ = FileExt::unlock(&file); return None; }
+    Some(read_holder(&path).unwrap_or_else(unknown))
+}
+
+/// Writer locks on a set of index directories, released on drop.
+#[derive(Debug)]
+pub struct WriterLock { files: Vec<File> }
+
+impl WriterLock {
+    /// Lock each of `index_dirs` for `command`, in order. A directory locked
+    /// by another process fails with `Error::IndexBusy`, or with `wait` is
+    /// polled every `POLL` until free (`waiting` is told once per directory).
+    pub fn acquire(index_dirs: &[PathBuf], command: &str, wait: bool, mut waiting: impl FnMut(&Path, &Holder)) -> Result<Self> {
+        let mut lock = Self { files: Vec::new() };
+        let me = Holder { pid: std::process::id(), command: command.to_string(), since: now_ms() };
+        for dir in index_dirs {
+            let path = lock_path(dir);
+            let mut told = false;
+            loop {
+                match try_lock(&path, &me)? {
+                    Ok(file) => { lock.files.push(file); break; }
+                    Err(other) if !wait => {
+                        return Err(Error::IndexBusy(format!("{} is being written by {}; pass --wait to wait for it", dir.display(), other)));
+                    }
+                    Err(other) => {
+                        if !told { waiting(dir, &other); told = true; }
+                        std::thread::sleep(POLL);
+                    }
+                }
+            }
+        }
+        Ok(lock)
+    }
+}
+
+impl Drop for WriterLock {
+    fn drop(&mut self) {
+        // The file stays: removing it would let a writer lock a new file at
+        // the same path while another still holds the old one.
+        for file in &self.files {
+            let _ = file.set_len(0);
+            let _ = FileExt::unlock(file);
+        }
+    }
+}
+
+/// Lock `path` for `me` and record it there; the holder when another
+/// process has it.
+fn try_lock(path: &Path, me: &Holder) -> Result<std::result::Result<File, Holder>> {
+    let io = |e: std::io::Error| Error::Operation(format!("lock {}: {}", path.display(), e));
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) { fs::create_dir_all(dir).map_err(io)?; }
+    let mut file = fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path).map_err(io)?;
+    if let Err(e) = FileExt::try_lock_exclusive(&file) {
+        if e.kind() != fs2::lock_contended_error().kind() { return Err(io(e)); }
+        // Empty while the holder is still writing its name.
+        return Ok(Err(read_holder(path).unwrap_or_else(unknown)));
+    }
+    let json = serde_json::to_string(me).map_err(|e| Error::Operation(e.to_string()))?;
+    file.set_len(0).map_err(io)?;
+    file.rewind().map_err(io)?;
+    file.write_all(json.as_bytes()).map_err(io)?;
+    file.flush().map_err(io)?;
+    Ok(Ok(file))
+}
+
+fn read_holder(path: &Path) -> Option<Holder> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+fn unknown() -> Holder { Holder { pid: 0, command: "unknown".to_string(), since: now_ms() } }
+
+/// `YYYY-MM-DD HH:MM:SS UTC` for milliseconds since the epoch.
+fn utc_time(ms: i64) -> String {
+    let secs = ms.div_euclid(1000);
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)
+}
@@ -0,0 +1,249 @@
+//! Kiwix ZIM archive reader for ingest (offline Wikipedia and friends).
+//!
+//! A ZIM file is an 80-byte header, a MIME type list, directory entries
+//! reached through a URL-ordered pointer list, and clusters of blobs (the
+//! entry bodies) stored raw or compressed with xz or zstd. `ZimSource` seeks
+//! to entries and clusters on demand, so an archive of any size streams
+//! article by article with the last `CACHED_CLUSTERS` decompressed clusters
+//! in memory. A cluster larger than `MAX_CLUSTER_BYTES`, stored or
+//! decompressed, is an error rather than an allocation.
+//!
+//! Only HTML articles are yielded: namespace `A` in older archives, `C` in
+//! current ones. Redirects, images and metadata entries are skipped. Article
+//! bodies go through the same HTML stripping as EPUB chapters. At ingest the
+//! title becomes the doc id and the namespace the last facet segment
+//! (`/<dir>/<archive>/<namespace>`, see `article_facet`). Compressed
+//! clusters need the `zim` feature.
+
+use crate::data_processor::DataProcessor;
+use crate::epub::strip_html;
+use crate::types::DocumentChunk;
+use anyhow::{anyhow, bail, Context, Result};
+use std::fs::File;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const MAGIC: u32 = 72_173_914;
+const HEADER_LEN: usize = 80;
+/// MIME indices from here up mark redirects, link targets and deleted entries.
+const FIRST_SPECIAL_MIME: u16 = 0xfffd;
+/// Cluster info bit: blob offsets are 8 bytes instead of 4.
+const EXTENDED_CLUSTER: u8 = 0x10;
+/// Largest cluster read or decompressed; writers aim for about 2 MiB.
+pub const MAX_CLUSTER_BYTES: u64 = 64 << 20;
+/// Decompressed clusters kept, most recently used first.
+pub const CACHED_CLUSTERS: usize = 4;
+
+/// One HTML article as plain text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZimArticle {
+    pub namespace: char,
+    /// Path inside the archive (`Water_purification`).
+    pub url: String,
+    /// Display title; the url when the entry has none.
+    pub title: String,
+    /// Paragraphs separated by blank lines.
+    pub text: String,
+}
+
+/// Whether `path` looks like a ZIM archive by extension.
+pub fn is_zim(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("zim"))
+}
+
+/// Facet of an article: the archive's directory facet, then the archive name
+/// and the namespace (`survival` + `wikipedia_en_medicine` + `A` →
+/// `survival/wikipedia_en_medicine/A`).
+pub fn article_facet(category: &str, archive: &str, namespace: char) -> String {
+    if category.trim_matches('/').is_empty() { return format!("{}{}/{}", if category.starts_with('/') { "/" } else { "" }, archive, namespace); }
+    format!("{}/{}/{}", category.trim_end_matches('/'), archive, namespace)
+}
+
+/// Decompressed cluster: blob `i` is `data[offsets[i]..offsets[i + 1]]`.
+struct Cluster { data: Vec<u8>, offsets: Vec<usize> }
+
+/// An open ZIM archive.
+pub struct ZimSource {
+    file: BufReader<File>,
+    mime_types: Vec<String>,
+    entry_count: u32,
+    url_ptr_pos: u64,
+    cluster_ptrs: Vec<u64>,
+    /// `cluster_ptrs` sorted, to find where each cluster ends.
+    cluster_starts: Vec<u64>,
+    /// End of the last cluster (the checksum, or the end of the file).
+    clusters_end: u64,
+    /// Most recently used first.
+    cached: VecDeque<(u32, Cluster)>,
+}
+
+impl ZimSource {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut file = BufReader::new(File::open(path).with_context(|| format!("open {}", path.display()))?);
+        let mut h = [0u8; HEADER_LEN];
+        file.read_exact(&mut h).context("truncated ZIM header")?;
+        if le_u32(&h, 0) != MAGIC { bail!("{} is not a ZIM archive", path.display()); }
+        let (entry_count, cluster_count) = (le_u32(&h, 24), le_u32(&h, 28));
+        let (url_ptr_pos, cluster_ptr_pos, mime_list_pos, checksum_pos) = (le_u64(&h, 32), le_u64(&h, 48), le_u64(&h, 56), le_u64(&h, 72));
+
+        file.seek(SeekFrom::Start(mime_list_pos))?;
+        let mut mime_types = Vec::new();
+        loop {
+            let mime = read_zstr(&mut file).context("MIME type list")?;
+            if mime.is_empty() { break; }
+            mime_types.push(mime);
+        }
+        file.seek(SeekFrom::Start(cluster_ptr_pos))?;
+        let cluster_ptrs = (0..cluster_count).map(|_| read_u64(&mut file)).collect::<Result<Vec<_>>>().context("cluster pointer list")?;
+        let mut cluster_starts = cluster_ptrs.clone();
+        cluster_starts.sort_unstable();
+        let file_len = file.get_ref().metadata()?.len();
+        let clusters_end = if checksum_pos > 0 && checksum_pos <= file_len { checksum_pos } else { file_len };
+        Ok(Self { file, mime_types, entry_count, url_ptr_pos, cluster_ptrs, cluster_starts, clusters_end, cached: VecDeque::new() })
+    }
+
+    /// Directory entries of every kind (articles are a subset).
+    pub fn entry_count(&self) -> u32 { self.entry_count }
+
+    /// HTML articles in URL order; articles without text are skipped.
+    pub fn articles(&mut self) -> impl Iterator<Item = Result<ZimArticle>> + '_ {
+        let mut next = 0;
+        std::iter::from_fn(move || {
+            while next < self.entry_count {
+                let index = next;
+                next += 1;
+                match self.article(index).with_context(|| format!("ZIM entry {}", index)) {
+                    Ok(Some(a)) => return Some(Ok(a)),
+                    Ok(None) => continue,
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            None
+        })
+    }
+
+    /// Articles chunked as `DataProcessor` chunks text files: doc id = title,
+    /// category from `article_facet`, `doc_path` = `<doc_path>#<url>`. One
+    /// `Vec` per article. Callers merging several sources should dedupe doc ids.
+    pub fn chunks<'a>(&'a mut self, processor: &'a DataProcessor, category: &'a str, archive: &'a str, doc_path: &'a str) -> impl Iterator<Item = Result<Vec<DocumentChunk>>> + 'a {
+        self.articles().map(move |a| {
+            let a = a?;
+            processor.chunk_zim_article(&a, &a.title, category, archive, doc_path)
+        })
+    }
+
+    /// Entry `index` if it is an HTML article.
+    fn article(&mut self, index: u32) -> Result<Option<ZimArticle>> {
+        self.file.seek(SeekFrom::Start(self.url_ptr_pos + 8 * index as u64))?;
+        let ptr = read_u64(&mut self.file)?;
+        self.file.seek(SeekFrom::Start(ptr))?;
+        let mut head = [0u8; 8];
+        self.file.read_exact(&mut head)?;
+        let (mime, namespace) = (le_u16(&head, 0), head[3] as char);
+        if mime >= FIRST_SPECIAL_MIME || !matches!(namespace, 'A' | 'C') { return Ok(None); }
+        if !self.mime_types.get(mime as usize).is_some_and(|m| m.starts_with("text/html")) { return Ok(None); }
+        let mut loc = [0u8; 8];
+        self.file.read_exact(&mut loc)?;
+        let (cluster, blob) = (le_u32(&loc, 0), le_u32(&loc, 4));
+        let url = read_zstr(&mut self.file)?;
+        let title = read_zstr(&mut self.file)?;
+        let html = String::from_utf8_lossy(self.blob(cluster, blob)?).into_owned();
+        let text = strip_html(&html).text;
+        if text.is_empty() { return Ok(None); }
+        Ok(Some(ZimArticle { namespace, title: if title.is_empty() { url.clone() } else { title }, url, text }))
+    }
+
+    fn blob(&mut self, cluster: u32, blob: u32) -> Result<&[u8]> {
+        match self.cached.iter().position(|(i, _)| *i == cluster) {
+            Some(at) => { let hit = self.cached.remove(at).expect("position in range"); self.cached.push_front(hit); }
+            None => {
+                let loaded = self.load_cluster(cluster).with_context(|| format!("cluster {}", cluster))?;
+                self.cached.truncate(CACHED_CLUSTERS - 1);
+                self.cached.push_front((cluster, loaded));
+            }
+        }
+        let Some((_, c)) = self.cached.front() else { unreachable!("cluster cached above") };
+        let (start, end) = (c.offsets.get(blob as usize), c.offsets.get(blob as usize + 1));
+        match (start, end) {
+            (Some(&s), Some(&e)) if s <= e && e <= c.data.len() => Ok(&c.data[s..e]),
+            _ => Err(anyhow!("blob {} out of range in cluster {}", blob, cluster)),
+        }
+    }
+
+    fn load_cluster(&mut self, cluster: u32) -> Result<Cluster> {
+        let start = *self.cluster_ptrs.get(cluster as usize).ok_or_else(|| anyhow!("no such cluster"))?;
+        let end = self.cluster_starts.get(self.cluster_starts.partition_point(|&p| p <= start)).copied().unwrap_or(self.clusters_end);
+        if end <= start { bail!("cluster has no data"); }
+        if end - start > MAX_CLUSTER_BYTES { bail!("cluster of {} bytes is over the {} byte limit", end - start, MAX_CLUSTER_BYTES); }
+        self.file.seek(SeekFrom::Start(start))?;
+        let mut raw = Vec::new();
+        (&mut self.file).take(end - start).read_to_end(&mut raw)?;
+        if (raw.len() as u64) < end - start { bail!("truncated cluster"); }
+        let (info, body) = (raw[0], &raw[1..]);
+        let data = match info & 0x0f {
+            0 | 1 => body.to_vec(),
+            #[cfg(feature = "zim")]
+            4 => {
+                let mut out = Capped(Vec::new());
+                lzma_rs::xz_decompress(&mut &body[..], &mut out).map_err(|e| anyhow!("xz: {:?}", e))?;
+                out.0
+            }
+            #[cfg(feature = "zim")]
+            5 => {
+                let mut out = Vec::new();
+                ruzstd::StreamingDecoder::new(body).map_err(|e| anyhow!("zstd: {:?}", e))?.take(MAX_CLUSTER_BYTES + 1).read_to_end(&mut out)?;
+                if out.len() as u64 > MAX_CLUSTER_BYTES { bail!("cluster decompresses past {} bytes", MAX_CLUSTER_BYTES); }
+                out
+            }
+            #[cfg(not(feature = "zim"))]
+            4 | 5 => bail!("compressed ZIM cluster; built without the `zim` feature"),
+            other => bail!("unsupported cluster compression {}", other),
+        };
+        let width = if info & EXTENDED_CLUSTER != 0 { 8 } else { 4 };
+        let offset = |i: usize| -> Result<usize> {
+            let bytes = data.get(i * width..(i + 1) * width).ok_or_else(|| anyhow!("truncated blob offsets"))?;
+            Ok(if width == 8 { le_u64(bytes, 0) as usize } else { le_u32(bytes, 0) as usize })
+        };
+        let first = offset(0)?;
+        if first == 0 || first % width != 0 || first > data.len() { bail!("bad blob offset table"); }
+        let offsets = (0..first / width).map(offset).collect::<Result<Vec<_>>>()?;
+        Ok(Cluster { data, offsets })
+    }
+}
+
+/// A `Vec` sink that fails once more than `MAX_CLUSTER_BYTES` are written.
+#[cfg(feature = "zim")]
+struct Capped(Vec<u8>);
+
+#[cfg(feature = "zim")]
+impl std::io::Write for Capped {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if (self.0.len() + buf.len()) as u64 > MAX_CLUSTER_BYTES {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("cluster decompresses past {} bytes", MAX_CLUSTER_BYTES)));
+        }
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+}
+
+fn le_u16(b: &[u8], at: usize) -> u16 { u16::from_le_bytes([b[at], b[at + 1]]) }
+
+fn le_u32(b: &[u8], at: usize) -> u32 { u32::from_le_bytes(b[at..at + 4].try_into().expect("4 bytes")) }
+
+fn le_u64(b: &[u8], at: usize) -> u64 { u64::from_le_bytes(b[at..at + 8].try_into().expect("8 bytes")) }
+
+fn read_u64(r: &mut impl Read) -> Result<u64> {
+    let mut b = [0u8; 8];
+    r.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+/// A NUL-terminated string.
+fn read_zstr(r: &mut impl BufRead) -> Result<String> {
+    let mut bytes = Vec::new();
+    r.read_until(0, &mut bytes)?;
+    if bytes.pop() != Some(0) { bail!("unterminated string"); }
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
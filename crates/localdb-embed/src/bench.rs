@@ -0,0 +1,63 @@
+//! Embedder throughput self-benchmark (`localdb-cli bench-embed`).
+//!
+//! `measure` embeds the same texts at each of several batch sizes on the
+//! loaded device and reports chunks and tokens per second; `recommend` picks
+//! the batch size ingest should use (`embedding.batch_size`). Larger batches
+//! cost memory, so one is only preferred when it is clearly faster.
+
+use anyhow::Result;
+use std::time::{Duration, Instant};
+
+use localdb_core::seed::SeededRng;
+use localdb_core::traits::Embedder;
+
+/// Batch sizes tried by default.
+pub const BATCH_SIZES: [usize; 7] = [1, 4, 8, 16, 32, 64, 128];
+
+/// Share by which a larger batch must beat the best smaller one in chunks/s
+/// to be recommended.
+pub const MIN_GAIN: f64 = 0.05;
+
+/// Throughput at one batch size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Throughput {
+    pub batch_size: usize,
+    pub elapsed: Duration,
+    pub chunks_per_sec: f64,
+    pub tokens_per_sec: f64,
+}
+
+/// Embed all of `texts` (holding `tokens` tokens in total) once per batch
+/// size, after one warm-up batch each. Batch sizes above `texts.len()` are
+/// skipped.
+pub fn measure(embedder: &dyn Embedder, texts: &[String], tokens: usize, batch_sizes: &[usize]) -> Result<Vec<Throughput>> {
+    let mut out = Vec::new();
+    for &batch_size in batch_sizes.iter().filter(|&&b| b > 0 && b <= texts.len()) {
+        embedder.embed_batch(&texts[..batch_size])?;
+        let started = Instant::now();
+        for batch in texts.chunks(batch_size) { embedder.embed_batch(batch)?; }
+        let elapsed = started.elapsed();
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+        out.push(Throughput { batch_size, elapsed, chunks_per_sec: texts.len() as f64 / secs, tokens_per_sec: tokens as f64 / secs });
+    }
+    Ok(out)
+}
+
+/// The smallest batch size within `MIN_GAIN` of the fastest seen so far,
+/// walking `results` from small to large batches.
+pub fn recommend(results: &[Throughput]) -> Option<usize> {
+    let mut sorted: Vec<&Throughput> = results.iter().collect();
+    sorted.sort_by_key(|t| t.batch_size);
+    let mut best = *sorted.first()?;
+    for t in sorted { if t.chunks_per_sec > best.chunks_per_sec * (1.0 + MIN_GAIN) { best = t; } }
+    Some(best.batch_size)
+}
+
+/// `n` deterministic filler texts of `words` words each, for when there is
+/// no ingested text to benchmark on.
+pub fn sample_texts(n: usize, words: usize, seed: u64) -> Vec<String> {
+    const VOCABULARY: [&str; 24] = ["water", "filter", "seed", "soil", "compost", "jar", "boil", "minutes", "store", "cool", "dry", "place",
+        "harvest", "winter", "garden", "bucket", "gravel", "sand", "layer", "spring", "label", "date", "keep", "clean"];
+    let mut rng = SeededRng::new(seed);
+    (0..n).map(|_| (0..words.max(1)).map(|_| VOCABULARY[rng.below(VOCABULARY.len())]).collect::<Vec<_>>().join(" ")).collect()
+}
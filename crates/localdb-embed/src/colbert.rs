@@ -0,0 +1,55 @@
+//! BGE-M3's multi-vector (ColBERT) output.
+//!
+//! BGE-M3 also ships `colbert_linear`: one linear layer applied to the last
+//! hidden state of every token but the first (`<s>`). Each resulting vector is
+//! L2-normalized and padding is dropped, so a text becomes one vector per
+//! token; a query scores a text by MaxSim (each query token's best cosine
+//! against the text's tokens, averaged over the query). The head is read from
+//! `colbert_linear.safetensors`, else the Hugging Face repo's
+//! `colbert_linear.pt`; a model directory with neither has no multi-vector output.
+
+use anyhow::Result;
+use candle_core::{DType, Device, Module, Tensor};
+use candle_nn::{Linear, VarBuilder};
+use std::path::Path;
+
+use localdb_core::types::MultiVector;
+
+/// Files the ColBERT head is loaded from, in order of preference.
+pub const COLBERT_HEAD_FILES: [&str; 2] = ["colbert_linear.safetensors", "colbert_linear.pt"];
+
+/// The `colbert_linear` layer (`hidden` → `hidden`).
+pub struct ColbertHead { linear: Linear, dim: usize }
+
+impl ColbertHead {
+    /// The head in `model_dir` for a model of width `hidden`, if the directory has one.
+    pub fn load(model_dir: &Path, hidden: usize, device: &Device, dtype: DType) -> Result<Option<Self>> {
+        let Some(path) = COLBERT_HEAD_FILES.iter().map(|f| model_dir.join(f)).find(|p| p.is_file()) else { return Ok(None) };
+        let vb = if path.extension().is_some_and(|e| e == "pt") { VarBuilder::from_pth(&path, dtype, device)? }
+            // Safety: relying on safetensors metadata
+            else { unsafe { VarBuilder::from_mmaped_safetensors(&[path], dtype, device)? } };
+        let linear = candle_nn::linear(hidden, hidden, vb)?;
+        Ok(Some(Self { linear, dim: hidden }))
+    }
+
+    /// Width of the token vectors.
+    pub fn dim(&self) -> usize { self.dim }
+
+    /// Token vectors of a batch from its hidden states `[B, T, H]` and the
+    /// attention mask `[B, T]` they came from.
+    pub fn vectors(&self, hidden_states: &Tensor, attention_mask: &Tensor) -> Result<Vec<MultiVector>> {
+        let vectors = self.linear.forward(hidden_states)?.to_dtype(DType::F32)?.to_device(&Device::Cpu)?.to_vec3::<f32>()?;
+        let mask = attention_mask.to_device(&Device::Cpu)?.to_vec2::<i64>()?;
+        Ok(vectors.into_iter().zip(&mask).map(|(rows, mask)| token_rows(rows, mask)).collect())
+    }
+}
+
+/// The rows of one text after its first token, masked positions dropped,
+/// each L2-normalized.
+pub fn token_rows(rows: Vec<Vec<f32>>, mask: &[i64]) -> MultiVector {
+    rows.into_iter().zip(mask).skip(1).filter(|(_, m)| **m != 0).map(|(mut v, _)| {
+        let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt().max(1e-6);
+        for x in &mut v { *x /= norm; }
+        v
+    }).collect()
+}
@@ -1,10 +1,83 @@
+//! Compute device for the embedding model.
+//!
+//! By default the first accelerator compiled in (feature `cuda`, then
+//! `metal`) that is present is used, else the CPU. `APP_DEVICE` — or the
+//! application's preference (`prefer_device`, `embedding.device` in the CLI)
+//! when it is unset — picks one explicitly: `auto`, `cpu`, `metal`, `cuda`
+//! or `cuda:N`. An explicit device that is not available is an error rather
+//! than a silent fallback to the CPU.
+
+use anyhow::{anyhow, bail, Result};
 use candle_core::Device;
+use std::sync::Mutex;
+
+/// Which device to embed on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeviceChoice {
+    #[default]
+    Auto,
+    Cpu,
+    Metal,
+    /// CUDA device by ordinal.
+    Cuda(usize),
+}
 
-pub fn select_device() -> Device {
-    #[cfg(feature = "metal")]
-    {
-        if let Ok(dev) = Device::new_metal(0) { println!("🚀 Device: Metal (MPS)"); return dev; }
+impl DeviceChoice {
+    /// `auto`, `cpu`, `metal`, `cuda` (device 0) or `cuda:N`.
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "auto" => Ok(Self::Auto),
+            "cpu" => Ok(Self::Cpu),
+            "metal" | "mps" => Ok(Self::Metal),
+            "cuda" => Ok(Self::Cuda(0)),
+            other => match other.strip_prefix("cuda:").map(str::parse::<usize>) {
+                Some(Ok(n)) => Ok(Self::Cuda(n)),
+                _ => bail!("unknown device '{}': expected auto, cpu, metal, cuda or cuda:N", s),
+            },
+        }
     }
-    println!("🖥️  Device: CPU");
-    Device::Cpu
 }
+
+static PREFERRED: Mutex<DeviceChoice> = Mutex::new(DeviceChoice::Auto);
+
+/// Device to use when `APP_DEVICE` is unset (e.g. from the application's config).
+pub fn prefer_device(choice: DeviceChoice) {
+    *PREFERRED.lock().unwrap_or_else(|e| e.into_inner()) = choice;
+}
+
+/// `APP_DEVICE` if set, else the preference given to `prefer_device`.
+pub fn device_choice() -> Result<DeviceChoice> {
+    match std::env::var("APP_DEVICE") {
+        Ok(v) => DeviceChoice::parse(&v),
+        Err(_) => Ok(*PREFERRED.lock().unwrap_or_else(|e| e.into_inner())),
+    }
+}
+
+/// Open the device chosen by `device_choice`.
+pub fn select_device() -> Result<Device> { open_device(device_choice()?) }
+
+/// Open `choice`; `Auto` tries CUDA device 0, then Metal, then the CPU.
+pub fn open_device(choice: DeviceChoice) -> Result<Device> {
+    let device = match choice {
+        DeviceChoice::Cpu => Device::Cpu,
+        DeviceChoice::Metal => metal().ok_or_else(|| anyhow!("Metal requested but {}", if cfg!(feature = "metal") { "no Metal device is available" } else { "this build has no Metal support (feature `metal`)" }))?,
+        DeviceChoice::Cuda(n) => cuda(n).ok_or_else(|| anyhow!("CUDA device {} requested but {}", n, if cfg!(feature = "cuda") { "it is not available" } else { "this build has no CUDA support (feature `cuda`)" }))?,
+        DeviceChoice::Auto => cuda(0).or_else(metal).unwrap_or(Device::Cpu),
+    };
+    match &device {
+        Device::Cuda(_) => println!("🚀 Device: CUDA"),
+        Device::Metal(_) => println!("🚀 Device: Metal (MPS)"),
+        Device::Cpu => println!("🖥️  Device: CPU"),
+    }
+    Ok(device)
+}
+
+#[cfg(feature = "cuda")]
+fn cuda(n: usize) -> Option<Device> { Device::new_cuda(n).ok() }
+#[cfg(not(feature = "cuda"))]
+fn cuda(_: usize) -> Option<Device> { None }
+
+#[cfg(feature = "metal")]
+fn metal() -> Option<Device> { Device::new_metal(0).ok() }
+#[cfg(not(feature = "metal"))]
+fn metal() -> Option<Device> { None }
@@ -0,0 +1,58 @@
+//! Fingerprint of a model directory's files, for embedder provenance.
+//!
+//! Two directories with the same model name can hold different weights (a
+//! fine-tune, another quantization, a re-download of a newer revision); their
+//! vectors must not be mixed. `model_fingerprint` hashes the files that shape
+//! the dense vectors: `config.json` and `tokenizer.json` in full and, for
+//! weights larger than `FULL_HASH_LIMIT`, the size plus the first, middle and
+//! last `SAMPLE` bytes (the safetensors header and tensors from across the
+//! file) so startup does not read gigabytes.
+
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use twox_hash::XxHash64;
+
+/// Files whose contents determine the dense vectors.
+pub const FINGERPRINT_FILES: [&str; 3] = ["config.json", "tokenizer.json", "model.safetensors"];
+
+/// Files up to this size are hashed in full.
+pub const FULL_HASH_LIMIT: u64 = 64 << 20;
+
+/// Bytes hashed at each sampled offset of larger files.
+pub const SAMPLE: u64 = 1 << 20;
+
+/// 16 hex digits identifying the contents of `model_dir`'s `FINGERPRINT_FILES`
+/// (missing files count as absent, not as errors).
+pub fn model_fingerprint(model_dir: &Path) -> Result<String> {
+    let mut hasher = XxHash64::with_seed(0);
+    for name in FINGERPRINT_FILES {
+        let path = model_dir.join(name);
+        if !path.is_file() { continue; }
+        hasher.write(name.as_bytes());
+        hash_file(&path, &mut hasher).with_context(|| format!("fingerprint {}", path.display()))?;
+    }
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+fn hash_file(path: &Path, hasher: &mut XxHash64) -> Result<()> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    hasher.write_u64(size);
+    let ranges = if size <= FULL_HASH_LIMIT { vec![(0, size)] } else { vec![(0, SAMPLE), (size / 2 - SAMPLE / 2, SAMPLE), (size - SAMPLE, SAMPLE)] };
+    let mut buf = vec![0u8; 64 << 10];
+    for (start, len) in ranges {
+        file.seek(SeekFrom::Start(start))?;
+        let mut left = len;
+        while left > 0 {
+            let want = left.min(buf.len() as u64) as usize;
+            let n = file.read(&mut buf[..want])?;
+            if n == 0 { break; }
+            hasher.write(&buf[..n]);
+            left -= n as u64;
+        }
+    }
+    Ok(())
+}
@@ -1,42 +1,134 @@
+//! localdb-embed
+//!
+//! Local embedding providers backed by Candle/safetensors, plus a fake
+//! deterministic embedder for tests and development.
+//!
+//! - `BgeM3Embedder` loads XLM‑R models (BGE‑M3, multilingual E5) and
+//!   `BertEmbedder` BERT models (GTE) from `model.safetensors`
+//! - `sparse`: `BgeM3Embedder` also gives BGE‑M3's lexical weights
+//!   (`Embedder::sparse`) when the model directory has its `sparse_linear` head
+//! - `colbert`: likewise its token-level vectors (`Embedder::multi_vector`)
+//!   with the `colbert_linear` head
+//! - `pool`: masked mean, CLS or max pooling (`Pooling`), per model
+//! - `registry` maps model names (`embedding.model`) to their loader, dim and
+//!   max_len
+//! - `FakeEmbedder` is enabled by `APP_USE_FAKE_EMBEDDINGS=1`; its hash seed is
+//!   `APP_SEED` (default 0), see `localdb_core::seed`
+//! - `get_default_embedder()` picks fake vs real at runtime; `load_embedder`
+//!   loads a given model and settings
+//! - `default_token_counter()` loads the real model's tokenizer for chunking
+//! - `window`: `BgeM3Embedder` can embed texts past `max_len` as overlapping
+//!   windows pooled into one vector instead of truncating them
+//! - `matryoshka`: vectors of Matryoshka-trained models cut to their first
+//!   256/384/512 dims for smaller indexes (`prefer_output_dim`)
+//! - `fingerprint` identifies a model directory's weights for `embedder_id`s
+//! - `bench` measures throughput per batch size (`localdb-cli bench-embed`)
+
 use anyhow::{Result, anyhow};
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 use candle_core::{Device, Tensor, DType};
 use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config as BertConfig};
 use candle_transformers::models::xlm_roberta::{XLMRobertaModel, Config as XLMRobertaConfig};
 use tokenizers::Tokenizer;
 
-use localdb_core::traits::Embedder as CoreEmbedder;
+use localdb_core::error::Error as CoreError;
+use localdb_core::profile::{self, Stage};
+use localdb_core::traits::{Embedder as CoreEmbedder, MultiVectorEmbedder, SparseEmbedder};
+use localdb_core::types::{Embedded, Heads, MultiVector, SparseVector};
 
+pub mod bench;
+pub mod colbert;
 mod device;
+pub mod fingerprint;
+pub mod matryoshka;
 mod pool;
+pub mod registry;
+pub mod sparse;
 mod tokenize;
+pub mod window;
 
+pub use colbert::ColbertHead;
 pub use device::*;
+pub use fingerprint::model_fingerprint;
+pub use matryoshka::{prefer_output_dim, Truncated};
 pub use pool::*;
+pub use registry::{model_spec, prefer_max_len, prefer_model, prefer_pooling, register_model, ModelRegistry, ModelShape, ModelSpec};
+pub use sparse::SparseHead;
 pub use tokenize::*;
+pub use window::{prefer_sliding_window, sliding_window};
+
+/// Maximum sequence length of the default model (and the fake embedder), in tokens.
+pub const MAX_LEN: usize = 256;
 
-pub struct BgeM3Embedder { model: XLMRobertaModel, tokenizer: Tokenizer, device: Device, dtype: DType }
+/// XLM‑R family embedder (BGE‑M3, multilingual E5), with BGE‑M3's sparse
+/// and ColBERT heads when the model directory has them.
+pub struct BgeM3Embedder { model: XLMRobertaModel, tokenizer: Tokenizer, device: Device, dtype: DType, spec: ModelSpec, shape: ModelShape, sparse_head: Option<SparseHead>, colbert_head: Option<ColbertHead>, window_overlap: Option<usize> }
 
 impl BgeM3Embedder {
+    /// Load BGE-M3 from the model directory.
     pub fn new() -> Result<Self> {
-        let device = select_device();
-        let dtype = match &device { Device::Metal(_) => DType::F16, _ => DType::F32 };
-        println!("🔄 Loading BGE-M3 (XLM-R) from local files... device={:?} dtype={:?}", device, dtype);
-        let model_dir = resolve_model_dir()?;
-        let tokenizer_path = model_dir.join("tokenizer.json");
-        let tokenizer = Tokenizer::from_file(&tokenizer_path)
-            .map_err(|e| anyhow!("Failed to load tokenizer from {}: {}", tokenizer_path.display(), e))?;
-        let config_path = model_dir.join("config.json");
-        let config: XLMRobertaConfig = serde_json::from_str(&std::fs::read_to_string(&config_path)?)?;
-        // Safetensors only: fail fast if missing
-        let st = model_dir.join("model.safetensors");
-        if !st.exists() { return Err(anyhow!("{} not found", st.display())); }
-        // Safety: relying on safetensors metadata
-        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&[st.to_string_lossy().into_owned()], dtype, &device)? };
+        let spec = ModelRegistry::current().get(registry::DEFAULT_MODEL)?.clone();
+        let model_dir = resolve_model_dir(&spec)?;
+        Self::load(spec, &model_dir)
+    }
+
+    /// Load the XLM‑R model `spec` from `model_dir`.
+    pub fn load(spec: ModelSpec, model_dir: &Path) -> Result<Self> {
+        let (device, dtype) = device_and_dtype()?;
+        println!("🔄 Loading {} (XLM-R) from local files... device={:?} dtype={:?}", spec.name, device, dtype);
+        let shape = model_shape(&spec, model_dir)?;
+        let (tokenizer, config, vb) = model_files(model_dir, &device, dtype)?;
+        let config: XLMRobertaConfig = serde_json::from_str(&config)?;
         let model = XLMRobertaModel::new(&config, vb)?;
-        Ok(Self { model, tokenizer, device, dtype })
+        let sparse_head = SparseHead::load(model_dir, &tokenizer, shape.dim, &device, dtype)?;
+        let colbert_head = ColbertHead::load(model_dir, shape.dim, &device, dtype)?;
+        Ok(Self { model, tokenizer, device, dtype, spec, shape, sparse_head, colbert_head, window_overlap: window::sliding_window() })
+    }
+
+    /// Embed texts longer than `max_len` as windows `overlap` tokens apart
+    /// (see `window`); `None` truncates them. Defaults to `sliding_window()`.
+    pub fn with_sliding_window(mut self, overlap: Option<usize>) -> Self {
+        self.window_overlap = overlap;
+        self
+    }
+
+    /// `embed_all` for texts embedded window by window (see `window`): each
+    /// text's dense vector is pooled over its windows, its sparse weights
+    /// are their highest per token and its token vectors all of theirs.
+    /// Runs as many windows per forward pass as there are texts.
+    fn embed_windows(&self, texts: &[String], overlap: usize, sparse_head: Option<&SparseHead>, colbert_head: Option<&ColbertHead>) -> Result<Embedded> {
+        let enc = profile::time(Stage::Tokenize, || self.tokenizer.encode_batch(texts.to_vec(), true)).map_err(|e| anyhow!("Tokenization failed: {}", e))?;
+        let (mut rows, mut owners) = (Vec::new(), Vec::new());
+        for (i, e) in enc.iter().enumerate() {
+            for w in window::windows(&e.get_ids()[..real_len(e)], self.max_len(), overlap) { rows.push(w); owners.push(i); }
+        }
+        let (mut vectors, mut sparse, mut tokens) = (Vec::with_capacity(rows.len()), Vec::new(), Vec::new());
+        for batch in rows.chunks(texts.len().max(1)) {
+            let (input_ids, attention_mask) = pad_on_device(batch, pad_id(&self.tokenizer), &self.device)?;
+            let _forward = profile::timer(Stage::EmbedForward);
+            let token_type_ids = Tensor::zeros(attention_mask.dims(), DType::I64, &self.device)?;
+            let hidden_states = self.model.forward(&input_ids, &attention_mask, &token_type_ids, None, None, None)?;
+            vectors.extend(pooled_rows(&hidden_states, &attention_mask, self.spec.pooling, self.dim())?);
+            if let Some(head) = sparse_head { sparse.extend(head.weights(&hidden_states, &input_ids, &attention_mask)?); }
+            if let Some(head) = colbert_head { tokens.extend(head.vectors(&hidden_states, &attention_mask)?); }
+        }
+        let mut per_text: Vec<(Vec<Vec<f32>>, Vec<usize>)> = vec![(Vec::new(), Vec::new()); texts.len()];
+        let (mut sparse_per_text, mut tokens_per_text) = (vec![Vec::new(); texts.len()], vec![Vec::new(); texts.len()]);
+        let (mut sparse, mut tokens) = (sparse.into_iter(), tokens.into_iter());
+        for ((owner, row), v) in owners.into_iter().zip(&rows).zip(vectors) {
+            per_text[owner].0.push(v);
+            per_text[owner].1.push(row.len());
+            if let Some(s) = sparse.next() { sparse_per_text[owner].push(s); }
+            if let Some(t) = tokens.next() { tokens_per_text[owner].extend(t); }
+        }
+        Ok(Embedded {
+            dense: per_text.iter().map(|(v, w)| window::pool_windows(v, w)).collect(),
+            sparse: sparse_head.map(|_| sparse_per_text.iter().map(|s| window::merge_sparse(s)).collect()),
+            tokens: colbert_head.map(|_| tokens_per_text),
+        })
     }
 
     /// Embed a single string (debug / one-off calls). Prefer `embed_batch`.
@@ -46,9 +138,9 @@ impl BgeM3Embedder {
         let max_len = self.max_len();
         let (input_ids, attention_mask) = tokenize_on_device(&self.tokenizer, text, max_len, &self.device)?;
         // XLM‑R in candle-transformers expects a token_type_ids tensor; use zeros.
-        let token_type_ids = Tensor::zeros((1, max_len), DType::I64, &self.device)?;
+        let token_type_ids = Tensor::zeros(attention_mask.dims(), DType::I64, &self.device)?;
         let hidden_states = self.model.forward(&input_ids, &attention_mask, &token_type_ids, None, None, None)?;
-        let embedding = masked_mean_l2(&hidden_states, &attention_mask)?;
+        let embedding = self.spec.pooling.pool(&hidden_states, &attention_mask)?;
         let emb_cpu = embedding.to_device(&Device::Cpu)?.to_vec1()?;
         assert_eq!(emb_cpu.len(), self.dim());
         if start.elapsed().as_millis() > 100 { println!("⚠️  Slow embedding"); }
@@ -57,61 +149,248 @@ impl BgeM3Embedder {
 }
 
 impl CoreEmbedder for BgeM3Embedder {
-    /// Embedding dimension (D)
-    fn dim(&self) -> usize { 1024 }
-    /// Maximum sequence length accepted by the model tokenizer
-    fn max_len(&self) -> usize { 256 }
-    /// Compute embeddings for a batch of texts on the configured device.
-    fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
-        use crate::tokenize::tokenize_batch_on_device;
-        let max_len = self.max_len();
-        let (input_ids, attention_mask) = tokenize_batch_on_device(&self.tokenizer, texts, max_len, &self.device, self.dtype)?;
+    /// Embedding dimension (D), `hidden_size` in `config.json`
+    fn dim(&self) -> usize { self.shape.dim }
+    /// Tokens embedded per text (see `ModelSpec::shape`)
+    fn max_len(&self) -> usize { self.shape.max_len }
+    /// Compute embeddings for a batch of passages on the configured device.
+    fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> { Ok(self.embed_all(texts, self.spec.passage_prefix, Heads::default())?.dense) }
+    fn embed_queries(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> { Ok(self.embed_all(texts, self.spec.query_prefix, Heads::default())?.dense) }
+    fn sparse(&self) -> Option<&dyn SparseEmbedder> { self.sparse_head.as_ref().map(|_| self as &dyn SparseEmbedder) }
+    fn multi_vector(&self) -> Option<&dyn MultiVectorEmbedder> { self.colbert_head.as_ref().map(|_| self as &dyn MultiVectorEmbedder) }
+    /// One forward pass for the dense vectors and both heads.
+    fn embed_passages_with(&self, texts: &[String], heads: Heads) -> Result<Embedded> { self.embed_all(texts, self.spec.passage_prefix, heads) }
+    fn embed_queries_with(&self, texts: &[String], heads: Heads) -> Result<Embedded> { self.embed_all(texts, self.spec.query_prefix, heads) }
+}
+
+impl BgeM3Embedder {
+    /// Dense vectors of `texts`, each with `prefix` prepended, and the
+    /// `heads` outputs the model has, all from the same hidden states.
+    fn embed_all(&self, texts: &[String], prefix: &str, heads: Heads) -> Result<Embedded> {
+        let texts = with_prefix(prefix, texts);
+        let sparse_head = self.sparse_head.as_ref().filter(|_| heads.sparse);
+        let colbert_head = self.colbert_head.as_ref().filter(|_| heads.tokens);
+        if let Some(overlap) = self.window_overlap { return self.embed_windows(&texts, overlap, sparse_head, colbert_head); }
+        let (input_ids, attention_mask) = profile::time(Stage::Tokenize, || tokenize_batch_on_device(&self.tokenizer, &texts, self.max_len(), &self.device, self.dtype))?;
+        let _forward = profile::timer(Stage::EmbedForward);
         let token_type_ids = Tensor::zeros(attention_mask.dims(), DType::I64, &self.device)?;
         let hidden_states = self.model.forward(&input_ids, &attention_mask, &token_type_ids, None, None, None)?;
-        let embedding = masked_mean_l2(&hidden_states, &attention_mask)?;
-        let cpu = embedding.to_device(&Device::Cpu)?;
-        let v = cpu.to_vec2::<f32>()?;
-        if !v.is_empty() { assert_eq!(v[0].len(), self.dim()); }
-        Ok(v)
+        Ok(Embedded {
+            dense: pooled_rows(&hidden_states, &attention_mask, self.spec.pooling, self.dim())?,
+            sparse: sparse_head.map(|h| h.weights(&hidden_states, &input_ids, &attention_mask)).transpose()?,
+            tokens: colbert_head.map(|h| h.vectors(&hidden_states, &attention_mask)).transpose()?,
+        })
+    }
+}
+
+impl SparseEmbedder for BgeM3Embedder {
+    /// Lexical weights of passages (see `sparse`); `embed_passages_with`
+    /// gives them with the dense vectors instead of another forward pass.
+    fn embed_sparse(&self, texts: &[String]) -> Result<Vec<SparseVector>> {
+        let missing = || anyhow!("{} has no sparse head ({} not found)", self.spec.name, sparse::SPARSE_HEAD_FILES.join(" or "));
+        self.embed_all(texts, self.spec.passage_prefix, Heads { sparse: true, tokens: false })?.sparse.ok_or_else(missing)
+    }
+}
+
+impl MultiVectorEmbedder for BgeM3Embedder {
+    fn token_dim(&self) -> usize { self.colbert_head.as_ref().map_or(self.dim(), ColbertHead::dim) }
+    /// Token vectors of passages (see `colbert`), as `embed_sparse`.
+    fn embed_tokens(&self, texts: &[String]) -> Result<Vec<MultiVector>> {
+        let missing = || anyhow!("{} has no ColBERT head ({} not found)", self.spec.name, colbert::COLBERT_HEAD_FILES.join(" or "));
+        self.embed_all(texts, self.spec.passage_prefix, Heads { sparse: false, tokens: true })?.tokens.ok_or_else(missing)
+    }
+}
+
+/// BERT family embedder (GTE).
+pub struct BertEmbedder { model: BertModel, tokenizer: Tokenizer, device: Device, dtype: DType, spec: ModelSpec, shape: ModelShape }
+
+impl BertEmbedder {
+    /// Load the BERT model `spec` from `model_dir`.
+    pub fn load(spec: ModelSpec, model_dir: &Path) -> Result<Self> {
+        let (device, dtype) = device_and_dtype()?;
+        println!("🔄 Loading {} (BERT) from local files... device={:?} dtype={:?}", spec.name, device, dtype);
+        let shape = model_shape(&spec, model_dir)?;
+        let (tokenizer, config, vb) = model_files(model_dir, &device, dtype)?;
+        let config: BertConfig = serde_json::from_str(&config)?;
+        let model = BertModel::load(vb, &config)?;
+        Ok(Self { model, tokenizer, device, dtype, spec, shape })
+    }
+}
+
+impl CoreEmbedder for BertEmbedder {
+    fn dim(&self) -> usize { self.shape.dim }
+    fn max_len(&self) -> usize { self.shape.max_len }
+    fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> { self.embed_prefixed(texts, self.spec.passage_prefix) }
+    fn embed_queries(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> { self.embed_prefixed(texts, self.spec.query_prefix) }
+}
+
+impl BertEmbedder {
+    /// Dense vectors of `texts`, each with `prefix` prepended.
+    fn embed_prefixed(&self, texts: &[String], prefix: &str) -> Result<Vec<Vec<f32>>> {
+        let texts = with_prefix(prefix, texts);
+        let (input_ids, attention_mask) = profile::time(Stage::Tokenize, || tokenize_batch_on_device(&self.tokenizer, &texts, self.max_len(), &self.device, self.dtype))?;
+        let _forward = profile::timer(Stage::EmbedForward);
+        let token_type_ids = Tensor::zeros(attention_mask.dims(), DType::I64, &self.device)?;
+        let hidden_states = self.model.forward(&input_ids, &token_type_ids, Some(&attention_mask))?;
+        pooled_rows(&hidden_states, &attention_mask, self.spec.pooling, self.dim())
     }
 }
 
+/// The selected device, with FP16 on accelerators and FP32 on the CPU.
+fn device_and_dtype() -> Result<(Device, DType)> {
+    let device = select_device()?;
+    let dtype = match &device { Device::Metal(_) | Device::Cuda(_) => DType::F16, Device::Cpu => DType::F32 };
+    Ok((device, dtype))
+}
+
+/// `spec`'s shape as the files in `model_dir` give it (see `ModelSpec::shape`).
+pub fn model_shape(spec: &ModelSpec, model_dir: &Path) -> Result<ModelShape> {
+    let config_path = model_dir.join("config.json");
+    let config = std::fs::read_to_string(&config_path).map_err(|e| anyhow!("Failed to read {}: {}", config_path.display(), e))?;
+    // Only the truncation settings are needed, not a whole tokenizer.
+    let tokenizer: serde_json::Value = std::fs::read_to_string(model_dir.join("tokenizer.json")).ok().and_then(|t| serde_json::from_str(&t).ok()).unwrap_or_default();
+    let truncation = tokenizer.pointer("/truncation/max_length").and_then(serde_json::Value::as_u64).map(|v| v as usize);
+    spec.shape(&config, truncation)
+}
+
+/// Tokenizer, `config.json` text and mapped weights of a model directory.
+fn model_files(model_dir: &Path, device: &Device, dtype: DType) -> Result<(Tokenizer, String, VarBuilder<'static>)> {
+    let tokenizer_path = model_dir.join("tokenizer.json");
+    let tokenizer = Tokenizer::from_file(&tokenizer_path)
+        .map_err(|e| anyhow!("Failed to load tokenizer from {}: {}", tokenizer_path.display(), e))?;
+    let config = std::fs::read_to_string(model_dir.join("config.json"))?;
+    // Safetensors only: fail fast if missing
+    let st = model_dir.join("model.safetensors");
+    if !st.exists() { return Err(CoreError::EmbedderUnavailable(format!("{} not found", st.display())).into()); }
+    // Safety: relying on safetensors metadata
+    let vb = unsafe { VarBuilder::from_mmaped_safetensors(&[st.to_string_lossy().into_owned()], dtype, device)? };
+    Ok((tokenizer, config, vb))
+}
+
+/// `texts` with `prefix` (a model's passage or query prefix), borrowed when
+/// it is empty.
+fn with_prefix<'a>(prefix: &str, texts: &'a [String]) -> std::borrow::Cow<'a, [String]> {
+    if prefix.is_empty() { return std::borrow::Cow::Borrowed(texts); }
+    std::borrow::Cow::Owned(texts.iter().map(|t| format!("{}{}", prefix, t)).collect())
+}
+
+/// Rows of `hidden_states` pooled by `pooling` and L2-normalized, on the CPU.
+fn pooled_rows(hidden_states: &Tensor, attention_mask: &Tensor, pooling: Pooling, dim: usize) -> Result<Vec<Vec<f32>>> {
+    let embedding = pooling.pool(hidden_states, attention_mask)?;
+    let v = embedding.to_device(&Device::Cpu)?.to_vec2::<f32>()?;
+    if let Some(row) = v.first() { assert_eq!(row.len(), dim); }
+    Ok(v)
+}
+
+/// The model chosen by `model_spec` (see `registry`), or the fake embedder
+/// at its dimension when `APP_USE_FAKE_EMBEDDINGS=1`, truncated to the
+/// width given to `prefer_output_dim` (see `matryoshka`).
 pub fn get_default_embedder() -> Result<Box<dyn CoreEmbedder>> {
+    let spec = model_spec()?;
+    if let Some(seed) = fake_embedding_seed() { println!("🧪 Using FakeEmbedder (seed {})", seed); return matryoshka::with_output_dim(&spec, Box::new(FakeEmbedder::new(spec.dim, seed))); }
+    matryoshka::with_output_dim(&spec, spec.load(&resolve_model_dir(&spec)?)?)
+}
+
+/// `spec` loaded as `get_default_embedder` loads the chosen model, but with
+/// sliding windows `overlap` tokens apart (XLM‑R only) and vectors of `dim`
+/// components instead of the process-wide preferences: another index
+/// generation's embedder (see `embed_provider::local::recorded_embedder` in
+/// `localdb-vector`).
+pub fn load_embedder(spec: &ModelSpec, overlap: Option<usize>, dim: usize) -> Result<Box<dyn CoreEmbedder>> {
+    let model: Box<dyn CoreEmbedder> = match fake_embedding_seed() {
+        Some(seed) => Box::new(FakeEmbedder::new(spec.dim, seed)),
+        None => {
+            let model_dir = resolve_model_dir(spec)?;
+            match spec.architecture {
+                registry::Architecture::XlmRoberta => Box::new(BgeM3Embedder::load(spec.clone(), &model_dir)?.with_sliding_window(overlap)),
+                registry::Architecture::Bert => spec.load(&model_dir)?,
+            }
+        }
+    };
+    if dim == model.dim() { Ok(model) } else { Ok(Box::new(matryoshka::Truncated::new(model, dim)?)) }
+}
+
+/// Tokenizer of the real model for sizing chunks, or `None` with the fake
+/// embedder or when no model directory is found (chunking then estimates).
+/// With sliding windows an XLM‑R model takes texts of any length, so its
+/// `max_len` no longer caps chunks.
+pub fn default_token_counter() -> Result<Option<ModelTokenCounter>> {
+    if fake_embedding_seed().is_some() { return Ok(None); }
+    let spec = model_spec()?;
+    let windowed = sliding_window().is_some() && spec.architecture == registry::Architecture::XlmRoberta;
+    match resolve_model_dir(&spec) {
+        Ok(dir) => Ok(Some(ModelTokenCounter::from_model_dir(&dir, if windowed { usize::MAX } else { model_shape(&spec, &dir)?.max_len })?)),
+        Err(e) if is_embedder_unavailable(&e) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Seed of the fake embedder when `APP_USE_FAKE_EMBEDDINGS=1`, else `None`.
+pub fn fake_embedding_seed() -> Option<u64> {
     let use_fake = std::env::var("APP_USE_FAKE_EMBEDDINGS").ok().map(|v| v == "1" || v.eq_ignore_ascii_case("true")).unwrap_or(false);
-    if use_fake { println!("🧪 Using FakeEmbedder"); return Ok(Box::new(FakeEmbedder::new(1024))); }
-    Ok(Box::new(BgeM3Embedder::new()?))
+    use_fake.then(localdb_core::seed::from_env)
 }
 
-struct FakeEmbedder { dim: usize }
-impl FakeEmbedder { fn new(dim: usize) -> Self { Self { dim } } }
+struct FakeEmbedder { dim: usize, seed: u64 }
+impl FakeEmbedder { fn new(dim: usize, seed: u64) -> Self { Self { dim, seed } } }
 impl CoreEmbedder for FakeEmbedder {
     fn dim(&self) -> usize { self.dim }
-    fn max_len(&self) -> usize { 256 }
+    fn max_len(&self) -> usize { MAX_LEN }
     fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
         use std::hash::{Hash, Hasher}; use twox_hash::XxHash64;
+        let _forward = profile::timer(Stage::EmbedForward);
         let mut result = Vec::with_capacity(texts.len());
         for text in texts {
             let mut v = vec![0f32; self.dim];
-            for (i, token) in text.split_whitespace().enumerate() { let mut hasher = XxHash64::with_seed(0); token.hash(&mut hasher); let h = hasher.finish(); let idx = (h as usize) % self.dim; let val = (((h >> 32) as u32) as f32) / (u32::MAX as f32); v[idx] += val + (i as f32 % 3.0) * 0.01; }
+            for (i, token) in text.split_whitespace().enumerate() { let mut hasher = XxHash64::with_seed(self.seed); token.hash(&mut hasher); let h = hasher.finish(); let idx = (h as usize) % self.dim; let val = (((h >> 32) as u32) as f32) / (u32::MAX as f32); v[idx] += val + (i as f32 % 3.0) * 0.01; }
             let norm = (v.iter().map(|x| x * x).sum::<f32>()).sqrt().max(1e-6); for x in &mut v { *x /= norm; }
             result.push(v);
         }
         Ok(result)
     }
+    fn sparse(&self) -> Option<&dyn SparseEmbedder> { Some(self) }
+    fn multi_vector(&self) -> Option<&dyn MultiVectorEmbedder> { Some(self) }
 }
 
-fn resolve_model_dir() -> Result<PathBuf> {
+impl SparseEmbedder for FakeEmbedder {
+    /// Each distinct lowercase word, hashed to a token id, weighted by its
+    /// length (up to 10 characters) / 10.
+    fn embed_sparse(&self, texts: &[String]) -> Result<Vec<SparseVector>> {
+        use std::hash::{Hash, Hasher}; use twox_hash::XxHash64;
+        Ok(texts.iter().map(|text| {
+            let mut v: SparseVector = text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).map(|w| {
+                let w = w.to_lowercase();
+                let mut hasher = XxHash64::with_seed(self.seed); w.hash(&mut hasher);
+                (hasher.finish() as u32, w.chars().count().min(10) as f32 / 10.0)
+            }).collect();
+            v.sort_by_key(|(id, _)| *id);
+            v.dedup_by_key(|(id, _)| *id);
+            v
+        }).collect())
+    }
+}
+
+impl MultiVectorEmbedder for FakeEmbedder {
+    fn token_dim(&self) -> usize { self.dim }
+    /// The fake embedding of each whitespace-separated word.
+    fn embed_tokens(&self, texts: &[String]) -> Result<Vec<MultiVector>> {
+        texts.iter().map(|text| self.embed_batch(&text.split_whitespace().map(str::to_string).collect::<Vec<_>>())).collect()
+    }
+}
+
+/// Directory holding `spec`'s files: `APP_MODEL_DIR`, `MODEL_DIR`, then
+/// `../models/<name>` and `models/<name>`.
+pub fn resolve_model_dir(spec: &ModelSpec) -> Result<PathBuf> {
     if let Ok(dir) = std::env::var("APP_MODEL_DIR") { let p = PathBuf::from(&dir); if p.exists() { println!("📦 Using APP_MODEL_DIR: {}", p.display()); return Ok(p); } }
     if let Ok(dir) = std::env::var("MODEL_DIR") { let p = PathBuf::from(&dir); if p.exists() { println!("📦 Using MODEL_DIR: {}", p.display()); return Ok(p); } }
-//! localdb-embed
-//!
-//! Local embedding providers backed by Candle/safetensors, plus a fake
-//! deterministic embedder for tests and development.
-//!
-//! - `BgeM3Embedder` loads XLM‑R/BGE‑M3 from `model.safetensors`
-//! - `FakeEmbedder` is enabled by `APP_USE_FAKE_EMBEDDINGS=1`
-//! - `get_default_embedder()` picks fake vs real at runtime
-    let root = Path::new("../models/bge-m3"); if root.exists() { println!("📦 Using model dir: {}", root.display()); return Ok(root.to_path_buf()); }
-    let legacy = Path::new("models/bge-m3"); if legacy.exists() { println!("📦 Using legacy model dir: {}", legacy.display()); return Ok(legacy.to_path_buf()); }
-    Err(anyhow!("Could not locate BGE-M3 model directory"))
+    let root = Path::new("../models").join(spec.name); if root.exists() { println!("📦 Using model dir: {}", root.display()); return Ok(root); }
+    let legacy = Path::new("models").join(spec.name); if legacy.exists() { println!("📦 Using legacy model dir: {}", legacy.display()); return Ok(legacy); }
+    Err(CoreError::EmbedderUnavailable(format!("Could not locate the {} model directory (models/{})", spec.name, spec.name)).into())
+}
+
+/// True when `err` means the embedding model is missing (as opposed to a
+/// corrupt model or a runtime failure). Callers use this to fall back to
+/// text-only operation instead of refusing to start.
+pub fn is_embedder_unavailable(err: &anyhow::Error) -> bool {
+    matches!(err.downcast_ref::<CoreError>(), Some(CoreError::EmbedderUnavailable(_)))
 }
@@ -0,0 +1,106 @@
+//! Matryoshka (MRL) truncation of dense vectors.
+//!
+//! A model trained with Matryoshka representation learning packs the most
+//! information into the first components of its vectors: their first 256,
+//! 384 or 512 dims, re-normalized, are a smaller vector that retrieves a
+//! little worse. Other models (BGE‑M3, e5-small and gte-base among them)
+//! spread it over every component and retrieve far worse truncated, so only
+//! a `ModelSpec` with `matryoshka` set is truncated (`check_truncation`).
+//! `prefer_output_dim` (`embedding.truncate_dim` in the CLI) makes
+//! `get_default_embedder` wrap the model in `Truncated`, whose `dim()` is the
+//! truncated width, so the Lance collection, the canary and `LocalProvider`'s
+//! `embedder_id` (`:d<dim>`) all follow, and cached full-width vectors are
+//! never mixed in. Only dense vectors shrink (2-4x for a 1024-dim model): the
+//! sparse and ColBERT outputs are not truncated, so in multi-vector mode the
+//! token table, by far the largest, stays as it is.
+
+use anyhow::{bail, Result};
+use std::sync::Mutex;
+
+use localdb_core::traits::{Embedder as CoreEmbedder, MultiVectorEmbedder, SparseEmbedder};
+use localdb_core::types::{Embedded, Heads};
+
+use crate::registry::ModelSpec;
+
+/// Widths a Matryoshka model's vectors can be truncated to.
+pub const MATRYOSHKA_DIMS: [usize; 3] = [256, 384, 512];
+
+static PREFERRED: Mutex<Option<usize>> = Mutex::new(None);
+
+/// Truncate the default embedder's vectors to `dim` components (`None`
+/// keeps the model's full width, the default).
+pub fn prefer_output_dim(dim: Option<usize>) {
+    *PREFERRED.lock().unwrap_or_else(|e| e.into_inner()) = dim;
+}
+
+/// Width given to `prefer_output_dim`, `None` when vectors are kept whole.
+pub fn output_dim() -> Option<usize> { *PREFERRED.lock().unwrap_or_else(|e| e.into_inner()) }
+
+/// Fail unless `dim` is one of `MATRYOSHKA_DIMS` and at most `model_dim`.
+pub fn check_output_dim(dim: usize, model_dim: usize) -> Result<()> {
+    if !MATRYOSHKA_DIMS.contains(&dim) || dim > model_dim { bail!("cannot truncate {}-dim vectors to {} dims (try {:?})", model_dim, dim, MATRYOSHKA_DIMS.iter().filter(|&&d| d < model_dim).collect::<Vec<_>>()); }
+    Ok(())
+}
+
+/// Fail unless `spec`'s vectors can be truncated to `dim`: a Matryoshka model
+/// and a width `check_output_dim` accepts. Its full width always passes.
+pub fn check_truncation(spec: &ModelSpec, dim: usize) -> Result<()> {
+    if dim == spec.dim { return Ok(()); }
+    if !spec.matryoshka { bail!("{} is not trained for Matryoshka truncation, so its truncated vectors retrieve far worse; remove embedding.truncate_dim", spec.name); }
+    check_output_dim(dim, spec.dim)
+}
+
+/// The first `dim` components of `v`, L2-normalized again.
+pub fn truncate(v: &[f32], dim: usize) -> Vec<f32> {
+    let mut out = v[..dim.min(v.len())].to_vec();
+    let norm = out.iter().map(|x| x * x).sum::<f32>().sqrt().max(1e-12);
+    for x in &mut out { *x /= norm; }
+    out
+}
+
+/// An embedder whose dense vectors are cut to their first `dim` components.
+pub struct Truncated { inner: Box<dyn CoreEmbedder>, dim: usize }
+
+impl Truncated {
+    /// Wrap `inner`; `dim` must be between 1 and its width.
+    pub fn new(inner: Box<dyn CoreEmbedder>, dim: usize) -> Result<Self> {
+        if dim == 0 || dim > inner.dim() { bail!("cannot truncate {}-dim vectors to {} dims", inner.dim(), dim); }
+        Ok(Self { inner, dim })
+    }
+}
+
+impl CoreEmbedder for Truncated {
+    fn dim(&self) -> usize { self.dim }
+    fn max_len(&self) -> usize { self.inner.max_len() }
+    fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Ok(self.inner.embed_batch(texts)?.iter().map(|v| truncate(v, self.dim)).collect())
+    }
+    fn embed_queries(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Ok(self.inner.embed_queries(texts)?.iter().map(|v| truncate(v, self.dim)).collect())
+    }
+    fn sparse(&self) -> Option<&dyn SparseEmbedder> { self.inner.sparse() }
+    fn multi_vector(&self) -> Option<&dyn MultiVectorEmbedder> { self.inner.multi_vector() }
+    fn embed_passages_with(&self, texts: &[String], heads: Heads) -> Result<Embedded> {
+        let mut out = self.inner.embed_passages_with(texts, heads)?;
+        out.dense = out.dense.iter().map(|v| truncate(v, self.dim)).collect();
+        Ok(out)
+    }
+    fn embed_queries_with(&self, texts: &[String], heads: Heads) -> Result<Embedded> {
+        let mut out = self.inner.embed_queries_with(texts, heads)?;
+        out.dense = out.dense.iter().map(|v| truncate(v, self.dim)).collect();
+        Ok(out)
+    }
+}
+
+/// `embedder`, a `spec` model, truncated to `output_dim()`, or unchanged when
+/// none is preferred or it is already that narrow. Fails when `spec` may not
+/// be truncated to it (`check_truncation`).
+pub fn with_output_dim(spec: &ModelSpec, embedder: Box<dyn CoreEmbedder>) -> Result<Box<dyn CoreEmbedder>> {
+    match output_dim() {
+        Some(dim) if dim != embedder.dim() => {
+            check_truncation(spec, dim)?;
+            Ok(Box::new(Truncated::new(embedder, dim)?))
+        }
+        _ => Ok(embedder),
+    }
+}
@@ -1,5 +1,50 @@
-use anyhow::Result;
-use candle_core::{DType, Tensor};
+//! Pooling utilities for embedding models.
+//!
+//! `masked_mean_l2` computes a mean over the time dimension using the attention
+//! mask, then L2-normalizes per vector. A model is trained for one pooling
+//! (`Pooling`, `ModelSpec::pooling`, `embedding.pooling` in the CLI) and
+//! retrieves worse with another: `cls_l2` takes the first token's hidden
+//! state, `masked_max_l2` the maximum over the unmasked tokens.
+
+use anyhow::{bail, Result};
+use candle_core::{DType, IndexOp, Tensor};
+
+/// How token hidden states become one vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Pooling {
+    /// Mean over the unmasked tokens (`masked_mean_l2`).
+    #[default]
+    Mean,
+    /// Hidden state of the first (`<s>`/`[CLS]`) token (`cls_l2`).
+    Cls,
+    /// Per-dimension maximum over the unmasked tokens (`masked_max_l2`).
+    Max,
+}
+
+impl Pooling {
+    /// `mean`, `cls` or `max`.
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "mean" => Ok(Self::Mean),
+            "cls" => Ok(Self::Cls),
+            "max" => Ok(Self::Max),
+            _ => bail!("unknown pooling '{}': expected mean, cls or max", s),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self { Self::Mean => "mean", Self::Cls => "cls", Self::Max => "max" }
+    }
+
+    /// `[B, H]` L2-normalized vectors from `hidden` `[B, T, H]`.
+    pub fn pool(self, hidden: &Tensor, attention_mask: &Tensor) -> Result<Tensor> {
+        match self {
+            Self::Mean => masked_mean_l2(hidden, attention_mask),
+            Self::Cls => cls_l2(hidden),
+            Self::Max => masked_max_l2(hidden, attention_mask),
+        }
+    }
+}
 
 pub fn masked_mean_l2(hidden: &Tensor, attention_mask: &Tensor) -> Result<Tensor> {
     let dims = hidden.dims();
@@ -23,7 +68,26 @@ pub fn masked_mean_l2(hidden: &Tensor, attention_mask: &Tensor) -> Result<Tensor
     assert_eq!(mean.dims(), &[batch, hidden_dim]);
     Ok(mean)
 }
-//! Pooling utilities for embedding models.
-//!
-//! `masked_mean_l2` computes a mean over the time dimension using the attention
-//! mask, then L2-normalizes per vector.
+
+/// First token's hidden state of each row, L2-normalized.
+pub fn cls_l2(hidden: &Tensor) -> Result<Tensor> {
+    assert_eq!(hidden.dims().len(), 3, "hidden shape must be [B,T,H]");
+    l2_normalize(&hidden.i((.., 0, ..))?.contiguous()?)
+}
+
+/// Per-dimension maximum over the unmasked tokens of each row, L2-normalized.
+pub fn masked_max_l2(hidden: &Tensor, attention_mask: &Tensor) -> Result<Tensor> {
+    assert_eq!(hidden.dims().len(), 3, "hidden shape must be [B,T,H]");
+    let mask = attention_mask.to_device(hidden.device())?.to_dtype(hidden.dtype())?;
+    // 0 on tokens, a large negative on padding (within FP16's range).
+    let penalty = mask.affine(1e4, -1e4)?.unsqueeze(2)?.broadcast_as(hidden.shape())?;
+    l2_normalize(&(hidden + penalty)?.max(1)?)
+}
+
+/// Rows of `v` `[B, H]` scaled to unit length.
+fn l2_normalize(v: &Tensor) -> Result<Tensor> {
+    let eps_val = match v.dtype() { DType::F16 => 1e-6f32, _ => 1e-12f32 };
+    let eps = Tensor::new(&[eps_val], v.device())?.to_dtype(v.dtype())?.unsqueeze(0)?;
+    let norm = v.sqr()?.sum_keepdim(1)?.sqrt()?.broadcast_add(&eps)?;
+    Ok(v.broadcast_div(&norm)?)
+}
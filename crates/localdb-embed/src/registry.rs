@@ -0,0 +1,182 @@
+//! Embedding models by name.
+//!
+//! A `ModelSpec` names a model and the architecture that loads its
+//! safetensors; the loaded model's dimension and sequence length come from
+//! its own `config.json` and tokenizer (`ModelSpec::shape`). `ModelRegistry::builtin()`
+//! knows `bge-m3` (the default), `e5-small` and `gte-base`; a model may also
+//! be named by its Hugging Face id (`BAAI/bge-m3`). `APP_MODEL` — or the
+//! application's preference (`prefer_model`, `embedding.model` in the CLI)
+//! when it is unset — picks the one `get_default_embedder` loads, so switching
+//! models is a config change plus a model directory (see `resolve_model_dir`).
+//! `prefer_max_len` (`embedding.max_len`) replaces the chosen model's
+//! `max_len`, up to what its positions allow (BGE‑M3 has 8192), and
+//! `prefer_pooling` (`embedding.pooling`) its `Pooling`. `register_model`
+//! adds a model (or replaces a built-in one) for the rest of the process;
+//! everything that picks a model by name reads `ModelRegistry::current()`.
+//!
+//! Models trained with instruction prefixes get them per side: E5 embeds
+//! passages as `passage: …` (`embed_batch`) and queries as `query: …`
+//! (`Embedder::embed_queries`).
+
+use anyhow::{bail, Result};
+use std::path::Path;
+use std::sync::Mutex;
+
+use localdb_core::traits::Embedder as CoreEmbedder;
+
+use crate::{BertEmbedder, BgeM3Embedder, Pooling};
+
+/// Model loaded when none is chosen.
+pub const DEFAULT_MODEL: &str = "bge-m3";
+
+/// Network family, which decides how the weights are loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Architecture {
+    /// XLM-RoBERTa (BGE-M3, multilingual E5).
+    XlmRoberta,
+    /// BERT (GTE).
+    Bert,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelSpec {
+    /// Config name, also the model's directory under `models/`.
+    pub name: &'static str,
+    pub hf_id: &'static str,
+    pub architecture: Architecture,
+    /// Width of the vectors before the model is loaded (the fake embedder,
+    /// `embedding.truncate_dim` checks); a loaded model's is its `hidden_size`.
+    pub dim: usize,
+    /// Tokens embedded per text, or 0 for all the model has positions for;
+    /// longer texts are truncated.
+    pub max_len: usize,
+    /// Prepended to passages before embedding (E5 expects `passage: `).
+    pub passage_prefix: &'static str,
+    /// Prepended to queries before embedding (E5 expects `query: `).
+    pub query_prefix: &'static str,
+    /// How token states become the text's vector by default, normally the one
+    /// the model was trained with. bge-m3 is trained for CLS but keeps Mean,
+    /// which its existing indexes were built with and its `embedder_id`
+    /// implies; `prefer_pooling(Pooling::Cls)` switches it.
+    pub pooling: Pooling,
+    /// Trained with Matryoshka representation learning, so the leading
+    /// components of its vectors are a usable smaller vector
+    /// (`embedding.truncate_dim`); none of the built-in models is.
+    pub matryoshka: bool,
+}
+
+/// Vector width and sequence length of a loaded model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelShape {
+    pub dim: usize,
+    pub max_len: usize,
+}
+
+impl ModelSpec {
+    /// Shape from the model's own `config.json`: `hidden_size` is the dim,
+    /// and `max_len` is `max_position_embeddings` (less XLM‑R's padding
+    /// offset), capped by the tokenizer's truncation length, if set, and by
+    /// the spec's `max_len` unless that is 0. Whether the dim fits the index
+    /// is checked against the Lance collection, not here.
+    pub fn shape(&self, config_json: &str, tokenizer_max_len: Option<usize>) -> Result<ModelShape> {
+        let config: serde_json::Value = serde_json::from_str(config_json)?;
+        let field = |name: &str| config.get(name).and_then(serde_json::Value::as_u64).map(|v| v as usize);
+        let Some(dim) = field("hidden_size") else { bail!("{}: config.json has no hidden_size", self.name) };
+        // RoBERTa positions start after the padding id.
+        let offset = match self.architecture { Architecture::XlmRoberta => field("pad_token_id").unwrap_or(1) + 1, Architecture::Bert => 0 };
+        let Some(positions) = field("max_position_embeddings").map(|p| p.saturating_sub(offset)) else { bail!("{}: config.json has no max_position_embeddings", self.name) };
+        let cap = (self.max_len > 0).then_some(self.max_len);
+        let max_len = [Some(positions), tokenizer_max_len, cap].into_iter().flatten().min().unwrap_or(positions);
+        if max_len == 0 { bail!("{}: config.json leaves no positions to embed", self.name); }
+        Ok(ModelShape { dim, max_len })
+    }
+
+    /// Load the model's weights and tokenizer from `model_dir`.
+    pub fn load(&self, model_dir: &Path) -> Result<Box<dyn CoreEmbedder>> {
+        Ok(match self.architecture {
+            Architecture::XlmRoberta => Box::new(BgeM3Embedder::load(self.clone(), model_dir)?),
+            Architecture::Bert => Box::new(BertEmbedder::load(self.clone(), model_dir)?),
+        })
+    }
+}
+
+/// The models that can be named in config.
+#[derive(Debug, Clone)]
+pub struct ModelRegistry { models: Vec<ModelSpec> }
+
+impl ModelRegistry {
+    pub fn builtin() -> Self {
+        Self { models: vec![
+            ModelSpec { name: "bge-m3", hf_id: "BAAI/bge-m3", architecture: Architecture::XlmRoberta, dim: 1024, max_len: 256, passage_prefix: "", query_prefix: "", pooling: Pooling::Mean, matryoshka: false },
+            ModelSpec { name: "e5-small", hf_id: "intfloat/multilingual-e5-small", architecture: Architecture::XlmRoberta, dim: 384, max_len: 0, passage_prefix: "passage: ", query_prefix: "query: ", pooling: Pooling::Mean, matryoshka: false },
+            ModelSpec { name: "gte-base", hf_id: "thenlper/gte-base", architecture: Architecture::Bert, dim: 768, max_len: 0, passage_prefix: "", query_prefix: "", pooling: Pooling::Mean, matryoshka: false },
+        ] }
+    }
+
+    /// `builtin()` with the models given to `register_model`.
+    pub fn current() -> Self {
+        let mut registry = Self::builtin();
+        for spec in REGISTERED.lock().unwrap_or_else(|e| e.into_inner()).iter() { registry.register(spec.clone()); }
+        registry
+    }
+
+    /// Add `spec`, replacing a model of the same name.
+    pub fn register(&mut self, spec: ModelSpec) {
+        self.models.retain(|m| m.name != spec.name);
+        self.models.push(spec);
+    }
+
+    /// The model called `name` (or with that Hugging Face id), ignoring case.
+    pub fn get(&self, name: &str) -> Result<&ModelSpec> {
+        let name = name.trim();
+        match self.models.iter().find(|m| m.name.eq_ignore_ascii_case(name) || m.hf_id.eq_ignore_ascii_case(name)) {
+            Some(spec) => Ok(spec),
+            None => bail!("unknown embedding model '{}': expected one of {}", name, self.names().join(", ")),
+        }
+    }
+
+    pub fn names(&self) -> Vec<&'static str> { self.models.iter().map(|m| m.name).collect() }
+}
+
+static REGISTERED: Mutex<Vec<ModelSpec>> = Mutex::new(Vec::new());
+static PREFERRED: Mutex<String> = Mutex::new(String::new());
+static PREFERRED_MAX_LEN: Mutex<Option<usize>> = Mutex::new(None);
+static PREFERRED_POOLING: Mutex<Option<Pooling>> = Mutex::new(None);
+
+/// Make `spec` nameable in config for the rest of the process, replacing a
+/// built-in or registered model of the same name.
+pub fn register_model(spec: ModelSpec) {
+    let mut registered = REGISTERED.lock().unwrap_or_else(|e| e.into_inner());
+    registered.retain(|m| m.name != spec.name);
+    registered.push(spec);
+}
+
+/// Model to load when `APP_MODEL` is unset (e.g. from the application's config).
+pub fn prefer_model(name: &str) {
+    *PREFERRED.lock().unwrap_or_else(|e| e.into_inner()) = name.to_string();
+}
+
+/// Tokens embedded per text by the model `model_spec` picks, instead of its
+/// registered `max_len` (`None` restores that).
+pub fn prefer_max_len(max_len: Option<usize>) {
+    *PREFERRED_MAX_LEN.lock().unwrap_or_else(|e| e.into_inner()) = max_len;
+}
+
+/// Pooling of the model `model_spec` picks, instead of its registered one
+/// (`None` restores that).
+pub fn prefer_pooling(pooling: Option<Pooling>) {
+    *PREFERRED_POOLING.lock().unwrap_or_else(|e| e.into_inner()) = pooling;
+}
+
+/// The model (built-in or registered) named by `APP_MODEL`, else by `prefer_model`, else
+/// `DEFAULT_MODEL`, with the `max_len` and pooling given to `prefer_max_len`
+/// and `prefer_pooling`.
+pub fn model_spec() -> Result<ModelSpec> {
+    let preferred = PREFERRED.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let name = std::env::var("APP_MODEL").ok().filter(|v| !v.trim().is_empty())
+        .unwrap_or(if preferred.is_empty() { DEFAULT_MODEL.to_string() } else { preferred });
+    let mut spec = ModelRegistry::current().get(&name)?.clone();
+    if let Some(max_len) = *PREFERRED_MAX_LEN.lock().unwrap_or_else(|e| e.into_inner()) { spec.max_len = max_len; }
+    if let Some(pooling) = *PREFERRED_POOLING.lock().unwrap_or_else(|e| e.into_inner()) { spec.pooling = pooling; }
+    Ok(spec)
+}
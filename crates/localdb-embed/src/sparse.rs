@@ -0,0 +1,63 @@
+//! BGE-M3's sparse (lexical weight) output.
+//!
+//! Beside the dense model, BGE-M3 ships `sparse_linear`: one linear layer from
+//! each token's last hidden state to a weight, passed through `relu`. A text's
+//! sparse vector keeps, per vocabulary token, the highest weight among its
+//! occurrences, leaving out special tokens (`<s>`, `</s>`, `<pad>`, `<unk>`);
+//! BGE-M3 scores a query against a text by the dot product over the tokens
+//! they share (the text index approximates it with BM25, see
+//! `localdb_text::sparse`). The
+//! head is read from `sparse_linear.safetensors`, else the Hugging Face repo's
+//! `sparse_linear.pt`; a model directory with neither has no sparse output.
+
+use anyhow::Result;
+use candle_core::{DType, Device, Module, Tensor};
+use candle_nn::{Linear, VarBuilder};
+use std::path::Path;
+use tokenizers::Tokenizer;
+
+use localdb_core::types::SparseVector;
+
+/// Files the sparse head is loaded from, in order of preference.
+pub const SPARSE_HEAD_FILES: [&str; 2] = ["sparse_linear.safetensors", "sparse_linear.pt"];
+
+/// Tokens that carry no lexical meaning, in XLM-R and BERT spelling.
+const SPECIAL_TOKENS: [&str; 8] = ["<s>", "</s>", "<pad>", "<unk>", "[CLS]", "[SEP]", "[PAD]", "[UNK]"];
+
+/// The `sparse_linear` layer and the special token ids it ignores.
+pub struct SparseHead { linear: Linear, special: Vec<u32> }
+
+impl SparseHead {
+    /// The head in `model_dir` for a model of width `hidden`, if the directory has one.
+    pub fn load(model_dir: &Path, tokenizer: &Tokenizer, hidden: usize, device: &Device, dtype: DType) -> Result<Option<Self>> {
+        let Some(path) = SPARSE_HEAD_FILES.iter().map(|f| model_dir.join(f)).find(|p| p.is_file()) else { return Ok(None) };
+        let vb = if path.extension().is_some_and(|e| e == "pt") { VarBuilder::from_pth(&path, dtype, device)? }
+            // Safety: relying on safetensors metadata
+            else { unsafe { VarBuilder::from_mmaped_safetensors(&[path], dtype, device)? } };
+        let linear = candle_nn::linear(hidden, 1, vb)?;
+        let special = SPECIAL_TOKENS.iter().filter_map(|t| tokenizer.token_to_id(t)).collect();
+        Ok(Some(Self { linear, special }))
+    }
+
+    /// Sparse vectors of a batch from its hidden states `[B, T, H]` and the
+    /// input ids and attention mask `[B, T]` they came from.
+    pub fn weights(&self, hidden_states: &Tensor, input_ids: &Tensor, attention_mask: &Tensor) -> Result<Vec<SparseVector>> {
+        let weights = self.linear.forward(hidden_states)?.relu()?.squeeze(2)?.to_dtype(DType::F32)?.to_device(&Device::Cpu)?.to_vec2::<f32>()?;
+        let ids = input_ids.to_device(&Device::Cpu)?.to_vec2::<i64>()?;
+        let mask = attention_mask.to_device(&Device::Cpu)?.to_vec2::<i64>()?;
+        Ok(weights.iter().zip(&ids).zip(&mask).map(|((w, ids), mask)| max_per_token(w, ids, mask, &self.special)).collect())
+    }
+}
+
+/// The highest positive weight of each token id among the unmasked positions,
+/// `special` ids left out, sorted by id.
+pub fn max_per_token(weights: &[f32], ids: &[i64], mask: &[i64], special: &[u32]) -> SparseVector {
+    let mut best: std::collections::BTreeMap<u32, f32> = std::collections::BTreeMap::new();
+    for ((w, id), m) in weights.iter().zip(ids).zip(mask) {
+        let id = *id as u32;
+        if *m == 0 || *w <= 0.0 || special.contains(&id) { continue; }
+        let slot = best.entry(id).or_insert(0.0);
+        *slot = slot.max(*w);
+    }
+    best.into_iter().collect()
+}
@@ -1,7 +1,20 @@
+//! Tokenization helpers for XLM‑R/BGE‑M3.
+//!
+//! Provides batched tokenization on the target device/dtype. Returns input ids
+//! and attention masks with shape `[B, T]`, where `T` is the longest text of
+//! the batch capped at `max_len`: a batch of short chunks does not pay for
+//! `max_len` positions. `ModelTokenCounter` counts tokens with the same
+//! tokenizer so the chunker can size chunks to `max_len`.
+
 use anyhow::{Result, anyhow};
 use candle_core::{Device, Tensor, DType};
-use tokenizers::Tokenizer;
+use std::path::Path;
+use tokenizers::{Encoding, Tokenizer};
+
+use localdb_core::traits::TokenCounter;
 
+/// Ids and attention masks of `texts`, truncated to `max_len` tokens and
+/// padded to the longest of them.
 pub fn tokenize_batch_on_device(
     tokenizer: &Tokenizer,
     texts: &[String],
@@ -9,40 +22,66 @@ pub fn tokenize_batch_on_device(
     device: &Device,
     _dtype: DType,
 ) -> Result<(Tensor, Tensor)> {
-    // Determine pad id from tokenizer config if present; fall back to 1
-    let pad_id: i64 = tokenizer.get_padding().map(|p| p.pad_id).unwrap_or(1) as i64;
     let enc = tokenizer
         .encode_batch(texts.to_vec(), true)
         .map_err(|e| anyhow!("Tokenization failed: {}", e))?;
+    let rows: Vec<Vec<u32>> = enc.iter().map(|e| e.get_ids()[..real_len(e).min(max_len)].to_vec()).collect();
+    pad_on_device(&rows, pad_id(tokenizer), device)
+}
+
+/// Ids `[B, T]` of `rows`, padded with `pad_id` to the longest row, and their
+/// attention masks.
+pub fn pad_on_device(rows: &[Vec<u32>], pad_id: u32, device: &Device) -> Result<(Tensor, Tensor)> {
+    let b = rows.len();
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0).max(1);
+    let mut ids: Vec<i64> = Vec::with_capacity(b * width);
+    let mut mask: Vec<i64> = Vec::with_capacity(b * width);
 
-    let b = enc.len();
-    let mut ids: Vec<i64> = Vec::with_capacity(b * max_len);
-    let mut mask: Vec<i64> = Vec::with_capacity(b * max_len);
-
-    for e in enc {
-        let mut v = e.get_ids().to_vec();
-        let mut m = e.get_attention_mask().to_vec();
-        if v.len() > max_len { v.truncate(max_len); m.truncate(max_len); }
-        if v.len() < max_len {
-            let pad = max_len - v.len();
-            v.extend(std::iter::repeat_n(pad_id as u32, pad));
-            m.extend(std::iter::repeat_n(0u32, pad));
-        }
-        ids.extend(v.into_iter().map(|x| x as i64));
-        mask.extend(m.into_iter().map(|x| x as i64));
+    for row in rows {
+        let pad = width - row.len();
+        ids.extend(row.iter().map(|&x| x as i64).chain(std::iter::repeat_n(pad_id as i64, pad)));
+        mask.extend(std::iter::repeat_n(1i64, row.len()).chain(std::iter::repeat_n(0i64, pad)));
     }
 
-    let input_ids = Tensor::from_iter(ids, device)?.reshape((b, max_len))?;
-    let attn_mask = Tensor::from_iter(mask, device)?.reshape((b, max_len))?;
+    let input_ids = Tensor::from_iter(ids, device)?.reshape((b, width))?;
+    let attn_mask = Tensor::from_iter(mask, device)?.reshape((b, width))?;
     Ok((input_ids, attn_mask))
 }
 
+/// Pad id from the tokenizer config if present; falls back to 1 (XLM‑R's `<pad>`).
+pub fn pad_id(tokenizer: &Tokenizer) -> u32 { tokenizer.get_padding().map(|p| p.pad_id).unwrap_or(1) }
+
+/// Real tokens of `encoding`. Counts the attention mask, not the ids: a
+/// tokenizer.json with padding of its own would otherwise widen every batch.
+pub fn real_len(encoding: &Encoding) -> usize { encoding.get_attention_mask().iter().filter(|&&m| m != 0).count() }
+
 pub fn tokenize_on_device(tokenizer: &Tokenizer, text: &str, max_len: usize, device: &Device) -> Result<(Tensor, Tensor)> {
     let (ids, mask) = tokenize_batch_on_device(tokenizer, &[text.to_string()], max_len, device, DType::F32)?;
-    // reshape already matches (1, max_len)
+    // reshape already matches (1, T)
     Ok((ids, mask))
 }
-//! Tokenization helpers for XLM‑R/BGE‑M3.
-//!
-//! Provides batched tokenization on the target device/dtype. Returns input ids
-//! and attention masks with shape `[B, T]`.
+
+/// Token counts from the model's `tokenizer.json`, special tokens included.
+pub struct ModelTokenCounter { tokenizer: Tokenizer, max_len: usize }
+
+impl ModelTokenCounter {
+    /// Load `tokenizer.json` from `model_dir`. Truncation and padding are
+    /// turned off so counts are exact past `max_len`.
+    pub fn from_model_dir(model_dir: &Path, max_len: usize) -> Result<Self> {
+        let path = model_dir.join("tokenizer.json");
+        let mut tokenizer = Tokenizer::from_file(&path).map_err(|e| anyhow!("Failed to load tokenizer from {}: {}", path.display(), e))?;
+        tokenizer.with_truncation(None).map_err(|e| anyhow!("Failed to disable truncation: {}", e))?;
+        tokenizer.with_padding(None);
+        Ok(Self { tokenizer, max_len })
+    }
+}
+
+impl TokenCounter for ModelTokenCounter {
+    fn count_tokens(&self, text: &str) -> usize {
+        // Unencodable text is rare; fall back to one token per character, which
+        // over-counts and so still keeps the chunk under `max_len`.
+        self.tokenizer.encode(text, true).map(|e| e.get_ids().len()).unwrap_or_else(|_| text.chars().count())
+    }
+
+    fn max_len(&self) -> usize { self.max_len }
+}
@@ -0,0 +1,76 @@
+//! Sliding-window embedding of texts longer than `max_len`.
+//!
+//! By default a text is truncated to the model's `max_len` tokens and its tail
+//! is not embedded at all. In sliding-window mode (`prefer_sliding_window`,
+//! `embedding.sliding_window` in the CLI) `BgeM3Embedder` cuts an over-length
+//! text into windows of `max_len` tokens, each `overlap` tokens into the one
+//! before and wrapped in the text's own `<s>`/`</s>`, embeds every window and
+//! pools them into one vector: the mean of the window vectors weighted by
+//! their length, L2-normalized. Texts that fit are embedded as before. The
+//! sparse and ColBERT heads read the same windows: a token's sparse weight is
+//! its highest in any window, and the token vectors are every window's.
+
+use std::sync::Mutex;
+
+use localdb_core::types::SparseVector;
+
+/// Tokens shared by consecutive windows when none is configured.
+pub const DEFAULT_OVERLAP: usize = 32;
+
+static PREFERRED: Mutex<Option<usize>> = Mutex::new(None);
+
+/// Embed over-length texts window by window, `overlap` tokens apart (`None`
+/// truncates them, the default).
+pub fn prefer_sliding_window(overlap: Option<usize>) {
+    *PREFERRED.lock().unwrap_or_else(|e| e.into_inner()) = overlap;
+}
+
+/// Window overlap given to `prefer_sliding_window`, `None` when texts are truncated.
+pub fn sliding_window() -> Option<usize> { *PREFERRED.lock().unwrap_or_else(|e| e.into_inner()) }
+
+/// Windows of at most `max_len` ids over `ids`, an encoding that starts and
+/// ends with a special token: each window holds the next stretch of the text
+/// between those two tokens and starts `overlap` tokens before the previous
+/// one ended. Ids that fit in `max_len` are one window.
+pub fn windows(ids: &[u32], max_len: usize, overlap: usize) -> Vec<Vec<u32>> {
+    if ids.len() <= max_len || max_len < 3 { return vec![ids[..ids.len().min(max_len)].to_vec()]; }
+    let (first, last, body) = (ids[0], ids[ids.len() - 1], &ids[1..ids.len() - 1]);
+    let width = max_len - 2;
+    let step = width - overlap.min(width - 1);
+    let mut out = Vec::new();
+    let mut start = 0;
+    loop {
+        let end = (start + width).min(body.len());
+        let mut window = Vec::with_capacity(end - start + 2);
+        window.push(first);
+        window.extend_from_slice(&body[start..end]);
+        window.push(last);
+        out.push(window);
+        if end == body.len() { return out; }
+        start += step;
+    }
+}
+
+/// One vector from the vectors of a text's windows: their mean weighted by
+/// `weights` (tokens per window), L2-normalized.
+pub fn pool_windows(vectors: &[Vec<f32>], weights: &[usize]) -> Vec<f32> {
+    let dim = vectors.first().map_or(0, Vec::len);
+    let mut pooled = vec![0f32; dim];
+    for (v, &w) in vectors.iter().zip(weights) {
+        for (p, x) in pooled.iter_mut().zip(v) { *p += x * w as f32; }
+    }
+    let norm = pooled.iter().map(|x| x * x).sum::<f32>().sqrt().max(1e-12);
+    for p in &mut pooled { *p /= norm; }
+    pooled
+}
+
+/// One sparse vector from those of a text's windows: each token's highest
+/// weight, sorted by id.
+pub fn merge_sparse(vectors: &[SparseVector]) -> SparseVector {
+    let mut best: std::collections::BTreeMap<u32, f32> = std::collections::BTreeMap::new();
+    for (id, w) in vectors.iter().flatten() {
+        let slot = best.entry(*id).or_insert(0.0);
+        *slot = slot.max(*w);
+    }
+    best.into_iter().collect()
+}
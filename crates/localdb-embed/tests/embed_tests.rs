@@ -21,3 +21,173 @@ fn fake_embedder_shapes_and_determinism() {
     for (a, b) in v1.iter().zip(v2.iter()) { assert!((a - b).abs() <= 1e-6); }
 }
 
+
+#[test]
+fn bench_measures_each_batch_size_and_prefers_small_batches() {
+    use localdb_embed::bench::{measure, recommend, sample_texts, Throughput};
+    use std::time::Duration;
+    std::env::set_var("APP_USE_FAKE_EMBEDDINGS", "1");
+
+    let embedder = get_default_embedder().expect("embedder");
+    let texts = sample_texts(20, 12, 7);
+    assert_eq!(texts, sample_texts(20, 12, 7), "filler is deterministic");
+    assert!(texts.iter().all(|t| t.split_whitespace().count() == 12));
+    let results = measure(embedder.as_ref(), &texts, 240, &[1, 8, 64]).expect("measure");
+    assert_eq!(results.iter().map(|r| r.batch_size).collect::<Vec<_>>(), vec![1, 8], "batches larger than the sample are skipped");
+    assert!(results.iter().all(|r| r.chunks_per_sec > 0.0 && (r.tokens_per_sec / r.chunks_per_sec - 12.0).abs() < 1e-6));
+
+    let at = |batch_size, chunks_per_sec| Throughput { batch_size, elapsed: Duration::from_secs(1), chunks_per_sec, tokens_per_sec: chunks_per_sec * 100.0 };
+    assert_eq!(recommend(&[at(32, 205.0), at(8, 120.0), at(16, 200.0)]), Some(16), "32 is not clearly faster than 16");
+    assert_eq!(recommend(&[at(1, 10.0), at(4, 30.0)]), Some(4));
+    assert_eq!(recommend(&[]), None);
+}
+
+#[test]
+fn devices_are_chosen_explicitly_or_detected() {
+    use localdb_embed::{open_device, DeviceChoice};
+
+    assert_eq!(DeviceChoice::parse("auto").unwrap(), DeviceChoice::Auto);
+    assert_eq!(DeviceChoice::parse(" CPU ").unwrap(), DeviceChoice::Cpu);
+    assert_eq!(DeviceChoice::parse("metal").unwrap(), DeviceChoice::Metal);
+    assert_eq!(DeviceChoice::parse("cuda").unwrap(), DeviceChoice::Cuda(0));
+    assert_eq!(DeviceChoice::parse("cuda:2").unwrap(), DeviceChoice::Cuda(2));
+    assert!(DeviceChoice::parse("cuda:x").is_err());
+    assert!(DeviceChoice::parse("tpu").is_err());
+
+    assert!(open_device(DeviceChoice::Cpu).unwrap().is_cpu());
+    open_device(DeviceChoice::Auto).expect("auto always finds a device");
+    if !cfg!(feature = "cuda") {
+        let err = open_device(DeviceChoice::Cuda(0)).unwrap_err().to_string();
+        assert!(err.contains("no CUDA support"), "{}", err);
+    }
+}
+
+#[test]
+fn registry_names_models_with_their_shapes() {
+    use localdb_embed::registry::{Architecture, ModelRegistry, ModelSpec, DEFAULT_MODEL};
+    use localdb_embed::Pooling;
+
+    let mut registry = ModelRegistry::builtin();
+    assert_eq!(registry.names(), vec!["bge-m3", "e5-small", "gte-base"]);
+    let bge = registry.get(DEFAULT_MODEL).unwrap();
+    assert_eq!((bge.dim, bge.max_len, bge.architecture), (1024, 256, Architecture::XlmRoberta));
+    assert_eq!(registry.get("BAAI/bge-m3").unwrap().name, "bge-m3", "Hugging Face ids name models too");
+    assert_eq!(registry.get(" GTE-Base ").unwrap().architecture, Architecture::Bert);
+    let e5 = registry.get("e5-small").unwrap();
+    assert_eq!((e5.passage_prefix, e5.query_prefix), ("passage: ", "query: "));
+    let err = registry.get("word2vec").unwrap_err().to_string();
+    assert!(err.contains("expected one of bge-m3, e5-small, gte-base"), "{}", err);
+
+    registry.register(ModelSpec { name: "gte-base", hf_id: "thenlper/gte-base", architecture: Architecture::Bert, dim: 768, max_len: 256, passage_prefix: "", query_prefix: "", pooling: Pooling::Cls, matryoshka: false });
+    assert_eq!(registry.names().len(), 3, "registering a known name replaces it");
+    assert_eq!(registry.get("gte-base").unwrap().max_len, 256);
+    assert_eq!(registry.get("gte-base").unwrap().pooling, Pooling::Cls);
+    assert_eq!(Pooling::parse(" CLS ").unwrap(), Pooling::Cls);
+    assert!(Pooling::parse("last").unwrap_err().to_string().contains("expected mean, cls or max"));
+}
+
+#[test]
+fn registered_models_can_be_named_in_config() {
+    use localdb_embed::registry::{Architecture, ModelRegistry, ModelSpec};
+    use localdb_embed::{register_model, Pooling};
+
+    assert!(ModelRegistry::current().get("mini-test").is_err());
+    register_model(ModelSpec { name: "mini-test", hf_id: "example/mini-test", architecture: Architecture::Bert, dim: 384, max_len: 128, passage_prefix: "", query_prefix: "", pooling: Pooling::Mean, matryoshka: false });
+    assert_eq!(ModelRegistry::current().get("example/mini-test").unwrap().dim, 384);
+    assert_eq!(ModelRegistry::builtin().names().len(), 3, "the built-in list is unchanged");
+}
+
+#[test]
+fn model_shape_comes_from_config_json() {
+    use localdb_embed::registry::{ModelRegistry, ModelShape};
+
+    let registry = ModelRegistry::builtin();
+    let bge = registry.get("bge-m3").unwrap();
+    let bge_config = r#"{"hidden_size": 1024, "max_position_embeddings": 8194, "pad_token_id": 1}"#;
+    assert_eq!(bge.shape(bge_config, None).unwrap(), ModelShape { dim: 1024, max_len: 256 });
+    assert_eq!(bge.shape(bge_config, Some(128)).unwrap().max_len, 128, "the tokenizer's truncation caps it");
+
+    let gte = registry.get("gte-base").unwrap();
+    assert_eq!(gte.shape(r#"{"hidden_size": 768, "max_position_embeddings": 400}"#, None).unwrap(), ModelShape { dim: 768, max_len: 400 });
+
+    assert_eq!(bge.shape(r#"{"hidden_size": 768, "max_position_embeddings": 514}"#, None).unwrap(), ModelShape { dim: 768, max_len: 256 }, "the dim is the model's own");
+    assert!(bge.shape(r#"{"max_position_embeddings": 514}"#, None).is_err());
+    assert!(gte.shape(r#"{"hidden_size": 768}"#, None).is_err());
+}
+
+#[test]
+fn sparse_weights_keep_each_tokens_highest_weight() {
+    use localdb_embed::sparse::max_per_token;
+
+    // <s>=0 jam=7 jar=9 jam=7 </s>=2 <pad>=1 (masked)
+    let weights = [0.9, 0.2, 0.0, 0.4, 0.8, 0.7];
+    let ids = [0, 7, 9, 7, 2, 1];
+    let mask = [1, 1, 1, 1, 1, 0];
+    assert_eq!(max_per_token(&weights, &ids, &mask, &[0, 1, 2]), vec![(7, 0.4)], "specials, zero weights and padding are dropped");
+
+    std::env::set_var("APP_USE_FAKE_EMBEDDINGS", "1");
+    let embedder = get_default_embedder().expect("embedder");
+    let sparse = embedder.sparse().expect("the fake embedder has sparse output");
+    let v = sparse.embed_sparse(&["Jam, jam and pectin".to_string()]).unwrap().remove(0);
+    assert_eq!(v.len(), 3, "one weight per distinct word: {:?}", v);
+    assert!(v.windows(2).all(|w| w[0].0 < w[1].0));
+}
+
+#[test]
+fn colbert_rows_skip_the_first_token_and_padding() {
+    use localdb_embed::colbert::token_rows;
+
+    let rows = vec![vec![9.0, 9.0], vec![3.0, 4.0], vec![0.0, 2.0], vec![1.0, 1.0]];
+    assert_eq!(token_rows(rows, &[1, 1, 1, 0]), vec![vec![0.6, 0.8], vec![0.0, 1.0]]);
+
+    std::env::set_var("APP_USE_FAKE_EMBEDDINGS", "1");
+    let embedder = get_default_embedder().expect("embedder");
+    let multi = embedder.multi_vector().expect("the fake embedder has token vectors");
+    let v = multi.embed_tokens(&["canning tomatoes at home".to_string()]).unwrap().remove(0);
+    assert_eq!(v.len(), 4);
+    assert!(v.iter().all(|t| t.len() == multi.token_dim()));
+}
+
+#[test]
+fn model_fingerprint_changes_with_the_weights() {
+    use localdb_embed::model_fingerprint;
+
+    let dir = std::env::temp_dir().join(format!("localdb-fingerprint-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("config.json"), r#"{"hidden_size": 1024}"#).unwrap();
+    std::fs::write(dir.join("model.safetensors"), b"weights v1").unwrap();
+    let first = model_fingerprint(&dir).unwrap();
+    assert_eq!(first.len(), 16);
+    assert_eq!(model_fingerprint(&dir).unwrap(), first, "stable for the same files");
+
+    std::fs::write(dir.join("model.safetensors"), b"weights v2").unwrap();
+    assert_ne!(model_fingerprint(&dir).unwrap(), first, "same size, other bytes");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn matryoshka_truncation_keeps_leading_dims_normalized() {
+    use localdb_core::traits::Embedder;
+    use localdb_embed::matryoshka::{check_output_dim, check_truncation, truncate};
+    use localdb_embed::Truncated;
+
+    let v = truncate(&[3.0, 4.0, 12.0], 2);
+    assert!((v[0] - 0.6).abs() < 1e-6 && (v[1] - 0.8).abs() < 1e-6, "{:?}", v);
+
+    struct Wide;
+    impl Embedder for Wide {
+        fn dim(&self) -> usize { 4 }
+        fn max_len(&self) -> usize { 8 }
+        fn embed_batch(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> { Ok(texts.iter().map(|_| vec![0.6, 0.0, 0.8, 0.0]).collect()) }
+    }
+    let narrow = Truncated::new(Box::new(Wide), 2).unwrap();
+    assert_eq!(narrow.dim(), 2);
+    assert_eq!(narrow.embed_batch(&["a".to_string()]).unwrap(), vec![vec![1.0, 0.0]]);
+    assert!(Truncated::new(Box::new(Wide), 8).is_err());
+    assert!(check_output_dim(512, 384).unwrap_err().to_string().contains("[256]"));
+    assert!(check_output_dim(0, 1024).is_err() && check_output_dim(300, 1024).is_err() && check_output_dim(256, 1024).is_ok());
+    let bge = localdb_embed::ModelRegistry::builtin().get("bge-m3").unwrap().clone();
+    assert!(check_truncation(&bge, 512).unwrap_err().to_string().contains("not trained for Matryoshka"));
+    assert!(check_truncation(&bge, 1024).is_ok(), "the full width is no truncation");
+    assert!(check_truncation(&localdb_embed::ModelSpec { matryoshka: true, ..bge }, 512).is_ok());
+}
@@ -1,5 +1,6 @@
 use candle_core::{Device, Tensor, DType};
-use localdb_embed::masked_mean_l2;
+use localdb_embed::{masked_mean_l2, Pooling};
+use localdb_embed::window::{merge_sparse, pool_windows, windows};
 
 #[test]
 fn masked_mean_l2_basic() {
@@ -20,3 +21,40 @@ fn masked_mean_l2_basic() {
         assert!((a - b).abs() < 1e-5, "a={} b={}", a, b);
     }
 }
+
+#[test]
+fn windows_cover_long_ids_with_overlap_and_keep_special_tokens() {
+    // <s>=0, </s>=2 around ten body tokens 10..20.
+    let ids: Vec<u32> = std::iter::once(0).chain(10..20).chain(std::iter::once(2)).collect();
+    assert_eq!(windows(&ids, 12, 4), vec![ids.clone()], "ids that fit are one window");
+    let w = windows(&ids, 6, 1);
+    assert_eq!(w, vec![vec![0, 10, 11, 12, 13, 2], vec![0, 13, 14, 15, 16, 2], vec![0, 16, 17, 18, 19, 2]]);
+    assert!(w.iter().all(|w| w.len() <= 6));
+    // An overlap as wide as the window still moves forward.
+    assert_eq!(windows(&ids, 6, 10).len(), 10 - 4 + 1);
+}
+
+#[test]
+fn pool_windows_weights_by_length_and_normalizes() {
+    let v = pool_windows(&[vec![1.0, 0.0], vec![0.0, 1.0]], &[3, 1]);
+    let norm = (9.0f32 + 1.0).sqrt();
+    assert!((v[0] - 3.0 / norm).abs() < 1e-6 && (v[1] - 1.0 / norm).abs() < 1e-6, "{:?}", v);
+}
+
+#[test]
+fn merge_sparse_keeps_each_tokens_highest_window_weight() {
+    assert_eq!(merge_sparse(&[vec![(3, 0.2), (7, 0.5)], vec![(1, 0.1), (3, 0.4)]]), vec![(1, 0.1), (3, 0.4), (7, 0.5)]);
+    assert!(merge_sparse(&[]).is_empty());
+}
+
+#[test]
+fn cls_and_max_pooling() {
+    let dev = Device::Cpu;
+    // Three tokens with hidden dim 2; the last is padding and would win the max.
+    let h = Tensor::from_slice(&[3.0f32, 0.0,  1.0, 4.0,  9.0, 9.0], (1, 3, 2), &dev).unwrap();
+    let mask = Tensor::from_slice(&[1i64, 1, 0], (1, 3), &dev).unwrap();
+    let cls: Vec<Vec<f32>> = Pooling::Cls.pool(&h, &mask).unwrap().to_vec2().unwrap();
+    assert!((cls[0][0] - 1.0).abs() < 1e-5 && cls[0][1].abs() < 1e-5, "{:?}", cls);
+    let max: Vec<Vec<f32>> = Pooling::Max.pool(&h, &mask).unwrap().to_vec2().unwrap();
+    assert!((max[0][0] - 0.6).abs() < 1e-5 && (max[0][1] - 0.8).abs() < 1e-5, "{:?}", max);
+}
@@ -1,55 +1,716 @@
+//! localdb-hybrid
+//!
+//! Thin façade that composes a text indexer and a vector indexer behind one
+//! `SearchEngine` trait. The engine indexes by embedding chunks once and writing
+//! to both backends, and queries by embedding the query once then merging hits.
+//!
+//! The merge prefers higher scores for duplicate ids and labels each hit with
+//! `SourceKind` so downstream callers can understand origin. Merging follows a
+//! `FusionStrategy` (max score by default, a weighted sum, or reciprocal rank
+//! fusion) with per-leg `FusionWeights`, which `localdb-cli tune` learns from
+//! click feedback. With `with_calibration`, each leg's raw scores are first
+//! mapped to probabilities of relevance (`localdb_core::calibration`, fitted
+//! by `localdb-cli calibrate`), so the score strategies compare like with like.
+//!
+//! Empty queries are served in browse mode (`browse`): newest documents from
+//! the text index, optionally filtered by facet, with no embedding call.
+//!
+//! When the embedding model is missing the engine runs in a degraded,
+//! text-only mode (`EmbedderState::EmbedderUnavailable`) instead of failing.
+//!
+//! With `with_preprocessor`, chunks and queries are cleaned (see
+//! `localdb_core::preprocess`) before they are embedded; the text leg keeps
+//! the original text.
+//!
+//! With `with_vector_timeout`, the vector leg (query embedding + `search_vec`)
+//! runs on its own thread; if it misses the deadline (cold mmap, huge nprobes)
+//! the text leg is served alone and `query_outcome` marks the result partial.
+//! At most `with_max_vector_legs` such threads run at once; past that the
+//! query is served from the text leg alone, also marked partial.
+//!
+//! With `with_hooks`, the `pre_index`, `pre_query` and `post_fusion` hooks of
+//! a `localdb_core::hooks::HookRegistry` run on every indexed batch, query and
+//! fused result list.
+//!
+//! `query_variants` searches several phrasings of one question at once (the
+//! user's own, or paraphrases such as `localdb_core::answer::keywords`): the
+//! variants are embedded in one batch, each is searched and fused on its own,
+//! and the per-variant rankings are merged by reciprocal rank. Cheap recall
+//! for vague queries; a hit found by several phrasings rises.
+//!
+//! `penalize_rejected` applies "not like this" feedback to fused hits: using
+//! the vectors stored with the chunks (`VectorIndexer::vectors`), each hit is
+//! pushed down by its cosine similarity to the closest rejected chunk, and
+//! the rejected chunks themselves are dropped.
+//!
+//! With `with_multi_vectors`, an embedder with token-level output
+//! (`Embedder::multi_vector`, BGE-M3's ColBERT head) also stores each chunk's
+//! token vectors (`VectorIndexer::index_token_vectors`), and the vector leg
+//! fetches `MULTI_VECTOR_CANDIDATES` times as many dense hits and rescores
+//! them by `max_sim` against the query's token vectors. Hits without stored
+//! token vectors keep their dense score.
+//!
+//! With `with_sparse`, an embedder with sparse output (`Embedder::sparse`,
+//! BGE-M3's lexical weights) also gives the text index each chunk's sparse
+//! vector and the text leg the query's (`TextIndexer::index_sparse`,
+//! `search_sparse`). Dense, sparse and token vectors of a batch come from one
+//! `Embedder::embed_passages_with` (or `embed_queries_with`) call, one forward
+//! pass for models that support it.
+//!
+//! With `with_reranker`, each leg fetches enough hits for the top `candidates`
+//! fused hits to be rescored by a `Reranker` (a cross-encoder, see
+//! `localdb-rerank`) reading the query with each chunk's stored text
+//! (`TextIndexer::texts`); the reranked hits come first, by its score. A
+//! reranker error is reported and the fused order kept.
+//!
+//! With `with_max_per_doc` (or per query, `QueryOptions::max_per_doc`), at
+//! most that many chunks of one document make the final top k: the legs
+//! fetch `DOC_CAP_DEPTH` times as many hits, and a document's chunks past
+//! its cap give their places to the next-best chunks of other documents.
+
 use anyhow::Result;
-use localdb_core::traits::{Embedder, TextIndexer, VectorIndexer, SearchEngine};
-use localdb_core::types::{DocumentChunk, SearchHit, SourceKind};
+use localdb_core::calibration::ScoreCalibration;
+use localdb_core::data_processor::doc_id_of;
+use localdb_core::error::Error as CoreError;
+use localdb_core::hooks::HookRegistry;
+use localdb_core::preprocess::Preprocessor;
+use localdb_core::progress;
+use localdb_core::traits::{Embedder, Reranker, TextIndexer, VectorIndexer, SearchEngine};
+use localdb_core::types::{DocumentChunk, Embedded, FusionWeights, Heads, SearchHit, SourceKind, SparseVector};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+/// Whether the engine has a working embedder.
+#[derive(Clone)]
+pub enum EmbedderState {
+    Ready(Arc<dyn Embedder>),
+    /// The model could not be loaded; only the text leg is served. Holds the reason.
+    EmbedderUnavailable(String),
+}
+
+impl EmbedderState {
+    /// State for the result of loading an embedder: a missing model is
+    /// `EmbedderUnavailable`, any other load error is returned unchanged.
+    pub fn from_result(embedder: Result<Box<dyn Embedder>>) -> Result<Self> {
+        match embedder {
+            Ok(e) => Ok(EmbedderState::Ready(Arc::from(e))),
+            Err(e) if matches!(e.downcast_ref::<CoreError>(), Some(CoreError::EmbedderUnavailable(_))) => Ok(EmbedderState::EmbedderUnavailable(e.to_string())),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Rank constant of reciprocal rank fusion (the usual 60).
+const RRF_K: f32 = 60.0;
+
+/// How text and vector hits are merged into one ranking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FusionStrategy {
+    /// Keep the higher weighted score per id.
+    #[default]
+    MaxScore,
+    /// Sum of the weighted scores per id; meant for calibrated scores.
+    WeightedSum,
+    /// Reciprocal rank fusion: sum over legs of `weight / (RRF_K + rank)`.
+    Rrf,
+}
+
+impl FusionStrategy {
+    /// Name used in config (`search.fusion.strategy`) and feedback logs.
+    pub fn name(self) -> &'static str {
+        match self {
+            FusionStrategy::MaxScore => "max_score",
+            FusionStrategy::WeightedSum => "weighted_sum",
+            FusionStrategy::Rrf => "rrf",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "max_score" => Some(FusionStrategy::MaxScore),
+            "weighted_sum" => Some(FusionStrategy::WeightedSum),
+            "rrf" => Some(FusionStrategy::Rrf),
+            _ => None,
+        }
+    }
+}
+
+/// Per-query settings that override the engine's.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryOptions {
+    /// At most this many chunks per document in the result; `None` keeps the
+    /// engine's cap (`with_max_per_doc`), `Some(0)` lifts it.
+    pub max_per_doc: Option<usize>,
+    /// Only chunks detected as this language (ISO 639-1); both legs filter
+    /// while searching (`TextIndexer::search_in`, `VectorIndexer::search_vec_in`).
+    pub lang: Option<String>,
+    /// Only chunks filed under this facet or its subfacets; both legs filter
+    /// while searching (`search_in_facet`, `search_vec_in_facet`), so a
+    /// sharded text index reads only that facet's shard.
+    pub facet: Option<String>,
+}
+
+/// Hits of one query; `partial` names the leg left out and why (e.g. the
+/// vector leg timed out), `None` when both legs answered.
+#[derive(Debug, Clone, Default)]
+pub struct QueryOutcome {
+    pub hits: Vec<SearchHit>,
+    pub partial: Option<String>,
+}
 
 pub struct HybridSearchEngine<TI, VI> where TI: TextIndexer, VI: VectorIndexer {
     text: TI,
-    vector: VI,
-    embedder: Box<dyn Embedder>,
+    vector: Arc<VI>,
+    embedder: EmbedderState,
+    strategy: FusionStrategy,
+    weights: FusionWeights,
+    calibration: ScoreCalibration,
+    vector_timeout: Option<Duration>,
+    max_vector_legs: usize,
+    vector_legs: Arc<AtomicUsize>,
+    preprocessor: Arc<Preprocessor>,
+    hooks: HookRegistry,
+    embed_batch_size: Option<usize>,
+    length_buckets: bool,
+    multi_vectors: bool,
+    sparse: bool,
+    reranker: Option<Arc<dyn Reranker>>,
+    rerank_candidates: usize,
+    max_per_doc: usize,
 }
 
-impl<TI, VI> HybridSearchEngine<TI, VI> where TI: TextIndexer, VI: VectorIndexer {
-    pub fn new(text: TI, vector: VI, embedder: Box<dyn Embedder>) -> Self { Self { text, vector, embedder } }
+/// Dense hits fetched per hit wanted when rescoring by MaxSim.
+pub const MULTI_VECTOR_CANDIDATES: usize = 4;
+
+/// Hits fetched per hit wanted under a per-document cap, so the chunks it
+/// drops can be replaced.
+pub const DOC_CAP_DEPTH: usize = 3;
+
+/// Vector legs running on their own threads at once under a timeout,
+/// abandoned ones included.
+pub const MAX_VECTOR_LEGS: usize = 4;
+
+impl<TI, VI> HybridSearchEngine<TI, VI> where TI: TextIndexer, VI: VectorIndexer + 'static {
+    pub fn new(text: TI, vector: VI, embedder: Box<dyn Embedder>) -> Self {
+        Self::with_state(text, vector, EmbedderState::Ready(Arc::from(embedder)))
+    }
+
+    /// Build an engine that serves text-only results because no embedder is available.
+    pub fn text_only(text: TI, vector: VI, reason: impl Into<String>) -> Self {
+        Self::with_state(text, vector, EmbedderState::EmbedderUnavailable(reason.into()))
+    }
+
+    fn with_state(text: TI, vector: VI, embedder: EmbedderState) -> Self {
+        Self { text, vector: Arc::new(vector), embedder, strategy: FusionStrategy::default(), weights: FusionWeights::default(), calibration: ScoreCalibration::default(), vector_timeout: None, max_vector_legs: MAX_VECTOR_LEGS, vector_legs: Arc::new(AtomicUsize::new(0)), preprocessor: Arc::new(Preprocessor::default()), hooks: HookRegistry::default(), embed_batch_size: None, length_buckets: false, multi_vectors: false, sparse: false, reranker: None, rerank_candidates: 0, max_per_doc: 0 }
+    }
+
+    /// Give up on the vector leg after `timeout` and serve text hits only
+    /// (`search.vector.timeout_ms` in the CLI). The abandoned search finishes
+    /// in the background and its hits are dropped.
+    pub fn with_vector_timeout(mut self, timeout: Duration) -> Self {
+        self.vector_timeout = Some(timeout);
+        self
+    }
+
+    /// Run at most `n` (at least 1) timed vector legs at once
+    /// (`search.vector.max_running` in the CLI); a query arriving while they
+    /// are all busy gets text hits only.
+    pub fn with_max_vector_legs(mut self, n: usize) -> Self {
+        self.max_vector_legs = n.max(1);
+        self
+    }
+
+    /// Clean chunk and query text with `preprocessor` before embedding
+    /// (`[embedding.preprocess]` in the CLI).
+    pub fn with_preprocessor(mut self, preprocessor: Preprocessor) -> Self {
+        self.preprocessor = Arc::new(preprocessor);
+        self
+    }
+
+    /// Embed chunks `batch_size` at a time when indexing (`embedding.batch_size`
+    /// in the CLI, see `localdb-cli bench-embed`); by default all in one call.
+    pub fn with_embed_batch_size(mut self, batch_size: usize) -> Self {
+        self.embed_batch_size = Some(batch_size.max(1));
+        self
+    }
+
+    /// Batch chunks of similar length together when indexing
+    /// (`embedding.sort_by_length` in the CLI): batches are padded to their
+    /// longest chunk, so mixing long and short chunks wastes most of a batch.
+    pub fn with_length_buckets(mut self, enabled: bool) -> Self {
+        self.length_buckets = enabled;
+        self
+    }
+
+    /// Store token vectors at indexing and rescore vector hits by MaxSim
+    /// (`embedding.multi_vector` in the CLI); no effect unless the embedder
+    /// has token-level output.
+    pub fn with_multi_vectors(mut self, enabled: bool) -> Self {
+        self.multi_vectors = enabled;
+        self
+    }
+
+    /// Give the text index each chunk's sparse vector at indexing
+    /// (`TextIndexer::index_sparse`) and the text leg the query's
+    /// (`search_sparse`), from the forward passes that give the dense ones
+    /// (`embedding.sparse` in the CLI); no effect unless the embedder has
+    /// sparse output.
+    pub fn with_sparse(mut self, enabled: bool) -> Self {
+        self.sparse = enabled;
+        self
+    }
+
+    /// Rescore the top `candidates` fused hits of every query with `reranker`
+    /// (`search.rerank.model` in the CLI).
+    pub fn with_reranker(mut self, reranker: Arc<dyn Reranker>, candidates: usize) -> Self {
+        self.reranker = Some(reranker);
+        self.rerank_candidates = candidates;
+        self
+    }
+
+    /// Return at most `max_per_doc` chunks of any one document per query
+    /// (`search.max_per_doc` in the CLI); 0, the default, sets no cap.
+    pub fn with_max_per_doc(mut self, max_per_doc: usize) -> Self {
+        self.max_per_doc = max_per_doc;
+        self
+    }
+
+    /// Merge legs with `strategy` and per-leg `weights`.
+    pub fn with_fusion(mut self, strategy: FusionStrategy, weights: FusionWeights) -> Self {
+        self.strategy = strategy;
+        self.weights = weights;
+        self
+    }
+
+    pub fn fusion_strategy(&self) -> FusionStrategy { self.strategy }
+
+    /// Map raw leg scores through `calibration` before the score strategies
+    /// weight them (rank fusion ignores scores).
+    pub fn with_calibration(mut self, calibration: ScoreCalibration) -> Self {
+        self.calibration = calibration;
+        self
+    }
+
+    /// Run the `pre_index`, `pre_query` and `post_fusion` hooks of `hooks`.
+    pub fn with_hooks(mut self, hooks: HookRegistry) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Build from the result of loading an embedder. A missing model degrades to
+    /// text-only mode; any other load error is returned unchanged.
+    pub fn from_embedder_result(text: TI, vector: VI, embedder: Result<Box<dyn Embedder>>) -> Result<Self> {
+        Ok(Self::with_state(text, vector, EmbedderState::from_result(embedder)?))
+    }
+
+    /// Build around an embedder already loaded (or known missing), so a
+    /// long-running caller such as `ingest --watch` loads the model once.
+    pub fn from_state(text: TI, vector: VI, embedder: EmbedderState) -> Self { Self::with_state(text, vector, embedder) }
+
+    pub fn embedder_state(&self) -> &EmbedderState { &self.embedder }
+
+    /// The text leg, e.g. to show how it rewrites a query.
+    pub fn text(&self) -> &TI { &self.text }
+
+    /// The vector leg.
+    pub fn vector(&self) -> &VI { &self.vector }
+
+    /// True when the vector leg is disabled for lack of an embedder.
+    pub fn is_degraded(&self) -> bool { matches!(self.embedder, EmbedderState::EmbedderUnavailable(_)) }
 
     pub fn index(&self, chunks: &[DocumentChunk]) -> Result<()> {
-        // 1) embed in batches
-        let batch_texts: Vec<String> = chunks.iter().map(|c| c.content.clone()).collect();
-        let embeddings = self.embedder.embed_batch(&batch_texts)?;
-        for e in &embeddings { assert_eq!(e.len(), self.embedder.dim()); }
-        // 2) vector index
-        self.vector.index(chunks, &embeddings)?;
+        let chunks: Cow<[DocumentChunk]> = if self.hooks.is_empty() { Cow::Borrowed(chunks) } else {
+            let mut owned = chunks.to_vec();
+            self.hooks.pre_index(&mut owned)?;
+            Cow::Owned(owned)
+        };
+        let chunks = chunks.as_ref();
+        match &self.embedder {
+            EmbedderState::Ready(embedder) => {
+                // 1) embed in batches, with the sparse and token vectors of each
+                // batch from the same forward pass; token vectors are stored as they come
+                let batch_texts = self.preprocessor.embedding_texts(chunks);
+                let order: Vec<usize> = if self.length_buckets { length_order(&batch_texts) } else { (0..chunks.len()).collect() };
+                let sorted: Vec<String> = order.iter().map(|&i| batch_texts[i].clone()).collect();
+                let batch_size = self.embed_batch_size.unwrap_or(chunks.len()).max(1);
+                let heads = Heads { sparse: self.sparse, tokens: self.multi_vectors };
+                progress::report("embed", 0, Some(chunks.len() as u64));
+                let mut embeddings = vec![Vec::new(); chunks.len()];
+                let mut sparse: Option<Vec<SparseVector>> = None;
+                for (i, (positions, batch)) in order.chunks(batch_size).zip(sorted.chunks(batch_size)).enumerate() {
+                    let out = embedder.embed_passages_with(batch, heads)?;
+                    for (&p, e) in positions.iter().zip(out.dense) { embeddings[p] = e; }
+                    if let Some(vectors) = out.sparse {
+                        let all = sparse.get_or_insert_with(|| vec![SparseVector::new(); chunks.len()]);
+                        for (&p, v) in positions.iter().zip(vectors) { all[p] = v; }
+                    }
+                    if let Some(tokens) = out.tokens {
+                        let ids: Vec<String> = positions.iter().map(|&p| chunks[p].id.clone()).collect();
+                        self.vector.index_token_vectors(&ids, &tokens)?;
+                    }
+                    progress::report("embed", ((i + 1) * batch_size).min(chunks.len()) as u64, Some(chunks.len() as u64));
+                }
+                for e in &embeddings { assert_eq!(e.len(), embedder.dim()); }
+                // 2) vector index
+                self.vector.index(chunks, &embeddings)?;
+                // 3) text index, with the sparse vectors if asked for
+                if let Some(sparse) = sparse { return self.text.index_sparse(chunks, &sparse); }
+            }
+            EmbedderState::EmbedderUnavailable(reason) => {
+                eprintln!("⚠️  Skipping vector indexing ({}); only the text index will be updated", reason);
+            }
+        }
         // 3) text index
         self.text.index(chunks)
     }
 
+    /// Browse mode: top `k` documents from the text index, optionally under
+    /// `facet`. Used for empty queries so a UI can show content before typing.
+    pub fn browse(&self, facet: Option<&str>, k: usize) -> Result<Vec<SearchHit>> {
+        let mut hits = self.text.browse(facet, k)?;
+        for h in &mut hits { h.source = SourceKind::Text; }
+        Ok(hits)
+    }
+
+    /// Hybrid search. An empty or whitespace-only query browses instead.
     pub fn query(&self, query: &str, k: usize) -> Result<Vec<SearchHit>> {
-        let q_vec = self.embedder.embed_batch(&[query.to_string()])?.remove(0);
-        let mut dense_hits = self.vector.search_vec(&q_vec, k)?;
-        for h in &mut dense_hits { h.source = SourceKind::Vector; }
-        let mut text_hits = self.text.search(query, k)?;
-        for h in &mut text_hits { h.source = SourceKind::Text; }
-        // merge unique ids, prioritize better score
-        use std::collections::HashMap;
-        let mut by_id: HashMap<String, SearchHit> = HashMap::new();
-        for h in dense_hits.into_iter().chain(text_hits.into_iter()) {
-            by_id.entry(h.id.clone()).and_modify(|old| { if h.score > old.score { *old = h.clone(); } }).or_insert(h);
-        }
-        let mut merged: Vec<SearchHit> = by_id.into_values().collect();
+        Ok(self.query_outcome(query, k)?.hits)
+    }
+
+    /// `query`, reporting whether the vector leg was cut off by the timeout.
+    pub fn query_outcome(&self, query: &str, k: usize) -> Result<QueryOutcome> {
+        self.query_outcome_with(query, k, QueryOptions::default())
+    }
+
+    /// `query_outcome` with per-query `options`.
+    pub fn query_outcome_with(&self, query: &str, k: usize, options: QueryOptions) -> Result<QueryOutcome> {
+        self.hooked_query_outcome(&self.hooks.pre_query(query)?, k, options)
+    }
+
+    /// `query_outcome_with` for a query the `pre_query` hooks already saw.
+    fn hooked_query_outcome(&self, query: &str, k: usize, options: QueryOptions) -> Result<QueryOutcome> {
+        if query.trim().is_empty() { return Ok(QueryOutcome { hits: self.browse(options.facet.as_deref(), k)?, partial: None }); }
+        let cap = options.max_per_doc.unwrap_or(self.max_per_doc);
+        let (mut fused, partial) = self.fused_per_query(&[query.to_string()], self.depth(k, cap), options.lang.as_deref(), options.facet.as_deref())?;
+        let mut merged = fused.remove(0);
+        self.hooks.post_fusion(query, &mut merged)?;
         merged.sort_by(|a,b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        self.rerank(query, &mut merged);
+        cap_per_doc(&mut merged, cap);
         merged.truncate(k);
-        Ok(merged)
+        Ok(QueryOutcome { hits: merged, partial })
+    }
+
+    /// Multi-query search: `query_outcome` over several phrasings of one
+    /// query, embedded as one batch, each searched and fused on its own, the
+    /// rankings then merged by reciprocal rank (scores are `1 / (RRF_K + rank)`
+    /// summed over variants). Empty and repeated variants are dropped; one
+    /// variant left is a plain `query_outcome`. `post_fusion` sees the first.
+    pub fn query_variants(&self, variants: &[String], k: usize) -> Result<QueryOutcome> {
+        self.query_variants_with(variants, k, QueryOptions::default())
+    }
+
+    /// `query_variants` with per-query `options`.
+    pub fn query_variants_with(&self, variants: &[String], k: usize, options: QueryOptions) -> Result<QueryOutcome> {
+        let mut queries: Vec<String> = Vec::new();
+        for v in variants {
+            let q = self.hooks.pre_query(v)?;
+            if !q.trim().is_empty() && !queries.iter().any(|seen| seen.trim().eq_ignore_ascii_case(q.trim())) { queries.push(q); }
+        }
+        if queries.len() < 2 { return self.hooked_query_outcome(queries.first().map_or("", String::as_str), k, options); }
+        let cap = options.max_per_doc.unwrap_or(self.max_per_doc);
+        let (fused, partial) = self.fused_per_query(&queries, self.depth(k, cap), options.lang.as_deref(), options.facet.as_deref())?;
+        let mut merged = rank_fusion(fused.into_iter().map(|mut hits| {
+            hits.sort_by(|a,b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+            hits
+        }).collect());
+        self.hooks.post_fusion(&queries[0], &mut merged)?;
+        merged.sort_by(|a,b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        self.rerank(&queries[0], &mut merged);
+        cap_per_doc(&mut merged, cap);
+        merged.truncate(k);
+        Ok(QueryOutcome { hits: merged, partial })
+    }
+
+    /// Hits each leg fetches for `k` results: enough to fill the reranker's
+    /// candidates, and to spare under a per-document cap of `cap`.
+    fn depth(&self, k: usize, cap: usize) -> usize {
+        let k = if cap > 0 { k * DOC_CAP_DEPTH } else { k };
+        if self.reranker.is_some() { k.max(self.rerank_candidates) } else { k }
+    }
+
+    /// Rescore the first `rerank_candidates` of `hits` (sorted best first)
+    /// with the reranker and move them to the front, best first. Hits it did
+    /// not read (past the candidates, or whose text the text index lacks)
+    /// follow in their fused order, scored as the lowest reranked hit. If
+    /// reranking fails (out of memory, say) `hits` keep their fused order.
+    fn rerank(&self, query: &str, hits: &mut Vec<SearchHit>) {
+        let Some(reranker) = &self.reranker else { return };
+        match self.reranked(reranker.as_ref(), query, hits.clone()) {
+            Ok(reranked) => *hits = reranked,
+            Err(e) => eprintln!("⚠️  Reranking failed ({:#}); showing results in fused order", e),
+        }
+    }
+
+    /// `hits` with their first `rerank_candidates` rescored by `reranker`, as `rerank`.
+    fn reranked(&self, reranker: &dyn Reranker, query: &str, mut hits: Vec<SearchHit>) -> Result<Vec<SearchHit>> {
+        let tail = hits.split_off(self.rerank_candidates.min(hits.len()));
+        let texts = self.text.texts(&hits.iter().map(|h| h.id.clone()).collect::<Vec<_>>())?;
+        let (mut read, unread): (Vec<SearchHit>, Vec<SearchHit>) = std::mem::take(&mut hits).into_iter().partition(|h| texts.contains_key(&h.id));
+        let passages: Vec<String> = read.iter().map(|h| texts.get(&h.id).cloned().unwrap_or_default()).collect();
+        if !passages.is_empty() {
+            let scores = reranker.score(query, &passages)?;
+            anyhow::ensure!(scores.len() == passages.len(), "reranker gave {} scores for {} passages", scores.len(), passages.len());
+            for (h, score) in read.iter_mut().zip(scores) { h.score = score; }
+            read.sort_by(|a,b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        }
+        let floor = read.last().map(|h| h.score);
+        hits.extend(read);
+        hits.extend(unread.into_iter().chain(tail).map(|h| SearchHit { score: floor.unwrap_or(h.score), ..h }));
+        Ok(hits)
+    }
+
+    /// Both legs of every query in `queries` (top `k` each, in `lang` and
+    /// under `facet` if given), fused per query (unsorted), and which leg was
+    /// left out if the vector leg timed out.
+    fn fused_per_query(&self, queries: &[String], k: usize, lang: Option<&str>, facet: Option<&str>) -> Result<(Vec<Vec<SearchHit>>, Option<String>)> {
+        let (pending, sparse) = match &self.embedder {
+            // The text legs need the queries' sparse vectors, which come with
+            // the dense ones: embed first, outside the vector timeout.
+            EmbedderState::Ready(embedder) if self.sparse && embedder.sparse().is_some() => {
+                let texts: Vec<String> = queries.iter().map(|q| self.preprocessor.clean(q)).collect();
+                let mut embedded = embedder.embed_queries_with(&texts, Heads { sparse: true, tokens: self.multi_vectors })?;
+                let sparse = embedded.sparse.take();
+                (Some(self.spawn_vector_leg(embedder.clone(), queries, Some(embedded), k, lang, facet)), sparse)
+            }
+            EmbedderState::Ready(embedder) => (Some(self.spawn_vector_leg(embedder.clone(), queries, None, k, lang, facet)), None),
+            EmbedderState::EmbedderUnavailable(_) => (None, None),
+        };
+        let mut text_legs = Vec::with_capacity(queries.len());
+        for (i, query) in queries.iter().enumerate() {
+            let mut text_hits = match sparse.as_ref().and_then(|s| s.get(i)) {
+                Some(weights) => self.text.search_sparse(query, weights, k, lang, facet)?,
+                None => self.text.search_in_facet(query, k, lang, facet)?,
+            };
+            for h in &mut text_hits { h.source = SourceKind::Text; }
+            text_legs.push(text_hits);
+        }
+        let (dense_legs, partial) = match pending {
+            None => (Vec::new(), None),
+            Some(VectorLeg::Done(legs)) => (legs?, None),
+            Some(VectorLeg::Busy) => {
+                let reason = format!("vector search busy ({} searches still running)", self.max_vector_legs);
+                eprintln!("⚠️  {}; showing text results only", reason);
+                (Vec::new(), Some(reason))
+            }
+            Some(VectorLeg::Running { rx, deadline, timeout }) => {
+                match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(legs) => (legs?, None),
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        let reason = format!("vector search timed out after {} ms", timeout.as_millis());
+                        eprintln!("⚠️  {}; showing text results only", reason);
+                        (Vec::new(), Some(reason))
+                    }
+                    Err(mpsc::RecvTimeoutError::Disconnected) => anyhow::bail!("vector search thread panicked"),
+                }
+            }
+        };
+        let mut dense_legs = dense_legs.into_iter();
+        let fused = text_legs.into_iter().map(|text_hits| {
+            let mut dense_hits = dense_legs.next().unwrap_or_default();
+            for h in &mut dense_hits { h.source = SourceKind::Vector; }
+            self.fuse(dense_hits, text_hits)
+        }).collect();
+        Ok((fused, partial))
+    }
+
+    /// Raw, unfused hits of both legs (text first), labelled by source and
+    /// without the vector timeout; `localdb-cli calibrate` fits on these.
+    pub fn leg_hits(&self, query: &str, k: usize) -> Result<Vec<SearchHit>> {
+        let query = &self.hooks.pre_query(query)?;
+        let mut hits = self.text.search(query, k)?;
+        for h in &mut hits { h.source = SourceKind::Text; }
+        if let EmbedderState::Ready(embedder) = &self.embedder {
+            let embedded = embedder.embed_queries_with(&[self.preprocessor.clean(query)], Heads { sparse: false, tokens: self.multi_vectors })?;
+            let dense = dense_legs(self.vector.as_ref(), &embedded, k, None, None)?;
+            hits.extend(dense.into_iter().flatten().map(|h| SearchHit { source: SourceKind::Vector, ..h }));
+        }
+        Ok(hits)
+    }
+
+    /// Drop the `rejected` chunks from `hits` and scale every other hit's
+    /// score by `1 - weight * cos` (`cos` the highest cosine similarity of its
+    /// stored vector to a rejected chunk's, floored at 0), then re-sort. Hits
+    /// or rejected chunks without a stored vector are left as they are.
+    pub fn penalize_rejected(&self, hits: &mut Vec<SearchHit>, rejected: &[String], weight: f32) -> Result<()> {
+        if rejected.is_empty() { return Ok(()); }
+        hits.retain(|h| !rejected.contains(&h.id));
+        let ids: Vec<String> = rejected.iter().chain(hits.iter().map(|h| &h.id)).cloned().collect();
+        let vectors = self.vector.vectors(&ids)?;
+        let negatives: Vec<&Vec<f32>> = rejected.iter().filter_map(|id| vectors.get(id)).collect();
+        if negatives.is_empty() { return Ok(()); }
+        for h in hits.iter_mut() {
+            let Some(v) = vectors.get(&h.id) else { continue };
+            let closest = negatives.iter().map(|n| cosine(v, n)).fold(0.0f32, f32::max);
+            h.score *= 1.0 - weight * closest;
+        }
+        hits.sort_by(|a,b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(())
+    }
+
+    /// Embed `queries` in one batch (unless `embedded` already holds them)
+    /// and search the vector index for each: inline without a timeout,
+    /// otherwise on a detached thread so the text legs run meanwhile, unless
+    /// `max_vector_legs` of them are still running.
+    fn spawn_vector_leg(&self, embedder: Arc<dyn Embedder>, queries: &[String], embedded: Option<Embedded>, k: usize, lang: Option<&str>, facet: Option<&str>) -> VectorLeg {
+        let (vector, preprocessor) = (self.vector.clone(), self.preprocessor.clone());
+        let heads = Heads { sparse: false, tokens: self.multi_vectors };
+        let texts: Vec<String> = queries.iter().map(|q| preprocessor.clean(q)).collect();
+        let (lang, facet) = (lang.map(str::to_string), facet.map(str::to_string));
+        let run = move || {
+            let embedded = match embedded { Some(e) => e, None => embedder.embed_queries_with(&texts, heads)? };
+            dense_legs(vector.as_ref(), &embedded, k, lang.as_deref(), facet.as_deref())
+        };
+        let Some(timeout) = self.vector_timeout else { return VectorLeg::Done(run()) };
+        let Some(slot) = LegSlot::take(&self.vector_legs, self.max_vector_legs) else { return VectorLeg::Busy };
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || { let _slot = slot; let _ = tx.send(run()); });
+        VectorLeg::Running { rx, deadline: Instant::now() + timeout, timeout }
+    }
+
+    /// Merge unique ids across both legs (unsorted). The surviving hit keeps
+    /// the source of the leg that contributed most, and document metadata from
+    /// whichever leg had it.
+    fn fuse(&self, dense_hits: Vec<SearchHit>, text_hits: Vec<SearchHit>) -> Vec<SearchHit> {
+        let weight = |source: SourceKind| match source { SourceKind::Text => self.weights.text, SourceKind::Vector => self.weights.vector };
+        // id -> (fused hit, best single-leg contribution)
+        let mut by_id: HashMap<String, (SearchHit, f32)> = HashMap::new();
+        for leg in [dense_hits, text_hits] {
+            for (rank, mut h) in leg.into_iter().enumerate() {
+                let part = match self.strategy {
+                    FusionStrategy::MaxScore | FusionStrategy::WeightedSum => self.calibration.apply(h.source, h.score) * weight(h.source),
+                    FusionStrategy::Rrf => weight(h.source) / (RRF_K + rank as f32 + 1.0),
+                };
+                h.score = part;
+                match by_id.get_mut(&h.id) {
+                    Some((old, best)) => {
+                        let total = match self.strategy { FusionStrategy::MaxScore => old.score.max(part), FusionStrategy::WeightedSum | FusionStrategy::Rrf => old.score + part };
+                        if part > *best { old.source = h.source; *best = part; }
+                        old.score = total;
+                        old.fill_from(&h);
+                    }
+                    None => { by_id.insert(h.id.clone(), (h, part)); }
+                }
+            }
+        }
+        by_id.into_values().map(|(h, _)| h).collect()
+    }
+}
+
+/// Search the vector index for each query embedded in `embedded` (top `k`,
+/// in `lang` and under `facet` if given), rescoring by MaxSim when it holds
+/// the queries' token vectors.
+fn dense_legs<VI: VectorIndexer>(vector: &VI, embedded: &Embedded, k: usize, lang: Option<&str>, facet: Option<&str>) -> Result<Vec<Vec<SearchHit>>> {
+    let Some(q_tokens) = &embedded.tokens else { return embedded.dense.iter().map(|q_vec| vector.search_vec_in_facet(q_vec, k, lang, facet)).collect() };
+    embedded.dense.iter().zip(q_tokens).map(|(q_vec, tokens)| {
+        let mut hits = vector.search_vec_in_facet(q_vec, k * MULTI_VECTOR_CANDIDATES, lang, facet)?;
+        let stored = vector.token_vectors(&hits.iter().map(|h| h.id.clone()).collect::<Vec<_>>())?;
+        for h in &mut hits {
+            if let Some(doc) = stored.get(&h.id) { h.score = max_sim(tokens, doc); }
+        }
+        hits.sort_by(|a,b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        hits.truncate(k);
+        Ok(hits)
+    }).collect()
+}
+
+/// ColBERT late interaction: each query token's highest dot product with a
+/// document token (cosine, vectors being L2-normalized), averaged over the
+/// query's tokens so scores stay in the range of a single cosine. 0 when
+/// either side has no tokens.
+pub fn max_sim(query: &[Vec<f32>], doc: &[Vec<f32>]) -> f32 {
+    if query.is_empty() || doc.is_empty() { return 0.0; }
+    let best = |q: &Vec<f32>| doc.iter().map(|d| q.iter().zip(d).map(|(x, y)| x * y).sum::<f32>()).fold(f32::MIN, f32::max);
+    query.iter().map(best).sum::<f32>() / query.len() as f32
+}
+
+/// Positions of `texts` from shortest to longest (ties keep their order), so
+/// consecutive batches hold texts of similar length.
+pub fn length_order(texts: &[String]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..texts.len()).collect();
+    order.sort_by_key(|&i| texts[i].len());
+    order
+}
+
+/// Drop the chunks of each document past its first `max_per_doc` in `hits`
+/// (sorted best first), so the next-best chunks of other documents move up;
+/// 0 keeps every hit. Documents are told apart by `doc_id_of` the chunk id.
+pub fn cap_per_doc(hits: &mut Vec<SearchHit>, max_per_doc: usize) {
+    if max_per_doc == 0 { return; }
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    hits.retain(|h| {
+        let n = seen.entry(doc_id_of(&h.id).to_string()).or_insert(0);
+        *n += 1;
+        *n <= max_per_doc
+    });
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denom = norm(a) * norm(b);
+    if denom == 0.0 { 0.0 } else { dot / denom }
+}
+
+/// Merge rankings (each sorted best first) by reciprocal rank: a hit scores
+/// `1 / (RRF_K + rank)` summed over the rankings it is in, and keeps the
+/// source of the ranking that placed it highest. Not sorted.
+pub fn rank_fusion(rankings: Vec<Vec<SearchHit>>) -> Vec<SearchHit> {
+    let mut by_id: HashMap<String, (SearchHit, f32)> = HashMap::new();
+    for ranking in rankings {
+        for (rank, mut h) in ranking.into_iter().enumerate() {
+            let part = 1.0 / (RRF_K + rank as f32 + 1.0);
+            h.score = part;
+            match by_id.get_mut(&h.id) {
+                Some((old, best)) => {
+                    if part > *best { old.source = h.source; *best = part; }
+                    old.score += part;
+                    old.fill_from(&h);
+                }
+                None => { by_id.insert(h.id.clone(), (h, part)); }
+            }
+        }
+    }
+    by_id.into_values().map(|(h, _)| h).collect()
+}
+
+/// Vector hits per query, the channel they arrive on before `deadline`, or
+/// `Busy` when no thread was free to search.
+enum VectorLeg {
+    Done(Result<Vec<Vec<SearchHit>>>),
+    Busy,
+    Running { rx: mpsc::Receiver<Result<Vec<Vec<SearchHit>>>>, deadline: Instant, timeout: Duration },
+}
+
+/// One of the `max` running vector legs counted by `running`, given back
+/// when dropped (also when the search panics).
+struct LegSlot(Arc<AtomicUsize>);
+
+impl LegSlot {
+    fn take(running: &Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        running.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| (n < max).then_some(n + 1)).ok()?;
+        Some(Self(running.clone()))
     }
 }
 
-impl<TI, VI> SearchEngine for HybridSearchEngine<TI, VI> where TI: TextIndexer, VI: VectorIndexer {
+impl Drop for LegSlot {
+    fn drop(&mut self) { self.0.fetch_sub(1, Ordering::AcqRel); }
+}
+
+impl<TI, VI> SearchEngine for HybridSearchEngine<TI, VI> where TI: TextIndexer, VI: VectorIndexer + 'static {
     fn index(&self, chunks: &[DocumentChunk]) -> Result<()> { Self::index(self, chunks) }
     fn query(&self, query: &str, k: usize) -> Result<Vec<SearchHit>> { Self::query(self, query, k) }
 }
-//! localdb-hybrid
-//!
-//! Thin façade that composes a text indexer and a vector indexer behind one
-//! `SearchEngine` trait. The engine indexes by embedding chunks once and writing
-//! to both backends, and queries by embedding the query once then merging hits.
-//!
-//! The merge prefers higher scores for duplicate ids and labels each hit with
-//! `SourceKind` so downstream callers can understand origin.
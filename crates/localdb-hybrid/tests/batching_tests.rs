@@ -0,0 +1,93 @@
+use std::sync::{Arc, Mutex};
+
+use localdb_core::traits::{Embedder, SparseEmbedder, TextIndexer};
+use localdb_core::types::{DocumentChunk, Embedded, Heads, SearchHit, SparseVector};
+use localdb_hybrid::{length_order, HybridSearchEngine};
+use localdb_testkit::{chunk, FakeTextIndexer, FakeVectorIndexer};
+
+/// Embeds a text as its length and records the lengths of each batch.
+struct Lengths(Arc<Mutex<Vec<Vec<usize>>>>);
+impl Embedder for Lengths {
+    fn dim(&self) -> usize { 1 }
+    fn max_len(&self) -> usize { 8 }
+    fn embed_batch(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        self.0.lock().expect("lock").push(texts.iter().map(String::len).collect());
+        Ok(texts.iter().map(|t| vec![t.len() as f32]).collect())
+    }
+}
+
+fn chunks() -> Vec<DocumentChunk> {
+    vec![chunk("a", "long long long"), chunk("b", "x"), chunk("c", "long long long long"), chunk("d", "xy")]
+}
+
+#[test]
+fn length_order_is_shortest_first_and_stable() {
+    let texts: Vec<String> = ["ccc", "a", "bb", "d"].iter().map(|s| s.to_string()).collect();
+    assert_eq!(length_order(&texts), [1, 3, 2, 0]);
+}
+
+#[test]
+fn length_buckets_batch_similar_chunks_and_keep_vectors_with_their_chunks() -> anyhow::Result<()> {
+    let batches = Arc::new(Mutex::new(Vec::new()));
+    let engine = HybridSearchEngine::new(FakeTextIndexer::new(), FakeVectorIndexer::new(), Box::new(Lengths(batches.clone()))).with_embed_batch_size(2);
+    engine.index(&chunks())?;
+    assert_eq!(*batches.lock().unwrap(), [vec![14, 1], vec![19, 2]], "input order by default");
+
+    let batches = Arc::new(Mutex::new(Vec::new()));
+    let engine = HybridSearchEngine::new(FakeTextIndexer::new(), FakeVectorIndexer::new(), Box::new(Lengths(batches.clone()))).with_embed_batch_size(2).with_length_buckets(true);
+    engine.index(&chunks())?;
+    assert_eq!(*batches.lock().unwrap(), [vec![1, 2], vec![14, 19]]);
+    for c in chunks() {
+        assert_eq!(engine.vector().vector(&c.id), Some(vec![c.content.len() as f32]), "vector of {}", c.id);
+    }
+    Ok(())
+}
+
+/// Embeds a text as its length, with one sparse weight per word, and counts
+/// its calls.
+struct Words(Arc<Mutex<usize>>);
+impl Embedder for Words {
+    fn dim(&self) -> usize { 1 }
+    fn max_len(&self) -> usize { 8 }
+    fn embed_batch(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> { Ok(texts.iter().map(|t| vec![t.len() as f32]).collect()) }
+    fn sparse(&self) -> Option<&dyn SparseEmbedder> { Some(self) }
+    fn embed_passages_with(&self, texts: &[String], heads: Heads) -> anyhow::Result<Embedded> {
+        *self.0.lock().expect("lock") += 1;
+        Ok(Embedded { dense: self.embed_batch(texts)?, sparse: if heads.sparse { Some(self.embed_sparse(texts)?) } else { None }, tokens: None })
+    }
+}
+impl SparseEmbedder for Words {
+    fn embed_sparse(&self, texts: &[String]) -> anyhow::Result<Vec<SparseVector>> {
+        Ok(texts.iter().map(|t| vec![(t.split_whitespace().count() as u32, 1.0)]).collect())
+    }
+}
+
+/// Records the sparse vectors it is given.
+#[derive(Default)]
+struct SparseText { indexed: Mutex<Vec<(String, SparseVector)>>, queries: Mutex<Vec<SparseVector>> }
+impl TextIndexer for SparseText {
+    fn index(&self, chunks: &[DocumentChunk]) -> anyhow::Result<()> { self.index_sparse(chunks, &vec![SparseVector::new(); chunks.len()]) }
+    fn index_sparse(&self, chunks: &[DocumentChunk], sparse: &[SparseVector]) -> anyhow::Result<()> {
+        self.indexed.lock().expect("lock").extend(chunks.iter().map(|c| c.id.clone()).zip(sparse.iter().cloned()));
+        Ok(())
+    }
+    fn search(&self, _query: &str, _k: usize) -> anyhow::Result<Vec<SearchHit>> { Ok(Vec::new()) }
+    fn search_sparse(&self, _query: &str, sparse: &SparseVector, _k: usize, _lang: Option<&str>, _facet: Option<&str>) -> anyhow::Result<Vec<SearchHit>> {
+        self.queries.lock().expect("lock").push(sparse.clone());
+        Ok(Vec::new())
+    }
+}
+
+#[test]
+fn sparse_vectors_come_with_each_batch_and_reach_the_text_index_and_queries() -> anyhow::Result<()> {
+    let calls = Arc::new(Mutex::new(0));
+    let engine = HybridSearchEngine::new(SparseText::default(), FakeVectorIndexer::new(), Box::new(Words(calls.clone()))).with_embed_batch_size(2).with_length_buckets(true).with_sparse(true);
+    engine.index(&chunks())?;
+    assert_eq!(*calls.lock().expect("lock"), 2, "one embedding call per batch");
+    let mut indexed = engine.text().indexed.lock().expect("lock").clone();
+    indexed.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(indexed, [("a".to_string(), vec![(3, 1.0)]), ("b".to_string(), vec![(1, 1.0)]), ("c".to_string(), vec![(4, 1.0)]), ("d".to_string(), vec![(1, 1.0)])]);
+    engine.query("two words", 5)?;
+    assert_eq!(*engine.text().queries.lock().expect("lock"), [vec![(2, 1.0)]]);
+    Ok(())
+}
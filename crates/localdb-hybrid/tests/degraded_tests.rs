@@ -0,0 +1,45 @@
+use localdb_core::error::Error as CoreError;
+use localdb_core::traits::{Embedder, TextIndexer, VectorIndexer};
+use localdb_core::types::{DocumentChunk, SearchHit, SourceKind};
+use localdb_hybrid::HybridSearchEngine;
+
+struct OneHitText;
+impl TextIndexer for OneHitText {
+    fn index(&self, _chunks: &[DocumentChunk]) -> anyhow::Result<()> { Ok(()) }
+    fn search(&self, _query: &str, _k: usize) -> anyhow::Result<Vec<SearchHit>> {
+        Ok(vec![SearchHit::new("a:0", 1.0, SourceKind::Text)])
+    }
+}
+
+struct PanicVector;
+impl VectorIndexer for PanicVector {
+    fn index(&self, _chunks: &[DocumentChunk], _embeddings: &[Vec<f32>]) -> anyhow::Result<()> { panic!("vector leg must be skipped") }
+    fn search_vec(&self, _q: &[f32], _k: usize) -> anyhow::Result<Vec<SearchHit>> { panic!("vector leg must be skipped") }
+}
+
+#[test]
+fn missing_model_degrades_to_text_only() {
+    let missing: anyhow::Result<Box<dyn Embedder>> = Err(CoreError::EmbedderUnavailable("no model".to_string()).into());
+    let engine = HybridSearchEngine::from_embedder_result(OneHitText, PanicVector, missing).expect("degraded engine");
+    assert!(engine.is_degraded());
+
+    let hits = engine.query("anything", 5).expect("query");
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].source, SourceKind::Text);
+    engine.index(&[]).expect("text-only index");
+}
+
+#[test]
+fn other_load_errors_are_not_swallowed() {
+    let broken: anyhow::Result<Box<dyn Embedder>> = Err(anyhow::anyhow!("corrupt safetensors"));
+    assert!(HybridSearchEngine::from_embedder_result(OneHitText, PanicVector, broken).is_err());
+}
+
+#[test]
+fn a_loaded_state_is_reused_across_engines() {
+    use localdb_hybrid::EmbedderState;
+    let missing: anyhow::Result<Box<dyn Embedder>> = Err(CoreError::EmbedderUnavailable("no model".to_string()).into());
+    let state = EmbedderState::from_result(missing).expect("unavailable is a state");
+    for _ in 0..2 { assert!(HybridSearchEngine::from_state(OneHitText, PanicVector, state.clone()).is_degraded()); }
+    assert!(EmbedderState::from_result(Err(anyhow::anyhow!("corrupt safetensors"))).is_err());
+}
@@ -0,0 +1,58 @@
+use localdb_core::types::{SearchHit, SourceKind};
+use localdb_hybrid::{cap_per_doc, HybridSearchEngine, QueryOptions};
+use localdb_testkit::{chunk, chunk_in, FakeEmbedder, FakeTextIndexer, FakeVectorIndexer};
+
+/// An encyclopedia's chunks outscore everything else for "soap"; `k` is honoured.
+fn text() -> FakeTextIndexer {
+    let ids = ["encyclopedia:1", "encyclopedia:2", "encyclopedia:3", "encyclopedia:4", "pamphlet:1", "manual:1"];
+    let text = FakeTextIndexer::new();
+    text.script("soap", ids.iter().enumerate().map(|(i, id)| SearchHit::new(*id, 10.0 - i as f32, SourceKind::Text)).collect());
+    text
+}
+
+fn ids(hits: &[SearchHit]) -> Vec<&str> { hits.iter().map(|h| h.id.as_str()).collect() }
+
+#[test]
+fn a_document_cap_lets_other_documents_in() -> anyhow::Result<()> {
+    let uncapped = HybridSearchEngine::new(text(), FakeVectorIndexer::new(), Box::new(FakeEmbedder::new(1)));
+    assert_eq!(ids(&uncapped.query("soap", 3)?), ["encyclopedia:1", "encyclopedia:2", "encyclopedia:3"]);
+
+    let capped = HybridSearchEngine::new(text(), FakeVectorIndexer::new(), Box::new(FakeEmbedder::new(1))).with_max_per_doc(2);
+    assert_eq!(ids(&capped.query("soap", 3)?), ["encyclopedia:1", "encyclopedia:2", "pamphlet:1"], "the legs fetch deeper to refill the slot");
+
+    let lifted = capped.query_outcome_with("soap", 3, QueryOptions { max_per_doc: Some(0), ..Default::default() })?;
+    assert_eq!(ids(&lifted.hits), ["encyclopedia:1", "encyclopedia:2", "encyclopedia:3"]);
+    let tighter = capped.query_variants_with(&["soap".to_string()], 3, QueryOptions { max_per_doc: Some(1), ..Default::default() })?;
+    assert_eq!(ids(&tighter.hits), ["encyclopedia:1", "pamphlet:1", "manual:1"]);
+    Ok(())
+}
+
+#[test]
+fn a_language_filter_searches_within_that_language() -> anyhow::Result<()> {
+    let mut chunks: Vec<_> = (1..=5).map(|i| chunk(&format!("en{}:1", i), "soap soap soap")).collect();
+    chunks.push(localdb_core::types::DocumentChunk { lang: Some("de".into()), ..chunk("de:1", "soap") });
+    let engine = HybridSearchEngine::new(FakeTextIndexer::with_chunks(&chunks), FakeVectorIndexer::new(), Box::new(FakeEmbedder::new(1)));
+    let outcome = engine.query_outcome_with("soap", 2, QueryOptions { lang: Some("DE".into()), ..Default::default() })?;
+    assert_eq!(ids(&outcome.hits), ["de:1"], "the only German chunk ranks below k English ones");
+    Ok(())
+}
+
+#[test]
+fn a_facet_filter_searches_within_that_facet() -> anyhow::Result<()> {
+    let mut chunks: Vec<_> = (1..=5).map(|i| chunk_in(&format!("tools{}:1", i), "/tools/saws", "soap soap soap")).collect();
+    chunks.push(chunk_in("jam:1", "/preserves/jam", "soap"));
+    let engine = HybridSearchEngine::new(FakeTextIndexer::new(), FakeVectorIndexer::new(), Box::new(FakeEmbedder::new(1)));
+    engine.index(&chunks)?;
+    let outcome = engine.query_outcome_with("soap", 2, QueryOptions { facet: Some("/preserves".into()), ..Default::default() })?;
+    assert_eq!(ids(&outcome.hits), ["jam:1"], "both legs search under the facet");
+    Ok(())
+}
+
+#[test]
+fn cap_per_doc_keeps_the_best_chunks_of_each_document() {
+    let mut hits: Vec<SearchHit> = ["a:1", "b:1", "a:2", "a:3", "b:2"].iter().map(|id| SearchHit::new(*id, 1.0, SourceKind::Text)).collect();
+    cap_per_doc(&mut hits, 0);
+    assert_eq!(hits.len(), 5, "0 sets no cap");
+    cap_per_doc(&mut hits, 1);
+    assert_eq!(ids(&hits), ["a:1", "b:1"]);
+}
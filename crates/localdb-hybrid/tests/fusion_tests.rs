@@ -0,0 +1,196 @@
+use localdb_core::traits::{Embedder, TextIndexer, VectorIndexer};
+use localdb_core::types::{DocumentChunk, FusionWeights, SearchHit, SourceKind};
+use localdb_hybrid::{FusionStrategy, HybridSearchEngine};
+use localdb_testkit::{FakeEmbedder, FakeTextIndexer, FakeVectorIndexer};
+
+fn hit(id: &str, score: f32, source: SourceKind) -> SearchHit { SearchHit::new(id, score, source) }
+
+fn text() -> FakeTextIndexer {
+    let text = FakeTextIndexer::new();
+    for query in ["q", "color"] { text.script(query, vec![hit("shared", 9.0, SourceKind::Text), hit("text-only", 8.0, SourceKind::Text)]); }
+    text
+}
+
+/// Fixed vector hits; `shared` carries a title the text leg lacks.
+struct Vector;
+impl VectorIndexer for Vector {
+    fn index(&self, _chunks: &[DocumentChunk], _embeddings: &[Vec<f32>]) -> anyhow::Result<()> { Ok(()) }
+    fn search_vec(&self, _q: &[f32], _k: usize) -> anyhow::Result<Vec<SearchHit>> {
+        Ok(vec![hit("vec-only", 0.9, SourceKind::Vector), SearchHit { title: Some("Field Guide".into()), ..hit("shared", 0.8, SourceKind::Vector) }])
+    }
+}
+
+fn ids(hits: &[SearchHit]) -> Vec<&str> { hits.iter().map(|h| h.id.as_str()).collect() }
+
+#[test]
+fn max_score_is_the_default_and_honours_weights() {
+    let engine = HybridSearchEngine::new(text(), Vector, Box::new(FakeEmbedder::new(2)));
+    assert_eq!(engine.fusion_strategy(), FusionStrategy::MaxScore);
+    let hits = engine.query("q", 3).unwrap();
+    assert_eq!(ids(&hits), ["shared", "text-only", "vec-only"]);
+    assert_eq!((hits[0].source, hits[0].title.as_deref()), (SourceKind::Text, Some("Field Guide")), "metadata survives from the other leg");
+
+    let engine = HybridSearchEngine::new(text(), Vector, Box::new(FakeEmbedder::new(2))).with_fusion(FusionStrategy::MaxScore, FusionWeights { text: 0.05, vector: 1.0 });
+    let hits = engine.query("q", 3).unwrap();
+    assert_eq!(ids(&hits), ["vec-only", "shared", "text-only"]);
+    assert_eq!(hits[1].source, SourceKind::Vector, "shared hit keeps the leg that contributed most");
+}
+
+#[test]
+fn rrf_rewards_ids_found_by_both_legs() {
+    let engine = HybridSearchEngine::new(text(), Vector, Box::new(FakeEmbedder::new(2))).with_fusion(FusionStrategy::Rrf, FusionWeights::default());
+    let hits = engine.query("q", 3).unwrap();
+    assert_eq!(hits[0].id, "shared");
+    assert_eq!(hits[0].source, SourceKind::Text, "rank 1 in the text leg beats rank 2 in the vector leg");
+    assert!((hits[0].score - (1.0 / 61.0 + 1.0 / 62.0)).abs() < 1e-6);
+    assert_eq!(FusionStrategy::parse("rrf"), Some(FusionStrategy::Rrf));
+    assert_eq!(FusionStrategy::parse(FusionStrategy::MaxScore.name()), Some(FusionStrategy::MaxScore));
+}
+
+#[test]
+fn calibrated_weighted_sum_compares_legs_as_probabilities() {
+    use localdb_core::calibration::{Isotonic, ScoreCalibration};
+    let calibration = ScoreCalibration {
+        text: Some(Isotonic { xs: vec![0.0, 10.0], ys: vec![0.0, 0.5] }),
+        vector: Some(Isotonic { xs: vec![0.5, 1.0], ys: vec![0.0, 1.0] }),
+    };
+    let engine = HybridSearchEngine::new(text(), Vector, Box::new(FakeEmbedder::new(2))).with_fusion(FusionStrategy::WeightedSum, FusionWeights::default()).with_calibration(calibration);
+    let hits = engine.query("q", 3).unwrap();
+    // vec-only 0.8; shared 0.45 + 0.6; text-only 0.4.
+    assert_eq!(ids(&hits), ["shared", "vec-only", "text-only"]);
+    assert!((hits[0].score - 1.05).abs() < 1e-5, "{}", hits[0].score);
+    assert_eq!(hits[0].source, SourceKind::Vector);
+    assert_eq!(FusionStrategy::parse("weighted_sum"), Some(FusionStrategy::WeightedSum));
+
+    let raw = engine.leg_hits("q", 3).unwrap();
+    assert_eq!(raw.iter().map(|h| (h.id.as_str(), h.score, h.source)).collect::<Vec<_>>(), [
+        ("shared", 9.0, SourceKind::Text), ("text-only", 8.0, SourceKind::Text), ("vec-only", 0.9, SourceKind::Vector), ("shared", 0.8, SourceKind::Vector),
+    ]);
+}
+
+struct Rewrite;
+impl localdb_core::hooks::Hook for Rewrite {
+    fn name(&self) -> &str { "rewrite" }
+    fn pre_query(&self, query: &mut String) -> anyhow::Result<()> { *query = query.replace("colour", "color"); Ok(()) }
+    fn post_fusion(&self, query: &str, hits: &mut Vec<SearchHit>) -> anyhow::Result<()> {
+        assert_eq!(query, "color");
+        hits.retain(|h| h.id != "vec-only");
+        Ok(())
+    }
+}
+
+struct Failing;
+impl localdb_core::hooks::Hook for Failing {
+    fn name(&self) -> &str { "failing" }
+    fn post_fusion(&self, _query: &str, _hits: &mut Vec<SearchHit>) -> anyhow::Result<()> { anyhow::bail!("nope") }
+}
+
+#[test]
+fn hooks_rewrite_queries_and_filter_fused_hits_in_order() {
+    use localdb_core::hooks::HookRegistry;
+    use std::sync::Arc;
+    let engine = HybridSearchEngine::new(text(), Vector, Box::new(FakeEmbedder::new(2))).with_hooks(HookRegistry::new().with(Arc::new(Rewrite)));
+    assert_eq!(ids(&engine.query("colour", 3).unwrap()), ["shared", "text-only"]);
+
+    let engine = HybridSearchEngine::new(text(), Vector, Box::new(FakeEmbedder::new(2))).with_hooks(HookRegistry::new().with(Arc::new(Rewrite)).with(Arc::new(Failing)));
+    let err = engine.query("colour", 3).unwrap_err();
+    assert!(format!("{:#}", err).contains("post_fusion hook `failing`: nope"), "{:#}", err);
+}
+
+/// Counts the queries it sees.
+struct Counting(std::sync::atomic::AtomicUsize);
+impl localdb_core::hooks::Hook for Counting {
+    fn name(&self) -> &str { "counting" }
+    fn pre_query(&self, _query: &mut String) -> anyhow::Result<()> { self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst); Ok(()) }
+}
+
+#[test]
+fn one_variant_left_runs_pre_query_once() {
+    use localdb_core::hooks::HookRegistry;
+    use std::sync::Arc;
+    let counting = Arc::new(Counting(Default::default()));
+    let engine = HybridSearchEngine::new(text(), Vector, Box::new(FakeEmbedder::new(2))).with_hooks(HookRegistry::new().with(counting.clone()));
+    engine.query_variants(&["q".to_string(), "Q".to_string()], 3).unwrap();
+    assert_eq!(counting.0.load(std::sync::atomic::Ordering::SeqCst), 2, "once per variant, not again for the one kept");
+}
+
+/// Text hits depend on the phrasing: each word of the query is a hit.
+struct Words;
+impl TextIndexer for Words {
+    fn index(&self, _chunks: &[DocumentChunk]) -> anyhow::Result<()> { Ok(()) }
+    fn search(&self, query: &str, _k: usize) -> anyhow::Result<Vec<SearchHit>> {
+        Ok(query.split_whitespace().enumerate().map(|(i, w)| hit(w, 10.0 - i as f32, SourceKind::Text)).collect())
+    }
+}
+
+/// Records the size of every batch it embeds.
+struct Batches(std::sync::Arc<std::sync::Mutex<Vec<usize>>>);
+impl Embedder for Batches {
+    fn dim(&self) -> usize { 2 }
+    fn max_len(&self) -> usize { 8 }
+    fn embed_batch(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        self.0.lock().expect("lock").push(texts.len());
+        Ok(texts.iter().map(|_| vec![1.0, 0.0]).collect())
+    }
+}
+
+#[test]
+fn query_variants_are_embedded_together_and_fused_by_rank() {
+    let batches = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let engine = HybridSearchEngine::new(Words, FakeVectorIndexer::new(), Box::new(Batches(batches.clone())));
+    let variants = ["canning jars".to_string(), "jars canning".to_string(), "sterilize jars".to_string(), " ".to_string(), "Canning Jars".to_string()];
+    let outcome = engine.query_variants(&variants, 3).unwrap();
+    assert_eq!(*batches.lock().unwrap(), [3], "blank and repeated variants dropped; one batch");
+    // jars: ranks 2, 1, 2; canning: 1, 2; sterilize: 1.
+    assert_eq!(ids(&outcome.hits), ["jars", "canning", "sterilize"]);
+    assert!((outcome.hits[0].score - (2.0 / 62.0 + 1.0 / 61.0)).abs() < 1e-6);
+
+    // A single phrasing is a plain query.
+    let single = engine.query_variants(&["sterilize jars".to_string(), String::new()], 3).unwrap();
+    assert_eq!(ids(&single.hits), ids(&engine.query("sterilize jars", 3).unwrap()));
+}
+
+#[test]
+fn multi_vectors_rescore_dense_hits_by_max_sim() {
+    use localdb_core::traits::MultiVectorEmbedder;
+    use localdb_core::types::MultiVector;
+    use localdb_hybrid::max_sim;
+    use std::collections::HashMap;
+
+    struct Tokens;
+    impl Embedder for Tokens {
+        fn dim(&self) -> usize { 2 }
+        fn max_len(&self) -> usize { 8 }
+        fn embed_batch(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> { Ok(texts.iter().map(|_| vec![1.0, 0.0]).collect()) }
+        fn multi_vector(&self) -> Option<&dyn MultiVectorEmbedder> { Some(self) }
+    }
+    impl MultiVectorEmbedder for Tokens {
+        fn token_dim(&self) -> usize { 2 }
+        fn embed_tokens(&self, texts: &[String]) -> anyhow::Result<Vec<MultiVector>> { Ok(texts.iter().map(|_| vec![vec![1.0, 0.0], vec![0.0, 1.0]]).collect()) }
+    }
+    struct Stored;
+    impl VectorIndexer for Stored {
+        fn index(&self, _chunks: &[DocumentChunk], _embeddings: &[Vec<f32>]) -> anyhow::Result<()> { Ok(()) }
+        fn search_vec(&self, _q: &[f32], k: usize) -> anyhow::Result<Vec<SearchHit>> {
+            assert_eq!(k, 4, "MaxSim picks from more dense candidates");
+            Ok(vec![hit("dense-best", 0.9, SourceKind::Vector), hit("tokens-best", 0.8, SourceKind::Vector), hit("no-tokens", 0.5, SourceKind::Vector)])
+        }
+        fn token_vectors(&self, ids: &[String]) -> anyhow::Result<HashMap<String, MultiVector>> {
+            let mut out = HashMap::new();
+            for id in ids {
+                match id.as_str() {
+                    "dense-best" => { out.insert(id.clone(), vec![vec![1.0, 0.0]]); }
+                    "tokens-best" => { out.insert(id.clone(), vec![vec![1.0, 0.0], vec![0.0, 1.0]]); }
+                    _ => {}
+                }
+            }
+            Ok(out)
+        }
+    }
+
+    assert_eq!(max_sim(&[vec![1.0, 0.0], vec![0.0, 1.0]], &[vec![1.0, 0.0]]), 0.5);
+    let engine = HybridSearchEngine::new(FakeTextIndexer::new(), Stored, Box::new(Tokens)).with_multi_vectors(true);
+    let hits = engine.query("q", 1).unwrap();
+    assert_eq!(ids(&hits), ["tokens-best"]);
+    assert!((hits[0].score - 1.0).abs() < 1e-6);
+}
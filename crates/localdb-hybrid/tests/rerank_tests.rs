@@ -0,0 +1,64 @@
+use std::sync::{Arc, Mutex};
+
+use localdb_core::traits::Reranker;
+use localdb_core::types::{SearchHit, SourceKind};
+use localdb_hybrid::HybridSearchEngine;
+use localdb_testkit::{chunk, FakeEmbedder, FakeTextIndexer, FakeVectorIndexer};
+
+/// Four text hits, best first; `c` has no stored text.
+fn text() -> FakeTextIndexer {
+    let text = FakeTextIndexer::with_chunks(&[chunk("a", "boil the jars"), chunk("b", "pressure canning of low-acid food"), chunk("d", "canning beans")]);
+    let hits: Vec<SearchHit> = ["a", "b", "c", "d"].iter().enumerate().map(|(i, id)| SearchHit::new(*id, 10.0 - i as f32, SourceKind::Text)).collect();
+    for query in ["pressure canning", "canning beans"] { text.script(query, hits.clone()); }
+    text
+}
+
+/// Scores a passage by how many query words it contains; records the passages it read.
+#[derive(Default)]
+struct Overlap { read: Mutex<Vec<String>> }
+impl Reranker for Overlap {
+    fn score(&self, query: &str, passages: &[String]) -> anyhow::Result<Vec<f32>> {
+        self.read.lock().expect("lock").extend(passages.iter().cloned());
+        Ok(passages.iter().map(|p| query.split_whitespace().filter(|w| p.contains(w)).count() as f32).collect())
+    }
+}
+
+/// Fails every call, as a reranker out of memory would.
+struct Broken;
+impl Reranker for Broken {
+    fn score(&self, _query: &str, _passages: &[String]) -> anyhow::Result<Vec<f32>> { anyhow::bail!("out of memory") }
+}
+
+fn ids(hits: &[SearchHit]) -> Vec<&str> { hits.iter().map(|h| h.id.as_str()).collect() }
+
+#[test]
+fn the_reranker_reorders_the_top_candidates() -> anyhow::Result<()> {
+    let reranker = Arc::new(Overlap::default());
+    let engine = HybridSearchEngine::new(text(), FakeVectorIndexer::new(), Box::new(FakeEmbedder::new(1))).with_reranker(reranker.clone(), 3);
+    let hits = engine.query("pressure canning", 4)?;
+    assert_eq!(ids(&hits), ["b", "a", "c", "d"], "c has no text and d is past the candidates: both follow in fused order");
+    assert_eq!(hits[0].score, 2.0);
+    assert!(hits[2..].iter().all(|h| h.score == hits[1].score), "unread hits tie with the lowest reranked one");
+    assert_eq!(reranker.read.lock().expect("lock").len(), 2, "only candidates with stored text are read");
+
+    let plain = HybridSearchEngine::new(text(), FakeVectorIndexer::new(), Box::new(FakeEmbedder::new(1)));
+    assert_eq!(ids(&plain.query("pressure canning", 4)?), ["a", "b", "c", "d"]);
+    Ok(())
+}
+
+#[test]
+fn legs_fetch_enough_hits_for_the_candidates() -> anyhow::Result<()> {
+    let engine = HybridSearchEngine::new(text(), FakeVectorIndexer::new(), Box::new(FakeEmbedder::new(1))).with_reranker(Arc::new(Overlap::default()), 4);
+    let hits = engine.query("canning beans", 1)?;
+    assert_eq!(ids(&hits), ["d"], "the 4th text hit wins after reranking though only one is returned");
+    Ok(())
+}
+
+#[test]
+fn a_failing_reranker_keeps_the_fused_order() -> anyhow::Result<()> {
+    let engine = HybridSearchEngine::new(text(), FakeVectorIndexer::new(), Box::new(FakeEmbedder::new(1))).with_reranker(Arc::new(Broken), 3);
+    let hits = engine.query("pressure canning", 4)?;
+    assert_eq!(ids(&hits), ["a", "b", "c", "d"]);
+    assert_eq!(hits[0].score, 10.0);
+    Ok(())
+}
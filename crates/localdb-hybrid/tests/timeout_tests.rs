@@ -0,0 +1,56 @@
+use localdb_core::traits::VectorIndexer;
+use localdb_core::types::{DocumentChunk, SearchHit, SourceKind};
+use localdb_hybrid::HybridSearchEngine;
+use localdb_testkit::{FakeEmbedder, FakeTextIndexer};
+use std::time::{Duration, Instant};
+
+fn text() -> FakeTextIndexer {
+    let text = FakeTextIndexer::new();
+    text.script("q", vec![SearchHit::new("text:0", 1.0, SourceKind::Text)]);
+    text
+}
+
+/// Answers after `delay`, like a cold mmap or an oversized nprobes sweep.
+struct SlowVector { delay: Duration }
+impl VectorIndexer for SlowVector {
+    fn index(&self, _chunks: &[DocumentChunk], _embeddings: &[Vec<f32>]) -> anyhow::Result<()> { Ok(()) }
+    fn search_vec(&self, _q: &[f32], _k: usize) -> anyhow::Result<Vec<SearchHit>> {
+        std::thread::sleep(self.delay);
+        Ok(vec![SearchHit::new("vec:0", 0.9, SourceKind::Vector)])
+    }
+}
+
+#[test]
+fn slow_vector_leg_falls_back_to_text_and_is_marked_partial() {
+    let engine = HybridSearchEngine::new(text(), SlowVector { delay: Duration::from_secs(2) }, Box::new(FakeEmbedder::new(2)))
+        .with_vector_timeout(Duration::from_millis(50));
+    let started = Instant::now();
+    let outcome = engine.query_outcome("q", 5).unwrap();
+    assert!(started.elapsed() < Duration::from_secs(1), "query waited for the vector leg");
+    assert!(outcome.partial.as_deref().is_some_and(|r| r.contains("timed out")));
+    assert_eq!(outcome.hits.len(), 1);
+    assert_eq!(outcome.hits[0].source, SourceKind::Text);
+}
+
+#[test]
+fn vector_leg_within_the_timeout_is_fused() {
+    let engine = HybridSearchEngine::new(text(), SlowVector { delay: Duration::ZERO }, Box::new(FakeEmbedder::new(2)))
+        .with_vector_timeout(Duration::from_secs(5));
+    let outcome = engine.query_outcome("q", 5).unwrap();
+    assert!(outcome.partial.is_none());
+    assert_eq!(outcome.hits.len(), 2);
+}
+
+#[test]
+fn vector_legs_past_the_limit_are_skipped_as_busy() {
+    let engine = HybridSearchEngine::new(text(), SlowVector { delay: Duration::from_secs(2) }, Box::new(FakeEmbedder::new(2)))
+        .with_vector_timeout(Duration::from_millis(50))
+        .with_max_vector_legs(1);
+    let first = engine.query_outcome("q", 5).unwrap();
+    assert!(first.partial.as_deref().is_some_and(|r| r.contains("timed out")));
+    let started = Instant::now();
+    let second = engine.query_outcome("q", 5).unwrap();
+    assert!(started.elapsed() < Duration::from_millis(50), "busy query waited for a vector leg");
+    assert!(second.partial.as_deref().is_some_and(|r| r.contains("busy")));
+    assert_eq!(second.hits.len(), 1);
+}
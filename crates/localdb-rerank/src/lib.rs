@@ -0,0 +1,113 @@
+//! localdb-rerank
+//!
+//! Cross-encoder reranking. A cross-encoder reads the query and a passage as
+//! one sequence and scores how well the passage answers the query; far more
+//! accurate than comparing two vectors computed apart, and far slower, so it
+//! only reorders the top fused hits (`HybridSearchEngine::with_reranker`).
+//!
+//! - `CrossEncoder` loads an XLM‑R sequence-classification model
+//!   (`bge-reranker-base`, `bge-reranker-large`, `bge-reranker-v2-m3`) from
+//!   `model.safetensors` and implements `localdb_core::traits::Reranker`;
+//!   scores are the sigmoid of its relevance logit, in (0, 1)
+//! - pairs are tokenized as `<s> query </s></s> passage </s>`, padded to the
+//!   longest in the batch; the longer of the two is cut until the pair fits
+//!   `max_len`, so a query over `max_len` is truncated rather than an error
+//! - `resolve_reranker_dir` finds the model directory (`APP_RERANK_MODEL_DIR`,
+//!   else `models/<name>`), on the device `localdb_embed::select_device` picks
+
+use anyhow::{anyhow, Result};
+use candle_core::{DType, Device, Tensor, D};
+use candle_nn::VarBuilder;
+use candle_transformers::models::xlm_roberta::{Config as XLMRobertaConfig, XLMRobertaForSequenceClassification};
+use std::path::{Path, PathBuf};
+use tokenizers::{PaddingParams, PaddingStrategy, Tokenizer, TruncationParams, TruncationStrategy};
+
+use localdb_core::error::Error as CoreError;
+use localdb_core::traits::Reranker;
+
+/// Reranker loaded when none is named.
+pub const DEFAULT_RERANKER: &str = "bge-reranker-v2-m3";
+
+/// Longest query + passage sequence scored, in tokens.
+pub const MAX_LEN: usize = 512;
+
+/// Pairs per forward pass.
+pub const BATCH: usize = 16;
+
+/// An XLM‑R cross-encoder with a one-logit classification head.
+pub struct CrossEncoder { model: XLMRobertaForSequenceClassification, tokenizer: Tokenizer, device: Device, max_len: usize }
+
+impl CrossEncoder {
+    /// Load the reranker `name` from its directory (see `resolve_reranker_dir`).
+    pub fn new(name: &str) -> Result<Self> { Self::load(&resolve_reranker_dir(name)?) }
+
+    /// Load the model in `model_dir` (`config.json`, `tokenizer.json`, `model.safetensors`).
+    pub fn load(model_dir: &Path) -> Result<Self> {
+        let st = model_dir.join("model.safetensors");
+        if !st.exists() { return Err(CoreError::NotFound(format!("{} not found", st.display())).into()); }
+        let device = localdb_embed::select_device()?;
+        let dtype = match &device { Device::Metal(_) | Device::Cuda(_) => DType::F16, Device::Cpu => DType::F32 };
+        println!("🔄 Loading reranker from {}... device={:?} dtype={:?}", model_dir.display(), device, dtype);
+        let config_json = std::fs::read_to_string(model_dir.join("config.json"))?;
+        let config: XLMRobertaConfig = serde_json::from_str(&config_json)?;
+        // RoBERTa positions start after the padding id.
+        let positions = serde_json::from_str::<serde_json::Value>(&config_json)?.get("max_position_embeddings").and_then(serde_json::Value::as_u64);
+        let max_len = positions.map_or(MAX_LEN, |p| (p as usize).saturating_sub(2).min(MAX_LEN));
+        let tokenizer = pair_tokenizer(&model_dir.join("tokenizer.json"), max_len)?;
+        // Safety: relying on safetensors metadata
+        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&[st], dtype, &device)? };
+        let model = XLMRobertaForSequenceClassification::new(1, &config, vb)?;
+        Ok(Self { model, tokenizer, device, max_len })
+    }
+
+    /// Longest query + passage sequence scored, in tokens.
+    pub fn max_len(&self) -> usize { self.max_len }
+
+    /// Relevance logits of `query` against each of `passages` (one batch).
+    fn logits(&self, query: &str, passages: &[String]) -> Result<Vec<f32>> {
+        let pairs: Vec<(String, String)> = passages.iter().map(|p| (query.to_string(), p.clone())).collect();
+        let enc = self.tokenizer.encode_batch(pairs, true).map_err(|e| anyhow!("Tokenization failed: {}", e))?;
+        // Padded to the longest pair, so every row is as wide as the first.
+        let width = enc.first().map_or(0, |e| e.get_ids().len());
+        let ids: Vec<i64> = enc.iter().flat_map(|e| e.get_ids().iter().map(|&x| x as i64)).collect();
+        let mask: Vec<i64> = enc.iter().flat_map(|e| e.get_attention_mask().iter().map(|&x| x as i64)).collect();
+        let input_ids = Tensor::from_vec(ids, (enc.len(), width), &self.device)?;
+        let attention_mask = Tensor::from_vec(mask, (enc.len(), width), &self.device)?;
+        let token_type_ids = Tensor::zeros(attention_mask.dims(), DType::I64, &self.device)?;
+        let logits = self.model.forward(&input_ids, &attention_mask, &token_type_ids)?;
+        Ok(logits.squeeze(D::Minus1)?.to_dtype(DType::F32)?.to_device(&Device::Cpu)?.to_vec1::<f32>()?)
+    }
+}
+
+impl Reranker for CrossEncoder {
+    /// `sigmoid(logit)` of each pair, `BATCH` pairs per forward pass.
+    fn score(&self, query: &str, passages: &[String]) -> Result<Vec<f32>> {
+        let mut scores = Vec::with_capacity(passages.len());
+        for batch in passages.chunks(BATCH) { scores.extend(self.logits(query, batch)?.into_iter().map(sigmoid)); }
+        Ok(scores)
+    }
+}
+
+/// `tokenizer.json` set up for query/passage pairs: truncation to `max_len`
+/// (longest side first, so normally the passage) and padding to the longest
+/// pair of a batch.
+pub fn pair_tokenizer(path: &Path, max_len: usize) -> Result<Tokenizer> {
+    let mut tokenizer = Tokenizer::from_file(path).map_err(|e| anyhow!("Failed to load tokenizer from {}: {}", path.display(), e))?;
+    tokenizer.with_truncation(Some(TruncationParams { max_length: max_len, strategy: TruncationStrategy::LongestFirst, ..Default::default() }))
+        .map_err(|e| anyhow!("Failed to set truncation: {}", e))?;
+    let padding = tokenizer.get_padding().cloned().unwrap_or(PaddingParams { pad_id: 1, pad_token: "<pad>".to_string(), ..Default::default() });
+    tokenizer.with_padding(Some(PaddingParams { strategy: PaddingStrategy::BatchLongest, ..padding }));
+    Ok(tokenizer)
+}
+
+/// Logistic function, mapping a logit to (0, 1).
+pub fn sigmoid(x: f32) -> f32 { 1.0 / (1.0 + (-x).exp()) }
+
+/// Directory holding reranker `name`'s files: `APP_RERANK_MODEL_DIR`, then
+/// `../models/<name>` and `models/<name>`.
+pub fn resolve_reranker_dir(name: &str) -> Result<PathBuf> {
+    if let Ok(dir) = std::env::var("APP_RERANK_MODEL_DIR") { let p = PathBuf::from(&dir); if p.exists() { println!("📦 Using APP_RERANK_MODEL_DIR: {}", p.display()); return Ok(p); } }
+    let root = Path::new("../models").join(name); if root.exists() { println!("📦 Using reranker dir: {}", root.display()); return Ok(root); }
+    let local = Path::new("models").join(name); if local.exists() { println!("📦 Using reranker dir: {}", local.display()); return Ok(local); }
+    Err(CoreError::NotFound(format!("Could not locate the {} reranker directory (models/{})", name, name)).into())
+}
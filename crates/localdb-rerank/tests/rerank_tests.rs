@@ -0,0 +1,17 @@
+use localdb_core::error::Error as CoreError;
+use localdb_rerank::{sigmoid, CrossEncoder};
+
+#[test]
+fn sigmoid_maps_logits_into_the_unit_interval() {
+    assert!((sigmoid(0.0) - 0.5).abs() < 1e-6);
+    assert!(sigmoid(8.0) > 0.99 && sigmoid(8.0) < 1.0);
+    assert!(sigmoid(-8.0) < 0.01 && sigmoid(-8.0) > 0.0);
+    assert!(sigmoid(2.0) > sigmoid(1.0), "order of the logits is kept");
+}
+
+#[test]
+fn a_directory_without_weights_is_not_found() {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let err = CrossEncoder::load(tmp.path()).err().expect("no model.safetensors");
+    assert!(matches!(err.downcast_ref::<CoreError>(), Some(CoreError::NotFound(_))), "{:#}", err);
+}
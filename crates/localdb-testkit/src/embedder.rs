@@ -0,0 +1,37 @@
+//! Deterministic `Embedder` fake (no model files).
+
+use anyhow::Result;
+use localdb_core::traits::Embedder;
+use localdb_core::seed::{SeededRng, DEFAULT_SEED};
+
+use crate::text::terms;
+
+/// Bag-of-words hashing embedder: each term adds weight to one dimension
+/// (picked by a `SeededRng` keyed on the term) and the vector is
+/// L2-normalized, so texts sharing terms are close. Identical for a seed on
+/// every machine; not comparable with vectors from a real model.
+pub struct FakeEmbedder { dim: usize, seed: u64 }
+
+impl FakeEmbedder {
+    pub fn new(dim: usize) -> Self { Self::with_seed(dim, DEFAULT_SEED) }
+
+    pub fn with_seed(dim: usize, seed: u64) -> Self { Self { dim: dim.max(1), seed } }
+}
+
+impl Default for FakeEmbedder {
+    fn default() -> Self { Self::new(64) }
+}
+
+impl Embedder for FakeEmbedder {
+    fn dim(&self) -> usize { self.dim }
+    fn max_len(&self) -> usize { 256 }
+    fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|text| {
+            let mut v = vec![0f32; self.dim];
+            for t in terms(text) { v[SeededRng::for_key(self.seed, &t).below(self.dim)] += 1.0; }
+            let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+            if norm > 0.0 { for x in &mut v { *x /= norm; } }
+            v
+        }).collect())
+    }
+}
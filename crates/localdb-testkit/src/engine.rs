@@ -0,0 +1,41 @@
+//! `SearchEngine` fake for callers that depend on the façade only.
+
+use anyhow::Result;
+use localdb_core::traits::{SearchEngine, TextIndexer};
+use localdb_core::types::{DocumentChunk, SearchHit};
+
+use crate::text::FakeTextIndexer;
+
+/// `SearchEngine` backed by a `FakeTextIndexer`: `index` stores chunks,
+/// `query` keyword-matches them (or serves scripted hits), and an empty query
+/// browses newest first, as `HybridSearchEngine` does.
+#[derive(Default)]
+pub struct FakeSearchEngine { text: FakeTextIndexer }
+
+impl FakeSearchEngine {
+    pub fn new() -> Self { Self::default() }
+
+    /// Pre-loaded with `chunks`.
+    pub fn with_chunks(chunks: &[DocumentChunk]) -> Self { Self { text: FakeTextIndexer::with_chunks(chunks) } }
+
+    /// Serve `hits` whenever `query` is asked.
+    pub fn script(&self, query: &str, hits: Vec<SearchHit>) -> &Self { self.text.script(query, hits); self }
+
+    /// Make every call fail with `message` (`None` restores normal behaviour).
+    pub fn fail_with(&self, message: Option<&str>) -> &Self { self.text.fail_with(message); self }
+
+    /// Stored chunks in insertion order.
+    pub fn indexed(&self) -> Vec<DocumentChunk> { self.text.indexed() }
+
+    /// Non-empty queries asked so far, oldest first.
+    pub fn queries(&self) -> Vec<String> { self.text.queries() }
+}
+
+impl SearchEngine for FakeSearchEngine {
+    fn index(&self, chunks: &[DocumentChunk]) -> Result<()> { self.text.index(chunks) }
+
+    fn query(&self, query: &str, k: usize) -> Result<Vec<SearchHit>> {
+        if query.trim().is_empty() { return self.text.browse(None, k); }
+        self.text.search(query, k)
+    }
+}
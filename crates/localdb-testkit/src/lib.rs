@@ -0,0 +1,63 @@
+//! localdb-testkit
+//!
+//! In-memory fakes of the `localdb-core` trait surfaces for downstream unit
+//! tests: `FakeTextIndexer`, `FakeVectorIndexer`, `FakeSearchEngine` and a
+//! deterministic `FakeEmbedder`. Nothing touches disk, Tantivy, Lance or a
+//! model, so an app embedding the engine can test its own logic against the
+//! traits in milliseconds.
+//!
+//! The fakes behave like the real engines where callers can observe it
+//! (ranked `SearchHit`s with the right `SourceKind`, newest-first browse with
+//! facet subtrees, re-indexing a chunk id replaces it) but score naively:
+//! term counts for text, dot product for vectors. Each fake records what it
+//! was asked (`queries`, `indexed`) and can serve scripted hits instead.
+
+use localdb_core::types::DocumentChunk;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+mod embedder;
+mod engine;
+mod text;
+mod vector;
+
+pub use embedder::FakeEmbedder;
+pub use engine::FakeSearchEngine;
+pub use text::FakeTextIndexer;
+pub use vector::FakeVectorIndexer;
+
+/// A chunk with `content` under `/test` (doc id = the part of `id` before `:`).
+pub fn chunk(id: &str, content: &str) -> DocumentChunk {
+    chunk_in(id, "/test", content)
+}
+
+/// A chunk with `content` under facet `category`.
+pub fn chunk_in(id: &str, category: &str, content: &str) -> DocumentChunk {
+    let doc_id = id.split_once(':').map(|(d, _)| d).unwrap_or(id).to_string();
+    DocumentChunk {
+        id: id.to_string(),
+        doc_path: format!("{}.txt", doc_id),
+        doc_id,
+        category: category.to_string(),
+        category_text: category.to_string(),
+        content: content.to_string(),
+        total_chunks: 1,
+        ..Default::default()
+    }
+}
+
+/// Lock ignoring poisoning: a panicking test must not cascade into others.
+/// Whether `category` is `facet` or below it.
+fn in_facet(category: &str, facet: &str) -> bool {
+    let (c, f) = (localdb_core::facets::normalize(category), localdb_core::facets::normalize(facet));
+    f == "/" || c == f || c.starts_with(&format!("{}/", f))
+}
+
+fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> { m.lock().unwrap_or_else(PoisonError::into_inner) }
+
+/// Insert `chunk`, replacing a stored chunk with the same id in place.
+fn upsert(chunks: &mut Vec<DocumentChunk>, chunk: &DocumentChunk) {
+    match chunks.iter_mut().find(|c| c.id == chunk.id) {
+        Some(existing) => *existing = chunk.clone(),
+        None => chunks.push(chunk.clone()),
+    }
+}
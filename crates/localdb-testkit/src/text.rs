@@ -0,0 +1,107 @@
+//! `TextIndexer` fake: keyword matching over stored chunks.
+
+use anyhow::{anyhow, Result};
+use localdb_core::traits::TextIndexer;
+use localdb_core::types::{DocumentChunk, SearchHit, SourceKind};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::{in_facet, lock, upsert};
+
+/// Scores a chunk by how often the query's (lowercased, alphanumeric) terms
+/// occur in its content; chunks without any term are not hits. Ties keep
+/// insertion order. `browse` returns the most recently indexed chunks first.
+#[derive(Default)]
+pub struct FakeTextIndexer {
+    chunks: Mutex<Vec<DocumentChunk>>,
+    scripted: Mutex<HashMap<String, Vec<SearchHit>>>,
+    queries: Mutex<Vec<String>>,
+    failure: Mutex<Option<String>>,
+}
+
+pub(crate) fn terms(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|t| !t.is_empty()).map(str::to_lowercase).collect()
+}
+
+impl FakeTextIndexer {
+    pub fn new() -> Self { Self::default() }
+
+    /// Pre-loaded with `chunks`.
+    pub fn with_chunks(chunks: &[DocumentChunk]) -> Self {
+        let fake = Self::new();
+        for c in chunks { upsert(&mut lock(&fake.chunks), c); }
+        fake
+    }
+
+    /// Serve `hits` (as given) whenever `query` is searched.
+    pub fn script(&self, query: &str, hits: Vec<SearchHit>) -> &Self {
+        lock(&self.scripted).insert(query.to_string(), hits);
+        self
+    }
+
+    /// Make every call fail with `message` (`None` restores normal behaviour).
+    pub fn fail_with(&self, message: Option<&str>) -> &Self {
+        *lock(&self.failure) = message.map(str::to_string);
+        self
+    }
+
+    /// Stored chunks in insertion order.
+    pub fn indexed(&self) -> Vec<DocumentChunk> { lock(&self.chunks).clone() }
+
+    /// Queries passed to `search`, oldest first.
+    pub fn queries(&self) -> Vec<String> { lock(&self.queries).clone() }
+
+    fn check(&self) -> Result<()> {
+        match lock(&self.failure).as_ref() { Some(m) => Err(anyhow!("{}", m)), None => Ok(()) }
+    }
+}
+
+impl TextIndexer for FakeTextIndexer {
+    fn index(&self, chunks: &[DocumentChunk]) -> Result<()> {
+        self.check()?;
+        let mut stored = lock(&self.chunks);
+        for c in chunks { upsert(&mut stored, c); }
+        Ok(())
+    }
+
+    fn search(&self, query: &str, k: usize) -> Result<Vec<SearchHit>> {
+        self.search_in(query, k, None)
+    }
+
+    fn search_in(&self, query: &str, k: usize, lang: Option<&str>) -> Result<Vec<SearchHit>> {
+        self.search_in_facet(query, k, lang, None)
+    }
+
+    /// Scripted hits are served as given, whatever the facet.
+    fn search_in_facet(&self, query: &str, k: usize, lang: Option<&str>, facet: Option<&str>) -> Result<Vec<SearchHit>> {
+        self.check()?;
+        lock(&self.queries).push(query.to_string());
+        let in_lang = |h: &SearchHit| lang.is_none_or(|l| h.in_lang(l));
+        if let Some(hits) = lock(&self.scripted).get(query) { return Ok(hits.iter().filter(|h| in_lang(h)).take(k).cloned().collect()); }
+        let wanted = terms(query);
+        let mut hits: Vec<SearchHit> = lock(&self.chunks).iter()
+            .filter(|c| lang.is_none_or(|l| c.lang.as_deref().is_some_and(|cl| cl.eq_ignore_ascii_case(l))))
+            .filter(|c| facet.is_none_or(|f| in_facet(&c.category, f)))
+            .filter_map(|c| {
+                let score = terms(&c.content).iter().filter(|t| wanted.contains(t)).count();
+                (score > 0).then(|| SearchHit::for_chunk(c, score as f32, SourceKind::Text))
+            }).collect();
+        hits.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        hits.truncate(k);
+        Ok(hits)
+    }
+
+    fn browse(&self, facet: Option<&str>, k: usize) -> Result<Vec<SearchHit>> {
+        self.check()?;
+        Ok(lock(&self.chunks).iter().rev()
+            .filter(|c| facet.is_none_or(|f| in_facet(&c.category, f)))
+            .take(k)
+            .map(|c| SearchHit::for_chunk(c, 1.0, SourceKind::Text))
+            .collect())
+    }
+
+    fn texts(&self, ids: &[String]) -> Result<HashMap<String, String>> {
+        self.check()?;
+        Ok(lock(&self.chunks).iter().filter(|c| ids.contains(&c.id)).map(|c| (c.id.clone(), c.content.clone())).collect())
+    }
+}
@@ -0,0 +1,95 @@
+//! `VectorIndexer` fake: exact dot-product search over stored vectors.
+
+use anyhow::{anyhow, Result};
+use localdb_core::traits::VectorIndexer;
+use localdb_core::types::{DocumentChunk, SearchHit, SourceKind};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::{in_facet, lock, upsert};
+
+/// Brute-force nearest neighbours by dot product (cosine for the normalized
+/// vectors embedders return). Enforces one vector per chunk and a single
+/// dimension, like the Lance collection does.
+#[derive(Default)]
+pub struct FakeVectorIndexer {
+    chunks: Mutex<Vec<DocumentChunk>>,
+    vectors: Mutex<Vec<(String, Vec<f32>)>>,
+    queries: Mutex<Vec<Vec<f32>>>,
+    failure: Mutex<Option<String>>,
+}
+
+impl FakeVectorIndexer {
+    pub fn new() -> Self { Self::default() }
+
+    /// Make every call fail with `message` (`None` restores normal behaviour).
+    pub fn fail_with(&self, message: Option<&str>) -> &Self {
+        *lock(&self.failure) = message.map(str::to_string);
+        self
+    }
+
+    /// Stored chunks in insertion order.
+    pub fn indexed(&self) -> Vec<DocumentChunk> { lock(&self.chunks).clone() }
+
+    /// Stored vector of chunk `id`.
+    pub fn vector(&self, id: &str) -> Option<Vec<f32>> {
+        lock(&self.vectors).iter().find(|(i, _)| i == id).map(|(_, v)| v.clone())
+    }
+
+    /// Query vectors passed to `search_vec`, oldest first.
+    pub fn queries(&self) -> Vec<Vec<f32>> { lock(&self.queries).clone() }
+
+    fn check(&self) -> Result<()> {
+        match lock(&self.failure).as_ref() { Some(m) => Err(anyhow!("{}", m)), None => Ok(()) }
+    }
+}
+
+impl VectorIndexer for FakeVectorIndexer {
+    fn index(&self, chunks: &[DocumentChunk], embeddings: &[Vec<f32>]) -> Result<()> {
+        self.check()?;
+        if chunks.len() != embeddings.len() { return Err(anyhow!("{} chunks but {} embeddings", chunks.len(), embeddings.len())); }
+        let mut vectors = lock(&self.vectors);
+        let dim = vectors.first().map(|(_, v)| v.len()).or_else(|| embeddings.first().map(Vec::len));
+        if let Some(bad) = embeddings.iter().find(|e| Some(e.len()) != dim) {
+            return Err(anyhow!("dim mismatch: got {} expected {}", bad.len(), dim.unwrap_or(0)));
+        }
+        let mut stored = lock(&self.chunks);
+        for (c, e) in chunks.iter().zip(embeddings) {
+            upsert(&mut stored, c);
+            match vectors.iter_mut().find(|(id, _)| *id == c.id) {
+                Some(existing) => existing.1 = e.clone(),
+                None => vectors.push((c.id.clone(), e.clone())),
+            }
+        }
+        Ok(())
+    }
+
+    fn search_vec(&self, query_vec: &[f32], k: usize) -> Result<Vec<SearchHit>> {
+        self.search_vec_in(query_vec, k, None)
+    }
+
+    fn search_vec_in(&self, query_vec: &[f32], k: usize, lang: Option<&str>) -> Result<Vec<SearchHit>> {
+        self.search_vec_in_facet(query_vec, k, lang, None)
+    }
+
+    fn search_vec_in_facet(&self, query_vec: &[f32], k: usize, lang: Option<&str>, facet: Option<&str>) -> Result<Vec<SearchHit>> {
+        self.check()?;
+        lock(&self.queries).push(query_vec.to_vec());
+        let vectors = lock(&self.vectors);
+        let chunks = lock(&self.chunks);
+        let in_lang = |id: &str| lang.is_none_or(|l| chunks.iter().any(|c| c.id == id && c.lang.as_deref().is_some_and(|cl| cl.eq_ignore_ascii_case(l))));
+        let under = |id: &str| facet.is_none_or(|f| chunks.iter().any(|c| c.id == id && in_facet(&c.category, f)));
+        let mut hits: Vec<SearchHit> = vectors.iter()
+            .filter(|(id, _)| in_lang(id) && under(id))
+            .map(|(id, v)| SearchHit::new(id.clone(), v.iter().zip(query_vec).map(|(a, b)| a * b).sum(), SourceKind::Vector))
+            .collect();
+        hits.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        hits.truncate(k);
+        Ok(hits)
+    }
+
+    fn vectors(&self, ids: &[String]) -> Result<HashMap<String, Vec<f32>>> {
+        self.check()?;
+        Ok(lock(&self.vectors).iter().filter(|(id, _)| ids.contains(id)).cloned().collect())
+    }
+}
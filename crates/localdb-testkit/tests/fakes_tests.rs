@@ -0,0 +1,77 @@
+use localdb_core::traits::{Embedder, SearchEngine, TextIndexer, VectorIndexer};
+use localdb_core::types::{SearchHit, SourceKind};
+use localdb_hybrid::HybridSearchEngine;
+use localdb_testkit::{chunk, chunk_in, FakeEmbedder, FakeSearchEngine, FakeTextIndexer, FakeVectorIndexer};
+
+fn ids(hits: &[SearchHit]) -> Vec<&str> { hits.iter().map(|h| h.id.as_str()).collect() }
+
+#[test]
+fn text_fake_ranks_by_term_count_and_browses_newest_first() {
+    let text = FakeTextIndexer::new();
+    text.index(&[
+        chunk_in("water:0", "/water", "Boil water. Boiling kills germs."),
+        chunk_in("seeds:0", "/garden/seeds", "Dry seeds before storing, water sparingly."),
+        chunk_in("soil:0", "/garden/soil", "Compost improves soil."),
+    ]).unwrap();
+    assert_eq!(ids(&text.search("water boil", 5).unwrap()), ["water:0", "seeds:0"]);
+    assert!(text.search("nothing here", 5).unwrap().is_empty());
+    assert_eq!(ids(&text.browse(Some("/garden"), 5).unwrap()), ["soil:0", "seeds:0"]);
+    assert_eq!(text.queries(), ["water boil", "nothing here"]);
+
+    text.index(&[chunk_in("water:0", "/water", "Filter first.")]).unwrap();
+    assert_eq!(text.indexed().len(), 3, "re-indexing an id replaces it");
+    assert!(text.search("boil", 5).unwrap().is_empty());
+
+    text.script("anything", vec![SearchHit::new("x", 3.0, SourceKind::Text)]);
+    assert_eq!(ids(&text.search("anything", 5).unwrap()), ["x"]);
+    text.fail_with(Some("disk full"));
+    assert_eq!(text.search("boil", 5).unwrap_err().to_string(), "disk full");
+}
+
+#[test]
+fn vector_fake_finds_nearest_and_checks_dimensions() {
+    let vector = FakeVectorIndexer::new();
+    vector.index(&[chunk("a:0", "a"), chunk("b:0", "b")], &[vec![1.0, 0.0], vec![0.0, 1.0]]).unwrap();
+    let hits = vector.search_vec(&[0.2, 0.9], 1).unwrap();
+    assert_eq!(ids(&hits), ["b:0"]);
+    assert_eq!(hits[0].source, SourceKind::Vector);
+    assert!(vector.index(&[chunk("c:0", "c")], &[vec![1.0, 0.0, 0.0]]).is_err(), "dim mismatch");
+    assert!(vector.index(&[chunk("c:0", "c")], &[]).is_err(), "one vector per chunk");
+    assert_eq!(vector.vector("a:0"), Some(vec![1.0, 0.0]));
+}
+
+#[test]
+fn fake_embedder_is_deterministic_and_normalized() {
+    let emb = FakeEmbedder::new(32);
+    let v = emb.embed_batch(&["rain barrel".into(), "rain barrel".into(), "".into()]).unwrap();
+    assert_eq!(v[0], v[1]);
+    assert!((v[0].iter().map(|x| x * x).sum::<f32>() - 1.0).abs() < 1e-5);
+    assert!(v[2].iter().all(|x| *x == 0.0));
+}
+
+#[test]
+fn fakes_drive_the_hybrid_engine_and_the_facade() {
+    let engine = HybridSearchEngine::new(FakeTextIndexer::new(), FakeVectorIndexer::new(), Box::new(FakeEmbedder::default()));
+    engine.index(&[chunk("rain:0", "collect rain water in barrels"), chunk("fire:0", "bank the fire overnight")]).unwrap();
+    assert_eq!(engine.query("rain water", 1).unwrap()[0].id, "rain:0");
+
+    let facade = FakeSearchEngine::with_chunks(&[chunk("a:0", "first"), chunk("b:0", "second")]);
+    assert_eq!(ids(&facade.query("second", 5).unwrap()), ["b:0"]);
+    assert_eq!(ids(&facade.query("  ", 5).unwrap()), ["b:0", "a:0"], "empty query browses newest first");
+    assert_eq!(facade.queries(), ["second"]);
+}
+
+#[test]
+fn rejected_chunks_push_down_similar_hits() {
+    let vector = FakeVectorIndexer::new();
+    vector.index(&[chunk("a:0", "a"), chunk("b:0", "b"), chunk("c:0", "c")], &[vec![1.0, 0.0], vec![0.9, 0.1], vec![0.0, 1.0]]).unwrap();
+    assert_eq!(vector.vectors(&["c:0".into(), "zz:0".into()]).unwrap().into_keys().collect::<Vec<_>>(), ["c:0"]);
+    let engine = HybridSearchEngine::new(FakeTextIndexer::new(), vector, Box::new(FakeEmbedder::default()));
+    let mut hits = vec![SearchHit::new("a:0", 1.0, SourceKind::Vector), SearchHit::new("b:0", 0.9, SourceKind::Vector), SearchHit::new("c:0", 0.8, SourceKind::Text)];
+    engine.penalize_rejected(&mut hits, &[], 0.5).unwrap();
+    assert_eq!(ids(&hits), ["a:0", "b:0", "c:0"], "nothing rejected");
+    engine.penalize_rejected(&mut hits, &["a:0".into()], 0.5).unwrap();
+    assert_eq!(ids(&hits), ["c:0", "b:0"]);
+    assert_eq!(hits[0].score, 0.8, "unlike the rejected chunk: untouched");
+    assert!(hits[1].score < 0.46, "{}", hits[1].score);
+}
@@ -1,14 +1,24 @@
+//! Build/rebuild a Tantivy index from a directory of `.txt` files.
+//!
+//! The indexer deletes the target index path if it already exists, then creates
+//! a fresh index using the crate's schema and tokenizer setup.
+
 use anyhow::Result;
+use std::collections::{BTreeSet, HashSet};
 use std::path::Path;
 use tantivy::{doc, Index, TantivyDocument};
-use tantivy::collector::TopDocs;
-use tantivy::query::QueryParser;
-use tantivy::schema::Value;
+use tantivy::collector::{DocSetCollector, TopDocs};
+use tantivy::query::{AllQuery, QueryParser};
 
+use localdb_core::data_processor::relative_doc_path;
+use localdb_core::profile::{self, Stage};
+use localdb_core::progress;
+use localdb_core::roots::RootMap;
 use localdb_core::traits::TextIndexer;
-use localdb_core::types::{DocumentChunk, SearchHit, SourceKind};
+use localdb_core::types::{is_in_file, DocumentChunk, SearchHit, SourceKind, SparseVector};
 
-use crate::tantivy_utils::{build_schema, register_tokenizer};
+use crate::tantivy_utils::{absolute_root, browse_query, browse_top, build_schema_with, commit_with_roots, data_roots, now_millis, register_tokenizer, stored_id, Analysis, DocFields, INDEXED_AT, TEXT_NGRAM, TEXT_SPARSE};
+use crate::{lang, sparse};
 
 pub struct TantivyIndexer {
 	index: Index,
@@ -17,12 +27,21 @@ pub struct TantivyIndexer {
 	category_field: tantivy::schema::Field,
 	category_text_field: tantivy::schema::Field,
 	path_field: tantivy::schema::Field,
+	indexed_at_field: Option<tantivy::schema::Field>,
+	ngram_field: Option<tantivy::schema::Field>,
+	sparse_field: Option<tantivy::schema::Field>,
+	doc_fields: DocFields,
+	ngrams: bool,
+	data_roots: Option<RootMap>,
 }
 
 impl TantivyIndexer {
     /// Create a new indexer in `index_dir`, destroying any existing index.
-    pub fn new(index_dir: std::path::PathBuf) -> Result<Self, anyhow::Error> {
-		let schema = build_schema();
+    pub fn new(index_dir: std::path::PathBuf) -> Result<Self, anyhow::Error> { Self::with_analysis(index_dir, Analysis::default()) }
+
+    /// Like `new`, with explicit analysis options (e.g. transliteration folding).
+    pub fn with_analysis(index_dir: std::path::PathBuf, analysis: Analysis) -> Result<Self, anyhow::Error> {
+		let schema = build_schema_with(analysis);
 		if index_dir.exists() { std::fs::remove_dir_all(&index_dir)?; }
 		std::fs::create_dir_all(&index_dir)?;
 		let index = Index::create_in_dir(&index_dir, schema.clone())?;
@@ -32,15 +51,58 @@ impl TantivyIndexer {
 		let category_field = schema.get_field("category")?;
 		let category_text_field = schema.get_field("category_text")?;
 		let path_field = schema.get_field("doc_path")?;
-		Ok(Self { index, id_field, text_field, category_field, category_text_field, path_field })
+		let indexed_at_field = schema.get_field(INDEXED_AT).ok();
+		let ngram_field = schema.get_field(TEXT_NGRAM).ok();
+		let sparse_field = schema.get_field(TEXT_SPARSE).ok();
+		Ok(Self { index, id_field, text_field, category_field, category_text_field, path_field, indexed_at_field, ngram_field, sparse_field, doc_fields: DocFields::of(&schema), ngrams: false, data_roots: None })
+	}
+
+    /// Whether `index_dir` holds an index (`open` would find one).
+    pub fn exists(index_dir: &Path) -> bool { index_dir.join("meta.json").is_file() }
+
+    /// Open an existing index for appending chunks; commits keep its recorded data roots.
+    pub fn open(index_dir: &Path) -> Result<Self, anyhow::Error> {
+		let index = Index::open_in_dir(index_dir)?;
+		register_tokenizer(&index);
+		let schema = index.schema();
+		let id_field = schema.get_field("id")?;
+		let text_field = schema.get_field("text")?;
+		let category_field = schema.get_field("category")?;
+		let category_text_field = schema.get_field("category_text")?;
+		let path_field = schema.get_field("doc_path")?;
+		// Absent in indexes built before ingest timestamps, n-grams and sparse embeddings.
+		let indexed_at_field = schema.get_field(INDEXED_AT).ok();
+		let ngram_field = schema.get_field(TEXT_NGRAM).ok();
+		let sparse_field = schema.get_field(TEXT_SPARSE).ok();
+		let data_roots = Some(data_roots(&index));
+		Ok(Self { index, id_field, text_field, category_field, category_text_field, path_field, indexed_at_field, ngram_field, sparse_field, doc_fields: DocFields::of(&schema), ngrams: false, data_roots })
 	}
 
+    /// Record `root` as the data root that chunk `doc_path`s are relative to.
+    pub fn with_data_root(mut self, root: &Path) -> Self { self.data_roots = Some(RootMap::single(absolute_root(root))); self }
+
+    /// Record the locations of several named data roots (multi-root ingest).
+    pub fn with_data_roots(mut self, roots: RootMap) -> Self { self.data_roots = Some(roots); self }
+
+    /// Also index character n-grams of chunks detected as a language that
+    /// stems poorly (Finnish, German); see `lang`.
+    pub fn with_ngram_fallback(mut self, enabled: bool) -> Self { self.ngrams = enabled; self }
+
+    /// Add the n-gram copy of `content` to `doc` when enabled and the language
+    /// calls for it: the chunk's stored `lang`, else a guess from `content`.
+    /// Skipped for indexes built before the n-gram field.
+    fn add_ngrams(&self, doc: &mut TantivyDocument, content: &str, lang: Option<&str>) {
+        let Some(field) = self.ngram_field.filter(|_| self.ngrams) else { return };
+        if lang.map(lang::Lang::from_code).unwrap_or_else(|| lang::detect(content)).uses_ngrams() { doc.add_text(field, content); }
+    }
+
     /// Recursively index `.txt` files from `data_dir`.
     ///
     /// Returns the number of files added to the index.
     pub fn index_files(&self, data_dir: &Path) -> Result<usize, anyhow::Error> {
 		let mut index_writer = self.index.writer(50_000_000)?;
 		let mut file_count = 0;
+		let now = now_millis();
 		for entry in walkdir::WalkDir::new(data_dir).into_iter().filter_map(|e| e.ok()) {
 			if entry.file_type().is_file() && entry.path().extension().is_some_and(|ext| ext == "txt") {
 				let file_path = entry.path();
@@ -48,61 +110,154 @@ impl TantivyIndexer {
 				let category = Self::extract_category_from_path(relative_path);
 				if let Ok(content) = std::fs::read_to_string(file_path) {
 					let doc_id = format!("{}", relative_path.display());
-					let doc = doc!(
+					let mut doc = doc!(
 						self.id_field => doc_id.clone(),
 						self.text_field => content.clone(),
 						self.category_field => tantivy::schema::Facet::from(&category),
 						self.category_text_field => category.clone(),
-						self.path_field => file_path.to_string_lossy().to_string()
+						self.path_field => relative_doc_path(file_path, data_dir)
 					);
+					if let Some(field) = self.indexed_at_field { doc.add_u64(field, now); }
+					self.add_ngrams(&mut doc, &content, None);
 					index_writer.add_document(doc)?;
 					file_count += 1;
 				}
 			}
 		}
-		index_writer.commit()?; Ok(file_count)
+		commit_with_roots(&mut index_writer, Some(&RootMap::single(absolute_root(data_dir))))?; Ok(file_count)
 	}
 
-	fn extract_category_from_path(path: &Path) -> String {
-		let components: Vec<_> = path.components().collect();
-		if components.len() >= 2 { let category = components[0].as_os_str().to_string_lossy(); let subcategory = components[1].as_os_str().to_string_lossy(); format!("/{}/{}", category, subcategory) }
-		else if components.len() == 1 { let category = components[0].as_os_str().to_string_lossy(); format!("/{}", category) }
-		else { "/misc".to_string() }
+	/// Re-point an existing index at a moved corpus: records `new_root` as the
+	/// location of root `root_name` (`""` for the unnamed single root) in the
+	/// index meta without touching documents.
+	pub fn relocate(index_dir: &Path, root_name: &str, new_root: &Path) -> Result<(), anyhow::Error> {
+		let index = Index::open_in_dir(index_dir)?;
+		register_tokenizer(&index);
+		let mut roots = data_roots(&index);
+		roots.set(root_name, absolute_root(new_root));
+		let mut writer: tantivy::IndexWriter = index.writer(15_000_000)?;
+		commit_with_roots(&mut writer, Some(&roots))?;
+		Ok(())
 	}
-}
 
-impl TextIndexer for TantivyIndexer {
-    fn index(&self, chunks: &[DocumentChunk]) -> anyhow::Result<()> {
+	/// Remove every chunk of the documents at `doc_paths` (stored, root-relative)
+	/// from an existing index, fragments of them (`<file>#…`) included, keeping
+	/// the recorded data roots.
+	pub fn delete_documents(index_dir: &Path, doc_paths: &[String]) -> Result<(), anyhow::Error> {
+		let index = Index::open_in_dir(index_dir)?;
+		register_tokenizer(&index);
+		let path_field = index.schema().get_field("doc_path")?;
+		let roots = data_roots(&index);
+		let mut paths: BTreeSet<String> = doc_paths.iter().cloned().collect();
+		for segment in index.reader()?.searcher().segment_readers() {
+			let inverted = segment.inverted_index(path_field)?;
+			for p in doc_paths {
+				let prefix = format!("{}#", p);
+				let mut stream = inverted.terms().range().ge(prefix.as_bytes()).into_stream()?;
+				while stream.advance() {
+					let Ok(stored) = std::str::from_utf8(stream.key()) else { continue };
+					if !is_in_file(stored, p) { break; }
+					paths.insert(stored.to_string());
+				}
+			}
+		}
+		let mut writer: tantivy::IndexWriter = index.writer(15_000_000)?;
+		for p in &paths { writer.delete_term(tantivy::Term::from_field_text(path_field, p)); }
+		commit_with_roots(&mut writer, Some(&roots))?;
+		Ok(())
+	}
+
+	/// Chunk ids of every live document in an existing index.
+	pub fn stored_ids(index_dir: &Path) -> Result<HashSet<String>, anyhow::Error> {
+		let index = Index::open_in_dir(index_dir)?;
+		register_tokenizer(&index);
+		let id_field = index.schema().get_field("id")?;
+		let searcher = index.reader()?.searcher();
+		let mut ids = HashSet::new();
+		for addr in searcher.search(&AllQuery, &DocSetCollector)? {
+			let doc: TantivyDocument = searcher.doc(addr)?;
+			ids.insert(stored_id(&doc, id_field, addr)?.to_string());
+		}
+		Ok(ids)
+	}
+
+	/// Remove the chunks with the given ids, keeping the recorded data roots.
+	pub fn delete_ids(index_dir: &Path, ids: &[String]) -> Result<(), anyhow::Error> {
+		let index = Index::open_in_dir(index_dir)?;
+		register_tokenizer(&index);
+		let id_field = index.schema().get_field("id")?;
+		let roots = data_roots(&index);
+		let mut writer: tantivy::IndexWriter = index.writer(15_000_000)?;
+		for id in ids { writer.delete_term(tantivy::Term::from_field_text(id_field, id)); }
+		commit_with_roots(&mut writer, Some(&roots))?;
+		Ok(())
+	}
+
+    /// Add `chunks`, with their sparse vectors if given, and commit.
+    fn write(&self, chunks: &[DocumentChunk], weights: Option<&[SparseVector]>) -> Result<()> {
+        let write = profile::timer(Stage::TantivyWrite);
         let mut index_writer = self.index.writer(50_000_000)?;
-        for c in chunks {
-            let doc = doc!(
+        let now = now_millis();
+        for (i, c) in chunks.iter().enumerate() {
+            progress::report("tantivy write", i as u64 + 1, Some(chunks.len() as u64));
+            let mut doc = doc!(
                 self.id_field => c.id.clone(),
                 self.text_field => c.content.clone(),
                 self.category_field => tantivy::schema::Facet::from(&c.category),
                 self.category_text_field => c.category_text.clone(),
                 self.path_field => c.doc_path.clone(),
             );
+            if let Some(field) = self.indexed_at_field { doc.add_u64(field, now); }
+            self.add_ngrams(&mut doc, &c.content, c.lang.as_deref());
+            if let (Some(field), Some(vectors)) = (self.sparse_field, weights) { doc.add_text(field, sparse::sparse_text(&vectors[i])); }
+            self.doc_fields.add(&mut doc, c);
             index_writer.add_document(doc)?;
         }
-        index_writer.commit()?;
+        drop(write);
+        profile::time(Stage::Commit, || commit_with_roots(&mut index_writer, self.data_roots.as_ref()))?;
         Ok(())
     }
 
+	fn extract_category_from_path(path: &Path) -> String {
+		let components: Vec<_> = path.components().collect();
+		if components.len() >= 2 { let category = components[0].as_os_str().to_string_lossy(); let subcategory = components[1].as_os_str().to_string_lossy(); format!("/{}/{}", category, subcategory) }
+		else if components.len() == 1 { let category = components[0].as_os_str().to_string_lossy(); format!("/{}", category) }
+		else { "/misc".to_string() }
+	}
+}
+
+impl TextIndexer for TantivyIndexer {
+    fn index(&self, chunks: &[DocumentChunk]) -> anyhow::Result<()> { self.write(chunks, None) }
+
+    /// Ignores `sparse` when the index predates the `text_sparse` field.
+    fn index_sparse(&self, chunks: &[DocumentChunk], sparse: &[SparseVector]) -> anyhow::Result<()> {
+        if sparse.len() != chunks.len() { anyhow::bail!("{} sparse vectors for {} chunks", sparse.len(), chunks.len()); }
+        self.write(chunks, Some(sparse))
+    }
+
     fn search(&self, query: &str, k: usize) -> anyhow::Result<Vec<SearchHit>> {
         let reader = self.index.reader()?;
         let searcher = reader.searcher();
         let qp = QueryParser::for_index(&self.index, vec![self.text_field]);
-        let q = qp.parse_query(query)?;
+        let q = qp.parse_query(&crate::query::preprocess_query(query))?;
         let top_docs = searcher.search(&q, &TopDocs::with_limit(k))?;
         let mut hits = Vec::new();
         for (score, addr) in top_docs {
             let doc: TantivyDocument = searcher.doc(addr)?;
-            let id = doc.get_first(self.id_field).and_then(|v| v.as_str()).unwrap_or("").to_string();
-            hits.push(SearchHit { id, score, source: SourceKind::Text });
-//! Build/rebuild a Tantivy index from a directory of `.txt` files.
-//!
-//! The indexer deletes the target index path if it already exists, then creates
-//! a fresh index using the crate's schema and tokenizer setup.
+            let id = stored_id(&doc, self.id_field, addr)?.to_string();
+            hits.push(self.doc_fields.fill(&doc, SearchHit::new(id, score, SourceKind::Text)));
+        }
+        Ok(hits)
+    }
+
+    fn browse(&self, facet: Option<&str>, k: usize) -> anyhow::Result<Vec<SearchHit>> {
+        let searcher = self.index.reader()?.searcher();
+        let query = browse_query(self.category_field, facet)?;
+        let mut hits = Vec::new();
+        for (score, addr) in browse_top(&searcher, query.as_ref(), k)? {
+            let doc: TantivyDocument = searcher.doc(addr)?;
+            let id = stored_id(&doc, self.id_field, addr)?.to_string();
+            hits.push(self.doc_fields.fill(&doc, SearchHit::new(id, score, SourceKind::Text)));
         }
         Ok(hits)
     }
@@ -0,0 +1,4 @@
+//! The language guess that decides whether a chunk also gets n-grams; lives in
+//! `localdb_core::lang` so chunking can store it on chunks.
+
+pub use localdb_core::lang::{detect, Lang};
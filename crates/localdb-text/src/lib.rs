@@ -1,10 +1,21 @@
+//! localdb-text
+//!
+//! Tantivy-based text indexing and search. See `index` and `search` modules and
+//! examples under `examples/` for CLI-like usage during development.
+
 pub mod tantivy_utils;
 pub mod index;
 pub mod search;
+pub mod stats;
+pub mod query;
+pub mod lang;
+pub mod translit;
+pub mod translate;
+pub mod rewrite;
+pub mod shard;
+pub mod sparse;
 
 pub use index::TantivyIndexer;
 pub use search::{TantivySearchEngine, SearchResult};
-//! localdb-text
-//!
-//! Tantivy-based text indexing and search. See `index` and `search` modules and
-//! examples under `examples/` for CLI-like usage during development.
+pub use shard::{ShardedIndexer, ShardedSearchEngine, TextIndex, TextSearch};
+pub use stats::{FacetStats, TermStat};
@@ -0,0 +1,31 @@
+//! Query pre-processing applied before Tantivy parsing.
+//!
+//! User queries arrive from terminals and copy/paste of downloaded content, so
+//! they may contain control characters, stray quotes, or megabytes of text.
+//! `preprocess_query` normalizes them into something the `QueryParser` can
+//! handle; it never panics on arbitrary input (see `fuzz/`).
+
+/// Upper bound on query length in characters; longer input is truncated.
+pub const MAX_QUERY_CHARS: usize = 1024;
+
+/// Normalize a raw user query:
+/// - control characters become spaces and whitespace runs collapse to one space
+/// - input is truncated to `MAX_QUERY_CHARS`
+/// - an unbalanced trailing `"` is dropped so phrase parsing cannot fail on it
+pub fn preprocess_query(raw: &str) -> String {
+    let mut chars: Vec<char> = raw
+        .chars()
+        .take(MAX_QUERY_CHARS)
+        .map(|ch| if ch.is_control() || ch.is_whitespace() { ' ' } else { ch })
+        .collect();
+    if chars.iter().filter(|&&c| c == '"').count() % 2 == 1 {
+        if let Some(pos) = chars.iter().rposition(|&c| c == '"') { chars.remove(pos); }
+    }
+    let mut out = String::with_capacity(chars.len());
+    for ch in chars {
+        if ch == ' ' && (out.is_empty() || out.ends_with(' ')) { continue; }
+        out.push(ch);
+    }
+    if out.ends_with(' ') { out.pop(); }
+    out
+}
@@ -0,0 +1,241 @@
+//! Query rewriting from the index's own vocabulary: spelling and word forms.
+//!
+//! Each plain query word is run through the `text` field's analyzer. A word
+//! the index has never seen is replaced by the most common indexed term
+//! within a small edit distance (`cannning` → `canning`), found by walking
+//! the term dictionary with a Levenshtein automaton; a known word is OR-ed
+//! with the other indexed forms sharing its stem (`canning` →
+//! `(canning OR canned OR cans)`), stemmed for the query's language
+//! (`localdb_core::lang::detect`; English for an undetected all-ASCII query,
+//! no forms otherwise). Stopwords, short words and anything in query syntax
+//! (quotes, `field:`, `+`/`-`, wildcards) are left alone.
+//!
+//! The result keeps both the corrected query (the text as typed with only
+//! the spelling fixes substituted, for phrases, snippets and the vector leg)
+//! and the expanded one (analyzed terms with forms OR-ed in, for the BM25
+//! OR/AND queries), and lists what changed so callers can show the query
+//! actually searched.
+
+use anyhow::Result;
+use levenshtein_automata::{Distance, LevenshteinAutomatonBuilder, DFA, SINK_STATE};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use tantivy::schema::Field;
+use tantivy::tokenizer::{Language, RawTokenizer, Stemmer, TextAnalyzer, TokenStream};
+use tantivy::{Index, Searcher, Term};
+
+use localdb_core::lang::{self, Lang};
+
+/// Words shorter than this are neither corrected nor expanded.
+pub const MIN_WORD_CHARS: usize = 4;
+
+/// Indexed forms OR-ed in per word, most frequent first.
+pub const MAX_FORMS: usize = 4;
+
+/// One change made to the query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// A word absent from the index replaced by a close indexed term.
+    Spelling { from: String, to: String },
+    /// Other indexed forms of a word searched alongside it.
+    Forms { word: String, forms: Vec<String> },
+}
+
+/// A query as typed and as searched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rewrite {
+    pub original: String,
+    /// `original` as typed with only the spelling corrections substituted.
+    pub corrected: String,
+    /// The analyzed words, corrected, with their forms OR-ed in, in
+    /// query-parser syntax.
+    pub expanded: String,
+    pub changes: Vec<Change>,
+}
+
+impl Rewrite {
+    /// The query unchanged.
+    pub fn unchanged(query: &str) -> Self {
+        Self { original: query.to_string(), corrected: query.to_string(), expanded: query.to_string(), changes: Vec::new() }
+    }
+
+    pub fn is_changed(&self) -> bool { !self.changes.is_empty() }
+}
+
+/// Rewrites queries against the terms of one text field.
+#[derive(Clone)]
+pub struct QueryRewriter {
+    index: Index,
+    searcher: Searcher,
+    field: Field,
+}
+
+impl QueryRewriter {
+    pub fn new(index: Index, searcher: Searcher, field: Field) -> Self { Self { index, searcher, field } }
+
+    /// Correct and expand the plain words of `query` (see module docs).
+    pub fn rewrite(&self, query: &str) -> Result<Rewrite> {
+        if query.contains(|c: char| "\"():*^~[]{}".contains(c)) { return Ok(Rewrite::unchanged(query)); }
+        let mut analyzer = self.index.tokenizer_for_field(self.field)?;
+        let mut stemmer = stemmer_for(query);
+        let mut out = Rewrite::unchanged(query);
+        let (mut corrected, mut expanded) = (Vec::new(), Vec::new());
+        for word in query.split_whitespace() {
+            corrected.push(word.to_string());
+            let Some((plain, indexed)) = plain_word(word).and_then(|w| Some(w).zip(single_token(&mut analyzer, w))).filter(|(_, t)| t.chars().count() >= MIN_WORD_CHARS) else {
+                expanded.push(word.to_string());
+                continue;
+            };
+            let term = if self.doc_freq(&indexed)? > 0 { indexed } else {
+                match self.closest_term(&indexed)? {
+                    Some(to) => {
+                        out.changes.push(Change::Spelling { from: word.to_string(), to: to.clone() });
+                        // Keep the word's punctuation and everything else as typed.
+                        if let Some(typed) = corrected.last_mut() { *typed = typed.replacen(plain, &to, 1); }
+                        to
+                    }
+                    None => indexed,
+                }
+            };
+            let forms = match &mut stemmer { Some(stemmer) => self.other_forms(&term, stemmer)?, None => Vec::new() };
+            if forms.is_empty() { expanded.push(term); } else {
+                expanded.push(format!("({})", std::iter::once(term.as_str()).chain(forms.iter().map(String::as_str)).collect::<Vec<_>>().join(" OR ")));
+                out.changes.push(Change::Forms { word: term, forms });
+            }
+        }
+        if out.is_changed() {
+            out.corrected = corrected.join(" ");
+            out.expanded = expanded.join(" ");
+        }
+        Ok(out)
+    }
+
+    fn doc_freq(&self, term: &str) -> Result<u64> {
+        Ok(self.searcher.doc_freq(&Term::from_field_text(self.field, term))?)
+    }
+
+    /// Indexed terms starting with `prefix` and their document frequencies,
+    /// summed over segments.
+    fn terms_with_prefix(&self, prefix: &str) -> Result<HashMap<String, u64>> {
+        let mut terms: HashMap<String, u64> = HashMap::new();
+        for segment in self.searcher.segment_readers() {
+            let inverted = segment.inverted_index(self.field)?;
+            let mut stream = inverted.terms().range().ge(prefix.as_bytes()).into_stream()?;
+            while stream.advance() {
+                if !stream.key().starts_with(prefix.as_bytes()) { break; }
+                let Ok(term) = std::str::from_utf8(stream.key()) else { continue };
+                *terms.entry(term.to_string()).or_default() += stream.value().doc_freq as u64;
+            }
+        }
+        Ok(terms)
+    }
+
+    /// Indexed terms within `budget` edits of `word` and their document
+    /// frequencies, summed over segments; the automaton visits only the
+    /// stretches of the dictionary that can still match.
+    fn terms_near(&self, word: &str, budget: u8) -> Result<HashMap<String, u64>> {
+        let mut terms: HashMap<String, u64> = HashMap::new();
+        for segment in self.searcher.segment_readers() {
+            let inverted = segment.inverted_index(self.field)?;
+            let mut stream = inverted.terms().search(Within(levenshtein(word, budget))).into_stream()?;
+            while stream.advance() {
+                let Ok(term) = std::str::from_utf8(stream.key()) else { continue };
+                *terms.entry(term.to_string()).or_default() += stream.value().doc_freq as u64;
+            }
+        }
+        Ok(terms)
+    }
+
+    /// The most frequent indexed term within the edit budget of `word`
+    /// (1 up to 5 characters, else 2), nearest first.
+    fn closest_term(&self, word: &str) -> Result<Option<String>> {
+        let budget = if word.chars().count() <= 5 { 1 } else { 2 };
+        let best = self.terms_near(word, budget)?.into_iter().filter(|(term, _)| term != word)
+            .map(|(term, df)| (edit_distance(word, &term), Reverse(df), term)).min();
+        Ok(best.map(|(_, _, t)| t))
+    }
+
+    /// Up to `MAX_FORMS` other indexed terms with the same stem as `term`
+    /// under `stemmer`, most frequent first.
+    fn other_forms(&self, term: &str, stemmer: &mut TextAnalyzer) -> Result<Vec<String>> {
+        let stem = stem_of(stemmer, term);
+        // Stems may alter the ending (`happy` → `happi`), so scan from the part they share.
+        let shared = term.chars().zip(stem.chars()).take_while(|(a, b)| a == b).count();
+        if shared < MIN_WORD_CHARS - 1 { return Ok(Vec::new()); }
+        let prefix: String = term.chars().take(shared).collect();
+        let mut forms: Vec<(String, u64)> = self.terms_with_prefix(&prefix)?.into_iter()
+            .filter(|(t, _)| t != term && stem_of(stemmer, t) == stem).collect();
+        forms.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Ok(forms.into_iter().take(MAX_FORMS).map(|(t, _)| t).collect())
+    }
+}
+
+/// A stemmer for `query`'s language, English when an all-ASCII query's
+/// language is not detected; `None` for other undetected queries.
+fn stemmer_for(query: &str) -> Option<TextAnalyzer> {
+    let language = match lang::detect(query) {
+        Lang::English => Language::English,
+        Lang::German => Language::German,
+        Lang::Finnish => Language::Finnish,
+        Lang::French => Language::French,
+        Lang::Spanish => Language::Spanish,
+        Lang::Russian => Language::Russian,
+        Lang::Unknown if query.is_ascii() => Language::English,
+        Lang::Unknown => return None,
+    };
+    Some(TextAnalyzer::builder(RawTokenizer::default()).filter(Stemmer::new(language)).build())
+}
+
+/// A DFA accepting the strings within `budget` (1 or 2) edits of `word`;
+/// the builders are made once, as they are slow to build.
+fn levenshtein(word: &str, budget: u8) -> DFA {
+    static BUILDERS: [OnceLock<LevenshteinAutomatonBuilder>; 2] = [OnceLock::new(), OnceLock::new()];
+    let budget = budget.clamp(1, 2);
+    BUILDERS[usize::from(budget - 1)].get_or_init(|| LevenshteinAutomatonBuilder::new(budget, false)).build_dfa(word)
+}
+
+/// A Levenshtein DFA walked over the term dictionary.
+struct Within(DFA);
+
+impl tantivy_fst::Automaton for Within {
+    type State = u32;
+    fn start(&self) -> u32 { self.0.initial_state() }
+    fn is_match(&self, state: &u32) -> bool { matches!(self.0.distance(*state), Distance::Exact(_)) }
+    fn can_match(&self, state: &u32) -> bool { *state != SINK_STATE }
+    fn accept(&self, state: &u32, byte: u8) -> u32 { self.0.transition(*state, byte) }
+}
+
+/// `word` without surrounding punctuation, if what remains is all letters.
+fn plain_word(word: &str) -> Option<&str> {
+    let w = word.trim_matches(|c: char| matches!(c, ',' | '.' | '!' | '?' | ';'));
+    (!w.is_empty() && w.chars().all(char::is_alphabetic)).then_some(w)
+}
+
+/// The one token `analyzer` makes of `word`; `None` for a stopword.
+fn single_token(analyzer: &mut TextAnalyzer, word: &str) -> Option<String> {
+    let mut stream = analyzer.token_stream(word);
+    let token = stream.advance().then(|| stream.token().text.clone())?;
+    (!stream.advance()).then_some(token)
+}
+
+fn stem_of(stemmer: &mut TextAnalyzer, word: &str) -> String {
+    let mut stream = stemmer.token_stream(word);
+    if stream.advance() { stream.token().text.clone() } else { word.to_string() }
+}
+
+/// Levenshtein distance in characters.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = (prev + usize::from(ca != *cb)).min(row[j] + 1).min(cur + 1);
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
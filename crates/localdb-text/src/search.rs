@@ -1,17 +1,55 @@
+//! BM25 search over the Tantivy index with boosted AND/phrase variants.
+//!
+//! Builds three subqueries (OR, AND-by-default, and phrase if applicable) and
+//! combines them with a Boolean SHOULD query using weights (OR×1, AND×2, PHRASE×4).
+
 use anyhow::Result;
-use tantivy::{Index, collector::TopDocs, query::QueryParser, TantivyDocument};
-use tantivy::query::{BoostQuery, BooleanQuery, Occur, Query};
-use tantivy::schema::Value;
+use std::collections::HashMap;
+use tantivy::{Index, collector::TopDocs, query::QueryParser, TantivyDocument, Term};
+use tantivy::query::{BoostQuery, BooleanQuery, ConstScoreQuery, Occur, Query, TermQuery};
+use tantivy::schema::{IndexRecordOption, Value};
+use tantivy::tokenizer::TokenStream;
+use localdb_core::answer::best_sentence;
+use localdb_core::facets::FacetAliases;
+use localdb_core::roots::RootMap;
 use localdb_core::traits::TextIndexer;
-use localdb_core::types::{DocumentChunk, SearchHit, SourceKind};
+use localdb_core::types::{DocumentChunk, SearchHit, SourceKind, SparseVector};
+
+use crate::query::preprocess_query;
+use crate::{lang, sparse};
+use crate::rewrite::{QueryRewriter, Rewrite};
+use crate::stats::{FacetStats, FacetTally};
+use crate::translate::QueryTranslator;
+use crate::tantivy_utils::{browse_query, browse_top, data_roots, ngram_query, stored_id, stored_str, DocFields, TEXT_NGRAM, TEXT_SPARSE};
+
+/// Weight of the character n-gram subquery relative to the OR query.
+const NGRAM_BOOST: f32 = 0.5;
+
+/// Weight of dictionary translations of the query relative to the OR query.
+const TRANSLATION_BOOST: f32 = 0.5;
+
+/// Weight of the sparse lexical-weight subquery relative to the OR query.
+const SPARSE_BOOST: f32 = 1.0;
+
+/// Length of the leading-text snippet shown for browse results.
+const BROWSE_SNIPPET_CHARS: usize = 200;
 
 pub struct TantivySearchEngine {
 	index: Index,
 	searcher: tantivy::Searcher,
 	id_field: tantivy::schema::Field,
 	text_field: tantivy::schema::Field,
+	category_field: tantivy::schema::Field,
 	category_text_field: tantivy::schema::Field,
 	path_field: tantivy::schema::Field,
+	ngram_field: Option<tantivy::schema::Field>,
+	sparse_field: Option<tantivy::schema::Field>,
+	lang_field: Option<tantivy::schema::Field>,
+	doc_fields: DocFields,
+	data_roots: RootMap,
+	translator: Option<QueryTranslator>,
+	facet_aliases: FacetAliases,
+	rewriter: Option<QueryRewriter>,
 }
 
 #[derive(Debug, Clone)]
@@ -21,6 +59,9 @@ pub struct SearchResult {
 	pub category: String,
 	pub path: String,
 	pub snippet: String,
+	/// `offline media: <label>` when the document's data root is unplugged;
+	/// such hits are still shown but cannot be opened or exported.
+	pub offline: Option<String>,
 }
 
 impl TantivySearchEngine {
@@ -32,21 +73,90 @@ impl TantivySearchEngine {
 		let schema = index.schema();
 		let id_field = schema.get_field("id")?;
 		let text_field = schema.get_field("text")?;
+		let category_field = schema.get_field("category")?;
 		let category_text_field = schema.get_field("category_text")?;
 		let path_field = schema.get_field("doc_path")?;
-		Ok(Self { index, searcher, id_field, text_field, category_text_field, path_field })
+		let ngram_field = schema.get_field(TEXT_NGRAM).ok();
+		let sparse_field = schema.get_field(TEXT_SPARSE).ok();
+		let lang_field = schema.get_field("lang").ok();
+		let doc_fields = DocFields::of(&schema);
+		let data_roots = data_roots(&index);
+		Ok(Self { index, searcher, id_field, text_field, category_field, category_text_field, path_field, ngram_field, sparse_field, lang_field, doc_fields, data_roots, translator: None, facet_aliases: FacetAliases::default(), rewriter: None })
 	}
 
+    /// Also match dictionary translations of the query words (cross-language
+    /// keyword search); applies to both `search` and the hybrid text leg.
+    pub fn with_translations(mut self, translator: QueryTranslator) -> Self {
+        self.translator = Some(translator).filter(|t| !t.is_empty());
+        self
+    }
+
+    /// Show renamed facets under their new names and let facet filters on a
+    /// new name also match documents still stored under the old one.
+    pub fn with_facet_aliases(mut self, aliases: FacetAliases) -> Self {
+        self.facet_aliases = aliases;
+        self
+    }
+
+    /// Correct misspelled query words and match other forms of each word
+    /// from the index's vocabulary (see `rewrite`); applies to both `search`
+    /// and the hybrid text leg.
+    pub fn with_query_rewriting(mut self, enabled: bool) -> Self {
+        self.rewriter = enabled.then(|| QueryRewriter::new(self.index.clone(), self.searcher.clone(), self.text_field));
+        self
+    }
+
+    /// The query this engine actually searches for `query_text`; unchanged
+    /// without `with_query_rewriting`.
+    pub fn rewrite(&self, query_text: &str) -> Result<Rewrite, anyhow::Error> {
+        let query_text = preprocess_query(query_text);
+        match &self.rewriter {
+            Some(r) => r.rewrite(&query_text),
+            None => Ok(Rewrite::unchanged(&query_text)),
+        }
+    }
+
+    /// Current name of a stored category.
+    fn display_category(&self, stored: &str) -> String {
+        self.facet_aliases.rename_stored(stored).unwrap_or_else(|| stored.to_string())
+    }
+
+    /// OR query over the translations of the query words, if any are known.
+    fn translation_query(&self, query_text: &str) -> Option<Box<dyn Query>> {
+        let words = self.translator.as_ref()?.translations(query_text);
+        // Dictionary entries are plain words; drop anything the parser would treat as syntax.
+        let text = words.iter().map(|w| w.chars().map(|c| if c.is_alphanumeric() { c } else { ' ' }).collect::<String>()).collect::<Vec<_>>().join(" ");
+        if text.trim().is_empty() { return None; }
+        let q = QueryParser::for_index(&self.index, vec![self.text_field]).parse_query(&text).ok()?;
+        Some(Box::new(BoostQuery::new(q, TRANSLATION_BOOST)))
+    }
+
+    /// Boosted OR over the token ids of `weights`, the query's sparse vector.
+    fn sparse_query(&self, weights: &SparseVector) -> Option<Box<dyn Query>> {
+        sparse::sparse_query(self.sparse_field?, weights).map(|q| Box::new(BoostQuery::new(q, SPARSE_BOOST)) as Box<dyn Query>)
+    }
+
     /// Run a BM25 search with AND/phrase boosting and return top `limit` results.
+    /// An empty (or whitespace-only) query browses instead (see `browse`).
     pub fn search(&self, query_text: &str, limit: usize) -> Result<Vec<SearchResult>, anyhow::Error> {
+        self.search_under(None, query_text, limit)
+    }
+
+    /// Like `search`, keeping only documents under `facet` (or its old names).
+    pub fn search_under(&self, facet: Option<&str>, query_text: &str, limit: usize) -> Result<Vec<SearchResult>, anyhow::Error> {
+        let query_text = &preprocess_query(query_text);
+        if query_text.is_empty() { return self.browse(facet, limit); }
+        // Word forms widen the OR/AND queries; phrases and snippets use the corrected words.
+        let rewrite = self.rewrite(query_text)?;
+        let query_text = &rewrite.corrected;
         // OR query (default behavior)
         let parser_or = QueryParser::for_index(&self.index, vec![self.text_field]);
-        let or_q = parser_or.parse_query(query_text)?;
+        let or_q = parser_or.parse_query(&rewrite.expanded)?;
 
         // AND query (conjunction by default)
         let mut parser_and = QueryParser::for_index(&self.index, vec![self.text_field]);
         parser_and.set_conjunction_by_default();
-        let and_q = parser_and.parse_query(query_text)?;
+        let and_q = parser_and.parse_query(&rewrite.expanded)?;
 
         // Phrase query if multiword
         let phrase_q: Option<Box<dyn Query>> = if query_text.split_whitespace().count() > 1 {
@@ -62,31 +172,115 @@ impl TantivySearchEngine {
         subs.push((Occur::Should, Box::new(BoostQuery::new(or_q.box_clone(), 1.0))));
         subs.push((Occur::Should, Box::new(BoostQuery::new(and_q.box_clone(), 2.0))));
         if let Some(pq) = phrase_q { subs.push((Occur::Should, Box::new(BoostQuery::new(pq, 4.0)))); }
-        let combined = BooleanQuery::new(subs);
+        // Languages that stem poorly also match on character n-grams (×0.5);
+        // other queries never do, not even as a fallback.
+        let ngram_q = self.ngram_field.filter(|_| lang::detect(query_text).uses_ngrams()).and_then(|f| ngram_query(&self.index, f, query_text));
+        if let Some(nq) = ngram_q.as_ref() {
+            subs.push((Occur::Should, Box::new(BoostQuery::new(nq.box_clone(), NGRAM_BOOST))));
+        }
+        if let Some(tq) = self.translation_query(query_text) { subs.push((Occur::Should, tq)); }
+        let mut combined: Box<dyn Query> = Box::new(BooleanQuery::new(subs));
+        let ngram_q = match facet.filter(|f| !matches!(f.trim(), "" | "/")) {
+            Some(f) => {
+                // The filter only selects documents; scores stay the text match's.
+                let filter = self.facet_query(Some(f))?;
+                combined = only_under(combined, filter.as_ref());
+                ngram_q.map(|q| only_under(q, filter.as_ref()))
+            }
+            None => ngram_q,
+        };
 
-        let top_docs = self.searcher.search(&combined, &TopDocs::with_limit(limit))?;
+        let mut top_docs = self.searcher.search(combined.as_ref(), &TopDocs::with_limit(limit))?;
+        // Whole words found nothing (an unseen inflection or compound of an
+        // n-gram language): fall back to n-grams alone.
+        if top_docs.is_empty() {
+            if let Some(nq) = ngram_q {
+                top_docs = self.searcher.search(nq.as_ref(), &TopDocs::with_limit(limit))?;
+                combined = nq;
+            }
+        }
         let mut results = Vec::new();
         for (score, doc_address) in top_docs { let doc: TantivyDocument = self.searcher.doc(doc_address)?;
-            let id = doc.get_first(self.id_field).unwrap().as_str().unwrap();
-            let category = doc.get_first(self.category_text_field).unwrap().as_str().unwrap();
-            let path = doc.get_first(self.path_field).unwrap().as_str().unwrap();
-            let snippet_generator = tantivy::snippet::SnippetGenerator::create(&self.searcher, &combined, self.text_field)?;
+            let id = stored_id(&doc, self.id_field, doc_address)?;
+            let category = stored_str(&doc, self.category_text_field, "category_text", id)?;
+            let path = stored_str(&doc, self.path_field, "doc_path", id)?;
+            let snippet_generator = tantivy::snippet::SnippetGenerator::create(&self.searcher, combined.as_ref(), self.text_field)?;
             let snippet = snippet_generator.snippet_from_doc(&doc);
-            results.push(SearchResult { score, id: id.to_string(), category: category.to_string(), path: path.to_string(), snippet: snippet.to_html() }); }
+            results.push(SearchResult { score, id: id.to_string(), category: self.display_category(category), path: self.display_path(path), snippet: snippet_html(&snippet, query_text), offline: self.data_roots.offline_label(path) }); }
 		Ok(results)
 	}
 
+    /// Browse mode: top `limit` documents, newest first, optionally under
+    /// `facet`. Snippets are the leading characters of the stored text.
+    pub fn browse(&self, facet: Option<&str>, limit: usize) -> Result<Vec<SearchResult>, anyhow::Error> {
+        let mut results = Vec::new();
+        for (score, doc_address) in self.browse_top(facet, limit)? { let doc: TantivyDocument = self.searcher.doc(doc_address)?;
+            let id = stored_id(&doc, self.id_field, doc_address)?;
+            let category = stored_str(&doc, self.category_text_field, "category_text", id)?;
+            let path = stored_str(&doc, self.path_field, "doc_path", id)?;
+            let text = stored_str(&doc, self.text_field, "text", id)?;
+            let snippet: String = text.chars().take(BROWSE_SNIPPET_CHARS).collect();
+            results.push(SearchResult { score, id: id.to_string(), category: self.display_category(category), path: self.display_path(path), snippet, offline: self.data_roots.offline_label(path) }); }
+		Ok(results)
+	}
+
+    /// Newest `limit` documents under `facet` (or its old names), newest first.
+    fn browse_top(&self, facet: Option<&str>, limit: usize) -> Result<Vec<(f32, tantivy::DocAddress)>, anyhow::Error> {
+        browse_top(&self.searcher, self.facet_query(facet)?.as_ref(), limit)
+    }
+
+    /// Documents under `facet` or any of its old names; all documents for `None`.
+    fn facet_query(&self, facet: Option<&str>) -> Result<Box<dyn Query>, anyhow::Error> {
+        Ok(match facet.filter(|_| !self.facet_aliases.is_empty()) {
+            Some(f) => {
+                let sources = self.facet_aliases.sources(f);
+                let mut subqueries = Vec::with_capacity(sources.len());
+                for s in &sources { subqueries.push((Occur::Should, browse_query(self.category_field, Some(s))?)); }
+                Box::new(BooleanQuery::new(subqueries))
+            }
+            None => browse_query(self.category_field, facet)?,
+        })
+    }
+
+    /// Stored `doc_path`s are relative to the recorded data roots; show them absolute.
+    fn display_path(&self, stored: &str) -> String {
+        self.data_roots.resolve(stored).to_string_lossy().to_string()
+    }
+
     /// Compute facet counts for the root facet under the given query.
     pub fn get_facet_counts(&self, query_text: &str) -> Result<Vec<(String, u64)>, anyhow::Error> {
 		let query_parser = QueryParser::for_index(&self.index, vec![self.text_field]);
-		let query = query_parser.parse_query(query_text)?;
+		let query = query_parser.parse_query(&preprocess_query(query_text))?;
 		let mut facet_collector = tantivy::collector::FacetCollector::for_field("category");
 		facet_collector.add_facet(tantivy::schema::Facet::root());
 		let facet_counts = self.searcher.search(&query, &facet_collector)?;
 		let mut facets = Vec::new();
-		for (facet, count) in facet_counts.get(&tantivy::schema::Facet::root().to_string()) { facets.push((facet.to_string(), count)); }
+		for (facet, count) in facet_counts.get(&tantivy::schema::Facet::root().to_string()) {
+			let name = self.facet_aliases.resolve(&facet.to_string());
+			match facets.iter_mut().find(|(n, _)| *n == name) { Some((_, c)) => *c += count, None => facets.push((name, count)) }
+		}
 		Ok(facets)
 	}
+
+    /// Chunk, document, length and top-`top_terms` statistics of every facet
+    /// (see `stats`), read from the stored text of all chunks.
+    pub fn facet_stats(&self, top_terms: usize) -> Result<Vec<FacetStats>, anyhow::Error> {
+        let mut analyzer = self.index.tokenizer_for_field(self.text_field)?;
+        let field = |doc: &TantivyDocument, f: tantivy::schema::Field| doc.get_first(f).and_then(|v| v.as_str()).unwrap_or("").to_string();
+        let mut tally = FacetTally::default();
+        for segment in self.searcher.segment_readers() {
+            let store = segment.get_store_reader(1)?;
+            for doc in store.iter::<TantivyDocument>(segment.alive_bitset()) {
+                let doc = doc?;
+                let text = field(&doc, self.text_field);
+                let mut tokens = Vec::new();
+                let mut stream = analyzer.token_stream(&text);
+                while stream.advance() { tokens.push(stream.token().text.clone()); }
+                tally.add(&self.display_category(&field(&doc, self.category_text_field)), &field(&doc, self.path_field), &tokens);
+            }
+        }
+        Ok(tally.finish(top_terms))
+    }
 }
 
 impl TextIndexer for TantivySearchEngine {
@@ -96,19 +290,94 @@ impl TextIndexer for TantivySearchEngine {
     }
 
     fn search(&self, query: &str, k: usize) -> anyhow::Result<Vec<SearchHit>> {
+        self.search_in(query, k, None)
+    }
+
+    fn search_in(&self, query: &str, k: usize, lang: Option<&str>) -> anyhow::Result<Vec<SearchHit>> {
+        self.search_in_facet(query, k, lang, None)
+    }
+
+    fn search_in_facet(&self, query: &str, k: usize, lang: Option<&str>, facet: Option<&str>) -> anyhow::Result<Vec<SearchHit>> {
+        self.search_sparse(query, &SparseVector::new(), k, lang, facet)
+    }
+
+    fn search_sparse(&self, query: &str, sparse: &SparseVector, k: usize, lang: Option<&str>, facet: Option<&str>) -> anyhow::Result<Vec<SearchHit>> {
+        let rewrite = self.rewrite(query)?;
         let query_parser = QueryParser::for_index(&self.index, vec![self.text_field]);
-        let query = query_parser.parse_query(query)?;
-        let top_docs = self.searcher.search(&query, &TopDocs::with_limit(k))?;
+        let mut query = query_parser.parse_query(&rewrite.expanded)?;
+        if let Some(tq) = self.translation_query(&rewrite.corrected) { query = Box::new(BooleanQuery::new(vec![(Occur::Should, query), (Occur::Should, tq)])); }
+        if let Some(sq) = self.sparse_query(sparse) { query = Box::new(BooleanQuery::new(vec![(Occur::Should, query), (Occur::Should, sq)])); }
+        if let Some(lang) = lang {
+            // Indexes from before chunks carried a language hold none in `lang`.
+            let Some(field) = self.lang_field else { return Ok(Vec::new()) };
+            let only_lang = TermQuery::new(Term::from_field_text(field, &lang.to_ascii_lowercase()), IndexRecordOption::Basic);
+            query = Box::new(BooleanQuery::new(vec![(Occur::Must, query), (Occur::Must, Box::new(ConstScoreQuery::new(Box::new(only_lang), 0.0)))]));
+        }
+        if facet.is_some() {
+            query = Box::new(BooleanQuery::new(vec![(Occur::Must, query), (Occur::Must, Box::new(ConstScoreQuery::new(self.facet_query(facet)?, 0.0)))]));
+        }
+        let top_docs = self.searcher.search(query.as_ref(), &TopDocs::with_limit(k))?;
         let mut hits = Vec::new();
         for (score, doc_address) in top_docs {
             let doc: TantivyDocument = self.searcher.doc(doc_address)?;
-            let id = doc.get_first(self.id_field).and_then(|v| v.as_str()).unwrap_or("").to_string();
-            hits.push(SearchHit { id, score, source: SourceKind::Text });
+            let id = stored_id(&doc, self.id_field, doc_address)?.to_string();
+            hits.push(self.doc_fields.fill(&doc, SearchHit::new(id, score, SourceKind::Text)));
         }
-//! BM25 search over the Tantivy index with boosted AND/phrase variants.
-//!
-//! Builds three subqueries (OR, AND-by-default, and phrase if applicable) and
-//! combines them with a Boolean SHOULD query using weights (OR×1, AND×2, PHRASE×4).
         Ok(hits)
     }
+
+    fn browse(&self, facet: Option<&str>, k: usize) -> anyhow::Result<Vec<SearchHit>> {
+        let mut hits = Vec::new();
+        for (score, doc_address) in self.browse_top(facet, k)? {
+            let doc: TantivyDocument = self.searcher.doc(doc_address)?;
+            let id = stored_id(&doc, self.id_field, doc_address)?.to_string();
+            hits.push(self.doc_fields.fill(&doc, SearchHit::new(id, score, SourceKind::Text)));
+        }
+        Ok(hits)
+    }
+
+    fn texts(&self, ids: &[String]) -> anyhow::Result<HashMap<String, String>> {
+        let mut out = HashMap::new();
+        for id in ids {
+            let query = TermQuery::new(Term::from_field_text(self.id_field, id), IndexRecordOption::Basic);
+            if let Some((_, doc_address)) = self.searcher.search(&query, &TopDocs::with_limit(1))?.into_iter().next() {
+                let doc: TantivyDocument = self.searcher.doc(doc_address)?;
+                out.insert(id.clone(), stored_str(&doc, self.text_field, "text", id)?.to_string());
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Snippet HTML with matched terms in `<b>`; for question-shaped queries the
+/// sentence that best answers it is also wrapped in `<strong>`.
+fn snippet_html(snippet: &tantivy::snippet::Snippet, query: &str) -> String {
+    let text = snippet.fragment();
+    let Some(answer) = best_sentence(text, query) else { return snippet.to_html() };
+    let mut cuts: Vec<usize> = vec![0, answer.start, answer.end, text.len()];
+    for h in snippet.highlighted() { cuts.extend([h.start, h.end]); }
+    cuts.sort_unstable();
+    cuts.dedup();
+    let mut html = String::new();
+    for w in cuts.windows(2) {
+        let at = w[0];
+        // Term highlights never span a sentence boundary, so tags nest properly.
+        if snippet.highlighted().iter().any(|h| h.end == at) { html.push_str("</b>"); }
+        if at == answer.end { html.push_str("</strong>"); }
+        if at == answer.start { html.push_str("<strong>"); }
+        if snippet.highlighted().iter().any(|h| h.start == at) { html.push_str("<b>"); }
+        html.push_str(&escape_html(&text[at..w[1]]));
+    }
+    if snippet.highlighted().iter().any(|h| h.end == text.len()) { html.push_str("</b>"); }
+    if answer.end == text.len() { html.push_str("</strong>"); }
+    html
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&#x27;")
+}
+
+/// `q` restricted to documents matching `filter`, scored by `q` alone.
+fn only_under(q: Box<dyn Query>, filter: &dyn Query) -> Box<dyn Query> {
+    Box::new(BooleanQuery::new(vec![(Occur::Must, q), (Occur::Must, Box::new(ConstScoreQuery::new(filter.box_clone(), 0.0)))]))
 }
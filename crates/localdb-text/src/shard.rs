@@ -0,0 +1,451 @@
+//! One Tantivy index per top-level facet, for corpora too large for one index
+//! on small hardware.
+//!
+//! A sharded index directory holds a `SHARDED` marker and one ordinary index
+//! per top-level facet (`/preserves/jam` → `<dir>/preserves`). A facet-filtered
+//! search or browse (`TextIndexer::search_in_facet`, so also the hybrid text
+//! leg) opens and reads only the shards the facet (and its old names) can
+//! live in, so the memory-mapped working set is that facet's index;
+//! unfiltered queries fan out to every shard and merge hits by score. Shards
+//! are opened on first use and kept open. Maintenance (`delete_documents`,
+//! `stored_ids`, `delete_ids`, `relocate`) runs on every shard.
+//!
+//! `TextIndex` and `TextSearch` take either layout, so callers need not know
+//! which one a directory holds; `localdb-cli` creates a sharded index when
+//! `data.shard_text_index` is on and the text index is rebuilt.
+//!
+//! BM25 statistics are per shard, so scores of hits from different shards
+//! are only roughly comparable; query rewriting uses each shard's own terms.
+
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use localdb_core::facets::FacetAliases;
+use localdb_core::roots::RootMap;
+use localdb_core::traits::TextIndexer;
+use localdb_core::types::{DocumentChunk, SearchHit, SparseVector};
+
+use crate::query::preprocess_query;
+use crate::rewrite::Rewrite;
+use crate::stats::FacetStats;
+use crate::tantivy_utils::Analysis;
+use crate::translate::QueryTranslator;
+use crate::{SearchResult, TantivyIndexer, TantivySearchEngine};
+
+/// Marker file of a sharded index directory.
+pub const SHARD_MARKER: &str = "SHARDED";
+
+/// Whether `index_dir` holds per-facet shards rather than a single index.
+pub fn is_sharded(index_dir: &Path) -> bool { index_dir.join(SHARD_MARKER).is_file() }
+
+/// Shard holding documents of `category`: its top-level facet, with
+/// characters unsafe in a directory name replaced by `_`; `misc` when empty.
+pub fn shard_name(category: &str) -> String {
+    let top = category.trim().trim_start_matches('/').split('/').next().unwrap_or("");
+    let name: String = top.chars().map(|c| if c.is_alphanumeric() || matches!(c, '-' | '_' | ' ') { c } else { '_' }).collect();
+    let name = name.trim().to_string();
+    if name.is_empty() || name.starts_with('.') { "misc".to_string() } else { name }
+}
+
+/// The shard indexes under `index_dir` by name, for per-shard maintenance
+/// (`TantivyIndexer::delete_documents`, `relocate`, `stored_ids`, …).
+pub fn shard_dirs(index_dir: &Path) -> Result<BTreeMap<String, PathBuf>> {
+    let mut shards = BTreeMap::new();
+    for entry in std::fs::read_dir(index_dir)? {
+        let path = entry?.path();
+        if !TantivyIndexer::exists(&path) { continue; }
+        if let Some(name) = path.file_name().and_then(|n| n.to_str()) { shards.insert(name.to_string(), path); }
+    }
+    Ok(shards)
+}
+
+/// Writes each chunk to the shard of its category, creating shards as new
+/// top-level facets appear.
+pub struct ShardedIndexer {
+    index_dir: PathBuf,
+    analysis: Analysis,
+    ngrams: bool,
+    data_roots: Option<RootMap>,
+}
+
+impl ShardedIndexer {
+    /// Create an empty sharded index in `index_dir`, destroying any existing index.
+    pub fn with_analysis(index_dir: PathBuf, analysis: Analysis) -> Result<Self> {
+        if index_dir.exists() { std::fs::remove_dir_all(&index_dir)?; }
+        std::fs::create_dir_all(&index_dir)?;
+        std::fs::write(index_dir.join(SHARD_MARKER), "")?;
+        Ok(Self { index_dir, analysis, ngrams: false, data_roots: None })
+    }
+
+    /// Open an existing sharded index for appending; shards created from now
+    /// on use `analysis`, existing ones keep theirs.
+    pub fn open(index_dir: &Path, analysis: Analysis) -> Result<Self> {
+        anyhow::ensure!(is_sharded(index_dir), "{} is not a sharded text index", index_dir.display());
+        Ok(Self { index_dir: index_dir.to_path_buf(), analysis, ngrams: false, data_roots: None })
+    }
+
+    /// Record the data roots in every shard written (see `TantivyIndexer::with_data_roots`).
+    pub fn with_data_roots(mut self, roots: RootMap) -> Self { self.data_roots = Some(roots); self }
+
+    /// See `TantivyIndexer::with_ngram_fallback`.
+    pub fn with_ngram_fallback(mut self, enabled: bool) -> Self { self.ngrams = enabled; self }
+
+    /// `TantivyIndexer::delete_documents` on every shard.
+    pub fn delete_documents(index_dir: &Path, doc_paths: &[String]) -> Result<()> {
+        for dir in shard_dirs(index_dir)?.values() { TantivyIndexer::delete_documents(dir, doc_paths)?; }
+        Ok(())
+    }
+
+    /// Chunk ids of every live document in every shard.
+    pub fn stored_ids(index_dir: &Path) -> Result<HashSet<String>> {
+        let mut ids = HashSet::new();
+        for dir in shard_dirs(index_dir)?.values() { ids.extend(TantivyIndexer::stored_ids(dir)?); }
+        Ok(ids)
+    }
+
+    /// `TantivyIndexer::delete_ids` on every shard.
+    pub fn delete_ids(index_dir: &Path, ids: &[String]) -> Result<()> {
+        for dir in shard_dirs(index_dir)?.values() { TantivyIndexer::delete_ids(dir, ids)?; }
+        Ok(())
+    }
+
+    /// `TantivyIndexer::relocate` on every shard; shards created later get
+    /// the roots given to `with_data_roots`.
+    pub fn relocate(index_dir: &Path, root_name: &str, new_root: &Path) -> Result<()> {
+        for dir in shard_dirs(index_dir)?.values() { TantivyIndexer::relocate(dir, root_name, new_root)?; }
+        Ok(())
+    }
+
+    fn shard(&self, name: &str) -> Result<TantivyIndexer> {
+        let dir = self.index_dir.join(name);
+        let indexer = if TantivyIndexer::exists(&dir) { TantivyIndexer::open(&dir)? } else { TantivyIndexer::with_analysis(dir, self.analysis)? };
+        let indexer = match &self.data_roots { Some(roots) => indexer.with_data_roots(roots.clone()), None => indexer };
+        Ok(indexer.with_ngram_fallback(self.ngrams))
+    }
+}
+
+impl TextIndexer for ShardedIndexer {
+    fn index(&self, chunks: &[DocumentChunk]) -> Result<()> {
+        let mut by_shard: BTreeMap<String, Vec<DocumentChunk>> = BTreeMap::new();
+        for c in chunks { by_shard.entry(shard_name(&c.category)).or_default().push(c.clone()); }
+        for (name, chunks) in by_shard { self.shard(&name)?.index(&chunks)?; }
+        Ok(())
+    }
+
+    fn index_sparse(&self, chunks: &[DocumentChunk], sparse: &[SparseVector]) -> Result<()> {
+        anyhow::ensure!(sparse.len() == chunks.len(), "{} sparse vectors for {} chunks", sparse.len(), chunks.len());
+        let mut by_shard: BTreeMap<String, (Vec<DocumentChunk>, Vec<SparseVector>)> = BTreeMap::new();
+        for (c, s) in chunks.iter().zip(sparse) {
+            let shard = by_shard.entry(shard_name(&c.category)).or_default();
+            shard.0.push(c.clone());
+            shard.1.push(s.clone());
+        }
+        for (name, (chunks, sparse)) in by_shard { self.shard(&name)?.index_sparse(&chunks, &sparse)?; }
+        Ok(())
+    }
+
+    fn search(&self, query: &str, k: usize) -> Result<Vec<SearchHit>> {
+        TextIndexer::search(&ShardedSearchEngine::open(&self.index_dir)?, query, k)
+    }
+
+    fn search_in(&self, query: &str, k: usize, lang: Option<&str>) -> Result<Vec<SearchHit>> {
+        TextIndexer::search_in(&ShardedSearchEngine::open(&self.index_dir)?, query, k, lang)
+    }
+
+    fn search_in_facet(&self, query: &str, k: usize, lang: Option<&str>, facet: Option<&str>) -> Result<Vec<SearchHit>> {
+        TextIndexer::search_in_facet(&ShardedSearchEngine::open(&self.index_dir)?, query, k, lang, facet)
+    }
+
+    fn search_sparse(&self, query: &str, sparse: &SparseVector, k: usize, lang: Option<&str>, facet: Option<&str>) -> Result<Vec<SearchHit>> {
+        TextIndexer::search_sparse(&ShardedSearchEngine::open(&self.index_dir)?, query, sparse, k, lang, facet)
+    }
+
+    fn browse(&self, facet: Option<&str>, k: usize) -> Result<Vec<SearchHit>> {
+        TextIndexer::browse(&ShardedSearchEngine::open(&self.index_dir)?, facet, k)
+    }
+}
+
+/// Fan-out/merge searcher over the shards of a sharded index.
+pub struct ShardedSearchEngine {
+    shards: BTreeMap<String, PathBuf>,
+    open: Mutex<HashMap<String, Arc<TantivySearchEngine>>>,
+    translator: Option<QueryTranslator>,
+    facet_aliases: FacetAliases,
+    rewrite: bool,
+}
+
+impl ShardedSearchEngine {
+    /// Find the shards of the index in `index_dir`; none is opened yet.
+    pub fn open(index_dir: &Path) -> Result<Self> {
+        anyhow::ensure!(is_sharded(index_dir), "{} is not a sharded text index", index_dir.display());
+        Ok(Self { shards: shard_dirs(index_dir)?, open: Mutex::new(HashMap::new()), translator: None, facet_aliases: FacetAliases::default(), rewrite: false })
+    }
+
+    /// See `TantivySearchEngine::with_translations`.
+    pub fn with_translations(mut self, translator: QueryTranslator) -> Self { self.translator = Some(translator); self }
+
+    /// See `TantivySearchEngine::with_facet_aliases`; facet filters also open
+    /// the shards of a facet's old names.
+    pub fn with_facet_aliases(mut self, aliases: FacetAliases) -> Self { self.facet_aliases = aliases; self }
+
+    /// See `TantivySearchEngine::with_query_rewriting`.
+    pub fn with_query_rewriting(mut self, enabled: bool) -> Self { self.rewrite = enabled; self }
+
+    /// Names of the shards, sorted.
+    pub fn shards(&self) -> Vec<&str> { self.shards.keys().map(String::as_str).collect() }
+
+    /// Names of the shards opened so far.
+    pub fn opened(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.open.lock().unwrap_or_else(|e| e.into_inner()).keys().cloned().collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Search every shard and keep the best `limit` hits.
+    pub fn search(&self, query_text: &str, limit: usize) -> Result<Vec<SearchResult>> { self.search_under(None, query_text, limit) }
+
+    /// Search only the shards `facet` can live in, keeping hits under it.
+    pub fn search_under(&self, facet: Option<&str>, query_text: &str, limit: usize) -> Result<Vec<SearchResult>> {
+        let mut results = Vec::new();
+        for engine in self.engines_for(facet)? { results.extend(engine.search_under(facet, query_text, limit)?); }
+        Ok(best(results, limit, |r| (r.score, r.id.as_str())))
+    }
+
+    /// Browse the shards `facet` can live in. Browse scores rank documents
+    /// within their shard, so shards interleave in the merged list.
+    pub fn browse(&self, facet: Option<&str>, limit: usize) -> Result<Vec<SearchResult>> {
+        let mut results = Vec::new();
+        for engine in self.engines_for(facet)? { results.extend(engine.browse(facet, limit)?); }
+        Ok(best(results, limit, |r| (r.score, r.id.as_str())))
+    }
+
+    /// The query as shown to the user: the first shard's rewrite that changes
+    /// it. Each shard searches with its own rewrite.
+    pub fn rewrite(&self, query_text: &str) -> Result<Rewrite> {
+        for engine in self.engines_for(None)? {
+            let rewrite = engine.rewrite(query_text)?;
+            if rewrite.is_changed() { return Ok(rewrite); }
+        }
+        Ok(Rewrite::unchanged(&preprocess_query(query_text)))
+    }
+
+    /// `TantivySearchEngine::facet_stats` of every shard; a facet lives in
+    /// one shard, so their lists are concatenated and re-sorted.
+    pub fn facet_stats(&self, top_terms: usize) -> Result<Vec<FacetStats>> {
+        let mut stats = Vec::new();
+        for engine in self.engines_for(None)? { stats.extend(engine.facet_stats(top_terms)?); }
+        stats.sort_by(|a, b| b.tokens.cmp(&a.tokens).then_with(|| a.facet.cmp(&b.facet)));
+        Ok(stats)
+    }
+
+    /// Facet counts under the query, summed over shards.
+    pub fn get_facet_counts(&self, query_text: &str) -> Result<Vec<(String, u64)>> {
+        let mut facets: Vec<(String, u64)> = Vec::new();
+        for engine in self.engines_for(None)? {
+            for (name, count) in engine.get_facet_counts(query_text)? {
+                match facets.iter_mut().find(|(n, _)| *n == name) { Some((_, c)) => *c += count, None => facets.push((name, count)) }
+            }
+        }
+        Ok(facets)
+    }
+
+    /// Open engines of the shards that can hold documents under `facet`
+    /// (all of them for no facet), opening any not yet open.
+    fn engines_for(&self, facet: Option<&str>) -> Result<Vec<Arc<TantivySearchEngine>>> {
+        let names: Vec<&str> = match facet.map(str::trim).filter(|f| !f.is_empty() && *f != "/") {
+            Some(f) => {
+                let wanted: Vec<String> = self.facet_aliases.sources(f).iter().map(|s| shard_name(s)).collect();
+                self.shards.keys().filter(|n| wanted.contains(n)).map(String::as_str).collect()
+            }
+            None => self.shards(),
+        };
+        let mut open = self.open.lock().unwrap_or_else(|e| e.into_inner());
+        let mut engines = Vec::with_capacity(names.len());
+        for name in names {
+            if !open.contains_key(name) {
+                let mut engine = TantivySearchEngine::new(self.shards[name].clone())?.with_facet_aliases(self.facet_aliases.clone()).with_query_rewriting(self.rewrite);
+                if let Some(t) = &self.translator { engine = engine.with_translations(t.clone()); }
+                open.insert(name.to_string(), Arc::new(engine));
+            }
+            engines.push(open[name].clone());
+        }
+        Ok(engines)
+    }
+}
+
+impl TextIndexer for ShardedSearchEngine {
+    fn index(&self, _chunks: &[DocumentChunk]) -> Result<()> {
+        // Read-only search adapter; indexing should be done via ShardedIndexer.
+        Ok(())
+    }
+
+    fn search(&self, query: &str, k: usize) -> Result<Vec<SearchHit>> {
+        self.search_in(query, k, None)
+    }
+
+    fn search_in(&self, query: &str, k: usize, lang: Option<&str>) -> Result<Vec<SearchHit>> {
+        self.search_in_facet(query, k, lang, None)
+    }
+
+    fn search_in_facet(&self, query: &str, k: usize, lang: Option<&str>, facet: Option<&str>) -> Result<Vec<SearchHit>> {
+        self.search_sparse(query, &SparseVector::new(), k, lang, facet)
+    }
+
+    fn search_sparse(&self, query: &str, sparse: &SparseVector, k: usize, lang: Option<&str>, facet: Option<&str>) -> Result<Vec<SearchHit>> {
+        let mut hits = Vec::new();
+        for engine in self.engines_for(facet)? { hits.extend(TextIndexer::search_sparse(engine.as_ref(), query, sparse, k, lang, facet)?); }
+        Ok(best(hits, k, |h| (h.score, h.id.as_str())))
+    }
+
+    fn browse(&self, facet: Option<&str>, k: usize) -> Result<Vec<SearchHit>> {
+        let mut hits = Vec::new();
+        for engine in self.engines_for(facet)? { hits.extend(TextIndexer::browse(engine.as_ref(), facet, k)?); }
+        Ok(best(hits, k, |h| (h.score, h.id.as_str())))
+    }
+
+    fn texts(&self, ids: &[String]) -> Result<HashMap<String, String>> {
+        let mut out = HashMap::new();
+        for engine in self.engines_for(None)? {
+            let missing: Vec<String> = ids.iter().filter(|id| !out.contains_key(*id)).cloned().collect();
+            if missing.is_empty() { break; }
+            out.extend(TextIndexer::texts(engine.as_ref(), &missing)?);
+        }
+        Ok(out)
+    }
+}
+
+/// A text index of either layout, for writing.
+pub enum TextIndex {
+    Single(TantivyIndexer),
+    Sharded(ShardedIndexer),
+}
+
+impl TextIndex {
+    /// Whether `index_dir` holds an index of either layout.
+    pub fn exists(index_dir: &Path) -> bool { TantivyIndexer::exists(index_dir) || is_sharded(index_dir) }
+
+    /// Create an empty index in `index_dir`, one shard per top-level facet
+    /// with `sharded`, destroying any existing index.
+    pub fn create(index_dir: PathBuf, analysis: Analysis, sharded: bool) -> Result<Self> {
+        if sharded { return Ok(Self::Sharded(ShardedIndexer::with_analysis(index_dir, analysis)?)); }
+        if is_sharded(&index_dir) { std::fs::remove_dir_all(&index_dir)?; }
+        Ok(Self::Single(TantivyIndexer::with_analysis(index_dir, analysis)?))
+    }
+
+    /// Open the existing index in `index_dir` for appending; new shards of a
+    /// sharded one use `analysis`.
+    pub fn open(index_dir: &Path, analysis: Analysis) -> Result<Self> {
+        if is_sharded(index_dir) { Ok(Self::Sharded(ShardedIndexer::open(index_dir, analysis)?)) } else { Ok(Self::Single(TantivyIndexer::open(index_dir)?)) }
+    }
+
+    /// See `TantivyIndexer::with_data_roots`.
+    pub fn with_data_roots(self, roots: RootMap) -> Self {
+        match self { Self::Single(i) => Self::Single(i.with_data_roots(roots)), Self::Sharded(i) => Self::Sharded(i.with_data_roots(roots)) }
+    }
+
+    /// See `TantivyIndexer::with_ngram_fallback`.
+    pub fn with_ngram_fallback(self, enabled: bool) -> Self {
+        match self { Self::Single(i) => Self::Single(i.with_ngram_fallback(enabled)), Self::Sharded(i) => Self::Sharded(i.with_ngram_fallback(enabled)) }
+    }
+
+    /// `TantivyIndexer::delete_documents` on an index of either layout.
+    pub fn delete_documents(index_dir: &Path, doc_paths: &[String]) -> Result<()> {
+        if is_sharded(index_dir) { ShardedIndexer::delete_documents(index_dir, doc_paths) } else { TantivyIndexer::delete_documents(index_dir, doc_paths) }
+    }
+
+    /// `TantivyIndexer::stored_ids` on an index of either layout.
+    pub fn stored_ids(index_dir: &Path) -> Result<HashSet<String>> {
+        if is_sharded(index_dir) { ShardedIndexer::stored_ids(index_dir) } else { TantivyIndexer::stored_ids(index_dir) }
+    }
+
+    /// `TantivyIndexer::delete_ids` on an index of either layout.
+    pub fn delete_ids(index_dir: &Path, ids: &[String]) -> Result<()> {
+        if is_sharded(index_dir) { ShardedIndexer::delete_ids(index_dir, ids) } else { TantivyIndexer::delete_ids(index_dir, ids) }
+    }
+
+    /// `TantivyIndexer::relocate` on an index of either layout.
+    pub fn relocate(index_dir: &Path, root_name: &str, new_root: &Path) -> Result<()> {
+        if is_sharded(index_dir) { ShardedIndexer::relocate(index_dir, root_name, new_root) } else { TantivyIndexer::relocate(index_dir, root_name, new_root) }
+    }
+
+    fn inner(&self) -> &dyn TextIndexer {
+        match self { Self::Single(i) => i, Self::Sharded(i) => i }
+    }
+}
+
+impl TextIndexer for TextIndex {
+    fn index(&self, chunks: &[DocumentChunk]) -> Result<()> { self.inner().index(chunks) }
+    fn index_sparse(&self, chunks: &[DocumentChunk], sparse: &[SparseVector]) -> Result<()> { self.inner().index_sparse(chunks, sparse) }
+    fn search(&self, query: &str, k: usize) -> Result<Vec<SearchHit>> { self.inner().search(query, k) }
+    fn search_in(&self, query: &str, k: usize, lang: Option<&str>) -> Result<Vec<SearchHit>> { self.inner().search_in(query, k, lang) }
+    fn search_in_facet(&self, query: &str, k: usize, lang: Option<&str>, facet: Option<&str>) -> Result<Vec<SearchHit>> { self.inner().search_in_facet(query, k, lang, facet) }
+    fn search_sparse(&self, query: &str, sparse: &SparseVector, k: usize, lang: Option<&str>, facet: Option<&str>) -> Result<Vec<SearchHit>> { self.inner().search_sparse(query, sparse, k, lang, facet) }
+    fn browse(&self, facet: Option<&str>, k: usize) -> Result<Vec<SearchHit>> { self.inner().browse(facet, k) }
+    fn texts(&self, ids: &[String]) -> Result<HashMap<String, String>> { self.inner().texts(ids) }
+}
+
+/// A text index of either layout, for searching.
+pub enum TextSearch {
+    Single(Box<TantivySearchEngine>),
+    Sharded(ShardedSearchEngine),
+}
+
+impl TextSearch {
+    /// Open the index in `index_dir`, whichever layout it has.
+    pub fn open(index_dir: &Path) -> Result<Self> {
+        if is_sharded(index_dir) { Ok(Self::Sharded(ShardedSearchEngine::open(index_dir)?)) } else { Ok(Self::Single(Box::new(TantivySearchEngine::new(index_dir.to_path_buf())?))) }
+    }
+
+    /// See `TantivySearchEngine::with_translations`.
+    pub fn with_translations(self, translator: QueryTranslator) -> Self {
+        match self { Self::Single(e) => Self::Single(Box::new(e.with_translations(translator))), Self::Sharded(e) => Self::Sharded(e.with_translations(translator)) }
+    }
+
+    /// See `TantivySearchEngine::with_facet_aliases`.
+    pub fn with_facet_aliases(self, aliases: FacetAliases) -> Self {
+        match self { Self::Single(e) => Self::Single(Box::new(e.with_facet_aliases(aliases))), Self::Sharded(e) => Self::Sharded(e.with_facet_aliases(aliases)) }
+    }
+
+    /// See `TantivySearchEngine::with_query_rewriting`.
+    pub fn with_query_rewriting(self, enabled: bool) -> Self {
+        match self { Self::Single(e) => Self::Single(Box::new(e.with_query_rewriting(enabled))), Self::Sharded(e) => Self::Sharded(e.with_query_rewriting(enabled)) }
+    }
+
+    /// See `TantivySearchEngine::rewrite` and `ShardedSearchEngine::rewrite`.
+    pub fn rewrite(&self, query_text: &str) -> Result<Rewrite> {
+        match self { Self::Single(e) => e.rewrite(query_text), Self::Sharded(e) => e.rewrite(query_text) }
+    }
+
+    /// See `TantivySearchEngine::facet_stats`.
+    pub fn facet_stats(&self, top_terms: usize) -> Result<Vec<FacetStats>> {
+        match self { Self::Single(e) => e.facet_stats(top_terms), Self::Sharded(e) => e.facet_stats(top_terms) }
+    }
+
+    fn inner(&self) -> &dyn TextIndexer {
+        match self { Self::Single(e) => e.as_ref(), Self::Sharded(e) => e }
+    }
+}
+
+impl TextIndexer for TextSearch {
+    fn index(&self, chunks: &[DocumentChunk]) -> Result<()> { self.inner().index(chunks) }
+    fn index_sparse(&self, chunks: &[DocumentChunk], sparse: &[SparseVector]) -> Result<()> { self.inner().index_sparse(chunks, sparse) }
+    fn search(&self, query: &str, k: usize) -> Result<Vec<SearchHit>> { self.inner().search(query, k) }
+    fn search_in(&self, query: &str, k: usize, lang: Option<&str>) -> Result<Vec<SearchHit>> { self.inner().search_in(query, k, lang) }
+    fn search_in_facet(&self, query: &str, k: usize, lang: Option<&str>, facet: Option<&str>) -> Result<Vec<SearchHit>> { self.inner().search_in_facet(query, k, lang, facet) }
+    fn search_sparse(&self, query: &str, sparse: &SparseVector, k: usize, lang: Option<&str>, facet: Option<&str>) -> Result<Vec<SearchHit>> { self.inner().search_sparse(query, sparse, k, lang, facet) }
+    fn browse(&self, facet: Option<&str>, k: usize) -> Result<Vec<SearchHit>> { self.inner().browse(facet, k) }
+    fn texts(&self, ids: &[String]) -> Result<HashMap<String, String>> { self.inner().texts(ids) }
+}
+
+/// The `limit` highest-scoring items, ties broken by id.
+fn best<T>(mut items: Vec<T>, limit: usize, key: impl Fn(&T) -> (f32, &str)) -> Vec<T> {
+    items.sort_by(|a, b| {
+        let (sa, ia) = key(a);
+        let (sb, ib) = key(b);
+        sb.total_cmp(&sa).then_with(|| ia.cmp(ib))
+    });
+    items.truncate(limit);
+    items
+}
@@ -0,0 +1,48 @@
+//! Sparse (lexical weight) embeddings in the text index.
+//!
+//! `TextIndexer::index_sparse` takes each chunk's `SparseVector` (BGE-M3's
+//! lexical weights, computed by the hybrid engine in the same forward pass as
+//! its dense vector) and puts it into the `text_sparse` field as its token
+//! ids, each repeated `round(weight × SPARSE_SCALE)` times (at most
+//! `MAX_REPEATS`; lighter tokens are dropped). `TextIndexer::search_sparse`
+//! ORs the query's token ids into the text leg, each boosted by its weight.
+//! This is not BGE-M3's dot product: BM25 saturates term frequency and
+//! normalizes by field length, so a heavier token scores higher, but not in
+//! proportion to its weight. It brings the model's learned term importance to
+//! the text leg without another index. Chunks indexed without sparse vectors
+//! have no sparse terms, so switching them on takes `ingest --full`.
+
+use tantivy::query::{BooleanQuery, BoostQuery, Occur, Query, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption};
+use tantivy::Term;
+
+use localdb_core::types::SparseVector;
+
+/// Repeats per unit of weight in `text_sparse`.
+pub const SPARSE_SCALE: f32 = 20.0;
+
+/// Most repeats of one token, so no single token swamps a chunk.
+pub const MAX_REPEATS: usize = 16;
+
+/// Times `weight` repeats its token in `text_sparse`.
+fn repeats(weight: f32) -> usize { ((weight * SPARSE_SCALE).round().max(0.0) as usize).min(MAX_REPEATS) }
+
+/// `text_sparse` value of `v`: token ids separated by spaces, repeated by weight.
+pub fn sparse_text(v: &SparseVector) -> String {
+    let mut terms = Vec::new();
+    for (id, weight) in v {
+        let id = id.to_string();
+        terms.extend(std::iter::repeat_n(id, repeats(*weight)));
+    }
+    terms.join(" ")
+}
+
+/// OR of the token ids of `v` on `field`, each boosted by its weight; `None`
+/// when no token is heavy enough to be indexed.
+pub fn sparse_query(field: Field, v: &SparseVector) -> Option<Box<dyn Query>> {
+    let subs: Vec<(Occur, Box<dyn Query>)> = v.iter().filter(|(_, w)| repeats(*w) > 0).map(|(id, w)| {
+        let term = TermQuery::new(Term::from_field_text(field, &id.to_string()), IndexRecordOption::WithFreqs);
+        (Occur::Should, Box::new(BoostQuery::new(Box::new(term), *w)) as Box<dyn Query>)
+    }).collect();
+    (!subs.is_empty()).then(|| Box::new(BooleanQuery::new(subs)) as Box<dyn Query>)
+}
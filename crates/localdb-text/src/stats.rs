@@ -0,0 +1,93 @@
+//! Per-facet term statistics, for explaining odd rankings.
+//!
+//! BM25 weighs a term by how rare it is across the whole index and normalises
+//! scores by chunk length, so a facet filled mostly by one large book skews
+//! both: that book's vocabulary looks common everywhere, and its long chunks
+//! score lower than short ones. `TantivySearchEngine::facet_stats` scans the
+//! stored chunks once and reports, per facet, how many chunks and documents it
+//! holds, their average length in indexed tokens (stopwords removed), how much
+//! of it the largest document accounts for, and its most frequent terms.
+
+use std::collections::{HashMap, HashSet};
+
+/// Share of a facet's tokens above which one document is said to dominate it.
+pub const DOMINANT_SHARE: f32 = 0.5;
+
+/// A term of a facet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermStat {
+    pub term: String,
+    /// Occurrences in the facet's chunks.
+    pub count: u64,
+    /// Chunks of the facet it occurs in.
+    pub chunks: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FacetStats {
+    pub facet: String,
+    pub chunks: u64,
+    /// Distinct `doc_path`s.
+    pub documents: usize,
+    /// Indexed tokens over all chunks.
+    pub tokens: u64,
+    /// The document with the most tokens, and its share (0–1) of the facet's.
+    pub largest: Option<(String, f32)>,
+    /// Most frequent first.
+    pub top_terms: Vec<TermStat>,
+}
+
+impl FacetStats {
+    pub fn avg_chunk_tokens(&self) -> f32 {
+        if self.chunks == 0 { 0.0 } else { self.tokens as f32 / self.chunks as f32 }
+    }
+
+    /// Whether the largest document holds more than `DOMINANT_SHARE` of the
+    /// facet while the facet has other documents.
+    pub fn is_dominated(&self) -> bool {
+        self.documents > 1 && self.largest.as_ref().is_some_and(|(_, share)| *share > DOMINANT_SHARE)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Tally {
+    chunks: u64,
+    tokens: u64,
+    docs: HashMap<String, u64>,
+    /// Term → (occurrences, chunks).
+    terms: HashMap<String, (u64, u64)>,
+}
+
+/// Running totals per facet, fed one chunk at a time.
+#[derive(Debug, Default)]
+pub struct FacetTally { facets: HashMap<String, Tally> }
+
+impl FacetTally {
+    /// Count one chunk of `doc_path` under `facet`, given its indexed tokens.
+    pub fn add(&mut self, facet: &str, doc_path: &str, tokens: &[String]) {
+        let tally = self.facets.entry(facet.to_string()).or_default();
+        tally.chunks += 1;
+        tally.tokens += tokens.len() as u64;
+        *tally.docs.entry(doc_path.to_string()).or_default() += tokens.len() as u64;
+        let mut in_chunk = HashSet::new();
+        for t in tokens {
+            let entry = tally.terms.entry(t.clone()).or_default();
+            entry.0 += 1;
+            if in_chunk.insert(t.as_str()) { entry.1 += 1; }
+        }
+    }
+
+    /// Facets with the most tokens first, each with its `top` terms.
+    pub fn finish(self, top: usize) -> Vec<FacetStats> {
+        let mut stats: Vec<FacetStats> = self.facets.into_iter().map(|(facet, t)| {
+            let largest = t.docs.iter().max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+                .map(|(path, &n)| (path.clone(), if t.tokens == 0 { 0.0 } else { n as f32 / t.tokens as f32 }));
+            let mut top_terms: Vec<TermStat> = t.terms.into_iter().map(|(term, (count, chunks))| TermStat { term, count, chunks }).collect();
+            top_terms.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.term.cmp(&b.term)));
+            top_terms.truncate(top);
+            FacetStats { facet, chunks: t.chunks, documents: t.docs.len(), tokens: t.tokens, largest, top_terms }
+        }).collect();
+        stats.sort_by(|a, b| b.tokens.cmp(&a.tokens).then_with(|| a.facet.cmp(&b.facet)));
+        stats
+    }
+}
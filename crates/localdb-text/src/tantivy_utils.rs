@@ -1,27 +1,234 @@
-use tantivy::schema::{Schema, TextFieldIndexing, TextOptions, IndexRecordOption, FacetOptions, STRING, STORED};
-use tantivy::tokenizer::{TextAnalyzer, SimpleTokenizer, LowerCaser, StopWordFilter};
-use tantivy::Index;
+//! Schema, tokenizer, and stored-value helpers shared by the indexer and searcher.
 
-pub fn build_schema() -> Schema {
+use anyhow::anyhow;
+use tantivy::collector::TopDocs;
+use tantivy::query::{AllQuery, BooleanQuery, Occur, Query, TermQuery};
+use tantivy::schema::{Schema, Field, TextFieldIndexing, TextOptions, IndexRecordOption, Facet, FacetOptions, Value, FAST, STRING, STORED};
+use tantivy::tokenizer::{TextAnalyzer, SimpleTokenizer, LowerCaser, NgramTokenizer, StopWordFilter, TokenStream, WhitespaceTokenizer};
+use tantivy::{DocAddress, Index, IndexWriter, Order, Searcher, TantivyDocument, Term};
+use std::path::{Path, PathBuf};
+
+use localdb_core::error::Error as CoreError;
+use localdb_core::folder_meta::{decode_meta, encode_meta};
+use localdb_core::roots::RootMap;
+use localdb_core::types::{DocumentChunk, SearchHit, SourceSpan};
+
+use crate::translit::TranslitFilter;
+
+/// Analysis choices fixed when an index is created. They are recorded in the
+/// schema (as the `text` field's tokenizer), so searchers pick them up.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Analysis {
+	/// Fold Cyrillic and Latin spellings together (see `translit`).
+	pub transliterate: bool,
+}
+
+const TEXT_TOKENIZER: &str = "text_with_stopwords";
+const TRANSLIT_TOKENIZER: &str = "text_translit";
+
+pub fn build_schema() -> Schema { build_schema_with(Analysis::default()) }
+
+pub fn build_schema_with(analysis: Analysis) -> Schema {
 	let mut schema_builder = Schema::builder();
 	let _id_field = schema_builder.add_text_field("id", STRING | STORED);
 	let _doc_id_field = schema_builder.add_text_field("doc_id", STRING | STORED);
 	let _doc_path_field = schema_builder.add_text_field("doc_path", STRING | STORED);
-	let text_field_indexing = TextFieldIndexing::default().set_tokenizer("text_with_stopwords").set_index_option(IndexRecordOption::WithFreqsAndPositions);
+	let tokenizer = if analysis.transliterate { TRANSLIT_TOKENIZER } else { TEXT_TOKENIZER };
+	let text_field_indexing = TextFieldIndexing::default().set_tokenizer(tokenizer).set_index_option(IndexRecordOption::WithFreqsAndPositions);
 	let text_options = TextOptions::default().set_indexing_options(text_field_indexing).set_stored();
 	let _text_field = schema_builder.add_text_field("text", text_options);
+	// Character trigrams of the text, filled only for languages that stem poorly
+	// (see `lang`); not stored.
+	let ngram_indexing = TextFieldIndexing::default().set_tokenizer(NGRAM_TOKENIZER).set_index_option(IndexRecordOption::WithFreqs);
+	let _ngram_field = schema_builder.add_text_field(TEXT_NGRAM, TextOptions::default().set_indexing_options(ngram_indexing));
+	// Sparse-embedding token ids, repeated by weight (see `sparse`); not stored.
+	let sparse_indexing = TextFieldIndexing::default().set_tokenizer(SPARSE_TOKENIZER).set_index_option(IndexRecordOption::WithFreqs);
+	let _sparse_field = schema_builder.add_text_field(TEXT_SPARSE, TextOptions::default().set_indexing_options(sparse_indexing));
 	let _category_field = schema_builder.add_facet_field("category", FacetOptions::default());
 	let _category_text_field = schema_builder.add_text_field("category_text", STRING | STORED);
+	// Milliseconds since the epoch at indexing time; orders browse mode (newest first).
+	let _indexed_at_field = schema_builder.add_u64_field(INDEXED_AT, FAST | STORED);
+	// Document metadata of the chunk, stored only (see `DocFields`).
+	let _title_field = schema_builder.add_text_field("title", STORED);
+	let _author_field = schema_builder.add_text_field("author", STORED);
+	let _created_at_field = schema_builder.add_i64_field("created_at", STORED);
+	let _meta_field = schema_builder.add_text_field("meta", STORED);
+	// ISO 639-1 code of the chunk's language (`DocumentChunk::lang`).
+	let _lang_field = schema_builder.add_text_field("lang", STRING | STORED);
+	// Location in the source file (`DocumentChunk::span`), stored only.
+	for name in SPAN_FIELDS { schema_builder.add_u64_field(name, STORED); }
 	schema_builder.build()
 }
 
+/// `start_offset`, `end_offset`, `start_line` and `end_line` of a chunk's `SourceSpan`.
+const SPAN_FIELDS: [&str; 4] = ["start_offset", "end_offset", "start_line", "end_line"];
+
+/// The stored document metadata fields (`title`, `author`, `created_at`,
+/// `meta` as `key\tvalue` lines, and `lang`) and the chunk's span; each is
+/// `None` in indexes built before it.
+#[derive(Debug, Clone, Copy)]
+pub struct DocFields {
+	title: Option<Field>,
+	author: Option<Field>,
+	created_at: Option<Field>,
+	meta: Option<Field>,
+	lang: Option<Field>,
+	span: Option<[Field; 4]>,
+}
+
+impl DocFields {
+	pub fn of(schema: &Schema) -> Self {
+		let span = SPAN_FIELDS.iter().map(|name| schema.get_field(name).ok()).collect::<Option<Vec<Field>>>().and_then(|f| f.try_into().ok());
+		Self { title: schema.get_field("title").ok(), author: schema.get_field("author").ok(), created_at: schema.get_field("created_at").ok(), meta: schema.get_field("meta").ok(), lang: schema.get_field("lang").ok(), span }
+	}
+
+	/// Store the document metadata of `chunk` on `doc`.
+	pub fn add(&self, doc: &mut TantivyDocument, chunk: &DocumentChunk) {
+		if let (Some(f), Some(title)) = (self.title, &chunk.title) { doc.add_text(f, title); }
+		if let (Some(f), Some(author)) = (self.author, &chunk.author) { doc.add_text(f, author); }
+		if let (Some(f), Some(at)) = (self.created_at, chunk.created_at) { doc.add_i64(f, at); }
+		if let Some(f) = self.meta.filter(|_| !chunk.meta.is_empty()) { doc.add_text(f, encode_meta(&chunk.meta)); }
+		if let (Some(f), Some(lang)) = (self.lang, &chunk.lang) { doc.add_text(f, lang); }
+		if let (Some(fields), Some(span)) = (self.span, chunk.span) {
+			for (f, v) in fields.into_iter().zip([span.start_offset, span.end_offset, span.start_line, span.end_line]) { doc.add_u64(f, v as u64); }
+		}
+	}
+
+	/// `hit` with the document metadata and span stored on `doc`.
+	pub fn fill(&self, doc: &TantivyDocument, hit: SearchHit) -> SearchHit {
+		let text = |f: Option<Field>| f.and_then(|f| doc.get_first(f)).and_then(|v| v.as_str()).map(str::to_string);
+		SearchHit {
+			title: text(self.title),
+			author: text(self.author),
+			created_at: self.created_at.and_then(|f| doc.get_first(f)).and_then(|v| v.as_i64()),
+			meta: text(self.meta).map(|m| decode_meta(&m)).unwrap_or_default(),
+			lang: text(self.lang),
+			span: self.span.and_then(|fields| {
+				let [start_offset, end_offset, start_line, end_line] = fields.map(|f| doc.get_first(f).and_then(|v| v.as_u64()).map(|v| v as usize));
+				Some(SourceSpan { start_offset: start_offset?, end_offset: end_offset?, start_line: start_line?, end_line: end_line? })
+			}),
+			..hit
+		}
+	}
+}
+
+/// Character n-gram companion of `text` (absent in indexes built before it existed).
+pub const TEXT_NGRAM: &str = "text_ngram";
+const NGRAM_TOKENIZER: &str = "text_ngram";
+/// N-gram length for `text_ngram`.
+pub const NGRAM_LEN: usize = 3;
+
+/// Sparse-embedding terms of `text` (absent in indexes built before it existed).
+pub const TEXT_SPARSE: &str = "text_sparse";
+const SPARSE_TOKENIZER: &str = "text_sparse";
+
+/// OR of the n-gram terms of `text` on `field`, or `None` when the text is too
+/// short to produce any n-gram.
+pub fn ngram_query(index: &Index, field: Field, text: &str) -> Option<Box<dyn Query>> {
+	let mut analyzer = index.tokenizers().get(NGRAM_TOKENIZER)?;
+	let mut stream = analyzer.token_stream(text);
+	let mut terms: Vec<String> = Vec::new();
+	while stream.advance() {
+		let t = &stream.token().text;
+		if !t.contains(' ') && !terms.contains(t) { terms.push(t.clone()); }
+	}
+	if terms.is_empty() { return None; }
+	let subs: Vec<(Occur, Box<dyn Query>)> = terms.into_iter()
+		.map(|t| (Occur::Should, Box::new(TermQuery::new(Term::from_field_text(field, &t), IndexRecordOption::WithFreqs)) as Box<dyn Query>))
+		.collect();
+	Some(Box::new(BooleanQuery::new(subs)))
+}
+
+/// Fast field used to sort browse results. Indexes built before it existed
+/// browse in index order instead.
+pub const INDEXED_AT: &str = "indexed_at";
+
+/// Current time as an `indexed_at` value.
+pub fn now_millis() -> u64 {
+	std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+/// Query matching every document, or only those under `facet` (a facet term
+/// also matches its descendants). `None`, `""` and `"/"` mean no filter.
+pub fn browse_query(category_field: Field, facet: Option<&str>) -> anyhow::Result<Box<dyn Query>> {
+	let Some(f) = facet.map(str::trim).filter(|f| !f.is_empty() && *f != "/") else { return Ok(Box::new(AllQuery)) };
+	let path = if f.starts_with('/') { f.to_string() } else { format!("/{}", f) };
+	let facet = Facet::from_text(&path).map_err(|e| anyhow!("invalid facet '{}': {:?}", path, e))?;
+	Ok(Box::new(TermQuery::new(Term::from_facet(category_field, &facet), IndexRecordOption::Basic)))
+}
+
+/// Top `k` documents for browse mode, newest first when the index has
+/// `indexed_at`. Scores are rank-based (`1 / (1 + rank)`) so they stay ordered
+/// when merged with other hits.
+pub fn browse_top(searcher: &Searcher, query: &dyn Query, k: usize) -> anyhow::Result<Vec<(f32, DocAddress)>> {
+	let addrs: Vec<DocAddress> = if searcher.schema().get_field(INDEXED_AT).is_ok() {
+		let by_time = TopDocs::with_limit(k).order_by_fast_field::<u64>(INDEXED_AT, Order::Desc);
+		searcher.search(query, &by_time)?.into_iter().map(|(_, a)| a).collect()
+	} else {
+		searcher.search(query, &TopDocs::with_limit(k))?.into_iter().map(|(_, a)| a).collect()
+	};
+	Ok(addrs.into_iter().enumerate().map(|(rank, a)| (1.0 / (1 + rank) as f32, a)).collect())
+}
+
 pub fn register_tokenizer(index: &Index) {
 	let stop_words = vec![
 		"a","an","and","are","as","at","be","by","for","from","has","he","in","is","it","its","of","on","that","the","to","was","will","with","or","but","not","this","these","they","them","their","there","then","than","so","if","when","where","why","how","what","which","who","whom","whose","can","could","should","would","may","might","must","shall","do","does","did","have","had","having",
 	];
+	let stop_filter = || StopWordFilter::remove(stop_words.iter().map(|s| s.to_string()));
 	let tokenizer = TextAnalyzer::builder(SimpleTokenizer::default())
 		.filter(LowerCaser)
-		.filter(StopWordFilter::remove(stop_words.into_iter().map(|s| s.to_string())))
+		.filter(stop_filter())
 		.build();
-	index.tokenizers().register("text_with_stopwords", tokenizer);
-}
\ No newline at end of file
+	index.tokenizers().register(TEXT_TOKENIZER, tokenizer);
+	let translit = TextAnalyzer::builder(SimpleTokenizer::default())
+		.filter(LowerCaser)
+		.filter(stop_filter())
+		.filter(TranslitFilter)
+		.build();
+	index.tokenizers().register(TRANSLIT_TOKENIZER, translit);
+	if let Ok(ngrams) = NgramTokenizer::new(NGRAM_LEN, NGRAM_LEN, false) {
+		index.tokenizers().register(NGRAM_TOKENIZER, TextAnalyzer::builder(ngrams).filter(LowerCaser).build());
+	}
+	index.tokenizers().register(SPARSE_TOKENIZER, TextAnalyzer::builder(WhitespaceTokenizer::default()).build());
+}
+
+/// Read a stored string field of `doc`, naming the document in the error when
+/// the field is absent or not a string.
+pub fn stored_str<'a>(doc: &'a TantivyDocument, field: Field, name: &str, doc_id: &str) -> Result<&'a str, CoreError> {
+	doc.get_first(field).and_then(|v| v.as_str()).ok_or_else(|| CoreError::BadField {
+		doc_id: doc_id.to_string(),
+		field: name.to_string(),
+		expected: "a stored string",
+	})
+}
+
+/// Read the stored `id` field; the error names the doc address since there is no id.
+pub fn stored_id(doc: &TantivyDocument, id_field: Field, addr: DocAddress) -> Result<&str, CoreError> {
+	stored_str(doc, id_field, "id", &format!("<segment {} doc {}>", addr.segment_ord, addr.doc_id))
+}
+
+const DATA_ROOT_PREFIX: &str = "data_root=";
+
+/// Absolute form of an ingest data root, recorded so relative `doc_path`s can
+/// be resolved at display time (falls back to `root` if it cannot be canonicalized).
+pub fn absolute_root(root: &Path) -> PathBuf {
+	std::fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf())
+}
+
+/// Commit `writer`, recording the data roots in the index meta payload
+/// (one `data_root=` line per root).
+pub fn commit_with_roots(writer: &mut IndexWriter, roots: Option<&RootMap>) -> tantivy::Result<()> {
+	let mut prepared = writer.prepare_commit()?;
+	if let Some(roots) = roots {
+		let payload = roots.encode().lines().map(|l| format!("{}{}", DATA_ROOT_PREFIX, l)).collect::<Vec<_>>().join("\n");
+		prepared.set_payload(&payload);
+	}
+	prepared.commit()?;
+	Ok(())
+}
+
+/// Data roots recorded by the last commit (empty if none).
+pub fn data_roots(index: &Index) -> RootMap {
+	let Some(payload) = index.load_metas().ok().and_then(|m| m.payload) else { return RootMap::default() };
+	RootMap::decode(&payload.lines().filter_map(|l| l.strip_prefix(DATA_ROOT_PREFIX)).collect::<Vec<_>>().join("\n"))
+}
@@ -0,0 +1,63 @@
+//! Cross-language query expansion from a small offline dictionary.
+//!
+//! BGE-M3 already makes the vector leg multilingual; the BM25 leg only matches
+//! the words typed. With a dictionary loaded, each query word is expanded with
+//! its translations (`jam` → `варенье`, `konfitüre`), which are OR-ed into the
+//! text query at a lower boost so exact-language matches still rank first.
+//!
+//! Dictionary files are UTF-8 lines `term<TAB>translation|translation`;
+//! blank lines and `#` comments are ignored. Entries apply in both directions.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default)]
+pub struct QueryTranslator { map: HashMap<String, Vec<String>> }
+
+impl QueryTranslator {
+    /// Load one dictionary file, or every `.tsv` file in a directory.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut t = Self::default();
+        if path.is_dir() {
+            let mut files: Vec<_> = std::fs::read_dir(path)?.filter_map(|e| e.ok()).map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|x| x == "tsv")).collect();
+            files.sort();
+            for f in files { t.add_entries(&std::fs::read_to_string(f)?); }
+        } else {
+            t.add_entries(&std::fs::read_to_string(path)?);
+        }
+        Ok(t)
+    }
+
+    /// Parse dictionary lines (see module docs) into the translator.
+    pub fn add_entries(&mut self, text: &str) {
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            let Some((term, targets)) = line.split_once('\t') else { continue };
+            let term = term.trim().to_lowercase();
+            for target in targets.split('|').map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty()) {
+                self.insert(&term, &target);
+                self.insert(&target, &term);
+            }
+        }
+    }
+
+    fn insert(&mut self, from: &str, to: &str) {
+        let list = self.map.entry(from.to_string()).or_default();
+        if !list.iter().any(|x| x == to) { list.push(to.to_string()); }
+    }
+
+    pub fn is_empty(&self) -> bool { self.map.is_empty() }
+
+    /// Translations of the words of `query` (not including the words
+    /// themselves), deduplicated in query order.
+    pub fn translations(&self, query: &str) -> Vec<String> {
+        let mut out: Vec<String> = Vec::new();
+        for word in query.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+            for t in self.map.get(&word.to_lowercase()).into_iter().flatten() {
+                if !out.contains(t) { out.push(t.clone()); }
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,70 @@
+//! Cyrillic↔Latin transliteration folding for mixed-script corpora.
+//!
+//! Russian preserving/gardening texts are often searched from Latin keyboards
+//! (`varene`, `solenye ogurtsy`). With folding enabled, `TranslitFilter` maps
+//! every token to one Latin spelling at both analysis and query time, so either
+//! script matches. Latin input is canonicalized too (`kh`→`h`, `j`→`y`) to
+//! absorb the common romanization variants.
+
+use tantivy::tokenizer::{Token, TokenFilter, TokenStream, Tokenizer};
+
+/// Latin spelling of a lowercase Cyrillic letter (Russian + Ukrainian).
+fn latin(c: char) -> Option<&'static str> {
+    Some(match c {
+        'а' => "a", 'б' => "b", 'в' => "v", 'г' => "g", 'д' => "d", 'е' | 'ё' | 'э' => "e",
+        'ж' => "zh", 'з' => "z", 'и' | 'і' => "i", 'й' => "y", 'к' => "k", 'л' => "l",
+        'м' => "m", 'н' => "n", 'о' => "o", 'п' => "p", 'р' => "r", 'с' => "s", 'т' => "t",
+        'у' => "u", 'ф' => "f", 'х' => "h", 'ц' => "ts", 'ч' => "ch", 'ш' => "sh",
+        'щ' => "shch", 'ъ' | 'ь' => "", 'ы' => "y", 'ю' => "yu", 'я' => "ya",
+        'ї' => "yi", 'є' => "ye", 'ґ' => "g",
+        _ => return None,
+    })
+}
+
+/// Fold a lowercase token to its canonical Latin spelling.
+pub fn fold_to_latin(token: &str) -> String {
+    let mut out = String::with_capacity(token.len());
+    for c in token.chars() {
+        match latin(c) {
+            Some(l) => out.push_str(l),
+            None => out.push(c),
+        }
+    }
+    if out.contains("kh") { out = out.replace("kh", "h"); }
+    if out.contains('j') { out = out.replace('j', "y"); }
+    out
+}
+
+/// Token filter applying `fold_to_latin`; place it after `LowerCaser`.
+#[derive(Clone)]
+pub struct TranslitFilter;
+
+impl TokenFilter for TranslitFilter {
+    type Tokenizer<T: Tokenizer> = TranslitTokenizer<T>;
+
+    fn transform<T: Tokenizer>(self, tokenizer: T) -> TranslitTokenizer<T> { TranslitTokenizer(tokenizer) }
+}
+
+#[derive(Clone)]
+pub struct TranslitTokenizer<T>(T);
+
+impl<T: Tokenizer> Tokenizer for TranslitTokenizer<T> {
+    type TokenStream<'a> = TranslitTokenStream<T::TokenStream<'a>>;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> Self::TokenStream<'a> { TranslitTokenStream(self.0.token_stream(text)) }
+}
+
+pub struct TranslitTokenStream<T>(T);
+
+impl<T: TokenStream> TokenStream for TranslitTokenStream<T> {
+    fn advance(&mut self) -> bool {
+        if !self.0.advance() { return false; }
+        let token = self.0.token_mut();
+        token.text = fold_to_latin(&token.text);
+        true
+    }
+
+    fn token(&self) -> &Token { self.0.token() }
+
+    fn token_mut(&mut self) -> &mut Token { self.0.token_mut() }
+}
@@ -0,0 +1,19 @@
+use localdb_core::traits::TextIndexer;
+use localdb_testkit::chunk_in;
+use localdb_text::{TantivyIndexer, TantivySearchEngine};
+
+#[test]
+fn question_queries_bold_the_answer_sentence() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let dir = tmp.path().join("answer");
+    TantivyIndexer::new(dir.clone())?.index(&[chunk_in("canning", "/preserves", "Wipe the rims before sealing. Boil the jars for ten minutes at sea level. Store in a cool cellar.")])?;
+    let engine = TantivySearchEngine::new(dir)?;
+
+    let snippet = &engine.search("how long to boil jars?", 5)?[0].snippet;
+    assert!(snippet.contains("<strong><b>Boil</b> the <b>jars</b> for ten minutes at sea level.</strong>"), "{}", snippet);
+    assert!(!snippet.contains("<strong>Wipe"), "{}", snippet);
+
+    let plain = &engine.search("boil jars", 5)?[0].snippet;
+    assert!(!plain.contains("<strong>"), "{}", plain);
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use localdb_core::traits::TextIndexer;
+use localdb_core::types::{DocumentChunk, SourceSpan};
+use localdb_testkit::chunk_in;
+use localdb_text::{TantivyIndexer, TantivySearchEngine};
+
+#[test]
+fn empty_query_browses_with_optional_facet() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let index_dir = tmp.path().join("tantivy");
+    let indexer = TantivyIndexer::new(index_dir.clone())?;
+    indexer.index(&[
+        chunk_in("a", "/survival/fire", "bow drill and tinder"),
+        chunk_in("b", "/survival/water", "boiling and filtering"),
+        chunk_in("c", "/computers", "soldering a serial cable"),
+    ])?;
+
+    assert_eq!(indexer.browse(None, 10)?.len(), 3);
+    let under_survival = indexer.browse(Some("/survival"), 10)?;
+    assert_eq!(under_survival.len(), 2);
+    assert!(under_survival.iter().all(|h| h.id != "c"));
+    assert_eq!(indexer.browse(Some("/survival/fire"), 10)?.len(), 1);
+    assert_eq!(indexer.browse(None, 2)?.len(), 2);
+
+    let engine = TantivySearchEngine::new(index_dir)?;
+    let results = engine.search("   ", 5)?;
+    assert_eq!(results.len(), 3);
+    assert!(results.windows(2).all(|w| w[0].score >= w[1].score));
+    assert_eq!(engine.browse(Some("computers"), 5)?[0].id, "c");
+    Ok(())
+}
+
+#[test]
+fn hits_carry_stored_document_metadata() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let index_dir = tmp.path().join("tantivy");
+    let indexer = TantivyIndexer::new(index_dir.clone())?;
+    let book = DocumentChunk {
+        title: Some("Putting Food By".into()),
+        author: Some("Ruth Hertzberg".into()),
+        created_at: Some(1_700_000_000_000),
+        meta: [("trust".to_string(), "high".to_string())].into_iter().collect(),
+        span: Some(SourceSpan { start_offset: 120, end_offset: 137, start_line: 4, end_line: 4 }),
+        ..chunk_in("book", "/food", "pack the jars hot")
+    };
+    indexer.index(&[book, chunk_in("note", "/food", "label the jars")])?;
+
+    let engine = TantivySearchEngine::new(index_dir)?;
+    for hits in [indexer.search("pack", 5)?, TextIndexer::search(&engine, "pack", 5)?, TextIndexer::browse(&engine, Some("/food"), 5)?] {
+        let hit = hits.iter().find(|h| h.id == "book").unwrap();
+        assert_eq!((hit.title.as_deref(), hit.author.as_deref(), hit.created_at), (Some("Putting Food By"), Some("Ruth Hertzberg"), Some(1_700_000_000_000)));
+        assert_eq!(hit.meta.get("trust").map(String::as_str), Some("high"));
+        assert_eq!(hit.span.map(|s| s.lines()).as_deref(), Some("line 4"));
+    }
+    let note = TextIndexer::search(&engine, "label", 5)?.remove(0);
+    assert_eq!((note.title, note.created_at, note.meta.is_empty(), note.span), (None, None, true, None));
+    Ok(())
+}
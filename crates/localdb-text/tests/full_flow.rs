@@ -4,7 +4,7 @@ use walkdir::WalkDir;
 
 fn root_paths() -> (PathBuf, PathBuf) {
     // crates/localdb-text -> crates -> repo root
-    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).ancestors().nth(2).unwrap().to_path_buf();
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).ancestors().nth(2).expect("repo root").to_path_buf();
     let data_dir = root.join("test_data/txt");
     let index_dir = root.join("test_data/indexes/tantivy");
     (data_dir, index_dir)
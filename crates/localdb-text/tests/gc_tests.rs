@@ -0,0 +1,33 @@
+use localdb_core::traits::TextIndexer;
+use localdb_testkit::chunk;
+use localdb_text::TantivyIndexer;
+
+#[test]
+fn stored_ids_track_deletes_and_appends() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let index_dir = tmp.path().join("tantivy");
+    TantivyIndexer::new(index_dir.clone())?.index(&[chunk("a", "alpha"), chunk("b", "beta")])?;
+    let mut ids: Vec<String> = TantivyIndexer::stored_ids(&index_dir)?.into_iter().collect();
+    ids.sort();
+    assert_eq!(ids, vec!["a", "b"]);
+
+    TantivyIndexer::delete_ids(&index_dir, &["a".to_string()])?;
+    TantivyIndexer::open(&index_dir)?.index(&[chunk("c", "gamma")])?;
+    let mut ids: Vec<String> = TantivyIndexer::stored_ids(&index_dir)?.into_iter().collect();
+    ids.sort();
+    assert_eq!(ids, vec!["b", "c"], "open appends instead of rebuilding");
+    Ok(())
+}
+
+#[test]
+fn deleting_a_file_removes_its_fragments() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let index_dir = tmp.path().join("tantivy");
+    let at = |id: &str, doc_path: &str| localdb_core::types::DocumentChunk { doc_path: doc_path.to_string(), ..chunk(id, "bread") };
+    TantivyIndexer::new(index_dir.clone())?.index(&[at("a", "wiki.zim#A/Bread"), at("b", "wiki.zim#A/Yeast"), at("c", "wiki.zim.txt"), at("d", "notes.txt")])?;
+    TantivyIndexer::delete_documents(&index_dir, &["wiki.zim".to_string()])?;
+    let mut ids: Vec<String> = TantivyIndexer::stored_ids(&index_dir)?.into_iter().collect();
+    ids.sort();
+    assert_eq!(ids, vec!["c", "d"]);
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use localdb_core::traits::TextIndexer;
+use localdb_core::types::DocumentChunk;
+use localdb_testkit::chunk;
+use localdb_text::lang::{detect, Lang};
+use localdb_text::{TantivyIndexer, TantivySearchEngine};
+
+const FINNISH: &str = "Maanviljelijä kylvää ohraa ja vehnää keväällä, kun maa on sulanut ja pelto on kuiva.";
+const ENGLISH: &str = "The farmer sows barley and wheat in spring when the ground has thawed.";
+
+#[test]
+fn detects_languages_that_need_ngrams() {
+    assert_eq!(detect(FINNISH), Lang::Finnish);
+    assert_eq!(detect(ENGLISH), Lang::English);
+    assert_eq!(detect("Die Wasserversorgung ist nicht mit dem Brunnen verbunden"), Lang::German);
+    assert!(detect("maanviljelijöille").uses_ngrams());
+    assert!(!Lang::English.uses_ngrams());
+}
+
+#[test]
+fn inflected_finnish_query_matches_via_ngrams() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let with_ngrams = tmp.path().join("ngram");
+    TantivyIndexer::new(with_ngrams.clone())?.with_ngram_fallback(true).index(&[chunk("fi", FINNISH), chunk("en", ENGLISH)])?;
+    let hits = TantivySearchEngine::new(with_ngrams)?.search("maanviljelijöille", 5)?;
+    assert_eq!(hits.first().map(|h| h.id.as_str()), Some("fi"));
+
+    // Whole-word BM25 alone cannot match the inflected form.
+    let words_only = tmp.path().join("words");
+    TantivyIndexer::new(words_only.clone())?.index(&[chunk("fi", FINNISH), chunk("en", ENGLISH)])?;
+    assert!(TantivySearchEngine::new(words_only)?.search("maanviljelijöille", 5)?.is_empty());
+
+    // An English query with no whole-word match does not fall back to n-grams.
+    assert_eq!(detect("barleys thawing"), Lang::English);
+    assert!(TantivySearchEngine::new(tmp.path().join("ngram"))?.search("barleys thawing", 5)?.iter().all(|h| h.id == "en"));
+    Ok(())
+}
+
+#[test]
+fn stored_chunk_language_picks_the_strategy_and_comes_back_on_hits() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    // Its own words vote English, but chunking recorded the document as Finnish.
+    let short = DocumentChunk { lang: Some("fi".to_string()), ..chunk("fi", "the maanviljelijä of the farm") };
+    assert_eq!(detect(&short.content), Lang::English);
+    TantivyIndexer::new(tmp.path().to_path_buf())?.with_ngram_fallback(true).index(&[short, chunk("en", ENGLISH)])?;
+    let hits = TextIndexer::search(&TantivySearchEngine::new(tmp.path().to_path_buf())?, "maanviljelijöille", 5)?;
+    assert_eq!(hits.first().map(|h| (h.id.as_str(), h.lang.as_deref())), Some(("fi", Some("fi"))));
+    Ok(())
+}
+
+#[test]
+fn indexes_built_before_ngrams_and_timestamps_still_open() -> anyhow::Result<()> {
+    use tantivy::schema::{FacetOptions, Schema, STORED, STRING, TEXT};
+    let tmp = tempfile::tempdir()?;
+    let mut builder = Schema::builder();
+    for name in ["id", "doc_path", "category_text"] { builder.add_text_field(name, STRING | STORED); }
+    builder.add_text_field("text", TEXT | STORED);
+    builder.add_facet_field("category", FacetOptions::default());
+    tantivy::Index::create_in_dir(tmp.path(), builder.build())?;
+
+    TantivyIndexer::open(tmp.path())?.with_ngram_fallback(true).index(&[chunk("fi", FINNISH)])?;
+    assert!(TantivyIndexer::stored_ids(tmp.path())?.contains("fi"));
+    Ok(())
+}
@@ -0,0 +1,26 @@
+use localdb_text::query::{preprocess_query, MAX_QUERY_CHARS};
+
+#[test]
+fn collapses_whitespace_and_control_chars() {
+    assert_eq!(preprocess_query("  fire\u{0}\t\nstarting  "), "fire starting");
+}
+
+#[test]
+fn drops_unbalanced_quote() {
+    assert_eq!(preprocess_query("\"paper casings"), "paper casings");
+    assert_eq!(preprocess_query("\"a b\" \"c"), "\"a b\" c");
+}
+
+#[test]
+fn unbalanced_quote_removal_is_idempotent() {
+    // Fuzz finding: removing a quote between spaces left a double space.
+    let once = preprocess_query("a \" b");
+    assert_eq!(once, "a b");
+    assert_eq!(preprocess_query(&once), once);
+}
+
+#[test]
+fn truncates_huge_input() {
+    let huge = "x".repeat(MAX_QUERY_CHARS * 4);
+    assert_eq!(preprocess_query(&huge).chars().count(), MAX_QUERY_CHARS);
+}
@@ -0,0 +1,51 @@
+use localdb_core::traits::TextIndexer;
+use localdb_testkit::chunk;
+use localdb_text::rewrite::{edit_distance, Change};
+use localdb_text::{TantivyIndexer, TantivySearchEngine};
+
+#[test]
+fn edit_distance_counts_characters() {
+    assert_eq!(edit_distance("cannning", "canning"), 1);
+    assert_eq!(edit_distance("jar", "jars"), 1);
+    assert_eq!(edit_distance("погреб", "погрeб"), 1);
+    assert_eq!(edit_distance("", "abc"), 3);
+}
+
+#[test]
+fn misspelled_and_inflected_words_are_rewritten_unless_raw() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let dir = tmp.path().join("rewrite");
+    TantivyIndexer::new(dir.clone())?.index(&[
+        chunk("canning", "Canning jars need a full water bath"),
+        chunk("canned", "Canned peaches keep for a year in the cellar"),
+        chunk("candles", "Candles light the cellar in winter"),
+    ])?;
+
+    let raw = TantivySearchEngine::new(dir.clone())?;
+    assert!(raw.search("cannning", 5)?.is_empty());
+    assert!(!raw.rewrite("cannning")?.is_changed());
+
+    let engine = TantivySearchEngine::new(dir)?.with_query_rewriting(true);
+    let rewrite = engine.rewrite("cannning jars")?;
+    assert_eq!(rewrite.corrected, "canning jars");
+    assert_eq!(rewrite.expanded, "(canning OR canned) jars");
+    assert_eq!(rewrite.changes[0], Change::Spelling { from: "cannning".to_string(), to: "canning".to_string() });
+    assert_eq!(rewrite.changes[1], Change::Forms { word: "canning".to_string(), forms: vec!["canned".to_string()] });
+
+    // Only spelling fixes reach the corrected text; case and punctuation stay as typed.
+    assert_eq!(engine.rewrite("How long do Cannning jars keep?")?.corrected, "How long do canning jars keep?");
+    let forms_only = engine.rewrite("Canning jars?")?;
+    assert!(forms_only.is_changed());
+    assert_eq!(forms_only.corrected, "Canning jars?");
+
+    let hits = engine.search("cannning", 5)?;
+    let mut ids: Vec<&str> = hits.iter().map(|h| h.id.as_str()).collect();
+    ids.sort_unstable();
+    assert_eq!(ids, vec!["canned", "canning"]);
+    assert_eq!(TextIndexer::search(&engine, "cannning", 5)?.len(), 2);
+
+    // Stopwords, short words and query syntax pass through untouched.
+    assert!(!engine.rewrite("the jar")?.is_changed());
+    assert!(!engine.rewrite("\"cannning jars\"")?.is_changed());
+    Ok(())
+}
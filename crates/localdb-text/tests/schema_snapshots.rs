@@ -0,0 +1,38 @@
+//! Snapshot of the Tantivy schema. Existing indexes keep the schema they
+//! were created with, so a changed field name, type, option or tokenizer
+//! must show up here as an explicit snapshot update (`cargo insta review`).
+
+use localdb_text::tantivy_utils::build_schema;
+use tantivy::schema::{FieldType, Schema};
+
+/// One `name: type options` line per field.
+fn render(schema: &Schema) -> String {
+    schema.fields().map(|(_, entry)| {
+        let mut options = Vec::new();
+        let kind = match entry.field_type() {
+            FieldType::Str(o) => {
+                if let Some(i) = o.get_indexing_options() { options.push(format!("indexed({}, {:?})", i.tokenizer(), i.index_option())); }
+                if o.is_stored() { options.push("stored".to_string()); }
+                if o.is_fast() { options.push("fast".to_string()); }
+                "str"
+            }
+            FieldType::U64(o) | FieldType::I64(o) => {
+                if o.is_indexed() { options.push("indexed".to_string()); }
+                if o.is_stored() { options.push("stored".to_string()); }
+                if o.is_fast() { options.push("fast".to_string()); }
+                if matches!(entry.field_type(), FieldType::U64(_)) { "u64" } else { "i64" }
+            }
+            FieldType::Facet(o) => {
+                if o.is_stored() { options.push("stored".to_string()); }
+                "facet"
+            }
+            other => return format!("{}: {:?}\n", entry.name(), other),
+        };
+        format!("{}: {}{}{}\n", entry.name(), kind, if options.is_empty() { "" } else { " " }, options.join(" "))
+    }).collect()
+}
+
+#[test]
+fn text_index_schema() {
+    insta::assert_snapshot!("tantivy", render(&build_schema()));
+}
@@ -0,0 +1,76 @@
+use localdb_core::traits::TextIndexer;
+use localdb_testkit::chunk_in;
+use localdb_text::shard::{is_sharded, shard_dirs, shard_name};
+use localdb_text::tantivy_utils::Analysis;
+use localdb_text::{ShardedIndexer, ShardedSearchEngine, TextIndex, TextSearch};
+
+#[test]
+fn shard_names_come_from_the_top_level_facet() {
+    assert_eq!(shard_name("/preserves/jam"), "preserves");
+    assert_eq!(shard_name("tools"), "tools");
+    assert_eq!(shard_name("/"), "misc");
+    assert_eq!(shard_name("/../etc"), "misc");
+    assert_eq!(shard_name("/a:b/c"), "a_b");
+}
+
+#[test]
+fn facet_filtered_queries_open_only_their_shard() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let dir = tmp.path().join("tantivy");
+    let indexer = ShardedIndexer::with_analysis(dir.clone(), Analysis::default())?;
+    indexer.index(&[
+        chunk_in("jam", "/preserves/jam", "Strawberry jam sets with pectin"),
+        chunk_in("pickles", "/preserves/pickles", "Dill pickles in a brine crock"),
+        chunk_in("saw", "/tools/saws", "Sharpen the saw before cutting jam jars a shelf"),
+    ])?;
+    // Appending creates shards for new top-level facets.
+    ShardedIndexer::open(&dir, Analysis::default())?.index(&[chunk_in("goat", "/animals/goats", "Goats need a dry shelter")])?;
+    assert!(is_sharded(&dir));
+    assert_eq!(shard_dirs(&dir)?.keys().cloned().collect::<Vec<_>>(), vec!["animals", "preserves", "tools"]);
+
+    let engine = ShardedSearchEngine::open(&dir)?;
+    assert!(engine.opened().is_empty());
+    let hits = engine.search_under(Some("/preserves/jam"), "jam", 10)?;
+    assert_eq!(hits.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), vec!["jam"]);
+    assert_eq!(engine.browse(Some("/preserves"), 10)?.len(), 2);
+    assert_eq!(engine.opened(), vec!["preserves"]);
+
+    // Unfiltered queries fan out and merge.
+    let mut ids: Vec<String> = engine.search("jam", 10)?.into_iter().map(|h| h.id).collect();
+    ids.sort_unstable();
+    assert_eq!(ids, vec!["jam", "saw"]);
+    assert_eq!(engine.opened(), vec!["animals", "preserves", "tools"]);
+    assert_eq!(TextIndexer::browse(&engine, None, 10)?.len(), 4);
+    assert_eq!(TextIndexer::search(&engine, "shelter", 10)?[0].id, "goat");
+    Ok(())
+}
+
+#[test]
+fn either_layout_is_maintained_and_searched_alike() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let dir = tmp.path().join("tantivy");
+    let index = TextIndex::create(dir.clone(), Analysis::default(), true)?;
+    index.index(&[
+        chunk_in("jam", "/preserves/jam", "Strawberry jam sets with pectin"),
+        chunk_in("saw", "/tools/saws", "Sharpen the saw before cutting jam jars a shelf"),
+    ])?;
+    assert!(TextIndex::exists(&dir));
+    let mut ids: Vec<String> = TextIndex::stored_ids(&dir)?.into_iter().collect();
+    ids.sort_unstable();
+    assert_eq!(ids, vec!["jam", "saw"]);
+
+    let search = TextSearch::open(&dir)?;
+    let hits = search.search_in_facet("jam", 10, None, Some("/tools"))?;
+    assert_eq!(hits.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), vec!["saw"]);
+    let TextSearch::Sharded(engine) = &search else { panic!("expected a sharded index") };
+    assert_eq!(engine.opened(), vec!["tools"], "a facet filter reads only its shard");
+
+    TextIndex::delete_ids(&dir, &["saw".to_string()])?;
+    assert_eq!(TextIndex::stored_ids(&dir)?.len(), 1);
+
+    // Rebuilding without sharding replaces the shards with one index.
+    TextIndex::create(dir.clone(), Analysis::default(), false)?.index(&[chunk_in("jam", "/preserves/jam", "jam")])?;
+    assert!(!is_sharded(&dir));
+    assert!(matches!(TextSearch::open(&dir)?, TextSearch::Single(_)));
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use localdb_core::traits::{Embedder, SparseEmbedder, TextIndexer};
+use localdb_core::types::SparseVector;
+use localdb_testkit::chunk;
+use localdb_text::sparse::{sparse_text, MAX_REPEATS};
+use localdb_text::{TantivyIndexer, TantivySearchEngine};
+
+/// Sparse-only embedder over a tiny vocabulary where synonyms share a token,
+/// the way a learned model relates words BM25 sees as unrelated.
+struct Lexicon;
+
+impl Lexicon {
+    fn token(word: &str) -> Option<u32> {
+        match word {
+            "canning" | "preserving" => Some(1),
+            "jars" => Some(2),
+            "potatoes" => Some(3),
+            _ => None,
+        }
+    }
+}
+
+impl Embedder for Lexicon {
+    fn dim(&self) -> usize { 1 }
+    fn max_len(&self) -> usize { 64 }
+    fn embed_batch(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> { Ok(texts.iter().map(|_| vec![1.0]).collect()) }
+    fn sparse(&self) -> Option<&dyn SparseEmbedder> { Some(self) }
+}
+
+impl SparseEmbedder for Lexicon {
+    fn embed_sparse(&self, texts: &[String]) -> anyhow::Result<Vec<SparseVector>> {
+        Ok(texts.iter().map(|t| {
+            let mut v: SparseVector = t.split_whitespace().filter_map(Lexicon::token).map(|id| (id, 0.3)).collect();
+            v.sort_by_key(|(id, _)| *id);
+            v.dedup_by_key(|(id, _)| *id);
+            v
+        }).collect())
+    }
+}
+
+#[test]
+fn sparse_terms_repeat_by_weight() {
+    assert_eq!(sparse_text(&vec![(7, 0.1), (9, 0.01)]), "7 7");
+    assert_eq!(sparse_text(&vec![(5, 10.0)]).split(' ').count(), MAX_REPEATS);
+}
+
+#[test]
+fn sparse_weights_find_chunks_without_the_query_words() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let dir = tmp.path().join("tantivy");
+    let chunks = [chunk("a", "preserving jars for winter"), chunk("b", "storing potatoes in sand")];
+    let texts: Vec<String> = chunks.iter().map(|c| c.content.clone()).collect();
+    TantivyIndexer::new(dir.clone())?.index_sparse(&chunks, &Lexicon.embed_sparse(&texts)?)?;
+
+    let engine = TantivySearchEngine::new(dir)?;
+    assert!(TextIndexer::search(&engine, "canning", 5)?.is_empty(), "no sparse vector, no match");
+    let query = Lexicon.embed_sparse(&["canning".to_string()])?.remove(0);
+    let hits = engine.search_sparse("canning", &query, 5, None, None)?;
+    assert_eq!(hits.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), ["a"]);
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use localdb_core::traits::TextIndexer;
+use localdb_testkit::chunk_in;
+use localdb_text::{TantivyIndexer, TantivySearchEngine};
+
+#[test]
+fn facet_stats_report_counts_lengths_and_a_dominant_document() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let index_dir = tmp.path().join("tantivy");
+    TantivyIndexer::new(index_dir.clone())?.index(&[
+        chunk_in("big:1", "/survival/fire", "the fire needs tinder and dry tinder"),
+        chunk_in("big:2", "/survival/fire", "fire fire fire and more fire kindling"),
+        chunk_in("small:1", "/survival/fire", "flint"),
+        chunk_in("radio:1", "/computers", "serial cable"),
+    ])?;
+
+    let stats = TantivySearchEngine::new(index_dir)?.facet_stats(2)?;
+    assert_eq!(stats.iter().map(|f| f.facet.as_str()).collect::<Vec<_>>(), ["/survival/fire", "/computers"]);
+    let fire = &stats[0];
+    assert_eq!((fire.chunks, fire.documents), (3, 2));
+    // Stopwords (`the`, `and`) are not indexed, so not counted.
+    assert_eq!(fire.tokens, 12);
+    assert!((fire.avg_chunk_tokens() - 4.0).abs() < 1e-6);
+    let (path, share) = fire.largest.clone().unwrap();
+    assert_eq!(path, "big.txt");
+    assert!((share - 11.0 / 12.0).abs() < 1e-6);
+    assert!(fire.is_dominated());
+    assert_eq!(fire.top_terms.len(), 2);
+    assert_eq!((fire.top_terms[0].term.as_str(), fire.top_terms[0].count, fire.top_terms[0].chunks), ("fire", 5, 2));
+    assert_eq!((fire.top_terms[1].term.as_str(), fire.top_terms[1].count), ("tinder", 2));
+
+    // A single-document facet is not "dominated".
+    assert!(!stats[1].is_dominated());
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use localdb_core::traits::TextIndexer;
+use localdb_testkit::chunk;
+use localdb_text::translate::QueryTranslator;
+use localdb_text::{TantivyIndexer, TantivySearchEngine};
+
+fn dictionary() -> QueryTranslator {
+    let mut t = QueryTranslator::default();
+    t.add_entries("# preserves\njam\tваренье|konfitüre\ncellar\tпогреб\n\nbroken line without tab\n");
+    t
+}
+
+#[test]
+fn entries_apply_in_both_directions() {
+    let t = dictionary();
+    assert_eq!(t.translations("Strawberry JAM"), vec!["варенье", "konfitüre"]);
+    assert_eq!(t.translations("варенье"), vec!["jam"]);
+    assert_eq!(t.translations("konfitüre погреб"), vec!["jam", "cellar"]);
+    assert!(t.translations("bread").is_empty());
+}
+
+#[test]
+fn translated_query_finds_other_language_documents() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let dir = tmp.path().join("translate");
+    TantivyIndexer::new(dir.clone())?.index(&[
+        chunk("ru", "Клубничное варенье варят на медленном огне"),
+        chunk("en", "Strawberry jam sets faster with lemon juice"),
+        chunk("other", "Seed potatoes are cut two days before planting"),
+    ])?;
+
+    let plain = TantivySearchEngine::new(dir.clone())?;
+    assert!(plain.search("jam", 5)?.iter().all(|h| h.id != "ru"));
+
+    let engine = TantivySearchEngine::new(dir)?.with_translations(dictionary());
+    let hits = engine.search("jam", 5)?;
+    // Same-language matches still rank first; the translation adds the Russian one.
+    assert_eq!(hits.first().map(|h| h.id.as_str()), Some("en"));
+    assert!(hits.iter().any(|h| h.id == "ru"));
+    assert!(hits.iter().all(|h| h.id != "other"));
+
+    let leg = TextIndexer::search(&engine, "jam", 5)?;
+    assert!(leg.iter().any(|h| h.id == "ru"));
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use localdb_core::traits::TextIndexer;
+use localdb_testkit::chunk;
+use localdb_text::tantivy_utils::Analysis;
+use localdb_text::translit::fold_to_latin;
+use localdb_text::{TantivyIndexer, TantivySearchEngine};
+
+#[test]
+fn folding_maps_both_scripts_to_one_spelling() {
+    assert_eq!(fold_to_latin("варенье"), "varene");
+    assert_eq!(fold_to_latin("хлеб"), fold_to_latin("khleb"));
+    assert_eq!(fold_to_latin("юшка"), fold_to_latin("jushka"));
+    assert_eq!(fold_to_latin("garden"), "garden");
+}
+
+#[test]
+fn cyrillic_text_matches_latin_query_and_back() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let dir = tmp.path().join("translit");
+    TantivyIndexer::with_analysis(dir.clone(), Analysis { transliterate: true })?.index(&[
+        chunk("ru", "Клубничное варенье варят на медленном огне"),
+        chunk("en", "Pickled cucumbers keep for a year in the cellar"),
+    ])?;
+    let engine = TantivySearchEngine::new(dir)?;
+    assert_eq!(engine.search("varene", 5)?.first().map(|h| h.id.as_str()), Some("ru"));
+    assert_eq!(engine.search("варенье", 5)?.first().map(|h| h.id.as_str()), Some("ru"));
+    assert_eq!(engine.search("погреб cellar", 5)?.first().map(|h| h.id.as_str()), Some("en"));
+
+    // Without folding the scripts stay apart.
+    let plain = tmp.path().join("plain");
+    TantivyIndexer::new(plain.clone())?.index(&[chunk("ru", "Клубничное варенье варят на медленном огне")])?;
+    assert!(TantivySearchEngine::new(plain)?.search("varene", 5)?.is_empty());
+    Ok(())
+}
@@ -1,4 +1,5 @@
 use std::path::{Path, PathBuf};
+use localdb_vector::embed_provider::EmbedProvider;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -9,12 +10,16 @@ async fn main() -> anyhow::Result<()> {
     let emb = "embeddings";
     let cache = "emb_cache";
 
-    let conn = localdb_vector::table::open_db(&db_path.to_string_lossy()).await?;
-    localdb_vector::table::ensure_embeddings_table(&conn, emb).await?;
-    localdb_vector::table::ensure_cache_table(&conn, cache).await?;
+    localdb_core::progress::enable(&ws_root.join("dev_data/progress"), "backfill");
 
+    let conn = localdb_vector::table::open_db(&db_path.to_string_lossy()).await?;
     let provider = localdb_vector::embed_provider::local::LocalProvider::new()?;
-    let n = localdb_vector::embed_backfill::backfill_embeddings(&conn, docs, emb, cache, &provider, 128, None).await?;
+    let dim = localdb_vector::table::ensure_collection_dim(&conn, docs, provider.dim()).await?;
+    localdb_vector::table::ensure_embeddings_table(&conn, emb, dim).await?;
+    localdb_vector::table::ensure_cache_table(&conn, cache, dim).await?;
+
+    let n = localdb_vector::embed_backfill::backfill_embeddings(&conn, docs, emb, cache, &provider, &localdb_core::preprocess::Preprocessor::default(), 128, None).await?;
     println!("Backfilled {} chunks into '{}'", n, emb);
+    localdb_core::progress::finish(None);
     Ok(())
 }
@@ -16,13 +16,9 @@ async fn main() -> anyhow::Result<()> {
     let chunks = processor.process_directory(&data_dir)?;
     println!("Preparing {} chunks...", chunks.len());
     // Convert to docs with no vectors
-    let docs: Vec<LanceDocument> = chunks.iter().map(|c| LanceDocument{
-        id: c.id.clone(), doc_id: c.doc_id.clone(), doc_path: c.doc_path.clone(),
-        category: c.category.clone(), category_text: c.category_text.clone(), content: c.content.clone(),
-        chunk_index: c.chunk_index, total_chunks: c.total_chunks, vector: Vec::new()
-    }).collect();
+    let docs: Vec<LanceDocument> = chunks.iter().map(|c| LanceDocument::from_chunk(c, Vec::new())).collect();
 
-    let indexer = localdb_vector::LanceDbIndexer::new(&db_path, table).await?;
+    let indexer = localdb_vector::LanceDbIndexer::new(&db_path, table).await?.with_data_root(&data_dir);
     // Use internal helper via public API: index requires embeddings; we'll insert batches with empty vectors by calling private conversion path.
     // For simplicity, reusing index with zero embeddings will mark rows as 'new'.
     // Build a zero-vecs slice matching docs
@@ -9,6 +9,7 @@ async fn main() -> anyhow::Result<()> {
     let emb = "embeddings";
     let embedder_id = "local:localdb_vector::embed_provider::local::LocalProvider:d1024"; // default id shape; override as needed
 
+    localdb_core::progress::enable(&ws_root.join("dev_data/progress"), "index_build");
     let conn = localdb_vector::table::open_db(&db_path.to_string_lossy()).await?;
 
     // 1) Copy vectors into serving column from embeddings side-table
@@ -17,7 +18,8 @@ async fn main() -> anyhow::Result<()> {
 
     // 2) Compute params
     let ready = localdb_vector::index_build::count_ready_vectors(&conn, docs).await?;
-    let params = localdb_vector::index_build::compute_ivfpq_params(ready, 1024);
+    let dim = localdb_vector::table::collection_dim(&conn, docs).await?.unwrap_or(localdb_vector::schema::EMBEDDING_DIM);
+    let params = localdb_vector::index_build::compute_ivfpq_params(ready, dim as usize);
     println!("Training params: ready={} nlist={} m={} nbits=8", ready, params.nlist, params.m);
 
     // 3) Build index with a timestamped name
@@ -29,10 +31,12 @@ async fn main() -> anyhow::Result<()> {
     // 4) Minimal validation and flip
     let valid = localdb_vector::index_build::validate_index(&conn, docs, 10, 32).await?;
     if valid {
+        localdb_core::progress::report("flip", 0, None);
         localdb_vector::index_build::flip_active_index(&conn, docs, &index_name).await?;
         println!("Activated index: {}", index_name);
     } else {
         eprintln!("Validation failed; not flipping active index");
     }
+    localdb_core::progress::finish((!valid).then(|| "validation failed".to_string()));
     Ok(())
 }
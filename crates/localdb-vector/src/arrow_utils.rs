@@ -0,0 +1,108 @@
+//! Fallible column and value extraction for Arrow record batches.
+//!
+//! Lance returns whatever is on disk; a table written by an older schema or a
+//! projection that dropped a column must surface as a typed error
+//! (`localdb_core::error::Error::BadColumn`/`BadField`) rather than a panic.
+
+use arrow_array::{Array, FixedSizeListArray, Float32Array, Int64Array, RecordBatch, StringArray, TimestampMillisecondArray};
+use arrow_array::cast::AsArray;
+
+use localdb_core::error::Error as CoreError;
+use localdb_core::folder_meta::decode_meta;
+use localdb_core::types::{Meta, SourceSpan};
+
+/// Downcast column `name` of `batch` to `T`, or report it as missing/mistyped.
+pub fn column<'a, T: Array + 'static>(batch: &'a RecordBatch, name: &str, expected: &'static str) -> Result<&'a T, CoreError> {
+    batch
+        .column_by_name(name)
+        .and_then(|c| c.as_any().downcast_ref::<T>())
+        .ok_or_else(|| CoreError::BadColumn { column: name.to_string(), expected })
+}
+
+/// Like `column`, but a missing column is `Ok(None)`; only a wrong type is an error.
+pub fn optional_column<'a, T: Array + 'static>(batch: &'a RecordBatch, name: &str, expected: &'static str) -> Result<Option<&'a T>, CoreError> {
+    match batch.column_by_name(name) {
+        None => Ok(None),
+        Some(c) => c
+            .as_any()
+            .downcast_ref::<T>()
+            .map(Some)
+            .ok_or_else(|| CoreError::BadColumn { column: name.to_string(), expected }),
+    }
+}
+
+pub fn string_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a StringArray, CoreError> {
+    column::<StringArray>(batch, name, "Utf8")
+}
+
+pub fn f32_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<Option<&'a Float32Array>, CoreError> {
+    optional_column::<Float32Array>(batch, name, "Float32")
+}
+
+/// The document metadata columns of a `documents` batch (`title`, `author`,
+/// `created_at`, `meta`, `lang`) and the span columns (`SPAN_COLUMNS`); each
+/// is `None` when the table predates it.
+pub struct DocColumns<'a> {
+    title: Option<&'a StringArray>,
+    author: Option<&'a StringArray>,
+    created_at: Option<&'a TimestampMillisecondArray>,
+    meta: Option<&'a StringArray>,
+    lang: Option<&'a StringArray>,
+    span: [Option<&'a Int64Array>; 4],
+}
+
+/// The columns of a chunk's `SourceSpan`, in field order.
+pub const SPAN_COLUMNS: [&str; 4] = ["start_offset", "end_offset", "start_line", "end_line"];
+
+impl<'a> DocColumns<'a> {
+    pub fn of(batch: &'a RecordBatch) -> Result<Self, CoreError> {
+        Ok(Self {
+            title: optional_column::<StringArray>(batch, "title", "Utf8")?,
+            author: optional_column::<StringArray>(batch, "author", "Utf8")?,
+            created_at: optional_column::<TimestampMillisecondArray>(batch, "created_at", "Timestamp(ms)")?,
+            meta: optional_column::<StringArray>(batch, "meta", "Utf8")?,
+            lang: optional_column::<StringArray>(batch, "lang", "Utf8")?,
+            span: [
+                optional_column::<Int64Array>(batch, SPAN_COLUMNS[0], "Int64")?,
+                optional_column::<Int64Array>(batch, SPAN_COLUMNS[1], "Int64")?,
+                optional_column::<Int64Array>(batch, SPAN_COLUMNS[2], "Int64")?,
+                optional_column::<Int64Array>(batch, SPAN_COLUMNS[3], "Int64")?,
+            ],
+        })
+    }
+
+    pub fn title(&self, i: usize) -> Option<String> { non_null(self.title, i).map(|c| c.value(i).to_string()) }
+
+    pub fn author(&self, i: usize) -> Option<String> { non_null(self.author, i).map(|c| c.value(i).to_string()) }
+
+    pub fn created_at(&self, i: usize) -> Option<i64> { non_null(self.created_at, i).map(|c| c.value(i)) }
+
+    pub fn meta(&self, i: usize) -> Meta { non_null(self.meta, i).map(|c| decode_meta(c.value(i))).unwrap_or_default() }
+
+    pub fn lang(&self, i: usize) -> Option<String> { non_null(self.lang, i).map(|c| c.value(i).to_string()) }
+
+    /// Row `i`'s span, when all four of its columns are set.
+    pub fn span(&self, i: usize) -> Option<SourceSpan> {
+        let [start_offset, end_offset, start_line, end_line] = self.span.map(|c| non_null(c, i).map(|c| c.value(i) as usize));
+        Some(SourceSpan { start_offset: start_offset?, end_offset: end_offset?, start_line: start_line?, end_line: end_line? })
+    }
+}
+
+fn non_null<T: Array>(col: Option<&T>, i: usize) -> Option<&T> { col.filter(|c| !c.is_null(i)) }
+
+pub fn vector_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a FixedSizeListArray, CoreError> {
+    column::<FixedSizeListArray>(batch, name, "FixedSizeList<Float32>")
+}
+
+/// Row `i` of a vector column as `Vec<f32>`; `Ok(None)` for a null vector.
+/// `doc_id` names the row in errors.
+pub fn vector_value(col: &FixedSizeListArray, i: usize, doc_id: &str) -> Result<Option<Vec<f32>>, CoreError> {
+    if col.is_null(i) { return Ok(None); }
+    let inner = col.value(i);
+    let vals = inner.as_primitive_opt::<arrow_array::types::Float32Type>().ok_or_else(|| CoreError::BadField {
+        doc_id: doc_id.to_string(),
+        field: "vector".to_string(),
+        expected: "Float32 items",
+    })?;
+    Ok(Some(vals.values().to_vec()))
+}
@@ -1,14 +1,33 @@
-use anyhow::Result;
+//! Lance-backed embedding cache keyed by `(content_hash, embedder_id)`.
+//!
+//! The cache is consulted prior to calling a provider and written through on
+//! cache misses. This enables offline operation and reduces repeated work.
+//! A cache table holds vectors of a single width (fixed at creation).
+//!
+//! `localdb-cli sync` ships entries with the files it copies
+//! (`file_entries`, `CacheEntry::encode`/`decode`, `put_missing`), and the
+//! receiving ingest embeds through `CachedEmbedder`, so synced chunks are not
+//! embedded a second time.
+
+use anyhow::{Result, anyhow};
 use lancedb::Connection;
-use lancedb::query::ExecutableQuery;
-use futures::TryStreamExt;
+use lancedb::query::{ExecutableQuery, QueryBase, Select};
 use arrow_array::{RecordBatch, RecordBatchIterator, StringArray, FixedSizeListArray};
-use arrow_array::cast::AsArray;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use chrono::Utc;
 
-use crate::schema::{build_cache_schema, EMBEDDING_DIM};
+use localdb_core::traits::{Embedder, MultiVectorEmbedder, SparseEmbedder};
+use localdb_core::types::{DocumentChunk, Embedded, Heads};
+
+use crate::arrow_utils::{string_column, vector_column, vector_value};
+use crate::schema::build_cache_schema;
+use crate::table::{doc_paths_filter, table_vector_dim, DELETE_BATCH};
+
+/// Cache key of a text: its blake3 hash (the `content_hash` of a chunk).
+pub fn hash_content(s: &str) -> String {
+    blake3::hash(s.as_bytes()).to_hex().to_string()
+}
 
 #[derive(Clone, Debug)]
 pub struct CacheEntry {
@@ -17,6 +36,22 @@ pub struct CacheEntry {
     pub vector: Vec<f32>,
 }
 
+impl CacheEntry {
+    /// `vec<TAB>embedder_id<TAB>content_hash<TAB>x,y,...`, one line per entry.
+    pub fn encode(&self) -> String {
+        let vector = self.vector.iter().map(f32::to_string).collect::<Vec<_>>().join(",");
+        format!("vec\t{}\t{}\t{}", self.embedder_id, self.content_hash, vector)
+    }
+
+    /// Parse an `encode` line; anything else (banners, log noise) is `None`.
+    pub fn decode(line: &str) -> Option<Self> {
+        let mut parts = line.splitn(4, '\t');
+        let (Some("vec"), Some(embedder_id), Some(content_hash), Some(vector)) = (parts.next(), parts.next(), parts.next(), parts.next()) else { return None };
+        let vector = vector.split(',').map(|x| x.trim().parse::<f32>().ok()).collect::<Option<Vec<_>>>()?;
+        Some(Self { content_hash: content_hash.to_string(), embedder_id: embedder_id.to_string(), vector })
+    }
+}
+
 pub async fn get_many(
     conn: &Connection,
     table: &str,
@@ -30,30 +65,14 @@ pub async fn get_many(
     let mut out = HashMap::new();
     let mut stream = t.query().execute().await?;
     while let Some(batch) = futures::TryStreamExt::try_next(&mut stream).await? {
-        let hash_col = batch
-            .column_by_name("content_hash")
-            .and_then(|c| c.as_any().downcast_ref::<StringArray>())
-            .expect("content_hash col");
-        let eid_col = batch
-            .column_by_name("embedder_id")
-            .and_then(|c| c.as_any().downcast_ref::<StringArray>())
-            .expect("embedder_id col");
-        let vec_col = batch
-            .column_by_name("vector")
-            .and_then(|c| c.as_any().downcast_ref::<FixedSizeListArray>())
-            .expect("vector col");
+        let hash_col = string_column(&batch, "content_hash")?;
+        let eid_col = string_column(&batch, "embedder_id")?;
+        let vec_col = vector_column(&batch, "vector")?;
         for i in 0..batch.num_rows() {
             let h = hash_col.value(i);
             if eid_col.value(i) != embedder_id { continue; }
             if !hashes.iter().any(|x| x == h) { continue; }
-            let list = vec_col.value(i);
-            let vals = list
-                .as_primitive::<arrow_array::types::Float32Type>()
-                .values()
-                .iter()
-                .copied()
-                .collect::<Vec<f32>>();
-            if vals.len() == EMBEDDING_DIM as usize { out.insert(h.to_string(), vals); }
+            if let Some(vals) = vector_value(vec_col, i, h)? { out.insert(h.to_string(), vals); }
         }
     }
     Ok(out)
@@ -61,12 +80,21 @@ pub async fn get_many(
 
 pub async fn put_many(conn: &Connection, table: &str, entries: &[CacheEntry]) -> Result<()> {
     if entries.is_empty() { return Ok(()); }
-    let names = conn.table_names().execute().await?;
-    if !names.contains(&table.to_string()) {
-        // create table
-        let schema = build_cache_schema();
-        let iter = RecordBatchIterator::new(vec![].into_iter(), schema.clone());
-        conn.create_table(table, Box::new(iter)).execute().await?;
+    let dim = entries[0].vector.len() as i32;
+    if let Some(bad) = entries.iter().find(|e| e.vector.len() as i32 != dim) {
+        return Err(anyhow!("cache entries have mixed dims: {} vs {}", bad.vector.len(), dim));
+    }
+    match table_vector_dim(conn, table).await? {
+        None => {
+            // create table
+            let schema = build_cache_schema(dim);
+            let iter = RecordBatchIterator::new(vec![].into_iter(), schema.clone());
+            conn.create_table(table, Box::new(iter)).execute().await?;
+        }
+        Some(existing) if existing != dim => {
+            return Err(anyhow!("cache table '{}' holds {}-dim vectors, got {}; use a separate cache table per dimension", table, existing, dim));
+        }
+        Some(_) => {}
     }
     let t = conn.open_table(table).execute().await?;
     // Build columns
@@ -82,19 +110,98 @@ pub async fn put_many(conn: &Connection, table: &str, entries: &[CacheEntry]) ->
         vectors.push(Some(e.vector.iter().map(|&x| Some(x)).collect()));
     }
     let batch = RecordBatch::try_new(
-        build_cache_schema(),
+        build_cache_schema(dim),
         vec![
             Arc::new(StringArray::from(hashes)),
             Arc::new(StringArray::from(eids)),
             Arc::new(arrow_array::TimestampMillisecondArray::from(created)),
-            Arc::new(FixedSizeListArray::from_iter_primitive::<arrow_array::types::Float32Type, _, _>(vectors.into_iter(), EMBEDDING_DIM)),
+            Arc::new(FixedSizeListArray::from_iter_primitive::<arrow_array::types::Float32Type, _, _>(vectors.into_iter(), dim)),
         ],
     )?;
-    let reader = Box::new(RecordBatchIterator::new(vec![Ok(batch)].into_iter(), build_cache_schema()));
+    let reader = Box::new(RecordBatchIterator::new(vec![Ok(batch)].into_iter(), build_cache_schema(dim)));
     t.add(reader).execute().await?;
     Ok(())
 }
-//! Lance-backed embedding cache keyed by `(content_hash, embedder_id)`.
-//!
-//! The cache is consulted prior to calling a provider and written through on
-//! cache misses. This enables offline operation and reduces repeated work.
+
+/// Serving vectors of the stored chunks of the files at `doc_paths` (their
+/// fragments included) as entries under `embedder_id`, keyed by each chunk's
+/// `content_hash`. Chunks without a serving vector are left out.
+pub async fn file_entries(conn: &Connection, docs_table: &str, embedder_id: &str, doc_paths: &[String]) -> Result<Vec<CacheEntry>> {
+    if !conn.table_names().execute().await?.contains(&docs_table.to_string()) { return Ok(Vec::new()); }
+    let t = conn.open_table(docs_table).execute().await?;
+    let mut out: BTreeMap<String, Vec<f32>> = BTreeMap::new();
+    for batch_paths in doc_paths.chunks(DELETE_BATCH) {
+        let mut stream = t.query().select(Select::columns(&["id", "content_hash", "vector"])).only_if(doc_paths_filter(batch_paths)).execute().await?;
+        while let Some(batch) = futures::TryStreamExt::try_next(&mut stream).await? {
+            let (ids, hashes, vectors) = (string_column(&batch, "id")?, string_column(&batch, "content_hash")?, vector_column(&batch, "vector")?);
+            for i in 0..batch.num_rows() {
+                if let Some(v) = vector_value(vectors, i, ids.value(i))? { out.insert(hashes.value(i).to_string(), v); }
+            }
+        }
+    }
+    Ok(out.into_iter().map(|(content_hash, vector)| CacheEntry { content_hash, embedder_id: embedder_id.to_string(), vector }).collect())
+}
+
+/// Add the `entries` that `table` does not hold yet; returns how many.
+pub async fn put_missing(conn: &Connection, table: &str, entries: Vec<CacheEntry>) -> Result<usize> {
+    let mut by_embedder: HashMap<String, Vec<CacheEntry>> = HashMap::new();
+    for e in entries { by_embedder.entry(e.embedder_id.clone()).or_default().push(e); }
+    let mut missing = Vec::new();
+    for (embedder_id, entries) in by_embedder {
+        let hashes: Vec<String> = entries.iter().map(|e| e.content_hash.clone()).collect();
+        let cached = get_many(conn, table, &embedder_id, &hashes).await?;
+        missing.extend(entries.into_iter().filter(|e| !cached.contains_key(&e.content_hash)));
+    }
+    put_many(conn, table, &missing).await?;
+    Ok(missing.len())
+}
+
+/// Embeds through `inner`, except texts whose `hash_content` has a vector in
+/// `vectors` (cache entries of the embedder `inner` is), which are served
+/// from there.
+pub struct CachedEmbedder {
+    inner: Arc<dyn Embedder>,
+    vectors: HashMap<String, Vec<f32>>,
+}
+
+impl CachedEmbedder {
+    pub fn new(inner: Arc<dyn Embedder>, vectors: HashMap<String, Vec<f32>>) -> Self { Self { inner, vectors } }
+
+    /// Vectors it serves without the model.
+    pub fn len(&self) -> usize { self.vectors.len() }
+
+    pub fn is_empty(&self) -> bool { self.vectors.is_empty() }
+}
+
+/// `inner` (whose id is `embedder_id`) answering from `table` for the
+/// `chunks` whose content it holds vectors of; `None` when it holds none.
+pub async fn cached_embedder(conn: &Connection, table: &str, embedder_id: &str, inner: Arc<dyn Embedder>, chunks: &[DocumentChunk]) -> Result<Option<CachedEmbedder>> {
+    let hashes: Vec<String> = chunks.iter().map(|c| hash_content(&c.content)).collect();
+    let vectors = get_many(conn, table, embedder_id, &hashes).await?;
+    Ok((!vectors.is_empty()).then(|| CachedEmbedder::new(inner, vectors)))
+}
+
+impl Embedder for CachedEmbedder {
+    fn dim(&self) -> usize { self.inner.dim() }
+    fn max_len(&self) -> usize { self.inner.max_len() }
+    fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let hashes: Vec<String> = texts.iter().map(|t| hash_content(t)).collect();
+        let misses: Vec<String> = texts.iter().zip(&hashes).filter(|(_, h)| !self.vectors.contains_key(*h)).map(|(t, _)| t.clone()).collect();
+        let mut embedded = if misses.is_empty() { Vec::new() } else { self.inner.embed_batch(&misses)? }.into_iter();
+        hashes.iter().map(|h| match self.vectors.get(h) {
+            Some(v) => Ok(v.clone()),
+            None => embedded.next().ok_or_else(|| anyhow!("embedder returned too few vectors")),
+        }).collect()
+    }
+    /// Queries are never cached; the cache holds passage vectors.
+    fn embed_queries(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> { self.inner.embed_queries(texts) }
+    fn sparse(&self) -> Option<&dyn SparseEmbedder> { self.inner.sparse() }
+    fn multi_vector(&self) -> Option<&dyn MultiVectorEmbedder> { self.inner.multi_vector() }
+    /// Served from the cache only when no other output is asked for: the
+    /// model runs for the heads anyway, and gives the dense vectors with them.
+    fn embed_passages_with(&self, texts: &[String], heads: Heads) -> Result<Embedded> {
+        if heads == Heads::default() { return Ok(Embedded { dense: self.embed_batch(texts)?, ..Embedded::default() }); }
+        self.inner.embed_passages_with(texts, heads)
+    }
+    fn embed_queries_with(&self, texts: &[String], heads: Heads) -> Result<Embedded> { self.inner.embed_queries_with(texts, heads) }
+}
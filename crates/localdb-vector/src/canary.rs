@@ -0,0 +1,77 @@
+//! The canary collection (`localdb_core::canary`) in LanceDB.
+//!
+//! `write_canary` embeds the canary documents and replaces the hidden
+//! `_canary` table with them; `self_test` checks the model a server loaded
+//! against that table and a sample of the serving collection.
+
+use anyhow::Result;
+use lancedb::{Connection, DistanceType};
+use lancedb::query::{ExecutableQuery, QueryBase, Select};
+use arrow_array::{Array, FixedSizeListArray};
+use std::collections::HashMap;
+use std::path::Path;
+
+use localdb_core::canary::{self, Problem, COLLECTION, QUERIES};
+use localdb_core::traits::Embedder;
+use localdb_core::types::{SearchHit, SourceKind};
+
+use crate::arrow_utils::{string_column, vector_value};
+use crate::table::collection_dim;
+use crate::writer::LanceDbIndexer;
+
+/// Vectors of the serving collection checked for corruption at startup.
+pub const VECTOR_SAMPLE: usize = 256;
+
+/// Embed the canary documents with `embedder` and replace the canary table.
+pub async fn write_canary(db_path: &Path, embedder: &dyn Embedder) -> Result<()> {
+    let chunks = canary::chunks();
+    let texts: Vec<String> = chunks.iter().map(|c| c.content.clone()).collect();
+    let embeddings = embedder.embed_batch(&texts)?;
+    LanceDbIndexer::new(db_path, COLLECTION).await?.overwrite(&chunks, &embeddings).await
+}
+
+/// Check `embedder` against the canary table and `docs_table`: recorded
+/// widths, sampled vectors, and the canary queries. `None` when no canary has
+/// been written yet (an index from before the self-test, or built without a model).
+pub async fn self_test(conn: &Connection, docs_table: &str, embedder: &dyn Embedder) -> Result<Option<Vec<Problem>>> {
+    let names = conn.table_names().execute().await?;
+    if !names.iter().any(|n| n == COLLECTION) { return Ok(None); }
+    let model = embedder.dim();
+    let mut problems = Vec::new();
+    for collection in [docs_table, COLLECTION] {
+        if !names.iter().any(|n| n == collection) { continue; }
+        problems.extend(canary::check_dim(collection, collection_dim(conn, collection).await?.map(|d| d as usize), model));
+        let vectors = sample_vectors(conn, collection, VECTOR_SAMPLE).await?;
+        problems.extend(canary::check_vectors(collection, &vectors));
+    }
+    // A canary of another width cannot be searched with this model's vectors.
+    if problems.iter().any(|p| matches!(p, Problem::DimMismatch { collection, .. } if collection == COLLECTION)) { return Ok(Some(problems)); }
+    let table = conn.open_table(COLLECTION).execute().await?;
+    let queries: Vec<String> = QUERIES.iter().map(|(q, _)| q.to_string()).collect();
+    let mut ranked: HashMap<String, Vec<SearchHit>> = HashMap::new();
+    for (query, q_vec) in queries.iter().zip(embedder.embed_queries(&queries)?) {
+        let mut stream = table.vector_search(q_vec)?.distance_type(DistanceType::Cosine).select(Select::columns(&["id"])).limit(1).execute().await?;
+        let mut hits = Vec::new();
+        while let Some(batch) = futures::TryStreamExt::try_next(&mut stream).await? {
+            let ids = string_column(&batch, "id")?;
+            hits.extend((0..batch.num_rows()).map(|i| SearchHit::new(ids.value(i), 1.0, SourceKind::Vector)));
+        }
+        ranked.insert(query.clone(), hits);
+    }
+    problems.extend(canary::check_queries(|q| Ok(ranked.remove(q).unwrap_or_default()))?);
+    Ok(Some(problems))
+}
+
+/// Up to `n` non-null vectors of `table`.
+async fn sample_vectors(conn: &Connection, table: &str, n: usize) -> Result<Vec<Vec<f32>>> {
+    let tbl = conn.open_table(table).execute().await?;
+    let mut stream = tbl.query().only_if("vector IS NOT NULL").select(Select::columns(&["vector"])).limit(n).execute().await?;
+    let mut vectors = Vec::new();
+    while let Some(batch) = futures::TryStreamExt::try_next(&mut stream).await? {
+        let Some(fsl) = batch.column_by_name("vector").and_then(|c| c.as_any().downcast_ref::<FixedSizeListArray>()) else { continue };
+        for i in 0..batch.num_rows() {
+            if let Some(v) = vector_value(fsl, i, "<sample>")? { vectors.push(v); }
+        }
+    }
+    Ok(vectors)
+}
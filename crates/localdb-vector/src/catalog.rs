@@ -0,0 +1,147 @@
+//! Per-file catalog with full-file hashes for bit-rot detection.
+//!
+//! Ingest records one row per source file (`doc_path`, `doc_id`, blake3 hash of
+//! the raw bytes, size). `scrub` re-hashes the files under the recorded data
+//! roots and reports, per document, whether the bytes still match — SD cards
+//! and old drives fail silently, and a changed hash is the only symptom.
+//!
+//! Each row also carries the document's extractive summary, looked up by
+//! `summaries` when results are displayed, and its inherited folder metadata.
+
+use anyhow::Result;
+use arrow_array::{Int64Array, RecordBatch, RecordBatchIterator, StringArray, TimestampMillisecondArray};
+use chrono::Utc;
+use lancedb::Connection;
+use lancedb::query::ExecutableQuery;
+use lancedb::table::NewColumnTransform;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use localdb_core::data_processor::file_hash;
+use localdb_core::folder_meta::{decode_meta, encode_meta};
+use localdb_core::retention::RetentionPolicy;
+use localdb_core::roots::RootMap;
+use localdb_core::types::FileRecord;
+
+use crate::arrow_utils::{column, optional_column, string_column};
+use crate::schema::build_catalog_schema;
+use crate::table::{ensure_table, sql_list, DELETE_BATCH};
+
+/// Default catalog table name.
+pub const CATALOG_TABLE: &str = "catalog";
+
+/// Upsert `records` keyed by `doc_path`.
+pub async fn put_records(conn: &Connection, table: &str, records: &[FileRecord]) -> Result<()> {
+    if records.is_empty() { return Ok(()); }
+    ensure_table(conn, table, build_catalog_schema()).await?;
+    let t = conn.open_table(table).execute().await?;
+    // Catalogs from before summaries/folder metadata: add the columns so the upsert schema matches.
+    let schema = t.schema().await?;
+    let missing: Vec<(String, String)> = ["summary", "meta"].iter()
+        .filter(|c| schema.field_with_name(c).is_err())
+        .map(|c| (c.to_string(), "CAST(NULL AS STRING)".to_string())).collect();
+    if !missing.is_empty() { t.add_columns(NewColumnTransform::SqlExpressions(missing), None).await?; }
+    let now = Utc::now().timestamp_millis();
+    let batch = RecordBatch::try_new(
+        build_catalog_schema(),
+        vec![
+            Arc::new(StringArray::from(records.iter().map(|r| r.doc_path.clone()).collect::<Vec<_>>())),
+            Arc::new(StringArray::from(records.iter().map(|r| r.doc_id.clone()).collect::<Vec<_>>())),
+            Arc::new(StringArray::from(records.iter().map(|r| r.category.clone()).collect::<Vec<_>>())),
+            Arc::new(StringArray::from(records.iter().map(|r| r.file_hash.clone()).collect::<Vec<_>>())),
+            Arc::new(Int64Array::from(records.iter().map(|r| r.size as i64).collect::<Vec<_>>())),
+            Arc::new(TimestampMillisecondArray::from(records.iter().map(|r| r.modified_at).collect::<Vec<_>>())),
+            Arc::new(TimestampMillisecondArray::from(vec![now; records.len()])),
+            Arc::new(StringArray::from(records.iter().map(|r| Some(r.summary.clone())).collect::<Vec<_>>())),
+            Arc::new(StringArray::from(records.iter().map(|r| Some(encode_meta(&r.meta))).collect::<Vec<_>>())),
+        ],
+    )?;
+    let reader = Box::new(RecordBatchIterator::new(vec![Ok(batch)].into_iter(), build_catalog_schema()));
+    let mut mi = t.merge_insert(&["doc_path"]);
+    mi.when_matched_update_all(None).when_not_matched_insert_all();
+    let _ = mi.execute(reader).await?;
+    Ok(())
+}
+
+/// All catalog rows (empty if the table does not exist yet).
+pub async fn records(conn: &Connection, table: &str) -> Result<Vec<FileRecord>> {
+    let names = conn.table_names().execute().await?;
+    if !names.contains(&table.to_string()) { return Ok(Vec::new()); }
+    let t = conn.open_table(table).execute().await?;
+    let mut stream = t.query().execute().await?;
+    let mut out = Vec::new();
+    while let Some(batch) = futures::TryStreamExt::try_next(&mut stream).await? {
+        let paths = string_column(&batch, "doc_path")?;
+        let doc_ids = string_column(&batch, "doc_id")?;
+        let categories = string_column(&batch, "category")?;
+        let hashes = string_column(&batch, "file_hash")?;
+        let sizes = column::<Int64Array>(&batch, "size", "Int64")?;
+        let modified = column::<TimestampMillisecondArray>(&batch, "modified_at", "Timestamp(ms)")?;
+        // Catalogs written before summaries existed have no `summary` column.
+        let summaries = optional_column::<StringArray>(&batch, "summary", "Utf8")?;
+        let metas = optional_column::<StringArray>(&batch, "meta", "Utf8")?;
+        for i in 0..batch.num_rows() {
+            out.push(FileRecord {
+                doc_id: doc_ids.value(i).to_string(), doc_path: paths.value(i).to_string(), category: categories.value(i).to_string(),
+                file_hash: hashes.value(i).to_string(), size: sizes.value(i) as u64, modified_at: modified.value(i),
+                summary: summaries.filter(|s| !s.is_null(i)).map(|s| s.value(i).to_string()).unwrap_or_default(),
+                meta: metas.filter(|m| !m.is_null(i)).map(|m| decode_meta(m.value(i))).unwrap_or_default(),
+            });
+        }
+    }
+    Ok(out)
+}
+
+/// Stored summaries keyed by `doc_id` (documents without one are omitted).
+pub async fn summaries(conn: &Connection, table: &str) -> Result<HashMap<String, String>> {
+    Ok(records(conn, table).await?.into_iter().filter(|r| !r.summary.is_empty()).map(|r| (r.doc_id, r.summary)).collect())
+}
+
+/// Outcome of re-hashing one cataloged file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScrubStatus {
+    /// Bytes match the hash recorded at ingest.
+    Ok,
+    /// The file changed on disk: bit-rot, tampering, or an edit since ingest.
+    Mismatch { actual: String },
+    /// The file is gone from its (reachable) root.
+    Missing,
+    /// The file's root is unplugged (`offline media: <label>`); not checked.
+    Offline(String),
+    /// The file exists but could not be read.
+    Unreadable(String),
+}
+
+/// Re-hash `record`'s file under `roots`.
+pub fn scrub_record(record: &FileRecord, roots: &RootMap) -> ScrubStatus {
+    if let Some(label) = roots.offline_label(&record.doc_path) { return ScrubStatus::Offline(label); }
+    let path = roots.resolve(&record.doc_path);
+    if !path.exists() { return ScrubStatus::Missing; }
+    match file_hash(&path) {
+        Ok(actual) if actual == record.file_hash => ScrubStatus::Ok,
+        Ok(actual) => ScrubStatus::Mismatch { actual },
+        Err(e) => ScrubStatus::Unreadable(e.to_string()),
+    }
+}
+
+/// Remove the catalog rows for `doc_paths`.
+pub async fn delete_records(conn: &Connection, table: &str, doc_paths: &[String]) -> Result<()> {
+    let names = conn.table_names().execute().await?;
+    if !names.contains(&table.to_string()) || doc_paths.is_empty() { return Ok(()); }
+    let t = conn.open_table(table).execute().await?;
+    for chunk in doc_paths.chunks(DELETE_BATCH) { t.delete(&format!("doc_path IN ({})", sql_list(chunk))).await?; }
+    Ok(())
+}
+
+/// Records whose retention period has expired as of `now`.
+pub fn expired(records: &[FileRecord], policy: &RetentionPolicy, now: SystemTime) -> Vec<FileRecord> {
+    records.iter()
+        .filter(|r| policy.is_expired(&r.category, UNIX_EPOCH + Duration::from_millis(r.modified_at.max(0) as u64), now))
+        .cloned().collect()
+}
+
+/// Re-hash every cataloged file; returns each record with its status.
+pub async fn scrub(conn: &Connection, table: &str, roots: &RootMap) -> Result<Vec<(FileRecord, ScrubStatus)>> {
+    Ok(records(conn, table).await?.into_iter().map(|r| { let s = scrub_record(&r, roots); (r, s) }).collect())
+}
@@ -1,3 +1,23 @@
+//! Resumable embedding backfill into side `embeddings` with write-through cache.
+//!
+//! Selection is status-driven: `embedding_status != 'ready'`. For each batch we
+//! mark rows `in_progress`, consult the cache, embed misses, write to
+//! `embeddings` + cache, and finally mark rows `ready` (or `error`). Every step
+//! is safe to repeat, so a run killed anywhere resumes on the next call: rows
+//! left `in_progress` are selected again and their `embeddings` rows are
+//! upserted by `(id, embedder_id)` rather than appended twice. The step
+//! boundaries are `localdb_core::fault` points (`backfill.*`).
+//!
+//! The collection records the `embedder_id` that backfilled it. A provider
+//! with another id (another model, seed, or weights under the same name)
+//! first marks every `ready` row `stale`, so the whole collection is
+//! re-embedded rather than mixing vectors of two embedders; its id is
+//! recorded once a run leaves no row stale.
+//!
+//! Rows are embedded as the `Preprocessor` cleans them, as at ingest, and
+//! the cache is keyed by the hash of that cleaned text, so vectors of text
+//! cleaned by other steps are never reused.
+
 use anyhow::{Result, anyhow};
 use lancedb::Connection;
 use arrow_array::{RecordBatch, RecordBatchIterator, StringArray, FixedSizeListArray};
@@ -7,14 +27,15 @@ use std::collections::HashSet;
 use std::sync::Arc;
 use chrono::Utc;
 
-use crate::embed_provider::EmbedProvider;
-use crate::cache::{get_many as cache_get_many, put_many as cache_put_many, CacheEntry};
-use crate::schema::{build_embeddings_schema, EMBEDDING_DIM};
+use localdb_core::fault;
+use localdb_core::preprocess::Preprocessor;
+use localdb_core::progress;
+use localdb_core::types::DocumentChunk;
 
-fn hash_content(s: &str) -> String {
-    let h = blake3::hash(s.as_bytes());
-    h.to_hex().to_string()
-}
+use crate::arrow_utils::{optional_column, string_column};
+use crate::embed_provider::EmbedProvider;
+use crate::cache::{get_many as cache_get_many, hash_content, put_many as cache_put_many, CacheEntry};
+use crate::schema::build_embeddings_schema;
 
 pub async fn backfill_embeddings(
     conn: &Connection,
@@ -22,59 +43,77 @@ pub async fn backfill_embeddings(
     emb_table: &str,
     cache_table: &str,
     provider: &dyn EmbedProvider,
+    preprocessor: &Preprocessor,
     batch_size: usize,
     limit_rows: Option<usize>,
 ) -> Result<usize> {
     let t = conn.open_table(docs_table).execute().await?;
+    if let Some(previous) = super::table::collection_embedder(conn, docs_table).await?.filter(|p| p != provider.embedder_id()) {
+        println!("🔁 Embedder changed ({} → {}); re-embedding every chunk", previous, provider.embedder_id());
+        t.update().only_if("embedding_status = 'ready'").column("embedding_status", "'stale'").execute().await?;
+    }
     let mut processed = 0usize;
-    let mut to_process: Vec<(String, String, String)> = Vec::new();
-    // Scan documents and collect (id, content, content_hash, embedding_status)
+    // Rows not ready, and with `strip_boilerplate` the rest of their documents
+    // too, as repeated lines are counted per document.
+    let whole_docs = !preprocessor.is_identity();
+    let mut rows: Vec<(DocumentChunk, bool)> = Vec::new();
+    let mut taken = 0usize;
     let mut stream = t.query().execute().await?;
-    while let Some(batch) = futures::TryStreamExt::try_next(&mut stream).await? {
-        let id_col = batch.column_by_name("id").and_then(|c| c.as_any().downcast_ref::<StringArray>()).ok_or_else(|| anyhow!("missing id"))?;
-        let content_col = batch.column_by_name("content").and_then(|c| c.as_any().downcast_ref::<StringArray>()).ok_or_else(|| anyhow!("missing content"))?;
-        let status_col = batch.column_by_name("embedding_status").and_then(|c| c.as_any().downcast_ref::<StringArray>());
+    'scan: while let Some(batch) = futures::TryStreamExt::try_next(&mut stream).await? {
+        let id_col = string_column(&batch, "id")?;
+        let doc_col = string_column(&batch, "doc_id")?;
+        let content_col = string_column(&batch, "content")?;
+        let status_col = optional_column::<StringArray>(&batch, "embedding_status", "Utf8")?;
         for i in 0..batch.num_rows() {
-            let id = id_col.value(i).to_string();
-            let content = content_col.value(i).to_string();
-            let chash = hash_content(&content);
             // Select rows that are not ready
             let take = match status_col { Some(sc) => sc.value(i) != "ready", None => true };
-            if take { to_process.push((id, content, chash)); }
-            if let Some(lim) = limit_rows { if to_process.len() >= lim { break; } }
+            if !take && !whole_docs { continue; }
+            let chunk = DocumentChunk { id: id_col.value(i).to_string(), doc_id: doc_col.value(i).to_string(), content: content_col.value(i).to_string(), ..Default::default() };
+            rows.push((chunk, take));
+            taken += take as usize;
+            if let Some(lim) = limit_rows { if taken >= lim { break 'scan; } }
         }
-        if let Some(lim) = limit_rows { if to_process.len() >= lim { break; } }
     }
-    if to_process.is_empty() { return Ok(0); }
+    let chunks: Vec<DocumentChunk> = rows.iter().map(|(c, _)| c.clone()).collect();
+    let texts = preprocessor.embedding_texts(&chunks);
+    // (id, text to embed, content hash, cache key)
+    let to_process: Vec<(String, String, String, String)> = rows.into_iter().zip(texts).filter(|((_, take), _)| *take).map(|((c, _), text)| {
+        let key = hash_content(&text);
+        (c.id, text, hash_content(&c.content), key)
+    }).collect();
+    if to_process.is_empty() { return record_embedder(conn, docs_table, provider).await.map(|_| 0); }
+    progress::report("embed", 0, Some(to_process.len() as u64));
 
-    // Ensure embeddings table exists
-    super::table::ensure_embeddings_table(conn, emb_table).await?;
-    super::table::ensure_cache_table(conn, cache_table).await?;
+    // Validate the provider against the collection, then ensure side tables exist
+    let dim = super::table::ensure_collection_dim(conn, docs_table, provider.dim()).await?;
+    super::table::ensure_embeddings_table(conn, emb_table, dim).await?;
+    super::table::ensure_cache_table(conn, cache_table, dim).await?;
     let emb = conn.open_table(emb_table).execute().await?;
 
     // Process in batches
     for chunk in to_process.chunks(batch_size) {
         // Mark in_progress for this chunk
-        let ids_list = chunk.iter().map(|(id,_,_)| format!("'{}'", id.replace("'","''"))).collect::<Vec<_>>().join(",");
+        let ids_list = chunk.iter().map(|(id,_,_,_)| format!("'{}'", id.replace("'","''"))).collect::<Vec<_>>().join(",");
         let filter = format!("id IN ({})", ids_list);
         let _ = t.update()
             .only_if(filter.clone())
             .column("embedding_status", "'in_progress'")
             .execute().await?;
+        fault::check("backfill.in_progress")?;
         // Cache lookup
-        let hashes: Vec<String> = chunk.iter().map(|(_,_,h)| h.clone()).collect();
+        let hashes: Vec<String> = chunk.iter().map(|(_,_,_,k)| k.clone()).collect();
         let cache_map = cache_get_many(conn, cache_table, provider.embedder_id(), &hashes).await?;
         // Build embed inputs for misses
         let mut texts = Vec::new();
         let mut miss_indices = Vec::new();
-        for (idx, (_id, content, h)) in chunk.iter().enumerate() {
-            if !cache_map.contains_key(h) { texts.push(content.clone()); miss_indices.push(idx); }
+        for (idx, (_id, text, _h, k)) in chunk.iter().enumerate() {
+            if !cache_map.contains_key(k) { texts.push(text.clone()); miss_indices.push(idx); }
         }
         let mut new_cache_entries = Vec::new();
         let mut vectors: Vec<Vec<f32>> = vec![Vec::new(); chunk.len()];
         // Hits
-        for (i, (_id, _content, h)) in chunk.iter().enumerate() {
-            if let Some(v) = cache_map.get(h) { vectors[i] = v.clone(); }
+        for (i, (_id, _text, _h, k)) in chunk.iter().enumerate() {
+            if let Some(v) = cache_map.get(k) { vectors[i] = v.clone(); }
         }
         // Misses
         if !texts.is_empty() {
@@ -83,9 +122,9 @@ pub async fn backfill_embeddings(
                     if embs.len() != texts.len() { return Err(anyhow!("embedder returned wrong count")); }
                     for (j, &i) in miss_indices.iter().enumerate() {
                         let v = &embs[j];
-                        if v.len() != EMBEDDING_DIM as usize { return Err(anyhow!("dim mismatch: got {} expected {}", v.len(), EMBEDDING_DIM)); }
+                        if v.len() != dim as usize { return Err(anyhow!("dim mismatch: got {} expected {}", v.len(), dim)); }
                         vectors[i] = v.clone();
-                        new_cache_entries.push(CacheEntry { content_hash: chunk[i].2.clone(), embedder_id: provider.embedder_id().to_string(), vector: v.clone() });
+                        new_cache_entries.push(CacheEntry { content_hash: chunk[i].3.clone(), embedder_id: provider.embedder_id().to_string(), vector: v.clone() });
                     }
                 }
                 Err(e) => {
@@ -104,9 +143,10 @@ pub async fn backfill_embeddings(
         }
         // Write new cache entries
         if !new_cache_entries.is_empty() { cache_put_many(conn, cache_table, &new_cache_entries).await?; }
+        fault::check("backfill.cache_written")?;
 
         // Write to embeddings table
-        let schema = build_embeddings_schema();
+        let schema = build_embeddings_schema(dim);
         let mut ids = Vec::new();
         let mut eids = Vec::new();
         let mut hashes = Vec::new();
@@ -127,11 +167,14 @@ pub async fn backfill_embeddings(
                 Arc::new(StringArray::from(eids)),
                 Arc::new(StringArray::from(hashes)),
                 Arc::new(arrow_array::TimestampMillisecondArray::from(times)),
-                Arc::new(FixedSizeListArray::from_iter_primitive::<arrow_array::types::Float32Type, _, _>(vecs.into_iter(), EMBEDDING_DIM)),
+                Arc::new(FixedSizeListArray::from_iter_primitive::<arrow_array::types::Float32Type, _, _>(vecs.into_iter(), dim)),
             ],
         )?;
         let reader = Box::new(RecordBatchIterator::new(vec![Ok(batch)].into_iter(), schema));
-        emb.add(reader).execute().await?;
+        let mut mi = emb.merge_insert(&["id", "embedder_id"]);
+        mi.when_matched_update_all(None).when_not_matched_insert_all();
+        mi.execute(reader).await?;
+        fault::check("backfill.embeddings_written")?;
         // Mark ready for all processed ids
         let now = Utc::now().timestamp_millis();
         let _ = t.update().only_if(filter)
@@ -141,13 +184,20 @@ pub async fn backfill_embeddings(
             .column("embedded_at", format!("CAST({} AS TIMESTAMP)", now))
             .column("content_hash", "content_hash")
             .execute().await?;
+        fault::check("backfill.ready")?;
         processed += chunk.len();
+        progress::report("embed", processed as u64, Some(to_process.len() as u64));
     }
-
+    record_embedder(conn, docs_table, provider).await?;
     Ok(processed)
 }
-//! Resumable embedding backfill into side `embeddings` with write-through cache.
-//!
-//! Selection is status-driven: `embedding_status != 'ready'`. For each batch we
-//! mark rows `in_progress`, consult the cache, embed misses, write to
-//! `embeddings` + cache, and finally mark rows `ready` (or `error`).
+
+/// Record `provider` as the collection's embedder unless rows of the previous
+/// one are still `stale` (a `limit_rows` run): until then the collection
+/// mixes both, and the next run goes on re-embedding (cache hits for the rows
+/// already done).
+async fn record_embedder(conn: &Connection, docs_table: &str, provider: &dyn EmbedProvider) -> Result<()> {
+    let t = conn.open_table(docs_table).execute().await?;
+    if t.count_rows(Some("embedding_status = 'stale'".to_string())).await? > 0 { return Ok(()); }
+    super::table::set_collection_embedder(conn, docs_table, provider.embedder_id()).await
+}
@@ -1,6 +1,23 @@
-use anyhow::Result;
+//! Local embedding provider using the crate `localdb-embed`.
+//!
+//! Respects `APP_USE_FAKE_EMBEDDINGS=1` to switch to the FakeEmbedder for fast
+//! and deterministic outputs in tests and development. A non-default
+//! `APP_SEED` gives the fake provider its own `embedder_id`, so cached vectors
+//! from another seed are never reused; so does a model other than the default
+//! (`localdb_embed::registry`). A real model's id ends in a fingerprint of
+//! its files (`localdb_embed::fingerprint`), so weights swapped under the same
+//! name are a different embedder and the backfill re-embeds. So are a
+//! `max_len` other than the model's registered one (`:l<n>`) and sliding
+//! windows (`:w<overlap>`), which change the vectors of long texts, and a
+//! pooling other than the registered one (`:p<pooling>`). `recorded_embedder`
+//! loads the embedder an id describes, to query an index built with another
+//! model or settings than the configured ones.
+
+use anyhow::{bail, Context, Result};
 use localdb_core::traits::Embedder as CoreEmbedder;
-use localdb_embed::get_default_embedder;
+use localdb_core::seed::DEFAULT_SEED;
+use localdb_embed::registry::{Architecture, DEFAULT_MODEL};
+use localdb_embed::{fake_embedding_seed, get_default_embedder, load_embedder, model_fingerprint, model_spec, resolve_model_dir, sliding_window, ModelRegistry, Pooling};
 
 use super::EmbedProvider;
 
@@ -13,18 +30,64 @@ impl LocalProvider {
     /// Create a new local provider, loading the default embedder.
     pub fn new() -> Result<Self> {
         let inner = get_default_embedder()?;
-        let id = format!("local:{}:d{}", std::any::type_name::<Self>(), inner.dim());
+        let id = embedder_id(inner.dim())?;
         Ok(Self { inner, id })
     }
 }
 
+/// The `embedder_id` of the configured model at width `dim`, without loading
+/// it (the model files are fingerprinted, not read into memory).
+pub fn embedder_id(dim: usize) -> Result<String> {
+    let mut id = format!("local:{}:d{}", std::any::type_name::<LocalProvider>(), dim);
+    let model = model_spec()?;
+    if model.name != DEFAULT_MODEL { id.push_str(&format!(":m{}", model.name)); }
+    match fake_embedding_seed() {
+        Some(seed) => if seed != DEFAULT_SEED { id.push_str(&format!(":s{}", seed)); },
+        None => {
+            let registered = ModelRegistry::current().get(model.name)?.clone();
+            if model.max_len != registered.max_len { id.push_str(&format!(":l{}", model.max_len)); }
+            if model.pooling != registered.pooling { id.push_str(&format!(":p{}", model.pooling.name())); }
+            // Passages embedded with their own prefix, not the query's.
+            if !model.passage_prefix.is_empty() { id.push_str(":xpassage"); }
+            if let Some(overlap) = sliding_window().filter(|_| model.architecture == Architecture::XlmRoberta) { id.push_str(&format!(":w{}", overlap)); }
+            id.push_str(&format!(":h{}", model_fingerprint(&resolve_model_dir(&model)?)?));
+        }
+    }
+    Ok(id)
+}
+
+/// The embedder whose `embedder_id` is `id`: its model, width, `max_len`,
+/// pooling and sliding window, whatever is configured now. Fails when the
+/// model's files no longer match the id's fingerprint.
+pub fn recorded_embedder(id: &str) -> Result<Box<dyn CoreEmbedder>> {
+    let prefix = format!("local:{}:", std::any::type_name::<LocalProvider>());
+    let Some(parts) = id.strip_prefix(&prefix) else { bail!("'{}' is not a local embedder id", id) };
+    let (mut dim, mut name, mut max_len, mut pooling, mut overlap, mut hash) = (None, DEFAULT_MODEL, None, None, None, None);
+    for part in parts.split(':') {
+        let (tag, value) = part.split_at(part.char_indices().nth(1).map_or(part.len(), |(i, _)| i));
+        match tag {
+            "d" => dim = Some(value.parse::<usize>().with_context(|| format!("bad width in embedder id '{}'", id))?),
+            "m" => name = value,
+            "l" => max_len = Some(value.parse::<usize>().with_context(|| format!("bad max_len in embedder id '{}'", id))?),
+            "p" => pooling = Some(Pooling::parse(value)?),
+            "w" => overlap = Some(value.parse::<usize>().with_context(|| format!("bad window overlap in embedder id '{}'", id))?),
+            "h" => hash = Some(value),
+            _ => {}
+        }
+    }
+    let Some(dim) = dim else { bail!("embedder id '{}' has no width", id) };
+    let mut spec = ModelRegistry::current().get(name)?.clone();
+    if let Some(max_len) = max_len { spec.max_len = max_len; }
+    if let Some(pooling) = pooling { spec.pooling = pooling; }
+    if let (Some(hash), None) = (hash, fake_embedding_seed()) {
+        if model_fingerprint(&resolve_model_dir(&spec)?)? != hash { bail!("the files of {} have changed since '{}' embedded that index", spec.name, id); }
+    }
+    load_embedder(&spec, overlap, dim)
+}
+
 impl EmbedProvider for LocalProvider {
     fn embedder_id(&self) -> &str { &self.id }
     fn dim(&self) -> usize { self.inner.dim() }
     fn max_len(&self) -> usize { self.inner.max_len() }
     fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> { self.inner.embed_batch(texts) }
-//! Local embedding provider using the crate `localdb-embed`.
-//!
-//! Respects `APP_USE_FAKE_EMBEDDINGS=1` to switch to the FakeEmbedder for fast
-//! and deterministic outputs in tests and development.
 }
@@ -0,0 +1,67 @@
+//! Orphan detection for `localdb-cli gc`.
+//!
+//! Ingest, retention, and interrupted backfills can leave the stores out of
+//! step: `embeddings` rows for chunks no longer in `documents`, `documents`
+//! rows for files dropped from the catalog, and cache entries written by an
+//! embedder that no longer produces any serving vectors. These helpers list
+//! and remove them; the CLI cross-checks the text index against `documents`.
+
+use anyhow::Result;
+use lancedb::Connection;
+use lancedb::query::{ExecutableQuery, QueryBase, Select};
+use std::collections::{BTreeSet, HashSet};
+
+use localdb_core::types::file_candidates;
+
+use crate::arrow_utils::string_column;
+use crate::table::{sql_list, DELETE_BATCH};
+
+/// The stored `doc_paths` whose file is not in `cataloged`, each once and
+/// sorted; a fragment (`<file>#…`: ZIM article, archive member, record)
+/// belongs to its file.
+pub fn uncataloged(doc_paths: &[String], cataloged: &HashSet<String>) -> Vec<String> {
+    doc_paths.iter().filter(|p| !file_candidates(p).any(|f| cataloged.contains(f))).cloned().collect::<BTreeSet<_>>().into_iter().collect()
+}
+
+/// Every value of the string `column` of `table` (empty if the table is missing).
+pub async fn column_values(conn: &Connection, table: &str, column: &str) -> Result<Vec<String>> {
+    let names = conn.table_names().execute().await?;
+    if !names.contains(&table.to_string()) { return Ok(Vec::new()); }
+    let t = conn.open_table(table).execute().await?;
+    let mut stream = t.query().select(Select::columns(&[column])).execute().await?;
+    let mut out = Vec::new();
+    while let Some(batch) = futures::TryStreamExt::try_next(&mut stream).await? {
+        let col = string_column(&batch, column)?;
+        out.extend((0..batch.num_rows()).map(|i| col.value(i).to_string()));
+    }
+    Ok(out)
+}
+
+/// `emb_table` ids whose chunk is no longer in `docs_table`.
+pub async fn orphan_embeddings(conn: &Connection, docs_table: &str, emb_table: &str) -> Result<Vec<String>> {
+    let live: HashSet<String> = column_values(conn, docs_table, "id").await?.into_iter().collect();
+    Ok(column_values(conn, emb_table, "id").await?.into_iter().filter(|id| !live.contains(id)).collect())
+}
+
+/// Delete rows of `table` by `id`.
+pub async fn delete_ids(conn: &Connection, table: &str, ids: &[String]) -> Result<()> {
+    if ids.is_empty() || !conn.table_names().execute().await?.contains(&table.to_string()) { return Ok(()); }
+    let t = conn.open_table(table).execute().await?;
+    for chunk in ids.chunks(DELETE_BATCH) { t.delete(&format!("id IN ({})", sql_list(chunk))).await?; }
+    Ok(())
+}
+
+/// Embedder ids with cache entries in `cache_table` but not in `keep`.
+pub async fn stale_cache_embedders(conn: &Connection, cache_table: &str, keep: &BTreeSet<String>) -> Result<BTreeSet<String>> {
+    Ok(column_values(conn, cache_table, "embedder_id").await?.into_iter().filter(|e| !keep.contains(e)).collect())
+}
+
+/// Drop every cache entry written by `embedders`; returns the rows removed.
+pub async fn purge_cache(conn: &Connection, cache_table: &str, embedders: &BTreeSet<String>) -> Result<usize> {
+    if embedders.is_empty() || !conn.table_names().execute().await?.contains(&cache_table.to_string()) { return Ok(0); }
+    let t = conn.open_table(cache_table).execute().await?;
+    let predicate = format!("embedder_id IN ({})", sql_list(&embedders.iter().cloned().collect::<Vec<_>>()));
+    let n = t.count_rows(Some(predicate.clone())).await?;
+    t.delete(&predicate).await?;
+    Ok(n)
+}
@@ -1,14 +1,32 @@
-use anyhow::Result;
+//! Training/build/flip utilities for IVF_PQ indices in Lance.
+//!
+//! Typical flow:
+//! 1) Copy vectors from `embeddings` to `documents.vector` for the target `embedder_id`
+//! 2) Compute params based on ready rows; build IVF_PQ under a unique name
+//! 3) Validate on a tiny sample; flip the active index pointer in `meta`
+//!
+//! `index_info` reports what a query will actually hit: the vector indices on
+//! `vector`, the build parameters recorded in `meta` (`index_build:<name>`),
+//! and how many rows are outside the index and thus searched by brute force.
+//! `vector_coverage` reports how far `documents.vector` lags `embeddings`:
+//! rows with no serving vector are invisible to vector search, and a row
+//! whose backfilled vector never got synced misses it silently.
+
+use anyhow::{Result, anyhow};
 use lancedb::{Connection, index::{Index, vector::IvfPqIndexBuilder}};
 use lancedb::DistanceType;
 use lancedb::query::{ExecutableQuery, QueryBase, Select};
 use arrow_array::Array;
-use arrow_array::{RecordBatch, RecordBatchIterator, StringArray, FixedSizeListArray};
-use arrow_array::cast::AsArray;
+use arrow_array::{RecordBatch, RecordBatchIterator, StringArray, FixedSizeListArray, TimestampMillisecondArray};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
-use crate::schema::{EMBEDDING_DIM};
-use crate::table::{set_meta, ensure_meta_table};
+use localdb_core::{fault, progress};
+
+use crate::arrow_utils::{column, string_column, vector_column, vector_value};
+use crate::schema::{build_serving_vector_schema, vector_dim};
+use crate::table::{check_collection_dim, get_meta, set_meta, ensure_meta_table, META_TABLE};
 
 pub struct IvfPqParams {
     pub nlist: usize,
@@ -44,71 +62,97 @@ pub fn compute_ivfpq_params(total_ready: usize, dim: usize) -> IvfPqParams {
     IvfPqParams { nlist, m, nbits: 8 }
 }
 
-/// Copy vectors from embeddings (for a given embedder_id) into documents.vector via merge_insert
+/// Rows per `merge_insert` when syncing serving vectors.
+pub const SYNC_BATCH_ROWS: usize = 50_000;
+/// Attempts per merge before the sync gives up. Earlier merges stay applied
+/// after a failure, but a rerun merges every row again (merges are
+/// idempotent).
+pub const SYNC_ATTEMPTS: u32 = 3;
+
+/// Copy vectors from embeddings (for a given embedder_id) into documents.vector
+/// via merge_insert, `SYNC_BATCH_ROWS` at a time.
 pub async fn sync_serving_vectors_from_embeddings(
     conn: &Connection,
     docs_table: &str,
     emb_table: &str,
     embedder_id: &str,
 ) -> Result<usize> {
+    sync_serving_vectors_in_batches(conn, docs_table, emb_table, embedder_id, SYNC_BATCH_ROWS).await
+}
+
+/// `sync_serving_vectors_from_embeddings` merging at most `batch_rows` rows
+/// at once: only one merge's rows are held in memory, progress is reported
+/// per merge, and a failed merge is retried (`SYNC_ATTEMPTS`) without
+/// redoing the ones before it. Returns the rows updated or inserted.
+pub async fn sync_serving_vectors_in_batches(
+    conn: &Connection,
+    docs_table: &str,
+    emb_table: &str,
+    embedder_id: &str,
+    batch_rows: usize,
+) -> Result<usize> {
+    progress::report("sync vectors", 0, None);
     let docs = conn.open_table(docs_table).execute().await?;
     let emb = conn.open_table(emb_table).execute().await?;
-    // Build a RecordBatchReader with (id, vector) for this embedder_id
-    let mut src_batches: Vec<Result<RecordBatch, arrow_schema::ArrowError>> = Vec::new();
+    let dim = vector_dim(&emb.schema().await?).ok_or_else(|| anyhow!("'{}' has no vector column", emb_table))?;
+    check_collection_dim(conn, docs_table, dim as usize).await?;
+    let schema = build_serving_vector_schema(dim);
+    let total = emb.count_rows(Some(format!("embedder_id = '{}'", embedder_id.replace('\'', "''")))).await? as u64;
+    let mut ids: Vec<String> = Vec::new();
+    let mut vectors: Vec<Option<Vec<Option<f32>>>> = Vec::new();
+    let mut synced = 0usize;
     let mut stream = emb.query().select(Select::columns(&["id","embedder_id","vector"])).execute().await?;
-    while let Some(batch) = futures::TryStreamExt::try_next(&mut stream).await? {
-        let eid = batch.column_by_name("embedder_id").unwrap().as_any().downcast_ref::<StringArray>().unwrap();
-        let id = batch.column_by_name("id").unwrap().as_any().downcast_ref::<StringArray>().unwrap();
-        let vecs = batch.column_by_name("vector").unwrap().as_any().downcast_ref::<FixedSizeListArray>().unwrap();
-        let mut ids = Vec::new();
-        let mut vectors: Vec<Option<Vec<Option<f32>>>> = Vec::new();
-        for i in 0..batch.num_rows() {
-            if eid.value(i) != embedder_id { continue; }
-            ids.push(id.value(i).to_string());
-            let arr = vecs.value(i);
-            let vals = arr.as_primitive::<arrow_array::types::Float32Type>();
-            let v = vals.values().iter().copied().map(Some).collect::<Vec<_>>();
-            vectors.push(Some(v));
+    loop {
+        let batch = futures::TryStreamExt::try_next(&mut stream).await?;
+        if let Some(batch) = &batch {
+            let eid = string_column(batch, "embedder_id")?;
+            let id = string_column(batch, "id")?;
+            let vecs = vector_column(batch, "vector")?;
+            for i in 0..batch.num_rows() {
+                if eid.value(i) != embedder_id { continue; }
+                let Some(v) = vector_value(vecs, i, id.value(i))? else { continue };
+                ids.push(id.value(i).to_string());
+                vectors.push(Some(v.into_iter().map(Some).collect()));
+            }
         }
-        if !ids.is_empty() {
-            let schema = Arc::new(arrow_schema::Schema::new(vec![
-                arrow_schema::Field::new("id", arrow_schema::DataType::Utf8, false),
-                arrow_schema::Field::new(
-                    "vector",
-                    arrow_schema::DataType::FixedSizeList(
-                        Arc::new(arrow_schema::Field::new("item", arrow_schema::DataType::Float32, true)),
-                        EMBEDDING_DIM,
-                    ),
-                    true,
-                ),
-            ]));
+        let done = batch.is_none();
+        // Merge full batches as they fill, and what is left at the end.
+        while ids.len() >= batch_rows.max(1) || (done && !ids.is_empty()) {
+            let n = ids.len().min(batch_rows.max(1));
             let rb = RecordBatch::try_new(
-                schema,
+                schema.clone(),
                 vec![
-                    Arc::new(StringArray::from(ids)),
-                    Arc::new(FixedSizeListArray::from_iter_primitive::<arrow_array::types::Float32Type, _, _>(vectors.into_iter(), EMBEDDING_DIM)),
+                    Arc::new(StringArray::from(ids.drain(..n).collect::<Vec<_>>())),
+                    Arc::new(FixedSizeListArray::from_iter_primitive::<arrow_array::types::Float32Type, _, _>(vectors.drain(..n), dim)),
                 ],
             )?;
-            src_batches.push(Ok(rb));
+            synced += merge_serving_vectors(&docs, rb).await?;
+            fault::check("sync.batch_merged")?;
+            progress::report("sync vectors", synced as u64, Some(total));
+        }
+        if done { break; }
+    }
+    Ok(synced)
+}
+
+/// Merge one batch of `(id, vector)` rows into `docs`, retrying failures.
+async fn merge_serving_vectors(docs: &lancedb::Table, rb: RecordBatch) -> Result<usize> {
+    let mut attempt = 1;
+    loop {
+        let reader = Box::new(RecordBatchIterator::new(vec![Ok(rb.clone())].into_iter(), rb.schema()));
+        // Update existing rows by id; insert all if not matched (shouldn’t happen)
+        let mut mi = docs.merge_insert(&["id"]);
+        mi.when_matched_update_all(None).when_not_matched_insert_all();
+        match mi.execute(reader).await {
+            Ok(res) => return Ok((res.num_inserted_rows + res.num_updated_rows) as usize),
+            Err(e) if attempt < SYNC_ATTEMPTS => {
+                eprintln!("⚠️  Merging {} serving vectors failed ({}); retrying", rb.num_rows(), e);
+                tokio::time::sleep(std::time::Duration::from_millis(500 * u64::from(attempt))).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(anyhow!("merging {} serving vectors failed after {} attempts: {}", rb.num_rows(), attempt, e)),
         }
     }
-    if src_batches.is_empty() { return Ok(0); }
-    // Merge insert: update existing rows by id; insert all if not matched (shouldn’t happen)
-    let reader = Box::new(RecordBatchIterator::new(src_batches.into_iter(), Arc::new(arrow_schema::Schema::new(vec![
-        arrow_schema::Field::new("id", arrow_schema::DataType::Utf8, false),
-        arrow_schema::Field::new(
-            "vector",
-            arrow_schema::DataType::FixedSizeList(
-                Arc::new(arrow_schema::Field::new("item", arrow_schema::DataType::Float32, true)),
-                EMBEDDING_DIM,
-            ),
-            true,
-        ),
-    ]))));
-    let mut mi = docs.merge_insert(&["id"]);
-    mi.when_matched_update_all(None).when_not_matched_insert_all();
-    let res = mi.execute(reader).await?;
-    Ok((res.num_inserted_rows + res.num_updated_rows) as usize)
 }
 
 pub async fn build_ivfpq_index(
@@ -117,6 +161,7 @@ pub async fn build_ivfpq_index(
     index_name: &str,
     params: &IvfPqParams,
 ) -> Result<()> {
+    progress::report("build ivf_pq", 0, None);
     let table = conn.open_table(docs_table).execute().await?;
     table
         .create_index(
@@ -131,11 +176,14 @@ pub async fn build_ivfpq_index(
         .name(index_name.to_string())
         .execute()
         .await?;
-    Ok(())
+    ensure_meta_table(conn, META_TABLE).await?;
+    let record = format!("nlist\t{}\nm\t{}\nnbits\t{}\nbuilt_at\t{}", params.nlist, params.m, params.nbits, chrono::Utc::now().timestamp_millis());
+    set_meta(conn, META_TABLE, &format!("index_build:{}", index_name), &record).await
 }
 
 /// Very simple validation: sample up to `sample` vectors and ensure top-k returns non-empty.
 pub async fn validate_index(conn: &Connection, docs_table: &str, k: usize, sample: usize) -> Result<bool> {
+    progress::report("validate", 0, None);
     let tbl = conn.open_table(docs_table).execute().await?;
     let mut stream = tbl.query().select(Select::columns(&["vector"])).limit(sample).execute().await?;
     let mut ok = 0usize;
@@ -143,10 +191,7 @@ pub async fn validate_index(conn: &Connection, docs_table: &str, k: usize, sampl
         if let Some(arr) = batch.column_by_name("vector") {
             if let Some(fsl) = arr.as_any().downcast_ref::<FixedSizeListArray>() {
                 for i in 0..batch.num_rows() {
-                    if !fsl.is_valid(i) { continue; }
-                    let inner = fsl.value(i);
-                    let vals = inner.as_primitive::<arrow_array::types::Float32Type>();
-                    let q = vals.values().to_vec();
+                    let Some(q) = vector_value(fsl, i, "<sample>")? else { continue };
                     let mut s = tbl.vector_search(q)?.distance_type(DistanceType::Cosine).limit(k).execute().await?;
                     if let Some(rb) = futures::TryStreamExt::try_next(&mut s).await? {
                         if rb.num_rows() > 0 { ok += 1; }
@@ -160,14 +205,145 @@ pub async fn validate_index(conn: &Connection, docs_table: &str, k: usize, sampl
 
 /// Flip active index pointer in meta table (keyed by docs table name)
 pub async fn flip_active_index(conn: &Connection, docs_table: &str, index_id: &str) -> Result<()> {
-    // Store in a global meta table named "meta"
-    ensure_meta_table(conn, "meta").await?;
+    // Store in the global meta table
+    ensure_meta_table(conn, META_TABLE).await?;
     let key = format!("active_index_id:{}", docs_table);
-    set_meta(conn, "meta", &key, index_id).await
+    set_meta(conn, META_TABLE, &key, index_id).await
+}
+
+/// State of the vector index of a collection (`localdb-cli stats --index`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IndexInfo {
+    /// `active_index_id:<table>` in `meta`, if an index was flipped active.
+    pub active_index: Option<String>,
+    /// Names of the indices Lance has on `vector` (it uses them whatever the pointer says).
+    pub vector_indices: Vec<String>,
+    /// The described index: the active one if it exists, else the first.
+    pub index_name: Option<String>,
+    /// Build parameters and time from `index_build:<name>` (indices built elsewhere lack them).
+    pub nlist: Option<usize>,
+    pub m: Option<usize>,
+    pub nbits: Option<usize>,
+    pub built_at_ms: Option<i64>,
+    /// Rows covered by the described index (trained/assigned at build time).
+    pub indexed_rows: usize,
+    /// Rows appended since: every query scans them exhaustively.
+    pub unindexed_rows: usize,
+    /// Rows in the collection, and rows with a serving vector.
+    pub rows: usize,
+    pub vectors: usize,
+    pub fragments: usize,
+}
+
+impl IndexInfo {
+    /// Whether vector queries go through an ANN index at all.
+    pub fn uses_ann(&self) -> bool { self.index_name.is_some() }
+
+    /// Vectors each query compares one by one: all of them without an index,
+    /// else those added after the build.
+    pub fn brute_force_rows(&self) -> usize { if self.uses_ann() { self.unindexed_rows } else { self.vectors } }
+}
+
+pub async fn index_info(conn: &Connection, docs_table: &str) -> Result<IndexInfo> {
+    let table = conn.open_table(docs_table).execute().await?;
+    let stats = table.stats().await?;
+    let mut info = IndexInfo {
+        active_index: get_meta(conn, META_TABLE, &format!("active_index_id:{}", docs_table)).await?,
+        rows: stats.num_rows,
+        vectors: count_ready_vectors(conn, docs_table).await?,
+        fragments: stats.fragment_stats.num_fragments,
+        ..Default::default()
+    };
+    info.vector_indices = table.list_indices().await?.into_iter().filter(|i| i.columns == ["vector"]).map(|i| i.name).collect();
+    info.index_name = info.active_index.clone().filter(|a| info.vector_indices.contains(a)).or_else(|| info.vector_indices.first().cloned());
+    let Some(name) = info.index_name.clone() else { return Ok(info) };
+    if let Some(s) = table.index_stats(&name).await? { info.indexed_rows = s.num_indexed_rows; info.unindexed_rows = s.num_unindexed_rows; }
+    let record = get_meta(conn, META_TABLE, &format!("index_build:{}", name)).await?.unwrap_or_default();
+    for (key, value) in record.lines().filter_map(|l| l.split_once('\t')) {
+        match key {
+            "nlist" => info.nlist = value.parse().ok(),
+            "m" => info.m = value.parse().ok(),
+            "nbits" => info.nbits = value.parse().ok(),
+            "built_at" => info.built_at_ms = value.parse().ok(),
+            _ => {}
+        }
+    }
+    Ok(info)
+}
+
+/// How `documents.vector` compares with the `embeddings` side table
+/// (`localdb-cli stats` and `maintain`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VectorCoverage {
+    pub rows: usize,
+    /// Rows with a null `vector`: vector search never returns them.
+    pub null: usize,
+    /// Null rows that do have a vector in `embeddings` (backfilled, never synced).
+    pub unsynced: usize,
+    /// Rows serving a vector other than their newest one in `embeddings`.
+    pub stale: usize,
+}
+
+impl VectorCoverage {
+    /// Share of rows with a null vector.
+    pub fn null_ratio(&self) -> f64 { self.null as f64 / self.rows.max(1) as f64 }
+
+    /// Share of rows whose serving vector lags `embeddings` (unsynced or stale).
+    pub fn lag_ratio(&self) -> f64 { (self.unsynced + self.stale) as f64 / self.rows.max(1) as f64 }
+
+    /// A warning when more than `max_ratio` of the rows lack a vector or lag
+    /// behind `embeddings`.
+    pub fn warning(&self, max_ratio: f64) -> Option<String> {
+        if self.lag_ratio() > max_ratio {
+            return Some(format!("{:.1}% of chunks serve no vector or an outdated one although `embeddings` has a newer one ({} unsynced, {} stale); sync the serving vectors", self.lag_ratio() * 100.0, self.unsynced, self.stale));
+        }
+        (self.null_ratio() > max_ratio).then(|| format!("{:.1}% of chunks ({}) have no vector; vector search misses them until they are embedded", self.null_ratio() * 100.0, self.null))
+    }
+}
+
+/// Fingerprint of a vector, to compare serving and backfilled vectors without holding both.
+fn vector_fingerprint(v: &[f32]) -> u64 {
+    let mut h = std::collections::hash_map::DefaultHasher::new();
+    for x in v { x.to_bits().hash(&mut h); }
+    h.finish()
+}
+
+/// Compare every row of `docs_table` with its newest vector in `emb_table`
+/// from `embedder_id`, the one serving (any embedder when `None`, for a
+/// collection that never recorded one); without `emb_table` only null
+/// vectors are counted.
+pub async fn vector_coverage(conn: &Connection, docs_table: &str, emb_table: &str, embedder_id: Option<&str>) -> Result<VectorCoverage> {
+    let mut newest: HashMap<String, (i64, u64)> = HashMap::new();
+    if conn.table_names().execute().await?.iter().any(|n| n == emb_table) {
+        let emb = conn.open_table(emb_table).execute().await?;
+        let mut q = emb.query().select(Select::columns(&["id", "embedded_at", "vector"]));
+        if let Some(id) = embedder_id { q = q.only_if(format!("embedder_id = '{}'", id.replace('\'', "''"))); }
+        let mut stream = q.execute().await?;
+        while let Some(batch) = futures::TryStreamExt::try_next(&mut stream).await? {
+            let id = string_column(&batch, "id")?;
+            let at = column::<TimestampMillisecondArray>(&batch, "embedded_at", "Timestamp(ms)")?;
+            let vecs = vector_column(&batch, "vector")?;
+            for i in 0..batch.num_rows() {
+                let Some(v) = vector_value(vecs, i, id.value(i))? else { continue };
+                let entry = newest.entry(id.value(i).to_string()).or_insert((i64::MIN, 0));
+                if at.value(i) >= entry.0 { *entry = (at.value(i), vector_fingerprint(&v)); }
+            }
+        }
+    }
+    let docs = conn.open_table(docs_table).execute().await?;
+    let mut coverage = VectorCoverage::default();
+    let mut stream = docs.query().select(Select::columns(&["id", "vector"])).execute().await?;
+    while let Some(batch) = futures::TryStreamExt::try_next(&mut stream).await? {
+        let id = string_column(&batch, "id")?;
+        let vecs = vector_column(&batch, "vector")?;
+        for i in 0..batch.num_rows() {
+            coverage.rows += 1;
+            let backfilled = newest.get(id.value(i)).map(|(_, f)| *f);
+            match vector_value(vecs, i, id.value(i))? {
+                None => { coverage.null += 1; if backfilled.is_some() { coverage.unsynced += 1; } }
+                Some(v) => if backfilled.is_some_and(|f| f != vector_fingerprint(&v)) { coverage.stale += 1; },
+            }
+        }
+    }
+    Ok(coverage)
 }
-//! Training/build/flip utilities for IVF_PQ indices in Lance.
-//!
-//! Typical flow:
-//! 1) Copy vectors from `embeddings` to `documents.vector` for the target `embedder_id`
-//! 2) Compute params based on ready rows; build IVF_PQ under a unique name
-//! 3) Validate on a tiny sample; flip the active index pointer in `meta`
@@ -0,0 +1,60 @@
+//! Per-query latency budget with adaptive `nprobes`.
+//!
+//! IVF search cost grows with the number of partitions probed. A query starts
+//! on the cheapest rung of `nprobes` and escalates only when it returned fewer
+//! than `k` confident hits and the next rung is predicted to fit in what is
+//! left of the budget. Fast machines keep escalating (recall); slow ones stop
+//! early (consistent interactive latency).
+
+use std::time::Duration;
+
+use localdb_core::config::Config;
+
+#[derive(Debug, Clone)]
+pub struct LatencyBudget {
+    /// Total wall time a single query may spend in vector search.
+    pub budget: Duration,
+    /// Ascending `nprobes` rungs; the first is always run.
+    pub nprobes: Vec<usize>,
+    /// A hit counts as confident when its score (1 - distance) reaches this.
+    pub min_score: f32,
+}
+
+impl Default for LatencyBudget {
+    fn default() -> Self { Self { budget: Duration::from_millis(150), nprobes: vec![8, 20, 64], min_score: 0.5 } }
+}
+
+impl LatencyBudget {
+    /// Read `search.vector.{latency_budget_ms, nprobes_ladder, min_confident_score}`,
+    /// falling back to the defaults for missing keys.
+    pub fn from_config(config: &Config) -> Self {
+        let d = Self::default();
+        let budget = config.get::<u64>("search.vector.latency_budget_ms").map(Duration::from_millis).unwrap_or(d.budget);
+        let mut nprobes = config.get::<Vec<usize>>("search.vector.nprobes_ladder").unwrap_or(d.nprobes);
+        nprobes.retain(|&n| n > 0);
+        nprobes.sort_unstable();
+        nprobes.dedup();
+        if nprobes.is_empty() { nprobes = Self::default().nprobes; }
+        let min_score = config.get::<f32>("search.vector.min_confident_score").unwrap_or(d.min_score);
+        Self { budget, nprobes, min_score }
+    }
+
+    /// `nprobes` for the first attempt.
+    pub fn initial_nprobes(&self) -> usize { self.nprobes.first().copied().unwrap_or(1) }
+
+    pub fn confident(&self, scores: impl IntoIterator<Item = f32>) -> usize {
+        scores.into_iter().filter(|&s| s >= self.min_score).count()
+    }
+
+    /// After attempt `rung` took `last` (with `spent` total so far) and yielded
+    /// `confident` hits, the `nprobes` to retry with, or `None` to stop.
+    /// The next attempt's cost is predicted to scale linearly with `nprobes`.
+    pub fn next_nprobes(&self, rung: usize, confident: usize, k: usize, spent: Duration, last: Duration) -> Option<usize> {
+        if confident >= k { return None; }
+        let cur = *self.nprobes.get(rung)?;
+        let next = *self.nprobes.get(rung + 1)?;
+        let predicted = last.mul_f64(next as f64 / cur.max(1) as f64);
+        if spent + predicted > self.budget { return None; }
+        Some(next)
+    }
+}
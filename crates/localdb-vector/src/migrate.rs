@@ -0,0 +1,106 @@
+//! Migrate positional chunk ids (`doc_id:N`) to content-based ids.
+//!
+//! Older ingests numbered chunks by position, so inserting a paragraph shifted
+//! every later id. Current ids come from `localdb_core::data_processor::chunk_id`
+//! (doc id + content-hash prefix, position kept in `chunk_index`). The mapping
+//! is computed from the `content_hash` column and applied with batched
+//! `UPDATE ... SET id = CASE ...` statements, each atomic, so an interrupted
+//! migration can be rerun: rows already carrying their new id map to nothing.
+//!
+//! `relativize_doc_paths` converts legacy absolute `doc_path`s under a known
+//! root into the portable root-relative form.
+
+use anyhow::Result;
+use lancedb::Connection;
+use lancedb::query::{ExecutableQuery, QueryBase, Select};
+use std::collections::HashMap;
+use std::path::Path;
+
+use localdb_core::data_processor::chunk_id_from_hash;
+
+use crate::arrow_utils::{column, string_column};
+
+/// Number of ids rewritten per UPDATE statement.
+const UPDATE_BATCH: usize = 256;
+
+/// Old id → new id for every row of `docs_table` whose id is not yet content-based.
+pub async fn chunk_id_mapping(conn: &Connection, docs_table: &str) -> Result<HashMap<String, String>> {
+    let t = conn.open_table(docs_table).execute().await?;
+    // doc_id → [(chunk_index, id, content_hash)]
+    let mut by_doc: HashMap<String, Vec<(i32, String, String)>> = HashMap::new();
+    let mut stream = t.query().select(Select::columns(&["id", "doc_id", "chunk_index", "content_hash"])).execute().await?;
+    while let Some(batch) = futures::TryStreamExt::try_next(&mut stream).await? {
+        let ids = string_column(&batch, "id")?;
+        let doc_ids = string_column(&batch, "doc_id")?;
+        let idx = column::<arrow_array::Int32Array>(&batch, "chunk_index", "Int32")?;
+        let hashes = string_column(&batch, "content_hash")?;
+        for i in 0..batch.num_rows() {
+            by_doc.entry(doc_ids.value(i).to_string()).or_default()
+                .push((idx.value(i), ids.value(i).to_string(), hashes.value(i).to_string()));
+        }
+    }
+    let mut mapping = HashMap::new();
+    for (doc_id, mut rows) in by_doc {
+        rows.sort_by_key(|(chunk_index, _, _)| *chunk_index);
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        for (_, old, hash) in rows {
+            let n = seen.entry(hash.clone()).or_insert(0);
+            let new = chunk_id_from_hash(&doc_id, &hash, *n);
+            *n += 1;
+            if new != old { mapping.insert(old, new); }
+        }
+    }
+    Ok(mapping)
+}
+
+/// Rewrite `id` in `table` according to `mapping`. Missing tables are skipped.
+/// Returns the number of mapped ids submitted.
+pub async fn rewrite_ids(conn: &Connection, table: &str, mapping: &HashMap<String, String>) -> Result<usize> {
+    let names = conn.table_names().execute().await?;
+    if !names.contains(&table.to_string()) || mapping.is_empty() { return Ok(0); }
+    let t = conn.open_table(table).execute().await?;
+    let pairs: Vec<(&String, &String)> = mapping.iter().collect();
+    for chunk in pairs.chunks(UPDATE_BATCH) {
+        let q = |s: &str| format!("'{}'", s.replace('\'', "''"));
+        let arms: String = chunk.iter().map(|(old, new)| format!(" WHEN {} THEN {}", q(old), q(new))).collect();
+        let in_list = chunk.iter().map(|(old, _)| q(old)).collect::<Vec<_>>().join(", ");
+        t.update()
+            .only_if(format!("id IN ({})", in_list))
+            .column("id", format!("CASE id{} ELSE id END", arms))
+            .execute()
+            .await?;
+    }
+    Ok(pairs.len())
+}
+
+/// Migrate `docs_table` and its `emb_table` side table to content-based chunk
+/// ids. Returns the number of chunks renamed (0 when already migrated).
+/// The Tantivy index is rebuilt on every ingest and needs no migration.
+pub async fn migrate_chunk_ids(conn: &Connection, docs_table: &str, emb_table: &str) -> Result<usize> {
+    let mapping = chunk_id_mapping(conn, docs_table).await?;
+    if mapping.is_empty() { return Ok(0); }
+    // Side table first: if interrupted, the documents table still holds old
+    // ids and the rerun recomputes the same mapping.
+    rewrite_ids(conn, emb_table, &mapping).await?;
+    rewrite_ids(conn, docs_table, &mapping).await?;
+    Ok(mapping.len())
+}
+
+/// Rewrite absolute `doc_path`s that start with `old_root` to be relative to
+/// it (with `/` separators). Paths outside `old_root` are left untouched.
+pub async fn relativize_doc_paths(conn: &Connection, table: &str, old_root: &Path) -> Result<()> {
+    let names = conn.table_names().execute().await?;
+    if !names.contains(&table.to_string()) { return Ok(()); }
+    let mut prefix = old_root.to_string_lossy().to_string();
+    if !prefix.ends_with(std::path::MAIN_SEPARATOR) { prefix.push(std::path::MAIN_SEPARATOR); }
+    let lit = format!("'{}'", prefix.replace('\'', "''"));
+    let rest = format!("substr(doc_path, {})", prefix.chars().count() + 1);
+    let relative = if std::path::MAIN_SEPARATOR == '/' { rest } else { format!("replace({}, '\\', '/')", rest) };
+    let t = conn.open_table(table).execute().await?;
+    t.update()
+        .only_if(format!("starts_with(doc_path, {})", lit))
+        .column("doc_path", relative)
+        .execute()
+        .await?;
+    Ok(())
+}
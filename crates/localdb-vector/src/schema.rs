@@ -1,9 +1,23 @@
+//! Arrow schema builders for Lance tables used by the vector pipeline.
+//!
+//! Includes `documents` (serving + status), `embeddings` (side table for
+//! training/AB), `emb_cache` (first-class cache), `<collection>_tokens`
+//! (token vectors in multi-vector mode), and `catalog` (per-file
+//! hashes, summaries, and folder metadata). The vector width is a runtime property of each collection, so
+//! every vector-bearing builder takes `dim`.
+
 use arrow_schema::{Schema, Field, DataType};
 use std::sync::Arc;
 
+/// Default dimensionality for new collections (BGE‑M3). Existing collections
+/// record their own dim in `meta` (see `table::collection_dim`).
 pub const EMBEDDING_DIM: i32 = 1024;
 
-pub fn build_arrow_schema() -> Arc<Schema> {
+fn vector_type(dim: i32) -> DataType {
+	DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim)
+}
+
+pub fn build_arrow_schema(dim: i32) -> Arc<Schema> {
 	Arc::new(Schema::new(vec![
 		Field::new("id", DataType::Utf8, false),
 		Field::new("doc_id", DataType::Utf8, false),
@@ -14,7 +28,7 @@ pub fn build_arrow_schema() -> Arc<Schema> {
 		Field::new("chunk_index", DataType::Int32, false),
 		Field::new("total_chunks", DataType::Int32, false),
 		// Serving vector column (nullable); filled only after validation/build
-		Field::new("vector", DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), EMBEDDING_DIM), true),
+		Field::new("vector", vector_type(dim), true),
 		// Resumability & index status
 		Field::new("content_hash", DataType::Utf8, false),
 		Field::new("embedding_status", DataType::Utf8, false),
@@ -23,36 +37,78 @@ pub fn build_arrow_schema() -> Arc<Schema> {
 		Field::new("embedded_at", DataType::Timestamp(arrow_schema::TimeUnit::Millisecond, None), true),
 		Field::new("index_status", DataType::Utf8, false),
 		Field::new("index_version", DataType::Int32, false),
+		// Document metadata (absent in tables written before it; see `writer::DOCUMENT_COLUMNS`)
+		Field::new("title", DataType::Utf8, true),
+		Field::new("author", DataType::Utf8, true),
+		Field::new("created_at", DataType::Timestamp(arrow_schema::TimeUnit::Millisecond, None), true),
+		// Free-form metadata (`key\tvalue` lines, see `localdb_core::folder_meta`).
+		Field::new("meta", DataType::Utf8, true),
+		// ISO 639-1 code of the chunk's language (`DocumentChunk::lang`).
+		Field::new("lang", DataType::Utf8, true),
+		// Location in the source file (`DocumentChunk::span`): bytes, end exclusive, and 1-based lines.
+		Field::new("start_offset", DataType::Int64, true),
+		Field::new("end_offset", DataType::Int64, true),
+		Field::new("start_line", DataType::Int64, true),
+		Field::new("end_line", DataType::Int64, true),
 	]))
 }
 
-pub fn build_embeddings_schema() -> Arc<Schema> {
+pub fn build_embeddings_schema(dim: i32) -> Arc<Schema> {
     Arc::new(Schema::new(vec![
         Field::new("id", DataType::Utf8, false),
         Field::new("embedder_id", DataType::Utf8, false),
         Field::new("content_hash", DataType::Utf8, false),
         Field::new("embedded_at", DataType::Timestamp(arrow_schema::TimeUnit::Millisecond, None), false),
-        Field::new(
-            "vector",
-            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), EMBEDDING_DIM),
-            true,
-        ),
+        Field::new("vector", vector_type(dim), true),
     ]))
 }
 
-pub fn build_cache_schema() -> Arc<Schema> {
+pub fn build_cache_schema(dim: i32) -> Arc<Schema> {
     Arc::new(Schema::new(vec![
         Field::new("content_hash", DataType::Utf8, false),
         Field::new("embedder_id", DataType::Utf8, false),
         Field::new("created_at", DataType::Timestamp(arrow_schema::TimeUnit::Millisecond, None), false),
-        Field::new(
-            "vector",
-            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), EMBEDDING_DIM),
-            true,
-        ),
+        Field::new("vector", vector_type(dim), true),
     ]))
 }
-//! Arrow schema builders for Lance tables used by the vector pipeline.
-//!
-//! Includes `documents` (serving + status), `embeddings` (side table for
-//! training/AB), and `emb_cache` (first-class cache).
+
+/// Per-file catalog (`catalog` table): full-file hash and summary recorded at ingest.
+pub fn build_catalog_schema() -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("doc_path", DataType::Utf8, false),
+        Field::new("doc_id", DataType::Utf8, false),
+        Field::new("category", DataType::Utf8, false),
+        Field::new("file_hash", DataType::Utf8, false),
+        Field::new("size", DataType::Int64, false),
+        Field::new("modified_at", DataType::Timestamp(arrow_schema::TimeUnit::Millisecond, None), false),
+        Field::new("hashed_at", DataType::Timestamp(arrow_schema::TimeUnit::Millisecond, None), false),
+        Field::new("summary", DataType::Utf8, true),
+        // Inherited folder metadata (`key\tvalue` lines, see `localdb_core::folder_meta`).
+        Field::new("meta", DataType::Utf8, true),
+    ]))
+}
+
+/// Token vectors of chunks (`<collection>_tokens`, see `tokens`): one row per token.
+pub fn build_token_vectors_schema(dim: i32) -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("position", DataType::Int32, false),
+        Field::new("vector", vector_type(dim), true),
+    ]))
+}
+
+/// `(id, vector)` source schema used when merging vectors into `documents`.
+pub fn build_serving_vector_schema(dim: i32) -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("vector", vector_type(dim), true),
+    ]))
+}
+
+/// Width of the `vector` column in `schema`, if present.
+pub fn vector_dim(schema: &Schema) -> Option<i32> {
+    match schema.field_with_name("vector").ok()?.data_type() {
+        DataType::FixedSizeList(_, dim) => Some(*dim),
+        _ => None,
+    }
+}
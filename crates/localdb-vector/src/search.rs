@@ -1,43 +1,118 @@
 use anyhow::Result;
+use std::collections::HashMap;
+use std::time::Instant;
 use futures::TryStreamExt;
 use lancedb::{connect, Connection};
-use lancedb::query::{QueryBase, ExecutableQuery};
+use lancedb::query::{QueryBase, ExecutableQuery, Select};
+use localdb_core::facets::FacetAliases;
+use localdb_core::roots::RootMap;
 use localdb_core::traits::Embedder;
 // Note: do not depend on the embedder provider crate here; accept an Embedder from callers.
 use localdb_core::traits::VectorIndexer;
-use localdb_core::types::{DocumentChunk, SearchHit, SourceKind};
+use localdb_core::types::{DocumentChunk, MultiVector, SearchHit, SourceKind};
 
-pub struct LanceSearchEngine { pub(crate) db: Connection, pub(crate) table_name: String, pub(crate) embedder: Box<dyn Embedder> }
+use crate::arrow_utils::{f32_column, string_column, vector_column, vector_value, DocColumns};
+use crate::latency::LatencyBudget;
+
+pub struct LanceSearchEngine { pub(crate) db: Connection, pub(crate) table_name: String, pub(crate) embedder: Box<dyn Embedder>, pub(crate) latency_budget: Option<LatencyBudget>, pub(crate) data_roots: RootMap, pub(crate) facet_aliases: FacetAliases }
 
 impl LanceSearchEngine {
+    /// Open a search engine over `table_name`, failing fast if the embedder's
+    /// dimension does not match the collection's stored vectors.
     pub async fn new(db_path: std::path::PathBuf, table_name: &str, embedder: Box<dyn Embedder>) -> Result<Self, anyhow::Error> {
         let db = connect(db_path.to_string_lossy().as_ref()).execute().await?;
-        Ok(Self { db, table_name: table_name.to_string(), embedder })
+        crate::table::check_collection_dim(&db, table_name, embedder.dim()).await?;
+        let data_roots = crate::table::data_roots(&db, table_name).await?;
+        let facet_aliases = crate::table::facet_aliases(&db).await?;
+        Ok(Self { db, table_name: table_name.to_string(), embedder, latency_budget: None, data_roots, facet_aliases })
     }
 
+    /// Search with adaptive `nprobes` under `budget` instead of the Lance default.
+    pub fn with_latency_budget(mut self, budget: LatencyBudget) -> Self { self.latency_budget = Some(budget); self }
+
 	pub async fn search(&self, query_text: &str, limit: usize) -> Result<Vec<LanceSearchResult>, anyhow::Error> {
-        let query_embedding = self.embedder.embed_batch(&[query_text.to_string()])?.remove(0);
+        let query_embedding = self.embedder.embed_queries(&[query_text.to_string()])?.remove(0);
         let table = self.db.open_table(&self.table_name).execute().await?;
-        let pq_limit = limit * 10; let mut results = table.vector_search(query_embedding)?.limit(pq_limit).execute().await?;
+        let pq_limit = limit * 10;
+		let mut all_results = match &self.latency_budget {
+			None => Self::fetch(&table, &query_embedding, pq_limit, None).await?,
+			Some(budget) => {
+				let started = Instant::now();
+				let mut rung = 0; let mut nprobes = budget.initial_nprobes();
+				loop {
+					let attempt = Instant::now();
+					let results = Self::fetch(&table, &query_embedding, pq_limit, Some(nprobes)).await?;
+					let confident = budget.confident(results.iter().take(limit).map(|r| r.score));
+					match budget.next_nprobes(rung, confident, limit, started.elapsed(), attempt.elapsed()) {
+						Some(next) => { rung += 1; nprobes = next; }
+						None => break results,
+					}
+				}
+			}
+		};
+		// Simple rerank
+		let query_lower = query_text.to_lowercase(); let query_words: Vec<&str> = query_lower.split_whitespace().collect();
+		for result in &mut all_results { let content_lower = result.content.to_lowercase(); let mut text_score = 0.0; for word in &query_words { if content_lower.contains(word) { text_score += 1.0; } } result.score = (result.score * 0.7) + (text_score / query_words.len() as f32 * 0.3); }
+		all_results.sort_by(|a,b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+		all_results.truncate(limit);
+		// Stored paths are relative to the recorded data roots; show them absolute
+		// and flag hits whose media is unplugged. Renamed facets show their new name.
+		for r in &mut all_results {
+			if let Some(c) = self.facet_aliases.rename_stored(&r.category) { r.category = c; }
+			r.offline = self.data_roots.offline_label(&r.path);
+			r.path = self.data_roots.resolve(&r.path).to_string_lossy().to_string();
+		}
+		Ok(all_results)
+	}
+
+	/// One ANN query; `nprobes = None` keeps the Lance default.
+	async fn fetch(table: &lancedb::Table, query: &[f32], limit: usize, nprobes: Option<usize>) -> Result<Vec<LanceSearchResult>> {
+		let mut q = table.vector_search(query.to_vec())?.limit(limit);
+		if let Some(n) = nprobes { q = q.nprobes(n); }
+		let mut results = q.execute().await?;
 		let mut all_results = Vec::new();
 		while let Some(batch) = TryStreamExt::try_next(&mut results).await? {
+			let ids = string_column(&batch, "id")?;
+			let categories = string_column(&batch, "category")?;
+			let paths = string_column(&batch, "doc_path")?;
+			let contents = string_column(&batch, "content")?;
+			let distances = match f32_column(&batch, "_distance")? { Some(d) => Some(d), None => f32_column(&batch, "distance")? };
+			let scores = f32_column(&batch, "_score")?;
 			for i in 0..batch.num_rows() {
-				let id = batch.column_by_name("id").unwrap().as_any().downcast_ref::<arrow_array::StringArray>().unwrap().value(i).to_string();
-				let category = batch.column_by_name("category").unwrap().as_any().downcast_ref::<arrow_array::StringArray>().unwrap().value(i).to_string();
-				let path = batch.column_by_name("doc_path").unwrap().as_any().downcast_ref::<arrow_array::StringArray>().unwrap().value(i).to_string();
-				let content = batch.column_by_name("content").unwrap().as_any().downcast_ref::<arrow_array::StringArray>().unwrap().value(i).to_string();
-				let score = if let Some(distance_col) = batch.column_by_name("_distance") { 1.0 - distance_col.as_any().downcast_ref::<arrow_array::Float32Array>().unwrap().value(i) }
-						else if let Some(distance_col) = batch.column_by_name("distance") { 1.0 - distance_col.as_any().downcast_ref::<arrow_array::Float32Array>().unwrap().value(i) }
-						else if let Some(score_col) = batch.column_by_name("_score") { score_col.as_any().downcast_ref::<arrow_array::Float32Array>().unwrap().value(i) }
+				let id = ids.value(i).to_string();
+				let category = categories.value(i).to_string();
+				let path = paths.value(i).to_string();
+				let content = contents.value(i).to_string();
+				let score = if let Some(d) = distances { 1.0 - d.value(i) }
+						else if let Some(sc) = scores { sc.value(i) }
 						else { 0.5 };
-				all_results.push(LanceSearchResult { score, id, category, path, content });
+				all_results.push(LanceSearchResult { score, id, category, path, content, offline: None });
 			}
 		}
-		// Simple rerank
-		let query_lower = query_text.to_lowercase(); let query_words: Vec<&str> = query_lower.split_whitespace().collect();
-		for result in &mut all_results { let content_lower = result.content.to_lowercase(); let mut text_score = 0.0; for word in &query_words { if content_lower.contains(word) { text_score += 1.0; } } result.score = (result.score * 0.7) + (text_score / query_words.len() as f32 * 0.3); }
-		all_results.sort_by(|a,b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
-		Ok(all_results.into_iter().take(limit).collect())
+		Ok(all_results)
+	}
+}
+
+impl super::writer::LanceDbIndexer {
+	fn search_vec_once(&self, rt: &tokio::runtime::Runtime, table: &lancedb::Table, q_vec: &[f32], k: usize, nprobes: Option<usize>, filter: Option<&str>) -> Result<Vec<SearchHit>> {
+		let mut stream = rt.block_on(async {
+			let mut q = table.vector_search(q_vec.to_vec())?.limit(k);
+			if let Some(n) = nprobes { q = q.nprobes(n); }
+			if let Some(f) = filter { q = q.only_if(f); }
+			q.execute().await
+		})?;
+		let mut hits = Vec::new();
+		while let Some(batch) = rt.block_on(async { TryStreamExt::try_next(&mut stream).await })? {
+			let ids = string_column(&batch, "id")?;
+			let distances = f32_column(&batch, "_distance")?;
+			let about = DocColumns::of(&batch)?;
+			for i in 0..batch.num_rows() {
+				let id = ids.value(i).to_string();
+				let score = if let Some(d) = distances { 1.0 - d.value(i) } else { 0.5 };
+				hits.push(SearchHit { title: about.title(i), author: about.author(i), created_at: about.created_at(i), meta: about.meta(i), lang: about.lang(i), span: about.span(i), ..SearchHit::new(id, score, SourceKind::Vector) });
+			}
+		}
+		Ok(hits)
 	}
 }
 
@@ -48,20 +123,67 @@ impl VectorIndexer for super::writer::LanceDbIndexer {
 		rt.block_on(async { self.index(chunks, embeddings).await })
 	}
 	fn search_vec(&self, q_vec: &[f32], k: usize) -> anyhow::Result<Vec<SearchHit>> {
+		self.search_vec_in(q_vec, k, None)
+	}
+	fn search_vec_in(&self, q_vec: &[f32], k: usize, lang: Option<&str>) -> anyhow::Result<Vec<SearchHit>> {
+		self.search_vec_in_facet(q_vec, k, lang, None)
+	}
+	fn search_vec_in_facet(&self, q_vec: &[f32], k: usize, lang: Option<&str>, facet: Option<&str>) -> anyhow::Result<Vec<SearchHit>> {
 		let rt = tokio::runtime::Runtime::new()?;
 		let table = rt.block_on(async { self.db.open_table(&self.table_name).execute().await })?;
-		let mut stream = rt.block_on(async { table.vector_search(q_vec.to_vec())?.limit(k).execute().await })?;
-		let mut hits = Vec::new();
-		while let Some(batch) = rt.block_on(async { TryStreamExt::try_next(&mut stream).await })? {
-			for i in 0..batch.num_rows() {
-				let id = batch.column_by_name("id").unwrap().as_any().downcast_ref::<arrow_array::StringArray>().unwrap().value(i).to_string();
-				let score = if let Some(distance_col) = batch.column_by_name("_distance") { 1.0 - distance_col.as_any().downcast_ref::<arrow_array::Float32Array>().unwrap().value(i) } else { 0.5 };
-				hits.push(SearchHit { id, score, source: SourceKind::Vector });
+		// Prefiltered, so the top `k` are all in `lang` and under `facet`; collections
+		// from before chunks carried a language have no `lang` column to match.
+		let lang = match lang {
+			Some(_) if rt.block_on(async { table.schema().await })?.field_with_name("lang").is_err() => return Ok(Vec::new()),
+			Some(lang) => Some(format!("lang = '{}'", lang.to_ascii_lowercase().replace('\'', "''"))),
+			None => None,
+		};
+		let facet = facet.and_then(|f| crate::table::facet_filter(f, &self.facet_aliases));
+		let filter = match (lang, facet) {
+			(Some(l), Some(f)) => Some(format!("{} AND ({})", l, f)),
+			(l, f) => l.or(f),
+		};
+		let filter = filter.as_deref();
+		let Some(budget) = &self.latency_budget else { return self.search_vec_once(&rt, &table, q_vec, k, None, filter) };
+		let started = Instant::now();
+		let mut rung = 0; let mut nprobes = budget.initial_nprobes();
+		loop {
+			let attempt = Instant::now();
+			let hits = self.search_vec_once(&rt, &table, q_vec, k, Some(nprobes), filter)?;
+			let confident = budget.confident(hits.iter().map(|h| h.score));
+			match budget.next_nprobes(rung, confident, k, started.elapsed(), attempt.elapsed()) {
+				Some(next) => { rung += 1; nprobes = next; }
+				None => return Ok(hits),
 			}
 		}
-		Ok(hits)
+	}
+	fn vectors(&self, ids: &[String]) -> anyhow::Result<HashMap<String, Vec<f32>>> {
+		let mut out = HashMap::new();
+		if ids.is_empty() { return Ok(out); }
+		let rt = tokio::runtime::Runtime::new()?;
+		let table = rt.block_on(async { self.db.open_table(&self.table_name).execute().await })?;
+		for batch_ids in ids.chunks(crate::table::DELETE_BATCH) {
+			let filter = format!("id IN ({})", crate::table::sql_list(batch_ids));
+			let mut stream = rt.block_on(async { table.query().select(Select::columns(&["id", "vector"])).only_if(filter).execute().await })?;
+			while let Some(batch) = rt.block_on(async { TryStreamExt::try_next(&mut stream).await })? {
+				let (ids, vecs) = (string_column(&batch, "id")?, vector_column(&batch, "vector")?);
+				for i in 0..batch.num_rows() {
+					if let Some(v) = vector_value(vecs, i, ids.value(i))? { out.insert(ids.value(i).to_string(), v); }
+				}
+			}
+		}
+		Ok(out)
+	}
+	fn index_token_vectors(&self, ids: &[String], vectors: &[MultiVector]) -> anyhow::Result<()> {
+		let rt = tokio::runtime::Runtime::new()?;
+		rt.block_on(crate::tokens::write_token_vectors(&self.db, &self.table_name, ids, vectors))
+	}
+	fn token_vectors(&self, ids: &[String]) -> anyhow::Result<HashMap<String, MultiVector>> {
+		let rt = tokio::runtime::Runtime::new()?;
+		rt.block_on(crate::tokens::read_token_vectors(&self.db, &self.table_name, ids))
 	}
 }
 
+/// `offline` is `offline media: <label>` when the document's data root is unplugged.
 #[derive(Debug, Clone)]
-pub struct LanceSearchResult { pub score: f32, pub id: String, pub category: String, pub path: String, pub content: String }
+pub struct LanceSearchResult { pub score: f32, pub id: String, pub category: String, pub path: String, pub content: String, pub offline: Option<String> }
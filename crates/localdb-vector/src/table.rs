@@ -1,12 +1,29 @@
-use anyhow::Result;
+//! LanceDB connection and housekeeping helpers.
+//!
+//! Provides database open functions, ensure-* helpers for tables, and a simple
+//! key/value metadata table used to store pointers such as the active index id
+//! and the per-collection embedding dimension, embedder, data root and score
+//! calibration.
+
+use anyhow::{Result, anyhow};
 use lancedb::{connect, Connection};
 
 use arrow_array::{RecordBatch, RecordBatchIterator, StringArray, TimestampMillisecondArray};
+use std::collections::BTreeSet;
 use std::sync::Arc;
 use chrono::Utc;
-use lancedb::query::{QueryBase, ExecutableQuery};
+use lancedb::query::{QueryBase, ExecutableQuery, Select};
 
-use crate::schema::{build_embeddings_schema, build_cache_schema};
+use localdb_core::calibration::ScoreCalibration;
+use localdb_core::facets::FacetAliases;
+use localdb_core::roots::RootMap;
+use localdb_core::seed::SeededRng;
+use localdb_core::types::DocumentChunk;
+
+use crate::schema::{build_embeddings_schema, build_cache_schema, vector_dim};
+
+/// Global meta table holding per-collection pointers and settings.
+pub const META_TABLE: &str = "meta";
 
 pub async fn open_db(uri: &str) -> Result<Connection> {
     Ok(connect(uri).execute().await?)
@@ -23,12 +40,20 @@ pub async fn ensure_table(conn: &Connection, name: &str, schema: Arc<arrow_schem
     Ok(())
 }
 
-pub async fn ensure_embeddings_table(conn: &Connection, name: &str) -> Result<()> {
-    ensure_table(conn, name, build_embeddings_schema()).await
+pub async fn ensure_embeddings_table(conn: &Connection, name: &str, dim: i32) -> Result<()> {
+    ensure_table(conn, name, build_embeddings_schema(dim)).await
 }
 
-pub async fn ensure_cache_table(conn: &Connection, name: &str) -> Result<()> {
-    ensure_table(conn, name, build_cache_schema()).await
+pub async fn ensure_cache_table(conn: &Connection, name: &str, dim: i32) -> Result<()> {
+    ensure_table(conn, name, build_cache_schema(dim)).await
+}
+
+/// Width of the `vector` column of an existing table, or `None` if the table is missing.
+pub async fn table_vector_dim(conn: &Connection, name: &str) -> Result<Option<i32>> {
+    let names = conn.table_names().execute().await?;
+    if !names.contains(&name.to_string()) { return Ok(None); }
+    let schema = conn.open_table(name).execute().await?.schema().await?;
+    Ok(vector_dim(&schema))
 }
 
 // Simple key/value meta table management for active index pointers and job state
@@ -75,7 +100,320 @@ pub async fn get_meta(conn: &Connection, table: &str, key: &str) -> Result<Optio
     }
     Ok(None)
 }
-//! LanceDB connection and housekeeping helpers.
-//!
-//! Provides database open functions, ensure-* helpers for tables, and a simple
-//! key/value metadata table used to store pointers such as the active index id.
+
+/// Remove `key` from the meta `table`, if present.
+pub async fn delete_meta(conn: &Connection, table: &str, key: &str) -> Result<()> {
+    let names = conn.table_names().execute().await?;
+    if !names.contains(&table.to_string()) { return Ok(()); }
+    conn.open_table(table).execute().await?.delete(&format!("key = '{}'", key.replace("'", "''"))).await?;
+    Ok(())
+}
+
+/// Index epoch: changes whenever the collection or the meta table is
+/// written (ingest, backfill, maintain, facet renames). `d<version>.m<version>`
+/// from the Lance table versions, 0 for a missing table. Keys HTTP caching.
+pub async fn index_epoch(conn: &Connection, collection: &str) -> Result<String> {
+    let names = conn.table_names().execute().await?;
+    let mut versions = Vec::new();
+    for name in [collection, META_TABLE] {
+        versions.push(if names.contains(&name.to_string()) { conn.open_table(name).execute().await?.version().await? } else { 0 });
+    }
+    Ok(format!("d{}.m{}", versions[0], versions[1]))
+}
+
+fn dim_key(collection: &str) -> String { format!("embedding_dim:{}", collection) }
+
+/// Embedding dimension of a collection (documents table): the value recorded in
+/// `meta`, else the width of the table's `vector` column, else `None`.
+pub async fn collection_dim(conn: &Connection, collection: &str) -> Result<Option<i32>> {
+    if let Some(v) = get_meta(conn, META_TABLE, &dim_key(collection)).await? {
+        let dim = v.parse::<i32>().map_err(|e| anyhow!("invalid {} in meta: '{}' ({})", dim_key(collection), v, e))?;
+        return Ok(Some(dim));
+    }
+    table_vector_dim(conn, collection).await
+}
+
+pub async fn set_collection_dim(conn: &Connection, collection: &str, dim: i32) -> Result<()> {
+    set_meta(conn, META_TABLE, &dim_key(collection), &dim.to_string()).await
+}
+
+/// Fail if `collection` is known to hold vectors of a different width than `dim`.
+/// Unknown collections pass.
+pub async fn check_collection_dim(conn: &Connection, collection: &str, dim: usize) -> Result<()> {
+    match collection_dim(conn, collection).await? {
+        Some(stored) if stored as usize != dim => Err(anyhow!(
+            "embedding dim mismatch for collection '{}': collection has {}, embedder produces {}",
+            collection, stored, dim
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Validate `dim` against the collection and record it in `meta` when not yet set.
+pub async fn ensure_collection_dim(conn: &Connection, collection: &str, dim: usize) -> Result<i32> {
+    check_collection_dim(conn, collection, dim).await?;
+    if get_meta(conn, META_TABLE, &dim_key(collection)).await?.is_none() {
+        set_collection_dim(conn, collection, dim as i32).await?;
+    }
+    Ok(dim as i32)
+}
+
+fn embedder_key(collection: &str) -> String { format!("embedder_id:{}", collection) }
+
+/// `embedder_id` of the provider that last backfilled the collection, if recorded.
+pub async fn collection_embedder(conn: &Connection, collection: &str) -> Result<Option<String>> {
+    get_meta(conn, META_TABLE, &embedder_key(collection)).await
+}
+
+pub async fn set_collection_embedder(conn: &Connection, collection: &str, embedder_id: &str) -> Result<()> {
+    set_meta(conn, META_TABLE, &embedder_key(collection), embedder_id).await
+}
+
+/// Drop `collection`, its `emb_table` side table and its token vectors, and
+/// forget its recorded dim and embedder, so the next write starts it again at
+/// whatever width the new vectors have (`localdb-cli ingest --full` after a
+/// model change).
+pub async fn drop_collection(conn: &Connection, collection: &str, emb_table: &str) -> Result<()> {
+    let names = conn.table_names().execute().await?;
+    for table in [collection.to_string(), emb_table.to_string(), crate::tokens::token_table(collection)] {
+        if names.contains(&table) { conn.drop_table(&table, &[]).await?; }
+    }
+    delete_meta(conn, META_TABLE, &dim_key(collection)).await?;
+    delete_meta(conn, META_TABLE, &embedder_key(collection)).await
+}
+
+fn chunking_key(collection: &str) -> String { format!("chunking:{}", collection) }
+
+/// `DataProcessor::chunking_fingerprint` of the ingest that built the collection, if recorded.
+pub async fn chunking_fingerprint(conn: &Connection, collection: &str) -> Result<Option<String>> {
+    get_meta(conn, META_TABLE, &chunking_key(collection)).await
+}
+
+pub async fn set_chunking_fingerprint(conn: &Connection, collection: &str, fingerprint: &str) -> Result<()> {
+    set_meta(conn, META_TABLE, &chunking_key(collection), fingerprint).await
+}
+
+fn sparse_terms_key(collection: &str) -> String { format!("sparse_text:{}", collection) }
+
+/// Whether the text index built with the collection holds sparse lexical
+/// weights (`TextIndexer::index_sparse`); false if none recorded.
+pub async fn text_sparse_terms(conn: &Connection, collection: &str) -> Result<bool> {
+    Ok(get_meta(conn, META_TABLE, &sparse_terms_key(collection)).await?.as_deref() == Some("true"))
+}
+
+pub async fn set_text_sparse_terms(conn: &Connection, collection: &str, sparse: bool) -> Result<()> {
+    set_meta(conn, META_TABLE, &sparse_terms_key(collection), &sparse.to_string()).await
+}
+
+fn data_root_key(collection: &str) -> String { format!("data_root:{}", collection) }
+
+/// Ingest data roots that the collection's relative `doc_path`s resolve against
+/// (empty if none recorded).
+pub async fn data_roots(conn: &Connection, collection: &str) -> Result<RootMap> {
+    Ok(get_meta(conn, META_TABLE, &data_root_key(collection)).await?.map(|v| RootMap::decode(&v)).unwrap_or_default())
+}
+
+pub async fn set_data_roots(conn: &Connection, collection: &str, roots: &RootMap) -> Result<()> {
+    set_meta(conn, META_TABLE, &data_root_key(collection), &roots.encode()).await
+}
+
+fn calibration_key(collection: &str) -> String { format!("calibration:{}", collection) }
+
+/// What a calibration was fitted against: the collection's recorded embedder
+/// and its table version, which every ingest, backfill or maintenance moves.
+async fn calibration_stamp(conn: &Connection, collection: &str) -> Result<String> {
+    let names = conn.table_names().execute().await?;
+    let version = if names.contains(&collection.to_string()) { conn.open_table(collection).execute().await?.version().await? } else { 0 };
+    Ok(format!("{}@d{}", collection_embedder(conn, collection).await?.unwrap_or_default(), version))
+}
+
+/// Fusion score calibration fitted for the collection as it is now (none if
+/// not recorded, or fitted before its chunks or embedder changed).
+pub async fn score_calibration(conn: &Connection, collection: &str) -> Result<ScoreCalibration> {
+    let Some(value) = get_meta(conn, META_TABLE, &calibration_key(collection)).await? else { return Ok(ScoreCalibration::default()) };
+    let Some((stamp, calibration)) = value.split_once('\n') else { return Ok(ScoreCalibration::default()) };
+    if stamp != calibration_stamp(conn, collection).await? { return Ok(ScoreCalibration::default()); }
+    Ok(ScoreCalibration::decode(calibration))
+}
+
+/// Record `calibration`, stamped with the collection's embedder and version.
+pub async fn set_score_calibration(conn: &Connection, collection: &str, calibration: &ScoreCalibration) -> Result<()> {
+    let stamp = calibration_stamp(conn, collection).await?;
+    set_meta(conn, META_TABLE, &calibration_key(collection), &format!("{}\n{}", stamp, calibration.encode())).await
+}
+
+/// Meta key of the facet alias table (`old\tnew` lines; see `localdb_core::facets`).
+const FACET_ALIASES_KEY: &str = "facet_aliases";
+
+pub async fn facet_aliases(conn: &Connection) -> Result<FacetAliases> {
+    Ok(get_meta(conn, META_TABLE, FACET_ALIASES_KEY).await?.map(|v| FacetAliases::decode(&v)).unwrap_or_default())
+}
+
+pub async fn set_facet_aliases(conn: &Connection, aliases: &FacetAliases) -> Result<()> {
+    set_meta(conn, META_TABLE, FACET_ALIASES_KEY, &aliases.encode()).await
+}
+
+/// Rewrite stored facet values of `table` to their aliased names. `columns`
+/// all hold the category (the first is read); returns the rows updated.
+pub async fn rewrite_facets(conn: &Connection, table: &str, columns: &[&str], aliases: &FacetAliases) -> Result<usize> {
+    let names = conn.table_names().execute().await?;
+    if aliases.is_empty() || columns.is_empty() || !names.contains(&table.to_string()) { return Ok(0); }
+    let t = conn.open_table(table).execute().await?;
+    let distinct = distinct_values(conn, table, columns[0]).await?;
+    let mut updated = 0;
+    for old in distinct {
+        let Some(new) = aliases.rename_stored(&old) else { continue };
+        let predicate = format!("{} = {}", columns[0], sql_list(std::slice::from_ref(&old)));
+        updated += t.count_rows(Some(predicate.clone())).await?;
+        let mut update = t.update().only_if(predicate);
+        for c in columns { update = update.column(*c, sql_list(std::slice::from_ref(&new))); }
+        update.execute().await?;
+    }
+    Ok(updated)
+}
+
+/// Distinct values of the string `column` of `table` (none if it is missing).
+pub async fn distinct_values(conn: &Connection, table: &str, column: &str) -> Result<BTreeSet<String>> {
+    let names = conn.table_names().execute().await?;
+    let mut distinct = BTreeSet::new();
+    if !names.contains(&table.to_string()) { return Ok(distinct); }
+    let t = conn.open_table(table).execute().await?;
+    let mut stream = t.query().select(Select::columns(&[column])).execute().await?;
+    while let Some(batch) = futures::TryStreamExt::try_next(&mut stream).await? {
+        let col = crate::arrow_utils::string_column(&batch, column)?;
+        for i in 0..batch.num_rows() { if !distinct.contains(col.value(i)) { distinct.insert(col.value(i).to_string()); } }
+    }
+    Ok(distinct)
+}
+
+/// Up to `n` distinct stored `doc_path`s from `collection`, for spot checks:
+/// a `seed`ed sample of the distinct paths among the first `50 * n` rows.
+pub async fn sample_doc_paths(conn: &Connection, collection: &str, n: usize, seed: u64) -> Result<Vec<String>> {
+    let names = conn.table_names().execute().await?;
+    if !names.contains(&collection.to_string()) { return Ok(Vec::new()); }
+    let t = conn.open_table(collection).execute().await?;
+    let mut stream = t.query().select(Select::columns(&["doc_path"])).limit(n.saturating_mul(50).max(1)).execute().await?;
+    let mut out: Vec<String> = Vec::new();
+    while let Some(batch) = futures::TryStreamExt::try_next(&mut stream).await? {
+        let paths = crate::arrow_utils::string_column(&batch, "doc_path")?;
+        for i in 0..batch.num_rows() {
+            let p = paths.value(i);
+            if !out.iter().any(|x| x == p) { out.push(p.to_string()); }
+        }
+    }
+    Ok(SeededRng::new(seed).sample(&out, n))
+}
+
+/// Stored chunks of `collection` whose `doc_path` starts with `prefix` (all
+/// chunks for `""`). Used to keep an offline root's documents in a rebuilt
+/// text index while its media is unplugged.
+pub async fn stored_chunks(conn: &Connection, collection: &str, prefix: &str) -> Result<Vec<DocumentChunk>> {
+    let filter = (!prefix.is_empty()).then(|| format!("starts_with(doc_path, '{}')", prefix.replace('\'', "''")));
+    query_chunks(conn, collection, filter).await
+}
+
+/// Stored chunks of one document in `chunk_index` order.
+pub async fn document_chunks(conn: &Connection, collection: &str, doc_id: &str) -> Result<Vec<DocumentChunk>> {
+    let mut chunks = query_chunks(conn, collection, Some(format!("doc_id = '{}'", doc_id.replace('\'', "''")))).await?;
+    chunks.sort_by_key(|c| c.chunk_index);
+    Ok(chunks)
+}
+
+/// Stored chunks with the given ids (in table order; unknown ids are skipped).
+pub async fn chunks_by_id(conn: &Connection, collection: &str, ids: &[String]) -> Result<Vec<DocumentChunk>> {
+    let mut out = Vec::new();
+    for chunk in ids.chunks(DELETE_BATCH) { out.extend(query_chunks(conn, collection, Some(format!("id IN ({})", sql_list(chunk)))).await?); }
+    Ok(out)
+}
+
+/// Call `each` on every stored chunk of `collection`, one Arrow batch in
+/// memory at a time (`localdb-cli stats --corpus`).
+pub async fn for_each_chunk(conn: &Connection, collection: &str, each: impl FnMut(DocumentChunk)) -> Result<()> {
+    scan_chunks(conn, collection, None, each).await
+}
+
+async fn query_chunks(conn: &Connection, collection: &str, filter: Option<String>) -> Result<Vec<DocumentChunk>> {
+    let mut out = Vec::new();
+    scan_chunks(conn, collection, filter, |c| out.push(c)).await?;
+    Ok(out)
+}
+
+async fn scan_chunks(conn: &Connection, collection: &str, filter: Option<String>, mut each: impl FnMut(DocumentChunk)) -> Result<()> {
+    let names = conn.table_names().execute().await?;
+    if !names.contains(&collection.to_string()) { return Ok(()); }
+    let t = conn.open_table(collection).execute().await?;
+    // Tables from before document metadata have none of its columns.
+    let schema = t.schema().await?;
+    let mut columns = vec!["id", "doc_id", "doc_path", "category", "category_text", "content", "chunk_index", "total_chunks"];
+    columns.extend(["title", "author", "created_at", "meta", "lang"].into_iter().chain(crate::arrow_utils::SPAN_COLUMNS).filter(|c| schema.field_with_name(c).is_ok()));
+    let mut q = t.query().select(Select::columns(&columns));
+    if let Some(f) = filter { q = q.only_if(f); }
+    let mut stream = q.execute().await?;
+    while let Some(batch) = futures::TryStreamExt::try_next(&mut stream).await? {
+        let s = |name| crate::arrow_utils::string_column(&batch, name);
+        let (ids, doc_ids, paths, cats, cat_texts, contents) = (s("id")?, s("doc_id")?, s("doc_path")?, s("category")?, s("category_text")?, s("content")?);
+        let idx = crate::arrow_utils::column::<arrow_array::Int32Array>(&batch, "chunk_index", "Int32")?;
+        let totals = crate::arrow_utils::column::<arrow_array::Int32Array>(&batch, "total_chunks", "Int32")?;
+        let about = crate::arrow_utils::DocColumns::of(&batch)?;
+        for i in 0..batch.num_rows() {
+            each(DocumentChunk {
+                id: ids.value(i).to_string(), doc_id: doc_ids.value(i).to_string(), doc_path: paths.value(i).to_string(),
+                category: cats.value(i).to_string(), category_text: cat_texts.value(i).to_string(), content: contents.value(i).to_string(),
+                chunk_index: idx.value(i) as usize, total_chunks: totals.value(i) as usize,
+                title: about.title(i), author: about.author(i), created_at: about.created_at(i), meta: about.meta(i), lang: about.lang(i), span: about.span(i),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Values per `IN (...)` list in batched deletes.
+pub(crate) const DELETE_BATCH: usize = 256;
+
+/// `'a', 'b', ...` with single quotes escaped, for SQL `IN` predicates.
+pub(crate) fn sql_list(items: &[String]) -> String {
+    items.iter().map(|s| format!("'{}'", s.replace('\'', "''"))).collect::<Vec<_>>().join(", ")
+}
+
+/// Filter for the chunks of the files at `doc_paths`, including fragments of
+/// them (`<file>#…`: ZIM articles, archive members, transcript cues, records).
+pub fn doc_paths_filter(doc_paths: &[String]) -> String {
+    doc_paths.iter().fold(format!("doc_path IN ({})", sql_list(doc_paths)), |filter, p| format!("{} OR starts_with(doc_path, '{}#')", filter, p.replace('\'', "''")))
+}
+
+/// Filter for the chunks filed under `facet` or its subfacets, or under any
+/// of its old names in `aliases`; `None` for the root facet. Stored
+/// categories may lack the leading `/`.
+pub fn facet_filter(facet: &str, aliases: &FacetAliases) -> Option<String> {
+    if localdb_core::facets::normalize(facet) == "/" { return None; }
+    let clauses: Vec<String> = aliases.sources(facet).iter().flat_map(|s| {
+        let bare = s.trim_start_matches('/').replace('\'', "''");
+        [format!("category IN ('/{0}', '{0}')", bare), format!("starts_with(category, '/{}/')", bare), format!("starts_with(category, '{}/')", bare)]
+    }).collect();
+    Some(clauses.join(" OR "))
+}
+
+/// Delete every chunk of the documents at `doc_paths`, fragments of them
+/// (`<file>#…`) included, from `docs_table` and their rows in the `emb_table` and token vector side tables. Returns the
+/// removed chunk ids.
+pub async fn delete_documents(conn: &Connection, docs_table: &str, emb_table: &str, doc_paths: &[String]) -> Result<Vec<String>> {
+    let names = conn.table_names().execute().await?;
+    if !names.contains(&docs_table.to_string()) || doc_paths.is_empty() { return Ok(Vec::new()); }
+    let docs = conn.open_table(docs_table).execute().await?;
+    let mut ids = Vec::new();
+    for chunk in doc_paths.chunks(DELETE_BATCH) {
+        let predicate = doc_paths_filter(chunk);
+        let mut stream = docs.query().only_if(predicate.as_str()).select(Select::columns(&["id"])).execute().await?;
+        while let Some(batch) = futures::TryStreamExt::try_next(&mut stream).await? {
+            let col = crate::arrow_utils::string_column(&batch, "id")?;
+            ids.extend((0..batch.num_rows()).map(|i| col.value(i).to_string()));
+        }
+        docs.delete(&predicate).await?;
+    }
+    if names.contains(&emb_table.to_string()) {
+        let emb = conn.open_table(emb_table).execute().await?;
+        for chunk in ids.chunks(DELETE_BATCH) { emb.delete(&format!("id IN ({})", sql_list(chunk))).await?; }
+    }
+    crate::tokens::delete_token_vectors(conn, docs_table, &ids).await?;
+    Ok(ids)
+}
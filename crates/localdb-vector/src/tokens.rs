@@ -0,0 +1,107 @@
+//! Token-level (ColBERT) vectors in a side table.
+//!
+//! In multi-vector mode each chunk also stores one vector per token in
+//! `<collection>_tokens`, one row per token (`id`, `position`, `vector`).
+//! Writing a chunk replaces its rows; `table::delete_documents` removes them
+//! with the document; `table::drop_collection` (`ingest --full`) drops the
+//! table. Queries read them back by chunk id to rescore dense candidates by
+//! MaxSim (see `localdb_hybrid`), through a BTree index on `id` that
+//! `index_token_ids` builds and extends after each ingest (rows written since
+//! are scanned). A 1024-dim token vector is
+//! 4 KB, so a full 256-token chunk costs about 1 MB: the table is far larger
+//! than `documents`, which is why the mode is opt-in.
+
+use anyhow::{Result, anyhow};
+use arrow_array::{FixedSizeListArray, Int32Array, RecordBatch, RecordBatchIterator, StringArray};
+use lancedb::Connection;
+use lancedb::index::{Index, scalar::BTreeIndexBuilder};
+use lancedb::table::{OptimizeAction, OptimizeOptions};
+use lancedb::query::{ExecutableQuery, QueryBase, Select};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use localdb_core::types::MultiVector;
+
+use crate::arrow_utils::{column, string_column, vector_column, vector_value};
+use crate::schema::build_token_vectors_schema;
+use crate::table::{ensure_table, sql_list, table_vector_dim, DELETE_BATCH};
+
+/// Side table holding the token vectors of `collection`'s chunks.
+pub fn token_table(collection: &str) -> String { format!("{}_tokens", collection) }
+
+/// Store the token vectors of chunks `ids` (same length), replacing their
+/// previous rows. Chunks with no token vectors are only cleared.
+pub async fn write_token_vectors(conn: &Connection, collection: &str, ids: &[String], vectors: &[MultiVector]) -> Result<()> {
+    assert_eq!(ids.len(), vectors.len(), "ids and token vectors length must match");
+    let Some(dim) = vectors.iter().flatten().next().map(|v| v.len() as i32) else { return delete_token_vectors(conn, collection, ids).await };
+    let table = token_table(collection);
+    match table_vector_dim(conn, &table).await? {
+        Some(stored) if stored != dim => return Err(anyhow!("'{}' holds {}-dim token vectors, got {}; ingest with --full after changing the model", table, stored, dim)),
+        _ => ensure_table(conn, &table, build_token_vectors_schema(dim)).await?,
+    }
+    delete_token_vectors(conn, collection, ids).await?;
+    let (mut row_ids, mut positions, mut rows): (Vec<String>, Vec<i32>, Vec<Option<Vec<Option<f32>>>>) = (Vec::new(), Vec::new(), Vec::new());
+    for (id, tokens) in ids.iter().zip(vectors) {
+        for (position, v) in tokens.iter().enumerate() {
+            if v.len() != dim as usize { return Err(anyhow!("token vector {} of chunk {} is {}-dim, expected {}", position, id, v.len(), dim)); }
+            row_ids.push(id.clone());
+            positions.push(position as i32);
+            rows.push(Some(v.iter().map(|&x| Some(x)).collect()));
+        }
+    }
+    let schema = build_token_vectors_schema(dim);
+    let batch = RecordBatch::try_new(schema.clone(), vec![
+        Arc::new(StringArray::from(row_ids)),
+        Arc::new(Int32Array::from(positions)),
+        Arc::new(FixedSizeListArray::from_iter_primitive::<arrow_array::types::Float32Type, _, _>(rows.into_iter(), dim)),
+    ])?;
+    let reader = Box::new(RecordBatchIterator::new(vec![Ok(batch)].into_iter(), schema));
+    conn.open_table(&table).execute().await?.add(reader).execute().await?;
+    Ok(())
+}
+
+/// Stored token vectors of chunks `ids`, in token order (chunks without any are left out).
+pub async fn read_token_vectors(conn: &Connection, collection: &str, ids: &[String]) -> Result<HashMap<String, MultiVector>> {
+    let table = token_table(collection);
+    let mut rows: HashMap<String, Vec<(i32, Vec<f32>)>> = HashMap::new();
+    if ids.is_empty() || !conn.table_names().execute().await?.contains(&table) { return Ok(HashMap::new()); }
+    let t = conn.open_table(&table).execute().await?;
+    for chunk in ids.chunks(DELETE_BATCH) {
+        let mut stream = t.query().select(Select::columns(&["id", "position", "vector"])).only_if(format!("id IN ({})", sql_list(chunk))).execute().await?;
+        while let Some(batch) = futures::TryStreamExt::try_next(&mut stream).await? {
+            let (ids, vecs) = (string_column(&batch, "id")?, vector_column(&batch, "vector")?);
+            let positions = column::<Int32Array>(&batch, "position", "Int32")?;
+            for i in 0..batch.num_rows() {
+                if let Some(v) = vector_value(vecs, i, ids.value(i))? { rows.entry(ids.value(i).to_string()).or_default().push((positions.value(i), v)); }
+            }
+        }
+    }
+    Ok(rows.into_iter().map(|(id, mut tokens)| {
+        tokens.sort_by_key(|(p, _)| *p);
+        (id, tokens.into_iter().map(|(_, v)| v).collect())
+    }).collect())
+}
+
+/// Index the `id` column of `collection`'s token table, or add the rows
+/// written since to the index, so `read_token_vectors` does not scan the
+/// whole table. Nothing to do without the table.
+pub async fn index_token_ids(conn: &Connection, collection: &str) -> Result<()> {
+    let table = token_table(collection);
+    if !conn.table_names().execute().await?.contains(&table) { return Ok(()); }
+    let t = conn.open_table(&table).execute().await?;
+    if t.list_indices().await?.iter().any(|i| i.columns == ["id"]) {
+        t.optimize(OptimizeAction::Index(OptimizeOptions::default())).await?;
+    } else {
+        t.create_index(&["id"], Index::BTree(BTreeIndexBuilder::default())).execute().await?;
+    }
+    Ok(())
+}
+
+/// Remove the token vectors of chunks `ids`.
+pub async fn delete_token_vectors(conn: &Connection, collection: &str, ids: &[String]) -> Result<()> {
+    let table = token_table(collection);
+    if ids.is_empty() || !conn.table_names().execute().await?.contains(&table) { return Ok(()); }
+    let t = conn.open_table(&table).execute().await?;
+    for chunk in ids.chunks(DELETE_BATCH) { t.delete(&format!("id IN ({})", sql_list(chunk))).await?; }
+    Ok(())
+}
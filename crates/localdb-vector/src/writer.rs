@@ -1,13 +1,35 @@
+//! Write `DocumentChunk`s into the Lance `documents` table.
+//!
+//! This helper converts chunks to Arrow record batches, computes `content_hash`
+//! and initializes embedding/index status fields. The serving vector column is
+//! optional and typically left null during backfill. Batches are upserted by
+//! chunk id, so rerunning an interrupted write does not duplicate rows. Each
+//! row also stores its document's metadata (`title`, `author`, `created_at`,
+//! `meta`), its language (`lang`) and its location in the source file
+//! (`start_offset`/`end_offset`, `start_line`/`end_line`); tables written
+//! before those columns get them added (null) on the next write.
+
 use anyhow::{Result, anyhow};
 use indicatif::{ProgressBar, ProgressStyle};
 use lancedb::{connect, Connection};
-use arrow_array::{RecordBatch, RecordBatchIterator, Int32Array, FixedSizeListArray, StringArray};
+use lancedb::database::CreateTableMode;
+use lancedb::table::NewColumnTransform;
+use arrow_array::{RecordBatch, RecordBatchIterator, Int32Array, Int64Array, FixedSizeListArray, StringArray};
 use arrow_array::TimestampMillisecondArray;
 use std::sync::Arc;
 use std::path::Path;
 
-use localdb_core::types::DocumentChunk;
+use localdb_core::facets::FacetAliases;
+use localdb_core::fault;
+use localdb_core::folder_meta::encode_meta;
+use localdb_core::profile::{self, Stage};
+use localdb_core::progress;
+use localdb_core::roots::RootMap;
+use localdb_core::types::{DocumentChunk, Meta, SourceSpan};
+use crate::index_build::{index_info, IndexInfo};
+use crate::latency::LatencyBudget;
 use crate::schema::{build_arrow_schema, EMBEDDING_DIM};
+use crate::table::{collection_dim, set_collection_dim, set_data_roots};
 use blake3;
 use chrono::Utc;
 
@@ -22,63 +44,164 @@ pub struct LanceDocument {
 	pub chunk_index: usize,
 	pub total_chunks: usize,
 	pub vector: Vec<f32>,
+	pub title: Option<String>,
+	pub author: Option<String>,
+	pub created_at: Option<i64>,
+	pub meta: Meta,
+	pub lang: Option<String>,
+	pub span: Option<SourceSpan>,
+}
+
+impl LanceDocument {
+    /// The row for `chunk` with `vector` (empty for none yet).
+    pub fn from_chunk(chunk: &DocumentChunk, vector: Vec<f32>) -> Self {
+        Self {
+            id: chunk.id.clone(), doc_id: chunk.doc_id.clone(), doc_path: chunk.doc_path.clone(), category: chunk.category.clone(),
+            category_text: chunk.category_text.clone(), content: chunk.content.clone(), chunk_index: chunk.chunk_index, total_chunks: chunk.total_chunks,
+            vector, title: chunk.title.clone(), author: chunk.author.clone(), created_at: chunk.created_at, meta: chunk.meta.clone(), lang: chunk.lang.clone(), span: chunk.span,
+        }
+    }
 }
 
-pub struct LanceDbIndexer { pub(crate) db: Connection, pub(crate) table_name: String }
+/// Nullable `documents` columns added after the table's first layout, with
+/// the SQL that fills them in older tables.
+const DOCUMENT_COLUMNS: &[(&str, &str)] = &[
+    ("title", "CAST(NULL AS STRING)"),
+    ("author", "CAST(NULL AS STRING)"),
+    ("created_at", "arrow_cast(NULL, 'Timestamp(Millisecond, None)')"),
+    ("meta", "CAST(NULL AS STRING)"),
+    ("lang", "CAST(NULL AS STRING)"),
+    ("start_offset", "CAST(NULL AS BIGINT)"),
+    ("end_offset", "CAST(NULL AS BIGINT)"),
+    ("start_line", "CAST(NULL AS BIGINT)"),
+    ("end_line", "CAST(NULL AS BIGINT)"),
+];
+
+pub struct LanceDbIndexer { pub(crate) db: Connection, pub(crate) table_name: String, pub(crate) latency_budget: Option<LatencyBudget>, pub(crate) data_roots: Option<RootMap>, pub(crate) facet_aliases: FacetAliases }
 
 impl LanceDbIndexer {
     /// Open (or create if needed) a LanceDB connection and prepare an indexer
     /// for the specified table name.
     pub async fn new(db_path: &Path, table_name: &str) -> Result<Self> {
 		let db = connect(db_path.to_string_lossy().as_ref()).execute().await?;
-		Ok(Self { db, table_name: table_name.to_string() })
+		Ok(Self { db, table_name: table_name.to_string(), latency_budget: None, data_roots: None, facet_aliases: FacetAliases::default() })
 	}
 
+    /// Use adaptive `nprobes` under `budget` for `search_vec`.
+    pub fn with_latency_budget(mut self, budget: LatencyBudget) -> Self { self.latency_budget = Some(budget); self }
+
+    /// Record `root` (in `meta`) as the data root that chunk `doc_path`s are relative to.
+    pub fn with_data_root(mut self, root: &Path) -> Self {
+        self.data_roots = Some(RootMap::single(std::fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf())));
+        self
+    }
+
+    /// Record the locations of several named data roots (multi-root ingest).
+    pub fn with_data_roots(mut self, roots: RootMap) -> Self { self.data_roots = Some(roots); self }
+
+    /// Let facet filters (`search_vec_in_facet`) also match chunks still
+    /// stored under a facet's old names.
+    pub fn with_facet_aliases(mut self, aliases: FacetAliases) -> Self { self.facet_aliases = aliases; self }
+
+    /// Vector index state of this collection (see `index_build::index_info`).
+    pub async fn index_info(&self) -> Result<IndexInfo> { index_info(&self.db, &self.table_name).await }
+
     /// Insert or append `chunks` into the `documents` table alongside their
     /// embedding vectors. The length of `chunks` and `embeddings` must match.
     pub async fn index(&self, chunks: &[DocumentChunk], embeddings: &[Vec<f32>]) -> Result<()> {
 		if chunks.is_empty() { println!("No chunks to index"); return Ok(()); }
 		assert_eq!(chunks.len(), embeddings.len(), "chunks and embeddings length must match");
-		println!("Indexing {} chunks into LanceDB table: {}", chunks.len(), self.table_name);
+		let dim = self.resolve_dim(embeddings).await?;
+		if let Some(roots) = &self.data_roots { set_data_roots(&self.db, &self.table_name, roots).await?; }
+		println!("Indexing {} chunks into LanceDB table: {} (dim={})", chunks.len(), self.table_name, dim);
 		let pb = ProgressBar::new(chunks.len() as u64);
-		pb.set_style(ProgressStyle::default_bar().template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} chunks ({percent}%) {msg}").unwrap().progress_chars("#>-") );
+		pb.set_style(ProgressStyle::default_bar().template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} chunks ({percent}%) {msg}")?.progress_chars("#>-") );
 		let mut processed = 0usize; let mut batch_docs = Vec::new(); let batch_size = 1000usize;
         for (i, (chunk, embedding)) in chunks.iter().zip(embeddings.iter()).enumerate() {
-            if embedding.len() != EMBEDDING_DIM as usize {
+            if !embedding.is_empty() && embedding.len() != dim as usize {
                 return Err(anyhow!(
                     "Embedding dim mismatch for chunk {} at index {}: got {}, expected {}",
-                    chunk.id, i, embedding.len(), EMBEDDING_DIM
+                    chunk.id, i, embedding.len(), dim
                 ));
             }
-            let doc = LanceDocument { id: chunk.id.clone(), doc_id: chunk.doc_id.clone(), doc_path: chunk.doc_path.clone(), category: chunk.category.clone(), category_text: chunk.category_text.clone(), content: chunk.content.clone(), chunk_index: chunk.chunk_index, total_chunks: chunk.total_chunks, vector: embedding.clone() };
-            batch_docs.push(doc); processed += 1; pb.set_position(processed as u64); pb.set_message(format!("Processing chunk {}", i + 1));
-            if batch_docs.len() >= batch_size || i == chunks.len() - 1 { self.insert_batch(&batch_docs).await?; batch_docs.clear(); if processed % 1000 == 0 { println!("\n📦 Processed batch of 1000 chunks..."); } }
+            let doc = LanceDocument::from_chunk(chunk, embedding.clone());
+            batch_docs.push(doc); processed += 1; pb.set_position(processed as u64); progress::report("lance write", processed as u64, Some(chunks.len() as u64)); pb.set_message(format!("Processing chunk {}", i + 1));
+            if batch_docs.len() >= batch_size || i == chunks.len() - 1 { self.insert_batch(&batch_docs, dim).await?; batch_docs.clear(); if processed % 1000 == 0 { println!("\n📦 Processed batch of 1000 chunks..."); } }
         }
 		pb.finish_with_message("✅ LanceDB indexing completed!");
 		println!("📊 Successfully indexed {} chunks into LanceDB", processed);
 		Ok(())
 	}
 
+    /// Replace the whole table with `chunks` and record the width of their
+    /// `embeddings` as the collection's dim, whatever was stored before. For
+    /// small internal collections rebuilt on every ingest (the canary).
+    pub async fn overwrite(&self, chunks: &[DocumentChunk], embeddings: &[Vec<f32>]) -> Result<()> {
+		assert_eq!(chunks.len(), embeddings.len(), "chunks and embeddings length must match");
+		let dim = embeddings.first().map_or(EMBEDDING_DIM, |e| e.len() as i32);
+		if let Some((chunk, e)) = chunks.iter().zip(embeddings).find(|(_, e)| e.len() != dim as usize) {
+			return Err(anyhow!("Embedding dim mismatch for chunk {}: got {}, expected {}", chunk.id, e.len(), dim));
+		}
+		let docs: Vec<LanceDocument> = chunks.iter().zip(embeddings).map(|(c, e)| LanceDocument::from_chunk(c, e.clone())).collect();
+		let record_batch = self.docs_to_record_batch(&docs, dim)?; let schema = record_batch.schema();
+		let reader = Box::new(RecordBatchIterator::new(vec![Ok(record_batch)].into_iter(), schema));
+		self.db.create_table(&self.table_name, reader).mode(CreateTableMode::Overwrite).execute().await?;
+		set_collection_dim(&self.db, &self.table_name, dim).await
+	}
+
     // Note: embedding should be handled by the façade/CLI. This crate only writes provided vectors.
 
-	async fn insert_batch(&self, docs: &[LanceDocument]) -> Result<()> {
+    /// Pick the vector width for this write: the collection's recorded dim if
+    /// known (provided embeddings must match), else the provided embeddings'
+    /// width, else `EMBEDDING_DIM`. New collections get their dim recorded in meta.
+    async fn resolve_dim(&self, embeddings: &[Vec<f32>]) -> Result<i32> {
+        let provided = embeddings.iter().find(|e| !e.is_empty()).map(|e| e.len() as i32);
+        let stored = collection_dim(&self.db, &self.table_name).await?;
+        let dim = match (stored, provided) {
+            (Some(s), Some(p)) if s != p => return Err(anyhow!(
+                "collection '{}' stores {}-dim vectors but embeddings are {}-dim", self.table_name, s, p
+            )),
+            (Some(s), _) => s,
+            (None, Some(p)) => p,
+            (None, None) => EMBEDDING_DIM,
+        };
+        if stored.is_none() { set_collection_dim(&self.db, &self.table_name, dim).await?; }
+        Ok(dim)
+    }
+
+	async fn insert_batch(&self, docs: &[LanceDocument], dim: i32) -> Result<()> {
 		if docs.is_empty() { return Ok(()); }
-		let record_batch = self.docs_to_record_batch(docs)?; let schema = record_batch.schema();
+		let _write = profile::timer(Stage::LanceWrite);
+		let record_batch = self.docs_to_record_batch(docs, dim)?; let schema = record_batch.schema();
 		let reader = Box::new(RecordBatchIterator::new(vec![Ok(record_batch)].into_iter(), schema));
 		if self.db.table_names().execute().await?.contains(&self.table_name) {
-			self.db.open_table(&self.table_name).execute().await?.add(reader).execute().await?;
+			// Chunk ids are content-based: an existing id is the same chunk written
+			// again, and keeps its vector and embedding state unless this write
+			// brings a vector or the content changed.
+			let table = self.db.open_table(&self.table_name).execute().await?;
+			let existing = table.schema().await?;
+			let missing: Vec<(String, String)> = DOCUMENT_COLUMNS.iter()
+				.filter(|(c, _)| existing.field_with_name(c).is_err())
+				.map(|(c, sql)| (c.to_string(), sql.to_string())).collect();
+			if !missing.is_empty() { table.add_columns(NewColumnTransform::SqlExpressions(missing), None).await?; }
+			let mut mi = table.merge_insert(&["id"]);
+			mi.when_matched_update_all(Some("source.vector IS NOT NULL OR target.content_hash IS DISTINCT FROM source.content_hash".to_string())).when_not_matched_insert_all();
+			mi.execute(reader).await?;
 		} else {
 			self.db.create_table(&self.table_name, reader).execute().await?;
 		}
+		fault::check("lance.batch_written")?;
 		Ok(())
 	}
 
     /// Convert internal `LanceDocument` entries into a `RecordBatch` using the
     /// `documents` schema.
-    fn docs_to_record_batch(&self, docs: &[LanceDocument]) -> Result<RecordBatch> {
-        let schema = build_arrow_schema();
+    fn docs_to_record_batch(&self, docs: &[LanceDocument], dim: i32) -> Result<RecordBatch> {
+        let schema = build_arrow_schema(dim);
         let mut ids = Vec::new(); let mut doc_ids = Vec::new(); let mut doc_paths = Vec::new(); let mut categories = Vec::new(); let mut category_texts = Vec::new(); let mut contents = Vec::new(); let mut chunk_indices = Vec::new(); let mut total_chunks = Vec::new(); let mut vectors: Vec<Option<Vec<Option<f32>>>> = Vec::new();
         let mut content_hashes = Vec::new(); let mut emb_status = Vec::new(); let mut emb_error: Vec<Option<String>> = Vec::new(); let mut emb_version = Vec::new(); let mut embedded_at: Vec<Option<i64>> = Vec::new(); let mut index_status = Vec::new(); let mut index_version = Vec::new();
+        let (mut titles, mut authors, mut created, mut metas, mut langs) = (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        let mut span_columns: [Vec<Option<i64>>; 4] = Default::default();
         let now = Utc::now().timestamp_millis();
         for doc in docs {
             ids.push(doc.id.clone());
@@ -89,6 +212,13 @@ impl LanceDbIndexer {
             contents.push(doc.content.clone());
             chunk_indices.push(doc.chunk_index as i32);
             total_chunks.push(doc.total_chunks as i32);
+            titles.push(doc.title.clone());
+            authors.push(doc.author.clone());
+            created.push(doc.created_at);
+            metas.push((!doc.meta.is_empty()).then(|| encode_meta(&doc.meta)));
+            langs.push(doc.lang.clone());
+            let span = doc.span.map(|s| [s.start_offset, s.end_offset, s.start_line, s.end_line]);
+            for (i, column) in span_columns.iter_mut().enumerate() { column.push(span.map(|v| v[i] as i64)); }
             let chash = blake3::hash(doc.content.as_bytes()).to_hex().to_string();
             content_hashes.push(chash);
             if doc.vector.is_empty() {
@@ -109,7 +239,7 @@ impl LanceDbIndexer {
                 index_version.push(0);
             }
         }
-        let record_batch = RecordBatch::try_new(schema, vec![
+        let mut columns: Vec<arrow_array::ArrayRef> = vec![
             Arc::new(StringArray::from(ids)),
             Arc::new(StringArray::from(doc_ids)),
             Arc::new(StringArray::from(doc_paths)),
@@ -118,7 +248,7 @@ impl LanceDbIndexer {
             Arc::new(StringArray::from(contents)),
             Arc::new(Int32Array::from(chunk_indices)),
             Arc::new(Int32Array::from(total_chunks)),
-            Arc::new(FixedSizeListArray::from_iter_primitive::<arrow_array::types::Float32Type, _, _>(vectors.into_iter(), EMBEDDING_DIM)),
+            Arc::new(FixedSizeListArray::from_iter_primitive::<arrow_array::types::Float32Type, _, _>(vectors.into_iter(), dim)),
             Arc::new(StringArray::from(content_hashes)),
             Arc::new(StringArray::from(emb_status)),
             Arc::new({
@@ -127,14 +257,16 @@ impl LanceDbIndexer {
             }),
             Arc::new(Int32Array::from(emb_version)),
             Arc::new(TimestampMillisecondArray::from(embedded_at)),
-//! Write `DocumentChunk`s into the Lance `documents` table.
-//!
-//! This helper converts chunks to Arrow record batches, computes `content_hash`
-//! and initializes embedding/index status fields. The serving vector column is
-//! optional and typically left null during backfill.
             Arc::new(StringArray::from(index_status)),
             Arc::new(Int32Array::from(index_version)),
-        ])?;
+            Arc::new(StringArray::from(titles)),
+            Arc::new(StringArray::from(authors)),
+            Arc::new(TimestampMillisecondArray::from(created)),
+            Arc::new(StringArray::from(metas)),
+            Arc::new(StringArray::from(langs)),
+        ];
+        columns.extend(span_columns.map(|c| Arc::new(Int64Array::from(c)) as arrow_array::ArrayRef));
+        let record_batch = RecordBatch::try_new(schema, columns)?;
         Ok(record_batch)
     }
 }
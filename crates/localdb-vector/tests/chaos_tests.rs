@@ -0,0 +1,119 @@
+//! Crash-recovery tests: kill the ingest write, the embedding backfill and the
+//! serving-vector sync at
+//! each `localdb_core::fault` point (at a seeded random step, `APP_SEED`), rerun, and
+//! check the tables converge to the same state as an uninterrupted run.
+
+use std::collections::BTreeSet;
+
+use lancedb::Connection;
+use localdb_core::fault;
+use localdb_core::preprocess::Preprocessor;
+use localdb_core::seed::SeededRng;
+use localdb_core::types::DocumentChunk;
+use localdb_vector::embed_provider::local::LocalProvider;
+use localdb_vector::embed_provider::EmbedProvider;
+use localdb_vector::embed_backfill::backfill_embeddings;
+use localdb_vector::gc::column_values;
+use localdb_vector::index_build::{sync_serving_vectors_in_batches, vector_coverage};
+use localdb_vector::LanceDbIndexer;
+
+const BACKFILL_POINTS: &[&str] = &["backfill.in_progress", "backfill.cache_written", "backfill.embeddings_written", "backfill.ready"];
+
+fn chunks(n: usize) -> Vec<DocumentChunk> {
+    (0..n).map(|i| DocumentChunk {
+        id: format!("doc{}:{:04}", i / 4, i),
+        doc_id: format!("doc{}", i / 4),
+        doc_path: format!("doc{}.txt", i / 4),
+        category: "/chaos".to_string(),
+        category_text: "/chaos".to_string(),
+        content: format!("chunk {} of the chaos corpus", i),
+        chunk_index: i % 4,
+        total_chunks: 4,
+        title: None,
+        author: None,
+        created_at: None,
+        lang: None,
+        meta: Default::default(),
+        span: None,
+    }).collect()
+}
+
+async fn seeded(dir: &std::path::Path, chunks: &[DocumentChunk]) -> anyhow::Result<Connection> {
+    let empty: Vec<Vec<f32>> = vec![Vec::new(); chunks.len()];
+    LanceDbIndexer::new(dir, "documents").await?.index(chunks, &empty).await?;
+    localdb_vector::table::open_db(&dir.to_string_lossy()).await
+}
+
+/// Every chunk is `ready` with exactly one `embeddings` row.
+async fn assert_consistent(conn: &Connection, chunks: &[DocumentChunk], context: &str) -> anyhow::Result<()> {
+    let statuses = column_values(conn, "documents", "embedding_status").await?;
+    assert!(statuses.iter().all(|s| s == "ready"), "{}: statuses {:?}", context, statuses.iter().collect::<BTreeSet<_>>());
+    let mut emb_ids = column_values(conn, "embeddings", "id").await?;
+    emb_ids.sort();
+    let want: Vec<String> = chunks.iter().map(|c| c.id.clone()).collect::<BTreeSet<_>>().into_iter().collect();
+    assert_eq!(emb_ids, want, "{}: one embeddings row per chunk", context);
+    Ok(())
+}
+
+#[tokio::test]
+async fn backfill_recovers_from_a_crash_at_every_point() -> anyhow::Result<()> {
+    std::env::set_var("APP_USE_FAKE_EMBEDDINGS", "1");
+    let provider = LocalProvider::new()?;
+    let chunks = chunks(40);
+    let batch = 8; // 5 batches, so every step below is reached
+    let mut rng = SeededRng::new(localdb_core::seed::from_env());
+    for point in BACKFILL_POINTS {
+        for _ in 0..2 {
+            let nth = rng.below(5) + 1;
+            let tmp = tempfile::tempdir()?;
+            let conn = seeded(tmp.path(), &chunks).await?;
+            fault::arm(point, nth);
+            let crashed = backfill_embeddings(&conn, "documents", "embeddings", "emb_cache", &provider, &Preprocessor::default(), batch, None).await;
+            fault::disarm();
+            assert!(crashed.is_err(), "{} #{} should have fired", point, nth);
+            backfill_embeddings(&conn, "documents", "embeddings", "emb_cache", &provider, &Preprocessor::default(), batch, None).await?;
+            assert_consistent(&conn, &chunks, &format!("{} #{}", point, nth)).await?;
+            assert_eq!(backfill_embeddings(&conn, "documents", "embeddings", "emb_cache", &provider, &Preprocessor::default(), batch, None).await?, 0, "nothing left to do");
+        }
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn ingest_write_recovers_from_a_crash_between_batches() -> anyhow::Result<()> {
+    let chunks = chunks(2_100); // writer batches are 1000 chunks
+    let empty: Vec<Vec<f32>> = vec![Vec::new(); chunks.len()];
+    for nth in [1, 2] {
+        let tmp = tempfile::tempdir()?;
+        let indexer = LanceDbIndexer::new(tmp.path(), "documents").await?;
+        fault::arm("lance.batch_written", nth);
+        assert!(indexer.index(&chunks, &empty).await.is_err());
+        fault::disarm();
+        indexer.index(&chunks, &empty).await?;
+        let conn = localdb_vector::table::open_db(&tmp.path().to_string_lossy()).await?;
+        let ids = column_values(&conn, "documents", "id").await?;
+        assert_eq!(ids.len(), chunks.len(), "rerun after crash in batch {} must not duplicate rows", nth);
+        assert_eq!(ids.iter().collect::<BTreeSet<_>>().len(), chunks.len());
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn serving_vector_sync_keeps_the_merges_before_a_crash() -> anyhow::Result<()> {
+    std::env::set_var("APP_USE_FAKE_EMBEDDINGS", "1");
+    let provider = LocalProvider::new()?;
+    let chunks = chunks(40);
+    let tmp = tempfile::tempdir()?;
+    let conn = seeded(tmp.path(), &chunks).await?;
+    backfill_embeddings(&conn, "documents", "embeddings", "emb_cache", &provider, &Preprocessor::default(), 8, None).await?;
+    fault::arm("sync.batch_merged", 2);
+    let crashed = sync_serving_vectors_in_batches(&conn, "documents", "embeddings", provider.embedder_id(), 16).await;
+    fault::disarm();
+    assert!(crashed.is_err());
+    let partial = vector_coverage(&conn, "documents", "embeddings", Some(provider.embedder_id())).await?;
+    assert_eq!((partial.null, partial.unsynced), (8, 8), "the two merges before the crash stay applied");
+    assert_eq!(sync_serving_vectors_in_batches(&conn, "documents", "embeddings", provider.embedder_id(), 16).await?, chunks.len());
+    let synced = vector_coverage(&conn, "documents", "embeddings", Some(provider.embedder_id())).await?;
+    assert_eq!((synced.rows, synced.null, synced.stale), (chunks.len(), 0, 0));
+    Ok(())
+}
@@ -1,10 +1,11 @@
 use std::path::PathBuf;
 
-use localdb_core::types::DocumentChunk;
+use localdb_core::types::{DocumentChunk, SourceSpan};
+use localdb_core::preprocess::Preprocessor;
 use localdb_vector::embed_provider::EmbedProvider;
-use arrow_array::{RecordBatch, RecordBatchIterator, StringArray, Int32Array, FixedSizeListArray, TimestampMillisecondArray};
+use arrow_array::{RecordBatch, RecordBatchIterator, StringArray, Int32Array, Int64Array, FixedSizeListArray, TimestampMillisecondArray};
 use std::sync::Arc;
-use localdb_vector::schema::build_arrow_schema;
+use localdb_vector::schema::{build_arrow_schema, EMBEDDING_DIM};
 
 fn blake3_hash(s: &str) -> String { blake3::hash(s.as_bytes()).to_hex().to_string() }
 
@@ -30,10 +31,16 @@ async fn backfill_and_sync_in_memory_fast() -> anyhow::Result<()> {
             content: format!("hello world {}", i),
             chunk_index: i as usize,
             total_chunks: n,
+            title: None,
+            author: None,
+            created_at: None,
+            lang: None,
+            meta: Default::default(),
+            span: None,
         })
         .collect();
     let conn = localdb_vector::table::open_db(&db_uri).await?;
-    let schema = build_arrow_schema();
+    let schema = build_arrow_schema(EMBEDDING_DIM);
     let mut ids = Vec::new();
     let mut doc_ids = Vec::new();
     let mut doc_paths = Vec::new();
@@ -87,14 +94,21 @@ async fn backfill_and_sync_in_memory_fast() -> anyhow::Result<()> {
             Arc::new(TimestampMillisecondArray::from(embedded_at)),
             Arc::new(StringArray::from(index_status)),
             Arc::new(Int32Array::from(index_version)),
+            Arc::new(StringArray::from(vec![None::<&str>; n])),
+            Arc::new(StringArray::from(vec![None::<&str>; n])),
+            Arc::new(TimestampMillisecondArray::from(vec![None::<i64>; n])),
+            Arc::new(StringArray::from(vec![None::<&str>; n])),
+            Arc::new(StringArray::from(vec![None::<&str>; n])),
+            Arc::new(Int64Array::from(vec![None::<i64>; n])), Arc::new(Int64Array::from(vec![None::<i64>; n])),
+            Arc::new(Int64Array::from(vec![None::<i64>; n])), Arc::new(Int64Array::from(vec![None::<i64>; n])),
         ],
     )?;
     let reader = Box::new(RecordBatchIterator::new(vec![Ok(rb)].into_iter(), schema));
     conn.create_table(docs_table, reader).execute().await?;
 
     // 2) Backfill via local provider into embeddings + cache
-    localdb_vector::table::ensure_embeddings_table(&conn, emb_table).await?;
-    localdb_vector::table::ensure_cache_table(&conn, cache_table).await?;
+    localdb_vector::table::ensure_embeddings_table(&conn, emb_table, EMBEDDING_DIM).await?;
+    localdb_vector::table::ensure_cache_table(&conn, cache_table, EMBEDDING_DIM).await?;
     let provider = localdb_vector::embed_provider::local::LocalProvider::new()?;
     let processed = localdb_vector::embed_backfill::backfill_embeddings(
         &conn,
@@ -102,12 +116,18 @@ async fn backfill_and_sync_in_memory_fast() -> anyhow::Result<()> {
         emb_table,
         cache_table,
         &provider,
+        &Preprocessor::default(),
         16,
         None,
     )
     .await?;
     assert_eq!(processed, chunks.len());
 
+    // Backfilled but not yet serving: every row lags `embeddings`.
+    let lagging = localdb_vector::index_build::vector_coverage(&conn, docs_table, emb_table, Some(provider.embedder_id())).await?;
+    assert_eq!((lagging.rows, lagging.null, lagging.unsynced, lagging.stale), (n, n, n, 0));
+    assert!(lagging.warning(0.01).is_some_and(|w| w.contains("unsynced")));
+
     // 3) Sync serving vectors from embeddings
     let updated = localdb_vector::index_build::sync_serving_vectors_from_embeddings(
         &conn,
@@ -117,6 +137,15 @@ async fn backfill_and_sync_in_memory_fast() -> anyhow::Result<()> {
     )
     .await?;
     assert!(updated >= chunks.len());
+    let synced = localdb_vector::index_build::vector_coverage(&conn, docs_table, emb_table, Some(provider.embedder_id())).await?;
+    assert_eq!((synced.null, synced.unsynced, synced.stale), (0, 0, 0));
+    assert!(synced.warning(0.01).is_none());
+
+    // 4) No ANN index yet: every vector is brute-forced
+    let info = localdb_vector::index_build::index_info(&conn, docs_table).await?;
+    assert!(!info.uses_ann());
+    assert_eq!((info.rows, info.vectors, info.brute_force_rows()), (n, n, n));
+    assert!(info.fragments >= 1);
 
     Ok(())
 }
@@ -146,11 +175,17 @@ async fn backfill_and_build_index_in_memory_slow() -> anyhow::Result<()> {
             content: format!("hello world {}", i),
             chunk_index: i as usize,
             total_chunks: n,
+            title: None,
+            author: None,
+            created_at: None,
+            lang: None,
+            meta: Default::default(),
+            span: None,
         })
         .collect();
     let conn = localdb_vector::table::open_db(&db_uri).await?;
-    let schema = localdb_vector::schema::build_arrow_schema();
-    use arrow_array::{RecordBatch, RecordBatchIterator, StringArray, Int32Array, FixedSizeListArray, TimestampMillisecondArray};
+    let schema = localdb_vector::schema::build_arrow_schema(EMBEDDING_DIM);
+    use arrow_array::{RecordBatch, RecordBatchIterator, StringArray, Int32Array, Int64Array, FixedSizeListArray, TimestampMillisecondArray};
     use std::sync::Arc;
     let (mut ids, mut doc_ids, mut doc_paths, mut cats, mut cat_txts, mut contents, mut idxs, mut totals) = (Vec::new(),Vec::new(),Vec::new(),Vec::new(),Vec::new(),Vec::new(),Vec::new(),Vec::new());
     let (mut vectors, mut hashes, mut emb_status, mut emb_err, mut emb_ver, mut emb_at, mut idx_status, mut idx_ver) = (Vec::new(),Vec::new(),Vec::new(),Vec::new(),Vec::new(),Vec::new(),Vec::new(),Vec::new());
@@ -173,15 +208,20 @@ async fn backfill_and_build_index_in_memory_slow() -> anyhow::Result<()> {
             Arc::new(StringArray::from(hashes)), Arc::new(StringArray::from(emb_status)), Arc::new(StringArray::from(emb_err)),
             Arc::new(Int32Array::from(emb_ver)), Arc::new(TimestampMillisecondArray::from(emb_at)),
             Arc::new(StringArray::from(idx_status)), Arc::new(Int32Array::from(idx_ver)),
+            Arc::new(StringArray::from(vec![None::<&str>; n])), Arc::new(StringArray::from(vec![None::<&str>; n])),
+            Arc::new(TimestampMillisecondArray::from(vec![None::<i64>; n])), Arc::new(StringArray::from(vec![None::<&str>; n])),
+            Arc::new(StringArray::from(vec![None::<&str>; n])),
+            Arc::new(Int64Array::from(vec![None::<i64>; n])), Arc::new(Int64Array::from(vec![None::<i64>; n])),
+            Arc::new(Int64Array::from(vec![None::<i64>; n])), Arc::new(Int64Array::from(vec![None::<i64>; n])),
         ],
     )?;
     let reader = Box::new(RecordBatchIterator::new(vec![Ok(rb)].into_iter(), schema));
     conn.create_table(docs_table, reader).execute().await?;
 
-    localdb_vector::table::ensure_embeddings_table(&conn, emb_table).await?;
-    localdb_vector::table::ensure_cache_table(&conn, cache_table).await?;
+    localdb_vector::table::ensure_embeddings_table(&conn, emb_table, EMBEDDING_DIM).await?;
+    localdb_vector::table::ensure_cache_table(&conn, cache_table, EMBEDDING_DIM).await?;
     let provider = localdb_vector::embed_provider::local::LocalProvider::new()?;
-    let processed = localdb_vector::embed_backfill::backfill_embeddings(&conn, docs_table, emb_table, cache_table, &provider, 64, None).await?;
+    let processed = localdb_vector::embed_backfill::backfill_embeddings(&conn, docs_table, emb_table, cache_table, &provider, &Preprocessor::default(), 64, None).await?;
     assert_eq!(processed, chunks.len());
     let updated = localdb_vector::index_build::sync_serving_vectors_from_embeddings(&conn, docs_table, emb_table, provider.embedder_id()).await?;
     assert!(updated >= chunks.len());
@@ -195,5 +235,382 @@ async fn backfill_and_build_index_in_memory_slow() -> anyhow::Result<()> {
     localdb_vector::index_build::flip_active_index(&conn, docs_table, &index_name).await?;
     let active = localdb_vector::table::get_meta(&conn, "meta", &format!("active_index_id:{}", docs_table)).await?;
     assert_eq!(active.as_deref(), Some(index_name.as_str()));
+    let info = localdb_vector::index_build::index_info(&conn, docs_table).await?;
+    assert!(info.uses_ann());
+    assert_eq!(info.index_name.as_deref(), Some(index_name.as_str()));
+    assert_eq!((info.nlist, info.m), (Some(params.nlist), Some(params.m)));
+    assert!(info.built_at_ms.is_some());
+    assert_eq!((info.indexed_rows, info.brute_force_rows()), (chunks.len(), 0));
+    Ok(())
+}
+
+#[tokio::test]
+async fn collection_dim_is_recorded_and_enforced() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let conn = localdb_vector::table::open_db(&tmp.path().to_string_lossy()).await?;
+    assert_eq!(localdb_vector::table::collection_dim(&conn, "docs384").await?, None);
+
+    let dim = localdb_vector::table::ensure_collection_dim(&conn, "docs384", 384).await?;
+    assert_eq!(dim, 384);
+    assert_eq!(localdb_vector::table::collection_dim(&conn, "docs384").await?, Some(384));
+    assert!(localdb_vector::table::check_collection_dim(&conn, "docs384", 1024).await.is_err());
+
+    // A second collection in the same deployment can use a different dim
+    localdb_vector::table::ensure_collection_dim(&conn, "docs1024", 1024).await?;
+    localdb_vector::table::ensure_embeddings_table(&conn, "emb384", 384).await?;
+    assert_eq!(localdb_vector::table::table_vector_dim(&conn, "emb384").await?, Some(384));
+    Ok(())
+}
+
+#[test]
+fn arrow_utils_report_missing_and_mistyped_columns() -> anyhow::Result<()> {
+    use arrow_schema::{DataType, Field, Schema};
+    use localdb_core::error::Error as CoreError;
+    use localdb_vector::arrow_utils::{f32_column, string_column, vector_column, vector_value};
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("vector", DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2), true),
+    ]));
+    let vectors = vec![Some(vec![Some(1.0f32), Some(2.0)]), None];
+    let batch = RecordBatch::try_new(schema, vec![
+        Arc::new(Int32Array::from(vec![1, 2])),
+        Arc::new(FixedSizeListArray::from_iter_primitive::<arrow_array::types::Float32Type, _, _>(vectors.into_iter(), 2)),
+    ])?;
+
+    assert!(matches!(string_column(&batch, "id"), Err(CoreError::BadColumn { .. })));
+    assert!(matches!(string_column(&batch, "content"), Err(CoreError::BadColumn { .. })));
+    assert!(f32_column(&batch, "_distance")?.is_none());
+    let vecs = vector_column(&batch, "vector")?;
+    assert_eq!(vector_value(vecs, 0, "a")?, Some(vec![1.0, 2.0]));
+    assert_eq!(vector_value(vecs, 1, "b")?, None);
+    Ok(())
+}
+
+#[test]
+fn latency_budget_escalates_only_when_needed_and_affordable() {
+    use localdb_vector::LatencyBudget;
+    use std::time::Duration;
+
+    let b = LatencyBudget { budget: Duration::from_millis(100), nprobes: vec![8, 16, 64], min_score: 0.5 };
+    assert_eq!(b.initial_nprobes(), 8);
+    assert_eq!(b.confident([0.9, 0.4, 0.6]), 2);
+    // enough confident hits: stop
+    assert_eq!(b.next_nprobes(0, 5, 5, Duration::from_millis(5), Duration::from_millis(5)), None);
+    // too few hits and headroom: escalate one rung
+    assert_eq!(b.next_nprobes(0, 2, 5, Duration::from_millis(5), Duration::from_millis(5)), Some(16));
+    // next rung predicted at 4x the last attempt would blow the budget
+    assert_eq!(b.next_nprobes(1, 2, 5, Duration::from_millis(40), Duration::from_millis(20)), None);
+    // top of the ladder
+    assert_eq!(b.next_nprobes(2, 0, 5, Duration::ZERO, Duration::ZERO), None);
+}
+
+#[tokio::test]
+async fn positional_chunk_ids_migrate_to_content_ids() -> anyhow::Result<()> {
+    use localdb_core::data_processor::chunk_id;
+
+    let tmp = tempfile::tempdir()?;
+    let indexer = localdb_vector::LanceDbIndexer::new(tmp.path(), "documents").await?;
+    let contents = ["alpha", "beta", "alpha"];
+    let chunks: Vec<DocumentChunk> = contents.iter().enumerate().map(|(i, c)| DocumentChunk {
+        id: format!("doc:{}", i),
+        doc_id: "doc".to_string(),
+        doc_path: "/tmp/doc.txt".to_string(),
+        category: "/test".to_string(),
+        category_text: "/test".to_string(),
+        content: c.to_string(),
+        chunk_index: i,
+        total_chunks: contents.len(),
+        title: None,
+        author: None,
+        created_at: None,
+        lang: None,
+        meta: Default::default(),
+        span: None,
+    }).collect();
+    let empty: Vec<Vec<f32>> = vec![Vec::new(); chunks.len()];
+    indexer.index(&chunks, &empty).await?;
+
+    let conn = localdb_vector::table::open_db(&tmp.path().to_string_lossy()).await?;
+    let mapping = localdb_vector::migrate::chunk_id_mapping(&conn, "documents").await?;
+    assert_eq!(mapping.get("doc:0"), Some(&chunk_id("doc", "alpha", 0)));
+    assert_eq!(mapping.get("doc:1"), Some(&chunk_id("doc", "beta", 0)));
+    assert_eq!(mapping.get("doc:2"), Some(&chunk_id("doc", "alpha", 1)));
+
+    assert_eq!(localdb_vector::migrate::migrate_chunk_ids(&conn, "documents", "embeddings").await?, 3);
+    assert!(localdb_vector::migrate::chunk_id_mapping(&conn, "documents").await?.is_empty());
+    assert_eq!(localdb_vector::migrate::migrate_chunk_ids(&conn, "documents", "embeddings").await?, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn document_metadata_round_trips_through_the_documents_table() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let indexer = localdb_vector::LanceDbIndexer::new(tmp.path(), "documents").await?;
+    let chunk = |i: usize, title: Option<&str>| DocumentChunk {
+        id: format!("book:{}", i), doc_id: "book".to_string(), doc_path: "book.epub".to_string(), category: "/food".to_string(), category_text: "/food".to_string(),
+        content: format!("chapter {}", i), chunk_index: i, total_chunks: 2,
+        title: title.map(str::to_string), author: title.map(|_| "R. Hertzberg".to_string()), created_at: title.map(|_| 1_700_000_000_000),
+        lang: title.map(|_| "en".to_string()),
+        meta: title.map(|_| [("trust".to_string(), "high".to_string())].into_iter().collect()).unwrap_or_default(),
+        span: title.map(|_| SourceSpan { start_offset: 120, end_offset: 480, start_line: 4, end_line: 9 }),
+    };
+    indexer.index(&[chunk(0, Some("Putting Food By")), chunk(1, None)], &[Vec::new(), Vec::new()]).await?;
+
+    let conn = localdb_vector::table::open_db(&tmp.path().to_string_lossy()).await?;
+    let stored = localdb_vector::table::document_chunks(&conn, "documents", "book").await?;
+    assert_eq!(stored[0].title.as_deref(), Some("Putting Food By"));
+    assert_eq!(stored[0].author.as_deref(), Some("R. Hertzberg"));
+    assert_eq!(stored[0].created_at, Some(1_700_000_000_000));
+    assert_eq!(stored[0].meta.get("trust").map(String::as_str), Some("high"));
+    assert_eq!(stored[0].lang.as_deref(), Some("en"));
+    assert_eq!(stored[0].span.map(|s| (s.start_offset, s.end_offset, s.start_line, s.end_line)), Some((120, 480, 4, 9)));
+    assert_eq!((stored[1].title.as_deref(), stored[1].created_at, stored[1].meta.is_empty(), stored[1].lang.as_deref(), stored[1].span), (None, None, true, None, None));
+    Ok(())
+}
+
+#[tokio::test]
+async fn scrub_detects_changed_and_missing_files() -> anyhow::Result<()> {
+    use localdb_core::data_processor::DataProcessor;
+    use localdb_core::roots::{DataRoot, RootMap};
+    use localdb_vector::catalog::{self, ScrubStatus, CATALOG_TABLE};
+
+    let data = tempfile::tempdir()?;
+    std::fs::write(data.path().join("a.txt"), "water")?;
+    std::fs::write(data.path().join("b.txt"), "fire")?;
+    std::fs::write(data.path().join("c.txt"), "shelter")?;
+    let roots = vec![DataRoot::single(data.path())];
+    let (_, records) = DataProcessor::new().process_roots_cataloged(&roots)?;
+    assert_eq!(records.len(), 3);
+
+    let db = tempfile::tempdir()?;
+    let conn = localdb_vector::table::open_db(&db.path().to_string_lossy()).await?;
+    catalog::put_records(&conn, CATALOG_TABLE, &records).await?;
+    catalog::put_records(&conn, CATALOG_TABLE, &records).await?; // re-ingest upserts
+    assert_eq!(catalog::records(&conn, CATALOG_TABLE).await?.len(), 3);
+
+    std::fs::write(data.path().join("b.txt"), "fjre")?; // one flipped byte
+    std::fs::remove_file(data.path().join("c.txt"))?;
+    let mut results = catalog::scrub(&conn, CATALOG_TABLE, &RootMap::for_roots(&roots)).await?;
+    results.sort_by(|a, b| a.0.doc_path.cmp(&b.0.doc_path));
+    assert_eq!(results[0].1, ScrubStatus::Ok);
+    assert!(matches!(results[1].1, ScrubStatus::Mismatch { .. }));
+    assert_eq!(results[2].1, ScrubStatus::Missing);
+
+    // Retention: expired records are found and removed from the catalog.
+    use localdb_core::retention::{RetentionPolicy, RetentionRule};
+    let policy = RetentionPolicy { rules: vec![RetentionRule { facet: "/".into(), max_age_days: Some(1) }] };
+    let later = std::time::SystemTime::now() + std::time::Duration::from_secs(3 * 86_400);
+    let records = catalog::records(&conn, CATALOG_TABLE).await?;
+    let expired = catalog::expired(&records, &policy, later);
+    assert_eq!(expired.len(), 3);
+    assert!(catalog::expired(&records, &policy, std::time::SystemTime::now()).is_empty());
+    catalog::delete_records(&conn, CATALOG_TABLE, &[expired[0].doc_path.clone()]).await?;
+    assert_eq!(catalog::records(&conn, CATALOG_TABLE).await?.len(), 2);
+    Ok(())
+}
+
+/// Embeds text as keyword counts, one dimension per canary topic.
+struct KeywordEmbedder(usize);
+
+impl localdb_core::traits::Embedder for KeywordEmbedder {
+    fn dim(&self) -> usize { self.0 }
+    fn max_len(&self) -> usize { 512 }
+    fn embed_batch(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        const TOPICS: &[&[&str]] = &[&["water"], &["fire"], &["tomato", "jar"], &["solar", "batter"]];
+        Ok(texts.iter().map(|t| {
+            let t = t.to_lowercase();
+            (0..self.0).map(|i| TOPICS.get(i).map_or(0.0, |words| words.iter().map(|w| t.matches(w).count()).sum::<usize>() as f32)).collect()
+        }).collect())
+    }
+}
+
+#[tokio::test]
+async fn startup_self_test_flags_a_model_that_does_not_fit_the_index() -> anyhow::Result<()> {
+    use localdb_core::canary::Problem;
+    use localdb_core::traits::Embedder;
+    use localdb_vector::canary::{self_test, write_canary};
+    let tmp = tempfile::tempdir()?;
+    let conn = localdb_vector::table::open_db(&tmp.path().to_string_lossy()).await?;
+    let model = KeywordEmbedder(4);
+    assert_eq!(self_test(&conn, "documents", &model).await?, None);
+
+    write_canary(tmp.path(), &model).await?;
+    assert_eq!(self_test(&conn, "documents", &model).await?, Some(vec![]));
+
+    // Corrupted serving vectors.
+    let chunks = localdb_core::canary::chunks();
+    let mut vectors = model.embed_batch(&chunks.iter().map(|c| c.content.clone()).collect::<Vec<_>>())?;
+    vectors[1] = vec![f32::NAN; 4];
+    vectors[2] = vec![0.0; 4];
+    localdb_vector::LanceDbIndexer::new(tmp.path(), "documents").await?.index(&chunks, &vectors).await?;
+    let problems = self_test(&conn, "documents", &model).await?.unwrap();
+    assert_eq!(problems, vec![Problem::BadVectors { collection: "documents".into(), count: 2, checked: 4 }]);
+
+    // Another model: wrong width everywhere, and the canary is not searched.
+    let problems = self_test(&conn, "documents", &KeywordEmbedder(8)).await?.unwrap();
+    assert!(problems.contains(&Problem::DimMismatch { collection: "_canary".into(), stored: 4, model: 8 }));
+    assert!(problems.contains(&Problem::DimMismatch { collection: "documents".into(), stored: 4, model: 8 }));
+    assert!(!problems.iter().any(|p| matches!(p, Problem::Missed { .. })));
+
+    // Re-ingesting with the new model replaces the canary.
+    write_canary(tmp.path(), &KeywordEmbedder(8)).await?;
+    assert_eq!(localdb_vector::table::collection_dim(&conn, "_canary").await?, Some(8));
+    Ok(())
+}
+
+/// `LocalProvider` under another `embedder_id`, as after swapping the weights.
+struct Swapped(localdb_vector::embed_provider::local::LocalProvider);
+
+impl EmbedProvider for Swapped {
+    fn embedder_id(&self) -> &str { "local:swapped:d1024" }
+    fn dim(&self) -> usize { self.0.dim() }
+    fn max_len(&self) -> usize { self.0.max_len() }
+    fn embed_batch(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> { self.0.embed_batch(texts) }
+}
+
+#[tokio::test]
+async fn a_changed_embedder_id_re_embeds_the_collection() -> anyhow::Result<()> {
+    use localdb_vector::embed_backfill::backfill_embeddings;
+    std::env::set_var("APP_USE_FAKE_EMBEDDINGS", "1");
+    let tmp = tempfile::tempdir()?;
+    let chunks: Vec<DocumentChunk> = (0..6).map(|i| DocumentChunk {
+        id: format!("c{}", i), doc_id: "d".into(), doc_path: "d.txt".into(), category: "/t".into(), category_text: "/t".into(),
+        content: format!("chunk {} text", i), chunk_index: i, total_chunks: 6, ..Default::default()
+    }).collect();
+    localdb_vector::LanceDbIndexer::new(tmp.path(), "documents").await?.index(&chunks, &vec![Vec::new(); chunks.len()]).await?;
+    let conn = localdb_vector::table::open_db(&tmp.path().to_string_lossy()).await?;
+    let provider = localdb_vector::embed_provider::local::LocalProvider::new()?;
+
+    assert_eq!(backfill_embeddings(&conn, "documents", "embeddings", "emb_cache", &provider, &Preprocessor::default(), 4, None).await?, 6);
+    assert_eq!(backfill_embeddings(&conn, "documents", "embeddings", "emb_cache", &provider, &Preprocessor::default(), 4, None).await?, 0);
+    assert_eq!(localdb_vector::table::collection_embedder(&conn, "documents").await?.as_deref(), Some(provider.embedder_id()));
+
+    let swapped = Swapped(provider);
+    assert_eq!(backfill_embeddings(&conn, "documents", "embeddings", "emb_cache", &swapped, &Preprocessor::default(), 4, None).await?, 6, "every chunk is re-embedded");
+    assert_eq!(localdb_vector::table::collection_embedder(&conn, "documents").await?.as_deref(), Some("local:swapped:d1024"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn calibration_is_dropped_when_the_collection_changes() -> anyhow::Result<()> {
+    use localdb_core::calibration::{Isotonic, ScoreCalibration};
+    use localdb_vector::table::{score_calibration, set_collection_embedder, set_score_calibration};
+    let tmp = tempfile::tempdir()?;
+    let chunk = |id: &str| DocumentChunk { id: id.into(), doc_id: id.into(), doc_path: format!("{}.txt", id), category: "/t".into(), category_text: "/t".into(), content: id.into(), total_chunks: 1, ..Default::default() };
+    let indexer = localdb_vector::LanceDbIndexer::new(tmp.path(), "documents").await?;
+    indexer.index(&[chunk("a")], &[vec![1.0, 0.0]]).await?;
+    let conn = localdb_vector::table::open_db(&tmp.path().to_string_lossy()).await?;
+    set_collection_embedder(&conn, "documents", "e1").await?;
+    let fitted = ScoreCalibration { text: Some(Isotonic { xs: vec![0.0, 10.0], ys: vec![0.1, 0.9] }), vector: None };
+    set_score_calibration(&conn, "documents", &fitted).await?;
+    assert_eq!(score_calibration(&conn, "documents").await?, fitted);
+
+    set_collection_embedder(&conn, "documents", "e2").await?;
+    assert!(score_calibration(&conn, "documents").await?.is_empty(), "another embedder");
+    set_score_calibration(&conn, "documents", &fitted).await?;
+    indexer.index(&[chunk("b")], &[vec![0.0, 1.0]]).await?;
+    assert!(score_calibration(&conn, "documents").await?.is_empty(), "re-ingested");
+    Ok(())
+}
+
+#[test]
+fn recorded_embedder_ids_load_their_embedder() -> anyhow::Result<()> {
+    use localdb_vector::embed_provider::local::{embedder_id, recorded_embedder, LocalProvider};
+    std::env::set_var("APP_USE_FAKE_EMBEDDINGS", "1");
+    let provider = LocalProvider::new()?;
+    let texts = vec!["rain barrel".to_string()];
+    let recorded = recorded_embedder(provider.embedder_id())?;
+    assert_eq!(recorded.embed_batch(&texts)?, provider.embed_batch(&texts)?);
+    let narrow = recorded_embedder(&embedder_id(256)?)?;
+    assert_eq!(narrow.dim(), 256, "a truncated generation is queried truncated");
+    assert!(recorded_embedder("remote:other:d8").is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn backfill_embeds_and_caches_the_preprocessed_text() -> anyhow::Result<()> {
+    use localdb_core::preprocess::Step;
+    use localdb_vector::cache::{get_many, hash_content};
+    std::env::set_var("APP_USE_FAKE_EMBEDDINGS", "1");
+    let tmp = tempfile::tempdir()?;
+    let chunks = vec![DocumentChunk {
+        id: "c0".into(), doc_id: "d".into(), doc_path: "d.txt".into(), category: "/t".into(), category_text: "/t".into(),
+        content: "Bread RISES".into(), total_chunks: 1, ..Default::default()
+    }];
+    localdb_vector::LanceDbIndexer::new(tmp.path(), "documents").await?.index(&chunks, &[Vec::new()]).await?;
+    let conn = localdb_vector::table::open_db(&tmp.path().to_string_lossy()).await?;
+    let provider = localdb_vector::embed_provider::local::LocalProvider::new()?;
+    let lowercase = Preprocessor::new(vec![Step::Lowercase]);
+    assert_eq!(localdb_vector::embed_backfill::backfill_embeddings(&conn, "documents", "embeddings", "emb_cache", &provider, &lowercase, 4, None).await?, 1);
+
+    let keys = [hash_content("bread rises"), hash_content("Bread RISES")];
+    let cached = get_many(&conn, "emb_cache", provider.embedder_id(), &keys).await?;
+    assert_eq!(cached.get(&keys[0]), provider.embed_batch(&["bread rises".to_string()])?.first(), "the cleaned text is embedded and keyed");
+    assert!(!cached.contains_key(&keys[1]));
+    Ok(())
+}
+
+/// Embeds every text as `[9, 9]`, counting the texts it is given.
+struct Counting(std::sync::atomic::AtomicUsize);
+
+impl localdb_core::traits::Embedder for Counting {
+    fn dim(&self) -> usize { 2 }
+    fn max_len(&self) -> usize { 8 }
+    fn embed_batch(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        self.0.fetch_add(texts.len(), std::sync::atomic::Ordering::SeqCst);
+        Ok(texts.iter().map(|_| vec![9.0, 9.0]).collect())
+    }
+}
+
+#[tokio::test]
+async fn synced_files_ship_the_vectors_of_their_chunks() -> anyhow::Result<()> {
+    use localdb_core::traits::Embedder;
+    use localdb_vector::cache::{cached_embedder, file_entries, put_missing, CacheEntry};
+    let tmp = tempfile::tempdir()?;
+    let chunk = |id: &str, doc_path: &str, content: &str| DocumentChunk {
+        id: id.into(), doc_id: id.into(), doc_path: doc_path.into(), category: "/t".into(), category_text: "/t".into(), content: content.into(), total_chunks: 1, ..Default::default()
+    };
+    let chunks = vec![chunk("a", "wiki.zim#A/Bread", "bread"), chunk("b", "wiki.zim", "front page"), chunk("c", "wiki.zim.bak", "old copy"), chunk("d", "notes.txt", "notes")];
+    let vectors: Vec<Vec<f32>> = (0..chunks.len()).map(|i| vec![i as f32, 1.0]).collect();
+    localdb_vector::LanceDbIndexer::new(tmp.path(), "documents").await?.index(&chunks, &vectors).await?;
+    let conn = localdb_vector::table::open_db(&tmp.path().to_string_lossy()).await?;
+
+    let entries = file_entries(&conn, "documents", "e1", &["wiki.zim".to_string()]).await?;
+    assert_eq!(entries.len(), 2, "the file and its fragments, not a file that only shares the prefix");
+    let shipped: Vec<CacheEntry> = entries.iter().filter_map(|e| CacheEntry::decode(&e.encode())).collect();
+    assert_eq!(shipped.iter().map(|e| (&e.content_hash, &e.vector)).collect::<Vec<_>>(), entries.iter().map(|e| (&e.content_hash, &e.vector)).collect::<Vec<_>>());
+    assert_eq!(put_missing(&conn, "emb_cache", shipped.clone()).await?, 2);
+    assert_eq!(put_missing(&conn, "emb_cache", shipped).await?, 0, "already cached");
+
+    let inner = Arc::new(Counting(Default::default()));
+    let cached = cached_embedder(&conn, "emb_cache", "e1", inner.clone(), &chunks).await?.expect("shipped vectors");
+    let out = cached.embed_batch(&["bread".to_string(), "notes".to_string(), "front page".to_string()])?;
+    assert_eq!(out, [vec![0.0, 1.0], vec![9.0, 9.0], vec![1.0, 1.0]]);
+    assert_eq!(inner.0.load(std::sync::atomic::Ordering::SeqCst), 1, "only the text without a shipped vector is embedded");
+    assert!(cached_embedder(&conn, "emb_cache", "another-model", inner, &chunks).await?.is_none());
+    Ok(())
+}
+
+#[test]
+fn fragments_of_cataloged_files_are_not_uncataloged() {
+    use localdb_vector::gc::uncataloged;
+    let cataloged: std::collections::HashSet<String> = ["wiki.zim", "notes/#1 jobs.txt", "tools.tar"].iter().map(|s| s.to_string()).collect();
+    let stored: Vec<String> = ["wiki.zim#A/Bread", "wiki.zim", "notes/#1 jobs.txt", "notes/#1 jobs.txt#2", "tools.tar#saw.md", "gone.zim#A/Bread", "gone.txt", "gone.txt"]
+        .iter().map(|s| s.to_string()).collect();
+    assert_eq!(uncataloged(&stored, &cataloged), ["gone.txt", "gone.zim#A/Bread"]);
+    assert!(uncataloged(&stored, &std::collections::HashSet::new()).contains(&"wiki.zim#A/Bread".to_string()));
+}
+
+#[tokio::test]
+async fn deleting_a_file_removes_its_fragments() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let chunk = |id: &str, doc_path: &str| DocumentChunk { id: id.into(), doc_id: id.into(), doc_path: doc_path.into(), content: id.into(), total_chunks: 1, ..Default::default() };
+    let chunks = vec![chunk("a", "wiki.zim#A/Bread"), chunk("b", "wiki.zim"), chunk("c", "wiki.zim.bak"), chunk("d", "notes.txt")];
+    localdb_vector::LanceDbIndexer::new(tmp.path(), "documents").await?.index(&chunks, &vec![vec![1.0, 0.0]; chunks.len()]).await?;
+    let conn = localdb_vector::table::open_db(&tmp.path().to_string_lossy()).await?;
+    let mut removed = localdb_vector::table::delete_documents(&conn, "documents", "embeddings", &["wiki.zim".to_string()]).await?;
+    removed.sort();
+    assert_eq!(removed, ["a", "b"]);
     Ok(())
 }
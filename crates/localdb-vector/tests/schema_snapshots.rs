@@ -0,0 +1,45 @@
+//! Snapshots of the Lance table schemas. Existing collections keep the
+//! schema they were written with, so a changed column name, type or
+//! nullability must show up here as an explicit snapshot update (`cargo
+//! insta review`) together with a migration (see `migrate`).
+
+use arrow_schema::{DataType, Schema, TimeUnit};
+use localdb_vector::schema::{build_arrow_schema, build_cache_schema, build_catalog_schema, build_embeddings_schema, build_token_vectors_schema, EMBEDDING_DIM};
+
+/// One `name: type` line per column, `?` marking nullable ones.
+fn render(schema: &Schema) -> String {
+    schema.fields().iter().map(|f| format!("{}: {}{}\n", f.name(), type_name(f.data_type()), if f.is_nullable() { "?" } else { "" })).collect()
+}
+
+fn type_name(t: &DataType) -> String {
+    match t {
+        DataType::Timestamp(TimeUnit::Millisecond, None) => "timestamp(ms)".to_string(),
+        DataType::FixedSizeList(item, dim) => format!("[{}{}; {}]", type_name(item.data_type()), if item.is_nullable() { "?" } else { "" }, dim),
+        other => format!("{:?}", other),
+    }
+}
+
+#[test]
+fn documents_schema() {
+    insta::assert_snapshot!("documents", render(&build_arrow_schema(EMBEDDING_DIM)));
+}
+
+#[test]
+fn embeddings_schema() {
+    insta::assert_snapshot!("embeddings", render(&build_embeddings_schema(EMBEDDING_DIM)));
+}
+
+#[test]
+fn cache_schema() {
+    insta::assert_snapshot!("emb_cache", render(&build_cache_schema(EMBEDDING_DIM)));
+}
+
+#[test]
+fn catalog_schema() {
+    insta::assert_snapshot!("catalog", render(&build_catalog_schema()));
+}
+
+#[test]
+fn token_vectors_schema() {
+    insta::assert_snapshot!("tokens", render(&build_token_vectors_schema(EMBEDDING_DIM)));
+}
@@ -0,0 +1,16 @@
+#![no_main]
+//! Feed arbitrary (possibly non-UTF-8) bytes through the paragraph chunker.
+
+use libfuzzer_sys::fuzz_target;
+use localdb_core::data_processor::DataProcessor;
+use std::path::Path;
+
+fuzz_target!(|data: &[u8]| {
+    let content = String::from_utf8_lossy(data);
+    let processor = DataProcessor::new();
+    let chunks = processor.chunk_text(&content, "fuzz", Path::new("fuzz.txt"), "/fuzz").expect("chunking never errors on text");
+    for c in &chunks {
+        assert!(c.chunk_index < c.total_chunks);
+        assert!(!c.content.trim().is_empty());
+    }
+});
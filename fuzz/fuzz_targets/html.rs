@@ -0,0 +1,15 @@
+#![no_main]
+//! Arbitrary (X)HTML through the EPUB/ZIM text extractor, and arbitrary bytes
+//! through the EPUB reader.
+
+use libfuzzer_sys::fuzz_target;
+use localdb_core::epub::{read_chapters, strip_html};
+
+fuzz_target!(|data: &[u8]| {
+    let html = String::from_utf8_lossy(data);
+    let chapter = strip_html(&html);
+    let paragraphs = chapter.text.split("\n\n").count();
+    assert!(chapter.images.iter().all(|i| i.paragraph <= paragraphs));
+    // Not a valid EPUB most of the time: an error, never a panic.
+    let _ = read_chapters(data);
+});
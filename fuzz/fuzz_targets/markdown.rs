@@ -0,0 +1,12 @@
+#![no_main]
+//! Arbitrary text through the embedding preprocessor's Markdown and
+//! boilerplate cleanup.
+
+use libfuzzer_sys::fuzz_target;
+use localdb_core::preprocess::{Preprocessor, Step};
+
+fuzz_target!(|text: &str| {
+    Preprocessor::new(vec![Step::StripMarkdown]).clean(text);
+    let all = Preprocessor::new(vec![Step::StripMarkdown, Step::StripBoilerplate, Step::CollapseWhitespace]).clean(text);
+    assert!(!all.contains("  ") && !all.contains('\n'));
+});
@@ -0,0 +1,18 @@
+#![no_main]
+//! Arbitrary `pdftotext` output: form-feed page splitting, then chunking of
+//! every page.
+
+use libfuzzer_sys::fuzz_target;
+use localdb_core::data_processor::{text_pages, DataProcessor};
+use std::path::Path;
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    let pages = text_pages(&text);
+    assert!(!pages.is_empty());
+    let processor = DataProcessor::new();
+    for page in &pages {
+        assert!(!page.contains('\x0c'));
+        processor.chunk_text(page, "fuzz", Path::new("fuzz.pdf"), "/fuzz").expect("chunking never errors on text");
+    }
+});
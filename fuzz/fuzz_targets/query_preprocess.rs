@@ -0,0 +1,14 @@
+#![no_main]
+//! Arbitrary query strings must normalize without panicking and stay parseable.
+
+use libfuzzer_sys::fuzz_target;
+use localdb_text::query::{preprocess_query, MAX_QUERY_CHARS};
+
+fuzz_target!(|raw: &str| {
+    let q = preprocess_query(raw);
+    assert!(q.chars().count() <= MAX_QUERY_CHARS);
+    assert_eq!(q.matches('"').count() % 2, 0);
+    assert!(!q.chars().any(char::is_control));
+    // Idempotent: pre-processing twice changes nothing
+    assert_eq!(preprocess_query(&q), q);
+});